@@ -4,17 +4,25 @@ document.addEventListener('DOMContentLoaded', () => {
   const loginScreen = document.getElementById('login-screen');
   const mainScreen = document.getElementById('main-screen');
   const loginForm = document.getElementById('login-form');
+  const usernameInput = document.getElementById('username-input');
   const passwordInput = document.getElementById('password-input');
+  const deviceNameInput = document.getElementById('device-name-input');
   const loginError = document.getElementById('login-error');
+  const ssoLogin = document.getElementById('sso-login');
 
   let filerInitialized = false;
+  // DEN_DISABLE_FILER: サーバーが filer API をマウントしていない
+  let filerDisabled = false;
 
   // ログイン処理
   loginForm.addEventListener('submit', async (e) => {
     e.preventDefault();
     loginError.hidden = true;
+    const deviceName = deviceNameInput.value.trim();
     try {
-      await Auth.login(passwordInput.value);
+      await Auth.login(passwordInput.value, usernameInput.value.trim(), deviceName);
+      try { localStorage.setItem('den_device_name', deviceName); }
+      catch { /* ignore */ }
       showMain();
     } catch {
       loginError.hidden = false;
@@ -23,11 +31,25 @@ document.addEventListener('DOMContentLoaded', () => {
     }
   });
 
+  // 端末名は次回ログインでも同じものを使う
+  try { deviceNameInput.value = localStorage.getItem('den_device_name') || ''; }
+  catch { /* ignore */ }
+
+  Auth.ssoEnabled().then((enabled) => {
+    ssoLogin.hidden = !enabled;
+  });
+
   // 既にトークンがあればサーバーに有効性を確認してからメイン画面へ
+  // 未ログインでも mTLS のクライアント証明書があればそれでログインする
   if (Auth.isLoggedIn()) {
     validateAndShow();
   } else {
     passwordInput.focus();
+    if (location.protocol === 'https:') {
+      Auth.certLogin().then((ok) => {
+        if (ok) showMain();
+      });
+    }
   }
 
   async function validateAndShow() {
@@ -220,6 +242,13 @@ document.addEventListener('DOMContentLoaded', () => {
     DenSettings.apply();
     DenSettings.bindUI();
 
+    // 無効化されたサブシステム（DEN_DISABLE_*）の UI を隠す
+    const features = await fetch('/api/system/features', { credentials: 'same-origin' })
+      .then((r) => (r.ok ? r.json() : {}))
+      .catch(() => ({}));
+    filerDisabled = features.filer === false;
+    document.querySelector('.tab[data-tab="filer"]').hidden = filerDisabled;
+
     // Den Connect モーダル早期初期化（Terminal タブからも利用するため）
     DenFiler.initDenConnectModal();
 
@@ -334,6 +363,7 @@ document.addEventListener('DOMContentLoaded', () => {
   }
 
   function switchTab(tabName) {
+    if (tabName === 'filer' && filerDisabled) return;
     // タブボタン更新
     document.querySelectorAll('.tab').forEach((t) => {
       t.classList.remove('active');
@@ -2,23 +2,62 @@
 // トークンは HttpOnly Cookie で管理（XSS でのトークン窃取を防止）
 const Auth = (() => {
   const LOGGED_IN_COOKIE = 'den_logged_in';
+  const CSRF_HEADER = 'X-Den-Request';
+
+  // CSRF 対策: Cookie 認証の変更系リクエストにはサーバーがカスタムヘッダーを要求する。
+  // 同一オリジンへの GET/HEAD 以外の fetch に一括で付与する。
+  const nativeFetch = window.fetch.bind(window);
+  window.fetch = (input, init) => {
+    const req = input instanceof Request ? input : null;
+    const method = ((init && init.method) || (req ? req.method : 'GET')).toUpperCase();
+    const url = new URL(req ? req.url : String(input), location.href);
+    if (method === 'GET' || method === 'HEAD' || url.origin !== location.origin) {
+      return nativeFetch(input, init);
+    }
+    const headers = new Headers((init && init.headers) || (req ? req.headers : undefined));
+    headers.set(CSRF_HEADER, '1');
+    return nativeFetch(input, { ...init, headers });
+  };
 
   /** JS 可読な den_logged_in フラグ Cookie の存在を確認 */
   function isLoggedIn() {
     return document.cookie.split(';').some(c => c.trim().startsWith(LOGGED_IN_COOKIE + '='));
   }
 
-  async function login(password) {
+  /** username 省略時はオーナー（DEN_PASSWORD）としてログイン。deviceName は端末一覧の表示名 */
+  async function login(password, username, deviceName) {
+    const body = username ? { username, password } : { password };
+    if (deviceName) body.device_name = deviceName;
     const res = await fetch('/api/login', {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       credentials: 'same-origin',
-      body: JSON.stringify({ password }),
+      body: JSON.stringify(body),
     });
     if (!res.ok) throw new Error('Unauthorized');
     // トークンは HttpOnly Cookie としてサーバーが Set-Cookie で設定済み
   }
 
+  /** mTLS のクライアント証明書でログイン（DEN_TLS_CLIENT_CA 未設定や証明書なしなら false） */
+  async function certLogin() {
+    try {
+      const res = await fetch('/api/auth/cert', { method: 'POST', credentials: 'same-origin' });
+      return res.ok;
+    } catch (_) {
+      return false;
+    }
+  }
+
+  /** OIDC シングルサインオンが設定されているか */
+  async function ssoEnabled() {
+    try {
+      const res = await fetch('/api/auth/oidc', { credentials: 'same-origin' });
+      return res.ok && (await res.json()).enabled === true;
+    } catch (_) {
+      return false;
+    }
+  }
+
   /** サーバー側で HttpOnly Cookie を無効化し、フラグ Cookie も削除 */
   async function logout() {
     try {
@@ -32,5 +71,5 @@ const Auth = (() => {
     document.cookie = LOGGED_IN_COOKIE + '=; Path=/; Max-Age=0';
   }
 
-  return { login, logout, isLoggedIn, clearToken };
+  return { login, logout, isLoggedIn, clearToken, ssoEnabled, certLogin };
 })();
@@ -123,6 +123,8 @@ const FilerEditor = (() => {
       view,
       themeCompartment,
       content: data.content,
+      // 保存時の競合検出用（ターミナル等での変更を上書きしない）
+      hash: data.hash || null,
       dirty: false,
     });
 
@@ -466,17 +468,26 @@ const FilerEditor = (() => {
     if (!file) return;
 
     const content = file.view.state.doc.toString();
+    const put = (ifMatch) => fetch(`${FilerRemote.getApiBase()}/write`, {
+      method: 'PUT',
+      credentials: 'same-origin',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ path, content, if_match: ifMatch || undefined }),
+    });
 
     try {
       await Spinner.wrap(editorContainer, async () => {
-        const resp = await fetch(`${FilerRemote.getApiBase()}/write`, {
-          method: 'PUT',
-          credentials: 'same-origin',
-          headers: { 'Content-Type': 'application/json' },
-          body: JSON.stringify({ path, content }),
-        });
+        let resp = await put(file.hash);
+        if (resp.status === 409) {
+          const err = await resp.json().catch(() => ({}));
+          const msg = `${err.error || 'File changed on disk'}. Overwrite anyway?`;
+          if (!(await Toast.confirm(msg))) return;
+          resp = await put(null);
+        }
 
         if (resp.ok) {
+          const saved = await resp.json().catch(() => ({}));
+          file.hash = saved.hash || null;
           file.content = content;
           file.dirty = false;
           renderTabs();
@@ -139,10 +139,14 @@ const FilerRemote = (() => {
   }
 
   /** SFTP connect */
-  async function connect(host, port, username, authType, password, keyPath) {
+  async function connect(host, port, username, authType, password, keyPath, savePassword) {
     if (mode === 'den') await disconnectAllDenSilent();
     const body = { host, port: port || 22, username, auth_type: authType };
-    if (authType === 'password') body.password = password;
+    if (authType === 'password') {
+      // 空ならサーバーに保存済みのパスワードを使う
+      if (password) body.password = password;
+      body.save_password = !!savePassword;
+    }
     if (authType === 'key') body.key_path = keyPath;
 
     const resp = await doConnectFetch(body);
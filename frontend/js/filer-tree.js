@@ -45,7 +45,7 @@ const FilerTree = (() => {
     if (isRoot) Spinner.show(treeEl);
     try {
       const showHidden = isShowHiddenEnabled();
-      const data = await apiFetch(`${FilerRemote.getApiBase()}/list?path=${enc(dirPath)}&show_hidden=${showHidden}`);
+      const data = await apiFetch(`${FilerRemote.getApiBase()}/list?path=${enc(dirPath)}&show_hidden=${showHidden}&git=true`);
       if (!data) return;
       if (isRoot) {
         treeEl.innerHTML = '';
@@ -105,7 +105,7 @@ const FilerTree = (() => {
 
       // 名前
       const name = document.createElement('span');
-      name.className = `tree-name${entry.is_dir ? ' dir' : ''}`;
+      name.className = `tree-name${entry.is_dir ? ' dir' : ''}${entry.git ? ` git-${entry.git}` : ''}`;
       name.textContent = entry.name;
       name.title = entry.name;
       row.appendChild(name);
@@ -484,6 +484,7 @@ const DenFiler = (() => {
     document.getElementById('sftp-username').value = '';
     document.getElementById('sftp-auth-type').value = 'password';
     document.getElementById('sftp-password').value = '';
+    document.getElementById('sftp-save-password').checked = false;
     document.getElementById('sftp-key-path').value = '';
     updateAuthFields();
     renderBookmarkSelect(null);
@@ -674,6 +675,7 @@ const DenFiler = (() => {
     const username = document.getElementById('sftp-username').value.trim();
     const authType = document.getElementById('sftp-auth-type').value;
     const password = document.getElementById('sftp-password').value;
+    const savePassword = document.getElementById('sftp-save-password').checked;
     const keyPath = document.getElementById('sftp-key-path').value.trim();
 
     if (!host || !username) {
@@ -684,7 +686,7 @@ const DenFiler = (() => {
     const submitBtn = document.getElementById('sftp-connect-submit');
     await Spinner.button(submitBtn, async () => {
       try {
-        await FilerRemote.connect(host, port, username, authType, password, keyPath);
+        await FilerRemote.connect(host, port, username, authType, password, keyPath, savePassword);
         document.getElementById('sftp-connect-modal').hidden = true;
         Toast.success(`Connected to ${username}@${host}`);
       } catch (e) {
@@ -932,6 +934,12 @@ const DenFiler = (() => {
     if (!isDir) {
       items.push({ label: 'Open', action: () => FilerEditor.openFile(path) });
       items.push({ label: 'Download', action: () => downloadFile(path) });
+      if (!FilerRemote.isRemote()) {
+        items.push({ label: 'Copy Share Link', action: () => copyShareLink(path) });
+      }
+      if (!FilerRemote.getApiBase().startsWith('/api/remote/')) {
+        items.push({ label: 'Copy curl Command', action: () => copyCurlCommand(path) });
+      }
       items.push({ separator: true });
     }
 
@@ -992,7 +1000,7 @@ const DenFiler = (() => {
     const name = await Toast.prompt('New file name:');
     if (!name) return;
     const fullPath = joinPath(dir, name);
-    const ok = await apiCall(`${FilerRemote.getApiBase()}/write`, 'PUT', { path: fullPath, content: '' });
+    const ok = await apiCall(`${FilerRemote.getApiBase()}/create`, 'POST', { path: fullPath });
     if (ok) {
       Toast.success('File created');
       FilerTree.refresh();
@@ -1061,6 +1069,50 @@ const DenFiler = (() => {
     }
   }
 
+  /** One-time download link (1 download, 60 min) that works without a den login */
+  async function copyShareLink(path) {
+    try {
+      const resp = await fetch('/api/filer/share', {
+        method: 'POST',
+        credentials: 'same-origin',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ path }),
+      });
+      const data = await resp.json().catch(() => null);
+      if (!resp.ok || !data) {
+        Toast.error((data && data.error) || 'Failed to create link');
+        return;
+      }
+      await DenClipboard.write(location.origin + data.url);
+      Toast.success('Share link copied (1 download, valid 60 min)');
+    } catch {
+      Toast.error('Failed to create link');
+    }
+  }
+
+  /** curl one-liner with a signed download URL (reusable for 60 min, no cookie needed) */
+  async function copyCurlCommand(path) {
+    const quote = (s) => `'${s.replace(/'/g, "'\\''")}'`;
+    try {
+      const resp = await fetch(`${FilerRemote.getApiBase()}/sign-url`, {
+        method: 'POST',
+        credentials: 'same-origin',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ path }),
+      });
+      const data = await resp.json().catch(() => null);
+      if (!resp.ok || !data) {
+        Toast.error((data && data.error) || 'Failed to sign URL');
+        return;
+      }
+      const name = path.split(/[/\\]/).pop() || 'download';
+      await DenClipboard.write(`curl -fo ${quote(name)} ${quote(location.origin + data.url)}`);
+      Toast.success('curl command copied (valid 60 min)');
+    } catch {
+      Toast.error('Failed to sign URL');
+    }
+  }
+
   // --- アップロード ---
 
   function showUploadModal() {
@@ -248,6 +248,102 @@ const DenSettings = (() => {
     }
   }
 
+  function formatUnixTime(secs) {
+    return secs ? new Date(secs * 1000).toLocaleString() : 'unknown';
+  }
+
+  async function loadDevices() {
+    const list = document.getElementById('device-list');
+    if (!list) return;
+    list.innerHTML = '<div class="tls-trust-empty">Loading...</div>';
+    let devices;
+    try {
+      const res = await fetch('/api/devices', { credentials: 'same-origin' });
+      if (!res.ok) throw new Error(`HTTP ${res.status}`);
+      devices = await res.json();
+    } catch (e) {
+      list.innerHTML = '<div class="tls-trust-empty">Failed to load devices.</div>';
+      console.warn('Failed to load devices:', e);
+      return;
+    }
+    if (devices.length === 0) {
+      list.innerHTML = '<div class="tls-trust-empty">No devices registered.</div>';
+      return;
+    }
+
+    list.innerHTML = devices.map((d) => `
+      <div class="device-item">
+        <div class="device-name">
+          <span class="tls-trust-display-name" data-id="${escHtml(d.id)}" title="Click to rename">${escHtml(d.name)}</span>
+          ${d.current ? '<span class="device-current">This device</span>' : ''}
+        </div>
+        <div class="tls-trust-meta">
+          <span class="tls-trust-timestamp" title="${escHtml(d.user_agent)}">Last used: ${escHtml(formatUnixTime(d.last_seen))}${d.last_ip ? ' from ' + escHtml(d.last_ip) : ''}</span>
+          <span class="tls-trust-timestamp">Signed in: ${escHtml(formatUnixTime(d.created_at))}</span>
+        </div>
+        <button class="modal-btn tls-trust-delete" type="button" data-id="${escHtml(d.id)}">Revoke</button>
+      </div>
+    `).join('');
+
+    list.querySelectorAll('.tls-trust-display-name').forEach((span) => {
+      span.addEventListener('click', () => {
+        const input = document.createElement('input');
+        input.type = 'text';
+        input.className = 'settings-input tls-trust-display-name-input';
+        input.value = span.textContent;
+        input.maxLength = 64;
+        span.replaceWith(input);
+        input.focus();
+        input.select();
+
+        let committed = false;
+        async function commit() {
+          if (committed) return;
+          committed = true;
+          try {
+            const res = await fetch(`/api/devices/${encodeURIComponent(span.dataset.id)}`, {
+              method: 'PUT',
+              headers: { 'Content-Type': 'application/json' },
+              credentials: 'same-origin',
+              body: JSON.stringify({ name: input.value.trim() }),
+            });
+            if (!res.ok) throw new Error(`HTTP ${res.status}`);
+          } catch {
+            Toast.error('Failed to rename device');
+          }
+          loadDevices();
+        }
+        input.addEventListener('blur', commit);
+        input.addEventListener('keydown', (e) => {
+          if (e.key === 'Enter') { e.preventDefault(); input.blur(); }
+          if (e.key === 'Escape') { e.preventDefault(); committed = true; loadDevices(); }
+        });
+      });
+    });
+
+    list.querySelectorAll('.tls-trust-delete').forEach((btn) => {
+      btn.addEventListener('click', () => {
+        const device = devices.find((d) => d.id === btn.dataset.id);
+        if (!device) return;
+        Spinner.button(btn, async () => {
+          const res = await fetch(`/api/devices/${encodeURIComponent(device.id)}`, {
+            method: 'DELETE',
+            credentials: 'same-origin',
+          });
+          if (!res.ok) throw new Error(`HTTP ${res.status}`);
+          if (device.current) {
+            // 自分の端末を取り消したらログイン画面へ
+            Auth.clearToken();
+            location.reload();
+            return;
+          }
+          Toast.success(`Signed out ${device.name}`);
+          loadDevices();
+        }).catch(() => Toast.error('Failed to revoke device'));
+      });
+    });
+  }
+
   let saveInFlight = false;
   let savePending = false;
 
@@ -712,6 +808,7 @@ const DenSettings = (() => {
     if (updateApplyBtn) updateApplyBtn.hidden = true;
     loadTlsStatus();
     loadTrustedTls();
+    loadDevices();
 
     modal.hidden = false;
   }
@@ -1096,7 +1193,12 @@ const DenSettings = (() => {
         const resp = await fetch('/api/system/version', { credentials: 'same-origin' });
         if (!resp.ok) throw new Error(`HTTP ${resp.status}`);
         const info = await resp.json();
-        if (info.update_available && info.latest) {
+        if (info.update_available && info.latest && !info.can_update) {
+          // Source/dev builds have no release signing key and can't self-update
+          updateStatus.textContent = 'v' + info.latest + ' available (install a release build to update)';
+          updateStatus.hidden = false;
+          updateStatus.className = 'update-status update-available';
+        } else if (info.update_available && info.latest) {
           updateStatus.textContent = 'v' + info.latest + ' available';
           updateStatus.hidden = false;
           updateStatus.className = 'update-status update-available';
@@ -621,7 +621,7 @@ const DenTerminal = (() => {
         // Any inbound frame proves the socket is live — feed the half-open check.
         st.lastReceiveTs = Date.now();
         if (typeof event.data === 'string') {
-          // Text branch carries only JSON control messages (pong / session_ended / snapshot).
+          // Text branch carries only JSON control messages (pong / session_ended / server_shutting_down / snapshot).
           try {
             const msg = JSON.parse(event.data);
             if (msg.type === 'pong') {
@@ -635,6 +635,13 @@ const DenTerminal = (() => {
               refreshSessionList();
               return;
             }
+            if (msg.type === 'server_shutting_down') {
+              // Server is stopping; onclose keeps retrying until it is back.
+              st.term.writeln(msg.restart
+                ? '\r\n\x1b[33mServer restarting, reconnecting...\x1b[0m'
+                : '\r\n\x1b[33mServer shutting down.\x1b[0m');
+              return;
+            }
             if (msg.type === 'snapshot') {
               // Next binary frame is a full redraw: reset before applying it.
               pendingSnapshot = true;
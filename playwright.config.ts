@@ -15,6 +15,8 @@ export default defineConfig({
     ignoreHTTPSErrors: true,
     trace: 'retain-on-failure',
     screenshot: 'only-on-failure',
+    // Cookie-authenticated API calls need the CSRF header (the frontend adds it to every fetch)
+    extraHTTPHeaders: { 'X-Den-Request': '1' },
   },
 
   webServer: {
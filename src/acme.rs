@@ -0,0 +1,712 @@
+//! Automatic certificates from an ACME CA (Let's Encrypt by default).
+//!
+//! With `DEN_ACME_DOMAINS` set, den answers HTTP-01 challenges on a plain
+//! HTTP listener (`DEN_ACME_HTTP_PORT`, default 80, which otherwise redirects
+//! to HTTPS), orders a certificate for those names and swaps it into the
+//! running TLS listener. It is renewed 30 days before it expires. The account
+//! key and the current certificate live in `data_dir/acme/`; until the first
+//! order completes the usual self-signed certificate is served.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::Router;
+use axum::extract::{Path as AxumPath, State};
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ring::rand::SystemRandom;
+use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::tls::ReloadableCert;
+
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+const ACCOUNT_KEY_FILENAME: &str = "account-key.der";
+const CERT_FILENAME: &str = "cert.pem";
+const KEY_FILENAME: &str = "key.pem";
+/// Renew when the certificate has less than this left
+const RENEW_BEFORE_SECS: u64 = 30 * 24 * 60 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_POLLS: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcmeConfig {
+    /// DNS names on the certificate (the first is the subject)
+    pub domains: Vec<String>,
+    /// Contact address registered with the CA
+    pub email: Option<String>,
+    /// ACME directory URL
+    pub directory_url: String,
+    /// Plain HTTP port for HTTP-01 challenges and the HTTPS redirect
+    pub http_port: u16,
+}
+
+/// `data_dir/acme/{cert.pem,key.pem}`
+pub fn certificate_paths(data_dir: &Path) -> (PathBuf, PathBuf) {
+    let dir = data_dir.join("acme");
+    (dir.join(CERT_FILENAME), dir.join(KEY_FILENAME))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Unix seconds at which the (first) certificate in `pem` expires
+fn not_after(pem: &[u8]) -> Option<u64> {
+    use rustls::pki_types::CertificateDer;
+    use rustls::pki_types::pem::PemObject;
+
+    let cert = CertificateDer::pem_slice_iter(pem).next()?.ok()?;
+    let (_, parsed) = x509_parser::parse_x509_certificate(&cert).ok()?;
+    u64::try_from(parsed.validity().not_after.timestamp()).ok()
+}
+
+fn needs_renewal(pem: Option<&[u8]>, now: u64) -> bool {
+    pem.and_then(not_after)
+        .is_none_or(|expires| expires.saturating_sub(now) < RENEW_BEFORE_SECS)
+}
+
+/// Write a file readable only by den (private keys)
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    std::fs::write(path, contents)
+        .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("failed to restrict {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+// --- HTTP-01 challenge listener ---
+
+/// token → key authorization, served at `/.well-known/acme-challenge/{token}`
+#[derive(Clone, Default)]
+pub struct Challenges {
+    inner: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Challenges {
+    fn insert(&self, token: &str, key_authorization: String) {
+        self.inner
+            .lock()
+            .expect("challenges poisoned")
+            .insert(token.to_string(), key_authorization);
+    }
+
+    fn remove(&self, token: &str) {
+        self.inner
+            .lock()
+            .expect("challenges poisoned")
+            .remove(token);
+    }
+
+    fn get(&self, token: &str) -> Option<String> {
+        self.inner
+            .lock()
+            .expect("challenges poisoned")
+            .get(token)
+            .cloned()
+    }
+}
+
+async fn serve_challenge(
+    State((challenges, _)): State<(Challenges, u16)>,
+    AxumPath(token): AxumPath<String>,
+) -> Response {
+    match challenges.get(&token) {
+        Some(key_authorization) => (
+            [(header::CONTENT_TYPE, "application/octet-stream")],
+            key_authorization,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Everything else on the plain HTTP port goes to the HTTPS listener
+async fn redirect_to_https(
+    State((_, https_port)): State<(Challenges, u16)>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    // Drop the port of a Host like "den.example:80" (IPv6 literals keep their brackets)
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let port = if https_port == 443 {
+        String::new()
+    } else {
+        format!(":{https_port}")
+    };
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    Redirect::permanent(&format!("https://{host}{port}{path}")).into_response()
+}
+
+pub fn challenge_router(challenges: Challenges, https_port: u16) -> Router {
+    Router::new()
+        .route("/.well-known/acme-challenge/{token}", get(serve_challenge))
+        .fallback(redirect_to_https)
+        .with_state((challenges, https_port))
+}
+
+// --- ACME client (RFC 8555) ---
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    #[serde(default)]
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: Option<String>,
+}
+
+/// JWK of a P-256 public key, members in RFC 7638 thumbprint order
+fn jwk(public_key: &[u8]) -> Value {
+    // Uncompressed point: 0x04 || x || y
+    json!({
+        "crv": "P-256",
+        "kty": "EC",
+        "x": URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+        "y": URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+    })
+}
+
+/// RFC 7638 thumbprint (serde_json keeps object members sorted)
+fn jwk_thumbprint(jwk: &Value) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(jwk.to_string().as_bytes()))
+}
+
+fn key_authorization(token: &str, thumbprint: &str) -> String {
+    format!("{token}.{thumbprint}")
+}
+
+struct AcmeClient {
+    http: reqwest::Client,
+    directory: Directory,
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+    /// Account URL once registered
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl AcmeClient {
+    async fn new(directory_url: &str, account_key_pkcs8: &[u8]) -> Result<Self, String> {
+        let rng = SystemRandom::new();
+        let key =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, account_key_pkcs8, &rng)
+                .map_err(|e| format!("invalid ACME account key: {e}"))?;
+        let http = reqwest::Client::builder()
+            .user_agent(concat!("den/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let directory = http
+            .get(directory_url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("ACME directory {directory_url}: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("ACME directory {directory_url}: {e}"))?;
+        Ok(Self {
+            http,
+            directory,
+            key,
+            rng,
+            kid: None,
+            nonce: None,
+        })
+    }
+
+    fn jwk(&self) -> Value {
+        jwk(self.key.public_key().as_ref())
+    }
+
+    async fn nonce(&mut self) -> Result<String, String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let resp = self
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .await
+            .map_err(|e| format!("ACME newNonce: {e}"))?;
+        replay_nonce(resp.headers()).ok_or_else(|| "ACME newNonce: no Replay-Nonce".to_string())
+    }
+
+    fn jws(&self, url: &str, nonce: &str, payload: Option<&Value>) -> Result<Value, String> {
+        let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+        match self.kid {
+            Some(ref kid) => protected["kid"] = json!(kid),
+            None => protected["jwk"] = self.jwk(),
+        }
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        // POST-as-GET has an empty payload
+        let payload = payload.map_or(String::new(), |p| URL_SAFE_NO_PAD.encode(p.to_string()));
+        let signature = self
+            .key
+            .sign(&self.rng, format!("{protected}.{payload}").as_bytes())
+            .map_err(|_| "ACME: signing failed".to_string())?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        }))
+    }
+
+    /// Signed POST. A `badNonce` error is retried once with the fresh nonce.
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<reqwest::Response, String> {
+        for attempt in 0..2 {
+            let nonce = self.nonce().await?;
+            let body = self.jws(url, &nonce, payload)?;
+            let resp = self
+                .http
+                .post(url)
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(|e| format!("ACME {url}: {e}"))?;
+            self.nonce = replay_nonce(resp.headers());
+            if resp.status().is_success() {
+                return Ok(resp);
+            }
+            let status = resp.status();
+            let problem: Value = resp.json().await.unwrap_or_default();
+            let kind = problem["type"].as_str().unwrap_or_default();
+            if attempt == 0 && kind.ends_with(":badNonce") {
+                continue;
+            }
+            return Err(format!(
+                "ACME {url}: {status} {kind} {}",
+                problem["detail"].as_str().unwrap_or_default()
+            ));
+        }
+        unreachable!("the second attempt always returns")
+    }
+
+    async fn post_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<T, String> {
+        self.post(url, payload)
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("ACME {url}: {e}"))
+    }
+
+    async fn register(&mut self, email: Option<&str>) -> Result<(), String> {
+        let mut payload = json!({ "termsOfServiceAgreed": true });
+        if let Some(email) = email {
+            payload["contact"] = json!([format!("mailto:{email}")]);
+        }
+        let url = self.directory.new_account.clone();
+        let resp = self.post(&url, Some(&payload)).await?;
+        let kid = resp
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or("ACME newAccount: no account URL")?;
+        self.kid = Some(kid.to_string());
+        Ok(())
+    }
+
+    async fn poll<T: serde::de::DeserializeOwned>(
+        &mut self,
+        url: &str,
+        done: impl Fn(&T) -> Option<Result<(), String>>,
+    ) -> Result<T, String> {
+        for _ in 0..MAX_POLLS {
+            let value: T = self.post_json(url, None).await?;
+            match done(&value) {
+                Some(Ok(())) => return Ok(value),
+                Some(Err(e)) => return Err(e),
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        Err(format!("ACME {url}: timed out"))
+    }
+}
+
+fn replay_nonce(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn load_or_create_account_key(path: &Path) -> Result<Vec<u8>, String> {
+    if let Ok(key) = std::fs::read(path) {
+        return Ok(key);
+    }
+    let pkcs8 =
+        EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+            .map_err(|_| "failed to generate ACME account key".to_string())?;
+    write_private(path, pkcs8.as_ref())?;
+    Ok(pkcs8.as_ref().to_vec())
+}
+
+/// Run one order to completion. Returns (certificate chain PEM, private key PEM).
+async fn order_certificate(
+    config: &AcmeConfig,
+    data_dir: &Path,
+    challenges: &Challenges,
+) -> Result<(String, String), String> {
+    let account_key =
+        load_or_create_account_key(&data_dir.join("acme").join(ACCOUNT_KEY_FILENAME))?;
+    let mut client = AcmeClient::new(&config.directory_url, &account_key).await?;
+    client.register(config.email.as_deref()).await?;
+    let thumbprint = jwk_thumbprint(&client.jwk());
+
+    let identifiers: Vec<Value> = config
+        .domains
+        .iter()
+        .map(|d| json!({ "type": "dns", "value": d }))
+        .collect();
+    let new_order = client.directory.new_order.clone();
+    let resp = client
+        .post(&new_order, Some(&json!({ "identifiers": identifiers })))
+        .await?;
+    let order_url = resp
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or("ACME newOrder: no order URL")?
+        .to_string();
+    let order: Order = resp
+        .json()
+        .await
+        .map_err(|e| format!("ACME newOrder: {e}"))?;
+
+    for authz_url in &order.authorizations {
+        let authz: Authorization = client.post_json(authz_url, None).await?;
+        if authz.status == "valid" {
+            continue;
+        }
+        let domain = authz.identifier.value;
+        let challenge = authz
+            .challenges
+            .into_iter()
+            .find(|c| c.kind == "http-01")
+            .ok_or_else(|| format!("ACME: no http-01 challenge for {domain}"))?;
+        let token = challenge.token.ok_or("ACME: challenge without token")?;
+        challenges.insert(&token, key_authorization(&token, &thumbprint));
+        let result = async {
+            client.post(&challenge.url, Some(&json!({}))).await?;
+            client
+                .poll(authz_url, |a: &Authorization| match a.status.as_str() {
+                    "valid" => Some(Ok(())),
+                    "pending" | "processing" => None,
+                    status => Some(Err(format!("ACME: authorization for {domain} is {status}"))),
+                })
+                .await
+        }
+        .await;
+        challenges.remove(&token);
+        result?;
+        tracing::info!("ACME: validated {domain}");
+    }
+
+    let cert_key = rcgen::KeyPair::generate().map_err(|e| e.to_string())?;
+    let csr = rcgen::CertificateParams::new(config.domains.clone())
+        .and_then(|params| params.serialize_request(&cert_key))
+        .map_err(|e| format!("ACME: failed to build CSR: {e}"))?;
+    let csr = URL_SAFE_NO_PAD.encode(csr.der());
+    let _: Order = client
+        .post_json(&order.finalize, Some(&json!({ "csr": csr })))
+        .await?;
+    let order: Order = client
+        .poll(&order_url, |o: &Order| match o.status.as_str() {
+            "valid" => Some(Ok(())),
+            "pending" | "ready" | "processing" => None,
+            status => Some(Err(format!("ACME: order is {status}"))),
+        })
+        .await?;
+    let certificate_url = order
+        .certificate
+        .ok_or("ACME: valid order without certificate")?;
+    let chain = client
+        .post(&certificate_url, None)
+        .await?
+        .text()
+        .await
+        .map_err(|e| format!("ACME certificate: {e}"))?;
+    Ok((chain, cert_key.serialize_pem()))
+}
+
+/// Obtain or renew when due, then swap the new certificate in
+async fn renew_if_due(
+    config: &AcmeConfig,
+    data_dir: &Path,
+    challenges: &Challenges,
+    cert: &ReloadableCert,
+) -> Result<(), String> {
+    let (cert_path, key_path) = certificate_paths(data_dir);
+    let current = std::fs::read(&cert_path).ok();
+    if !needs_renewal(current.as_deref(), unix_now()) {
+        return Ok(());
+    }
+    tracing::info!(
+        "ACME: requesting a certificate for {}",
+        config.domains.join(", ")
+    );
+    let (chain_pem, key_pem) = order_certificate(config, data_dir, challenges).await?;
+    cert.replace_pem(chain_pem.as_bytes(), key_pem.as_bytes())?;
+    write_private(&key_path, key_pem.as_bytes())?;
+    std::fs::write(&cert_path, &chain_pem)
+        .map_err(|e| format!("failed to write {}: {e}", cert_path.display()))?;
+    tracing::info!(
+        "ACME: certificate installed for {}",
+        config.domains.join(", ")
+    );
+    Ok(())
+}
+
+/// Start the challenge listener and the renewal loop
+pub fn spawn(
+    config: AcmeConfig,
+    data_dir: PathBuf,
+    bind_address: String,
+    https_port: u16,
+    cert: Arc<ReloadableCert>,
+) -> tokio::task::JoinHandle<()> {
+    let challenges = Challenges::default();
+    let router = challenge_router(challenges.clone(), https_port);
+    tokio::spawn(async move {
+        let listener =
+            match tokio::net::TcpListener::bind(format!("{bind_address}:{}", config.http_port))
+                .await
+            {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!(
+                        "ACME: cannot listen on port {} for HTTP-01 challenges: {e}",
+                        config.http_port
+                    );
+                    return;
+                }
+            };
+        tracing::info!("ACME: HTTP-01 listener on port {}", config.http_port);
+        let http = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("ACME: HTTP listener stopped: {e}");
+            }
+        });
+        loop {
+            let wait = match renew_if_due(&config, &data_dir, &challenges, &cert).await {
+                Ok(()) => CHECK_INTERVAL,
+                Err(e) => {
+                    tracing::error!("ACME: {e}");
+                    RETRY_INTERVAL
+                }
+            };
+            if http.is_finished() {
+                return;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[test]
+    fn jwk_thumbprint_is_canonical() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+        let jwk = jwk(key.public_key().as_ref());
+        let text = jwk.to_string();
+        // RFC 7638: required members only, lexicographic order, no whitespace
+        assert!(text.starts_with(r#"{"crv":"P-256","kty":"EC","x":""#));
+        assert!(!text.contains(' '));
+        let thumbprint = jwk_thumbprint(&jwk);
+        assert_eq!(thumbprint.len(), 43);
+        assert_eq!(
+            key_authorization("tok", &thumbprint),
+            format!("tok.{thumbprint}")
+        );
+    }
+
+    #[test]
+    fn jws_signature_verifies() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let client = AcmeClient {
+            http: reqwest::Client::new(),
+            directory: Directory {
+                new_nonce: String::new(),
+                new_account: String::new(),
+                new_order: String::new(),
+            },
+            key: EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap(),
+            rng,
+            kid: Some("https://ca.example/acct/1".into()),
+            nonce: None,
+        };
+        let jws = client
+            .jws(
+                "https://ca.example/order",
+                "n0nce",
+                Some(&json!({ "a": 1 })),
+            )
+            .unwrap();
+        let protected: Value = serde_json::from_slice(
+            &URL_SAFE_NO_PAD
+                .decode(jws["protected"].as_str().unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(protected["kid"], "https://ca.example/acct/1");
+        assert!(protected.get("jwk").is_none());
+        let signed = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        let signature = URL_SAFE_NO_PAD
+            .decode(jws["signature"].as_str().unwrap())
+            .unwrap();
+        ring::signature::UnparsedPublicKey::new(
+            &ring::signature::ECDSA_P256_SHA256_FIXED,
+            client.key.public_key().as_ref(),
+        )
+        .verify(signed.as_bytes(), &signature)
+        .unwrap();
+
+        // POST-as-GET
+        let jws = client
+            .jws("https://ca.example/order", "n0nce", None)
+            .unwrap();
+        assert_eq!(jws["payload"], "");
+    }
+
+    #[test]
+    fn renewal_is_due_thirty_days_before_expiry() {
+        let mut params = rcgen::CertificateParams::new(vec!["den.example".to_string()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2030, 1, 31);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let pem = params.self_signed(&key).unwrap().pem();
+        let expires = not_after(pem.as_bytes()).unwrap();
+        assert!(!needs_renewal(
+            Some(pem.as_bytes()),
+            expires - RENEW_BEFORE_SECS - 1
+        ));
+        assert!(needs_renewal(
+            Some(pem.as_bytes()),
+            expires - RENEW_BEFORE_SECS + 1
+        ));
+        assert!(needs_renewal(None, 0));
+        assert!(needs_renewal(Some(b"garbage"), 0));
+    }
+
+    #[tokio::test]
+    async fn challenge_listener_answers_tokens_and_redirects() {
+        let challenges = Challenges::default();
+        challenges.insert("abc", "abc.thumb".to_string());
+        let app = challenge_router(challenges.clone(), 8443);
+
+        let get = |uri: &str, host: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(header::HOST, host)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app
+            .clone()
+            .oneshot(get("/.well-known/acme-challenge/abc", "den.example"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"abc.thumb");
+
+        challenges.remove("abc");
+        let resp = app
+            .clone()
+            .oneshot(get("/.well-known/acme-challenge/abc", "den.example"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = app
+            .clone()
+            .oneshot(get("/files?x=1", "den.example:80"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers()[header::LOCATION],
+            "https://den.example:8443/files?x=1"
+        );
+    }
+}
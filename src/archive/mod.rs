@@ -0,0 +1,3 @@
+// ダウンロード用アーカイブ形式（ストリーミング生成）
+pub mod tar;
+pub mod zip;
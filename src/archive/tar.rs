@@ -0,0 +1,160 @@
+//! Minimal streaming tar (ustar + GNU long names) writer.
+//!
+//! Only what downloads need: regular files and directories. Callers write a
+//! header, then exactly `size` bytes of content, then the padding, and finish
+//! with `end_of_archive()`.
+
+const BLOCK: usize = 512;
+/// GNU tar's pseudo-entry name for paths longer than the ustar name field
+const LONG_LINK_NAME: &[u8] = b"././@LongLink";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    File,
+    Dir,
+}
+
+/// Header block(s) for one entry. Paths over 100 bytes get a GNU `L` record first.
+pub fn header(path: &str, entry_type: EntryType, size: u64, mode: u32, mtime: u64) -> Vec<u8> {
+    let mut name = path.trim_start_matches('/').to_string();
+    if entry_type == EntryType::Dir && !name.ends_with('/') {
+        name.push('/');
+    }
+    let mut out = Vec::with_capacity(BLOCK * 3);
+    if name.len() > 100 {
+        let long = name.as_bytes();
+        out.extend_from_slice(&raw_header(
+            LONG_LINK_NAME,
+            b'L',
+            long.len() as u64 + 1,
+            0,
+            0,
+        ));
+        out.extend_from_slice(long);
+        out.push(0);
+        out.resize(out.len() + padding(long.len() as u64 + 1), 0);
+    }
+    let (type_flag, size) = match entry_type {
+        EntryType::File => (b'0', size),
+        EntryType::Dir => (b'5', 0),
+    };
+    let short = &name.as_bytes()[..name.len().min(100)];
+    out.extend_from_slice(&raw_header(short, type_flag, size, mode & 0o7777, mtime));
+    out
+}
+
+/// Zero bytes needed after `size` bytes of content to reach a block boundary.
+pub fn padding(size: u64) -> usize {
+    let rem = (size % BLOCK as u64) as usize;
+    if rem == 0 { 0 } else { BLOCK - rem }
+}
+
+/// Two zero blocks terminate the archive.
+pub fn end_of_archive() -> Vec<u8> {
+    vec![0; BLOCK * 2]
+}
+
+fn raw_header(name: &[u8], type_flag: u8, size: u64, mode: u32, mtime: u64) -> [u8; BLOCK] {
+    let mut h = [0u8; BLOCK];
+    h[..name.len()].copy_from_slice(name);
+    write_octal(&mut h[100..108], u64::from(mode));
+    write_octal(&mut h[108..116], 0); // uid
+    write_octal(&mut h[116..124], 0); // gid
+    write_numeric(&mut h[124..136], size);
+    write_numeric(&mut h[136..148], mtime);
+    h[156] = type_flag;
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+
+    // Checksum is computed with its own field set to spaces
+    h[148..156].fill(b' ');
+    let sum: u32 = h.iter().map(|&b| u32::from(b)).sum();
+    let chk = format!("{sum:06o}\0 ");
+    h[148..156].copy_from_slice(chk.as_bytes());
+    h
+}
+
+/// Zero-padded octal with a trailing NUL.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let s = format!("{value:0digits$o}");
+    field[..digits].copy_from_slice(&s.as_bytes()[s.len() - digits..]);
+    field[digits] = 0;
+}
+
+/// Octal when it fits, otherwise GNU base-256 (sizes ≥ 8 GiB).
+fn write_numeric(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    if value < 1u64 << (3 * digits) {
+        write_octal(field, value);
+    } else {
+        field.fill(0);
+        field[0] = 0x80;
+        let len = field.len();
+        field[len - 8..].copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn octal_field(field: &[u8]) -> u64 {
+        let s = std::str::from_utf8(field)
+            .unwrap()
+            .trim_end_matches(['\0', ' ']);
+        u64::from_str_radix(s, 8).unwrap()
+    }
+
+    #[test]
+    fn file_header_layout() {
+        let h = header("dir/a.txt", EntryType::File, 5, 0o644, 1_700_000_000);
+        assert_eq!(h.len(), BLOCK);
+        assert_eq!(&h[..9], b"dir/a.txt");
+        assert_eq!(octal_field(&h[100..108]), 0o644);
+        assert_eq!(octal_field(&h[124..136]), 5);
+        assert_eq!(octal_field(&h[136..148]), 1_700_000_000);
+        assert_eq!(h[156], b'0');
+        assert_eq!(&h[257..263], b"ustar\0");
+
+        let stored = octal_field(&h[148..154]);
+        let mut copy = h.clone();
+        copy[148..156].fill(b' ');
+        let sum: u64 = copy.iter().map(|&b| u64::from(b)).sum();
+        assert_eq!(stored, sum);
+    }
+
+    #[test]
+    fn dir_header_has_trailing_slash_and_no_size() {
+        let h = header("/srv/logs", EntryType::Dir, 4096, 0o755, 0);
+        assert_eq!(&h[..10], b"srv/logs/\0");
+        assert_eq!(h[156], b'5');
+        assert_eq!(octal_field(&h[124..136]), 0);
+    }
+
+    #[test]
+    fn long_names_use_gnu_longlink() {
+        let name = "d/".repeat(60) + "file.txt";
+        let h = header(&name, EntryType::File, 1, 0o644, 0);
+        // L header + one name block + real header
+        assert_eq!(h.len(), BLOCK * 3);
+        assert_eq!(h[156], b'L');
+        assert_eq!(&h[BLOCK..BLOCK + name.len()], name.as_bytes());
+        assert_eq!(h[BLOCK * 2 + 156], b'0');
+    }
+
+    #[test]
+    fn huge_size_uses_base256() {
+        let mut field = [0u8; 12];
+        write_numeric(&mut field, 10 << 30);
+        assert_eq!(field[0], 0x80);
+        assert_eq!(u64::from_be_bytes(field[4..].try_into().unwrap()), 10 << 30);
+    }
+
+    #[test]
+    fn padding_to_block() {
+        assert_eq!(padding(0), 0);
+        assert_eq!(padding(1), 511);
+        assert_eq!(padding(512), 0);
+    }
+}
@@ -0,0 +1,291 @@
+//! Minimal streaming zip writer (deflate, data descriptors, no zip64).
+//!
+//! Entries are written front to back without seeking: each local header has
+//! the "sizes follow" flag set and is followed by a data descriptor, and the
+//! central directory is emitted by `finish()`. Callers must keep the archive
+//! under 4 GiB and 65535 entries.
+
+use std::io::{self, Read, Write};
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIG: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+/// bit 3: sizes in data descriptor, bit 11: UTF-8 names
+const FLAGS: u16 = (1 << 3) | (1 << 11);
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+/// 2.0: deflate + directories
+const VERSION_NEEDED: u16 = 20;
+/// Upper byte 3 = Unix, so external attributes carry the mode
+const VERSION_MADE_BY: u16 = (3 << 8) | 20;
+
+/// Hard format limits without zip64.
+pub const MAX_ENTRIES: usize = u16::MAX as usize;
+pub const MAX_SIZE: u64 = u32::MAX as u64;
+
+struct CentralEntry {
+    name: Vec<u8>,
+    method: u16,
+    dos_time: u16,
+    dos_date: u16,
+    crc: u32,
+    compressed: u32,
+    uncompressed: u32,
+    external_attrs: u32,
+    offset: u32,
+}
+
+pub struct ZipWriter<W: Write> {
+    out: CountingWriter<W>,
+    entries: Vec<CentralEntry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: CountingWriter {
+                inner: out,
+                written: 0,
+            },
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a directory entry (`name` gets a trailing `/`).
+    pub fn add_dir(&mut self, name: &str, mtime: u64) -> io::Result<()> {
+        let mut name = name.trim_matches('/').to_string();
+        name.push('/');
+        let offset = self.offset()?;
+        let (dos_time, dos_date) = dos_datetime(mtime);
+        self.write_local_header(name.as_bytes(), METHOD_STORED, dos_time, dos_date)?;
+        self.write_descriptor(0, 0, 0)?;
+        self.entries.push(CentralEntry {
+            name: name.into_bytes(),
+            method: METHOD_STORED,
+            dos_time,
+            dos_date,
+            crc: 0,
+            compressed: 0,
+            uncompressed: 0,
+            external_attrs: (0o040755 << 16) | 0x10,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// Add a file, deflating everything read from `data`.
+    pub fn add_file(&mut self, name: &str, mtime: u64, data: &mut impl Read) -> io::Result<()> {
+        let name = name.trim_start_matches('/');
+        let offset = self.offset()?;
+        let (dos_time, dos_date) = dos_datetime(mtime);
+        self.write_local_header(name.as_bytes(), METHOD_DEFLATE, dos_time, dos_date)?;
+
+        let start = self.out.written;
+        let mut crc = crc32fast::Hasher::new();
+        let mut uncompressed = 0u64;
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(&mut self.out, flate2::Compression::fast());
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = data.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            crc.update(&buf[..n]);
+            uncompressed += n as u64;
+            encoder.write_all(&buf[..n])?;
+        }
+        encoder.finish()?;
+        let compressed = self.out.written - start;
+
+        let crc = crc.finalize();
+        let compressed = to_u32(compressed)?;
+        let uncompressed = to_u32(uncompressed)?;
+        self.write_descriptor(crc, compressed, uncompressed)?;
+        self.entries.push(CentralEntry {
+            name: name.as_bytes().to_vec(),
+            method: METHOD_DEFLATE,
+            dos_time,
+            dos_date,
+            crc,
+            compressed,
+            uncompressed,
+            external_attrs: 0o100644 << 16,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.entries.len() > MAX_ENTRIES {
+            return Err(io::Error::other("too many zip entries"));
+        }
+        let cd_start = self.offset()?;
+        for e in &self.entries {
+            let mut h = Vec::with_capacity(46 + e.name.len());
+            put32(&mut h, CENTRAL_HEADER_SIG);
+            put16(&mut h, VERSION_MADE_BY);
+            put16(&mut h, VERSION_NEEDED);
+            put16(&mut h, FLAGS);
+            put16(&mut h, e.method);
+            put16(&mut h, e.dos_time);
+            put16(&mut h, e.dos_date);
+            put32(&mut h, e.crc);
+            put32(&mut h, e.compressed);
+            put32(&mut h, e.uncompressed);
+            put16(&mut h, e.name.len() as u16);
+            put16(&mut h, 0); // extra length
+            put16(&mut h, 0); // comment length
+            put16(&mut h, 0); // disk number
+            put16(&mut h, 0); // internal attributes
+            put32(&mut h, e.external_attrs);
+            put32(&mut h, e.offset);
+            h.extend_from_slice(&e.name);
+            self.out.write_all(&h)?;
+        }
+        let cd_size = to_u32(u64::from(self.offset()?) - u64::from(cd_start))?;
+
+        let count = self.entries.len() as u16;
+        let mut end = Vec::with_capacity(22);
+        put32(&mut end, END_OF_CENTRAL_DIR_SIG);
+        put16(&mut end, 0); // this disk
+        put16(&mut end, 0); // disk with central directory
+        put16(&mut end, count);
+        put16(&mut end, count);
+        put32(&mut end, cd_size);
+        put32(&mut end, cd_start);
+        put16(&mut end, 0); // comment length
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(self.out.inner)
+    }
+
+    fn offset(&self) -> io::Result<u32> {
+        to_u32(self.out.written)
+    }
+
+    fn write_local_header(
+        &mut self,
+        name: &[u8],
+        method: u16,
+        dos_time: u16,
+        dos_date: u16,
+    ) -> io::Result<()> {
+        if name.len() > u16::MAX as usize {
+            return Err(io::Error::other("zip entry name too long"));
+        }
+        let mut h = Vec::with_capacity(30 + name.len());
+        put32(&mut h, LOCAL_HEADER_SIG);
+        put16(&mut h, VERSION_NEEDED);
+        put16(&mut h, FLAGS);
+        put16(&mut h, method);
+        put16(&mut h, dos_time);
+        put16(&mut h, dos_date);
+        put32(&mut h, 0); // crc (in descriptor)
+        put32(&mut h, 0); // compressed size (in descriptor)
+        put32(&mut h, 0); // uncompressed size (in descriptor)
+        put16(&mut h, name.len() as u16);
+        put16(&mut h, 0); // extra length
+        h.extend_from_slice(name);
+        self.out.write_all(&h)
+    }
+
+    fn write_descriptor(&mut self, crc: u32, compressed: u32, uncompressed: u32) -> io::Result<()> {
+        let mut d = Vec::with_capacity(16);
+        put32(&mut d, DATA_DESCRIPTOR_SIG);
+        put32(&mut d, crc);
+        put32(&mut d, compressed);
+        put32(&mut d, uncompressed);
+        self.out.write_all(&d)
+    }
+}
+
+struct CountingWriter<W: Write> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn to_u32(v: u64) -> io::Result<u32> {
+    u32::try_from(v).map_err(|_| io::Error::other("zip archive exceeds 4 GiB"))
+}
+
+fn put16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+/// MS-DOS (time, date) in UTC; clamped to the format's 1980–2107 range.
+fn dos_datetime(unix_secs: u64) -> (u16, u16) {
+    use chrono::{Datelike, Timelike};
+    let Some(dt) = chrono::DateTime::from_timestamp(unix_secs as i64, 0) else {
+        return (0, (1 << 5) | 1);
+    };
+    if dt.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let year = (dt.year() - 1980).min(127) as u16;
+    let date = (year << 9) | ((dt.month() as u16) << 5) | dt.day() as u16;
+    let time = ((dt.hour() as u16) << 11) | ((dt.minute() as u16) << 5) | (dt.second() as u16 / 2);
+    (time, date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dos_datetime_encoding() {
+        // 2024-03-15 12:34:56 UTC
+        let (time, date) = dos_datetime(1_710_506_096);
+        assert_eq!(date >> 9, 2024 - 1980);
+        assert_eq!((date >> 5) & 0xf, 3);
+        assert_eq!(date & 0x1f, 15);
+        assert_eq!(time >> 11, 12);
+        assert_eq!((time >> 5) & 0x3f, 34);
+        assert_eq!(time & 0x1f, 28);
+        assert_eq!(dos_datetime(0), (0, 0x21));
+    }
+
+    #[test]
+    fn archive_structure() {
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add_dir("docs", 0).unwrap();
+        zip.add_file("docs/a.txt", 0, &mut &b"hello hello hello"[..])
+            .unwrap();
+        let out = zip.finish().unwrap();
+
+        assert_eq!(&out[..4], &LOCAL_HEADER_SIG.to_le_bytes());
+        let eocd = &out[out.len() - 22..];
+        assert_eq!(&eocd[..4], &END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 2);
+        let cd_size = u32::from_le_bytes(eocd[12..16].try_into().unwrap()) as usize;
+        let cd_start = u32::from_le_bytes(eocd[16..20].try_into().unwrap()) as usize;
+        assert_eq!(cd_start + cd_size, out.len() - 22);
+        assert_eq!(
+            &out[cd_start..cd_start + 4],
+            &CENTRAL_HEADER_SIG.to_le_bytes()
+        );
+
+        // Second central entry: CRC of the file content
+        let second = cd_start + 46 + "docs/".len();
+        let crc = u32::from_le_bytes(out[second + 16..second + 20].try_into().unwrap());
+        assert_eq!(crc, crc32fast::hash(b"hello hello hello"));
+    }
+}
@@ -1,4 +1,5 @@
 use axum::{
+    Extension,
     http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
@@ -6,6 +7,8 @@ use bytes::Bytes;
 use rust_embed::Embed;
 use std::sync::OnceLock;
 
+use crate::auth::CspNonce;
+
 #[derive(Embed)]
 #[folder = "frontend/"]
 struct FrontendAssets;
@@ -51,30 +54,52 @@ fn sha2_digest(data: &[u8]) -> [u8; 32] {
 }
 
 /// 静的ファイル配信ハンドラ
-pub async fn serve_static(axum::extract::Path(path): axum::extract::Path<String>) -> Response {
+pub async fn serve_static(
+    nonce: Option<Extension<CspNonce>>,
+    axum::extract::Path(path): axum::extract::Path<String>,
+) -> Response {
     // /index.html must return the cache-busted version, same as /
     if path == "index.html" {
-        return serve_index().await;
+        return serve_index(nonce).await;
     }
     serve_file(&path)
 }
 
 /// index.html 配信
-pub async fn serve_index() -> Response {
+///
+/// CSP nonce がある場合は全 `<script>` に付与する。nonce はレスポンスごとに変わるため、
+/// キャッシュ・ETag による再利用はさせない（古い nonce の本文と新しいヘッダーが食い違う）。
+pub async fn serve_index(nonce: Option<Extension<CspNonce>>) -> Response {
     let (body, etag) = CACHED_INDEX.get_or_init(build_index_html);
 
+    let Some(Extension(CspNonce(nonce))) = nonce else {
+        return (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+                (header::CACHE_CONTROL, "public, max-age=60".to_string()),
+                (header::ETAG, etag.clone()),
+            ],
+            body.clone(),
+        )
+            .into_response();
+    };
     (
         StatusCode::OK,
         [
-            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
-            (header::CACHE_CONTROL, "public, max-age=60".to_string()),
-            (header::ETAG, etag.clone()),
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-store"),
         ],
-        body.clone(),
+        add_script_nonce(&String::from_utf8_lossy(body), &nonce),
     )
         .into_response()
 }
 
+/// `<script` タグに `nonce="..."` を付ける（nonce は base64 なので属性値にそのまま入る）
+fn add_script_nonce(html: &str, nonce: &str) -> String {
+    html.replace("<script", &format!("<script nonce=\"{nonce}\""))
+}
+
 /// multiplexer 用 layout/config を `data_dir` に書き出し、各絶対パスを返す。
 /// `shell`（Den の設定シェル）は zellij `default_shell` / tmux `default-command` の
 /// `__DEN_SHELL__` プレースホルダへ展開され、mux セッションのシェルを plain Den
@@ -0,0 +1,340 @@
+//! Append-only audit log of mutating API calls.
+//!
+//! Every non-GET request that passes `auth_middleware` is recorded with who
+//! made it (user / API token / client IP), the route and its path-like
+//! arguments, and the response status. Entries go to one JSON Lines file per
+//! UTC day under `data_dir/audit/`; files older than the
+//! `audit_retention_days` setting are pruned once a day.
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Query, State},
+    http::{Method, Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::ip_filter::ClientOrigin;
+
+/// Request arguments worth recording. Only these keys are copied from the
+/// query string / JSON body, so secrets (passwords, file content) never land
+/// in the log.
+const RECORDED_ARGS: &[&str] = &[
+    "path",
+    "from",
+    "to",
+    "target",
+    "local_path",
+    "remote_path",
+    "host",
+    "username",
+    "name",
+];
+/// JSON bodies larger than this are not inspected for arguments.
+const MAX_INSPECTED_BODY: usize = 2 * 1024 * 1024;
+/// Argument values are truncated to this many chars.
+const MAX_ARG_CHARS: usize = 1024;
+const DEFAULT_LIST_LIMIT: usize = 200;
+const MAX_LIST_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339
+    pub at: String,
+    /// None = owner
+    pub user: Option<String>,
+    /// API token ID when the call was made with a token
+    pub token: Option<String>,
+    pub client: Option<String>,
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    pub status: u16,
+}
+
+pub struct AuditLog {
+    dir: PathBuf,
+    /// Serializes appends; holds the UTC date of the last prune
+    last_pruned: Mutex<Option<String>>,
+}
+
+impl AuditLog {
+    pub fn new(data_dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(data_dir).join("audit"),
+            last_pruned: Mutex::new(None),
+        }
+    }
+
+    /// Blocking; call from `spawn_blocking`.
+    pub fn append(&self, entry: &AuditEntry, retention_days: u16) -> std::io::Result<()> {
+        let mut last_pruned = self.last_pruned.lock().unwrap();
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        fs::create_dir_all(&self.dir)?;
+        if last_pruned.as_deref() != Some(today.as_str()) {
+            self.prune(retention_days);
+            *last_pruned = Some(today.clone());
+        }
+        let mut line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+        line.push('\n');
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(format!("{today}.jsonl")))?
+            .write_all(line.as_bytes())
+    }
+
+    fn prune(&self, retention_days: u16) {
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days.into()))
+            .format("%Y-%m-%d")
+            .to_string();
+        for day in self.days() {
+            if day < cutoff
+                && let Err(e) = fs::remove_file(self.dir.join(format!("{day}.jsonl")))
+            {
+                tracing::warn!("Failed to prune audit log {day}: {e}");
+            }
+        }
+    }
+
+    /// Day file stems (`YYYY-MM-DD`), oldest first
+    fn days(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut days: Vec<String> = entries
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(".jsonl").map(str::to_string)
+            })
+            .filter(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+            .collect();
+        days.sort();
+        days
+    }
+
+    /// Newest entries first. Blocking; call from `spawn_blocking`.
+    pub fn recent(&self, limit: usize, filter: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+        let _guard = self.last_pruned.lock().unwrap();
+        let mut result = Vec::new();
+        for day in self.days().iter().rev() {
+            let Ok(content) = fs::read_to_string(self.dir.join(format!("{day}.jsonl"))) else {
+                continue;
+            };
+            let entries = content
+                .lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+                .filter(|e| filter(e));
+            for entry in entries {
+                result.push(entry);
+                if result.len() >= limit {
+                    return result;
+                }
+            }
+        }
+        result
+    }
+}
+
+fn is_mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn record_args(args: &mut BTreeMap<String, String>, key: &str, value: &str) {
+    if RECORDED_ARGS.contains(&key) {
+        args.insert(key.to_string(), value.chars().take(MAX_ARG_CHARS).collect());
+    }
+}
+
+/// Record mutating calls. Must be layered inside `auth_middleware` so the
+/// `AuthUser` extension is present.
+pub async fn audit_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !is_mutating(req.method()) {
+        return next.run(req).await;
+    }
+    let Some(user) = req.extensions().get::<AuthUser>().cloned() else {
+        return next.run(req).await;
+    };
+
+    let mut args = BTreeMap::new();
+    if let Ok(Query(query)) = Query::<Vec<(String, String)>>::try_from_uri(req.uri()) {
+        for (key, value) in &query {
+            record_args(&mut args, key, value);
+        }
+    }
+    let is_json = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    let req = match content_length {
+        Some(len) if is_json && len <= MAX_INSPECTED_BODY => {
+            let (parts, body) = req.into_parts();
+            let Ok(bytes) = axum::body::to_bytes(body, MAX_INSPECTED_BODY).await else {
+                return StatusCode::BAD_REQUEST.into_response();
+            };
+            if let Ok(serde_json::Value::Object(map)) = serde_json::from_slice(&bytes) {
+                for (key, value) in &map {
+                    if let Some(value) = value.as_str() {
+                        record_args(&mut args, key, value);
+                    }
+                }
+            }
+            Request::from_parts(parts, Body::from(bytes))
+        }
+        _ => req,
+    };
+
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let client = req
+        .extensions()
+        .get::<ClientOrigin>()
+        .and_then(|origin| origin.ip)
+        .map(|ip| ip.to_string());
+
+    let resp = next.run(req).await;
+
+    let entry = AuditEntry {
+        at: chrono::Utc::now().to_rfc3339(),
+        user: user.username,
+        token: user.token_id,
+        client,
+        method,
+        path,
+        args,
+        status: resp.status().as_u16(),
+    };
+    let audit_state = Arc::clone(&state);
+    tokio::task::spawn_blocking(move || {
+        let retention_days = audit_state.store.load_settings().audit_retention_days;
+        if let Err(e) = audit_state.audit.append(&entry, retention_days) {
+            tracing::warn!("Failed to write audit log: {e}");
+        }
+    });
+    resp
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+    /// Only entries by this user (`owner` = the DEN_PASSWORD owner)
+    pub user: Option<String>,
+}
+
+/// GET /api/audit (admin only)
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AuditQuery>,
+) -> impl IntoResponse {
+    let limit = q
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let audit_state = Arc::clone(&state);
+    let result = tokio::task::spawn_blocking(move || {
+        audit_state
+            .audit
+            .recent(limit, |e| match q.user.as_deref() {
+                None => true,
+                Some("owner") => e.user.is_none(),
+                Some(user) => e.user.as_deref() == Some(user),
+            })
+    })
+    .await;
+    match result {
+        Ok(entries) => Json::<Vec<AuditEntry>>(entries).into_response(),
+        Err(e) => {
+            tracing::error!("audit list task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user: Option<&str>, path: &str) -> AuditEntry {
+        AuditEntry {
+            at: chrono::Utc::now().to_rfc3339(),
+            user: user.map(str::to_string),
+            token: None,
+            client: None,
+            method: "POST".into(),
+            path: path.into(),
+            args: BTreeMap::new(),
+            status: 200,
+        }
+    }
+
+    #[test]
+    fn append_and_read_newest_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(&tmp.path().to_string_lossy());
+        log.append(&entry(None, "/api/filer/mkdir"), 30).unwrap();
+        log.append(&entry(Some("alice"), "/api/filer/write"), 30)
+            .unwrap();
+        log.append(&entry(None, "/api/filer/delete"), 30).unwrap();
+
+        let all = log.recent(10, |_| true);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].path, "/api/filer/delete");
+        assert_eq!(all[2].path, "/api/filer/mkdir");
+        assert_eq!(log.recent(1, |_| true).len(), 1);
+        let alice = log.recent(10, |e| e.user.as_deref() == Some("alice"));
+        assert_eq!(alice.len(), 1);
+        assert_eq!(alice[0].path, "/api/filer/write");
+    }
+
+    #[test]
+    fn old_days_are_pruned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(&tmp.path().to_string_lossy());
+        fs::create_dir_all(&log.dir).unwrap();
+        let old = serde_json::to_string(&entry(None, "/old")).unwrap();
+        fs::write(log.dir.join("2000-01-01.jsonl"), old).unwrap();
+        fs::write(log.dir.join("notes.txt"), "ignored").unwrap();
+
+        log.append(&entry(None, "/new"), 30).unwrap();
+        assert!(!log.dir.join("2000-01-01.jsonl").exists());
+        assert!(log.dir.join("notes.txt").exists());
+        let paths: Vec<_> = log
+            .recent(10, |_| true)
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(paths, vec!["/new"]);
+    }
+
+    #[test]
+    fn only_whitelisted_args_are_recorded() {
+        let mut args = BTreeMap::new();
+        record_args(&mut args, "path", "/srv/a.txt");
+        record_args(&mut args, "password", "hunter2");
+        record_args(&mut args, "content", "secret file body");
+        assert_eq!(args.len(), 1);
+        assert_eq!(args["path"], "/srv/a.txt");
+    }
+}
@@ -5,29 +5,132 @@ use axum::{
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use base64::Engine;
 use hmac::{Hmac, KeyInit, Mac};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::AppState;
+use crate::ip_filter::ClientOrigin;
+use crate::notify::{Notifier, SecurityEvent};
+use crate::store::{LoginAttemptRecord, Role, Settings, Store, UserAccount};
+use crate::tls::ClientCertIdentity;
 
 type HmacSha256 = Hmac<Sha256>;
 
-/// トークン有効期限（秒）: 24時間
-const TOKEN_TTL_SECS: u64 = 24 * 60 * 60;
+/// 追跡するクライアント IP の上限（超えたら締め出し中でない最も古い記録から捨てる）
+const MAX_TRACKED_CLIENTS: usize = 10_000;
 
-/// レートリミット: ウィンドウ内の最大ログイン試行回数
-const MAX_LOGIN_ATTEMPTS: usize = 5;
-/// レートリミット: スライディングウィンドウ（秒）
-const RATE_LIMIT_WINDOW_SECS: u64 = 60;
+/// レート制限のキー。IPv6 は /64 単位にまとめる（1 回線で /128 を使い捨てられるため）
+pub(crate) fn client_key(ip: Option<IpAddr>) -> Option<IpAddr> {
+    match ip? {
+        IpAddr::V6(v6) if v6.to_ipv4_mapped().is_none() => {
+            let masked = u128::from(v6) & !((1u128 << 64) - 1);
+            Some(IpAddr::V6(masked.into()))
+        }
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4),
+        v4 => Some(v4),
+    }
+}
+
+/// ログイン試行の制限値（Settings の login_* から取得）
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLimits {
+    /// ウィンドウ内でこの回数失敗すると締め出す
+    pub max_attempts: usize,
+    /// 失敗回数を数えるスライディングウィンドウ
+    pub window: Duration,
+    /// 締め出し時間
+    pub ban: Duration,
+}
+
+impl LoginLimits {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            max_attempts: usize::from(settings.login_max_attempts.max(1)),
+            window: Duration::from_secs(settings.login_window_secs.into()),
+            ban: Duration::from_secs(settings.login_ban_secs.into()),
+        }
+    }
+}
+
+impl Default for LoginLimits {
+    fn default() -> Self {
+        Self::from_settings(&Settings::default())
+    }
+}
+
+/// 1 クライアント IP の失敗記録（時刻は unix 秒。再起動後も復元できるよう壁時計で持つ）
+#[derive(Default)]
+struct ClientAttempts {
+    failures: VecDeque<u64>,
+    banned_until: Option<u64>,
+}
+
+impl ClientAttempts {
+    fn prune(&mut self, now: u64, window: Duration) {
+        while self
+            .failures
+            .front()
+            .is_some_and(|t| now.saturating_sub(*t) > window.as_secs())
+        {
+            self.failures.pop_front();
+        }
+        if self.banned_until.is_some_and(|t| now >= t) {
+            self.banned_until = None;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.failures.is_empty() && self.banned_until.is_none()
+    }
+}
 
-/// ログイン試行のグローバルレートリミッター（スライディングウィンドウ方式）
-/// 単一パスワード認証のため、IP 単位ではなくグローバルで制限する。
+/// `GET /api/auth/bans` の 1 エントリ
+#[derive(Debug, Serialize)]
+pub struct LoginClientStatus {
+    /// None = 送信元アドレス不明。IPv6 は /64 のネットワークアドレス
+    pub ip: Option<IpAddr>,
+    /// ウィンドウ内の失敗回数
+    pub recent_failures: usize,
+    /// 締め出し中なら残り秒数
+    pub banned_for_secs: Option<u64>,
+}
+
+/// ログイン試行のレートリミッター（送信元 IP ごとのスライディングウィンドウ方式）
+/// 失敗が上限に達した IP だけを一定時間締め出すため、攻撃者がオーナーを
+/// 締め出すことはできない。Web ログインと SSH パスワード認証で共有する。
 pub struct LoginRateLimiter {
-    attempts: Mutex<VecDeque<Instant>>,
+    clients: Mutex<HashMap<Option<IpAddr>, ClientAttempts>>,
+    /// 締め出しの保存先（None = メモリのみ）。再起動で締め出しが解けないようにする
+    persister: Option<Arc<BanPersister>>,
+    /// 保存要求の通し番号（古いスナップショットで上書きしないため）
+    generation: std::sync::atomic::AtomicU64,
+}
+
+/// 締め出し一覧を `login-attempts.json` に書く。書き込みは blocking スレッドで行い、
+/// 後から要求されたスナップショットだけを書く
+struct BanPersister {
+    store: Store,
+    /// 最後に書いた generation
+    written: Mutex<u64>,
+}
+
+impl BanPersister {
+    fn write(&self, generation: u64, records: &[LoginAttemptRecord]) {
+        let mut written = self.written.lock().expect("ban persister lock poisoned");
+        if generation <= *written {
+            return;
+        }
+        *written = generation;
+        if let Err(e) = self.store.save_login_attempts(records) {
+            tracing::warn!("Failed to save login attempts: {e}");
+        }
+    }
 }
 
 impl Default for LoginRateLimiter {
@@ -37,40 +140,437 @@ impl Default for LoginRateLimiter {
 }
 
 impl LoginRateLimiter {
+    /// メモリのみ（再起動で記録は消える）
     pub fn new() -> Self {
         Self {
-            attempts: Mutex::new(VecDeque::new()),
+            clients: Mutex::new(HashMap::new()),
+            persister: None,
+            generation: Default::default(),
         }
     }
 
-    /// レートリミット内であれば true を返す（記録はしない）
-    pub fn check(&self) -> bool {
-        let mut attempts = self.attempts.lock().expect("rate limiter lock poisoned");
-        let window = std::time::Duration::from_secs(RATE_LIMIT_WINDOW_SECS);
-        let now = Instant::now();
+    /// Store に保存された記録を復元し、以後の変更も保存する
+    pub fn persistent(store: Store) -> Self {
+        let clients = store
+            .load_login_attempts()
+            .into_iter()
+            .map(|r| {
+                let attempts = ClientAttempts {
+                    failures: r.failures.into(),
+                    banned_until: r.banned_until,
+                };
+                (r.ip, attempts)
+            })
+            .collect();
+        Self {
+            clients: Mutex::new(clients),
+            persister: Some(Arc::new(BanPersister {
+                store,
+                written: Mutex::new(0),
+            })),
+            generation: Default::default(),
+        }
+    }
 
-        // ウィンドウ外の古いエントリを削除
-        while let Some(front) = attempts.front() {
-            if now.duration_since(*front) > window {
-                attempts.pop_front();
-            } else {
-                break;
-            }
+    /// 締め出しが変わったときだけ呼ぶ。ファイル書き込みはランタイム上なら
+    /// blocking スレッドへ逃がす（失敗ログインのたびに async スレッドで書かない）
+    fn persist_bans(&self, clients: &HashMap<Option<IpAddr>, ClientAttempts>) {
+        let Some(ref persister) = self.persister else {
+            return;
+        };
+        let records: Vec<LoginAttemptRecord> = clients
+            .iter()
+            .filter(|(_, c)| c.banned_until.is_some())
+            .map(|(ip, c)| LoginAttemptRecord {
+                ip: *ip,
+                failures: Vec::new(),
+                banned_until: c.banned_until,
+            })
+            .collect();
+        let generation = self
+            .generation
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1;
+        let persister = Arc::clone(persister);
+        let write = move || persister.write(generation, &records);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(write)),
+            Err(_) => write(),
         }
+    }
+
+    /// 締め出し中でなければ true を返す（記録はしない）
+    pub fn check(&self, ip: Option<IpAddr>) -> bool {
+        let ip = client_key(ip);
+        let clients = self.clients.lock().expect("rate limiter lock poisoned");
+        let now = unix_now();
+        clients
+            .get(&ip)
+            .and_then(|c| c.banned_until)
+            .is_none_or(|until| now >= until)
+    }
+
+    /// 失敗した試行を記録し、上限に達したら締め出す。今回締め出したら true
+    pub fn record_failure(&self, ip: Option<IpAddr>, limits: LoginLimits) -> bool {
+        let ip = client_key(ip);
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        let now = unix_now();
+        clients.retain(|_, c| {
+            c.prune(now, limits.window);
+            !c.is_empty()
+        });
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&ip) {
+            evict_one(&mut clients);
+        }
+        let client = clients.entry(ip).or_default();
+        client.failures.push_back(now);
+        let locked_out = client.failures.len() >= limits.max_attempts;
+        if locked_out {
+            client.failures.clear();
+            client.banned_until = Some(now + limits.ban.as_secs());
+            tracing::warn!(
+                "Login locked out for {}s: {}",
+                limits.ban.as_secs(),
+                ip.map_or_else(|| "unknown client".to_string(), |ip| ip.to_string())
+            );
+            self.persist_bans(&clients);
+        }
+        locked_out
+    }
+
+    /// ログイン成功時に失敗記録を消す
+    pub fn record_success(&self, ip: Option<IpAddr>) {
+        let ip = client_key(ip);
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        // 締め出し中はログインできないので、消えるのは保存していない失敗記録だけ
+        clients.remove(&ip);
+    }
+
+    /// 失敗記録のあるクライアント（締め出し中を含む）
+    pub fn status(&self, limits: LoginLimits) -> Vec<LoginClientStatus> {
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        let now = unix_now();
+        let mut result: Vec<LoginClientStatus> = clients
+            .iter_mut()
+            .filter_map(|(ip, c)| {
+                c.prune(now, limits.window);
+                (!c.is_empty()).then(|| LoginClientStatus {
+                    ip: *ip,
+                    recent_failures: c.failures.len(),
+                    banned_for_secs: c.banned_until.map(|t| t - now),
+                })
+            })
+            .collect();
+        result.sort_by_key(|c| {
+            (
+                c.banned_for_secs.is_none(),
+                std::cmp::Reverse(c.recent_failures),
+            )
+        });
+        result
+    }
+}
 
-        attempts.len() < MAX_LOGIN_ATTEMPTS
+/// 追跡数が上限のとき 1 件捨てる。締め出し中の記録は最後まで残す
+/// （アドレスを使い捨てて自分の締め出しを押し出せないように）。締め出し中でない
+/// 記録があれば最後の失敗が最も古いもの、全て締め出し中なら最も早く解けるもの。
+fn evict_one(clients: &mut HashMap<Option<IpAddr>, ClientAttempts>) {
+    let victim = clients
+        .iter()
+        .filter(|(_, c)| c.banned_until.is_none())
+        .min_by_key(|(_, c)| c.failures.back().copied())
+        .or_else(|| clients.iter().min_by_key(|(_, c)| c.banned_until))
+        .map(|(ip, _)| *ip);
+    if let Some(ip) = victim {
+        clients.remove(&ip);
     }
+}
 
-    /// 失敗した試行を記録する
-    pub fn record_failure(&self) {
-        let mut attempts = self.attempts.lock().expect("rate limiter lock poisoned");
-        attempts.push_back(Instant::now());
+/// 失敗を記録し（上限は現在の設定値）、締め出したらセキュリティ通知を送る
+pub(crate) fn record_login_failure(
+    limiter: &LoginRateLimiter,
+    store: &Store,
+    notifier: &Notifier,
+    ip: Option<IpAddr>,
+) {
+    let limits = LoginLimits::from_settings(&store.load_settings());
+    if limiter.record_failure(ip, limits) {
+        notifier.notify(SecurityEvent::LoginLockout {
+            ip,
+            ban_secs: limits.ban.as_secs(),
+        });
     }
 }
 
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock before epoch")
+        .as_secs()
+}
+
+/// GET /api/auth/bans (admin only)
+pub async fn login_bans(State(state): State<Arc<AppState>>) -> Json<Vec<LoginClientStatus>> {
+    let limits = LoginLimits::from_settings(&state.store.load_settings());
+    Json(state.rate_limiter.status(limits))
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
+    /// 省略時は DEN_PASSWORD のオーナーとしてログイン
+    #[serde(default)]
+    pub username: Option<String>,
     pub password: String,
+    /// 端末一覧に表示する名前（省略時は User-Agent から作る）
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+/// 認証済みリクエストの主体。`auth_middleware` が request extensions に入れる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser {
+    /// None = DEN_PASSWORD のオーナー
+    pub username: Option<String>,
+    pub role: Role,
+    /// API トークン経由のときのスコープ（None = 対話ログイン、制限なし）
+    pub scopes: Option<Vec<String>>,
+    /// API トークン経由のときのトークン ID（監査ログ用）
+    pub token_id: Option<String>,
+    /// ログイン端末 ID（端末に紐づかないトークンは None）
+    pub device_id: Option<String>,
+}
+
+impl AuthUser {
+    pub fn owner() -> Self {
+        Self {
+            username: None,
+            role: Role::Admin,
+            scopes: None,
+            token_id: None,
+            device_id: None,
+        }
+    }
+
+    /// Logged in with a password (not an API token).
+    pub fn is_interactive(&self) -> bool {
+        self.scopes.is_none()
+    }
+
+    /// Terminal session namespace (None = the owner's root namespace).
+    /// Read-only accounts have no sessions of their own; they watch the owner's.
+    pub fn namespace(&self) -> Option<&str> {
+        if self.is_read_only() {
+            return None;
+        }
+        self.username.as_deref()
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// The DEN_PASSWORD owner (or one of the owner's tokens), not a user account.
+    pub fn is_owner(&self) -> bool {
+        self.username.is_none()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.role == Role::ReadOnly
+    }
+
+    /// Whether this principal may call `method path`. Interactive logins may
+    /// call anything; API tokens only routes covered by their scopes.
+    ///
+    /// Read-only accounts are further limited to `*:read` requests, on top of
+    /// any token scopes, except for their own account (`SELF_SERVICE_ROUTES`).
+    pub fn allows(&self, method: &axum::http::Method, path: &str) -> bool {
+        let required = required_scope(method, path);
+        if self.is_read_only()
+            && !is_read_request(method, required.as_deref())
+            && !is_self_service(path)
+        {
+            return false;
+        }
+        let Some(ref scopes) = self.scopes else {
+            return true;
+        };
+        let Some(required) = required else {
+            // API 仕様はどのスコープのトークンでも読める
+            return matches!(*method, axum::http::Method::GET | axum::http::Method::HEAD)
+                && TOKEN_OPEN_ROUTES.contains(&path);
+        };
+        scopes.iter().any(|s| scope_matches(s, &required))
+    }
+}
+
+/// API トークンの平文プレフィックス（`den_` + 64 hex）
+pub const API_TOKEN_PREFIX: &str = "den_";
+
+/// API トークンで操作できる領域（パス prefix → 領域, 固定アクション）。
+/// ここにないルート（トークン・ユーザー管理、自己更新、Quick Connect）は
+/// API トークンでは呼べない。
+const SCOPE_AREAS: &[(&str, &str, Option<&str>)] = &[
+    // POST だが読み取りのみのジョブ（監視・容量集計・重複検出）
+    ("/api/filer/watch", "filer", Some("read")),
+    ("/api/filer/du", "filer", Some("read")),
+    ("/api/filer/dedupe-scan", "filer", Some("read")),
+    ("/api/filer/preview-session", "filer", Some("read")),
+    ("/api/filer/sign-url", "filer", Some("read")),
+    ("/api/sftp/sign-url", "sftp", Some("read")),
+    ("/api/filer/", "filer", None),
+    ("/api/diff", "filer", Some("read")),
+    ("/api/sftp/", "sftp", None),
+    ("/api/transfer", "sftp", None),
+    ("/api/sync-jobs", "sftp", None),
+    ("/api/ws", "terminal", Some("attach")),
+    ("/api/terminal/", "terminal", None),
+    ("/api/multiplexer/", "terminal", None),
+    ("/api/settings", "settings", None),
+    ("/api/keep-awake", "settings", None),
+    ("/api/clipboard-history", "clipboard", None),
+    ("/api/events", "events", Some("read")),
+    ("/metrics", "metrics", Some("read")),
+];
+
+/// 自分のアカウントだけを操作するルート（パスワード変更・自分の端末の管理）。
+/// 読み取り専用ロールでも呼べる。API トークンからは従来どおり呼べない。
+const SELF_SERVICE_ROUTES: &[&str] = &["/api/auth/change-password", "/api/devices"];
+
+/// スコープに関係なく、どの API トークンでも GET できるルート
+const TOKEN_OPEN_ROUTES: &[&str] = &["/api/openapi.json"];
+
+fn is_self_service(path: &str) -> bool {
+    SELF_SERVICE_ROUTES
+        .iter()
+        .any(|route| path == *route || path.starts_with(&format!("{route}/")))
+}
+
+/// スコープの領域名一覧（トークン発行時の検証用）
+pub fn scope_areas() -> impl Iterator<Item = &'static str> {
+    let mut areas: Vec<&str> = SCOPE_AREAS.iter().map(|(_, area, _)| *area).collect();
+    areas.dedup();
+    areas.into_iter()
+}
+
+/// `area:action` required for a request: GET/HEAD is `read`, anything else
+/// `write`, unless the area fixes the action (terminal attach, diff).
+fn required_scope(method: &axum::http::Method, path: &str) -> Option<String> {
+    let (_, area, action) = SCOPE_AREAS
+        .iter()
+        .find(|(prefix, _, _)| path.starts_with(prefix))?;
+    let action = action.unwrap_or(
+        if matches!(*method, axum::http::Method::GET | axum::http::Method::HEAD) {
+            "read"
+        } else {
+            "write"
+        },
+    );
+    Some(format!("{area}:{action}"))
+}
+
+/// GET/HEAD outside the scoped areas (e.g. `/api/auth/me`), or a request
+/// whose required scope is `area:read`. Terminal attach is allowed; the
+/// WebSocket handler downgrades read-only users to observers.
+fn is_read_request(method: &axum::http::Method, required: Option<&str>) -> bool {
+    match required {
+        Some(scope) => scope.ends_with(":read") || scope == "terminal:attach",
+        None => matches!(*method, axum::http::Method::GET | axum::http::Method::HEAD),
+    }
+}
+
+/// `*` matches everything, `area:*` any action in the area.
+fn scope_matches(granted: &str, required: &str) -> bool {
+    if granted == "*" || granted == required {
+        return true;
+    }
+    match (granted.split_once(':'), required.split_once(':')) {
+        (Some((area, "*")), Some((required_area, _))) => area == required_area,
+        _ => false,
+    }
+}
+
+/// Whether `scope` is something a token may be issued with.
+pub fn is_valid_scope(scope: &str) -> bool {
+    if scope == "*" {
+        return true;
+    }
+    let Some((area, action)) = scope.split_once(':') else {
+        return false;
+    };
+    scope_areas().any(|a| a == area) && matches!(action, "read" | "write" | "attach" | "*")
+}
+
+/// sha256 hex of an API token (only the hash is stored)
+pub fn hash_api_token(token: &str) -> String {
+    use sha2::Digest;
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[derive(Serialize)]
+pub struct MeResponse {
+    pub username: Option<String>,
+    pub role: Role,
+}
+
+/// argon2id でパスワードをハッシュ化（PHC 文字列）
+pub fn hash_password(password: &str) -> String {
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).expect("16-byte salt");
+    argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 hash")
+        .to_string()
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return false;
+    };
+    argon2::Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok()
+}
+
+/// オーナー（DEN_PASSWORD / DEN_PASSWORD_HASH）の認証情報
+#[derive(Clone)]
+pub enum OwnerCredential {
+    Plain(String),
+    /// argon2id PHC 文字列（`den hash-password` で生成）
+    Hash(String),
+}
+
+impl OwnerCredential {
+    /// argon2 の検証は数十 ms かかるため、async からは blocking スレッドで呼ぶこと
+    pub fn verify(&self, password: &str) -> bool {
+        match self {
+            Self::Plain(expected) => constant_time_eq(password, expected),
+            Self::Hash(hash) => verify_password(password, hash),
+        }
+    }
+
+    /// トークン署名・ブックマーク暗号鍵の導出に使う秘密（ハッシュ設定時はハッシュ自体）
+    pub fn secret(&self) -> &str {
+        match self {
+            Self::Plain(s) | Self::Hash(s) => s,
+        }
+    }
+
+    /// 平文パスワード（ハッシュのみ設定時は None）
+    pub fn plaintext(&self) -> Option<&str> {
+        match self {
+            Self::Plain(s) => Some(s),
+            Self::Hash(_) => None,
+        }
+    }
+}
+
+/// 現在のオーナー認証情報。`POST /api/auth/change-password` で保存したハッシュが
+/// あれば環境変数（`configured`）より優先する
+pub fn current_owner_credential(configured: &OwnerCredential, store: &Store) -> OwnerCredential {
+    store
+        .load_owner_password_hash()
+        .map_or_else(|| configured.clone(), OwnerCredential::Hash)
 }
 
 #[derive(Serialize)]
@@ -96,7 +596,7 @@ pub fn generate_token_at(password: &str, secret: &[u8], issued_at: u64) -> Strin
 }
 
 /// トークンを検証（HMAC チェック + 有効期限チェック）
-pub fn validate_token(token: &str, password: &str, secret: &[u8]) -> bool {
+pub fn validate_token(token: &str, password: &str, secret: &[u8], ttl_secs: u64) -> bool {
     let Some((timestamp_hex, sig)) = token.split_once('.') else {
         return false;
     };
@@ -106,12 +606,7 @@ pub fn validate_token(token: &str, password: &str, secret: &[u8]) -> bool {
     };
 
     // 有効期限チェック
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("system clock before epoch")
-        .as_secs();
-
-    if now.saturating_sub(issued_at) > TOKEN_TTL_SECS {
+    if token_age_secs(issued_at) > ttl_secs {
         return false;
     }
 
@@ -120,6 +615,138 @@ pub fn validate_token(token: &str, password: &str, secret: &[u8]) -> bool {
     constant_time_eq(sig, &expected)
 }
 
+fn token_age_secs(issued_at: u64) -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock before epoch")
+        .as_secs()
+        .saturating_sub(issued_at)
+}
+
+/// オーナー/ユーザートークンの発行時刻（署名は検証しない）
+fn token_issued_at(token: &str) -> Option<u64> {
+    let token = token.split_once('/').map_or(token, |(_, rest)| rest);
+    let token = token.split_once(':').map_or(token, |(_, rest)| rest);
+    let (timestamp_hex, _) = token.split_once('.')?;
+    u64::from_str_radix(timestamp_hex, 16).ok()
+}
+
+/// ユーザートークン: `"{username}:{issued_at_hex}.{hmac_hex}"`。
+/// 署名鍵にパスワードハッシュを含めるため、パスワード変更で既存トークンは無効になる。
+pub fn generate_user_token(user: &UserAccount, secret: &[u8]) -> String {
+    format!(
+        "{}:{}",
+        user.username,
+        generate_token(&user_token_key(user), secret)
+    )
+}
+
+fn user_token_key(user: &UserAccount) -> String {
+    format!("user:{}:{}", user.username, user.password_hash)
+}
+
+/// アカウントのトークン署名鍵（`user` が None ならオーナー）。
+/// パスワードが変わると鍵も変わり、それで署名したものは無効になる
+pub(crate) fn account_token_key(state: &AppState, user: Option<&UserAccount>) -> String {
+    match user {
+        None => state.owner_credential().secret().to_string(),
+        Some(user) => user_token_key(user),
+    }
+}
+
+/// 端末に紐づくトークンの署名鍵。端末 ID を含めるので別の端末 ID に付け替えられない
+fn device_token_key(key: &str, device_id: &str) -> String {
+    format!("{key}\ndevice:{device_id}")
+}
+
+/// ログイントークンを発行する（`user` が None ならオーナー）。
+/// 端末付きは `"{device_id}/{owner or user token}"` で、端末を消すと無効になる。
+pub(crate) fn issue_session_token(
+    state: &AppState,
+    user: Option<&UserAccount>,
+    device_id: Option<&str>,
+) -> String {
+    let key = account_token_key(state, user);
+    let key = match device_id {
+        Some(id) => device_token_key(&key, id),
+        None => key,
+    };
+    let token = generate_token(&key, &state.hmac_secret());
+    let token = match user {
+        Some(user) => format!("{}:{token}", user.username),
+        None => token,
+    };
+    match device_id {
+        Some(id) => format!("{id}/{token}"),
+        None => token,
+    }
+}
+
+/// トークンから認証主体を解決する（オーナー・登録ユーザー・API トークン）
+pub(crate) fn authenticate(state: &AppState, token: &str) -> Option<AuthUser> {
+    if let Some(secret) = token.strip_prefix(API_TOKEN_PREFIX)
+        && secret.len() == 64
+        && secret.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return authenticate_api_token(state, token);
+    }
+    let (device, token) = match token.split_once('/') {
+        Some((id, rest)) => (Some(state.store.get_device(id)?), rest),
+        None => (None, token),
+    };
+    let (user, signed) = match token.split_once(':') {
+        None => (None, token),
+        Some((username, rest)) => (Some(state.store.get_user(username)?), rest),
+    };
+    let key = account_token_key(state, user.as_ref());
+    let username = user.as_ref().map(|u| u.username.clone());
+    let key = match device {
+        // 端末は発行したアカウントのものに限る
+        Some(ref device) if device.user != username => return None,
+        Some(ref device) => device_token_key(&key, &device.id),
+        None => key,
+    };
+    if !validate_token(
+        signed,
+        &key,
+        &state.hmac_secret(),
+        state.config.token_ttl_secs(),
+    ) {
+        return None;
+    }
+    Some(AuthUser {
+        username,
+        role: user.map_or(Role::Admin, |u| u.role),
+        scopes: None,
+        token_id: None,
+        device_id: device.map(|d| d.id),
+    })
+}
+
+fn authenticate_api_token(state: &AppState, token: &str) -> Option<AuthUser> {
+    let hash = hash_api_token(token);
+    let api_token = state
+        .store
+        .load_api_tokens()
+        .into_iter()
+        .find(|t| constant_time_eq(&t.token_hash, &hash))?;
+    if api_token.is_expired() {
+        return None;
+    }
+    // 発行ユーザーの現在の権限で動く（削除済みユーザーのトークンは無効）
+    let role = match api_token.username {
+        Some(ref username) => state.store.get_user(username)?.role,
+        None => Role::Admin,
+    };
+    Some(AuthUser {
+        username: api_token.username,
+        role,
+        scopes: Some(api_token.scopes),
+        token_id: Some(api_token.id),
+        device_id: None,
+    })
+}
+
 fn compute_hmac(password: &str, secret: &[u8], issued_at: u64) -> String {
     let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
     mac.update(password.as_bytes());
@@ -139,60 +766,295 @@ pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
 }
 
 /// Cookie name for the auth token (HttpOnly)
-const TOKEN_COOKIE: &str = "den_token";
+pub(crate) const TOKEN_COOKIE: &str = "den_token";
 /// Cookie name for the login flag (readable by JS for isLoggedIn check)
 const LOGGED_IN_COOKIE: &str = "den_logged_in";
+/// Cookie name for the login device id (HttpOnly)
+pub(crate) const DEVICE_COOKIE: &str = "den_device";
 
 /// ログイン API
 /// トークンは HttpOnly Cookie で設定。レスポンスボディは `{"ok": true}` のみ。
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    origin: Option<axum::Extension<ClientOrigin>>,
+    req_headers: HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> Result<Response, StatusCode> {
-    if !state.rate_limiter.check() {
-        tracing::warn!("Login rate limited");
+    let origin = origin.map(|axum::Extension(o)| o).unwrap_or_default();
+    let client_ip = origin.ip;
+    if !state.rate_limiter.check(client_ip) {
+        tracing::warn!("Login rate limited: {client_ip:?}");
         return Err(StatusCode::TOO_MANY_REQUESTS);
     }
 
-    if req.password == state.config.password {
-        let token = generate_token(&state.config.password, &state.hmac_secret);
-        tracing::info!("Login successful");
+    // Some(None) = オーナー、Some(Some(user)) = 登録ユーザー
+    let account = match req.username.as_deref().filter(|u| !u.is_empty()) {
+        None => {
+            let owner = state.owner_credential();
+            let password = req.password.clone();
+            let vault_state = state.clone();
+            let verified = tokio::task::spawn_blocking(move || {
+                let verified = owner.verify(&password);
+                // The plaintext password is at hand: open the secrets vault
+                if verified {
+                    vault_state.unlock_vault(&password);
+                }
+                verified
+            })
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            verified.then_some(None)
+        }
+        Some(username) => {
+            // argon2 の検証は数十 ms かかるため blocking スレッドで行う
+            let user = state.store.get_user(username);
+            let password = req.password.clone();
+            let verified = tokio::task::spawn_blocking(move || {
+                user.filter(|u| verify_password(&password, &u.password_hash))
+            })
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            verified.map(Some)
+        }
+    };
 
-        let mut headers = HeaderMap::new();
-        let secure_attr = cookie_secure_attr(state.config.tls_enabled);
-        // HttpOnly Cookie: JS からアクセス不可（XSS 対策）
-        let token_cookie = format!(
-            "{}={}; HttpOnly; SameSite=Strict; Path=/; Max-Age={}{}",
-            TOKEN_COOKIE, token, TOKEN_TTL_SECS, secure_attr
-        );
-        headers.insert(
-            header::SET_COOKIE,
-            HeaderValue::from_str(&token_cookie).expect("valid cookie value"),
-        );
-        // Flag Cookie: JS から isLoggedIn() チェック用（トークン値は含まない）
-        let flag_cookie = format!(
-            "{}=1; SameSite=Strict; Path=/; Max-Age={}{}",
-            LOGGED_IN_COOKIE, TOKEN_TTL_SECS, secure_attr
-        );
-        headers.append(
-            header::SET_COOKIE,
-            HeaderValue::from_str(&flag_cookie).expect("valid cookie value"),
+    if let Some(account) = account {
+        match req.username.as_deref().filter(|u| !u.is_empty()) {
+            Some(username) => tracing::info!("Login successful: {username}"),
+            None => tracing::info!("Login successful"),
+        }
+        state.rate_limiter.record_success(client_ip);
+        crate::notify::record_login(
+            &state.store,
+            &state.notifier,
+            req.username.as_deref().filter(|u| !u.is_empty()),
+            client_ip,
+            "password",
         );
 
+        let headers = start_session(
+            &state,
+            account.as_ref(),
+            &req_headers,
+            &origin,
+            req.device_name.as_deref(),
+        );
         Ok((headers, Json(LoginSuccess { ok: true })).into_response())
     } else {
-        state.rate_limiter.record_failure();
-        tracing::warn!("Login failed: incorrect password");
+        record_login_failure(
+            &state.rate_limiter,
+            &state.store,
+            &state.notifier,
+            client_ip,
+        );
+        tracing::warn!("Login failed: incorrect credentials ({client_ip:?})");
         Err(StatusCode::UNAUTHORIZED)
     }
 }
 
+/// POST /api/auth/cert — mTLS のクライアント証明書でログインする（パスワード不要）。
+/// CN が DEN_TLS_CLIENT_OWNER_CN ならオーナー、登録ユーザー名と一致すればそのユーザー。
+pub async fn cert_login(
+    State(state): State<Arc<AppState>>,
+    origin: Option<axum::Extension<ClientOrigin>>,
+    cert: Option<axum::Extension<ClientCertIdentity>>,
+    req_headers: HeaderMap,
+) -> Response {
+    if state.config.tls_client_ca.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(axum::Extension(cert)) = cert else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let Some(account) = cert_account(&state, &cert) else {
+        tracing::warn!(
+            "Client certificate is not linked to an account: CN={:?} {}",
+            cert.common_name,
+            cert.fingerprint
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            "no den account is linked to this certificate",
+        )
+            .into_response();
+    };
+
+    let username = account.as_ref().map(|u| u.username.as_str());
+    match username {
+        Some(username) => tracing::info!("Login successful (client certificate): {username}"),
+        None => tracing::info!("Login successful (client certificate)"),
+    }
+    let origin = origin.map(|axum::Extension(o)| o).unwrap_or_default();
+    crate::notify::record_login(
+        &state.store,
+        &state.notifier,
+        username,
+        origin.ip,
+        "client-cert",
+    );
+    let headers = start_session(&state, account.as_ref(), &req_headers, &origin, None);
+    (headers, Json(LoginSuccess { ok: true })).into_response()
+}
+
+/// Some(None) = オーナー、Some(Some(user)) = 登録ユーザー、None = 対応するアカウントなし
+fn cert_account(state: &AppState, cert: &ClientCertIdentity) -> Option<Option<UserAccount>> {
+    let common_name = cert.common_name.as_deref()?;
+    if state.config.tls_client_owner_cn.as_deref() == Some(common_name) {
+        return Some(None);
+    }
+    state.store.get_user(common_name).map(Some)
+}
+
+/// GET /api/auth/me
+pub async fn me(axum::Extension(user): axum::Extension<AuthUser>) -> Json<MeResponse> {
+    Json(MeResponse {
+        username: user.username,
+        role: user.role,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+enum ChangePasswordError {
+    WrongPassword,
+    Io(std::io::Error),
+}
+
+/// POST /api/auth/change-password
+///
+/// 現在のパスワードを確認してハッシュを更新し、HMAC シークレットを作り直して
+/// 全員の既存ログインを無効にする。呼び出し元には新しいセッション Cookie を返す。
+/// 誤ったパスワードはログイン失敗と同じくレート制限に数える。
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<AuthUser>,
+    origin: Option<axum::Extension<ClientOrigin>>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Response {
+    if let Err(msg) = crate::users_api::validate_password(&req.new_password) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+    }
+    let origin = origin.map(|axum::Extension(o)| o).unwrap_or_default();
+    let client_ip = origin.ip;
+    if !state.rate_limiter.check(client_ip) {
+        tracing::warn!("Password change rate limited: {client_ip:?}");
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+
+    let task_state = Arc::clone(&state);
+    let username = user.username.clone();
+    let device_id = user.device_id.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        apply_password_change(&task_state, username.as_deref(), device_id.as_deref(), &req)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(token)) => {
+            match user.username {
+                Some(ref username) => tracing::info!("Password changed: {username}"),
+                None => tracing::info!("Owner password changed"),
+            }
+            state.rate_limiter.record_success(client_ip);
+            let headers = session_cookies(&state, secure_cookies(&state, &origin), &token);
+            (headers, Json(LoginSuccess { ok: true })).into_response()
+        }
+        Ok(Err(ChangePasswordError::WrongPassword)) => {
+            record_login_failure(
+                &state.rate_limiter,
+                &state.store,
+                &state.notifier,
+                client_ip,
+            );
+            tracing::warn!("Password change failed: incorrect password ({client_ip:?})");
+            (StatusCode::FORBIDDEN, "current password is incorrect").into_response()
+        }
+        Ok(Err(ChangePasswordError::Io(e))) => {
+            tracing::error!("Failed to save password: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(e) => {
+            tracing::error!("change_password task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Blocking（argon2）。成功時は呼び出し元の新しいログイントークンを返す。
+/// 呼び出し元の端末だけ残し、同じアカウントの他の端末は登録から外す。
+fn apply_password_change(
+    state: &AppState,
+    username: Option<&str>,
+    device_id: Option<&str>,
+    req: &ChangePasswordRequest,
+) -> Result<String, ChangePasswordError> {
+    let new_hash = hash_password(&req.new_password);
+    match username {
+        None => {
+            let owner = state.owner_credential();
+            if !owner.verify(&req.current_password) {
+                return Err(ChangePasswordError::WrongPassword);
+            }
+            state
+                .store
+                .save_owner_password_hash(&new_hash)
+                .map_err(ChangePasswordError::Io)?;
+            crate::store_api::reencrypt_den_bookmarks(&state.store, owner.secret(), &new_hash);
+            state.rewrap_vault_key(&req.current_password, &req.new_password);
+            state.rotate_hmac_secret();
+            forget_other_devices(state, None, device_id);
+            Ok(issue_session_token(state, None, device_id))
+        }
+        Some(username) => {
+            let account = state
+                .store
+                .update_users(|users| {
+                    let user = users.iter_mut().find(|u| u.username == username)?;
+                    if !verify_password(&req.current_password, &user.password_hash) {
+                        return None;
+                    }
+                    user.password_hash = new_hash;
+                    Some(user.clone())
+                })
+                .map_err(ChangePasswordError::Io)?
+                .ok_or(ChangePasswordError::WrongPassword)?;
+            // ユーザーのトークンはパスワードハッシュに紐付くので、HMAC の作り直しは不要
+            // （作り直すとオーナーや他のユーザーまでログアウトさせてしまう）
+            forget_other_devices(state, Some(username), device_id);
+            Ok(issue_session_token(state, Some(&account), device_id))
+        }
+    }
+}
+
+fn forget_other_devices(state: &AppState, user: Option<&str>, keep: Option<&str>) {
+    if let Err(e) = state.store.update_devices(|devices| {
+        devices.retain(|d| d.user.as_deref() != user || Some(d.id.as_str()) == keep)
+    }) {
+        tracing::warn!("Failed to update devices: {e}");
+    }
+}
+
 /// ログアウト API
 /// HttpOnly Cookie `den_token` と JS フラグ Cookie `den_logged_in` を削除する。
-/// 認証不要（無効クッキーの削除は無害）。
-pub async fn logout(State(state): State<Arc<AppState>>) -> Response {
+/// 認証不要（無効クッキーの削除は無害）。有効なトークンならその端末の登録も外す。
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    origin: Option<axum::Extension<ClientOrigin>>,
+    req_headers: HeaderMap,
+) -> Response {
+    if let Some(device_id) = request_token(&req_headers)
+        .and_then(|t| authenticate(&state, &t))
+        .and_then(|u| u.device_id)
+    {
+        crate::devices_api::remove_device(&state, &device_id);
+    }
+    let origin = origin.map(|axum::Extension(o)| o).unwrap_or_default();
     let mut headers = HeaderMap::new();
-    let secure_attr = cookie_secure_attr(state.config.tls_enabled);
+    let secure_attr = cookie_secure_attr(secure_cookies(&state, &origin));
     let token_cookie = format!(
         "{}=; HttpOnly; SameSite=Strict; Path=/; Max-Age=0{}",
         TOKEN_COOKIE, secure_attr
@@ -212,12 +1074,114 @@ pub async fn logout(State(state): State<Arc<AppState>>) -> Response {
     (StatusCode::NO_CONTENT, headers).into_response()
 }
 
-fn cookie_secure_attr(tls_enabled: bool) -> &'static str {
-    if tls_enabled { "; Secure" } else { "" }
+/// ログイン Cookie（`den_token` + `den_logged_in`）の Set-Cookie ヘッダー
+pub(crate) fn session_cookies(state: &AppState, secure: bool, token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let secure_attr = cookie_secure_attr(secure);
+    let max_age = state.config.token_ttl_secs();
+    // HttpOnly Cookie: JS からアクセス不可（XSS 対策）
+    let token_cookie = format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/; Max-Age={}{}",
+        TOKEN_COOKIE, token, max_age, secure_attr
+    );
+    headers.insert(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&token_cookie).expect("valid cookie value"),
+    );
+    // Flag Cookie: JS から isLoggedIn() チェック用（トークン値は含まない）
+    let flag_cookie = format!(
+        "{}=1; SameSite=Strict; Path=/; Max-Age={}{}",
+        LOGGED_IN_COOKIE, max_age, secure_attr
+    );
+    headers.append(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&flag_cookie).expect("valid cookie value"),
+    );
+    headers
+}
+
+/// ログイン成功時の共通処理: 端末を登録し、その端末に紐づくセッション Cookie を作る
+pub(crate) fn start_session(
+    state: &AppState,
+    account: Option<&UserAccount>,
+    req_headers: &HeaderMap,
+    origin: &ClientOrigin,
+    device_name: Option<&str>,
+) -> HeaderMap {
+    let secure = secure_cookies(state, origin);
+    let device = crate::devices_api::register_device(
+        state,
+        account.map(|u| u.username.as_str()),
+        req_headers,
+        origin.ip,
+        device_name,
+    );
+    let device_id = device.as_ref().map(|d| d.id.as_str());
+    let token = issue_session_token(state, account, device_id);
+    let mut headers = session_cookies(state, secure, &token);
+    if let Some(id) = device_id {
+        headers.append(header::SET_COOKIE, device_cookie(id, secure));
+    }
+    headers
+}
+
+/// 端末 ID Cookie。次回ログイン時に同じ端末として登録し直すためのもので、認証には使わない。
+/// OIDC のコールバック（他サイトからの遷移）でも送られるよう SameSite=Lax。
+fn device_cookie(device_id: &str, secure: bool) -> HeaderValue {
+    let cookie = format!(
+        "{}={}; HttpOnly; SameSite=Lax; Path=/api; Max-Age={}{}",
+        DEVICE_COOKIE,
+        device_id,
+        365 * 24 * 60 * 60,
+        cookie_secure_attr(secure)
+    );
+    HeaderValue::from_str(&cookie).expect("valid cookie value")
+}
+
+/// スライディング更新: Cookie ログインのトークンが有効期限の半分を過ぎていれば
+/// 新しいトークンを発行する。Bearer クライアントは自分でトークンを管理するので対象外。
+fn refreshed_session_cookies(
+    state: &AppState,
+    req: &Request<axum::body::Body>,
+    user: &AuthUser,
+) -> Option<HeaderMap> {
+    let headers = req.headers();
+    if !user.is_interactive() || bearer_token(headers).is_some() {
+        return None;
+    }
+    let token = extract_cookie(headers, TOKEN_COOKIE)?;
+    if token_age_secs(token_issued_at(&token)?) < state.config.token_ttl_secs() / 2 {
+        return None;
+    }
+    let account = match user.username {
+        None => None,
+        Some(ref username) => Some(state.store.get_user(username)?),
+    };
+    let token = issue_session_token(state, account.as_ref(), user.device_id.as_deref());
+    let origin = req
+        .extensions()
+        .get::<ClientOrigin>()
+        .copied()
+        .unwrap_or_default();
+    Some(session_cookies(
+        state,
+        secure_cookies(state, &origin),
+        &token,
+    ))
+}
+
+/// Cookie に Secure を付けるか: den 自身が TLS 終端するか、信頼済みプロキシが
+/// HTTPS で受けたと伝えている場合
+pub(crate) fn secure_cookies(state: &AppState, origin: &ClientOrigin) -> bool {
+    state.config.tls_enabled || origin.forwarded_https
+}
+
+fn cookie_secure_attr(secure: bool) -> &'static str {
+    if secure { "; Secure" } else { "" }
 }
 
 /// Cookie ヘッダーから指定名の値を抽出
-fn extract_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+pub(crate) fn extract_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
     headers
         .get(header::COOKIE)
         .and_then(|v| v.to_str().ok())
@@ -231,32 +1195,75 @@ fn extract_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
         })
 }
 
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|s| s.to_string())
+}
+
+/// Authorization: Bearer ヘッダー（優先）または den_token Cookie のトークン
+pub(crate) fn request_token(headers: &HeaderMap) -> Option<String> {
+    bearer_token(headers).or_else(|| extract_cookie(headers, TOKEN_COOKIE))
+}
+
+/// CSRF 対策ヘッダー。Cookie 認証の変更系リクエストに必須（フロントエンドの fetch ラッパーが付与）。
+/// 他オリジンのページは CORS プリフライトなしにカスタムヘッダーを付けられない。
+pub const CSRF_HEADER: &str = "x-den-request";
+
+/// Cookie 認証の POST/PUT/DELETE 等で CSRF ヘッダーが無いもの。Bearer は対象外
+/// （攻撃ページがブラウザに付けさせられない）。
+fn is_csrf_suspect(req: &Request<axum::body::Body>) -> bool {
+    !matches!(
+        *req.method(),
+        axum::http::Method::GET | axum::http::Method::HEAD | axum::http::Method::OPTIONS
+    ) && bearer_token(req.headers()).is_none()
+        && !req.headers().contains_key(CSRF_HEADER)
+}
+
 /// トークン認証ミドルウェア
 /// 認証ソース（優先順）:
 /// 1. Authorization: Bearer <token> ヘッダー（API クライアント・テスト用）
-/// 2. den_token Cookie（ブラウザ用、HttpOnly）
+/// 2. den_token Cookie（ブラウザ用、HttpOnly）。変更系メソッドは `CSRF_HEADER` も必須
+///
+/// 認証に成功すると `AuthUser` を request extensions に入れる。
+/// Cookie ログインは利用中に有効期限が延長される（`refreshed_session_cookies`）。
 pub async fn auth_middleware(
     State(state): State<Arc<AppState>>,
-    req: Request<axum::body::Body>,
+    mut req: Request<axum::body::Body>,
     next: Next,
 ) -> Response {
     let path = req.uri().path().to_string();
+    // 署名付き URL（?expires=..&sig=..）はトークンがないか無効なときだけ見る
+    let user = request_token(req.headers())
+        .and_then(|t| authenticate(&state, &t))
+        .or_else(|| crate::signed_url::authenticate(&state, &req));
 
-    // Authorization ヘッダーからトークンを取得（優先）
-    let token = req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-        .map(|s| s.to_string())
-        // フォールバック: Cookie からトークンを取得
-        .or_else(|| extract_cookie(req.headers(), TOKEN_COOKIE));
-
-    match token {
-        Some(t) if validate_token(&t, &state.config.password, &state.hmac_secret) => {
-            next.run(req).await
+    match user {
+        Some(user) if !user.allows(req.method(), &path) => {
+            tracing::debug!("Scope/role rejected: {} {path}", req.method());
+            StatusCode::FORBIDDEN.into_response()
         }
-        _ => {
+        Some(_) if is_csrf_suspect(&req) => {
+            tracing::warn!("CSRF header missing: {} {path}", req.method());
+            StatusCode::FORBIDDEN.into_response()
+        }
+        Some(user) => {
+            if let Some(ref device_id) = user.device_id {
+                let ip = req.extensions().get::<ClientOrigin>().and_then(|o| o.ip);
+                crate::devices_api::touch_device(&state, device_id, ip);
+            }
+            let refreshed = refreshed_session_cookies(&state, &req, &user);
+            req.extensions_mut().insert(user);
+            let mut resp = next.run(req).await;
+            for cookie in refreshed.iter().flat_map(|h| h.get_all(header::SET_COOKIE)) {
+                resp.headers_mut()
+                    .append(header::SET_COOKIE, cookie.clone());
+            }
+            resp
+        }
+        None => {
             tracing::debug!("Auth rejected: {path}");
             StatusCode::UNAUTHORIZED.into_response()
         }
@@ -265,25 +1272,26 @@ pub async fn auth_middleware(
 
 /// User-only auth middleware.
 /// Applied to /api/remote/* so that only interactive browser sessions
-/// can proxy through Quick Connect — future non-interactive tokens
-/// (API keys, etc.) should not be able to use these routes.
+/// can proxy through Quick Connect — API tokens and read-only accounts are
+/// rejected here.
 pub async fn user_auth_middleware(
     State(state): State<Arc<AppState>>,
-    req: Request<axum::body::Body>,
+    mut req: Request<axum::body::Body>,
     next: Next,
 ) -> Response {
     let path = req.uri().path().to_string();
 
-    let token = req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-        .map(|s| s.to_string())
-        .or_else(|| extract_cookie(req.headers(), TOKEN_COOKIE));
-
-    match token {
-        Some(t) if validate_token(&t, &state.config.password, &state.hmac_secret) => {
+    match request_token(req.headers()).and_then(|t| authenticate(&state, &t)) {
+        Some(_) if is_csrf_suspect(&req) => {
+            tracing::warn!("CSRF header missing: {} {path}", req.method());
+            StatusCode::FORBIDDEN.into_response()
+        }
+        Some(user) if user.is_read_only() => {
+            tracing::debug!("Read-only user rejected: {path}");
+            StatusCode::FORBIDDEN.into_response()
+        }
+        Some(user) if user.is_interactive() => {
+            req.extensions_mut().insert(user);
             next.run(req).await
         }
         _ => {
@@ -293,45 +1301,177 @@ pub async fn user_auth_middleware(
     }
 }
 
+/// Admin-only routes (user management, self-update). Must be layered inside
+/// `auth_middleware` so the `AuthUser` extension is present.
+pub async fn admin_middleware(req: Request<axum::body::Body>, next: Next) -> Response {
+    match req.extensions().get::<AuthUser>() {
+        Some(user) if user.is_admin() => next.run(req).await,
+        Some(_) => {
+            tracing::debug!("Admin route rejected: {}", req.uri().path());
+            StatusCode::FORBIDDEN.into_response()
+        }
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Owner-only routes (SFTP, clipboard history): the server has a single SFTP
+/// connection and a single clipboard, so user accounts must not share them.
+/// Must be layered inside `auth_middleware` like `admin_middleware`.
+pub async fn owner_middleware(req: Request<axum::body::Body>, next: Next) -> Response {
+    match req.extensions().get::<AuthUser>() {
+        Some(user) if user.is_owner() => next.run(req).await,
+        Some(_) => {
+            tracing::debug!("Owner route rejected: {}", req.uri().path());
+            StatusCode::FORBIDDEN.into_response()
+        }
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// SPA シェルのインライン `<script>` に付ける nonce（`csp_middleware` がリクエストに載せる）
+#[derive(Clone, Debug)]
+pub struct CspNonce(pub String);
+
 /// Content-Security-Policy ミドルウェア
-/// script-src 'self' で外部スクリプト注入を防止し、XSS リスクを軽減する。
+/// /api/* には厳格な API 用ポリシー、それ以外（SPA シェル・静的ファイル）にはページ用ポリシーを付与する。
+/// ページ用ポリシーが `{nonce}` を含む場合はレスポンスごとに nonce を生成し、
+/// `CspNonce` としてハンドラ（`assets::serve_index`）に渡す。
+///
+/// ページ用ポリシーに frame-ancestors が無ければ DEN_FRAME_ANCESTORS を足す。
 ///
 /// ハンドラが独自に CSP を設定済みの場合（例: filer preview）は上書きしない。
-pub async fn csp_middleware(req: Request<axum::body::Body>, next: Next) -> Response {
+pub async fn csp_middleware(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let policies = &state.config.csp;
+    let is_api = req.uri().path().starts_with("/api/");
+    let nonce = (!is_api && policies.page.contains("{nonce}")).then(|| {
+        let nonce = base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 16]>());
+        req.extensions_mut().insert(CspNonce(nonce.clone()));
+        nonce
+    });
     let mut resp = next.run(req).await;
     if !resp.headers().contains_key(header::CONTENT_SECURITY_POLICY) {
-        resp.headers_mut().insert(
-            header::CONTENT_SECURITY_POLICY,
-            HeaderValue::from_static(
-                "default-src 'self'; script-src 'self' 'wasm-unsafe-eval'; style-src 'self' 'unsafe-inline'; connect-src 'self' ws: wss: https://cdn.jsdelivr.net; img-src 'self' data: blob:",
-            ),
-        );
+        let policy = if is_api {
+            &policies.api
+        } else {
+            &policies.page
+        };
+        let mut policy = policy.replace("{nonce}", nonce.as_deref().unwrap_or_default());
+        if let Some(ref ancestors) = state.config.security_headers.frame_ancestors
+            && !policy.contains("frame-ancestors")
+        {
+            policy = format!("{policy}; frame-ancestors {ancestors}");
+        }
+        if let Ok(value) = HeaderValue::from_str(&policy) {
+            resp.headers_mut()
+                .insert(header::CONTENT_SECURITY_POLICY, value);
+        }
     }
     resp
 }
 
+/// CSP 以外のセキュリティヘッダー（`config::SecurityHeaders`）を付ける。
+/// HSTS は HTTPS で受けたとき（TLS 終端または信頼済みプロキシ経由）だけ。
+/// ハンドラが設定済みのヘッダー（例: 共有リンクの no-referrer）は上書きしない。
+pub async fn security_headers_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let origin = req
+        .extensions()
+        .get::<ClientOrigin>()
+        .copied()
+        .unwrap_or_default();
+    let mut resp = next.run(req).await;
+    let config = &state.config.security_headers;
+    let https = secure_cookies(&state, &origin);
+    for (name, value) in [
+        (
+            header::STRICT_TRANSPORT_SECURITY,
+            config.hsts.as_ref().filter(|_| https),
+        ),
+        (
+            header::X_CONTENT_TYPE_OPTIONS,
+            config.content_type_options.as_ref(),
+        ),
+        (header::REFERRER_POLICY, config.referrer_policy.as_ref()),
+    ] {
+        if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok())
+            && !resp.headers().contains_key(&name)
+        {
+            resp.headers_mut().insert(name, value);
+        }
+    }
+    resp
+}
+
+/// CORS（`config::CorsConfig`）。プリフライトには認証前に応答する。
+/// 変更系リクエストに必要な `CSRF_HEADER` と Bearer トークンを許可する
+pub fn cors_layer(cors: &crate::config::CorsConfig) -> tower_http::cors::CorsLayer {
+    use axum::http::{HeaderName, Method};
+    use tower_http::cors::{AllowOrigin, CorsLayer};
+
+    let allow_origin = if cors.origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            cors.origins
+                .iter()
+                .filter_map(|o| HeaderValue::from_str(o).ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static(CSRF_HEADER),
+        ])
+        .expose_headers([header::CONTENT_DISPOSITION])
+        .allow_credentials(cors.credentials)
+        .max_age(Duration::from_secs(10 * 60))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     const TEST_SECRET: &[u8] = b"test-secret-key-for-unit-tests!!";
+    const TEST_TTL: u64 = 24 * 60 * 60;
 
     #[test]
     fn token_roundtrip() {
         let token = generate_token("password", TEST_SECRET);
-        assert!(validate_token(&token, "password", TEST_SECRET));
+        assert!(validate_token(&token, "password", TEST_SECRET, TEST_TTL));
     }
 
     #[test]
     fn token_wrong_password_fails() {
         let token = generate_token("password", TEST_SECRET);
-        assert!(!validate_token(&token, "wrong", TEST_SECRET));
+        assert!(!validate_token(&token, "wrong", TEST_SECRET, TEST_TTL));
     }
 
     #[test]
     fn token_wrong_secret_fails() {
         let token = generate_token("password", TEST_SECRET);
-        assert!(!validate_token(&token, "password", b"different-secret"));
+        assert!(!validate_token(
+            &token,
+            "password",
+            b"different-secret",
+            TEST_TTL
+        ));
     }
 
     #[test]
@@ -356,7 +1496,7 @@ mod tests {
             .as_secs()
             - 25 * 60 * 60;
         let token = generate_token_at("password", TEST_SECRET, old_time);
-        assert!(!validate_token(&token, "password", TEST_SECRET));
+        assert!(!validate_token(&token, "password", TEST_SECRET, TEST_TTL));
     }
 
     #[test]
@@ -368,7 +1508,31 @@ mod tests {
             .as_secs()
             - 23 * 60 * 60;
         let token = generate_token_at("password", TEST_SECRET, recent_time);
-        assert!(validate_token(&token, "password", TEST_SECRET));
+        assert!(validate_token(&token, "password", TEST_SECRET, TEST_TTL));
+    }
+
+    #[test]
+    fn token_ttl_is_configurable() {
+        let two_hours_ago = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - 2 * 60 * 60;
+        let token = generate_token_at("password", TEST_SECRET, two_hours_ago);
+        assert!(validate_token(&token, "password", TEST_SECRET, 3 * 60 * 60));
+        assert!(!validate_token(&token, "password", TEST_SECRET, 60 * 60));
+    }
+
+    #[test]
+    fn issued_at_of_owner_and_user_tokens() {
+        let owner = generate_token_at("password", TEST_SECRET, 0x1234);
+        assert_eq!(token_issued_at(&owner), Some(0x1234));
+        assert_eq!(token_issued_at(&format!("alice:{owner}")), Some(0x1234));
+        assert_eq!(
+            token_issued_at(&format!("0123456789abcdef/alice:{owner}")),
+            Some(0x1234)
+        );
+        assert_eq!(token_issued_at("garbage"), None);
     }
 
     #[test]
@@ -378,7 +1542,7 @@ mod tests {
         let last = token.pop().unwrap();
         let replacement = if last == '0' { '1' } else { '0' };
         token.push(replacement);
-        assert!(!validate_token(&token, "test", TEST_SECRET));
+        assert!(!validate_token(&token, "test", TEST_SECRET, TEST_TTL));
     }
 
     #[test]
@@ -387,14 +1551,151 @@ mod tests {
         let parts: Vec<&str> = token.split('.').collect();
         // タイムスタンプを改ざん
         let tampered = format!("ff{}.{}", parts[0], parts[1]);
-        assert!(!validate_token(&tampered, "test", TEST_SECRET));
+        assert!(!validate_token(&tampered, "test", TEST_SECRET, TEST_TTL));
     }
 
     #[test]
     fn token_invalid_format() {
-        assert!(!validate_token("not-a-token", "password", TEST_SECRET));
-        assert!(!validate_token("", "password", TEST_SECRET));
-        assert!(!validate_token("abc.def.ghi", "password", TEST_SECRET));
+        assert!(!validate_token(
+            "not-a-token",
+            "password",
+            TEST_SECRET,
+            TEST_TTL
+        ));
+        assert!(!validate_token("", "password", TEST_SECRET, TEST_TTL));
+        assert!(!validate_token(
+            "abc.def.ghi",
+            "password",
+            TEST_SECRET,
+            TEST_TTL
+        ));
+    }
+
+    #[test]
+    fn password_hash_roundtrip() {
+        let hash = hash_password("correct horse");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not-a-hash"));
+        // Salted: same password, different hash
+        assert_ne!(hash, hash_password("correct horse"));
+    }
+
+    #[test]
+    fn owner_credential_plain_and_hash() {
+        let plain = OwnerCredential::Plain("secret".into());
+        assert!(plain.verify("secret"));
+        assert!(!plain.verify("Secret"));
+        assert_eq!(plain.plaintext(), Some("secret"));
+
+        let hash = hash_password("secret");
+        let hashed = OwnerCredential::Hash(hash.clone());
+        assert!(hashed.verify("secret"));
+        assert!(!hashed.verify(&hash));
+        assert_eq!(hashed.secret(), hash);
+        assert!(hashed.plaintext().is_none());
+    }
+
+    #[test]
+    fn user_token_is_bound_to_password_hash() {
+        let user = UserAccount {
+            username: "alice".into(),
+            password_hash: "$argon2id$v=19$m=19456,t=2,p=1$aaaa$bbbb".into(),
+            role: Role::User,
+            created_at: String::new(),
+            oidc_subject: None,
+        };
+        let token = generate_user_token(&user, TEST_SECRET);
+        let (username, rest) = token.split_once(':').unwrap();
+        assert_eq!(username, "alice");
+        assert!(validate_token(
+            rest,
+            &user_token_key(&user),
+            TEST_SECRET,
+            TEST_TTL
+        ));
+
+        let rotated = UserAccount {
+            password_hash: "$argon2id$v=19$m=19456,t=2,p=1$cccc$dddd".into(),
+            ..user
+        };
+        assert!(!validate_token(
+            rest,
+            &user_token_key(&rotated),
+            TEST_SECRET,
+            TEST_TTL
+        ));
+    }
+
+    #[test]
+    fn api_token_scopes() {
+        use axum::http::Method;
+        let token_user = |scopes: &[&str]| AuthUser {
+            scopes: Some(scopes.iter().map(|s| s.to_string()).collect()),
+            ..AuthUser::owner()
+        };
+
+        let reader = token_user(&["filer:read"]);
+        assert!(reader.allows(&Method::GET, "/api/filer/list"));
+        assert!(!reader.allows(&Method::PUT, "/api/filer/write"));
+        assert!(reader.allows(&Method::POST, "/api/diff"));
+        assert!(!reader.allows(&Method::GET, "/api/sftp/list"));
+        // Routes outside any area are never reachable with a token
+        assert!(!reader.allows(&Method::GET, "/api/tokens"));
+
+        let terminal = token_user(&["terminal:*"]);
+        assert!(terminal.allows(&Method::GET, "/api/ws"));
+        assert!(terminal.allows(&Method::DELETE, "/api/terminal/sessions/x"));
+        assert!(!terminal.allows(&Method::GET, "/api/filer/list"));
+
+        let all = token_user(&["*"]);
+        assert!(all.allows(&Method::POST, "/api/sync-jobs"));
+        assert!(!all.allows(&Method::GET, "/api/users"));
+
+        // The API description is readable with any scope
+        assert!(reader.allows(&Method::GET, "/api/openapi.json"));
+        assert!(terminal.allows(&Method::GET, "/api/openapi.json"));
+        assert!(!terminal.allows(&Method::POST, "/api/openapi.json"));
+
+        assert!(AuthUser::owner().allows(&Method::GET, "/api/users"));
+    }
+
+    #[test]
+    fn read_only_role() {
+        use axum::http::Method;
+        let viewer = AuthUser {
+            username: Some("monitor".into()),
+            role: Role::ReadOnly,
+            ..AuthUser::owner()
+        };
+        assert!(viewer.allows(&Method::GET, "/api/terminal/sessions"));
+        assert!(viewer.allows(&Method::GET, "/api/filer/read"));
+        assert!(viewer.allows(&Method::GET, "/api/ws"));
+        assert!(viewer.allows(&Method::GET, "/api/auth/me"));
+        assert!(viewer.allows(&Method::POST, "/api/filer/du"));
+        assert!(viewer.allows(&Method::POST, "/api/diff"));
+        assert!(!viewer.allows(&Method::POST, "/api/terminal/sessions"));
+        assert!(!viewer.allows(&Method::POST, "/api/filer/mkdir"));
+        assert!(!viewer.allows(&Method::PUT, "/api/settings"));
+        assert!(!viewer.allows(&Method::POST, "/api/tokens"));
+        // Own account: password and devices
+        assert!(viewer.allows(&Method::POST, "/api/auth/change-password"));
+        assert!(viewer.allows(&Method::DELETE, "/api/devices/abc"));
+        assert!(!viewer.allows(&Method::POST, "/api/auth/bans"));
+        // Watches the owner's sessions
+        assert_eq!(viewer.namespace(), None);
+    }
+
+    #[test]
+    fn scope_validation() {
+        assert!(is_valid_scope("filer:read"));
+        assert!(is_valid_scope("terminal:attach"));
+        assert!(is_valid_scope("sftp:*"));
+        assert!(is_valid_scope("*"));
+        assert!(!is_valid_scope("filer"));
+        assert!(!is_valid_scope("filer:delete"));
+        assert!(!is_valid_scope("users:read"));
     }
 
     #[test]
@@ -436,12 +1737,14 @@ mod tests {
         assert_eq!(cookie_secure_attr(true), "; Secure");
     }
 
+    const CLIENT: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7)));
+
     #[test]
     fn rate_limiter_check_does_not_count() {
         let limiter = LoginRateLimiter::new();
         // check() を何度呼んでもカウントは増えない
         for _ in 0..10 {
-            assert!(limiter.check());
+            assert!(limiter.check(CLIENT));
         }
     }
 
@@ -450,9 +1753,127 @@ mod tests {
         let limiter = LoginRateLimiter::new();
         // 5回失敗を記録 → check() が false になる
         for _ in 0..5 {
-            assert!(limiter.check());
-            limiter.record_failure();
+            assert!(limiter.check(CLIENT));
+            limiter.record_failure(CLIENT, LoginLimits::default());
+        }
+        assert!(!limiter.check(CLIENT));
+        let status = limiter.status(LoginLimits::default());
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].ip, CLIENT);
+        assert!(status[0].banned_for_secs.is_some());
+    }
+
+    #[test]
+    fn rate_limiter_is_per_client() {
+        let limiter = LoginRateLimiter::new();
+        let limits = LoginLimits {
+            max_attempts: 2,
+            ..LoginLimits::default()
+        };
+        assert!(!limiter.record_failure(CLIENT, limits));
+        assert!(limiter.record_failure(CLIENT, limits));
+        assert!(!limiter.check(CLIENT));
+        // 他の IP（オーナー）は締め出されない
+        let owner = Some(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+        assert!(limiter.check(owner));
+
+        // 成功で失敗記録がリセットされる
+        limiter.record_failure(owner, limits);
+        limiter.record_success(owner);
+        limiter.record_failure(owner, limits);
+        assert!(limiter.check(owner));
+    }
+
+    #[test]
+    fn rate_limiter_state_survives_restart() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = Store::new(tmp.path().to_path_buf()).unwrap();
+        let limits = LoginLimits {
+            max_attempts: 3,
+            ..LoginLimits::default()
+        };
+        let limiter = LoginRateLimiter::persistent(store.clone());
+        limiter.record_failure(CLIENT, limits);
+        limiter.record_failure(CLIENT, limits);
+        limiter.record_failure(CLIENT, limits);
+        let other = Some(IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 2)));
+        limiter.record_failure(other, limits);
+        drop(limiter);
+
+        let restarted = LoginRateLimiter::persistent(store.clone());
+        assert!(!restarted.check(CLIENT));
+        // 保存するのは締め出しだけ（途中までの失敗回数は書かない）
+        let saved = store.load_login_attempts();
+        assert_eq!(saved.len(), 1);
+        assert!(saved[0].failures.is_empty());
+        restarted.record_failure(other, limits);
+        assert!(restarted.check(other));
+    }
+
+    #[test]
+    fn rate_limiter_keys_ipv6_by_prefix() {
+        let limiter = LoginRateLimiter::new();
+        let limits = LoginLimits {
+            max_attempts: 2,
+            ..LoginLimits::default()
+        };
+        let a: IpAddr = "2001:db8:1:2::1".parse().unwrap();
+        let b: IpAddr = "2001:db8:1:2:ffff::9".parse().unwrap();
+        limiter.record_failure(Some(a), limits);
+        assert!(limiter.record_failure(Some(b), limits));
+        // 同じ /64 の別アドレスも締め出される。別の /64 は影響なし
+        assert!(!limiter.check(Some("2001:db8:1:2::77".parse().unwrap())));
+        assert!(limiter.check(Some("2001:db8:1:3::1".parse().unwrap())));
+        // IPv4-mapped は IPv4 として扱う
+        assert_eq!(
+            client_key(Some("::ffff:203.0.113.7".parse().unwrap())),
+            CLIENT
+        );
+    }
+
+    #[test]
+    fn eviction_keeps_active_bans() {
+        let ip = |n: u8| Some(IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, n)));
+        let mut clients = HashMap::new();
+        clients.insert(
+            ip(1),
+            ClientAttempts {
+                failures: VecDeque::new(),
+                banned_until: Some(u64::MAX),
+            },
+        );
+        for (n, t) in [(2, 50), (3, 10)] {
+            clients.insert(
+                ip(n),
+                ClientAttempts {
+                    failures: VecDeque::from([t]),
+                    banned_until: None,
+                },
+            );
         }
-        assert!(!limiter.check());
+        evict_one(&mut clients);
+        // The oldest non-banned entry goes, not the ban (no failures left)
+        assert!(!clients.contains_key(&ip(3)));
+        evict_one(&mut clients);
+        assert!(!clients.contains_key(&ip(2)));
+        assert!(clients.contains_key(&ip(1)));
+    }
+
+    #[test]
+    fn rate_limiter_ban_expires() {
+        let limiter = LoginRateLimiter::new();
+        let limits = LoginLimits {
+            max_attempts: 1,
+            window: Duration::from_secs(60),
+            ban: Duration::ZERO,
+        };
+        limiter.record_failure(CLIENT, limits);
+        assert!(limiter.check(CLIENT));
+        assert!(
+            limiter
+                .status(limits)
+                .iter()
+                .all(|c| c.banned_for_secs.is_none())
+        );
     }
 }
@@ -0,0 +1,121 @@
+//! Startup reconciliation of persistent state left behind when a previous run
+//! crashed or was killed.
+//!
+//! Features that keep in-progress work on disk register a [`CleanupTask`] in
+//! [`TASKS`]. Every task runs once from `create_app`, before any request is
+//! served, so nothing it touches can be in use yet.
+
+use std::path::Path;
+
+use crate::store::Store;
+
+/// What a cleanup task may look at
+pub struct CleanupContext<'a> {
+    pub data_dir: &'a Path,
+    pub store: &'a Store,
+}
+
+pub struct CleanupTask {
+    /// Shown in the log
+    pub name: &'static str,
+    /// Returns how many orphaned items were removed or reset
+    pub run: fn(&CleanupContext) -> std::io::Result<usize>,
+}
+
+/// 永続化状態を追加したら、異常終了後の後始末をここに登録する
+pub const TASKS: &[CleanupTask] = &[
+    CleanupTask {
+        name: "upload part files",
+        run: crate::filer::upload::cleanup_orphaned_parts,
+    },
+    CleanupTask {
+        name: "trash items",
+        run: crate::filer::trash::cleanup_orphaned_items,
+    },
+];
+
+/// Run every registered task. A failing task is logged and the rest still run.
+pub fn run(ctx: &CleanupContext) -> usize {
+    run_tasks(TASKS, ctx)
+}
+
+fn run_tasks(tasks: &[CleanupTask], ctx: &CleanupContext) -> usize {
+    let mut total = 0;
+    for task in tasks {
+        match (task.run)(ctx) {
+            Ok(0) => {}
+            Ok(n) => {
+                tracing::info!(
+                    "Startup cleanup: {} removed {n} orphaned item(s)",
+                    task.name
+                );
+                total += n;
+            }
+            Err(e) => tracing::warn!("Startup cleanup: {} failed: {e}", task.name),
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failing_task_does_not_stop_the_others() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = Store::new(tmp.path().to_path_buf()).unwrap();
+        let ctx = CleanupContext {
+            data_dir: tmp.path(),
+            store: &store,
+        };
+        let tasks = [
+            CleanupTask {
+                name: "broken",
+                run: |_| Err(std::io::Error::other("boom")),
+            },
+            CleanupTask {
+                name: "works",
+                run: |_| Ok(2),
+            },
+        ];
+        assert_eq!(run_tasks(&tasks, &ctx), 2);
+    }
+
+    #[test]
+    fn removes_orphaned_upload_parts_and_trash_items() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = Store::new(tmp.path().join("data")).unwrap();
+        let data_dir = tmp.path().join("data");
+
+        // A part file journaled by a run that never finished it
+        let part = tmp.path().join(".big.iso.0123abcd.part");
+        std::fs::write(&part, b"partial").unwrap();
+        let unrelated = tmp.path().join("notes.txt");
+        std::fs::write(&unrelated, b"keep").unwrap();
+        store
+            .save_pending_uploads(&[part.clone(), unrelated.clone()])
+            .unwrap();
+
+        // Trash: one half-made item, one real item
+        let trash = data_dir.join("trash");
+        let empty_item = trash.join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(empty_item.join("files")).unwrap();
+        let real_item = trash.join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(real_item.join("files")).unwrap();
+        std::fs::write(real_item.join("files").join("a.txt"), b"a").unwrap();
+
+        let ctx = CleanupContext {
+            data_dir: &data_dir,
+            store: &store,
+        };
+        assert_eq!(run(&ctx), 2);
+        assert!(!part.exists());
+        assert!(unrelated.exists());
+        assert!(store.load_pending_uploads().is_empty());
+        assert!(!empty_item.exists());
+        assert!(real_item.exists());
+        // Nothing left to do on the next start
+        assert_eq!(run(&ctx), 0);
+    }
+}
@@ -2,6 +2,8 @@ use std::env;
 use std::fmt;
 use std::str::FromStr;
 
+use crate::auth::OwnerCredential;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Environment {
     Development,
@@ -29,25 +31,235 @@ impl fmt::Display for Environment {
     }
 }
 
+/// 管理用エンドポイントの専用リスナー（DEN_ADMIN_PORT / DEN_ADMIN_BIND_ADDRESS）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminListener {
+    pub bind_address: String,
+    pub port: u16,
+}
+
+/// DEN_DISABLE_* で無効化するサブシステム。無効なものはルート自体をマウントしない
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DisabledFeatures {
+    /// ローカルファイラ（/api/filer/*, /api/diff）
+    pub filer: bool,
+    /// SFTP・転送・同期ジョブ
+    pub sftp: bool,
+    /// Quick Connect（他の Den への接続）
+    pub remote: bool,
+    /// GitHub リリースの確認と自己更新
+    pub update: bool,
+}
+
+impl DisabledFeatures {
+    fn from_env() -> Self {
+        Self {
+            filer: env_flag("DEN_DISABLE_FILER"),
+            sftp: env_flag("DEN_DISABLE_SFTP"),
+            remote: env_flag("DEN_DISABLE_REMOTE"),
+            update: env_flag("DEN_DISABLE_UPDATE"),
+        }
+    }
+}
+
+/// 既定の SPA シェル用ポリシー。インラインスクリプトは nonce 付きのものだけ許可
+pub const DEFAULT_PAGE_CSP: &str = "default-src 'self'; script-src 'self' 'wasm-unsafe-eval' 'nonce-{nonce}'; style-src 'self' 'unsafe-inline'; connect-src 'self' ws: wss: https://cdn.jsdelivr.net; img-src 'self' data: blob:";
+/// 既定の API 用ポリシー。JSON やファイル内容はリソースを読み込まず、埋め込まれることもない
+pub const DEFAULT_API_CSP: &str = "default-src 'none'; frame-ancestors 'none'; base-uri 'none'";
+
+/// Content-Security-Policy（DEN_CSP / DEN_CSP_API）。
+/// `{nonce}` はレスポンスごとに生成する nonce に置換される
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CspPolicies {
+    /// SPA シェル・静的ファイル用
+    pub page: String,
+    /// /api/* 用（独自に CSP を設定するハンドラは除く）
+    pub api: String,
+}
+
+impl Default for CspPolicies {
+    fn default() -> Self {
+        Self {
+            page: DEFAULT_PAGE_CSP.to_string(),
+            api: DEFAULT_API_CSP.to_string(),
+        }
+    }
+}
+
+impl CspPolicies {
+    fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let policies = Self {
+            page: env_string("DEN_CSP").unwrap_or(defaults.page),
+            api: env_string("DEN_CSP_API").unwrap_or(defaults.api),
+        };
+        for (name, policy) in [("DEN_CSP", &policies.page), ("DEN_CSP_API", &policies.api)] {
+            if axum::http::HeaderValue::from_str(&policy.replace("{nonce}", "")).is_err() {
+                return Err(format!("{name}: not a valid header value"));
+            }
+        }
+        Ok(policies)
+    }
+}
+
+/// CSP 以外のセキュリティヘッダー（DEN_HSTS / DEN_CONTENT_TYPE_OPTIONS /
+/// DEN_REFERRER_POLICY / DEN_FRAME_ANCESTORS）。`off` で個別に無効化できる（None）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityHeaders {
+    /// Strict-Transport-Security。HTTPS で受けたレスポンスにだけ付ける
+    pub hsts: Option<String>,
+    /// X-Content-Type-Options
+    pub content_type_options: Option<String>,
+    /// Referrer-Policy
+    pub referrer_policy: Option<String>,
+    /// ページ用 CSP に足す frame-ancestors（ポリシー側に指定があればそちらを優先）
+    pub frame_ancestors: Option<String>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            hsts: Some("max-age=31536000".to_string()),
+            content_type_options: Some("nosniff".to_string()),
+            referrer_policy: Some("same-origin".to_string()),
+            frame_ancestors: Some("'self'".to_string()),
+        }
+    }
+}
+
+impl SecurityHeaders {
+    fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let value = |name: &str, default: Option<String>| match env_string(name) {
+            None => Ok(default),
+            Some(v) if v.eq_ignore_ascii_case("off") => Ok(None),
+            // frame-ancestors は CSP に埋め込むので他のディレクティブを混ぜさせない
+            Some(v)
+                if axum::http::HeaderValue::from_str(&v).is_err()
+                    || (name == "DEN_FRAME_ANCESTORS" && v.contains(';')) =>
+            {
+                Err(format!("{name}: not a valid header value"))
+            }
+            Some(v) => Ok(Some(v)),
+        };
+        Ok(Self {
+            hsts: value("DEN_HSTS", defaults.hsts)?,
+            content_type_options: value("DEN_CONTENT_TYPE_OPTIONS", defaults.content_type_options)?,
+            referrer_policy: value("DEN_REFERRER_POLICY", defaults.referrer_policy)?,
+            frame_ancestors: value("DEN_FRAME_ANCESTORS", defaults.frame_ancestors)?,
+        })
+    }
+}
+
+/// 他オリジンのフロントエンド・アプリからの API 呼び出しを許可する（DEN_CORS_*）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsConfig {
+    /// 許可するオリジン（`https://app.example` 形式）。`*` は全オリジン
+    pub origins: Vec<String>,
+    /// Cookie 付きリクエストを許可する（`*` とは併用できない）
+    pub credentials: bool,
+}
+
+impl CorsConfig {
+    fn from_env() -> Result<Option<Self>, String> {
+        let origins = env_list("DEN_CORS_ORIGINS");
+        if origins.is_empty() {
+            return Ok(None);
+        }
+        let credentials = env_flag("DEN_CORS_CREDENTIALS");
+        for origin in &origins {
+            if origin == "*" {
+                if credentials {
+                    return Err(
+                        "DEN_CORS_CREDENTIALS cannot be used with DEN_CORS_ORIGINS=*".into(),
+                    );
+                }
+                continue;
+            }
+            // オリジンはスキーム・ホスト・ポートだけ（パスや末尾の / は一致しなくなる）
+            let valid = reqwest::Url::parse(origin).is_ok_and(|url| {
+                matches!(url.scheme(), "https" | "http")
+                    && url.host().is_some()
+                    && url.origin().ascii_serialization() == *origin
+            });
+            if !valid {
+                return Err(format!(
+                    "DEN_CORS_ORIGINS: expected an origin like https://app.example: {origin}"
+                ));
+            }
+        }
+        Ok(Some(Self {
+            origins,
+            credentials,
+        }))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub port: u16,
+    /// DEN_PASSWORD（DEN_PASSWORD_HASH 指定時は空でもよい）
     pub password: String,
+    /// DEN_PASSWORD_HASH: argon2id PHC 文字列。指定時は DEN_PASSWORD より優先
+    pub password_hash: Option<String>,
     pub shell: String,
     pub env: Environment,
     pub log_level: String,
+    /// data_dir/logs/ のファイル出力（DEN_LOG_FORMAT / DEN_LOG_ROTATION / DEN_LOG_MAX_*）
+    pub log_files: crate::logs::LogConfig,
     pub data_dir: String,
     pub bind_address: String,
     /// SSH ポート（None = SSH 無効、DEN_SSH_PORT で指定）
     pub ssh_port: Option<u16>,
+    /// ローカル専用の HTTP リスナー（DEN_LOCAL_SOCKET）。Unix ソケットのパス、
+    /// Windows では名前付きパイプ（\\.\pipe\den）。TCP リスナーと併用
+    pub local_socket: Option<String>,
+    /// 管理用エンドポイント（ユーザー管理・監査ログ・診断・/metrics など）を別ポートに分ける。
+    /// 指定時はメインのリスナーから外す（None = メインのリスナーで提供）
+    pub admin_listener: Option<AdminListener>,
     /// HTTPS/WSS を有効化する
     pub tls_enabled: bool,
-    /// 明示指定のサーバー証明書（DER）。未指定なら自己署名を data_dir/tls/ に生成
+    /// 明示指定のサーバー証明書（PEM チェーンまたは DER）。未指定なら自己署名を data_dir/tls/ に生成
     pub tls_cert_path: Option<String>,
-    /// 明示指定の秘密鍵（PKCS#8 DER）
+    /// 明示指定の秘密鍵（PEM または PKCS#8 DER）
     pub tls_key_path: Option<String>,
     /// 自己署名証明書に追加する SAN（カンマ区切り）
     pub tls_subject_alt_names: Vec<String>,
+    /// クライアント証明書の発行 CA（PEM、DEN_TLS_CLIENT_CA）。指定するとこの CA が署名した
+    /// 証明書のない接続は TLS ハンドシェイクで拒否する（mTLS）
+    pub tls_client_ca: Option<String>,
+    /// オーナーとしてログインするクライアント証明書の CN（DEN_TLS_CLIENT_OWNER_CN）。
+    /// それ以外の CN は同名の登録ユーザーになる
+    pub tls_client_owner_cn: Option<String>,
+    /// ACME で証明書を自動取得・更新する（DEN_ACME_DOMAINS ほか）。DEN_TLS が必要
+    pub acme: Option<crate::acme::AcmeConfig>,
+    /// ファイラがアクセスできるディレクトリ（DEN_FILER_ROOTS、OS のパス区切り）。空なら無制限
+    pub filer_roots: Vec<String>,
+    /// ログイントークンの有効期限（時間、DEN_TOKEN_TTL_HOURS）。利用中は期限の半分を過ぎると再発行
+    pub token_ttl_hours: u64,
+    /// HMAC シークレットを data_dir に保存し、再起動後もログインを維持する（DEN_PERSIST_SECRET）
+    pub persist_secret: bool,
+    /// 接続を許可するクライアント（DEN_ALLOW_CIDRS、カンマ区切り）。空なら全て許可
+    pub allow_cidrs: Vec<String>,
+    /// 接続を拒否するクライアント（DEN_DENY_CIDRS、カンマ区切り）。許可リストより優先
+    pub deny_cidrs: Vec<String>,
+    /// X-Forwarded-For を信頼するリバースプロキシ（DEN_TRUSTED_PROXIES、カンマ区切り）
+    pub trusted_proxies: Vec<String>,
+    /// OIDC シングルサインオン（DEN_OIDC_*）。None ならパスワードログインのみ
+    pub oidc: Option<crate::oidc::OidcConfig>,
+    /// シークレット保管庫の鍵ファイル（DEN_VAULT_KEYFILE）。None ならオーナーのパスワードから導出
+    pub vault_keyfile: Option<String>,
+    /// /metrics 専用の Bearer トークン（DEN_METRICS_TOKEN）。未設定なら管理者ログインのみ
+    pub metrics_token: Option<String>,
+    /// トレース・メトリクスの OTLP/HTTP エクスポート（DEN_OTLP_*）。None なら無効
+    pub otlp: Option<crate::telemetry::OtlpConfig>,
+    pub disabled: DisabledFeatures,
+    pub csp: CspPolicies,
+    pub security_headers: SecurityHeaders,
+    /// CORS（DEN_CORS_ORIGINS / DEN_CORS_CREDENTIALS）。None なら同一オリジンのみ
+    pub cors: Option<CorsConfig>,
+    /// セキュリティイベントの通知先（DEN_NOTIFY_*）。既定は通知なし
+    pub notify: crate::notify::NotifyConfig,
 }
 
 impl Config {
@@ -67,14 +279,36 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(default_port);
 
-        let password = match env::var("DEN_PASSWORD") {
-            Ok(p) if !p.is_empty() => p,
-            _ => {
-                eprintln!("ERROR: DEN_PASSWORD environment variable is required.");
-                eprintln!("  Set it before starting Den: DEN_PASSWORD=your_password cargo run");
+        let secret = |name: &str| {
+            env_secret(name).unwrap_or_else(|e| {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            })
+        };
+        // パスワードは前後の空白も含めてそのまま使う
+        let password = match secret_file("DEN_PASSWORD") {
+            Ok(Some(password)) => password,
+            Ok(None) => env::var("DEN_PASSWORD").unwrap_or_default(),
+            Err(e) => {
+                eprintln!("ERROR: {e}");
                 std::process::exit(1);
             }
         };
+        let password_hash = secret("DEN_PASSWORD_HASH");
+        if let Some(ref hash) = password_hash
+            && argon2::password_hash::PasswordHash::new(hash).is_err()
+        {
+            eprintln!("ERROR: DEN_PASSWORD_HASH is not a valid argon2 hash.");
+            eprintln!("  Generate one with: den hash-password");
+            std::process::exit(1);
+        }
+        if password.is_empty() && password_hash.is_none() {
+            eprintln!("ERROR: DEN_PASSWORD or DEN_PASSWORD_HASH environment variable is required.");
+            eprintln!("  Set it before starting Den: DEN_PASSWORD=your_password cargo run");
+            eprintln!("  or read it from a file: DEN_PASSWORD_FILE=/run/secrets/den_password");
+            eprintln!("  or store a hash instead: DEN_PASSWORD_HASH=$(den hash-password)");
+            std::process::exit(1);
+        }
 
         let shell = env::var("DEN_SHELL").unwrap_or_else(|_| {
             if cfg!(windows) {
@@ -125,15 +359,15 @@ impl Config {
         };
         let bind_address =
             env::var("DEN_BIND_ADDRESS").unwrap_or_else(|_| default_bind.to_string());
-        let tls_enabled = env::var("DEN_TLS")
-            .ok()
-            .map(|v| {
-                matches!(
-                    v.trim().to_ascii_lowercase().as_str(),
-                    "1" | "true" | "yes" | "on"
-                )
-            })
-            .unwrap_or(false);
+        let local_socket = env_string("DEN_LOCAL_SOCKET");
+        let admin_listener = match admin_listener_from_env(&bind_address, port) {
+            Ok(admin_listener) => admin_listener,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let tls_enabled = env_flag("DEN_TLS");
         let tls_cert_path = env::var("DEN_TLS_CERT_PATH")
             .ok()
             .map(|v| v.trim().to_string())
@@ -142,32 +376,427 @@ impl Config {
             .ok()
             .map(|v| v.trim().to_string())
             .filter(|v| !v.is_empty());
-        let tls_subject_alt_names = env::var("DEN_TLS_SAN")
-            .ok()
+        let tls_subject_alt_names = env_list("DEN_TLS_SAN");
+        let tls_client_ca = env_string("DEN_TLS_CLIENT_CA");
+        let tls_client_owner_cn = env_string("DEN_TLS_CLIENT_OWNER_CN");
+        if tls_client_ca.is_some() && !tls_enabled {
+            eprintln!("ERROR: DEN_TLS_CLIENT_CA requires DEN_TLS=true");
+            std::process::exit(1);
+        }
+        let log_files = match log_files_from_env() {
+            Ok(log_files) => log_files,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let acme = match acme_from_env(tls_enabled, tls_cert_path.is_some()) {
+            Ok(acme) => acme,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let filer_roots = env::var_os("DEN_FILER_ROOTS")
             .map(|v| {
-                v.split(',')
-                    .map(str::trim)
-                    .filter(|s| !s.is_empty())
-                    .map(ToString::to_string)
+                env::split_paths(&v)
+                    .map(|p| p.to_string_lossy().trim().to_string())
+                    .filter(|p| !p.is_empty())
                     .collect::<Vec<_>>()
             })
             .unwrap_or_default();
+        let token_ttl_hours = env::var("DEN_TOKEN_TTL_HOURS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|&h| (1..=MAX_TOKEN_TTL_HOURS).contains(&h))
+            .unwrap_or(DEFAULT_TOKEN_TTL_HOURS);
+        let persist_secret = env_flag("DEN_PERSIST_SECRET");
+        let allow_cidrs = env_list("DEN_ALLOW_CIDRS");
+        let deny_cidrs = env_list("DEN_DENY_CIDRS");
+        let trusted_proxies = env_list("DEN_TRUSTED_PROXIES");
+        if let Some(e) = allow_cidrs
+            .iter()
+            .chain(&deny_cidrs)
+            .chain(&trusted_proxies)
+            .find_map(|c| c.parse::<crate::ip_filter::Cidr>().err())
+        {
+            eprintln!("ERROR: DEN_ALLOW_CIDRS / DEN_DENY_CIDRS / DEN_TRUSTED_PROXIES: {e}");
+            std::process::exit(1);
+        }
+        let vault_keyfile = env_string("DEN_VAULT_KEYFILE");
+        if let Some(Err(e)) = vault_keyfile
+            .as_deref()
+            .map(crate::vault::VaultKey::from_keyfile)
+        {
+            eprintln!("ERROR: DEN_VAULT_KEYFILE: {e}");
+            std::process::exit(1);
+        }
+        let oidc = match oidc_from_env() {
+            Ok(oidc) => oidc,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let otlp = match otlp_from_env() {
+            Ok(otlp) => otlp,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let csp = match CspPolicies::from_env() {
+            Ok(csp) => csp,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let security_headers = match SecurityHeaders::from_env() {
+            Ok(headers) => headers,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let cors = match CorsConfig::from_env() {
+            Ok(cors) => cors,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
+        let notify = match notify_from_env() {
+            Ok(notify) => notify,
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        };
 
         Self {
             port,
             password,
+            password_hash,
             shell,
             env,
             log_level,
+            log_files,
             data_dir,
             bind_address,
             ssh_port,
+            local_socket,
+            admin_listener,
             tls_enabled,
             tls_cert_path,
             tls_key_path,
             tls_subject_alt_names,
+            tls_client_ca,
+            tls_client_owner_cn,
+            acme,
+            filer_roots,
+            token_ttl_hours,
+            persist_secret,
+            allow_cidrs,
+            deny_cidrs,
+            trusted_proxies,
+            oidc,
+            vault_keyfile,
+            metrics_token: secret("DEN_METRICS_TOKEN"),
+            otlp,
+            disabled: DisabledFeatures::from_env(),
+            csp,
+            security_headers,
+            cors,
+            notify,
+        }
+    }
+
+    pub fn owner_credential(&self) -> OwnerCredential {
+        match self.password_hash {
+            Some(ref hash) => OwnerCredential::Hash(hash.clone()),
+            None => OwnerCredential::Plain(self.password.clone()),
         }
     }
+
+    pub fn token_ttl_secs(&self) -> u64 {
+        self.token_ttl_hours * 60 * 60
+    }
+}
+
+pub const DEFAULT_TOKEN_TTL_HOURS: u64 = 24;
+/// 1 年
+const MAX_TOKEN_TTL_HOURS: u64 = 365 * 24;
+
+/// 前後の空白を除いた値（未設定・空なら None）
+fn env_string(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// シークレットの値。`NAME` の代わりに `NAME_FILE` でファイルから読める
+/// （ps やタスクマネージャーから見えるプロセス環境に置かずに済む）
+fn env_secret(name: &str) -> Result<Option<String>, String> {
+    Ok(match secret_file(name)? {
+        Some(value) => Some(value),
+        None => env_string(name),
+    })
+}
+
+/// `NAME_FILE` が指すファイルの内容（末尾の改行は除く）。`NAME` との併用はエラー
+fn secret_file(name: &str) -> Result<Option<String>, String> {
+    let file_var = format!("{name}_FILE");
+    let Some(path) = env_string(&file_var) else {
+        return Ok(None);
+    };
+    if env::var_os(name).is_some_and(|v| !v.is_empty()) {
+        return Err(format!("{name} and {file_var} cannot both be set"));
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("{file_var}: {path}: {e}"))?;
+    let value = content.trim_end_matches(['\r', '\n']);
+    if value.is_empty() {
+        return Err(format!("{file_var}: {path} is empty"));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// DEN_ADMIN_PORT を指定すると管理用エンドポイントを別リスナーで提供する。
+/// DEN_ADMIN_BIND_ADDRESS の既定は DEN_BIND_ADDRESS
+fn admin_listener_from_env(bind_address: &str, port: u16) -> Result<Option<AdminListener>, String> {
+    let Some(admin_port) = env_string("DEN_ADMIN_PORT") else {
+        return Ok(None);
+    };
+    let admin_port = admin_port
+        .parse::<u16>()
+        .ok()
+        .filter(|&p| p > 0)
+        .ok_or_else(|| format!("DEN_ADMIN_PORT: invalid port: {admin_port}"))?;
+    let admin_bind_address =
+        env_string("DEN_ADMIN_BIND_ADDRESS").unwrap_or_else(|| bind_address.to_string());
+    if admin_port == port && admin_bind_address == bind_address {
+        return Err("DEN_ADMIN_PORT: must differ from DEN_PORT".into());
+    }
+    Ok(Some(AdminListener {
+        bind_address: admin_bind_address,
+        port: admin_port,
+    }))
+}
+
+/// DEN_ACME_DOMAINS（カンマ区切り）を指定すると ACME を使う。HTTP-01 で検証するので
+/// ワイルドカードは不可。DEN_TLS_CERT_PATH とは併用できない
+fn acme_from_env(
+    tls_enabled: bool,
+    explicit_cert: bool,
+) -> Result<Option<crate::acme::AcmeConfig>, String> {
+    let domains = env_list("DEN_ACME_DOMAINS");
+    if domains.is_empty() {
+        return Ok(None);
+    }
+    if !tls_enabled {
+        return Err("DEN_ACME_DOMAINS requires DEN_TLS=true".into());
+    }
+    if explicit_cert {
+        return Err("DEN_ACME_DOMAINS cannot be combined with DEN_TLS_CERT_PATH".into());
+    }
+    if let Some(bad) = domains.iter().find(|d| {
+        d.starts_with(['.', '-'])
+            || !d.contains('.')
+            || !d
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    }) {
+        return Err(format!("DEN_ACME_DOMAINS: not a DNS name: {bad}"));
+    }
+    let directory_url = env_string("DEN_ACME_DIRECTORY")
+        .unwrap_or_else(|| crate::acme::LETS_ENCRYPT_DIRECTORY.to_string());
+    let parsed =
+        reqwest::Url::parse(&directory_url).map_err(|e| format!("DEN_ACME_DIRECTORY: {e}"))?;
+    if !matches!(parsed.scheme(), "https" | "http") {
+        return Err("DEN_ACME_DIRECTORY: must be an http(s) URL".into());
+    }
+    let http_port = match env_string("DEN_ACME_HTTP_PORT") {
+        None => 80,
+        Some(v) => v
+            .parse()
+            .map_err(|_| format!("DEN_ACME_HTTP_PORT: invalid port: {v}"))?,
+    };
+    Ok(Some(crate::acme::AcmeConfig {
+        domains,
+        email: env_string("DEN_ACME_EMAIL"),
+        directory_url,
+        http_port,
+    }))
+}
+
+/// DEN_OTLP_ENDPOINT（コレクターのベース URL）を指定すると OTLP エクスポートを有効化。
+/// DEN_OTLP_HEADERS は `key=value` のカンマ区切り（API キーなど）
+fn otlp_from_env() -> Result<Option<crate::telemetry::OtlpConfig>, String> {
+    let Some(endpoint) = env_string("DEN_OTLP_ENDPOINT") else {
+        return Ok(None);
+    };
+    let parsed = reqwest::Url::parse(&endpoint).map_err(|e| format!("DEN_OTLP_ENDPOINT: {e}"))?;
+    if !matches!(parsed.scheme(), "https" | "http") {
+        return Err("DEN_OTLP_ENDPOINT: must be an http(s) URL".into());
+    }
+    let headers = env_list("DEN_OTLP_HEADERS")
+        .into_iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(format!("DEN_OTLP_HEADERS: expected key=value: {pair}")),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Some(crate::telemetry::OtlpConfig {
+        endpoint,
+        headers,
+        service_name: env_string("DEN_OTLP_SERVICE_NAME")
+            .unwrap_or_else(|| crate::telemetry::DEFAULT_SERVICE_NAME.to_string()),
+    }))
+}
+
+fn log_files_from_env() -> Result<crate::logs::LogConfig, String> {
+    use crate::logs::{LogConfig, LogFormat, LogRotation};
+    let format = match env_string("DEN_LOG_FORMAT").as_deref() {
+        None | Some("text") => LogFormat::Text,
+        Some("json") => LogFormat::Json,
+        Some(v) => return Err(format!("DEN_LOG_FORMAT: expected text or json: {v}")),
+    };
+    let rotation = match env_string("DEN_LOG_ROTATION").as_deref() {
+        None | Some("daily") => LogRotation::Daily,
+        Some("hourly") => LogRotation::Hourly,
+        Some("never") => LogRotation::Never,
+        Some(v) => {
+            return Err(format!(
+                "DEN_LOG_ROTATION: expected daily, hourly or never: {v}"
+            ));
+        }
+    };
+    let max_size_bytes = match env_string("DEN_LOG_MAX_SIZE_MB") {
+        None => None,
+        Some(v) => match v.parse::<u64>() {
+            Ok(0) => None,
+            Ok(mb) => Some(mb * 1024 * 1024),
+            Err(_) => return Err(format!("DEN_LOG_MAX_SIZE_MB: not a number: {v}")),
+        },
+    };
+    let max_files = match env_string("DEN_LOG_MAX_FILES") {
+        None => 0,
+        Some(v) => v
+            .parse()
+            .map_err(|_| format!("DEN_LOG_MAX_FILES: not a number: {v}"))?,
+    };
+    Ok(LogConfig {
+        format,
+        rotation,
+        max_size_bytes,
+        max_files,
+    })
+}
+
+/// DEN_OIDC_ISSUER / DEN_OIDC_CLIENT_ID / DEN_OIDC_REDIRECT_URL は全て指定するか全て省略する
+fn oidc_from_env() -> Result<Option<crate::oidc::OidcConfig>, String> {
+    let issuer = env_string("DEN_OIDC_ISSUER");
+    let client_id = env_string("DEN_OIDC_CLIENT_ID");
+    let redirect_url = env_string("DEN_OIDC_REDIRECT_URL");
+    let (issuer, client_id, redirect_url) = match (issuer, client_id, redirect_url) {
+        (None, None, None) => return Ok(None),
+        (Some(i), Some(c), Some(r)) => (i, c, r),
+        _ => {
+            return Err(
+                "DEN_OIDC_ISSUER, DEN_OIDC_CLIENT_ID and DEN_OIDC_REDIRECT_URL must be set together"
+                    .into(),
+            );
+        }
+    };
+    // ID トークンの署名は検証しないため、IdP との通信は TLS 必須（ループバックのみ http 可）
+    let parsed = reqwest::Url::parse(&issuer).map_err(|e| format!("DEN_OIDC_ISSUER: {e}"))?;
+    if !crate::oidc::is_trusted_idp_url(&parsed) {
+        return Err("DEN_OIDC_ISSUER: must be an https URL (http only for localhost)".into());
+    }
+    let parsed =
+        reqwest::Url::parse(&redirect_url).map_err(|e| format!("DEN_OIDC_REDIRECT_URL: {e}"))?;
+    if !matches!(parsed.scheme(), "https" | "http") {
+        return Err("DEN_OIDC_REDIRECT_URL: must be an http(s) URL".into());
+    }
+    Ok(Some(crate::oidc::OidcConfig {
+        issuer,
+        client_id,
+        client_secret: env_secret("DEN_OIDC_CLIENT_SECRET")?,
+        redirect_url,
+        owner_subject: env_string("DEN_OIDC_OWNER_SUBJECT"),
+    }))
+}
+
+/// DEN_NOTIFY_WEBHOOK_URL / DEN_NOTIFY_NTFY_URL / DEN_NOTIFY_EMAIL で通知先を指定する。
+/// DEN_NOTIFY_EVENTS（カンマ区切り）で送るイベントを絞れる（既定は全て）
+fn notify_from_env() -> Result<crate::notify::NotifyConfig, String> {
+    use crate::notify::{EventKind, NotifyConfig};
+
+    let webhook_url = env_string("DEN_NOTIFY_WEBHOOK_URL");
+    let ntfy_url = env_string("DEN_NOTIFY_NTFY_URL");
+    for (name, url) in [
+        ("DEN_NOTIFY_WEBHOOK_URL", &webhook_url),
+        ("DEN_NOTIFY_NTFY_URL", &ntfy_url),
+    ] {
+        let Some(url) = url else { continue };
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("{name}: {e}"))?;
+        if !matches!(parsed.scheme(), "https" | "http") {
+            return Err(format!("{name}: must be an http(s) URL"));
+        }
+    }
+    let events = match env::var("DEN_NOTIFY_EVENTS") {
+        Ok(_) => env_list("DEN_NOTIFY_EVENTS")
+            .iter()
+            .map(|name| {
+                EventKind::parse(name).ok_or_else(|| {
+                    let known: Vec<&str> = EventKind::ALL.iter().map(|k| k.as_str()).collect();
+                    format!(
+                        "DEN_NOTIFY_EVENTS: unknown event {name:?} (expected {})",
+                        known.join(", ")
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Err(_) => EventKind::ALL.to_vec(),
+    };
+    Ok(NotifyConfig {
+        webhook_url,
+        ntfy_url,
+        ntfy_token: env_secret("DEN_NOTIFY_NTFY_TOKEN")?,
+        email_to: env_string("DEN_NOTIFY_EMAIL"),
+        sendmail: env_string("DEN_NOTIFY_SENDMAIL"),
+        events,
+    })
+}
+
+/// カンマ区切りのリスト（空要素は無視）
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .ok()
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+}
+
+/// `1` / `true` / `yes` / `on` を true とみなす
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .ok()
+        .map(|v| {
+            matches!(
+                v.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
 }
 
 #[cfg(test)]
@@ -182,15 +811,67 @@ mod tests {
             env::remove_var("DEN_ENV");
             env::remove_var("DEN_PORT");
             env::set_var("DEN_PASSWORD", "test_password");
+            env::remove_var("DEN_PASSWORD_HASH");
+            env::remove_var("DEN_PASSWORD_FILE");
+            env::remove_var("DEN_PASSWORD_HASH_FILE");
+            env::remove_var("DEN_METRICS_TOKEN");
+            env::remove_var("DEN_METRICS_TOKEN_FILE");
             env::remove_var("DEN_SHELL");
             env::remove_var("DEN_LOG_LEVEL");
+            env::remove_var("DEN_LOG_FORMAT");
+            env::remove_var("DEN_LOG_ROTATION");
+            env::remove_var("DEN_LOG_MAX_SIZE_MB");
+            env::remove_var("DEN_LOG_MAX_FILES");
+            env::remove_var("DEN_OTLP_ENDPOINT");
+            env::remove_var("DEN_OTLP_HEADERS");
+            env::remove_var("DEN_OTLP_SERVICE_NAME");
             env::remove_var("DEN_DATA_DIR");
             env::remove_var("DEN_BIND_ADDRESS");
             env::remove_var("DEN_SSH_PORT");
+            env::remove_var("DEN_ADMIN_PORT");
+            env::remove_var("DEN_ADMIN_BIND_ADDRESS");
             env::remove_var("DEN_TLS");
             env::remove_var("DEN_TLS_CERT_PATH");
             env::remove_var("DEN_TLS_KEY_PATH");
             env::remove_var("DEN_TLS_SAN");
+            env::remove_var("DEN_TLS_CLIENT_CA");
+            env::remove_var("DEN_TLS_CLIENT_OWNER_CN");
+            env::remove_var("DEN_ACME_DOMAINS");
+            env::remove_var("DEN_ACME_EMAIL");
+            env::remove_var("DEN_ACME_DIRECTORY");
+            env::remove_var("DEN_ACME_HTTP_PORT");
+            env::remove_var("DEN_FILER_ROOTS");
+            env::remove_var("DEN_TOKEN_TTL_HOURS");
+            env::remove_var("DEN_PERSIST_SECRET");
+            env::remove_var("DEN_ALLOW_CIDRS");
+            env::remove_var("DEN_DENY_CIDRS");
+            env::remove_var("DEN_TRUSTED_PROXIES");
+            env::remove_var("DEN_OIDC_ISSUER");
+            env::remove_var("DEN_OIDC_CLIENT_ID");
+            env::remove_var("DEN_OIDC_CLIENT_SECRET");
+            env::remove_var("DEN_OIDC_CLIENT_SECRET_FILE");
+            env::remove_var("DEN_OIDC_REDIRECT_URL");
+            env::remove_var("DEN_OIDC_OWNER_SUBJECT");
+            env::remove_var("DEN_CSP");
+            env::remove_var("DEN_CSP_API");
+            env::remove_var("DEN_HSTS");
+            env::remove_var("DEN_CONTENT_TYPE_OPTIONS");
+            env::remove_var("DEN_REFERRER_POLICY");
+            env::remove_var("DEN_FRAME_ANCESTORS");
+            env::remove_var("DEN_CORS_ORIGINS");
+            env::remove_var("DEN_CORS_CREDENTIALS");
+            env::remove_var("DEN_NOTIFY_WEBHOOK_URL");
+            env::remove_var("DEN_NOTIFY_NTFY_URL");
+            env::remove_var("DEN_NOTIFY_NTFY_TOKEN");
+            env::remove_var("DEN_NOTIFY_NTFY_TOKEN_FILE");
+            env::remove_var("DEN_NOTIFY_EMAIL");
+            env::remove_var("DEN_NOTIFY_SENDMAIL");
+            env::remove_var("DEN_NOTIFY_EVENTS");
+            env::remove_var("DEN_VAULT_KEYFILE");
+            env::remove_var("DEN_DISABLE_FILER");
+            env::remove_var("DEN_DISABLE_SFTP");
+            env::remove_var("DEN_DISABLE_REMOTE");
+            env::remove_var("DEN_DISABLE_UPDATE");
         }
     }
 
@@ -208,6 +889,9 @@ mod tests {
         assert!(config.tls_cert_path.is_none());
         assert!(config.tls_key_path.is_none());
         assert!(config.tls_subject_alt_names.is_empty());
+        assert!(config.tls_client_ca.is_none());
+        assert_eq!(config.token_ttl_hours, 24);
+        assert!(!config.persist_secret);
     }
 
     #[test]
@@ -253,6 +937,57 @@ mod tests {
         clear_env();
     }
 
+    #[test]
+    #[serial]
+    fn password_hash_takes_precedence() {
+        clear_env();
+        let hash = crate::auth::hash_password("hashed");
+        unsafe {
+            env::remove_var("DEN_PASSWORD");
+            env::set_var("DEN_PASSWORD_HASH", &hash);
+        }
+        let config = Config::from_env();
+        assert_eq!(config.password_hash.as_deref(), Some(hash.as_str()));
+        let owner = config.owner_credential();
+        assert!(owner.verify("hashed"));
+        assert!(owner.plaintext().is_none());
+        clear_env();
+        assert!(
+            Config::from_env()
+                .owner_credential()
+                .verify("test_password")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn secrets_from_files() {
+        clear_env();
+        let dir = tempfile::tempdir().unwrap();
+        let password_file = dir.path().join("password");
+        std::fs::write(&password_file, " spaced secret \n").unwrap();
+        let token_file = dir.path().join("token");
+        std::fs::write(&token_file, "metrics-token\r\n").unwrap();
+        unsafe {
+            env::remove_var("DEN_PASSWORD");
+            env::set_var("DEN_PASSWORD_FILE", &password_file);
+            env::set_var("DEN_METRICS_TOKEN_FILE", &token_file);
+        }
+        let config = Config::from_env();
+        assert_eq!(config.password, " spaced secret ");
+        assert_eq!(config.metrics_token.as_deref(), Some("metrics-token"));
+
+        // 環境変数との併用・空ファイル・読めないファイルはエラー
+        unsafe { env::set_var("DEN_METRICS_TOKEN", "other") };
+        assert!(env_secret("DEN_METRICS_TOKEN").is_err());
+        unsafe { env::remove_var("DEN_METRICS_TOKEN") };
+        std::fs::write(&token_file, "\n").unwrap();
+        assert!(env_secret("DEN_METRICS_TOKEN").is_err());
+        unsafe { env::set_var("DEN_METRICS_TOKEN_FILE", dir.path().join("missing")) };
+        assert!(env_secret("DEN_METRICS_TOKEN").is_err());
+        clear_env();
+    }
+
     #[test]
     #[serial]
     fn custom_bind_address() {
@@ -272,9 +1007,16 @@ mod tests {
             env::set_var("DEN_TLS_CERT_PATH", "data/tls/cert.der");
             env::set_var("DEN_TLS_KEY_PATH", "data/tls/key.der");
             env::set_var("DEN_TLS_SAN", "den-a, 10.0.0.2, localhost");
+            env::set_var("DEN_TLS_CLIENT_CA", "data/tls/client-ca.pem");
+            env::set_var("DEN_TLS_CLIENT_OWNER_CN", "me");
         }
         let config = Config::from_env();
         assert!(config.tls_enabled);
+        assert_eq!(
+            config.tls_client_ca.as_deref(),
+            Some("data/tls/client-ca.pem")
+        );
+        assert_eq!(config.tls_client_owner_cn.as_deref(), Some("me"));
         assert_eq!(config.tls_cert_path.as_deref(), Some("data/tls/cert.der"));
         assert_eq!(config.tls_key_path.as_deref(), Some("data/tls/key.der"));
         assert_eq!(
@@ -288,6 +1030,332 @@ mod tests {
         clear_env();
     }
 
+    #[test]
+    #[serial]
+    fn log_file_settings() {
+        use crate::logs::{LogConfig, LogFormat, LogRotation};
+        clear_env();
+        assert_eq!(log_files_from_env().unwrap(), LogConfig::default());
+        unsafe {
+            env::set_var("DEN_LOG_FORMAT", "json");
+            env::set_var("DEN_LOG_ROTATION", "hourly");
+            env::set_var("DEN_LOG_MAX_SIZE_MB", "50");
+            env::set_var("DEN_LOG_MAX_FILES", "10");
+        }
+        let logs = log_files_from_env().unwrap();
+        assert_eq!(logs.format, LogFormat::Json);
+        assert_eq!(logs.rotation, LogRotation::Hourly);
+        assert_eq!(logs.max_size_bytes, Some(50 * 1024 * 1024));
+        assert_eq!(logs.max_files, 10);
+        unsafe { env::set_var("DEN_LOG_ROTATION", "weekly") };
+        assert!(log_files_from_env().is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn otlp_settings() {
+        clear_env();
+        assert_eq!(otlp_from_env().unwrap(), None);
+        unsafe {
+            env::set_var("DEN_OTLP_ENDPOINT", "http://collector:4318");
+            env::set_var("DEN_OTLP_HEADERS", "x-api-key = secret, x-team=ops");
+        }
+        let otlp = otlp_from_env().unwrap().unwrap();
+        assert_eq!(otlp.endpoint, "http://collector:4318");
+        assert_eq!(
+            otlp.headers,
+            vec![
+                ("x-api-key".to_string(), "secret".to_string()),
+                ("x-team".to_string(), "ops".to_string())
+            ]
+        );
+        assert_eq!(otlp.service_name, "den");
+        unsafe { env::set_var("DEN_OTLP_HEADERS", "no-value") };
+        assert!(otlp_from_env().is_err());
+        unsafe { env::set_var("DEN_OTLP_ENDPOINT", "collector:4318") };
+        assert!(otlp_from_env().is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn admin_listener_settings() {
+        clear_env();
+        assert_eq!(admin_listener_from_env("0.0.0.0", 8080).unwrap(), None);
+        unsafe { env::set_var("DEN_ADMIN_PORT", "8081") };
+        assert_eq!(
+            admin_listener_from_env("0.0.0.0", 8080).unwrap(),
+            Some(AdminListener {
+                bind_address: "0.0.0.0".into(),
+                port: 8081
+            })
+        );
+        unsafe { env::set_var("DEN_ADMIN_BIND_ADDRESS", "192.168.1.10") };
+        assert_eq!(
+            admin_listener_from_env("0.0.0.0", 8081).unwrap(),
+            Some(AdminListener {
+                bind_address: "192.168.1.10".into(),
+                port: 8081
+            })
+        );
+        unsafe { env::remove_var("DEN_ADMIN_BIND_ADDRESS") };
+        assert!(admin_listener_from_env("0.0.0.0", 8081).is_err());
+        unsafe { env::set_var("DEN_ADMIN_PORT", "0") };
+        assert!(admin_listener_from_env("0.0.0.0", 8080).is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn acme_settings() {
+        clear_env();
+        assert_eq!(acme_from_env(true, false).unwrap(), None);
+        unsafe {
+            env::set_var("DEN_ACME_DOMAINS", "den.example.com, www.den.example.com");
+            env::set_var("DEN_ACME_EMAIL", "me@example.com");
+            env::set_var("DEN_ACME_HTTP_PORT", "8080");
+        }
+        let acme = acme_from_env(true, false).unwrap().unwrap();
+        assert_eq!(
+            acme.domains,
+            vec![
+                "den.example.com".to_string(),
+                "www.den.example.com".to_string()
+            ]
+        );
+        assert_eq!(acme.email.as_deref(), Some("me@example.com"));
+        assert_eq!(acme.directory_url, crate::acme::LETS_ENCRYPT_DIRECTORY);
+        assert_eq!(acme.http_port, 8080);
+        assert!(acme_from_env(false, false).is_err());
+        assert!(acme_from_env(true, true).is_err());
+        unsafe { env::set_var("DEN_ACME_DOMAINS", "*.example.com") };
+        assert!(acme_from_env(true, false).is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn filer_roots_parse() {
+        clear_env();
+        let roots = env::join_paths(["/srv/share", "/home/family"]).unwrap();
+        unsafe { env::set_var("DEN_FILER_ROOTS", &roots) };
+        let config = Config::from_env();
+        assert_eq!(
+            config.filer_roots,
+            vec!["/srv/share".to_string(), "/home/family".to_string()]
+        );
+        clear_env();
+        assert!(Config::from_env().filer_roots.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn token_settings_parse() {
+        clear_env();
+        unsafe {
+            env::set_var("DEN_TOKEN_TTL_HOURS", "168");
+            env::set_var("DEN_PERSIST_SECRET", "on");
+        }
+        let config = Config::from_env();
+        assert_eq!(config.token_ttl_hours, 168);
+        assert_eq!(config.token_ttl_secs(), 168 * 60 * 60);
+        assert!(config.persist_secret);
+
+        unsafe { env::set_var("DEN_TOKEN_TTL_HOURS", "0") };
+        assert_eq!(Config::from_env().token_ttl_hours, DEFAULT_TOKEN_TTL_HOURS);
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn ip_filter_lists_parse() {
+        clear_env();
+        unsafe {
+            env::set_var("DEN_ALLOW_CIDRS", "100.64.0.0/10, fd7a:115c:a1e0::/48,");
+            env::set_var("DEN_DENY_CIDRS", "100.64.0.13");
+            env::set_var("DEN_TRUSTED_PROXIES", "127.0.0.1");
+        }
+        let config = Config::from_env();
+        assert_eq!(
+            config.allow_cidrs,
+            vec![
+                "100.64.0.0/10".to_string(),
+                "fd7a:115c:a1e0::/48".to_string()
+            ]
+        );
+        assert_eq!(config.deny_cidrs, vec!["100.64.0.13".to_string()]);
+        assert_eq!(config.trusted_proxies, vec!["127.0.0.1".to_string()]);
+        clear_env();
+        assert!(Config::from_env().allow_cidrs.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn csp_policies() {
+        clear_env();
+        assert_eq!(Config::from_env().csp, CspPolicies::default());
+        unsafe {
+            env::set_var(
+                "DEN_CSP",
+                "default-src 'self'; script-src 'self' 'nonce-{nonce}'",
+            );
+        }
+        let csp = CspPolicies::from_env().unwrap();
+        assert_eq!(
+            csp.page,
+            "default-src 'self'; script-src 'self' 'nonce-{nonce}'"
+        );
+        assert_eq!(csp.api, DEFAULT_API_CSP);
+        unsafe {
+            env::set_var("DEN_CSP_API", "default-src 'none'\nX-Injected: 1");
+        }
+        assert!(CspPolicies::from_env().is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn security_headers() {
+        clear_env();
+        assert_eq!(
+            Config::from_env().security_headers,
+            SecurityHeaders::default()
+        );
+        unsafe {
+            env::set_var("DEN_HSTS", "max-age=63072000; includeSubDomains");
+            env::set_var("DEN_REFERRER_POLICY", "OFF");
+        }
+        let headers = SecurityHeaders::from_env().unwrap();
+        assert_eq!(
+            headers.hsts.as_deref(),
+            Some("max-age=63072000; includeSubDomains")
+        );
+        assert_eq!(headers.referrer_policy, None);
+        assert_eq!(headers.content_type_options.as_deref(), Some("nosniff"));
+        unsafe {
+            env::set_var("DEN_FRAME_ANCESTORS", "'self'; script-src *");
+        }
+        assert!(SecurityHeaders::from_env().is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn cors_settings() {
+        clear_env();
+        assert_eq!(CorsConfig::from_env(), Ok(None));
+        unsafe {
+            env::set_var(
+                "DEN_CORS_ORIGINS",
+                "https://app.example, http://localhost:5173",
+            );
+            env::set_var("DEN_CORS_CREDENTIALS", "true");
+        }
+        let cors = CorsConfig::from_env().unwrap().unwrap();
+        assert_eq!(
+            cors.origins,
+            vec![
+                "https://app.example".to_string(),
+                "http://localhost:5173".to_string()
+            ]
+        );
+        assert!(cors.credentials);
+        // ワイルドカードと Cookie は併用できない
+        unsafe { env::set_var("DEN_CORS_ORIGINS", "*") };
+        assert!(CorsConfig::from_env().is_err());
+        unsafe { env::remove_var("DEN_CORS_CREDENTIALS") };
+        assert!(CorsConfig::from_env().is_ok());
+        for bad in ["https://app.example/", "app.example", "ftp://app.example"] {
+            unsafe { env::set_var("DEN_CORS_ORIGINS", bad) };
+            assert!(CorsConfig::from_env().is_err(), "{bad}");
+        }
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn notify_settings() {
+        use crate::notify::EventKind;
+
+        clear_env();
+        let notify = notify_from_env().unwrap();
+        assert!(!notify.has_channels());
+        assert_eq!(notify.events, EventKind::ALL.to_vec());
+        unsafe {
+            env::set_var("DEN_NOTIFY_NTFY_URL", "https://ntfy.sh/my-den");
+            env::set_var("DEN_NOTIFY_EVENTS", "login_lockout, ssh_key_added");
+        }
+        let notify = notify_from_env().unwrap();
+        assert!(notify.has_channels());
+        assert_eq!(
+            notify.events,
+            vec![EventKind::LoginLockout, EventKind::SshKeyAdded]
+        );
+        unsafe {
+            env::set_var("DEN_NOTIFY_EVENTS", "everything");
+        }
+        assert!(notify_from_env().is_err());
+        unsafe {
+            env::remove_var("DEN_NOTIFY_EVENTS");
+            env::set_var("DEN_NOTIFY_WEBHOOK_URL", "ftp://example.com/hook");
+        }
+        assert!(notify_from_env().is_err());
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn disabled_features() {
+        clear_env();
+        assert_eq!(Config::from_env().disabled, DisabledFeatures::default());
+        unsafe {
+            env::set_var("DEN_DISABLE_FILER", "1");
+            env::set_var("DEN_DISABLE_UPDATE", "true");
+        }
+        let disabled = Config::from_env().disabled;
+        assert!(disabled.filer && disabled.update);
+        assert!(!disabled.sftp && !disabled.remote);
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn oidc_settings() {
+        clear_env();
+        assert_eq!(oidc_from_env(), Ok(None));
+        unsafe {
+            env::set_var("DEN_OIDC_ISSUER", "https://idp.example");
+        }
+        assert!(oidc_from_env().is_err());
+        unsafe {
+            env::set_var("DEN_OIDC_CLIENT_ID", "den");
+            env::set_var(
+                "DEN_OIDC_REDIRECT_URL",
+                "https://den.example/api/auth/oidc/callback",
+            );
+            env::set_var("DEN_OIDC_OWNER_SUBJECT", " 1234 ");
+        }
+        let oidc = oidc_from_env().unwrap().unwrap();
+        assert_eq!(oidc.client_id, "den");
+        assert_eq!(oidc.client_secret, None);
+        assert_eq!(oidc.owner_subject.as_deref(), Some("1234"));
+        unsafe {
+            env::set_var("DEN_OIDC_ISSUER", "idp.example");
+        }
+        assert!(oidc_from_env().is_err());
+        // No TLS to a remote IdP: the unsigned ID token could be forged in transit
+        unsafe {
+            env::set_var("DEN_OIDC_ISSUER", "http://idp.example");
+        }
+        assert!(oidc_from_env().is_err());
+        unsafe {
+            env::set_var("DEN_OIDC_ISSUER", "http://127.0.0.1:5556");
+        }
+        assert!(oidc_from_env().is_ok());
+        clear_env();
+    }
+
     #[test]
     fn environment_from_str() {
         assert_eq!(
@@ -0,0 +1,426 @@
+//! `GET /api/dashboard`: everything the UI home screen shows, in one call.
+//!
+//! Sessions, attached clients and the `claude` processes are the caller's
+//! own (their session namespace). SFTP state and free space in the data
+//! directory are server-wide. Recent warnings and errors come from
+//! [`ErrorLayer`], which keeps the last few `WARN`/`ERROR` events in memory;
+//! they are only shown to admins.
+
+use axum::{Extension, Json, extract::State};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::Context;
+
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::pty::registry::{SessionInfo, unscoped_name};
+use crate::sftp::api::StatusResponse as SftpStatus;
+
+/// Number of warnings/errors kept for the dashboard
+const RECENT_ERRORS: usize = 20;
+/// Longer messages are cut (with fields) to this many characters
+const MAX_MESSAGE_CHARS: usize = 500;
+/// Process name of Claude Code (`claude.exe` on Windows)
+const CLAUDE_PROGRAM: &str = "claude";
+
+static RECENT: Mutex<VecDeque<RecentError>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct RecentError {
+    pub time: DateTime<Utc>,
+    /// "error" or "warn"
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// Keeps the last [`RECENT_ERRORS`] warnings and errors (added in `main`)
+pub struct ErrorLayer;
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ErrorLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if *meta.level() > tracing::Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        message.push_str(&visitor.fields);
+        if let Some((cut, _)) = message.char_indices().nth(MAX_MESSAGE_CHARS) {
+            message.truncate(cut);
+        }
+        let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() == RECENT_ERRORS {
+            recent.pop_front();
+        }
+        recent.push_back(RecentError {
+            time: Utc::now(),
+            level: meta.level().as_str().to_ascii_lowercase(),
+            target: meta.target().to_string(),
+            message,
+        });
+    }
+}
+
+/// `message` plus the other fields as ` key=value`
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// Newest first
+pub fn recent_errors() -> Vec<RecentError> {
+    RECENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DiskSpace {
+    pub path: String,
+    /// Bytes
+    pub total: u64,
+    /// Bytes available to den
+    pub available: u64,
+}
+
+#[cfg(unix)]
+fn disk_space(path: &Path) -> std::io::Result<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut st = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is NUL-terminated and st is only read after statvfs succeeded.
+    if unsafe { libc::statvfs(path.as_ptr(), st.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let st = unsafe { st.assume_init() };
+    #[allow(clippy::unnecessary_cast)] // 型はプラットフォームによって u32 / u64
+    let (blocks, avail, frsize) = (st.f_blocks as u64, st.f_bavail as u64, st.f_frsize as u64);
+    Ok((blocks.saturating_mul(frsize), avail.saturating_mul(frsize)))
+}
+
+#[cfg(windows)]
+fn disk_space(path: &Path) -> std::io::Result<(u64, u64)> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let (mut available, mut total, mut free) = (0u64, 0u64, 0u64);
+    // SAFETY: wide is NUL-terminated and the out pointers are valid u64s.
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((total, available))
+}
+
+/// PID → (parent PID, process name) of every process on the machine
+#[cfg(target_os = "linux")]
+fn process_table() -> Option<HashMap<u32, (u32, String)>> {
+    let mut table = HashMap::new();
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        // 列挙中に終了したプロセスは読めないので飛ばす
+        if let Ok(stat) = std::fs::read_to_string(entry.path().join("stat"))
+            && let Some(parsed) = parse_proc_stat(&stat)
+        {
+            table.insert(pid, parsed);
+        }
+    }
+    Some(table)
+}
+
+/// `/proc/<pid>/stat` is `pid (comm) state ppid ...`; comm may contain spaces and `)`
+#[cfg(target_os = "linux")]
+fn parse_proc_stat(stat: &str) -> Option<(u32, String)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let comm = stat.get(open + 1..close)?;
+    let ppid = stat[close + 1..].split_whitespace().nth(1)?.parse().ok()?;
+    Some((ppid, comm.to_string()))
+}
+
+#[cfg(windows)]
+fn process_table() -> Option<HashMap<u32, (u32, String)>> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, PROCESSENTRY32, Process32First, Process32Next, TH32CS_SNAPPROCESS,
+    };
+
+    // SAFETY: TH32CS_SNAPPROCESS with 0 takes a snapshot of all processes.
+    let snap = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    if snap == INVALID_HANDLE_VALUE {
+        return None;
+    }
+    let mut entry = PROCESSENTRY32 {
+        dwSize: std::mem::size_of::<PROCESSENTRY32>() as u32,
+        ..unsafe { std::mem::zeroed() }
+    };
+    let mut table = HashMap::new();
+    // SAFETY: entry is a PROCESSENTRY32 with dwSize set; snap is a valid snapshot.
+    if unsafe { Process32First(snap, &mut entry) } != 0 {
+        loop {
+            let exe: Vec<u8> = entry
+                .szExeFile
+                .iter()
+                .take_while(|&&c| c != 0)
+                .map(|&c| c as u8)
+                .collect();
+            // "claude.exe" → "claude"
+            let exe = String::from_utf8_lossy(&exe);
+            let name = Path::new(exe.as_ref())
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            table.insert(entry.th32ProcessID, (entry.th32ParentProcessID, name));
+            // SAFETY: as above; returns 0 after the last process.
+            if unsafe { Process32Next(snap, &mut entry) } == 0 {
+                break;
+            }
+        }
+    }
+    // SAFETY: snap is a valid handle from CreateToolhelp32Snapshot.
+    unsafe { CloseHandle(snap) };
+    Some(table)
+}
+
+/// Not implemented on other platforms
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_table() -> Option<HashMap<u32, (u32, String)>> {
+    None
+}
+
+/// Names of the sessions (`roots`: name, shell PID) that have a process
+/// called `program` at or below their shell, sorted
+fn sessions_running(
+    table: &HashMap<u32, (u32, String)>,
+    roots: &[(String, u32)],
+    program: &str,
+) -> Vec<String> {
+    // 循環した親子関係でも止まるように上限を設ける
+    const MAX_HOPS: usize = 64;
+    let mut found: Vec<String> = Vec::new();
+    for (&pid, (_, name)) in table {
+        if !name.eq_ignore_ascii_case(program) {
+            continue;
+        }
+        let mut current = pid;
+        for _ in 0..MAX_HOPS {
+            if let Some((session, _)) = roots.iter().find(|(_, root)| *root == current) {
+                found.push(session.clone());
+                break;
+            }
+            match table.get(&current) {
+                Some(&(parent, _)) if parent != current && parent != 0 => current = parent,
+                _ => break,
+            }
+        }
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DashboardResponse {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    /// The caller's running terminal sessions
+    pub sessions: Vec<SessionInfo>,
+    /// Clients attached to those sessions (browser tabs, SSH, observers)
+    pub attached_clients: usize,
+    /// Sessions with a `claude` process running in them. None = the process
+    /// list can't be read on this platform
+    pub claude_sessions: Option<Vec<String>>,
+    /// None = SFTP is disabled (`DEN_DISABLE_SFTP`)
+    pub sftp: Option<SftpStatus>,
+    /// Disk holding `DEN_DATA_DIR`. None = could not be read
+    pub disk: Option<DiskSpace>,
+    /// Newest first. Admins only (None for other users)
+    pub recent_errors: Option<Vec<RecentError>>,
+}
+
+/// GET /api/dashboard
+#[utoipa::path(
+    get,
+    path = "/api/dashboard",
+    tag = "system",
+    summary = "Home screen summary",
+    responses(
+        (status = 200, body = DashboardResponse),
+    )
+)]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> Json<DashboardResponse> {
+    let namespace = user.namespace();
+    let sessions: Vec<SessionInfo> = state
+        .registry
+        .list_in(namespace)
+        .await
+        .into_iter()
+        .filter(|s| s.alive)
+        .collect();
+    let attached_clients = sessions.iter().map(|s| s.client_count).sum();
+    let roots: Vec<(String, u32)> = state
+        .registry
+        .child_pids()
+        .await
+        .into_iter()
+        .filter_map(|(key, pid)| Some((unscoped_name(namespace, &key)?.to_string(), pid)))
+        .collect();
+    let sftp = if state.config.disabled.sftp {
+        None
+    } else {
+        let s = state.sftp_manager.status().await;
+        Some(SftpStatus {
+            connected: s.connected,
+            host: s.host,
+            username: s.username,
+        })
+    };
+
+    // プロセス一覧とディスク容量はブロッキング I/O
+    let data_dir = state.config.data_dir.clone();
+    let (claude_sessions, disk) = tokio::task::spawn_blocking(move || {
+        let claude = if roots.is_empty() {
+            Some(Vec::new())
+        } else {
+            process_table().map(|table| sessions_running(&table, &roots, CLAUDE_PROGRAM))
+        };
+        let disk = match disk_space(Path::new(&data_dir)) {
+            Ok((total, available)) => Some(DiskSpace {
+                path: data_dir,
+                total,
+                available,
+            }),
+            Err(e) => {
+                tracing::debug!("dashboard: free space of {data_dir} unavailable: {e}");
+                None
+            }
+        };
+        (claude, disk)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        tracing::error!("dashboard task panicked: {e}");
+        (None, None)
+    });
+
+    Json(DashboardResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: crate::metrics::uptime().as_secs(),
+        sessions,
+        attached_clients,
+        claude_sessions,
+        sftp,
+        disk,
+        recent_errors: user.is_admin().then(recent_errors),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn error_layer_keeps_warnings_and_errors() {
+        let subscriber = tracing_subscriber::registry().with(ErrorLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("dashboard-test: not kept");
+            tracing::warn!(path = "/tmp/x", "dashboard-test: disk is slow");
+            tracing::error!("dashboard-test: {}", "broken");
+        });
+        let ours: Vec<_> = recent_errors()
+            .into_iter()
+            .filter(|e| e.message.starts_with("dashboard-test"))
+            .collect();
+        assert_eq!(ours.len(), 2);
+        assert_eq!(ours[0].level, "error");
+        assert_eq!(ours[0].message, "dashboard-test: broken");
+        assert_eq!(ours[1].level, "warn");
+        assert_eq!(ours[1].message, "dashboard-test: disk is slow path=/tmp/x");
+    }
+
+    #[test]
+    fn claude_is_found_below_session_shells() {
+        let table: HashMap<u32, (u32, String)> = [
+            (1, (0, "init".into())),
+            (100, (1, "bash".into())),
+            (101, (100, "claude".into())),
+            (200, (1, "pwsh".into())),
+            (201, (200, "node".into())),
+            (202, (201, "Claude".into())),
+            (300, (1, "zsh".into())),
+            (400, (1, "claude".into())),
+        ]
+        .into_iter()
+        .collect();
+        let roots = vec![
+            ("work".to_string(), 100),
+            ("build".to_string(), 200),
+            ("idle".to_string(), 300),
+        ];
+        assert_eq!(
+            sessions_running(&table, &roots, CLAUDE_PROGRAM),
+            ["build", "work"]
+        );
+        assert!(sessions_running(&table, &[], CLAUDE_PROGRAM).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn proc_stat_with_odd_command_names() {
+        assert_eq!(
+            parse_proc_stat("4242 (claude) S 4100 4242 4100 34816"),
+            Some((4100, "claude".to_string()))
+        );
+        assert_eq!(
+            parse_proc_stat("7 (a) b (c)) R 1 7 7 0"),
+            Some((1, "a) b (c)".to_string()))
+        );
+        assert_eq!(parse_proc_stat("garbage"), None);
+    }
+
+    #[test]
+    fn disk_space_of_temp_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let (total, available) = disk_space(dir.path()).unwrap();
+        assert!(total > 0);
+        assert!(available <= total);
+        assert!(disk_space(&dir.path().join("missing")).is_err());
+    }
+}
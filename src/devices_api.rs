@@ -0,0 +1,324 @@
+// ログイン端末の管理（自分の端末の一覧・名前変更・取り消し）
+// テスト: tests/api_test.rs の devices セクション
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::AppState;
+use crate::auth::{AuthUser, DEVICE_COOKIE, extract_cookie};
+use crate::store::{Device, MAX_DEVICES_PER_ACCOUNT};
+
+const MAX_DEVICE_NAME_CHARS: usize = 64;
+const MAX_USER_AGENT_CHARS: usize = 512;
+/// last_seen / last_ip を書き戻す最短間隔（リクエストごとには書かない）
+const TOUCH_INTERVAL_SECS: u64 = 300;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// 制御文字を除いて前後の空白を削る。空または 64 文字超は None
+fn clean_device_name(name: &str) -> Option<String> {
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim();
+    (!name.is_empty() && name.chars().count() <= MAX_DEVICE_NAME_CHARS).then(|| name.to_string())
+}
+
+/// User-Agent から端末の既定名を作る（"Safari on iPhone" など）
+pub fn describe_user_agent(user_agent: &str) -> String {
+    // 先にマッチしたものを採用する（Edge の UA は Chrome/ と Safari/ も含む）
+    const BROWSERS: &[(&str, &str)] = &[
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("CriOS/", "Chrome"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ];
+    const PLATFORMS: &[(&str, &str)] = &[
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("CrOS", "ChromeOS"),
+        ("Windows", "Windows"),
+        ("Macintosh", "macOS"),
+        ("Linux", "Linux"),
+    ];
+    let find = |table: &[(&str, &'static str)]| {
+        table
+            .iter()
+            .find(|(needle, _)| user_agent.contains(needle))
+            .map(|(_, name)| *name)
+    };
+    match (find(BROWSERS), find(PLATFORMS)) {
+        (Some(browser), Some(platform)) => format!("{browser} on {platform}"),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        // curl/8.0 などはプログラム名だけ
+        (None, None) => user_agent
+            .split(['/', ' '])
+            .next()
+            .and_then(clean_device_name)
+            .unwrap_or_else(|| "Unknown device".to_string()),
+    }
+}
+
+/// ログイン時に端末を登録する（`user` が None ならオーナー）。
+/// `den_device` Cookie の端末が同じアカウントのものなら新しく作らず使い回し、
+/// 名前を指定したときだけ付け直す。保存に失敗したら None（端末に紐づかないトークンになる）。
+pub(crate) fn register_device(
+    state: &AppState,
+    user: Option<&str>,
+    headers: &HeaderMap,
+    ip: Option<IpAddr>,
+    name: Option<&str>,
+) -> Option<Device> {
+    let user_agent: String = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .chars()
+        .take(MAX_USER_AGENT_CHARS)
+        .collect();
+    let fingerprint = hex::encode(Sha256::digest(user_agent.as_bytes()))[..16].to_string();
+    let name = name.and_then(clean_device_name);
+    let previous = extract_cookie(headers, DEVICE_COOKIE);
+    let now = unix_now();
+
+    let result = state.store.update_devices(|devices| {
+        if let Some(device) = previous.and_then(|id| {
+            devices
+                .iter_mut()
+                .find(|d| d.id == id && d.user.as_deref() == user)
+        }) {
+            if let Some(name) = name {
+                device.name = name;
+            }
+            device.user_agent = user_agent;
+            device.fingerprint = fingerprint;
+            device.last_seen = now;
+            device.last_ip = ip;
+            return device.clone();
+        }
+        let owned = devices.iter().filter(|d| d.user.as_deref() == user).count();
+        if owned >= MAX_DEVICES_PER_ACCOUNT
+            && let Some(stale) = devices
+                .iter()
+                .filter(|d| d.user.as_deref() == user)
+                .min_by_key(|d| d.last_seen)
+                .map(|d| d.id.clone())
+        {
+            devices.retain(|d| d.id != stale);
+        }
+        let device = Device {
+            id: hex::encode(rand::random::<[u8; 8]>()),
+            name: name.unwrap_or_else(|| describe_user_agent(&user_agent)),
+            user: user.map(str::to_string),
+            user_agent,
+            fingerprint,
+            created_at: now,
+            last_seen: now,
+            last_ip: ip,
+        };
+        devices.push(device.clone());
+        device
+    });
+    match result {
+        Ok(device) => {
+            tracing::info!("Device signed in: {} ({})", device.name, device.id);
+            Some(device)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to register device: {e}");
+            None
+        }
+    }
+}
+
+/// 利用中の端末の last_seen / last_ip を更新する（`TOUCH_INTERVAL_SECS` ごと、または IP が変わったとき）
+pub(crate) fn touch_device(state: &AppState, id: &str, ip: Option<IpAddr>) {
+    let now = unix_now();
+    let stale = state.store.get_device(id).is_some_and(|d| {
+        now.saturating_sub(d.last_seen) >= TOUCH_INTERVAL_SECS || (ip.is_some() && d.last_ip != ip)
+    });
+    if !stale {
+        return;
+    }
+    if let Err(e) = state.store.update_devices(|devices| {
+        if let Some(device) = devices.iter_mut().find(|d| d.id == id) {
+            device.last_seen = now;
+            device.last_ip = ip.or(device.last_ip);
+        }
+    }) {
+        tracing::warn!("Failed to update device {id}: {e}");
+    }
+}
+
+/// 端末の登録を外す（その端末のトークンは以後無効）
+pub(crate) fn remove_device(state: &AppState, id: &str) {
+    if let Err(e) = state
+        .store
+        .update_devices(|devices| devices.retain(|d| d.id != id))
+    {
+        tracing::warn!("Failed to remove device {id}: {e}");
+    }
+}
+
+/// アカウントの端末をすべて外す（アカウント削除・管理者によるパスワード再設定）
+pub(crate) fn remove_user_devices(state: &AppState, user: &str) {
+    if let Err(e) = state
+        .store
+        .update_devices(|devices| devices.retain(|d| d.user.as_deref() != Some(user)))
+    {
+        tracing::warn!("Failed to remove devices of {user}: {e}");
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+    pub user_agent: String,
+    pub fingerprint: String,
+    pub created_at: u64,
+    pub last_seen: u64,
+    pub last_ip: Option<IpAddr>,
+    /// このリクエストを送っている端末
+    pub current: bool,
+}
+
+impl DeviceInfo {
+    fn new(device: Device, current: Option<&str>) -> Self {
+        Self {
+            current: current == Some(device.id.as_str()),
+            id: device.id,
+            name: device.name,
+            user_agent: device.user_agent,
+            fingerprint: device.fingerprint,
+            created_at: device.created_at,
+            last_seen: device.last_seen,
+            last_ip: device.last_ip,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenameDeviceRequest {
+    pub name: String,
+}
+
+/// GET /api/devices — 自分の端末（最近使った順）
+pub async fn list_devices(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> Json<Vec<DeviceInfo>> {
+    let mut devices: Vec<Device> = state
+        .store
+        .load_devices()
+        .into_iter()
+        .filter(|d| d.user == user.username)
+        .collect();
+    devices.sort_by_key(|d| std::cmp::Reverse(d.last_seen));
+    Json(
+        devices
+            .into_iter()
+            .map(|d| DeviceInfo::new(d, user.device_id.as_deref()))
+            .collect(),
+    )
+}
+
+/// PUT /api/devices/{id}
+pub async fn rename_device(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Path(id): Path<String>,
+    Json(req): Json<RenameDeviceRequest>,
+) -> impl IntoResponse {
+    let Some(name) = clean_device_name(&req.name) else {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "device name must be 1-64 chars",
+        )
+            .into_response();
+    };
+    let result = state.store.update_devices(|devices| {
+        let device = devices
+            .iter_mut()
+            .find(|d| d.id == id && d.user == user.username)?;
+        device.name = name;
+        Some(device.clone())
+    });
+    match result {
+        Ok(Some(device)) => {
+            Json(DeviceInfo::new(device, user.device_id.as_deref())).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "device not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to save devices: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// DELETE /api/devices/{id} — その端末のログインを取り消す
+pub async fn revoke_device(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = state.store.update_devices(|devices| {
+        let before = devices.len();
+        devices.retain(|d| !(d.id == id && d.user == user.username));
+        devices.len() != before
+    });
+    match result {
+        Ok(true) => {
+            tracing::info!("Device revoked: {id}");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "device not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to save devices: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_agent_names() {
+        let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1";
+        assert_eq!(describe_user_agent(iphone), "Safari on iPhone");
+        let edge = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36 Edg/124.0";
+        assert_eq!(describe_user_agent(edge), "Edge on Windows");
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0";
+        assert_eq!(describe_user_agent(firefox), "Firefox on Linux");
+        assert_eq!(describe_user_agent("curl/8.5.0"), "curl");
+        assert_eq!(describe_user_agent(""), "Unknown device");
+    }
+
+    #[test]
+    fn device_names_are_cleaned() {
+        assert_eq!(
+            clean_device_name("  Work laptop\u{7} ").as_deref(),
+            Some("Work laptop")
+        );
+        assert_eq!(clean_device_name(" \t "), None);
+        assert!(clean_device_name(&"x".repeat(64)).is_some());
+        assert!(clean_device_name(&"x".repeat(65)).is_none());
+    }
+}
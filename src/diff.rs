@@ -0,0 +1,403 @@
+//! Line diff between two files, each either local (filer) or remote (SFTP).
+//!
+//! Myers O(ND) on the lines left after trimming the common prefix/suffix,
+//! grouped into unified-diff style hunks.
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::filer::api::{ErrorResponse, err, is_binary};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Default context lines around each change
+const DEFAULT_CONTEXT: usize = 3;
+/// Upper bound for requested context lines
+const MAX_CONTEXT: usize = 100;
+/// Edit distance beyond which we give up (bounds time and trace memory)
+const MAX_EDIT_DISTANCE: usize = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffSource {
+    Local,
+    Sftp,
+}
+
+#[derive(Deserialize)]
+pub struct DiffSide {
+    pub source: DiffSource,
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct DiffRequest {
+    /// Old side (`-` lines)
+    pub left: DiffSide,
+    /// New side (`+` lines)
+    pub right: DiffSide,
+    pub context: Option<usize>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Add,
+    Remove,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub text: String,
+}
+
+/// One `@@ -old_start,old_lines +new_start,new_lines @@` block.
+#[derive(Debug, Serialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Serialize)]
+pub struct DiffResponse {
+    left_path: String,
+    right_path: String,
+    identical: bool,
+    /// Either side is binary; no hunks are produced
+    binary: bool,
+    hunks: Vec<Hunk>,
+}
+
+/// POST /api/diff
+pub async fn diff(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DiffRequest>,
+) -> Result<Json<DiffResponse>, ApiError> {
+    let context = req.context.unwrap_or(DEFAULT_CONTEXT).min(MAX_CONTEXT);
+    let (left_path, left) = read_side(&state, &req.left).await?;
+    let (right_path, right) = read_side(&state, &req.right).await?;
+
+    if left == right {
+        return Ok(Json(DiffResponse {
+            left_path,
+            right_path,
+            identical: true,
+            binary: is_binary(&left),
+            hunks: Vec::new(),
+        }));
+    }
+    if is_binary(&left) || is_binary(&right) {
+        return Ok(Json(DiffResponse {
+            left_path,
+            right_path,
+            identical: false,
+            binary: true,
+            hunks: Vec::new(),
+        }));
+    }
+
+    let hunks = tokio::task::spawn_blocking(move || {
+        let old = String::from_utf8_lossy(&left);
+        let new = String::from_utf8_lossy(&right);
+        diff_lines(&old, &new, context)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+    .ok_or_else(|| {
+        err(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Files differ too much to diff",
+        )
+    })?;
+
+    Ok(Json(DiffResponse {
+        left_path,
+        right_path,
+        identical: false,
+        binary: false,
+        hunks,
+    }))
+}
+
+async fn read_side(state: &AppState, side: &DiffSide) -> Result<(String, Vec<u8>), ApiError> {
+    match side.source {
+        DiffSource::Local => {
+            let raw = side.path.clone();
+            let roots = state.filer_roots.clone();
+            let (path, data) =
+                tokio::task::spawn_blocking(move || crate::filer::api::read_limited(&roots, &raw))
+                    .await
+                    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))??;
+            Ok((path.to_string_lossy().into_owned(), data))
+        }
+        DiffSource::Sftp => crate::sftp::api::read_limited(state, &side.path).await,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Diff two texts line by line. `None` if the edit distance is too large.
+pub fn diff_lines(old: &str, new: &str, context: usize) -> Option<Vec<Hunk>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut ops = vec![Op::Equal; prefix];
+    ops.extend(myers(
+        &a[prefix..a.len() - suffix],
+        &b[prefix..b.len() - suffix],
+        MAX_EDIT_DISTANCE,
+    )?);
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+
+    Some(build_hunks(&ops, &a, &b, context))
+}
+
+/// Shortest edit script (Myers 1986). Keeps one V snapshot per edit step,
+/// trimmed to the live diagonals, so memory is O(D²).
+fn myers(a: &[&str], b: &[&str], max_d: usize) -> Option<Vec<Op>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let offset = n + m;
+    let mut v = vec![0isize; (2 * offset + 2) as usize];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let idx = |k: isize| (k + offset) as usize;
+
+    let limit = (offset as usize).min(max_d) as isize;
+    let mut found = false;
+    'outer: for d in 0..=limit {
+        trace.push(v[idx(-d)..=idx(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                found = true;
+                break 'outer;
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let snap = &trace[d as usize];
+        let get = |k: isize| snap[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+        x = prev_x;
+        y = prev_y;
+    }
+    while x > 0 && y > 0 {
+        ops.push(Op::Equal);
+        x -= 1;
+        y -= 1;
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+fn build_hunks(ops: &[Op], a: &[&str], b: &[&str], context: usize) -> Vec<Hunk> {
+    // (old, new) line index before each op
+    let mut pos = Vec::with_capacity(ops.len() + 1);
+    let (mut i, mut j) = (0usize, 0usize);
+    for op in ops {
+        pos.push((i, j));
+        match op {
+            Op::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+    pos.push((i, j));
+
+    let mut hunks = Vec::new();
+    let mut next = 0;
+    while let Some(first) = (next..ops.len()).find(|&k| ops[k] != Op::Equal) {
+        let start = first.saturating_sub(context).max(next);
+        // Extend while the gap to the next change fits in 2×context
+        let mut end = first;
+        loop {
+            while end < ops.len() && ops[end] != Op::Equal {
+                end += 1;
+            }
+            let gap = ops[end..].iter().take_while(|&&op| op == Op::Equal).count();
+            if end + gap < ops.len() && gap <= 2 * context {
+                end += gap;
+            } else {
+                break;
+            }
+        }
+        let stop = (end + context).min(ops.len());
+
+        let (old_from, new_from) = pos[start];
+        let (old_to, new_to) = pos[stop];
+        let lines = ops[start..stop]
+            .iter()
+            .zip(&pos[start..stop])
+            .map(|(op, &(oi, ni))| match op {
+                Op::Equal => DiffLine {
+                    kind: LineKind::Context,
+                    text: a[oi].to_string(),
+                },
+                Op::Delete => DiffLine {
+                    kind: LineKind::Remove,
+                    text: a[oi].to_string(),
+                },
+                Op::Insert => DiffLine {
+                    kind: LineKind::Add,
+                    text: b[ni].to_string(),
+                },
+            })
+            .collect();
+        let old_lines = old_to - old_from;
+        let new_lines = new_to - new_from;
+        hunks.push(Hunk {
+            // unified diff convention: empty ranges point at the line before
+            old_start: if old_lines == 0 {
+                old_from
+            } else {
+                old_from + 1
+            },
+            old_lines,
+            new_start: if new_lines == 0 {
+                new_from
+            } else {
+                new_from + 1
+            },
+            new_lines,
+            lines,
+        });
+        next = stop;
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(hunks: &[Hunk]) -> String {
+        let mut out = String::new();
+        for h in hunks {
+            out.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                h.old_start, h.old_lines, h.new_start, h.new_lines
+            ));
+            for l in &h.lines {
+                let sign = match l.kind {
+                    LineKind::Context => ' ',
+                    LineKind::Add => '+',
+                    LineKind::Remove => '-',
+                };
+                out.push(sign);
+                out.push_str(&l.text);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn identical_has_no_hunks() {
+        assert!(diff_lines("a\nb\n", "a\nb\n", 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn single_change_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4x\n5\n6\n7\n8\n";
+        assert_eq!(
+            render(&diff_lines(old, new, 2).unwrap()),
+            "@@ -2,5 +2,5 @@\n 2\n 3\n-4\n+4x\n 5\n 6\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_split_into_hunks() {
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new: String = (1..=20)
+            .filter(|&i| i != 19)
+            .map(|i| {
+                if i == 2 {
+                    "two\n".to_string()
+                } else {
+                    format!("{i}\n")
+                }
+            })
+            .collect();
+        let hunks = diff_lines(&old, &new, 1).unwrap();
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(render(&hunks[1..]), "@@ -18,3 +18,2 @@\n 18\n-19\n 20\n");
+    }
+
+    #[test]
+    fn insert_into_empty() {
+        let hunks = diff_lines("", "a\nb\n", 3).unwrap();
+        assert_eq!(render(&hunks), "@@ -0,0 +1,2 @@\n+a\n+b\n");
+    }
+
+    #[test]
+    fn myers_finds_minimal_script() {
+        let a = ["a", "b", "c", "a", "b", "b", "a"];
+        let b = ["c", "b", "a", "b", "a", "c"];
+        let ops = myers(&a, &b, 100).unwrap();
+        let edits = ops.iter().filter(|&&op| op != Op::Equal).count();
+        assert_eq!(edits, 5);
+    }
+
+    #[test]
+    fn edit_distance_limit() {
+        let old: String = (0..50).map(|i| format!("a{i}\n")).collect();
+        let new: String = (0..50).map(|i| format!("b{i}\n")).collect();
+        assert!(diff_lines(&old, &new, 3).is_some());
+        let a: Vec<&str> = old.lines().collect();
+        let b: Vec<&str> = new.lines().collect();
+        assert!(myers(&a, &b, 10).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+//! Global server → client event channel.
+//!
+//! Long-running operations (SFTP transfers, directory watches, etc.) publish
+//! progress here and every connected `/api/events` WebSocket of the account
+//! that started them receives a JSON copy. Events are fire-and-forget: a
+//! client that connects late or lags simply misses them.
+
+use axum::{
+    Extension,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    response::IntoResponse,
+};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::filer::watch::FsChangeKind;
+use crate::sftp::transfer::TransferDirection;
+use crate::shutdown::{Shutdown, client_message};
+
+/// Broadcast buffer per subscriber. Progress events are throttled at the
+/// source, so this only has to absorb short bursts.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Heartbeat response (same contract as the terminal WS).
+const PONG_MSG: &str = r#"{"type":"pong"}"#;
+
+/// Server-side events pushed over `/api/events`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    TransferProgress {
+        id: String,
+        direction: TransferDirection,
+        path: String,
+        transferred: u64,
+        total: Option<u64>,
+        /// Average rate since the transfer started (bytes/sec)
+        rate: u64,
+    },
+    TransferDone {
+        id: String,
+        direction: TransferDirection,
+        path: String,
+        transferred: u64,
+    },
+    TransferError {
+        id: String,
+        direction: TransferDirection,
+        path: String,
+        error: String,
+    },
+    TransferCancelled {
+        id: String,
+        direction: TransferDirection,
+        path: String,
+    },
+    /// A watched directory (see `/api/filer/watch`) changed
+    FsChange {
+        watch_id: String,
+        kind: FsChangeKind,
+        path: String,
+        /// Rename source, when known
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from: Option<String>,
+    },
+    /// Directory size job (see `/api/filer/du`) progress
+    DuProgress {
+        id: String,
+        path: String,
+        size: u64,
+        files: u64,
+    },
+    DuDone {
+        id: String,
+        path: String,
+        size: u64,
+        files: u64,
+        dirs: u64,
+    },
+    DuError {
+        id: String,
+        path: String,
+        error: String,
+    },
+    DuCancelled {
+        id: String,
+        path: String,
+    },
+}
+
+/// A serialized event and the account it belongs to.
+struct Published {
+    /// Username (None = the owner); only that account's sockets receive it
+    account: Option<String>,
+    json: Arc<str>,
+}
+
+#[derive(Clone)]
+pub struct EventHub {
+    tx: broadcast::Sender<Arc<Published>>,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    /// Serialize once and fan out to the subscribers of `account`
+    /// (None = the owner). No-op without listeners.
+    pub fn publish(&self, account: Option<&str>, event: &Event) {
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        match serde_json::to_string(event) {
+            Ok(json) => {
+                let _ = self.tx.send(Arc::new(Published {
+                    account: account.map(str::to_string),
+                    json: Arc::from(json),
+                }));
+            }
+            Err(e) => tracing::warn!("events: serialize failed: {e}"),
+        }
+    }
+
+    /// Events of `account` only (None = the owner).
+    pub fn subscribe(&self, account: Option<&str>) -> Subscription {
+        Subscription {
+            rx: self.tx.subscribe(),
+            account: account.map(str::to_string),
+        }
+    }
+}
+
+pub struct Subscription {
+    rx: broadcast::Receiver<Arc<Published>>,
+    account: Option<String>,
+}
+
+impl Subscription {
+    /// Next event for this account, skipping other accounts' events.
+    pub async fn recv(&mut self) -> Result<Arc<str>, broadcast::error::RecvError> {
+        loop {
+            let published = self.rx.recv().await?;
+            if published.account == self.account {
+                return Ok(Arc::clone(&published.json));
+            }
+        }
+    }
+}
+
+/// Client → server commands on the events socket.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum EventsCommand {
+    #[serde(rename = "ping")]
+    Ping,
+}
+
+/// GET /api/events (WebSocket)
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> axum::response::Response {
+    let rx = state.events.subscribe(user.username.as_deref());
+    let shutdown = state.shutdown.clone();
+    ws.on_upgrade(move |socket| handle_socket(socket, rx, shutdown))
+        .into_response()
+}
+
+async fn handle_socket(socket: WebSocket, mut rx: Subscription, shutdown: Shutdown) {
+    let _open = crate::metrics::SocketGuard::open(&crate::metrics::EVENT_SOCKETS);
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (pong_tx, mut pong_rx) = tokio::sync::mpsc::channel::<()>(4);
+
+    let outbound = async {
+        loop {
+            let msg: Arc<str> = tokio::select! {
+                pong = pong_rx.recv() => match pong {
+                    Some(()) => Arc::from(PONG_MSG),
+                    None => break,
+                },
+                recv = rx.recv() => match recv {
+                    Ok(msg) => msg,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::debug!("events: client lagged {n} events");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                restart = shutdown.started() => {
+                    let _ = ws_tx
+                        .send(Message::Text(client_message(restart).into()))
+                        .await;
+                    let _ = ws_tx.close().await;
+                    break;
+                }
+            };
+            if ws_tx
+                .send(Message::Text(msg.as_ref().into()))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    };
+
+    let inbound = async move {
+        while let Some(Ok(msg)) = ws_rx.next().await {
+            match msg {
+                Message::Text(text) => {
+                    if let Ok(EventsCommand::Ping) = serde_json::from_str(&text) {
+                        let _ = pong_tx.try_send(());
+                    }
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = outbound => {},
+        _ = inbound => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_serializes_with_type_tag() {
+        let json = serde_json::to_value(Event::TransferCancelled {
+            id: "t1".to_string(),
+            direction: TransferDirection::Download,
+            path: "/tmp/a".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json["type"], "transfer_cancelled");
+        assert_eq!(json["id"], "t1");
+    }
+
+    #[tokio::test]
+    async fn publish_reaches_subscriber() {
+        let hub = EventHub::new();
+        let mut rx = hub.subscribe(None);
+        hub.publish(
+            None,
+            &Event::TransferDone {
+                id: "t1".to_string(),
+                direction: TransferDirection::Upload,
+                path: "/tmp/a".to_string(),
+                transferred: 3,
+            },
+        );
+        let msg = rx.recv().await.unwrap();
+        assert!(msg.contains(r#""type":"transfer_done""#));
+    }
+
+    #[tokio::test]
+    async fn events_reach_only_their_account() {
+        let hub = EventHub::new();
+        let mut owner = hub.subscribe(None);
+        let mut alice = hub.subscribe(Some("alice"));
+        let du = |id: &str| Event::DuCancelled {
+            id: id.to_string(),
+            path: "/tmp".to_string(),
+        };
+        hub.publish(Some("alice"), &du("alice-job"));
+        hub.publish(None, &du("owner-job"));
+        hub.publish(Some("alice"), &du("alice-job-2"));
+
+        assert!(owner.recv().await.unwrap().contains("owner-job"));
+        assert!(alice.recv().await.unwrap().contains("alice-job"));
+        assert!(alice.recv().await.unwrap().contains("alice-job-2"));
+    }
+}
@@ -6,37 +6,96 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{fs, io};
 
 use crate::AppState;
+use crate::archive::zip;
+
+use super::bookmarks::record_recent;
+use super::git::GitStatus;
+use super::journal::Journal;
+use super::roots::FilerRoots;
 
 // --- 定数 ---
 
+/// ダウンロードストリームの読み込み単位
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+/// ディレクトリ zip ダウンロードの合計サイズ上限（非圧縮）: 1GB
+const MAX_ZIP_TOTAL_SIZE: u64 = 1024 * 1024 * 1024;
+/// zip ストリームのチャンクサイズ
+const ZIP_CHUNK_SIZE: usize = 64 * 1024;
 /// テキスト読み込み上限: 10MB
 const MAX_READ_SIZE: u64 = 10 * 1024 * 1024;
-/// アップロード上限: 50MB
+/// `format=hex` のデフォルト範囲（ダンプは元の約 4 倍になる）
+const DEFAULT_HEX_BYTES: u64 = 4096;
+/// `format=hex` の範囲上限
+const MAX_HEX_BYTES: u64 = 256 * 1024;
+/// アップロード上限: 50MB（これより大きいファイルはチャンクアップロードを使う）
 const MAX_UPLOAD_SIZE: usize = 50 * 1024 * 1024;
+/// multipart アップロードのリクエストボディ上限（境界やパスフィールド分の余裕込み）
+pub const UPLOAD_BODY_LIMIT: usize = MAX_UPLOAD_SIZE + 1024 * 1024;
+/// 衝突時リネーム (`name (N).ext`) の試行上限
+const MAX_RENAME_ATTEMPTS: u32 = 1000;
+/// bulk リクエストあたりの操作数上限
+const MAX_BULK_OPS: usize = 500;
+/// tail のデフォルト行数
+const DEFAULT_TAIL_LINES: usize = 100;
+/// tail の行数上限
+const MAX_TAIL_LINES: usize = 10_000;
+/// tail で末尾から逆方向に読むブロックサイズ
+const TAIL_SCAN_BLOCK: usize = 64 * 1024;
+/// preview のデフォルト行数
+const DEFAULT_PREVIEW_LINES: usize = 200;
+/// preview の行数上限
+const MAX_PREVIEW_LINES: usize = 10_000;
+/// preview で読む先頭バイト数のデフォルト
+const DEFAULT_PREVIEW_BYTES: u64 = 256 * 1024;
+/// 総行数を数えるファイルサイズ上限（超えたら total_lines は null）
+const MAX_LINE_COUNT_SIZE: u64 = 100 * 1024 * 1024;
 /// 検索深さ上限
 const MAX_SEARCH_DEPTH: u32 = 10;
 /// 検索結果上限
 const MAX_SEARCH_RESULTS: usize = 100;
+/// 並列検索のワーカースレッド上限
+const MAX_SEARCH_THREADS: usize = 8;
+/// 内容検索の読み込みバッファ（先頭をバイナリ判定にも使う）
+const SEARCH_READ_BUF: usize = 64 * 1024;
 
 // --- リクエスト/レスポンス型 ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListQuery {
     pub path: String,
     #[serde(default)]
     pub show_hidden: bool,
+    /// git 作業ツリー内なら各エントリに git の状態を付ける
+    #[serde(default)]
+    pub git: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct FilerEntry {
     name: String,
     is_dir: bool,
     size: u64,
     modified: Option<String>,
+    /// 書き込み不可（Windows の読み取り専用属性 / unix で書き込みビットなし）
+    readonly: bool,
+    /// Windows の隠し属性（ドットファイルは名前で判定する）
+    hidden: bool,
+    /// unix パーミッションビット（Windows では None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    /// シンボリックリンク / ジャンクション（is_dir・size はリンク先のもの）
+    is_symlink: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_target: Option<String>,
+    /// `list?git=true` のときのみ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    git: Option<GitStatus>,
 }
 
 impl FilerEntry {
@@ -46,7 +105,58 @@ impl FilerEntry {
             is_dir,
             size,
             modified,
+            readonly: false,
+            hidden: false,
+            mode: None,
+            is_symlink: false,
+            link_target: None,
+            git: None,
+        }
+    }
+
+    /// リンクとして印を付ける（リンク先が読めなければ target は None）
+    pub fn with_link(mut self, target: Option<String>) -> Self {
+        self.is_symlink = true;
+        self.link_target = target;
+        self
+    }
+
+    /// unix パーミッションを設定（readonly も書き込みビットから導出する）
+    pub fn with_mode(mut self, mode: Option<u32>) -> Self {
+        self.mode = mode.map(|m| m & 0o7777);
+        self.readonly = mode.is_some_and(|m| m & 0o222 == 0);
+        self
+    }
+
+    fn from_metadata(name: String, metadata: &fs::Metadata) -> Self {
+        Self {
+            name,
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: modified_rfc3339(metadata),
+            readonly: metadata.permissions().readonly(),
+            hidden: has_hidden_attribute(metadata),
+            mode: unix_mode(metadata),
+            is_symlink: false,
+            link_target: None,
+            git: None,
+        }
+    }
+
+    /// `path` の lstat から作る。リンクならリンク先の種別・サイズを使う
+    /// （壊れたリンクはリンク自体の情報のまま）。
+    fn from_path(name: String, path: &Path, metadata: &fs::Metadata) -> Self {
+        if !metadata.is_symlink() {
+            return Self::from_metadata(name, metadata);
         }
+        let entry = match fs::metadata(path) {
+            Ok(target_meta) => Self::from_metadata(name, &target_meta),
+            Err(_) => Self::from_metadata(name, metadata),
+        };
+        let target = fs::read_link(path)
+            .ok()
+            .map(|t| strip_verbatim_prefix(&t).to_string_lossy().into_owned());
+        entry.with_link(target)
     }
 
     pub fn name(&self) -> &str {
@@ -58,7 +168,7 @@ impl FilerEntry {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct FilerListing {
     path: String,
     parent: Option<String>,
@@ -83,17 +193,49 @@ impl FilerListing {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ReadQuery {
     pub path: String,
 }
 
-#[derive(Serialize)]
+/// ローカル read 用: `offset` / `length` 指定で部分読み込み
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReadRangeQuery {
+    pub path: String,
+    pub offset: Option<u64>,
+    pub length: Option<u64>,
+    #[serde(default)]
+    pub format: ReadFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadFormat {
+    #[default]
+    Text,
+    /// `hexdump -C` 形式（offset / hex / ASCII）。バイナリでも内容を返す
+    Hex,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct FileContent {
     path: String,
     content: String,
     size: u64,
     is_binary: bool,
+    /// 部分読み込み時の開始位置
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<u64>,
+    /// 部分読み込み時のファイル全体のサイズ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_size: Option<u64>,
+    /// 内容のハッシュ。書き戻し時に `if_match` として渡すと競合を検出できる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<String>,
 }
 
 impl FileContent {
@@ -103,38 +245,321 @@ impl FileContent {
             content,
             size,
             is_binary,
+            offset: None,
+            total_size: None,
+            hash: None,
+            mtime: None,
         }
     }
+
+    /// 全体を読んだときのバージョン情報（ハッシュ・更新日時）を付ける
+    pub fn with_version(mut self, data: &[u8], mtime: Option<String>) -> Self {
+        self.hash = Some(content_hash(data));
+        self.mtime = mtime;
+        self
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TailQuery {
+    pub path: String,
+    /// Number of trailing lines (default 100)
+    pub lines: Option<usize>,
+    /// Follow mode: resume from the `size` returned by the previous call
+    pub offset: Option<u64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TailResponse {
+    path: String,
+    content: String,
+    /// File offset `content` starts at
+    offset: u64,
+    /// End of the returned data — pass back as `offset` to follow
+    size: u64,
+    /// Bytes between the requested start and `offset` were skipped
+    truncated: bool,
+    /// File shrank below the given `offset` (rotated / truncated)
+    rotated: bool,
+    is_binary: bool,
+}
+
+impl TailResponse {
+    /// `data` was read from `start`; `from` is where the caller asked to start.
+    pub fn new(path: String, data: &[u8], start: u64, from: u64, rotated: bool) -> Self {
+        let binary = is_binary(data);
+        let content = if binary {
+            String::new()
+        } else {
+            String::from_utf8_lossy(data).into_owned()
+        };
+        Self {
+            path,
+            content,
+            offset: start,
+            size: start + data.len() as u64,
+            truncated: start > from,
+            rotated,
+            is_binary: binary,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PreviewQuery {
+    pub path: String,
+    /// Number of leading lines (default 200)
+    pub lines: Option<usize>,
+    /// Max bytes read from the start of the file (default 256KB)
+    pub bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-8-bom")]
+    Utf8Bom,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    /// Not valid UTF-8 (shown with replacement characters)
+    #[serde(rename = "unknown")]
+    Unknown,
+    #[serde(rename = "binary")]
+    Binary,
+}
+
+impl TextEncoding {
+    /// BOM → null bytes (binary) → UTF-8 validity. `head` may end mid-character.
+    fn detect(head: &[u8]) -> Self {
+        if head.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            Self::Utf8Bom
+        } else if head.starts_with(&[0xFF, 0xFE]) {
+            Self::Utf16Le
+        } else if head.starts_with(&[0xFE, 0xFF]) {
+            Self::Utf16Be
+        } else if is_binary(head) {
+            Self::Binary
+        } else if std::str::from_utf8(utf8_prefix(head)).is_ok() {
+            Self::Utf8
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PreviewResponse {
+    path: String,
+    content: String,
+    /// Lines in `content`
+    lines: usize,
+    /// Lines in the whole file (null for very large or UTF-16 files)
+    total_lines: Option<u64>,
+    /// File size
+    size: u64,
+    /// `content` does not cover the whole file
+    truncated: bool,
+    encoding: TextEncoding,
+    is_binary: bool,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct WriteRequest {
     pub path: String,
     pub content: String,
+    /// read が返した `hash`。指定時、現在の内容と一致しなければ 409
+    #[serde(default)]
+    pub if_match: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WriteResponse {
+    /// 書き込んだ内容のハッシュ（次回の `if_match` に使う）
+    hash: String,
+    mtime: Option<String>,
+}
+
+impl WriteResponse {
+    pub fn new(content: &[u8], mtime: Option<String>) -> Self {
+        Self {
+            hash: content_hash(content),
+            mtime,
+        }
+    }
+}
+
+/// 楽観的排他制御用の内容ハッシュ（SHA-256 hex）
+pub(crate) fn content_hash(data: &[u8]) -> String {
+    use sha2::Digest;
+
+    hex::encode(sha2::Sha256::digest(data))
+}
+
+/// `if_match` と現在の内容を比較する。`current` が None ならファイルが消えている
+pub(crate) fn check_if_match(
+    if_match: Option<&str>,
+    current: Option<&[u8]>,
+) -> Result<(), ApiError> {
+    let Some(expected) = if_match else {
+        return Ok(());
+    };
+    match current {
+        Some(data) if content_hash(data) == expected => Ok(()),
+        Some(_) => Err(err(
+            StatusCode::CONFLICT,
+            "File changed on disk since it was read",
+        )),
+        None => Err(err(
+            StatusCode::CONFLICT,
+            "File was deleted since it was read",
+        )),
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct MkdirRequest {
     pub path: String,
 }
 
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateRequest {
+    pub path: String,
+    /// Settings の `file_templates` から選ぶテンプレート名（省略時は空ファイル）
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SymlinkRequest {
+    /// 作成するリンクのパス
+    pub path: String,
+    /// リンク先（相対パスはリンクのディレクトリ基準、そのまま保存される）
+    pub target: String,
+}
+
+/// 省略した属性は変更しない
 #[derive(Deserialize)]
+pub struct AttributesRequest {
+    pub path: String,
+    pub readonly: Option<bool>,
+    /// Windows のみ
+    pub hidden: Option<bool>,
+    /// unix のみ。8 進数文字列（`"644"`, `"0755"`）
+    pub mode: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct RenameRequest {
     pub from: String,
     pub to: String,
 }
 
+/// 宛先が既に存在する場合の挙動
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// 409 を返す
+    #[default]
+    Fail,
+    /// 既存の宛先を削除してから書き込む
+    Overwrite,
+    /// `name (1).ext` のように空いている名前を探す
+    Rename,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CopyMoveRequest {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CopyMoveResponse {
+    /// 実際の宛先パス（rename ポリシーで変わりうる）
+    path: String,
+}
+
+impl CopyMoveResponse {
+    pub(crate) fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BulkOp {
+    Delete(DeleteQuery),
+    Copy(CopyMoveRequest),
+    Move(CopyMoveRequest),
+}
+
 #[derive(Deserialize)]
+pub struct BulkRequest {
+    pub ops: Vec<BulkOp>,
+    #[serde(default)]
+    pub stop_on_error: bool,
+}
+
+#[derive(Serialize)]
+pub struct BulkResult {
+    index: usize,
+    ok: bool,
+    /// copy/move の実際の宛先
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BulkResponse {
+    results: Vec<BulkResult>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct DeleteQuery {
     pub path: String,
+    /// true ならゴミ箱へ移動（`/api/filer/trash` から復元可能）
+    #[serde(default)]
+    pub trash: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct DownloadQuery {
     pub path: String,
 }
 
 #[derive(Deserialize)]
+pub struct DownloadDirQuery {
+    pub path: String,
+    #[serde(default)]
+    pub show_hidden: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// 大文字小文字を区別しない部分一致
+    #[default]
+    Substring,
+    /// ファイル名に対する glob (`*.rs`, `test_?.py`)
+    Glob,
+    Regex,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SearchQuery {
     pub path: String,
     pub query: String,
@@ -142,9 +567,122 @@ pub struct SearchQuery {
     pub content: bool,
     #[serde(default)]
     pub show_hidden: bool,
+    #[serde(default)]
+    pub mode: SearchMode,
+    /// .gitignore / .ignore に従ってスキップする（ローカルのみ）
+    #[serde(default = "default_true")]
+    pub gitignore: bool,
+    /// 除外する glob パターン（カンマ区切り, 例: `node_modules,*.min.js`）
+    #[serde(default)]
+    pub exclude: Option<String>,
 }
 
-#[derive(Serialize)]
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgo {
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgo {
+    /// coreutils command printing `<hex>  <path>`
+    pub fn command(self) -> &'static str {
+        match self {
+            ChecksumAlgo::Md5 => "md5sum",
+            ChecksumAlgo::Sha1 => "sha1sum",
+            ChecksumAlgo::Sha256 => "sha256sum",
+            ChecksumAlgo::Sha512 => "sha512sum",
+        }
+    }
+
+    pub fn hex_len(self) -> usize {
+        match self {
+            ChecksumAlgo::Md5 => 32,
+            ChecksumAlgo::Sha1 => 40,
+            ChecksumAlgo::Sha256 => 64,
+            ChecksumAlgo::Sha512 => 128,
+        }
+    }
+
+    pub fn hasher(self) -> ChecksumHasher {
+        use sha2::Digest;
+        match self {
+            ChecksumAlgo::Md5 => ChecksumHasher::Md5(md5::Md5::new()),
+            ChecksumAlgo::Sha1 => ChecksumHasher::Sha1(sha1::Sha1::new()),
+            ChecksumAlgo::Sha256 => ChecksumHasher::Sha256(sha2::Sha256::new()),
+            ChecksumAlgo::Sha512 => ChecksumHasher::Sha512(sha2::Sha512::new()),
+        }
+    }
+}
+
+/// Incremental hasher for a `ChecksumAlgo`.
+pub enum ChecksumHasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+}
+
+impl ChecksumHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        match self {
+            ChecksumHasher::Md5(h) => h.update(data),
+            ChecksumHasher::Sha1(h) => h.update(data),
+            ChecksumHasher::Sha256(h) => h.update(data),
+            ChecksumHasher::Sha512(h) => h.update(data),
+        }
+    }
+
+    /// Lowercase hex digest
+    pub fn finish(self) -> String {
+        use sha2::Digest;
+        match self {
+            ChecksumHasher::Md5(h) => hex::encode(h.finalize()),
+            ChecksumHasher::Sha1(h) => hex::encode(h.finalize()),
+            ChecksumHasher::Sha256(h) => hex::encode(h.finalize()),
+            ChecksumHasher::Sha512(h) => hex::encode(h.finalize()),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChecksumQuery {
+    pub path: String,
+    #[serde(default)]
+    pub algo: ChecksumAlgo,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ChecksumResponse {
+    path: String,
+    algo: ChecksumAlgo,
+    hash: String,
+    /// "exec" (computed on the remote host) or "stream" (hashed by den;
+    /// always the case for local files)
+    source: &'static str,
+}
+
+impl ChecksumResponse {
+    pub fn new(path: String, algo: ChecksumAlgo, hash: String, source: &'static str) -> Self {
+        Self {
+            path,
+            algo,
+            hash,
+            source,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct SearchResult {
     path: String,
     is_dir: bool,
@@ -163,11 +701,17 @@ impl SearchResult {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, utoipa::ToSchema)]
 pub struct ErrorResponse {
     error: String,
 }
 
+impl ErrorResponse {
+    pub fn message(&self) -> &str {
+        &self.error
+    }
+}
+
 /// 共通エラー型
 type ApiError = (StatusCode, Json<ErrorResponse>);
 
@@ -225,6 +769,23 @@ pub(crate) fn resolve_path(raw: &str) -> Result<PathBuf, ApiError> {
     Ok(strip_verbatim_prefix(&result))
 }
 
+/// 最後の要素がシンボリックリンクでも辿らずに解決する（親ディレクトリのみ正規化）。
+/// リンク自体を対象にする delete / rename / copy / move 用。
+pub(crate) fn resolve_entry_path(raw: &str) -> Result<PathBuf, ApiError> {
+    if raw.is_empty() || raw.contains('\0') {
+        return resolve_path(raw);
+    }
+    let expanded = PathBuf::from(expand_home(raw));
+    match (expanded.parent(), expanded.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            let parent = resolve_path(&parent.to_string_lossy())?;
+            Ok(parent.join(name))
+        }
+        // ルートや `..` で終わるパスは通常どおり
+        _ => resolve_path(raw),
+    }
+}
+
 /// Windows の `\\?\` verbatim プレフィックスを除去した PathBuf を返す
 fn strip_verbatim_prefix(path: &Path) -> PathBuf {
     let s = path.to_string_lossy();
@@ -317,12 +878,46 @@ fn has_hidden_attribute(_metadata: &fs::Metadata) -> bool {
     false
 }
 
+fn modified_rfc3339(metadata: &fs::Metadata) -> Option<String> {
+    metadata.modified().ok().map(|t| {
+        let dt: chrono::DateTime<chrono::Utc> = t.into();
+        dt.to_rfc3339()
+    })
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
 fn is_hidden_entry(name: &str, metadata: &fs::Metadata) -> bool {
     is_hidden_name(name) || has_hidden_attribute(metadata)
 }
 
+/// Content-Disposition に安全なファイル名（ASCII のみ、空なら "download"）
+///
+/// ヘッダーインジェクション防止: ASCII 英数字 + 安全な記号のみ許可
+pub(crate) fn attachment_name(file_name: &str) -> String {
+    let safe_name: String = file_name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == ' ' || *c == '.' || *c == '_' || *c == '-')
+        .collect();
+    if safe_name.is_empty() {
+        "download".to_string()
+    } else {
+        safe_name
+    }
+}
+
 /// I/O エラーを API エラーに変換（OS エラー詳細はログのみ、クライアントにはジェネリックメッセージ）
-fn io_err(e: io::Error) -> ApiError {
+pub(crate) fn io_err(e: io::Error) -> ApiError {
     let (status, msg) = match e.kind() {
         io::ErrorKind::NotFound => (StatusCode::NOT_FOUND, "Not found"),
         io::ErrorKind::PermissionDenied => (StatusCode::FORBIDDEN, "Permission denied"),
@@ -337,12 +932,26 @@ fn io_err(e: io::Error) -> ApiError {
 // --- API ハンドラ ---
 
 /// GET /api/filer/list
+#[utoipa::path(
+    get,
+    path = "/api/filer/list",
+    tag = "filer",
+    summary = "List a directory",
+    params(ListQuery),
+    responses(
+        (status = 200, body = FilerListing),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
 pub async fn list(
-    _state: State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Query(q): Query<ListQuery>,
 ) -> Result<Json<FilerListing>, ApiError> {
+    let roots = state.filer_roots.clone();
     tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&q.path)?;
+        let path = roots.resolve(&q.path)?;
 
         if !path.is_dir() {
             return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
@@ -373,34 +982,41 @@ pub async fn list(
                 continue;
             }
 
-            let modified = metadata.modified().ok().map(|t| {
-                let dt: chrono::DateTime<chrono::Utc> = t.into();
-                dt.to_rfc3339()
-            });
+            entries.push(FilerEntry::from_path(name, &entry.path(), &metadata));
+        }
 
-            entries.push(FilerEntry {
-                name,
-                is_dir: metadata.is_dir(),
-                size: metadata.len(),
-                modified,
-            });
+        if q.git {
+            let statuses = super::git::statuses(&path);
+            let inherited = statuses.get("*").copied();
+            for entry in &mut entries {
+                entry.git = statuses.get(&entry.name).copied().or(inherited);
+            }
         }
 
         // ディレクトリ優先、その後名前でソート（キャッシュ付きで比較ごとのアロケーション回避）
+        #[allow(clippy::unnecessary_sort_by)]
         entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir));
         entries.sort_by_cached_key(|e| (!e.is_dir, e.name.to_lowercase()));
 
         // 親ディレクトリ（ドライブルート "C:\" の parent は "C:" → Some("") 相当を None に）
+        // ルート制限時はルートより上に辿らせない
         let parent = path
             .parent()
             .filter(|p| !p.as_os_str().is_empty() && *p != path)
+            .filter(|p| roots.contains(p))
             .map(|p| p.to_string_lossy().into_owned());
 
-        // ドライブルート（parent が None）のときドライブ一覧を付与
-        let drives = if parent.is_none() {
-            list_drives()
-        } else {
+        // ドライブルート（parent が None）のときドライブ一覧を付与（制限時は許可ルート一覧）
+        let drives = if parent.is_some() {
             Vec::new()
+        } else if roots.is_restricted() {
+            roots
+                .roots()
+                .iter()
+                .map(|r| r.to_string_lossy().into_owned())
+                .collect()
+        } else {
+            list_drives()
         };
 
         Ok(Json(FilerListing {
@@ -415,78 +1031,507 @@ pub async fn list(
 }
 
 /// GET /api/filer/read
+#[utoipa::path(
+    get,
+    path = "/api/filer/read",
+    tag = "filer",
+    summary = "Read a text file (optionally a byte range or a hex dump)",
+    params(ReadRangeQuery),
+    responses(
+        (status = 200, body = FileContent),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
 pub async fn read(
-    _state: State<Arc<AppState>>,
-    Query(q): Query<ReadQuery>,
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ReadRangeQuery>,
 ) -> Result<Json<FileContent>, ApiError> {
+    let roots = state.filer_roots.clone();
     tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&q.path)?;
-
-        let metadata = fs::metadata(&path).map_err(io_err)?;
-        if !metadata.is_file() {
-            return Err(err(StatusCode::NOT_FOUND, "Not a file"));
-        }
-        if metadata.len() > MAX_READ_SIZE {
-            return Err(err(
-                StatusCode::PAYLOAD_TOO_LARGE,
-                &format!(
-                    "File too large: {} bytes (max {})",
-                    metadata.len(),
-                    MAX_READ_SIZE
-                ),
-            ));
+        if q.offset.is_some() || q.length.is_some() || q.format == ReadFormat::Hex {
+            return read_range(&roots, &q);
         }
-
-        let data = fs::read(&path).map_err(io_err)?;
+        let (path, data) = read_limited(&roots, &q.path)?;
         let binary = is_binary(&data);
+        record_recent(&state.store, &path, "read");
 
         let content = if binary {
             String::new()
         } else {
             String::from_utf8_lossy(&data).into_owned()
         };
-
-        Ok(Json(FileContent {
-            path: path.to_string_lossy().into_owned(),
-            content,
-            size: metadata.len(),
-            is_binary: binary,
-        }))
+        let mtime = fs::metadata(&path).ok().as_ref().and_then(modified_rfc3339);
+
+        Ok(Json(
+            FileContent::new(
+                path.to_string_lossy().into_owned(),
+                content,
+                data.len() as u64,
+                binary,
+            )
+            .with_version(&data, mtime),
+        ))
     })
     .await
     .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
 }
 
-/// PUT /api/filer/write
-pub async fn write(
-    _state: State<Arc<AppState>>,
-    Json(req): Json<WriteRequest>,
-) -> Result<StatusCode, ApiError> {
-    tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&req.path)?;
+/// 部分読み込み: 読み込み上限は `length` に対して適用する（ファイルサイズは問わない）
+fn read_range(roots: &FilerRoots, q: &ReadRangeQuery) -> Result<Json<FileContent>, ApiError> {
+    use std::io::{Read, Seek};
 
-        tracing::info!("filer: write {}", path.display());
+    let path = roots.resolve(&q.path)?;
+    let metadata = fs::metadata(&path).map_err(io_err)?;
+    if !metadata.is_file() {
+        return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+    }
+    let mut file = fs::File::open(&path).map_err(io_err)?;
+    let total = metadata.len();
+    let offset = q.offset.unwrap_or(0).min(total);
+    let length = match q.format {
+        ReadFormat::Text => q.length.unwrap_or(MAX_READ_SIZE).min(MAX_READ_SIZE),
+        ReadFormat::Hex => q.length.unwrap_or(DEFAULT_HEX_BYTES).min(MAX_HEX_BYTES),
+    };
 
-        if let Some(parent) = path.parent()
-            && !parent.exists()
-        {
-            fs::create_dir_all(parent).map_err(io_err)?;
+    file.seek(io::SeekFrom::Start(offset)).map_err(io_err)?;
+    let mut data = Vec::new();
+    file.take(length).read_to_end(&mut data).map_err(io_err)?;
+
+    let binary = is_binary(&data);
+    // 範囲の境界で UTF-8 が途切れても置換文字になるだけ
+    let content = if q.format == ReadFormat::Hex {
+        hexdump(&data, offset)
+    } else if binary {
+        String::new()
+    } else {
+        String::from_utf8_lossy(&data).into_owned()
+    };
+    Ok(Json(FileContent {
+        path: path.to_string_lossy().into_owned(),
+        content,
+        size: data.len() as u64,
+        is_binary: binary,
+        offset: Some(offset),
+        total_size: Some(total),
+        hash: None,
+        mtime: None,
+    }))
+}
+
+/// `hexdump -C` 互換のダンプ。`base` は先頭バイトのファイル内オフセット。
+fn hexdump(data: &[u8], base: u64) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::with_capacity(data.len() * 4 + 80);
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x} ", base + (i * 16) as u64);
+        for col in 0..16 {
+            if col == 8 {
+                out.push(' ');
+            }
+            match chunk.get(col) {
+                Some(b) => {
+                    let _ = write!(out, " {b:02x}");
+                }
+                None => out.push_str("   "),
+            }
         }
+        out.push_str("  |");
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    out
+}
 
-        fs::write(&path, req.content.as_bytes()).map_err(io_err)?;
-        Ok(StatusCode::OK)
+/// GET /api/filer/tail?path=...&lines=...&offset=...
+///
+/// 末尾 N 行を返す。`offset` 付きなら前回の `size` 以降の追記分のみ
+/// （ログの follow 用）。
+#[utoipa::path(
+    get,
+    path = "/api/filer/tail",
+    tag = "filer",
+    summary = "Read the end of a file, or what was appended since offset",
+    params(TailQuery),
+    responses(
+        (status = 200, body = TailResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+pub async fn tail(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<TailQuery>,
+) -> Result<Json<TailResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        use std::io::{Read, Seek};
+
+        let path = roots.resolve(&q.path)?;
+        let lines = q
+            .lines
+            .unwrap_or(DEFAULT_TAIL_LINES)
+            .clamp(1, MAX_TAIL_LINES);
+        let metadata = fs::metadata(&path).map_err(io_err)?;
+        if !metadata.is_file() {
+            return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+        }
+        let mut file = fs::File::open(&path).map_err(io_err)?;
+        let size = metadata.len();
+        let (from, rotated) = match q.offset {
+            Some(o) if o <= size => (o, false),
+            Some(_) => (0, true),
+            None => (0, false),
+        };
+        let floor = from.max(size.saturating_sub(MAX_READ_SIZE));
+        let start = tail_lines_start(&mut file, size, floor, lines).map_err(io_err)?;
+
+        file.seek(io::SeekFrom::Start(start)).map_err(io_err)?;
+        let mut data = Vec::new();
+        file.take(size - start)
+            .read_to_end(&mut data)
+            .map_err(io_err)?;
+        Ok(Json(TailResponse::new(
+            path.to_string_lossy().into_owned(),
+            &data,
+            start,
+            from,
+            rotated,
+        )))
     })
     .await
     .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
 }
 
-/// POST /api/filer/mkdir
-pub async fn mkdir(
-    _state: State<Arc<AppState>>,
-    Json(req): Json<MkdirRequest>,
-) -> Result<StatusCode, ApiError> {
+/// GET /api/filer/checksum?path=...&algo=md5|sha1|sha256|sha512
+#[utoipa::path(
+    get,
+    path = "/api/filer/checksum",
+    tag = "filer",
+    summary = "Hash a file",
+    params(ChecksumQuery),
+    responses(
+        (status = 200, body = ChecksumResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+pub async fn checksum(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ChecksumQuery>,
+) -> Result<Json<ChecksumResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        use std::io::Read;
+
+        let path = roots.resolve(&q.path)?;
+        // Windows はディレクトリを File::open できないので先に metadata で判定
+        if !fs::metadata(&path).map_err(io_err)?.is_file() {
+            return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+        }
+        let mut file = fs::File::open(&path).map_err(io_err)?;
+        let mut hasher = q.algo.hasher();
+        let mut buf = vec![0u8; DOWNLOAD_CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buf).map_err(io_err)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(Json(ChecksumResponse::new(
+            path.to_string_lossy().into_owned(),
+            q.algo,
+            hasher.finish(),
+            "stream",
+        )))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// GET /api/filer/preview?path=...&lines=...&bytes=...
+///
+/// 先頭 N 行（かつ先頭 `bytes` バイト以内）だけを返す。巨大なログを
+/// 丸ごと転送せずに開くため。総行数はサーバー側で数える。
+#[utoipa::path(
+    get,
+    path = "/api/filer/preview",
+    tag = "filer",
+    summary = "Read the first lines of a file",
+    params(PreviewQuery),
+    responses(
+        (status = 200, body = PreviewResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+pub async fn preview(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<PreviewQuery>,
+) -> Result<Json<PreviewResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        use std::io::Read;
+
+        let path = roots.resolve(&q.path)?;
+        let metadata = fs::metadata(&path).map_err(io_err)?;
+        if !metadata.is_file() {
+            return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+        }
+        let size = metadata.len();
+        let max_lines = q
+            .lines
+            .unwrap_or(DEFAULT_PREVIEW_LINES)
+            .clamp(1, MAX_PREVIEW_LINES);
+        let max_bytes = q
+            .bytes
+            .unwrap_or(DEFAULT_PREVIEW_BYTES)
+            .clamp(1, MAX_READ_SIZE);
+
+        let mut file = fs::File::open(&path).map_err(io_err)?;
+        let mut head = Vec::new();
+        (&mut file)
+            .take(max_bytes)
+            .read_to_end(&mut head)
+            .map_err(io_err)?;
+
+        let encoding = TextEncoding::detect(&head);
+        let (content, consumed) = match encoding {
+            TextEncoding::Binary => (String::new(), 0),
+            TextEncoding::Utf8Bom => {
+                let (text, used) = first_lines(&head[3..], max_lines);
+                (text, used + 3)
+            }
+            TextEncoding::Utf8 | TextEncoding::Unknown => first_lines(&head, max_lines),
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                first_lines_utf16(&head, max_lines, encoding == TextEncoding::Utf16Le)
+            }
+        };
+
+        let total_lines = match encoding {
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be | TextEncoding::Binary => None,
+            _ if size > MAX_LINE_COUNT_SIZE => None,
+            // 読んだ先頭分に続けて残りを数える（先頭を読み直さない）
+            _ => Some(count_lines(&head, &mut file).map_err(io_err)?),
+        };
+
+        Ok(Json(PreviewResponse {
+            path: path.to_string_lossy().into_owned(),
+            lines: content.lines().count(),
+            content,
+            total_lines,
+            size,
+            truncated: (consumed as u64) < size,
+            encoding,
+            is_binary: encoding == TextEncoding::Binary,
+        }))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// 末尾で途切れたマルチバイト文字を除いた最長の UTF-8 プレフィックス候補
+fn utf8_prefix(data: &[u8]) -> &[u8] {
+    match std::str::from_utf8(data) {
+        Ok(_) => data,
+        Err(e) if e.error_len().is_none() => &data[..e.valid_up_to()],
+        Err(_) => data,
+    }
+}
+
+/// 先頭 `max_lines` 行（改行込み）と、それが占めるバイト数
+fn first_lines(data: &[u8], max_lines: usize) -> (String, usize) {
+    let data = utf8_prefix(data);
+    let end = data
+        .iter()
+        .enumerate()
+        .filter(|&(_, &b)| b == b'\n')
+        .nth(max_lines - 1)
+        .map_or(data.len(), |(i, _)| i + 1);
+    (String::from_utf8_lossy(&data[..end]).into_owned(), end)
+}
+
+/// UTF-16 版（BOM 付き）。戻り値のバイト数は BOM を含む
+fn first_lines_utf16(data: &[u8], max_lines: usize, little_endian: bool) -> (String, usize) {
+    let units = data[2..].chunks_exact(2).map(|b| {
+        if little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        }
+    });
+    let mut text = String::new();
+    let mut consumed = 2;
+    let mut lines = 0;
+    for c in char::decode_utf16(units) {
+        let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+        consumed += c.len_utf16() * 2;
+        text.push(c);
+        if c == '\n' {
+            lines += 1;
+            if lines == max_lines {
+                break;
+            }
+        }
+    }
+    (text, consumed)
+}
+
+/// `head` と、続く `rest` の残り全体の行数（末尾が改行でない最終行も 1 行）
+fn count_lines(head: &[u8], rest: &mut impl io::Read) -> io::Result<u64> {
+    let mut newlines = head.iter().filter(|&&b| b == b'\n').count() as u64;
+    let mut last = head.last().copied();
+    let mut buf = vec![0u8; DOWNLOAD_CHUNK_SIZE];
+    loop {
+        let n = rest.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        newlines += buf[..n].iter().filter(|&&b| b == b'\n').count() as u64;
+        last = Some(buf[n - 1]);
+    }
+    Ok(match last {
+        None => 0,
+        Some(b'\n') => newlines,
+        Some(_) => newlines + 1,
+    })
+}
+
+/// Offset where the last `lines` lines of `file[..size]` begin, never below
+/// `floor`. Scans backwards block by block; a trailing newline does not
+/// count as an extra (empty) line.
+fn tail_lines_start(
+    file: &mut (impl io::Read + io::Seek),
+    size: u64,
+    floor: u64,
+    lines: usize,
+) -> io::Result<u64> {
+    let mut pos = size;
+    let mut newlines = 0;
+    let mut buf = vec![0u8; TAIL_SCAN_BLOCK];
+    while pos > floor {
+        let block = (pos - floor).min(TAIL_SCAN_BLOCK as u64) as usize;
+        pos -= block as u64;
+        file.seek(io::SeekFrom::Start(pos))?;
+        file.read_exact(&mut buf[..block])?;
+        for i in (0..block).rev() {
+            if buf[i] != b'\n' || pos + i as u64 == size - 1 {
+                continue;
+            }
+            newlines += 1;
+            if newlines == lines {
+                return Ok(pos + i as u64 + 1);
+            }
+        }
+    }
+    Ok(floor)
+}
+
+/// Resolve and read a regular file, enforcing the text read limit (blocking).
+pub(crate) fn read_limited(roots: &FilerRoots, raw: &str) -> Result<(PathBuf, Vec<u8>), ApiError> {
+    let path = roots.resolve(raw)?;
+
+    let metadata = fs::metadata(&path).map_err(io_err)?;
+    if !metadata.is_file() {
+        return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+    }
+    if metadata.len() > MAX_READ_SIZE {
+        return Err(err(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!(
+                "File too large: {} bytes (max {})",
+                metadata.len(),
+                MAX_READ_SIZE
+            ),
+        ));
+    }
+
+    let data = fs::read(&path).map_err(io_err)?;
+    Ok((path, data))
+}
+
+/// PUT /api/filer/write
+///
+/// `if_match` 付きなら、エディタで開いた後にターミナル等で変更されていないか確認する。
+#[utoipa::path(
+    put,
+    path = "/api/filer/write",
+    tag = "filer",
+    summary = "Write a text file",
+    request_body = WriteRequest,
+    responses(
+        (status = 200, body = WriteResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 409, description = "Destination exists or the file changed", body = ErrorResponse),
+    )
+)]
+pub async fn write(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<WriteRequest>,
+) -> Result<Json<WriteResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let path = roots.resolve(&req.path)?;
+
+        if req.if_match.is_some() {
+            let current = match fs::read(&path) {
+                Ok(data) => Some(data),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(io_err(e)),
+            };
+            check_if_match(req.if_match.as_deref(), current.as_deref())?;
+        }
+
+        tracing::info!("filer: write {}", path.display());
+
+        if let Some(parent) = path.parent()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent).map_err(io_err)?;
+        }
+
+        fs::write(&path, req.content.as_bytes()).map_err(io_err)?;
+        record_recent(&state.store, &path, "write");
+        let mtime = fs::metadata(&path).ok().as_ref().and_then(modified_rfc3339);
+        Ok(Json(WriteResponse::new(req.content.as_bytes(), mtime)))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// POST /api/filer/mkdir
+#[utoipa::path(
+    post,
+    path = "/api/filer/mkdir",
+    tag = "filer",
+    summary = "Create a directory",
+    request_body = MkdirRequest,
+    responses(
+        (status = 201, description = "Created"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+    )
+)]
+pub async fn mkdir(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MkdirRequest>,
+) -> Result<StatusCode, ApiError> {
+    let roots = state.filer_roots.clone();
     tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&req.path)?;
+        let path = roots.resolve(&req.path)?;
 
         tracing::info!("filer: mkdir {}", path.display());
         fs::create_dir_all(&path).map_err(io_err)?;
@@ -496,113 +1541,884 @@ pub async fn mkdir(
     .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
 }
 
+/// create 用の初期内容（テンプレート未指定なら空）
+pub(crate) fn template_content(state: &AppState, name: Option<&str>) -> Result<String, ApiError> {
+    let Some(name) = name else {
+        return Ok(String::new());
+    };
+    state
+        .store
+        .load_settings()
+        .file_templates
+        .unwrap_or_default()
+        .into_iter()
+        .find(|t| t.name.trim() == name.trim())
+        .map(|t| t.content)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Template not found"))
+}
+
+/// POST /api/filer/create
+///
+/// 新規ファイルを作成する（既存なら 409）。write と違い上書きしない。
+#[utoipa::path(
+    post,
+    path = "/api/filer/create",
+    tag = "filer",
+    summary = "Create a new file, optionally from a template",
+    request_body = CreateRequest,
+    responses(
+        (status = 201, body = CopyMoveResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 409, description = "Destination exists or the file changed", body = ErrorResponse),
+    )
+)]
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateRequest>,
+) -> Result<(StatusCode, Json<CopyMoveResponse>), ApiError> {
+    let content = template_content(&state, req.template.as_deref())?;
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let path = roots.resolve(&req.path)?;
+
+        tracing::info!("filer: create {}", path.display());
+
+        if let Some(parent) = path.parent()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent).map_err(io_err)?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => err(StatusCode::CONFLICT, "File already exists"),
+                _ => io_err(e),
+            })?;
+        io::Write::write_all(&mut file, content.as_bytes()).map_err(io_err)?;
+        record_recent(&state.store, &path, "write");
+        Ok((StatusCode::CREATED, Json(CopyMoveResponse::new(&path))))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// POST /api/filer/symlink
+pub async fn symlink(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SymlinkRequest>,
+) -> Result<StatusCode, ApiError> {
+    if req.target.is_empty() || req.target.contains('\0') {
+        return Err(err(StatusCode::BAD_REQUEST, "Invalid link target"));
+    }
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let link = roots.resolve_entry(&req.path)?;
+        if fs::symlink_metadata(&link).is_ok() {
+            return Err(err(StatusCode::CONFLICT, "Destination already exists"));
+        }
+        let target = Path::new(&req.target);
+        let resolved_target = link.parent().unwrap_or(Path::new("")).join(target);
+        // ルート制限時は外を指すリンクも作らせない（辿っても拒否されるが紛らわしい）
+        if roots.is_restricted() {
+            roots.resolve(&resolved_target.to_string_lossy())?;
+        }
+        let is_dir = fs::metadata(&resolved_target).is_ok_and(|m| m.is_dir());
+
+        tracing::info!("filer: symlink {} -> {}", link.display(), target.display());
+        create_symlink(target, &link, is_dir).map_err(io_err)?;
+        Ok(StatusCode::CREATED)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// POST /api/filer/attributes
+///
+/// 読み取り専用・隠し属性（Windows）や unix パーミッションを変更し、更新後のエントリを返す。
+pub async fn attributes(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AttributesRequest>,
+) -> Result<Json<FilerEntry>, ApiError> {
+    let mode = req
+        .mode
+        .as_deref()
+        .map(|m| {
+            u32::from_str_radix(m.trim().trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|m| *m <= 0o7777)
+                .ok_or_else(|| err(StatusCode::BAD_REQUEST, "Invalid mode"))
+        })
+        .transpose()?;
+    if cfg!(windows) && mode.is_some() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Unix mode is not supported on Windows",
+        ));
+    }
+    if !cfg!(windows) && req.hidden.is_some() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Hidden attribute is only supported on Windows",
+        ));
+    }
+
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let path = roots.resolve(&req.path)?;
+        let metadata = fs::metadata(&path).map_err(io_err)?;
+
+        tracing::info!("filer: attributes {}", path.display());
+        let mut permissions = metadata.permissions();
+        if let Some(mode) = mode {
+            set_unix_mode(&mut permissions, mode);
+        }
+        if let Some(readonly) = req.readonly {
+            set_readonly(&mut permissions, readonly);
+        }
+        if mode.is_some() || req.readonly.is_some() {
+            fs::set_permissions(&path, permissions).map_err(io_err)?;
+        }
+        if let Some(hidden) = req.hidden {
+            set_hidden_attribute(&path, hidden).map_err(io_err)?;
+        }
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        let metadata = fs::metadata(&path).map_err(io_err)?;
+        Ok(Json(FilerEntry::from_metadata(name, &metadata)))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+#[cfg(unix)]
+fn set_unix_mode(permissions: &mut fs::Permissions, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+
+    permissions.set_mode(mode);
+}
+
+#[cfg(not(unix))]
+fn set_unix_mode(_permissions: &mut fs::Permissions, _mode: u32) {}
+
+/// unix: 読み取り専用化は全書き込みビットを落とし、解除は所有者の書き込みビットだけ戻す
+/// （`Permissions::set_readonly(false)` は world-writable にしてしまう）
+#[cfg(unix)]
+fn set_readonly(permissions: &mut fs::Permissions, readonly: bool) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = permissions.mode();
+    permissions.set_mode(if readonly {
+        mode & !0o222
+    } else {
+        mode | 0o200
+    });
+}
+
+#[cfg(not(unix))]
+fn set_readonly(permissions: &mut fs::Permissions, readonly: bool) {
+    permissions.set_readonly(readonly);
+}
+
+#[cfg(windows)]
+fn set_hidden_attribute(path: &Path, hidden: bool) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::MetadataExt;
+    use windows_sys::Win32::Storage::FileSystem::{FILE_ATTRIBUTE_HIDDEN, SetFileAttributesW};
+
+    let attrs = fs::metadata(path)?.file_attributes();
+    let attrs = if hidden {
+        attrs | FILE_ATTRIBUTE_HIDDEN
+    } else {
+        attrs & !FILE_ATTRIBUTE_HIDDEN
+    };
+    let wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    if unsafe { SetFileAttributesW(wide.as_ptr(), attrs) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn set_hidden_attribute(_path: &Path, _hidden: bool) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
 /// POST /api/filer/rename
+#[utoipa::path(
+    post,
+    path = "/api/filer/rename",
+    tag = "filer",
+    summary = "Rename a file or directory",
+    request_body = RenameRequest,
+    responses(
+        (status = 200, description = "Renamed"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
 pub async fn rename(
-    _state: State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Json(req): Json<RenameRequest>,
 ) -> Result<StatusCode, ApiError> {
+    let roots = state.filer_roots.clone();
+    let journal = state.filer_journal.clone();
     tokio::task::spawn_blocking(move || {
-        let from = resolve_path(&req.from)?;
-        let to = resolve_path(&req.to)?;
+        let from = roots.resolve_entry(&req.from)?;
+        let to = roots.resolve(&req.to)?;
 
         tracing::info!("filer: rename {} -> {}", from.display(), to.display());
         fs::rename(&from, &to).map_err(io_err)?;
+        journal.rename(&from, &to);
         Ok(StatusCode::OK)
     })
     .await
     .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
 }
 
+/// POST /api/filer/copy
+#[utoipa::path(
+    post,
+    path = "/api/filer/copy",
+    tag = "filer",
+    summary = "Copy a file or directory",
+    request_body = CopyMoveRequest,
+    responses(
+        (status = 200, body = CopyMoveResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Destination exists or the file changed", body = ErrorResponse),
+    )
+)]
+pub async fn copy(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CopyMoveRequest>,
+) -> Result<Json<CopyMoveResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let to = copy_entry(&roots, &req)?;
+        Ok(Json(CopyMoveResponse::new(&to)))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// POST /api/filer/move
+///
+/// rename が別ボリュームで失敗した場合は copy + delete にフォールバックする。
+#[utoipa::path(
+    post,
+    path = "/api/filer/move",
+    tag = "filer",
+    summary = "Move a file or directory",
+    request_body = CopyMoveRequest,
+    responses(
+        (status = 200, body = CopyMoveResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Destination exists or the file changed", body = ErrorResponse),
+    )
+)]
+pub async fn move_path(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CopyMoveRequest>,
+) -> Result<Json<CopyMoveResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    let journal = state.filer_journal.clone();
+    tokio::task::spawn_blocking(move || {
+        let to = move_entry(&roots, &req, &journal)?;
+        Ok(Json(CopyMoveResponse::new(&to)))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// コピーを実行し、実際の宛先を返す
+fn copy_entry(roots: &FilerRoots, req: &CopyMoveRequest) -> Result<PathBuf, ApiError> {
+    let (from, to) = prepare_copy_move(roots, req)?;
+
+    tracing::info!("filer: copy {} -> {}", from.display(), to.display());
+    copy_or_cleanup(&from, &to)?;
+    Ok(to)
+}
+
+/// 移動を実行し、実際の宛先を返す
+fn move_entry(
+    roots: &FilerRoots,
+    req: &CopyMoveRequest,
+    journal: &Journal,
+) -> Result<PathBuf, ApiError> {
+    let (from, to) = prepare_copy_move(roots, req)?;
+
+    tracing::info!("filer: move {} -> {}", from.display(), to.display());
+    move_or_copy(&from, &to)?;
+    journal.moved(&from, &to);
+    Ok(to)
+}
+
+/// copy/move 共通の検証と衝突解決。戻り値は (元, 実際の宛先)。
+fn prepare_copy_move(
+    roots: &FilerRoots,
+    req: &CopyMoveRequest,
+) -> Result<(PathBuf, PathBuf), ApiError> {
+    let from = roots.resolve_entry(&req.from)?;
+    let to = roots.resolve(&req.to)?;
+    let from_meta = fs::symlink_metadata(&from).map_err(io_err)?;
+
+    let to_parent = to
+        .parent()
+        .ok_or_else(|| err(StatusCode::BAD_REQUEST, "Invalid destination"))?;
+    if !to_parent.is_dir() {
+        return Err(err(
+            StatusCode::NOT_FOUND,
+            "Destination directory not found",
+        ));
+    }
+    // ディレクトリを自身の配下へコピー/移動すると無限に再帰する
+    // リンクはリンク自体のパスで比較する（resolve_entry で親は正規化済み）
+    let from_canon = if from_meta.is_symlink() {
+        from.clone()
+    } else {
+        fs::canonicalize(&from).map_err(io_err)?
+    };
+    let to_canon = fs::canonicalize(to_parent)
+        .map_err(io_err)?
+        .join(to.file_name().unwrap_or_default());
+    if to_canon == from_canon {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Source and destination are the same",
+        ));
+    }
+    if from_meta.is_dir() && to_canon.starts_with(&from_canon) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Cannot copy a directory into itself",
+        ));
+    }
+
+    if fs::symlink_metadata(&to).is_err() {
+        return Ok((from, to));
+    }
+    match req.on_conflict {
+        ConflictPolicy::Fail => Err(err(StatusCode::CONFLICT, "Destination already exists")),
+        ConflictPolicy::Overwrite => {
+            // 宛先が元の祖先なら、削除で元ごと消えてしまう
+            if from_canon.starts_with(&to_canon) {
+                return Err(err(
+                    StatusCode::BAD_REQUEST,
+                    "Cannot overwrite a parent of the source",
+                ));
+            }
+            tracing::info!("filer: overwrite {}", to.display());
+            remove_path(&to).map_err(io_err)?;
+            Ok((from, to))
+        }
+        ConflictPolicy::Rename => {
+            let to = unique_destination(&to)
+                .ok_or_else(|| err(StatusCode::CONFLICT, "No free destination name"))?;
+            Ok((from, to))
+        }
+    }
+}
+
+/// rename し、別ボリュームなら copy + delete にフォールバック
+pub(super) fn move_or_copy(from: &Path, to: &Path) -> Result<(), ApiError> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            tracing::info!("filer: cross-device move, falling back to copy + delete");
+            copy_or_cleanup(from, to)?;
+            remove_path(from).map_err(io_err)
+        }
+        Err(e) => Err(io_err(e)),
+    }
+}
+
+/// `name (1).ext`, `name (2).ext`, ... のうち存在しない最初のパス
+pub(super) fn unique_destination(path: &Path) -> Option<PathBuf> {
+    let parent = path.parent()?;
+    let file_name = path.file_name()?.to_string_lossy().into_owned();
+    // ディレクトリやドットファイルは拡張子扱いしない
+    let (stem, ext) = match file_name.rfind('.') {
+        Some(i) if i > 0 && !path.is_dir() => file_name.split_at(i),
+        _ => (file_name.as_str(), ""),
+    };
+    (1..=MAX_RENAME_ATTEMPTS)
+        .map(|n| parent.join(format!("{stem} ({n}){ext}")))
+        .find(|p| fs::symlink_metadata(p).is_err())
+}
+
+/// コピーし、失敗したら途中まで作った宛先を片付ける
+fn copy_or_cleanup(from: &Path, to: &Path) -> Result<(), ApiError> {
+    copy_recursive(from, to).map_err(|e| {
+        if let Err(cleanup) = remove_path(to) {
+            tracing::debug!("filer: cleanup of {} failed: {cleanup}", to.display());
+        }
+        io_err(e)
+    })
+}
+
+/// ファイル/ディレクトリを再帰コピー。ディレクトリへのシンボリックリンクは
+/// ループ防止のためスキップし、ファイルへのリンクは実体をコピーする。
+/// リンクはリンク先をコピーせず、同じターゲットを指すリンクとして複製する
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        let target = fs::read_link(from)?;
+        return create_symlink(&target, to, is_dir_link(&metadata));
+    }
+    if !metadata.is_dir() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// リンクは辿らずリンク自体を削除する
+pub(super) fn remove_path(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_symlink() {
+        // Windows のディレクトリリンク・ジャンクションは remove_dir で消す
+        if is_dir_link(&metadata) {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        }
+    } else if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Windows のディレクトリシンボリックリンク / ジャンクションか（unix では常に false）
+#[cfg(windows)]
+fn is_dir_link(metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+
+    metadata.file_type().is_symlink_dir()
+}
+
+#[cfg(not(windows))]
+fn is_dir_link(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+    if is_dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
 /// DELETE /api/filer/delete
+#[utoipa::path(
+    delete,
+    path = "/api/filer/delete",
+    tag = "filer",
+    summary = "Delete a file or directory, or move it to the trash",
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Deleted"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
 pub async fn delete(
-    _state: State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Query(q): Query<DeleteQuery>,
 ) -> Result<StatusCode, ApiError> {
+    let roots = state.filer_roots.clone();
+    let journal = state.filer_journal.clone();
+    let trash_root = super::trash::trash_root(&state);
     tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&q.path)?;
+        delete_entry(&roots, &q, &trash_root, &journal)?;
+        Ok(StatusCode::OK)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
 
-        tracing::info!("filer: delete {}", path.display());
+fn delete_entry(
+    roots: &FilerRoots,
+    q: &DeleteQuery,
+    trash_root: &Path,
+    journal: &Journal,
+) -> Result<(), ApiError> {
+    let path = roots.resolve_entry(&q.path)?;
+
+    if q.trash {
+        tracing::info!("filer: trash {}", path.display());
+        let entry = super::trash::move_to_trash(trash_root, &path)?;
+        journal.trashed(&path, entry.id);
+        return Ok(());
+    }
 
-        if path.is_dir() {
-            fs::remove_dir_all(&path).map_err(io_err)?;
-        } else {
-            fs::remove_file(&path).map_err(io_err)?;
-        }
+    tracing::info!("filer: delete {}", path.display());
+    remove_path(&path).map_err(io_err)
+}
 
-        Ok(StatusCode::OK)
+/// POST /api/filer/bulk
+///
+/// 操作を順に実行し、項目ごとの結果を返す。失敗しても後続は実行する
+/// （`stop_on_error` 指定時は以降を Skipped とする）。
+pub async fn bulk(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BulkRequest>,
+) -> Result<Json<BulkResponse>, ApiError> {
+    if req.ops.is_empty() || req.ops.len() > MAX_BULK_OPS {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Bulk request must contain 1-{MAX_BULK_OPS} operations"),
+        ));
+    }
+    let roots = state.filer_roots.clone();
+    let journal = state.filer_journal.clone();
+    let trash_root = super::trash::trash_root(&state);
+    tokio::task::spawn_blocking(move || {
+        let mut results = Vec::with_capacity(req.ops.len());
+        let mut failed = false;
+        for (index, op) in req.ops.iter().enumerate() {
+            if failed && req.stop_on_error {
+                results.push(BulkResult {
+                    index,
+                    ok: false,
+                    path: None,
+                    error: Some("Skipped".to_string()),
+                });
+                continue;
+            }
+            let outcome = match op {
+                BulkOp::Delete(q) => delete_entry(&roots, q, &trash_root, &journal).map(|()| None),
+                BulkOp::Copy(req) => copy_entry(&roots, req).map(Some),
+                BulkOp::Move(req) => move_entry(&roots, req, &journal).map(Some),
+            };
+            results.push(match outcome {
+                Ok(path) => BulkResult {
+                    index,
+                    ok: true,
+                    path: path.map(|p| p.to_string_lossy().into_owned()),
+                    error: None,
+                },
+                Err((_, Json(e))) => {
+                    failed = true;
+                    BulkResult {
+                        index,
+                        ok: false,
+                        path: None,
+                        error: Some(e.message().to_string()),
+                    }
+                }
+            });
+        }
+        Ok(Json(BulkResponse { results }))
     })
     .await
     .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
 }
 
 /// GET /api/filer/download
+///
+/// ファイルはメモリに載せずストリーミングする（サイズ上限なし）。
+#[utoipa::path(
+    get,
+    path = "/api/filer/download",
+    tag = "filer",
+    summary = "Download a file",
+    params(DownloadQuery),
+    responses(
+        (status = 200, description = "File contents", content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
 pub async fn download(
-    _state: State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Query(q): Query<DownloadQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let path = state.filer_roots.resolve(&q.path)?;
+
+    let metadata = tokio::fs::metadata(&path).await.map_err(io_err)?;
+    if !metadata.is_file() {
+        return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+    }
+    let file = tokio::fs::File::open(&path).await.map_err(io_err)?;
+    let len = metadata.len();
+
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let safe_name = attachment_name(&file_name);
+
+    let mime = mime_guess::from_path(&path)
+        .first_or_octet_stream()
+        .to_string();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", safe_name),
+            ),
+            (header::CONTENT_LENGTH, len.to_string()),
+        ],
+        file_body(file, len),
+    ))
+}
+
+/// Stream exactly `len` bytes of `file` as a response body. Reading stops at
+/// `len` so a file that grows meanwhile still matches Content-Length.
+pub(crate) fn file_body(file: tokio::fs::File, len: u64) -> axum::body::Body {
+    use tokio::io::AsyncReadExt;
+
+    let reader = file.take(len);
+    let stream = futures::stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut buf = vec![0u8; DOWNLOAD_CHUNK_SIZE];
+        match reader.read(&mut buf).await {
+            Ok(0) if reader.limit() > 0 => Some((
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "file shrank during download",
+                )),
+                None,
+            )),
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(bytes::Bytes::from(buf)), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    axum::body::Body::from_stream(stream)
+}
+
+/// One file or directory to put into a zip download.
+struct ZipItem {
+    /// Path inside the archive (`/`-separated, relative to the root)
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+    mtime: u64,
+}
+
+/// GET /api/filer/download-dir?path=...&show_hidden=...
+///
+/// Streams the directory tree as a zip. The tree is walked up front so the
+/// size cap can be enforced before any bytes go out; symlinks are skipped.
+pub async fn download_dir(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<DownloadDirQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let roots = state.filer_roots.clone();
+    let (root, items) = tokio::task::spawn_blocking(move || {
+        let root = roots.resolve(&q.path)?;
+        if !fs::metadata(&root).map_err(io_err)?.is_dir() {
+            return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
+        }
+        let mut items = Vec::new();
+        let mut total = 0u64;
+        collect_zip_items(&root, "", q.show_hidden, &mut items, &mut total)?;
+        Ok((root, items))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))??;
+
+    let dir_name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    tracing::info!(
+        "filer: download-dir {} ({} entries)",
+        root.display(),
+        items.len()
+    );
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<io::Result<bytes::Bytes>>(4);
     tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&q.path)?;
+        let mut out = ChannelWriter {
+            tx,
+            buf: Vec::with_capacity(ZIP_CHUNK_SIZE),
+        };
+        let result = write_zip(&items, &mut out).and_then(|()| io::Write::flush(&mut out));
+        if let Err(e) = result {
+            // 受信側が切断済み（クライアント中断）なら送信も失敗するだけ
+            tracing::warn!("filer: download-dir {} aborted: {e}", root.display());
+            let _ = out.tx.blocking_send(Err(e));
+        }
+    });
+
+    let body = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}.zip\"",
+                    attachment_name(&dir_name)
+                ),
+            ),
+        ],
+        axum::body::Body::from_stream(body),
+    ))
+}
 
-        let metadata = fs::metadata(&path).map_err(io_err)?;
-        if !metadata.is_file() {
-            return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+fn collect_zip_items(
+    dir: &Path,
+    prefix: &str,
+    show_hidden: bool,
+    items: &mut Vec<ZipItem>,
+    total: &mut u64,
+) -> Result<(), ApiError> {
+    let mut entries: Vec<fs::DirEntry> = fs::read_dir(dir)
+        .map_err(io_err)?
+        .filter_map(|entry| match entry {
+            Ok(e) => Some(e),
+            Err(e) => {
+                tracing::debug!("filer: zip entry error in {}: {e}", dir.display());
+                None
+            }
+        })
+        .collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // DirEntry::metadata はシンボリックリンクを辿らない（ループ防止）
+        let metadata = match entry.metadata() {
+            Ok(m) => m,
+            Err(e) => {
+                tracing::debug!(
+                    "filer: zip metadata error for {}: {e}",
+                    entry.path().display()
+                );
+                continue;
+            }
+        };
+        if !show_hidden && is_hidden_entry(&name, &metadata) {
+            continue;
+        }
+        if !metadata.is_dir() && !metadata.is_file() {
+            continue;
         }
 
-        // ダウンロードサイズ上限: 100MB
-        const MAX_DOWNLOAD_SIZE: u64 = 100 * 1024 * 1024;
-        if metadata.len() > MAX_DOWNLOAD_SIZE {
+        if items.len() >= zip::MAX_ENTRIES {
             return Err(err(
                 StatusCode::PAYLOAD_TOO_LARGE,
-                &format!(
-                    "File too large: {} bytes (max {})",
-                    metadata.len(),
-                    MAX_DOWNLOAD_SIZE
-                ),
+                &format!("Too many entries (max {})", zip::MAX_ENTRIES),
             ));
         }
+        if metadata.is_file() {
+            *total += metadata.len();
+            if *total > MAX_ZIP_TOTAL_SIZE {
+                return Err(err(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    &format!("Directory too large (max {MAX_ZIP_TOTAL_SIZE} bytes)"),
+                ));
+            }
+        }
 
-        let data = fs::read(&path).map_err(io_err)?;
-        let file_name = path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .into_owned();
-
-        // ヘッダーインジェクション防止: ASCII 英数字 + 安全な記号のみ許可
-        let safe_name: String = file_name
-            .chars()
-            .filter(|c| {
-                c.is_ascii_alphanumeric() || *c == ' ' || *c == '.' || *c == '_' || *c == '-'
-            })
-            .collect();
-        let safe_name = if safe_name.is_empty() {
-            "download".to_string()
+        let rel = format!("{prefix}{name}");
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        items.push(ZipItem {
+            name: rel.clone(),
+            path: entry.path(),
+            is_dir: metadata.is_dir(),
+            mtime,
+        });
+        if metadata.is_dir() {
+            collect_zip_items(&entry.path(), &format!("{rel}/"), show_hidden, items, total)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_zip(items: &[ZipItem], out: &mut impl io::Write) -> io::Result<()> {
+    let mut zip = zip::ZipWriter::new(out);
+    for item in items {
+        if item.is_dir {
+            zip.add_dir(&item.name, item.mtime)?;
         } else {
-            safe_name
-        };
+            let mut file = fs::File::open(&item.path)?;
+            zip.add_file(&item.name, item.mtime, &mut file)?;
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
 
-        let mime = mime_guess::from_path(&path)
-            .first_or_octet_stream()
-            .to_string();
+/// Blocking writer that forwards fixed-size chunks to an async body stream.
+struct ChannelWriter {
+    tx: tokio::sync::mpsc::Sender<io::Result<bytes::Bytes>>,
+    buf: Vec<u8>,
+}
 
-        Ok((
-            [
-                (header::CONTENT_TYPE, mime),
-                (
-                    header::CONTENT_DISPOSITION,
-                    format!("attachment; filename=\"{}\"", safe_name),
-                ),
-            ],
-            data,
-        ))
-    })
-    .await
-    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+impl io::Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= ZIP_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = bytes::Bytes::from(std::mem::replace(
+            &mut self.buf,
+            Vec::with_capacity(ZIP_CHUNK_SIZE),
+        ));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
 }
 
 /// POST /api/filer/upload (multipart)
 pub async fn upload(
-    _state: State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     mut multipart: Multipart,
 ) -> Result<StatusCode, ApiError> {
     let mut target_path: Option<String> = None;
@@ -663,9 +2479,10 @@ pub async fn upload(
     }
 
     let dir_path = target_path.unwrap_or_else(|| "~".to_string());
+    let roots = state.filer_roots.clone();
 
     tokio::task::spawn_blocking(move || {
-        let dir = resolve_path(&dir_path)?;
+        let dir = roots.resolve(&dir_path)?;
         let dest = dir.join(&file_name);
 
         tracing::info!("filer: upload {} ({} bytes)", dest.display(), data.len());
@@ -677,145 +2494,290 @@ pub async fn upload(
 }
 
 /// GET /api/filer/search
+#[utoipa::path(
+    get,
+    path = "/api/filer/search",
+    tag = "filer",
+    summary = "Search file names or contents",
+    params(SearchQuery),
+    responses(
+        (status = 200, body = Vec<SearchResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed filer roots", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
 pub async fn search(
-    _state: State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Query(q): Query<SearchQuery>,
 ) -> Result<Json<Vec<SearchResult>>, ApiError> {
-    let path = resolve_path(&q.path)?;
+    let path = state.filer_roots.resolve(&q.path)?;
 
     if !path.is_dir() {
         return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
     }
+    if q.content && q.mode == SearchMode::Glob {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Glob mode only matches file names",
+        ));
+    }
 
-    let query_lower = q.query.to_lowercase();
+    let matcher = SearchMatcher::new(q.mode, &q.query)?;
+    let walker = search_walker(&path, &q)?;
     let content_search = q.content;
-    let show_hidden = q.show_hidden;
-
-    let results = tokio::task::spawn_blocking(move || {
-        let mut results = Vec::new();
-        search_recursive(
-            &path,
-            &query_lower,
-            content_search,
-            show_hidden,
-            0,
-            &mut results,
-        );
-        results
-    })
-    .await
-    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Search failed"))?;
+
+    let results =
+        tokio::task::spawn_blocking(move || search_walk(walker, &matcher, content_search))
+            .await
+            .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Search failed"))?;
 
     Ok(Json(results))
 }
 
-fn search_recursive(
-    dir: &Path,
-    query: &str,
-    content_search: bool,
-    show_hidden: bool,
-    depth: u32,
-    results: &mut Vec<SearchResult>,
-) {
-    if depth > MAX_SEARCH_DEPTH || results.len() >= MAX_SEARCH_RESULTS {
-        return;
-    }
+/// 検索クエリをモードに応じてコンパイルしたもの
+enum SearchMatcher {
+    /// 小文字化済みのクエリ
+    Substring(String),
+    Glob(globset::GlobMatcher),
+    Regex(regex::Regex),
+}
 
-    let entries = match fs::read_dir(dir) {
-        Ok(e) => e,
-        Err(e) => {
-            tracing::debug!("filer: search read_dir error for {}: {e}", dir.display());
-            return;
+impl SearchMatcher {
+    fn new(mode: SearchMode, query: &str) -> Result<Self, ApiError> {
+        match mode {
+            SearchMode::Substring => Ok(Self::Substring(query.to_lowercase())),
+            SearchMode::Glob => globset::GlobBuilder::new(query)
+                .case_insensitive(true)
+                .build()
+                .map(|g| Self::Glob(g.compile_matcher()))
+                .map_err(|e| err(StatusCode::BAD_REQUEST, &format!("Invalid glob: {e}"))),
+            SearchMode::Regex => regex::RegexBuilder::new(query)
+                .case_insensitive(true)
+                .size_limit(1024 * 1024)
+                .build()
+                .map(Self::Regex)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, &format!("Invalid regex: {e}"))),
         }
-    };
+    }
 
-    for entry_result in entries {
-        let entry = match entry_result {
-            Ok(e) => e,
-            Err(e) => {
-                tracing::debug!("filer: search entry error in {}: {e}", dir.display());
-                continue;
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            // ASCII 快速パス: ASCII のみなら to_ascii_lowercase で済ませる
+            Self::Substring(query) => {
+                if text.is_ascii() {
+                    text.to_ascii_lowercase().contains(query.as_str())
+                } else {
+                    text.to_lowercase().contains(query.as_str())
+                }
             }
-        };
-        if results.len() >= MAX_SEARCH_RESULTS {
-            return;
+            Self::Glob(glob) => glob.is_match(text),
+            Self::Regex(re) => re.is_match(text),
         }
+    }
+}
 
-        let path = entry.path();
-        let name = entry.file_name().to_string_lossy().into_owned();
-        // Short-circuit: skip by name before paying for metadata syscall
-        if !show_hidden && is_hidden_name(&name) {
-            continue;
-        }
-        let metadata = match entry.metadata() {
-            Ok(m) => m,
-            Err(e) => {
-                tracing::debug!("filer: search metadata error for {}: {e}", path.display());
-                continue;
+/// gitignore・除外パターン・隠しファイル設定を反映したウォーカー
+fn search_walker(root: &Path, q: &SearchQuery) -> Result<ignore::WalkBuilder, ApiError> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root);
+    for pattern in q
+        .exclude
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+    {
+        // override の `!` 付きパターンは「無視」を意味する
+        overrides.add(&format!("!{pattern}")).map_err(|e| {
+            err(
+                StatusCode::BAD_REQUEST,
+                &format!("Invalid exclude pattern: {e}"),
+            )
+        })?;
+    }
+    let overrides = overrides.build().map_err(|e| {
+        err(
+            StatusCode::BAD_REQUEST,
+            &format!("Invalid exclude pattern: {e}"),
+        )
+    })?;
+
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder
+        .max_depth(Some(MAX_SEARCH_DEPTH as usize + 1))
+        .threads(search_threads())
+        .follow_links(false)
+        // 隠しファイルは is_hidden_entry で判定する（`$` 始まりや Windows 属性も含む）
+        .hidden(false)
+        .git_ignore(q.gitignore)
+        .git_exclude(q.gitignore)
+        .git_global(false)
+        .ignore(q.gitignore)
+        .parents(q.gitignore)
+        // git リポジトリ外でも .gitignore を尊重する
+        .require_git(false)
+        .overrides(overrides);
+    if !q.show_hidden {
+        builder.filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
             }
-        };
-        if !show_hidden && has_hidden_attribute(&metadata) {
-            continue;
-        }
+            let name = entry.file_name().to_string_lossy();
+            // Short-circuit: skip by name before paying for metadata syscall
+            !is_hidden_name(&name)
+                && !entry
+                    .metadata()
+                    .is_ok_and(|metadata| has_hidden_attribute(&metadata))
+        });
+    }
+    Ok(builder)
+}
 
-        let is_dir = metadata.is_dir();
-        let name_lower = name.to_lowercase();
+/// 並列ウォーカーで検索する。結果が上限に達した時点で全スレッドを打ち切る。
+///
+/// 返す順序はスレッドのスケジューリングに依存しないよう (path, line) でソートする。
+fn search_walk(
+    walker: ignore::WalkBuilder,
+    matcher: &SearchMatcher,
+    content_search: bool,
+) -> Vec<SearchResult> {
+    let results = Mutex::new(Vec::new());
+    // 予約済みの結果数。上限を超える予約は捨てる
+    let found = AtomicUsize::new(0);
+
+    walker.build_parallel().run(|| {
+        let results = &results;
+        let found = &found;
+        Box::new(move |entry_result| {
+            let entry = match entry_result {
+                Ok(e) => e,
+                Err(e) => {
+                    tracing::debug!("filer: search walk error: {e}");
+                    return ignore::WalkState::Continue;
+                }
+            };
+            if entry.depth() == 0 {
+                return ignore::WalkState::Continue;
+            }
+            let mut local = Vec::new();
+            let state = search_entry(&entry, matcher, content_search, found, &mut local);
+            if !local.is_empty() {
+                results
+                    .lock()
+                    .expect("search results poisoned")
+                    .extend(local);
+            }
+            state
+        })
+    });
 
-        // ファイル名マッチ
-        if name_lower.contains(query) {
-            results.push(SearchResult {
-                path: path.to_string_lossy().into_owned(),
-                is_dir,
-                line: None,
-                context: None,
-            });
+    let mut results = results.into_inner().expect("search results poisoned");
+    results.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    results
+}
+
+/// 1 エントリ分の名前・内容マッチ。上限に達したら `Quit` を返す。
+fn search_entry(
+    entry: &ignore::DirEntry,
+    matcher: &SearchMatcher,
+    content_search: bool,
+    found: &AtomicUsize,
+    out: &mut Vec<SearchResult>,
+) -> ignore::WalkState {
+    let reserve = || found.fetch_add(1, Ordering::Relaxed) < MAX_SEARCH_RESULTS;
+    if found.load(Ordering::Relaxed) >= MAX_SEARCH_RESULTS {
+        return ignore::WalkState::Quit;
+    }
+
+    let path = entry.path();
+    let name = entry.file_name().to_string_lossy();
+    let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+
+    // ファイル名マッチ
+    let name_matches = matcher.is_match(&name);
+    if name_matches {
+        if !reserve() {
+            return ignore::WalkState::Quit;
         }
+        out.push(SearchResult {
+            path: path.to_string_lossy().into_owned(),
+            is_dir,
+            line: None,
+            context: None,
+        });
+    }
 
-        // 内容検索（テキストファイルのみ）
-        if content_search
-            && !is_dir
-            && !name_lower.contains(query)
-            && metadata.len() <= MAX_READ_SIZE
-            && let Ok(file_content) = fs::read(&path)
-            && !is_binary(&file_content)
-        {
-            let text = String::from_utf8_lossy(&file_content);
-            let path_str = path.to_string_lossy().into_owned();
-            for (i, line) in text.lines().enumerate() {
-                if results.len() >= MAX_SEARCH_RESULTS {
-                    return;
-                }
-                // ASCII 快速パス: 行に大文字がなければ直接比較、そうでなければ to_lowercase
-                let matches = if line.is_ascii() {
-                    line.to_ascii_lowercase().contains(query)
-                } else {
-                    line.to_lowercase().contains(query)
-                };
-                if matches {
-                    results.push(SearchResult {
-                        path: path_str.clone(),
-                        is_dir: false,
-                        line: Some((i + 1) as u32),
-                        context: Some(line.chars().take(200).collect()),
-                    });
-                }
+    // 内容検索（テキストファイルのみ）
+    if content_search
+        && !is_dir
+        && !name_matches
+        && entry
+            .metadata()
+            .is_ok_and(|m| m.is_file() && m.len() <= MAX_READ_SIZE)
+        && let Ok(file) = fs::File::open(path)
+    {
+        let path_str = path.to_string_lossy().into_owned();
+        let scan = scan_lines(file, |line_no, line| {
+            if !matcher.is_match(line) {
+                return true;
             }
+            if !reserve() {
+                return false;
+            }
+            out.push(SearchResult {
+                path: path_str.clone(),
+                is_dir: false,
+                line: Some(line_no),
+                context: Some(line.chars().take(200).collect()),
+            });
+            true
+        });
+        if let Err(e) = scan {
+            tracing::debug!("filer: search read error for {}: {e}", path.display());
         }
+    }
 
-        // ディレクトリを再帰
-        if is_dir {
-            search_recursive(
-                &path,
-                query,
-                content_search,
-                show_hidden,
-                depth + 1,
-                results,
-            );
+    if found.load(Ordering::Relaxed) >= MAX_SEARCH_RESULTS {
+        ignore::WalkState::Quit
+    } else {
+        ignore::WalkState::Continue
+    }
+}
+
+/// テキストファイルを 1 行ずつ読み、`f(行番号, 行)` が false を返したら打ち切る。
+///
+/// ファイル全体をメモリに載せない。先頭バッファにヌルバイトがあればバイナリとして何もしない。
+fn scan_lines(file: fs::File, mut f: impl FnMut(u32, &str) -> bool) -> io::Result<()> {
+    use io::BufRead;
+
+    let mut reader = io::BufReader::with_capacity(SEARCH_READ_BUF, file);
+    if is_binary(reader.fill_buf()?) {
+        return Ok(());
+    }
+    let mut buf = Vec::new();
+    let mut line_no = 0u32;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            return Ok(());
+        }
+        line_no += 1;
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if !f(line_no, line) {
+            return Ok(());
         }
     }
 }
 
+/// 検索ワーカースレッド数（CPU 数、上限 MAX_SEARCH_THREADS）
+fn search_threads() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_SEARCH_THREADS)
+}
+
 /// Windows: GetLogicalDrives で接続済みドライブ一覧を返す。非 Windows は空。
 #[cfg(windows)]
 fn list_drives() -> Vec<String> {
@@ -839,6 +2801,58 @@ fn list_drives() -> Vec<String> {
 mod tests {
     use super::*;
 
+    fn tail_start(data: &str, floor: u64, lines: usize) -> u64 {
+        let mut cursor = io::Cursor::new(data.as_bytes());
+        tail_lines_start(&mut cursor, data.len() as u64, floor, lines).unwrap()
+    }
+
+    #[test]
+    fn tail_lines_start_counts_from_end() {
+        let data = "a\nb\nc\nd\n";
+        assert_eq!(&data[tail_start(data, 0, 2) as usize..], "c\nd\n");
+        assert_eq!(&data[tail_start(data, 0, 10) as usize..], data);
+        // No trailing newline: the partial last line counts as a line
+        let data = "a\nb\nc";
+        assert_eq!(&data[tail_start(data, 0, 1) as usize..], "c");
+        // Never below the floor (follow offset / read cap)
+        assert_eq!(tail_start("a\nb\nc\n", 4, 10), 4);
+    }
+
+    #[test]
+    fn hexdump_matches_hexdump_c() {
+        let dump = hexdump(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0hi", 0x100);
+        assert_eq!(
+            dump,
+            "00000100  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|\n\
+             00000110  03 00 68 69                                       |..hi|\n"
+        );
+        assert_eq!(hexdump(b"", 0), "");
+    }
+
+    #[test]
+    fn preview_helpers() {
+        assert_eq!(first_lines(b"a\nb\nc", 2), ("a\nb\n".to_string(), 4));
+        assert_eq!(first_lines(b"a\nb", 5), ("a\nb".to_string(), 3));
+        // A multi-byte character cut by the byte cap is dropped, not mangled
+        assert_eq!(first_lines("aé".as_bytes()[..2].as_ref(), 1).0, "a");
+
+        assert_eq!(TextEncoding::detect(b"plain"), TextEncoding::Utf8);
+        assert_eq!(
+            TextEncoding::detect(b"\xEF\xBB\xBFx"),
+            TextEncoding::Utf8Bom
+        );
+        assert_eq!(TextEncoding::detect(b"\xFF\xFEa\0"), TextEncoding::Utf16Le);
+        assert_eq!(TextEncoding::detect(b"a\0b"), TextEncoding::Binary);
+        assert_eq!(TextEncoding::detect(b"\xFFabc"), TextEncoding::Unknown);
+
+        let (text, used) = first_lines_utf16(b"\xFF\xFEa\0\n\0b\0", 1, true);
+        assert_eq!((text.as_str(), used), ("a\n", 6));
+
+        assert_eq!(count_lines(b"a\nb", &mut &b"\nc\n"[..]).unwrap(), 3);
+        assert_eq!(count_lines(b"a", &mut &b""[..]).unwrap(), 1);
+        assert_eq!(count_lines(b"", &mut &b""[..]).unwrap(), 0);
+    }
+
     #[test]
     fn expand_home_tilde() {
         let result = expand_home("~/test");
@@ -962,6 +2976,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::io_other_error)]
     fn io_err_other() {
         let e = std::io::Error::new(std::io::ErrorKind::Other, "fail");
         let (status, _) = io_err(e);
@@ -0,0 +1,157 @@
+//! Quick access for the file panel: recently opened files and bookmarks.
+//!
+//! Both live in the Store (not browser storage) so the list follows the
+//! user across devices. Recent entries are recorded by the local filer's
+//! read/write/create handlers; bookmarks are plain CRUD.
+
+use axum::{
+    Json,
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::store::{FILER_BOOKMARKS_MAX, FilerBookmark, FilerRecent, Store};
+
+use super::api::{ErrorResponse, err};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const MAX_LABEL_CHARS: usize = 100;
+
+/// Best-effort: a failed write only costs a recent-list entry.
+pub(crate) fn record_recent(store: &Store, path: &Path, action: &str) {
+    if let Err(e) = store.add_filer_recent(&path.to_string_lossy(), action) {
+        tracing::warn!("filer: failed to record recent path: {e}");
+    }
+}
+
+/// GET /api/filer/recent
+pub async fn recent(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<FilerRecent>>, ApiError> {
+    let store = state.store.clone();
+    tokio::task::spawn_blocking(move || store.load_filer_recent())
+        .await
+        .map(Json)
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))
+}
+
+/// DELETE /api/filer/recent
+pub async fn clear_recent(State(state): State<Arc<AppState>>) -> Result<StatusCode, ApiError> {
+    let store = state.store.clone();
+    tokio::task::spawn_blocking(move || store.clear_filer_recent())
+        .await
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+        .map_err(|e| {
+            tracing::error!("Failed to clear filer recent list: {e}");
+            err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save")
+        })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct BookmarkRequest {
+    pub path: String,
+    /// Defaults to the last path component
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Resolve the bookmarked path (must exist and be inside the filer roots)
+/// and pick a label.
+fn validate(state: &AppState, req: BookmarkRequest) -> Result<(String, String), ApiError> {
+    let path = state.filer_roots.resolve(&req.path)?;
+    if !path.exists() {
+        return Err(err(StatusCode::NOT_FOUND, "Path not found"));
+    }
+    let label = match req.label.as_deref().map(str::trim) {
+        Some(label) if !label.is_empty() => label.to_string(),
+        _ => path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned()),
+    };
+    if label.chars().count() > MAX_LABEL_CHARS {
+        return Err(err(StatusCode::BAD_REQUEST, "Label too long"));
+    }
+    Ok((label, path.to_string_lossy().into_owned()))
+}
+
+fn save_err(e: std::io::Error) -> ApiError {
+    tracing::error!("Failed to save filer bookmarks: {e}");
+    err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save")
+}
+
+/// GET /api/filer/bookmarks
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<FilerBookmark>>, ApiError> {
+    let store = state.store.clone();
+    tokio::task::spawn_blocking(move || store.load_filer_bookmarks())
+        .await
+        .map(Json)
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))
+}
+
+/// POST /api/filer/bookmarks
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BookmarkRequest>,
+) -> Result<(StatusCode, Json<FilerBookmark>), ApiError> {
+    tokio::task::spawn_blocking(move || {
+        let (label, path) = validate(&state, req)?;
+        match state
+            .store
+            .add_filer_bookmark(label, path)
+            .map_err(save_err)?
+        {
+            Some(bookmark) => Ok((StatusCode::CREATED, Json(bookmark))),
+            None => Err(err(
+                StatusCode::BAD_REQUEST,
+                &format!("Too many bookmarks (max {FILER_BOOKMARKS_MAX})"),
+            )),
+        }
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// PUT /api/filer/bookmarks/{id}
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    Json(req): Json<BookmarkRequest>,
+) -> Result<Json<FilerBookmark>, ApiError> {
+    tokio::task::spawn_blocking(move || {
+        let (label, path) = validate(&state, req)?;
+        state
+            .store
+            .update_filer_bookmark(&id, label, path)
+            .map_err(save_err)?
+            .map(Json)
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Bookmark not found"))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// DELETE /api/filer/bookmarks/{id}
+pub async fn remove(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let store = state.store.clone();
+    let removed = tokio::task::spawn_blocking(move || store.remove_filer_bookmark(&id))
+        .await
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+        .map_err(save_err)?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(err(StatusCode::NOT_FOUND, "Bookmark not found"))
+    }
+}
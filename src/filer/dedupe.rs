@@ -0,0 +1,200 @@
+//! Duplicate file finder.
+//!
+//! `POST /api/filer/dedupe-scan` walks a directory, groups regular files by
+//! size and only hashes sizes that occur more than once, so most of the tree
+//! is never read. Symlinks are not followed.
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::AppState;
+
+use super::api::{ChecksumAlgo, ErrorResponse, err, io_err};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Max directory depth below the scan root.
+const MAX_DEDUPE_DEPTH: usize = 32;
+/// Stop collecting after this many files (the result is marked truncated).
+const MAX_DEDUPE_FILES: usize = 200_000;
+/// Max duplicate groups returned (largest reclaimable first).
+const MAX_DEDUPE_GROUPS: usize = 500;
+/// Read buffer for hashing.
+const HASH_BUF_SIZE: usize = 64 * 1024;
+
+fn default_min_size() -> u64 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct DedupeRequest {
+    pub path: String,
+    /// Ignore files smaller than this (default 1: empty files are never
+    /// reported as duplicates)
+    #[serde(default = "default_min_size")]
+    pub min_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    /// sha256
+    pub hash: String,
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DedupeResponse {
+    pub groups: Vec<DuplicateGroup>,
+    pub scanned_files: usize,
+    pub hashed_files: usize,
+    /// Bytes freed by keeping one copy per group
+    pub reclaimable: u64,
+    /// File limit hit or too many groups; results are partial
+    pub truncated: bool,
+}
+
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = ChecksumAlgo::Sha256.hasher();
+    let mut buf = vec![0u8; HASH_BUF_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+fn find_duplicates(root: &Path, min_size: u64) -> DedupeResponse {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut scanned_files = 0usize;
+    let mut truncated = false;
+
+    let walker = ignore::WalkBuilder::new(root)
+        .standard_filters(false)
+        .follow_links(false)
+        .max_depth(Some(MAX_DEDUPE_DEPTH))
+        .build();
+    for entry in walker.filter_map(Result::ok) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        if scanned_files >= MAX_DEDUPE_FILES {
+            truncated = true;
+            break;
+        }
+        scanned_files += 1;
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.len() >= min_size {
+            by_size
+                .entry(meta.len())
+                .or_default()
+                .push(entry.into_path());
+        }
+    }
+
+    let mut hashed_files = 0usize;
+    let mut groups = Vec::new();
+    for (size, paths) in by_size {
+        if paths.len() < 2 {
+            continue;
+        }
+        let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+        for path in paths {
+            match hash_file(&path) {
+                Ok(hash) => {
+                    hashed_files += 1;
+                    by_hash
+                        .entry(hash)
+                        .or_default()
+                        .push(path.to_string_lossy().into_owned());
+                }
+                Err(e) => tracing::debug!("filer: dedupe skip {}: {e}", path.display()),
+            }
+        }
+        groups.extend(
+            by_hash
+                .into_iter()
+                .filter(|(_, paths)| paths.len() > 1)
+                .map(|(hash, mut paths)| {
+                    paths.sort();
+                    DuplicateGroup { size, hash, paths }
+                }),
+        );
+    }
+
+    let wasted = |g: &DuplicateGroup| g.size * (g.paths.len() as u64 - 1);
+    groups.sort_by(|a, b| {
+        wasted(b)
+            .cmp(&wasted(a))
+            .then_with(|| a.paths[0].cmp(&b.paths[0]))
+    });
+    let reclaimable = groups.iter().map(wasted).sum();
+    if groups.len() > MAX_DEDUPE_GROUPS {
+        groups.truncate(MAX_DEDUPE_GROUPS);
+        truncated = true;
+    }
+    DedupeResponse {
+        groups,
+        scanned_files,
+        hashed_files,
+        reclaimable,
+        truncated,
+    }
+}
+
+/// POST /api/filer/dedupe-scan
+pub async fn scan(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DedupeRequest>,
+) -> Result<Json<DedupeResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let root = roots.resolve(&req.path)?;
+        if !fs::metadata(&root).map_err(io_err)?.is_dir() {
+            return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
+        }
+        tracing::info!("filer: dedupe scan {}", root.display());
+        Ok(Json(find_duplicates(&root, req.min_size)))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_identical_content_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), "same content").unwrap();
+        fs::write(dir.path().join("sub/b.txt"), "same content").unwrap();
+        // Same size, different bytes
+        fs::write(dir.path().join("c.txt"), "diff content").unwrap();
+        fs::write(dir.path().join("empty1"), "").unwrap();
+        fs::write(dir.path().join("empty2"), "").unwrap();
+
+        let result = find_duplicates(dir.path(), 1);
+        assert_eq!(result.scanned_files, 5);
+        assert_eq!(result.hashed_files, 3);
+        assert_eq!(result.groups.len(), 1);
+        let group = &result.groups[0];
+        assert_eq!(group.size, 12);
+        assert_eq!(group.paths.len(), 2);
+        assert!(group.paths[0].ends_with("a.txt"));
+        assert_eq!(result.reclaimable, 12);
+        assert!(!result.truncated);
+    }
+}
@@ -0,0 +1,478 @@
+//! Background directory size jobs (`du`).
+//!
+//! `POST /api/filer/du` starts a walk on a blocking thread and returns a job
+//! id right away. Progress and the final total are published on the
+//! `/api/events` socket of the account that started the job;
+//! `GET /api/filer/du/{id}` can be polled instead.
+//! Symlinks are counted as links (never followed), so a loop cannot make a
+//! job run forever.
+
+use axum::{
+    Extension, Json,
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::events::{Event, EventHub};
+
+use super::api::{ErrorResponse, err, io_err};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Max jobs kept (running + finished). The oldest finished job is dropped
+/// first; when every slot is running, new jobs are refused.
+const MAX_DU_JOBS: usize = 32;
+
+/// Minimum interval between progress events for a single job.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Largest direct children reported in the result.
+const MAX_DU_CHILDREN: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuState {
+    Running,
+    Done,
+    Cancelled,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuChild {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub files: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DuStatus {
+    pub id: String,
+    pub path: String,
+    pub state: DuState,
+    /// Total bytes of regular files (and link entries) seen so far
+    pub size: u64,
+    pub files: u64,
+    pub dirs: u64,
+    /// Directories/files that could not be read (skipped)
+    pub skipped: u64,
+    /// Largest direct children, biggest first (set when done)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<DuChild>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct DuCounters {
+    size: AtomicU64,
+    files: AtomicU64,
+    dirs: AtomicU64,
+    skipped: AtomicU64,
+}
+
+struct DuJob {
+    path: PathBuf,
+    /// Username that started the job (None = the owner)
+    account: Option<String>,
+    started: Instant,
+    counters: DuCounters,
+    cancelled: AtomicBool,
+    /// Final state (None while running)
+    outcome: Mutex<Option<DuOutcome>>,
+}
+
+struct DuOutcome {
+    state: DuState,
+    children: Option<Vec<DuChild>>,
+    error: Option<String>,
+}
+
+impl DuJob {
+    fn status(&self, id: &str) -> DuStatus {
+        let c = &self.counters;
+        let outcome = self.outcome.lock().expect("du outcome poisoned");
+        DuStatus {
+            id: id.to_string(),
+            path: self.path.to_string_lossy().into_owned(),
+            state: outcome.as_ref().map_or(DuState::Running, |o| o.state),
+            size: c.size.load(Ordering::Relaxed),
+            files: c.files.load(Ordering::Relaxed),
+            dirs: c.dirs.load(Ordering::Relaxed),
+            skipped: c.skipped.load(Ordering::Relaxed),
+            children: outcome.as_ref().and_then(|o| o.children.clone()),
+            error: outcome.as_ref().and_then(|o| o.error.clone()),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.outcome.lock().expect("du outcome poisoned").is_none()
+    }
+}
+
+#[derive(Clone)]
+pub struct DuManager {
+    jobs: Arc<Mutex<HashMap<String, Arc<DuJob>>>>,
+    events: EventHub,
+}
+
+impl DuManager {
+    pub fn new(events: EventHub) -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            events,
+        }
+    }
+
+    /// Register a job for `path` on behalf of `account` and start walking
+    /// it. Returns None when every slot is taken by a running job.
+    pub fn start(&self, path: PathBuf, account: Option<&str>) -> Option<String> {
+        let job = Arc::new(DuJob {
+            path,
+            account: account.map(str::to_string),
+            started: Instant::now(),
+            counters: DuCounters::default(),
+            cancelled: AtomicBool::new(false),
+            outcome: Mutex::new(None),
+        });
+        let mut jobs = self.jobs.lock().expect("du jobs poisoned");
+        if jobs.len() >= MAX_DU_JOBS {
+            let oldest_finished = jobs
+                .iter()
+                .filter(|(_, j)| !j.is_running())
+                .min_by_key(|(_, j)| j.started)
+                .map(|(id, _)| id.clone())?;
+            jobs.remove(&oldest_finished);
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        jobs.insert(id.clone(), Arc::clone(&job));
+        drop(jobs);
+
+        let manager = self.clone();
+        let job_id = id.clone();
+        tokio::task::spawn_blocking(move || manager.run(&job_id, &job));
+        Some(id)
+    }
+
+    /// Jobs of other accounts are treated as missing.
+    pub fn get(&self, id: &str, account: Option<&str>) -> Option<DuStatus> {
+        let jobs = self.jobs.lock().expect("du jobs poisoned");
+        jobs.get(id)
+            .filter(|job| job.account.as_deref() == account)
+            .map(|job| job.status(id))
+    }
+
+    /// Cancel a running job, or forget a finished one.
+    pub fn cancel(&self, id: &str, account: Option<&str>) -> bool {
+        let mut jobs = self.jobs.lock().expect("du jobs poisoned");
+        match jobs.get(id).filter(|job| job.account.as_deref() == account) {
+            Some(job) if job.is_running() => {
+                job.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            Some(_) => {
+                jobs.remove(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn run(&self, id: &str, job: &DuJob) {
+        let path = job.path.to_string_lossy().into_owned();
+        let mut last_emit = Instant::now();
+        let mut progress = || {
+            let now = Instant::now();
+            if now.duration_since(last_emit) < PROGRESS_INTERVAL {
+                return;
+            }
+            last_emit = now;
+            self.events.publish(
+                job.account.as_deref(),
+                &Event::DuProgress {
+                    id: id.to_string(),
+                    path: path.clone(),
+                    size: job.counters.size.load(Ordering::Relaxed),
+                    files: job.counters.files.load(Ordering::Relaxed),
+                },
+            );
+        };
+
+        let result = measure(&job.path, &job.counters, &job.cancelled, &mut progress);
+        let outcome = match result {
+            Ok(mut children) => {
+                children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+                children.truncate(MAX_DU_CHILDREN);
+                DuOutcome {
+                    state: DuState::Done,
+                    children: Some(children),
+                    error: None,
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => DuOutcome {
+                state: DuState::Cancelled,
+                children: None,
+                error: None,
+            },
+            Err(e) => DuOutcome {
+                state: DuState::Error,
+                children: None,
+                error: Some(e.to_string()),
+            },
+        };
+
+        let c = &job.counters;
+        let event = match outcome.state {
+            DuState::Done => Event::DuDone {
+                id: id.to_string(),
+                path,
+                size: c.size.load(Ordering::Relaxed),
+                files: c.files.load(Ordering::Relaxed),
+                dirs: c.dirs.load(Ordering::Relaxed),
+            },
+            DuState::Cancelled => Event::DuCancelled {
+                id: id.to_string(),
+                path,
+            },
+            _ => Event::DuError {
+                id: id.to_string(),
+                path,
+                error: outcome.error.clone().unwrap_or_default(),
+            },
+        };
+        tracing::debug!(
+            "filer: du {} finished in {:?}",
+            job.path.display(),
+            job.started.elapsed()
+        );
+        *job.outcome.lock().expect("du outcome poisoned") = Some(outcome);
+        self.events.publish(job.account.as_deref(), &event);
+    }
+}
+
+/// Walk `root`, accumulating into `counters`. Returns the per-child totals of
+/// `root` itself. Cancellation surfaces as `ErrorKind::Interrupted`.
+fn measure(
+    root: &Path,
+    counters: &DuCounters,
+    cancelled: &AtomicBool,
+    progress: &mut dyn FnMut(),
+) -> std::io::Result<Vec<DuChild>> {
+    let mut children = Vec::new();
+    counters.dirs.fetch_add(1, Ordering::Relaxed);
+    for entry in fs::read_dir(root)? {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(std::io::ErrorKind::Interrupted.into());
+        }
+        let Ok(entry) = entry else {
+            counters.skipped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        let Ok(meta) = entry.path().symlink_metadata() else {
+            counters.skipped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        let before_size = counters.size.load(Ordering::Relaxed);
+        let before_files = counters.files.load(Ordering::Relaxed);
+        if meta.is_dir() {
+            walk_dir(&entry.path(), counters, cancelled, progress)?;
+        } else {
+            count_file(&meta, counters);
+        }
+        children.push(DuChild {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            is_dir: meta.is_dir(),
+            size: counters.size.load(Ordering::Relaxed) - before_size,
+            files: counters.files.load(Ordering::Relaxed) - before_files,
+        });
+        progress();
+    }
+    Ok(children)
+}
+
+fn walk_dir(
+    dir: &Path,
+    counters: &DuCounters,
+    cancelled: &AtomicBool,
+    progress: &mut dyn FnMut(),
+) -> std::io::Result<()> {
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(std::io::ErrorKind::Interrupted.into());
+        }
+        counters.dirs.fetch_add(1, Ordering::Relaxed);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            counters.skipped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        for entry in entries {
+            let Ok(path) = entry.map(|e| e.path()) else {
+                counters.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            match path.symlink_metadata() {
+                Ok(meta) if meta.is_dir() => stack.push(path),
+                Ok(meta) => count_file(&meta, counters),
+                Err(_) => {
+                    counters.skipped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        progress();
+    }
+    Ok(())
+}
+
+fn count_file(meta: &fs::Metadata, counters: &DuCounters) {
+    counters.size.fetch_add(meta.len(), Ordering::Relaxed);
+    counters.files.fetch_add(1, Ordering::Relaxed);
+}
+
+// --- API ハンドラ ---
+
+#[derive(Deserialize)]
+pub struct DuRequest {
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct DuStartResponse {
+    pub id: String,
+    pub path: String,
+}
+
+/// POST /api/filer/du
+pub async fn start(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Json(req): Json<DuRequest>,
+) -> Result<(StatusCode, Json<DuStartResponse>), ApiError> {
+    let roots = state.filer_roots.clone();
+    let dir = tokio::task::spawn_blocking(move || {
+        let dir = roots.resolve(&req.path)?;
+        if !fs::metadata(&dir).map_err(io_err)?.is_dir() {
+            return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
+        }
+        Ok(dir)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))??;
+
+    let id = state
+        .du_jobs
+        .start(dir.clone(), user.username.as_deref())
+        .ok_or_else(|| {
+            err(
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many directory size jobs running",
+            )
+        })?;
+    tracing::info!("filer: du {} ({id})", dir.display());
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(DuStartResponse {
+            id,
+            path: dir.to_string_lossy().into_owned(),
+        }),
+    ))
+}
+
+/// GET /api/filer/du/{id}
+pub async fn status(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Json<DuStatus>, ApiError> {
+    state
+        .du_jobs
+        .get(&id, user.username.as_deref())
+        .map(Json)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Job not found"))
+}
+
+/// DELETE /api/filer/du/{id}
+pub async fn cancel(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    if state.du_jobs.cancel(&id, user.username.as_deref()) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(err(StatusCode::NOT_FOUND, "Job not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_sums_tree_and_children() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("big/nested")).unwrap();
+        fs::write(dir.path().join("big/a.bin"), vec![0u8; 100]).unwrap();
+        fs::write(dir.path().join("big/nested/b.bin"), vec![0u8; 50]).unwrap();
+        fs::write(dir.path().join("small.txt"), "abc").unwrap();
+
+        let counters = DuCounters::default();
+        let mut children =
+            measure(dir.path(), &counters, &AtomicBool::new(false), &mut || {}).unwrap();
+        children.sort_by_key(|c| std::cmp::Reverse(c.size));
+
+        assert_eq!(counters.size.load(Ordering::Relaxed), 153);
+        assert_eq!(counters.files.load(Ordering::Relaxed), 3);
+        assert_eq!(counters.dirs.load(Ordering::Relaxed), 3);
+        assert_eq!(children[0].name, "big");
+        assert_eq!(children[0].size, 150);
+        assert_eq!(children[0].files, 2);
+        assert_eq!(children[1].name, "small.txt");
+    }
+
+    #[test]
+    fn measure_stops_when_cancelled() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let result = measure(
+            dir.path(),
+            &DuCounters::default(),
+            &AtomicBool::new(true),
+            &mut || {},
+        );
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Interrupted);
+    }
+
+    #[tokio::test]
+    async fn job_publishes_done_event() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("f"), "12345").unwrap();
+        let events = EventHub::new();
+        let mut rx = events.subscribe(Some("alice"));
+        let manager = DuManager::new(events);
+
+        let id = manager
+            .start(dir.path().to_path_buf(), Some("alice"))
+            .unwrap();
+        let msg = rx.recv().await.unwrap();
+        assert!(msg.contains(r#""type":"du_done""#));
+        let status = manager.get(&id, Some("alice")).unwrap();
+        assert_eq!(status.state, DuState::Done);
+        assert_eq!(status.size, 5);
+        // Other accounts can neither see nor cancel it
+        assert!(manager.get(&id, None).is_none());
+        assert!(!manager.cancel(&id, Some("bob")));
+        assert!(manager.cancel(&id, Some("alice")));
+        assert!(manager.get(&id, Some("alice")).is_none());
+    }
+}
@@ -0,0 +1,222 @@
+//! Git status decorations for directory listings.
+//!
+//! When `GET /api/filer/list?git=true` lists a directory inside a git work
+//! tree, `git status --porcelain` is run once for that directory and each
+//! entry is tagged with its state (like an IDE sidebar). Directories get the
+//! summary of what changed beneath them. Outside a repository, or without a
+//! `git` executable, listings are returned undecorated.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use super::api::resolve_path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GitStatus {
+    Modified,
+    Added,
+    Renamed,
+    Untracked,
+    Ignored,
+    Conflicted,
+}
+
+impl GitStatus {
+    /// Porcelain v1 `XY` code
+    fn from_xy(x: u8, y: u8) -> Option<Self> {
+        Some(match (x, y) {
+            (b'?', b'?') => GitStatus::Untracked,
+            (b'!', b'!') => GitStatus::Ignored,
+            (b'U', _) | (_, b'U') | (b'A', b'A') | (b'D', b'D') => GitStatus::Conflicted,
+            (b'R', _) | (b'C', _) => GitStatus::Renamed,
+            (b'A', _) => GitStatus::Added,
+            (b' ', b' ') => return None,
+            _ => GitStatus::Modified,
+        })
+    }
+
+    /// What a directory shows for a change somewhere below it.
+    fn for_parent(self) -> Option<Self> {
+        match self {
+            // 無視ファイルを含むだけのディレクトリは装飾しない
+            GitStatus::Ignored => None,
+            GitStatus::Untracked | GitStatus::Conflicted => Some(self),
+            _ => Some(GitStatus::Modified),
+        }
+    }
+
+    /// Priority when several changes roll up into one directory.
+    fn rank(self) -> u8 {
+        match self {
+            GitStatus::Ignored => 0,
+            GitStatus::Untracked => 1,
+            GitStatus::Added | GitStatus::Renamed | GitStatus::Modified => 2,
+            GitStatus::Conflicted => 3,
+        }
+    }
+}
+
+/// Cheap pre-check so non-repository directories never spawn git.
+fn in_work_tree(dir: &Path) -> bool {
+    dir.ancestors().any(|d| d.join(".git").exists())
+}
+
+fn git(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    // status は index を更新しようとしてロックを取るため、並行する git 操作と衝突させない
+    cmd.current_dir(dir).env("GIT_OPTIONAL_LOCKS", "0");
+    cmd
+}
+
+/// Status of each direct child of `dir`, keyed by file name. Empty when
+/// `dir` is not in a work tree or git fails. Blocking.
+pub fn statuses(dir: &Path) -> HashMap<String, GitStatus> {
+    if !in_work_tree(dir) {
+        return HashMap::new();
+    }
+    let toplevel = match git(dir).args(["rev-parse", "--show-toplevel"]).output() {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim().to_string(),
+        Ok(_) => return HashMap::new(),
+        Err(e) => {
+            tracing::debug!("filer: git unavailable: {e}");
+            return HashMap::new();
+        }
+    };
+    let Ok(toplevel) = resolve_path(&toplevel) else {
+        return HashMap::new();
+    };
+    let output = match git(dir)
+        .args([
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--ignored",
+            "--untracked-files=normal",
+            "--",
+            ".",
+        ])
+        .output()
+    {
+        Ok(o) if o.status.success() => o.stdout,
+        Ok(o) => {
+            tracing::debug!(
+                "filer: git status failed in {}: {}",
+                dir.display(),
+                String::from_utf8_lossy(&o.stderr).trim()
+            );
+            return HashMap::new();
+        }
+        Err(e) => {
+            tracing::debug!("filer: git status failed: {e}");
+            return HashMap::new();
+        }
+    };
+    let Ok(relative_dir) = dir.strip_prefix(&toplevel) else {
+        return HashMap::new();
+    };
+    children_status(relative_dir, &parse_porcelain(&output))
+}
+
+/// Parse `git status --porcelain=v1 -z` into (status, repo-relative path).
+fn parse_porcelain(output: &[u8]) -> Vec<(GitStatus, PathBuf)> {
+    let mut result = Vec::new();
+    let mut records = output.split(|&b| b == 0).filter(|r| !r.is_empty());
+    while let Some(record) = records.next() {
+        if record.len() < 4 {
+            continue;
+        }
+        let (x, y) = (record[0], record[1]);
+        // rename/copy は元パスが次のレコードに続く
+        if matches!(x, b'R' | b'C') {
+            records.next();
+        }
+        if let Some(status) = GitStatus::from_xy(x, y) {
+            let path = String::from_utf8_lossy(&record[3..]);
+            result.push((status, PathBuf::from(path.trim_end_matches('/'))));
+        }
+    }
+    result
+}
+
+/// Map repo-relative changes onto the direct children of `dir` (also
+/// repo-relative). A change that covers `dir` itself (inside an untracked or
+/// ignored directory) applies to every child, reported under the `"*"` key.
+fn children_status(dir: &Path, changes: &[(GitStatus, PathBuf)]) -> HashMap<String, GitStatus> {
+    let mut map: HashMap<String, GitStatus> = HashMap::new();
+    for (status, path) in changes {
+        if dir.starts_with(path) {
+            map.insert("*".to_string(), *status);
+            continue;
+        }
+        let Ok(rest) = path.strip_prefix(dir) else {
+            continue;
+        };
+        let mut components = rest.components();
+        let Some(Component::Normal(first)) = components.next() else {
+            continue;
+        };
+        let status = if components.next().is_none() {
+            *status
+        } else {
+            match status.for_parent() {
+                Some(s) => s,
+                None => continue,
+            }
+        };
+        let name = first.to_string_lossy().into_owned();
+        map.entry(name)
+            .and_modify(|current| {
+                if status.rank() > current.rank() {
+                    *current = status;
+                }
+            })
+            .or_insert(status);
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_porcelain_records() {
+        let out = b" M src/lib.rs\0?? new.txt\0R  b.txt\0a.txt\0!! target/\0UU conflict.rs\0";
+        let parsed = parse_porcelain(out);
+        assert_eq!(
+            parsed,
+            vec![
+                (GitStatus::Modified, PathBuf::from("src/lib.rs")),
+                (GitStatus::Untracked, PathBuf::from("new.txt")),
+                (GitStatus::Renamed, PathBuf::from("b.txt")),
+                (GitStatus::Ignored, PathBuf::from("target")),
+                (GitStatus::Conflicted, PathBuf::from("conflict.rs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rolls_changes_up_to_children() {
+        let changes = vec![
+            (GitStatus::Modified, PathBuf::from("src/filer/api.rs")),
+            (GitStatus::Untracked, PathBuf::from("src/filer/new/")),
+            (GitStatus::Ignored, PathBuf::from("src/filer/tmp/x.log")),
+            (GitStatus::Untracked, PathBuf::from("src/notes.txt")),
+            (GitStatus::Modified, PathBuf::from("README.md")),
+        ];
+        let map = children_status(Path::new("src"), &changes);
+        assert_eq!(map.get("filer"), Some(&GitStatus::Modified));
+        assert_eq!(map.get("notes.txt"), Some(&GitStatus::Untracked));
+        assert!(!map.contains_key("README.md"));
+        assert_eq!(map.len(), 2);
+
+        let map = children_status(
+            Path::new("target/debug"),
+            &[(GitStatus::Ignored, "target".into())],
+        );
+        assert_eq!(map.get("*"), Some(&GitStatus::Ignored));
+    }
+}
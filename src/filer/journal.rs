@@ -0,0 +1,229 @@
+//! Operation journal for undoing filer mutations.
+//!
+//! Rename, move and trash-delete record what they did; `POST /api/filer/undo`
+//! reverts the most recent entry. Permanent deletes cannot be undone and are
+//! not recorded. The journal is in memory only (a restart clears it).
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::AppState;
+
+use super::api::{ConflictPolicy, ErrorResponse, err, io_err, move_or_copy};
+use super::roots::FilerRoots;
+use super::trash;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Oldest entries are dropped beyond this.
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JournalOp {
+    Rename {
+        from: String,
+        to: String,
+    },
+    Move {
+        from: String,
+        to: String,
+    },
+    /// Soft delete; `trash_id` is the trash item holding the original
+    Trash {
+        path: String,
+        trash_id: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEntry {
+    pub id: String,
+    #[serde(flatten)]
+    pub op: JournalOp,
+    pub at: String,
+}
+
+#[derive(Clone, Default)]
+pub struct Journal {
+    entries: Arc<Mutex<VecDeque<JournalEntry>>>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, op: JournalOp) {
+        let mut entries = self.entries.lock().expect("journal poisoned");
+        if entries.len() >= MAX_JOURNAL_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(JournalEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            op,
+            at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
+    pub(super) fn rename(&self, from: &Path, to: &Path) {
+        self.record(JournalOp::Rename {
+            from: from.to_string_lossy().into_owned(),
+            to: to.to_string_lossy().into_owned(),
+        });
+    }
+
+    pub(super) fn moved(&self, from: &Path, to: &Path) {
+        self.record(JournalOp::Move {
+            from: from.to_string_lossy().into_owned(),
+            to: to.to_string_lossy().into_owned(),
+        });
+    }
+
+    pub(super) fn trashed(&self, path: &Path, trash_id: String) {
+        self.record(JournalOp::Trash {
+            path: path.to_string_lossy().into_owned(),
+            trash_id,
+        });
+    }
+
+    /// Newest first.
+    pub fn list(&self) -> Vec<JournalEntry> {
+        let entries = self.entries.lock().expect("journal poisoned");
+        entries.iter().rev().cloned().collect()
+    }
+
+    fn pop(&self) -> Option<JournalEntry> {
+        self.entries.lock().expect("journal poisoned").pop_back()
+    }
+
+    fn push(&self, entry: JournalEntry) {
+        self.entries
+            .lock()
+            .expect("journal poisoned")
+            .push_back(entry);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().expect("journal poisoned").clear();
+    }
+}
+
+/// Revert one entry. Never overwrites: a path that was reused since the
+/// operation is reported as a conflict.
+fn revert(op: &JournalOp, trash_root: &Path, roots: &FilerRoots) -> Result<PathBuf, ApiError> {
+    match op {
+        JournalOp::Rename { from, to } | JournalOp::Move { from, to } => {
+            let (current, original) = (Path::new(to), roots.resolve_entry(from)?);
+            fs::symlink_metadata(current).map_err(io_err)?;
+            if fs::symlink_metadata(&original).is_ok() {
+                return Err(err(StatusCode::CONFLICT, "Original path is in use"));
+            }
+            tracing::info!(
+                "filer: undo {} -> {}",
+                current.display(),
+                original.display()
+            );
+            move_or_copy(current, &original)?;
+            Ok(original)
+        }
+        JournalOp::Trash { trash_id, .. } => {
+            trash::restore_item(trash_root, roots, trash_id, ConflictPolicy::Fail)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct UndoResponse {
+    pub undone: JournalEntry,
+    /// Where the item is now
+    pub path: String,
+}
+
+/// GET /api/filer/journal
+pub async fn list(State(state): State<Arc<AppState>>) -> Json<Vec<JournalEntry>> {
+    Json(state.filer_journal.list())
+}
+
+/// DELETE /api/filer/journal
+pub async fn clear(State(state): State<Arc<AppState>>) -> StatusCode {
+    state.filer_journal.clear();
+    StatusCode::NO_CONTENT
+}
+
+/// POST /api/filer/undo
+///
+/// 直近の操作を取り消す。対象が消えている（404）エントリは破棄し、
+/// それ以外の失敗では journal に戻して再試行できるようにする。
+pub async fn undo(State(state): State<Arc<AppState>>) -> Result<Json<UndoResponse>, ApiError> {
+    let journal = state.filer_journal.clone();
+    let entry = journal
+        .pop()
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Nothing to undo"))?;
+    let trash_root = trash::trash_root(&state);
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || match revert(&entry.op, &trash_root, &roots) {
+        Ok(path) => Ok(Json(UndoResponse {
+            undone: entry,
+            path: path.to_string_lossy().into_owned(),
+        })),
+        Err(e) => {
+            if e.0 != StatusCode::NOT_FOUND {
+                journal.push(entry);
+            }
+            Err(e)
+        }
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_is_bounded_and_newest_first() {
+        let journal = Journal::new();
+        for i in 0..MAX_JOURNAL_ENTRIES + 5 {
+            journal.rename(Path::new(&format!("/a{i}")), Path::new("/b"));
+        }
+        let entries = journal.list();
+        assert_eq!(entries.len(), MAX_JOURNAL_ENTRIES);
+        assert_eq!(
+            entries[0].op,
+            JournalOp::Rename {
+                from: format!("/a{}", MAX_JOURNAL_ENTRIES + 4),
+                to: "/b".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn revert_move_refuses_to_overwrite() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let from = dir.path().join("a.txt");
+        let to = dir.path().join("b.txt");
+        fs::write(&to, "moved").unwrap();
+        fs::write(&from, "reused").unwrap();
+        let op = JournalOp::Move {
+            from: from.to_string_lossy().into_owned(),
+            to: to.to_string_lossy().into_owned(),
+        };
+
+        let (status, _) = revert(&op, dir.path(), &FilerRoots::default()).unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        fs::remove_file(&from).unwrap();
+        assert_eq!(
+            revert(&op, dir.path(), &FilerRoots::default()).unwrap(),
+            from
+        );
+        assert_eq!(fs::read_to_string(&from).unwrap(), "moved");
+        assert!(!to.exists());
+    }
+}
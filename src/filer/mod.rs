@@ -1,3 +1,13 @@
 // v0.3: ファイラ機能
 pub mod api;
+pub mod bookmarks;
+pub mod dedupe;
+pub mod du;
+pub mod git;
+pub mod journal;
 pub mod preview;
+pub mod roots;
+pub mod share;
+pub mod trash;
+pub mod upload;
+pub mod watch;
@@ -22,12 +22,12 @@ use std::{fs, io};
 
 use crate::AppState;
 
-use super::api::{ErrorResponse, err, resolve_path};
+use super::api::{ErrorResponse, err};
 
 /// Token lifetime: renewed every time the preview is toggled open.
 const PREVIEW_TTL: Duration = Duration::from_secs(15 * 60);
 
-/// Max served asset size (matches `/api/filer/download`).
+/// Max served asset size (assets are read into memory).
 const MAX_PREVIEW_SIZE: u64 = 100 * 1024 * 1024;
 
 /// Max live preview sessions (defensive cap — each is tiny but unbounded
@@ -120,9 +120,10 @@ pub async fn create_session(
     Json(req): Json<CreateRequest>,
 ) -> Result<Json<CreateResponse>, ApiError> {
     let store = state.preview_store.clone();
+    let roots = state.filer_roots.clone();
 
     tokio::task::spawn_blocking(move || {
-        let path = resolve_path(&req.path)?;
+        let path = roots.resolve(&req.path)?;
 
         let metadata = fs::metadata(&path).map_err(|e| io_err(e, "Not found"))?;
         if !metadata.is_file() {
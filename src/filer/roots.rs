@@ -0,0 +1,161 @@
+//! Optional filer root jail (`DEN_FILER_ROOTS`).
+//!
+//! When roots are configured, every local path the filer touches must
+//! resolve inside one of them. `resolve_path` canonicalizes the path (or its
+//! nearest existing ancestor), so a symlink pointing outside a root is
+//! rejected by the same prefix check.
+
+use axum::{Json, http::StatusCode};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::api::{ErrorResponse, err, resolve_entry_path, resolve_path};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(Clone, Default)]
+pub struct FilerRoots {
+    /// None = unrestricted
+    roots: Option<Arc<[PathBuf]>>,
+}
+
+impl FilerRoots {
+    /// Canonicalize the configured roots. Roots that cannot be resolved are
+    /// dropped with a warning; if none remain, every path is denied rather
+    /// than silently falling back to unrestricted access.
+    pub fn new(configured: &[String]) -> Self {
+        if configured.is_empty() {
+            return Self::default();
+        }
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for raw in configured {
+            match resolve_path(raw) {
+                Ok(root) if root.is_dir() => {
+                    if !roots.contains(&root) {
+                        roots.push(root);
+                    }
+                }
+                _ => tracing::warn!("filer: root {raw} is not an existing directory, ignored"),
+            }
+        }
+        if roots.is_empty() {
+            tracing::error!("filer: no usable filer roots, local file access is disabled");
+        }
+        Self {
+            roots: Some(roots.into()),
+        }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        self.roots.is_some()
+    }
+
+    /// Configured roots (empty when unrestricted).
+    pub fn roots(&self) -> &[PathBuf] {
+        self.roots.as_deref().unwrap_or_default()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        match &self.roots {
+            None => true,
+            Some(roots) => roots.iter().any(|root| path.starts_with(root)),
+        }
+    }
+
+    /// `resolve_path` plus the jail check.
+    ///
+    /// `~` (the panel's default start) falls back to the first root when the
+    /// home directory is outside the jail.
+    pub fn resolve(&self, raw: &str) -> Result<PathBuf, ApiError> {
+        if raw == "~"
+            && let Some(first) = self.roots().first()
+        {
+            let home = resolve_path(raw)?;
+            return Ok(if self.contains(&home) {
+                home
+            } else {
+                first.clone()
+            });
+        }
+        self.check(resolve_path(raw)?)
+    }
+
+    /// Like `resolve`, but a symlink in the last component is kept as is
+    /// (delete/rename/copy/move act on the link, not on its target).
+    pub fn resolve_entry(&self, raw: &str) -> Result<PathBuf, ApiError> {
+        self.check(resolve_entry_path(raw)?)
+    }
+
+    fn check(&self, path: PathBuf) -> Result<PathBuf, ApiError> {
+        if !self.contains(&path) {
+            tracing::warn!("filer: denied access outside roots: {}", path.display());
+            return Err(err(
+                StatusCode::FORBIDDEN,
+                "Path is outside the allowed filer roots",
+            ));
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrestricted_by_default() {
+        let roots = FilerRoots::new(&[]);
+        assert!(!roots.is_restricted());
+        assert!(roots.resolve("~").is_ok());
+    }
+
+    #[test]
+    fn rejects_paths_outside_roots() {
+        let allowed = tempfile::TempDir::new().unwrap();
+        let other = tempfile::TempDir::new().unwrap();
+        let roots = FilerRoots::new(&[allowed.path().to_string_lossy().into_owned()]);
+
+        let inside = allowed.path().join("new.txt");
+        assert!(roots.resolve(&inside.to_string_lossy()).is_ok());
+        let (status, _) = roots.resolve(&other.path().to_string_lossy()).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let escape = allowed.path().join("..").join("x");
+        assert!(roots.resolve(&escape.to_string_lossy()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlink_escape() {
+        let allowed = tempfile::TempDir::new().unwrap();
+        let other = tempfile::TempDir::new().unwrap();
+        std::fs::write(other.path().join("secret.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(other.path(), allowed.path().join("link")).unwrap();
+        let roots = FilerRoots::new(&[allowed.path().to_string_lossy().into_owned()]);
+
+        let via_link = allowed.path().join("link").join("secret.txt");
+        assert!(roots.resolve(&via_link.to_string_lossy()).is_err());
+        let new_via_link = allowed.path().join("link").join("new.txt");
+        assert!(roots.resolve(&new_via_link.to_string_lossy()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_entry_keeps_link_inside_root() {
+        let allowed = tempfile::TempDir::new().unwrap();
+        let other = tempfile::TempDir::new().unwrap();
+        let link = allowed.path().join("link");
+        std::os::unix::fs::symlink(other.path(), &link).unwrap();
+        let roots = FilerRoots::new(&[allowed.path().to_string_lossy().into_owned()]);
+
+        let resolved = roots.resolve_entry(&link.to_string_lossy()).unwrap();
+        assert_eq!(resolved.file_name().unwrap(), "link");
+        assert!(resolved.starts_with(allowed.path().canonicalize().unwrap()));
+    }
+
+    #[test]
+    fn no_usable_roots_denies_everything() {
+        let roots = FilerRoots::new(&["/definitely/not/a/den/root".to_string()]);
+        assert!(roots.is_restricted());
+        assert!(roots.resolve("/").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! One-time, expiring download links for single files.
+//!
+//! `POST /api/filer/share` issues an unguessable token that lets anyone with
+//! the link download that one file, without a den login, until it expires or
+//! runs out of downloads. Links live in memory only, so a restart revokes
+//! them all.
+
+use axum::{
+    Json,
+    extract::{Path as AxumPath, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::AppState;
+use crate::auth::AuthUser;
+
+use super::api::{ErrorResponse, attachment_name, err, file_body, io_err};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const DEFAULT_EXPIRES_MINS: u64 = 60;
+/// 7 days
+const MAX_EXPIRES_MINS: u64 = 7 * 24 * 60;
+const DEFAULT_MAX_DOWNLOADS: u32 = 1;
+const MAX_DOWNLOADS: u32 = 1000;
+/// Defensive cap on live links; the one expiring soonest is dropped
+const MAX_SHARES: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareInfo {
+    pub token: String,
+    /// Path as requested (re-resolved against the filer roots on download)
+    pub path: String,
+    /// None = owner
+    pub created_by: Option<String>,
+    /// Unix seconds
+    pub expires_at: u64,
+    pub downloads_left: u32,
+}
+
+#[derive(Clone, Default)]
+pub struct ShareStore {
+    inner: Arc<Mutex<HashMap<String, ShareInfo>>>,
+}
+
+impl ShareStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn create(
+        &self,
+        path: String,
+        created_by: Option<String>,
+        expires_at: u64,
+        downloads: u32,
+    ) -> ShareInfo {
+        let mut bytes = [0u8; 32];
+        rand::rng().fill(&mut bytes[..]);
+        let share = ShareInfo {
+            token: hex::encode(bytes),
+            path,
+            created_by,
+            expires_at,
+            downloads_left: downloads,
+        };
+        let mut map = self.inner.lock().expect("share store poisoned");
+        prune_expired(&mut map);
+        if map.len() >= MAX_SHARES
+            && let Some(oldest) = map
+                .values()
+                .min_by_key(|s| s.expires_at)
+                .map(|s| s.token.clone())
+        {
+            map.remove(&oldest);
+        }
+        map.insert(share.token.clone(), share.clone());
+        share
+    }
+
+    /// Use up one download. The link is removed with its last download.
+    fn take(&self, token: &str) -> Option<String> {
+        let mut map = self.inner.lock().expect("share store poisoned");
+        prune_expired(&mut map);
+        let share = map.get_mut(token)?;
+        share.downloads_left -= 1;
+        let path = share.path.clone();
+        if share.downloads_left == 0 {
+            map.remove(token);
+        }
+        Some(path)
+    }
+
+    fn peek(&self, token: &str) -> Option<String> {
+        let mut map = self.inner.lock().expect("share store poisoned");
+        prune_expired(&mut map);
+        map.get(token).map(|s| s.path.clone())
+    }
+
+    /// Soonest to expire first
+    fn list(&self, filter: impl Fn(&ShareInfo) -> bool) -> Vec<ShareInfo> {
+        let mut map = self.inner.lock().expect("share store poisoned");
+        prune_expired(&mut map);
+        let mut shares: Vec<ShareInfo> = map.values().filter(|s| filter(s)).cloned().collect();
+        shares.sort_by_key(|s| s.expires_at);
+        shares
+    }
+
+    fn revoke(&self, token: &str, allowed: impl Fn(&ShareInfo) -> bool) -> bool {
+        let mut map = self.inner.lock().expect("share store poisoned");
+        if map.get(token).is_some_and(allowed) {
+            map.remove(token);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn prune_expired(map: &mut HashMap<String, ShareInfo>) {
+    let now = unix_now();
+    map.retain(|_, s| s.expires_at > now);
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Admins see and revoke every link, others only their own
+fn visible_to(user: &AuthUser, share: &ShareInfo) -> bool {
+    user.is_admin() || share.created_by == user.username
+}
+
+// --- Handlers ---
+
+#[derive(Deserialize)]
+pub struct CreateRequest {
+    pub path: String,
+    /// Default 60, at most 7 days
+    pub expires_in_mins: Option<u64>,
+    /// Default 1, at most 1000
+    pub max_downloads: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct CreateResponse {
+    #[serde(flatten)]
+    pub share: ShareInfo,
+    /// Path of the public download link (prefix with the den origin)
+    pub url: String,
+}
+
+/// POST /api/filer/share
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<AuthUser>,
+    Json(req): Json<CreateRequest>,
+) -> Result<Json<CreateResponse>, ApiError> {
+    let expires_in_mins = req.expires_in_mins.unwrap_or(DEFAULT_EXPIRES_MINS);
+    if !(1..=MAX_EXPIRES_MINS).contains(&expires_in_mins) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "expires_in_mins must be between 1 and 10080",
+        ));
+    }
+    let max_downloads = req.max_downloads.unwrap_or(DEFAULT_MAX_DOWNLOADS);
+    if !(1..=MAX_DOWNLOADS).contains(&max_downloads) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "max_downloads must be between 1 and 1000",
+        ));
+    }
+
+    let roots = state.filer_roots.clone();
+    let path = req.path.clone();
+    let metadata = tokio::task::spawn_blocking(move || {
+        let resolved = roots.resolve(&path)?;
+        std::fs::metadata(&resolved).map_err(io_err)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))??;
+    if !metadata.is_file() {
+        return Err(err(StatusCode::BAD_REQUEST, "Not a file"));
+    }
+
+    let share = state.shares.create(
+        req.path,
+        user.username,
+        unix_now() + expires_in_mins * 60,
+        max_downloads,
+    );
+    tracing::info!(
+        "filer: shared {} ({} download(s), {} min)",
+        share.path,
+        max_downloads,
+        expires_in_mins
+    );
+    Ok(Json(CreateResponse {
+        url: format!("/api/filer/shared/{}", share.token),
+        share,
+    }))
+}
+
+/// GET /api/filer/share
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<AuthUser>,
+) -> Json<Vec<ShareInfo>> {
+    Json(state.shares.list(|s| visible_to(&user, s)))
+}
+
+/// DELETE /api/filer/share/{token}
+pub async fn revoke(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<AuthUser>,
+    AxumPath(token): AxumPath<String>,
+) -> StatusCode {
+    if state.shares.revoke(&token, |s| visible_to(&user, s)) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// GET /api/filer/shared/{token} — public; the token is the only authorization.
+/// HEAD requests (link previews) don't use up a download.
+pub async fn serve(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    AxumPath(token): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    let gone = || err(StatusCode::NOT_FOUND, "Link expired or already used");
+    let path = state.shares.peek(&token).ok_or_else(gone)?;
+
+    // The filer roots may have changed since the link was made
+    let resolved = state.filer_roots.resolve(&path)?;
+    let metadata = tokio::fs::metadata(&resolved).await.map_err(io_err)?;
+    if !metadata.is_file() {
+        return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+    }
+    let file = tokio::fs::File::open(&resolved).await.map_err(io_err)?;
+    // 開けたときだけ回数を消費する（ファイルが無い・ルート外なら link は残る）。
+    // 同時アクセスで最後の 1 回を取り合った場合は負けた側が 404
+    if method != Method::HEAD && state.shares.take(&token).is_none() {
+        return Err(gone());
+    }
+    let len = metadata.len();
+    let file_name = resolved
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    if method != Method::HEAD {
+        tracing::info!("filer: shared link downloaded: {}", resolved.display());
+    }
+
+    let mut resp = (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", attachment_name(&file_name)),
+            ),
+            (header::CONTENT_LENGTH, len.to_string()),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        file_body(file, len),
+    )
+        .into_response();
+    resp.headers_mut().insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    resp.headers_mut().insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downloads_are_counted() {
+        let store = ShareStore::new();
+        let share = store.create("a.txt".into(), None, unix_now() + 60, 2);
+        assert_eq!(store.peek(&share.token).as_deref(), Some("a.txt"));
+        assert_eq!(store.take(&share.token).as_deref(), Some("a.txt"));
+        assert_eq!(store.list(|_| true)[0].downloads_left, 1);
+        assert_eq!(store.take(&share.token).as_deref(), Some("a.txt"));
+        assert_eq!(store.take(&share.token), None);
+        assert!(store.list(|_| true).is_empty());
+    }
+
+    #[test]
+    fn expired_links_are_dropped() {
+        let store = ShareStore::new();
+        let share = store.create("a.txt".into(), None, unix_now(), 1);
+        assert_eq!(store.take(&share.token), None);
+        assert!(store.list(|_| true).is_empty());
+    }
+
+    #[test]
+    fn revoke_checks_ownership() {
+        let store = ShareStore::new();
+        let share = store.create("a.txt".into(), Some("alice".into()), unix_now() + 60, 1);
+        assert!(!store.revoke(&share.token, |s| s.created_by.as_deref() == Some("bob")));
+        assert!(store.revoke(&share.token, |s| s.created_by.as_deref() == Some("alice")));
+        assert!(!store.revoke(&share.token, |_| true));
+    }
+}
@@ -0,0 +1,255 @@
+//! Managed trash for soft filer deletes.
+//!
+//! `DELETE /api/filer/delete?trash=true` moves the item to
+//! `<data_dir>/trash/<id>/files/<name>` next to a `meta.json` recording the
+//! original path, so it can be listed, restored or purged later.
+
+use axum::{
+    Json,
+    extract::{Path as AxumPath, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fs, io};
+
+use crate::AppState;
+use crate::cleanup::CleanupContext;
+
+use super::api::{
+    ConflictPolicy, CopyMoveResponse, ErrorResponse, err, io_err, move_or_copy, remove_path,
+    unique_destination,
+};
+use super::roots::FilerRoots;
+
+const TRASH_DIR: &str = "trash";
+const META_FILE: &str = "meta.json";
+const FILES_DIR: &str = "files";
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    pub deleted_at: String,
+    pub is_dir: bool,
+    /// File size (0 for directories)
+    pub size: u64,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreQuery {
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+pub(super) fn trash_root(state: &AppState) -> PathBuf {
+    PathBuf::from(&state.config.data_dir).join(TRASH_DIR)
+}
+
+/// Move `path` into the trash and record where it came from.
+pub(super) fn move_to_trash(root: &Path, path: &Path) -> Result<TrashEntry, ApiError> {
+    let metadata = fs::symlink_metadata(path).map_err(io_err)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| err(StatusCode::BAD_REQUEST, "Cannot trash a root directory"))?
+        .to_string_lossy()
+        .into_owned();
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let item_dir = root.join(&id);
+    let files_dir = item_dir.join(FILES_DIR);
+    fs::create_dir_all(&files_dir).map_err(io_err)?;
+
+    let entry = TrashEntry {
+        id,
+        name: name.clone(),
+        original_path: path.to_string_lossy().into_owned(),
+        deleted_at: chrono::Utc::now().to_rfc3339(),
+        is_dir: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+    };
+    // メタデータを先に書く: 移動後にクラッシュしても一覧から辿れるように
+    let result = write_meta(&item_dir, &entry)
+        .map_err(io_err)
+        .and_then(|()| move_or_copy(path, &files_dir.join(&name)));
+    if let Err(e) = result {
+        if let Err(cleanup) = fs::remove_dir_all(&item_dir) {
+            tracing::debug!("filer: trash cleanup failed: {cleanup}");
+        }
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+/// Startup cleanup: remove trash items a crash left half-made. An item whose
+/// `files` directory is missing or empty holds nothing (the move never
+/// happened, so the original is still in place); anything with data is kept.
+pub fn cleanup_orphaned_items(ctx: &CleanupContext) -> io::Result<usize> {
+    let read_dir = match fs::read_dir(ctx.data_dir.join(TRASH_DIR)) {
+        Ok(rd) => rd,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    for dir in read_dir.filter_map(Result::ok) {
+        let item_dir = dir.path();
+        if uuid::Uuid::parse_str(&dir.file_name().to_string_lossy()).is_err() || !item_dir.is_dir()
+        {
+            continue;
+        }
+        let has_files =
+            fs::read_dir(item_dir.join(FILES_DIR)).is_ok_and(|mut files| files.next().is_some());
+        if has_files {
+            continue;
+        }
+        match fs::remove_dir_all(&item_dir) {
+            Ok(()) => removed += 1,
+            Err(e) => tracing::warn!("filer: cannot remove {}: {e}", item_dir.display()),
+        }
+    }
+    Ok(removed)
+}
+
+fn write_meta(item_dir: &Path, entry: &TrashEntry) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(entry).map_err(io::Error::other)?;
+    fs::write(item_dir.join(META_FILE), json)
+}
+
+fn load_entries(root: &Path) -> Vec<TrashEntry> {
+    let read_dir = match fs::read_dir(root) {
+        Ok(rd) => rd,
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!("filer: cannot read trash {}: {e}", root.display());
+            }
+            return Vec::new();
+        }
+    };
+    let mut entries: Vec<TrashEntry> = read_dir
+        .filter_map(Result::ok)
+        .filter_map(|dir| {
+            let meta_path = dir.path().join(META_FILE);
+            let content = fs::read_to_string(&meta_path).ok()?;
+            match serde_json::from_str(&content) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    tracing::warn!("filer: corrupt trash entry {}: {e}", meta_path.display());
+                    None
+                }
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    entries
+}
+
+/// Trash item directory for `id`; ids are UUIDs, anything else is unknown
+/// (also keeps `..` and separators out of the path).
+fn item_dir(root: &Path, id: &str) -> Result<PathBuf, ApiError> {
+    let not_found = || err(StatusCode::NOT_FOUND, "Trash item not found");
+    uuid::Uuid::parse_str(id).map_err(|_| not_found())?;
+    let dir = root.join(id);
+    if !dir.join(META_FILE).is_file() {
+        return Err(not_found());
+    }
+    Ok(dir)
+}
+
+/// Move a trashed item back to its original path; returns where it landed.
+pub(super) fn restore_item(
+    root: &Path,
+    roots: &FilerRoots,
+    id: &str,
+    on_conflict: ConflictPolicy,
+) -> Result<PathBuf, ApiError> {
+    let dir = item_dir(root, id)?;
+    let content = fs::read_to_string(dir.join(META_FILE)).map_err(io_err)?;
+    let entry: TrashEntry = serde_json::from_str(&content)
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Corrupt trash entry"))?;
+
+    // 削除後に DEN_FILER_ROOTS が狭められていれば元の場所には戻せない（403）
+    let mut target = roots.resolve_entry(&entry.original_path)?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(io_err)?;
+    }
+    if fs::symlink_metadata(&target).is_ok() {
+        match on_conflict {
+            ConflictPolicy::Fail => {
+                return Err(err(StatusCode::CONFLICT, "Destination already exists"));
+            }
+            ConflictPolicy::Overwrite => remove_path(&target).map_err(io_err)?,
+            ConflictPolicy::Rename => {
+                target = unique_destination(&target)
+                    .ok_or_else(|| err(StatusCode::CONFLICT, "No free destination name"))?;
+            }
+        }
+    }
+
+    tracing::info!("filer: restore {} -> {}", entry.name, target.display());
+    move_or_copy(&dir.join(FILES_DIR).join(&entry.name), &target)?;
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        tracing::warn!("filer: trash cleanup for {id} failed: {e}");
+    }
+    Ok(target)
+}
+
+// --- Handlers ---
+
+/// GET /api/filer/trash
+pub async fn list(State(state): State<Arc<AppState>>) -> Result<Json<Vec<TrashEntry>>, ApiError> {
+    let root = trash_root(&state);
+    tokio::task::spawn_blocking(move || Ok(Json(load_entries(&root))))
+        .await
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// POST /api/filer/trash/{id}/restore?on_conflict=fail|overwrite|rename
+pub async fn restore(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    Query(q): Query<RestoreQuery>,
+) -> Result<Json<CopyMoveResponse>, ApiError> {
+    let root = trash_root(&state);
+    let roots = state.filer_roots.clone();
+    tokio::task::spawn_blocking(move || {
+        let target = restore_item(&root, &roots, &id, q.on_conflict)?;
+        Ok(Json(CopyMoveResponse::new(&target)))
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// DELETE /api/filer/trash/{id}
+pub async fn purge(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let root = trash_root(&state);
+    tokio::task::spawn_blocking(move || {
+        let dir = item_dir(&root, &id)?;
+        tracing::info!("filer: purge trash item {id}");
+        fs::remove_dir_all(&dir).map_err(io_err)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
+
+/// DELETE /api/filer/trash
+pub async fn empty(State(state): State<Arc<AppState>>) -> Result<StatusCode, ApiError> {
+    let root = trash_root(&state);
+    tokio::task::spawn_blocking(move || {
+        tracing::info!("filer: empty trash");
+        match fs::remove_dir_all(&root) {
+            Ok(()) => Ok(StatusCode::NO_CONTENT),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(StatusCode::NO_CONTENT),
+            Err(e) => Err(io_err(e)),
+        }
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+}
@@ -0,0 +1,386 @@
+//! Chunked, resumable uploads for large local files.
+//!
+//! `init` reserves a hidden `.part` file next to the destination, chunks are
+//! written straight to disk at their offsets, and `complete` renames the part
+//! file into place. After a dropped connection the client asks for the
+//! session status and resumes from `received`.
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path as AxumPath, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::AppState;
+use crate::cleanup::CleanupContext;
+use crate::store::Store;
+
+use super::api::{ErrorResponse, err, io_err};
+
+/// Max body size of one chunk request (enforced as the route's body limit)
+pub const MAX_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Concurrent upload sessions
+const MAX_SESSIONS: usize = 16;
+
+/// Sessions without a chunk for this long are dropped with their part file
+const SESSION_IDLE_TTL: Duration = Duration::from_secs(60 * 60);
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+struct UploadSession {
+    dest: PathBuf,
+    part: PathBuf,
+    size: u64,
+    received: u64,
+    overwrite: bool,
+    /// A chunk write is in flight (chunks of one session are serialized)
+    writing: bool,
+    last_active: Instant,
+}
+
+#[derive(Clone)]
+pub struct UploadManager {
+    sessions: Arc<Mutex<HashMap<String, UploadSession>>>,
+    /// Part files of open sessions are journaled to `uploads.json` so a crash
+    /// doesn't leave them behind (see [`cleanup_orphaned_parts`])
+    store: Store,
+}
+
+impl UploadManager {
+    pub fn new(store: Store) -> Self {
+        Self {
+            sessions: Arc::default(),
+            store,
+        }
+    }
+
+    /// Record the part files of the open sessions (called with the lock held
+    /// so journal writes follow the session map in order)
+    fn journal(&self, sessions: &HashMap<String, UploadSession>) {
+        let parts: Vec<PathBuf> = sessions.values().map(|s| s.part.clone()).collect();
+        if let Err(e) = self.store.save_pending_uploads(&parts) {
+            tracing::warn!("filer: failed to save upload journal: {e}");
+        }
+    }
+
+    fn status(&self, id: &str) -> Result<UploadStatus, ApiError> {
+        let sessions = self.sessions.lock().expect("upload sessions poisoned");
+        let session = sessions.get(id).ok_or_else(not_found)?;
+        Ok(UploadStatus::new(id, session))
+    }
+
+    /// Remove idle sessions and return their part files for deletion.
+    fn prune_idle(&self) -> Vec<PathBuf> {
+        let mut sessions = self.sessions.lock().expect("upload sessions poisoned");
+        let now = Instant::now();
+        let mut stale = Vec::new();
+        sessions.retain(|id, s| {
+            let keep = s.writing || now.duration_since(s.last_active) < SESSION_IDLE_TTL;
+            if !keep {
+                tracing::info!("filer: upload {id} expired");
+                stale.push(s.part.clone());
+            }
+            keep
+        });
+        if !stale.is_empty() {
+            self.journal(&sessions);
+        }
+        stale
+    }
+}
+
+fn not_found() -> ApiError {
+    err(StatusCode::NOT_FOUND, "Upload session not found")
+}
+
+#[derive(Deserialize)]
+pub struct InitRequest {
+    /// Destination directory
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ChunkQuery {
+    pub offset: u64,
+}
+
+#[derive(Serialize)]
+pub struct UploadStatus {
+    id: String,
+    path: String,
+    size: u64,
+    received: u64,
+    /// Largest accepted chunk body
+    chunk_size: usize,
+}
+
+impl UploadStatus {
+    fn new(id: &str, session: &UploadSession) -> Self {
+        Self {
+            id: id.to_string(),
+            path: session.dest.to_string_lossy().into_owned(),
+            size: session.size,
+            received: session.received,
+            chunk_size: MAX_CHUNK_SIZE,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CompleteResponse {
+    path: String,
+    size: u64,
+}
+
+/// POST /api/filer/upload/init
+pub async fn init(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<InitRequest>,
+) -> Result<(StatusCode, Json<UploadStatus>), ApiError> {
+    let manager = state.uploads.clone();
+    for part in manager.prune_idle() {
+        let _ = tokio::fs::remove_file(&part).await;
+    }
+
+    // パストラバーサル防止: ベースネームのみ使用
+    let file_name = Path::new(&req.name)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .filter(|n| !n.is_empty())
+        .ok_or_else(|| err(StatusCode::BAD_REQUEST, "Invalid file name"))?;
+    let dir = state.filer_roots.resolve(&req.path)?;
+    if !tokio::fs::metadata(&dir).await.map_err(io_err)?.is_dir() {
+        return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
+    }
+    let dest = dir.join(&file_name);
+    if !req.overwrite && tokio::fs::symlink_metadata(&dest).await.is_ok() {
+        return Err(err(StatusCode::CONFLICT, "Destination already exists"));
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let part = dir.join(format!(".{file_name}.{}.part", &id[..8]));
+    {
+        let sessions = manager.sessions.lock().expect("upload sessions poisoned");
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(err(
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many uploads in progress",
+            ));
+        }
+    }
+    // 書き込み権限やディスクの問題をここで表面化させる
+    tokio::fs::File::create(&part).await.map_err(io_err)?;
+
+    tracing::info!(
+        "filer: upload {id} started: {} ({} bytes)",
+        dest.display(),
+        req.size
+    );
+    let session = UploadSession {
+        dest,
+        part,
+        size: req.size,
+        received: 0,
+        overwrite: req.overwrite,
+        writing: false,
+        last_active: Instant::now(),
+    };
+    let status = UploadStatus::new(&id, &session);
+    let mut sessions = manager.sessions.lock().expect("upload sessions poisoned");
+    sessions.insert(id, session);
+    manager.journal(&sessions);
+    Ok((StatusCode::CREATED, Json(status)))
+}
+
+/// GET /api/filer/upload/{id}
+pub async fn status(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Json<UploadStatus>, ApiError> {
+    state.uploads.status(&id).map(Json)
+}
+
+/// PUT /api/filer/upload/{id}?offset=N (raw body)
+///
+/// `offset` may rewind (a resent chunk) but must not skip past `received`.
+pub async fn chunk(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    Query(q): Query<ChunkQuery>,
+    body: Bytes,
+) -> Result<Json<UploadStatus>, ApiError> {
+    let manager = state.uploads.clone();
+    let part = {
+        let mut sessions = manager.sessions.lock().expect("upload sessions poisoned");
+        let session = sessions.get_mut(&id).ok_or_else(not_found)?;
+        if session.writing {
+            return Err(err(StatusCode::CONFLICT, "Another chunk is being written"));
+        }
+        if q.offset > session.received {
+            return Err(err(
+                StatusCode::CONFLICT,
+                &format!("Offset {} is past received {}", q.offset, session.received),
+            ));
+        }
+        if q.offset + body.len() as u64 > session.size {
+            return Err(err(StatusCode::BAD_REQUEST, "Chunk exceeds declared size"));
+        }
+        session.writing = true;
+        session.part.clone()
+    };
+
+    // クライアント切断でこの future が drop されても writing を戻す
+    let guard = WritingGuard {
+        manager: &manager,
+        id: &id,
+    };
+    let result = write_at(&part, q.offset, &body).await;
+    drop(guard);
+
+    let mut sessions = manager.sessions.lock().expect("upload sessions poisoned");
+    let session = sessions.get_mut(&id).ok_or_else(not_found)?;
+    session.last_active = Instant::now();
+    result.map_err(io_err)?;
+    session.received = session.received.max(q.offset + body.len() as u64);
+    Ok(Json(UploadStatus::new(&id, session)))
+}
+
+struct WritingGuard<'a> {
+    manager: &'a UploadManager,
+    id: &'a str,
+}
+
+impl Drop for WritingGuard<'_> {
+    fn drop(&mut self) {
+        let mut sessions = self
+            .manager
+            .sessions
+            .lock()
+            .expect("upload sessions poisoned");
+        if let Some(session) = sessions.get_mut(self.id) {
+            session.writing = false;
+        }
+    }
+}
+
+async fn write_at(part: &Path, offset: u64, data: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(part).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    file.write_all(data).await?;
+    file.flush().await
+}
+
+/// POST /api/filer/upload/{id}/complete
+pub async fn complete(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<(StatusCode, Json<CompleteResponse>), ApiError> {
+    let session = {
+        let mut sessions = state
+            .uploads
+            .sessions
+            .lock()
+            .expect("upload sessions poisoned");
+        let session = sessions.get(&id).ok_or_else(not_found)?;
+        if session.writing {
+            return Err(err(StatusCode::CONFLICT, "A chunk is still being written"));
+        }
+        if session.received < session.size {
+            return Err(err(
+                StatusCode::CONFLICT,
+                &format!(
+                    "Upload incomplete: {} of {} bytes",
+                    session.received, session.size
+                ),
+            ));
+        }
+        let session = sessions.remove(&id).ok_or_else(not_found)?;
+        state.uploads.journal(&sessions);
+        session
+    };
+
+    if !session.overwrite && tokio::fs::symlink_metadata(&session.dest).await.is_ok() {
+        let _ = tokio::fs::remove_file(&session.part).await;
+        return Err(err(StatusCode::CONFLICT, "Destination already exists"));
+    }
+    if let Err(e) = tokio::fs::rename(&session.part, &session.dest).await {
+        let _ = tokio::fs::remove_file(&session.part).await;
+        return Err(io_err(e));
+    }
+    tracing::info!(
+        "filer: upload {id} complete: {} ({} bytes)",
+        session.dest.display(),
+        session.size
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(CompleteResponse {
+            path: session.dest.to_string_lossy().into_owned(),
+            size: session.size,
+        }),
+    ))
+}
+
+/// DELETE /api/filer/upload/{id}
+pub async fn abort(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let session = {
+        let mut sessions = state
+            .uploads
+            .sessions
+            .lock()
+            .expect("upload sessions poisoned");
+        if sessions.get(&id).is_some_and(|s| s.writing) {
+            return Err(err(StatusCode::CONFLICT, "A chunk is still being written"));
+        }
+        let session = sessions.remove(&id).ok_or_else(not_found)?;
+        state.uploads.journal(&sessions);
+        session
+    };
+    tracing::info!("filer: upload {id} aborted");
+    if let Err(e) = tokio::fs::remove_file(&session.part).await {
+        tracing::debug!("filer: remove {} failed: {e}", session.part.display());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Startup cleanup: delete part files journaled by a previous run that ended
+/// without completing or aborting them. Only hidden `.part` files are touched,
+/// in case the journal was edited by hand.
+pub fn cleanup_orphaned_parts(ctx: &CleanupContext) -> std::io::Result<usize> {
+    let parts = ctx.store.load_pending_uploads();
+    if parts.is_empty() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for part in &parts {
+        let is_part_file = part
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .is_some_and(|n| n.starts_with('.') && n.ends_with(".part"));
+        if !is_part_file {
+            continue;
+        }
+        match std::fs::remove_file(part) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("filer: cannot remove {}: {e}", part.display()),
+        }
+    }
+    ctx.store.save_pending_uploads(&[])?;
+    Ok(removed)
+}
@@ -0,0 +1,395 @@
+//! Directory change notifications for the file panel.
+//!
+//! `POST /api/filer/watch` registers a (non-recursive) watch on a directory;
+//! changes are debounced and published as `fs_change` events on the
+//! `/api/events` socket of the account that opened the watch, so open panels
+//! can refresh without polling.
+
+use axum::{
+    Extension, Json,
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher, event::ModifyKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::events::{Event, EventHub};
+
+use super::api::{ErrorResponse, err, io_err};
+
+/// Max live watches. The oldest is dropped when full (clients that went
+/// away without unwatching would otherwise leak inotify handles).
+const MAX_WATCHES: usize = 64;
+
+/// Changes arriving within this window are coalesced into one batch.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Beyond this many changes per watch in one batch, a single `rescan` is
+/// sent instead (e.g. a build dumping thousands of artifacts).
+const MAX_CHANGES_PER_BATCH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsChangeKind {
+    Create,
+    Modify,
+    Delete,
+    Rename,
+    /// Too many changes to list individually; reload the directory
+    Rescan,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Change {
+    kind: FsChangeKind,
+    path: PathBuf,
+    /// Rename source, when the backend reports both sides
+    from: Option<PathBuf>,
+}
+
+struct WatchEntry {
+    dir: PathBuf,
+    /// Username that opened the watch (None = the owner)
+    account: Option<String>,
+    created: Instant,
+}
+
+#[derive(Default)]
+struct WatchState {
+    /// Created lazily on the first watch (needs a running runtime)
+    watcher: Option<RecommendedWatcher>,
+    watches: HashMap<String, WatchEntry>,
+}
+
+#[derive(Clone)]
+pub struct WatchManager {
+    inner: Arc<Mutex<WatchState>>,
+    events: EventHub,
+}
+
+impl WatchManager {
+    pub fn new(events: EventHub) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(WatchState::default())),
+            events,
+        }
+    }
+
+    /// Start watching `dir` for `account`; returns the watch id.
+    pub fn add(&self, dir: PathBuf, account: Option<&str>) -> notify::Result<String> {
+        let mut state = self.inner.lock().expect("watch state poisoned");
+        if state.watcher.is_none() {
+            state.watcher = Some(self.spawn_watcher()?);
+        }
+
+        if state.watches.len() >= MAX_WATCHES
+            && let Some(oldest) = state
+                .watches
+                .iter()
+                .min_by_key(|(_, w)| w.created)
+                .map(|(id, _)| id.clone())
+        {
+            tracing::debug!("filer: watch limit reached, dropping {oldest}");
+            remove_locked(&mut state, &oldest);
+        }
+
+        let already_watched = state.watches.values().any(|w| w.dir == dir);
+        if !already_watched && let Some(watcher) = state.watcher.as_mut() {
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        state.watches.insert(
+            id.clone(),
+            WatchEntry {
+                dir,
+                account: account.map(str::to_string),
+                created: Instant::now(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Watches of other accounts are treated as missing.
+    pub fn remove(&self, id: &str, account: Option<&str>) -> bool {
+        let mut state = self.inner.lock().expect("watch state poisoned");
+        let owned = state
+            .watches
+            .get(id)
+            .is_some_and(|w| w.account.as_deref() == account);
+        owned && remove_locked(&mut state, id)
+    }
+
+    fn spawn_watcher(&self) -> notify::Result<RecommendedWatcher> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => {
+                    for change in classify(event) {
+                        let _ = tx.send(change);
+                    }
+                }
+                Err(e) => tracing::warn!("filer: watch error: {e}"),
+            })?;
+        tokio::spawn(debounce_loop(
+            rx,
+            Arc::downgrade(&self.inner),
+            self.events.clone(),
+        ));
+        Ok(watcher)
+    }
+}
+
+fn remove_locked(state: &mut WatchState, id: &str) -> bool {
+    let Some(entry) = state.watches.remove(id) else {
+        return false;
+    };
+    let still_watched = state.watches.values().any(|w| w.dir == entry.dir);
+    if !still_watched
+        && let Some(watcher) = state.watcher.as_mut()
+        && let Err(e) = watcher.unwatch(&entry.dir)
+    {
+        // The directory may already be gone, which drops the watch anyway
+        tracing::debug!("filer: unwatch {} failed: {e}", entry.dir.display());
+    }
+    true
+}
+
+/// Map a backend event to panel-level changes (access events are ignored).
+fn classify(event: notify::Event) -> Vec<Change> {
+    let kind = match event.kind {
+        EventKind::Create(_) => FsChangeKind::Create,
+        EventKind::Remove(_) => FsChangeKind::Delete,
+        EventKind::Modify(ModifyKind::Name(_)) => FsChangeKind::Rename,
+        EventKind::Modify(_) => FsChangeKind::Modify,
+        EventKind::Any | EventKind::Other => FsChangeKind::Modify,
+        EventKind::Access(_) => return Vec::new(),
+    };
+    match (kind, event.paths.as_slice()) {
+        (FsChangeKind::Rename, [from, to]) => vec![Change {
+            kind,
+            path: to.clone(),
+            from: Some(from.clone()),
+        }],
+        (_, paths) => paths
+            .iter()
+            .map(|path| Change {
+                kind,
+                path: path.clone(),
+                from: None,
+            })
+            .collect(),
+    }
+}
+
+async fn debounce_loop(
+    mut rx: mpsc::UnboundedReceiver<Change>,
+    state: std::sync::Weak<Mutex<WatchState>>,
+    events: EventHub,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut batch = vec![first];
+        let deadline = tokio::time::Instant::now() + DEBOUNCE;
+        while let Ok(Some(change)) = tokio::time::timeout_at(deadline, rx.recv()).await {
+            if !batch.contains(&change) {
+                batch.push(change);
+            }
+        }
+
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let watches: Vec<(String, PathBuf, Option<String>)> = {
+            let state = state.lock().expect("watch state poisoned");
+            state
+                .watches
+                .iter()
+                .map(|(id, w)| (id.clone(), w.dir.clone(), w.account.clone()))
+                .collect()
+        };
+        for (id, dir, account) in &watches {
+            for event in events_for_watch(id, dir, &batch) {
+                events.publish(account.as_deref(), &event);
+            }
+        }
+    }
+}
+
+/// Changes that touch `dir` itself or its direct children.
+fn events_for_watch(id: &str, dir: &Path, batch: &[Change]) -> Vec<Event> {
+    let touches = |p: &Path| p == dir || p.parent() == Some(dir);
+    let relevant: Vec<&Change> = batch
+        .iter()
+        .filter(|c| touches(&c.path) || c.from.as_deref().is_some_and(touches))
+        .collect();
+
+    if relevant.len() > MAX_CHANGES_PER_BATCH {
+        return vec![Event::FsChange {
+            watch_id: id.to_string(),
+            kind: FsChangeKind::Rescan,
+            path: dir.to_string_lossy().into_owned(),
+            from: None,
+        }];
+    }
+    relevant
+        .into_iter()
+        .map(|c| Event::FsChange {
+            watch_id: id.to_string(),
+            kind: c.kind,
+            path: c.path.to_string_lossy().into_owned(),
+            from: c.from.as_ref().map(|p| p.to_string_lossy().into_owned()),
+        })
+        .collect()
+}
+
+// --- Handlers ---
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(Deserialize)]
+pub struct WatchRequest {
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct WatchResponse {
+    pub id: String,
+    pub path: String,
+}
+
+/// POST /api/filer/watch
+pub async fn watch(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Json(req): Json<WatchRequest>,
+) -> Result<Json<WatchResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    let dir = tokio::task::spawn_blocking(move || {
+        let dir = roots.resolve(&req.path)?;
+        if !std::fs::metadata(&dir).map_err(io_err)?.is_dir() {
+            return Err(err(StatusCode::BAD_REQUEST, "Not a directory"));
+        }
+        Ok(dir)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))??;
+
+    let id = state
+        .watches
+        .add(dir.clone(), user.username.as_deref())
+        .map_err(|e| {
+            tracing::warn!("filer: watch {} failed: {e}", dir.display());
+            err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to watch directory",
+            )
+        })?;
+    tracing::debug!("filer: watch {} ({id})", dir.display());
+    Ok(Json(WatchResponse {
+        id,
+        path: dir.to_string_lossy().into_owned(),
+    }))
+}
+
+/// DELETE /api/filer/watch/{id}
+pub async fn unwatch(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    if state.watches.remove(&id, user.username.as_deref()) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(err(StatusCode::NOT_FOUND, "Watch not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RenameMode};
+
+    fn change(kind: FsChangeKind, path: &str) -> Change {
+        Change {
+            kind,
+            path: PathBuf::from(path),
+            from: None,
+        }
+    }
+
+    #[test]
+    fn classify_rename_pairs() {
+        let event = notify::Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("/w/a"))
+            .add_path(PathBuf::from("/w/b"));
+        assert_eq!(
+            classify(event),
+            vec![Change {
+                kind: FsChangeKind::Rename,
+                path: PathBuf::from("/w/b"),
+                from: Some(PathBuf::from("/w/a")),
+            }]
+        );
+
+        let event =
+            notify::Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/w/c"));
+        assert_eq!(classify(event), vec![change(FsChangeKind::Create, "/w/c")]);
+    }
+
+    #[test]
+    fn events_only_for_direct_children() {
+        let batch = vec![
+            change(FsChangeKind::Create, "/w/a"),
+            change(FsChangeKind::Modify, "/w/sub/b"),
+            change(FsChangeKind::Delete, "/other/c"),
+        ];
+        let events = events_for_watch("id", Path::new("/w"), &batch);
+        assert_eq!(events.len(), 1);
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["type"], "fs_change");
+        assert_eq!(json["kind"], "create");
+        assert_eq!(json["path"], "/w/a");
+    }
+
+    #[test]
+    fn large_batch_collapses_to_rescan() {
+        let batch: Vec<Change> = (0..=MAX_CHANGES_PER_BATCH)
+            .map(|i| change(FsChangeKind::Create, &format!("/w/{i}")))
+            .collect();
+        let events = events_for_watch("id", Path::new("/w"), &batch);
+        assert_eq!(events.len(), 1);
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["kind"], "rescan");
+    }
+
+    #[tokio::test]
+    async fn publishes_change_for_new_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let hub = EventHub::new();
+        let mut rx = hub.subscribe(Some("alice"));
+        let manager = WatchManager::new(hub);
+        let id = manager
+            .add(dir.path().to_path_buf(), Some("alice"))
+            .unwrap();
+
+        std::fs::write(dir.path().join("new.txt"), "x").unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("no fs_change event")
+            .unwrap();
+        assert!(msg.contains(r#""type":"fs_change""#));
+        assert!(msg.contains(&id));
+        assert!(msg.contains("new.txt"));
+
+        // Only the account that opened the watch can close it
+        assert!(!manager.remove(&id, None));
+        assert!(manager.remove(&id, Some("alice")));
+        assert!(!manager.remove(&id, Some("alice")));
+    }
+}
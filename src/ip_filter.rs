@@ -0,0 +1,367 @@
+//! Optional client IP allow/deny lists (`DEN_ALLOW_CIDRS` / `DEN_DENY_CIDRS`).
+//!
+//! Applied to every HTTP request before authentication and to SSH
+//! connections, so a leaked password is useless from outside the allowed
+//! networks. The deny list wins over the allow list; an empty allow list
+//! means "any address not denied".
+//!
+//! `DEN_TRUSTED_PROXIES` lists reverse proxies whose `X-Forwarded-For` /
+//! `X-Forwarded-Proto` headers describe the real client. The resolved
+//! [`ClientOrigin`] is what the lists, login rate limiting, the audit log and
+//! the cookie `Secure` flag see.
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::AppState;
+
+/// `10.0.0.0/8`, `fd00::/8`, or a bare address (single host)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid address: {s}"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|&p| p <= max)
+                .ok_or_else(|| format!("invalid prefix length: {s}"))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_eq(u32::from(net).into(), u32::from(ip).into(), 32, self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_eq(u128::from(net), u128::from(ip), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_eq(net: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+    if prefix == 0 {
+        return true;
+    }
+    let shift = u32::from(bits - prefix);
+    (net ^ ip) >> shift == 0
+}
+
+/// Entries are validated by `Config::from_env`; anything unparsable here is
+/// dropped with a warning.
+fn parse_cidrs(list: &[String]) -> Arc<[Cidr]> {
+    list.iter()
+        .filter_map(|s| match s.parse::<Cidr>() {
+            Ok(cidr) => Some(cidr),
+            Err(e) => {
+                tracing::warn!("Ignoring CIDR entry: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Default)]
+pub struct IpFilter {
+    allow: Arc<[Cidr]>,
+    deny: Arc<[Cidr]>,
+}
+
+impl IpFilter {
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        Self {
+            allow: parse_cidrs(allow),
+            deny: parse_cidrs(deny),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|c| c.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|c| c.contains(ip))
+    }
+}
+
+/// Reverse proxies whose `X-Forwarded-For` header is believed
+/// (`DEN_TRUSTED_PROXIES`). Without any, the TCP peer is the client.
+#[derive(Clone, Default)]
+pub struct TrustedProxies(Arc<[Cidr]>);
+
+impl TrustedProxies {
+    pub fn new(list: &[String]) -> Self {
+        Self(parse_cidrs(list))
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|c| c.contains(ip))
+    }
+
+    /// The real client address: the peer itself, or — when the peer is a
+    /// trusted proxy — the right-most `X-Forwarded-For` hop that isn't one.
+    /// Hops left of that are client-supplied and never trusted.
+    fn client_ip(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.contains(peer) {
+            return Some(peer);
+        }
+        let hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+        let mut client = peer;
+        for hop in hops.iter().rev() {
+            let Ok(ip) = hop.parse::<IpAddr>() else {
+                break;
+            };
+            client = ip;
+            if !self.contains(ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    pub fn origin(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> ClientOrigin {
+        let via_proxy = peer.is_some_and(|p| self.contains(p));
+        // The left-most value is the scheme the client used at the edge proxy
+        let forwarded_https = via_proxy
+            && headers
+                .get("x-forwarded-proto")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"));
+        ClientOrigin {
+            ip: self.client_ip(headers, peer),
+            forwarded_https,
+        }
+    }
+}
+
+/// Where a request really came from, after unwrapping trusted proxies.
+/// `ip_filter_middleware` inserts it as a request extension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientOrigin {
+    /// None = no peer address (only possible in-process)
+    pub ip: Option<IpAddr>,
+    /// A trusted proxy says the client connected over HTTPS
+    pub forwarded_https: bool,
+}
+
+impl ClientOrigin {
+    /// A connection over the local socket (`DEN_LOCAL_SOCKET`)
+    fn local() -> Self {
+        Self {
+            ip: Some(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)),
+            forwarded_https: false,
+        }
+    }
+}
+
+/// Request extension marking connections from the local socket listener.
+/// They count as 127.0.0.1 for the lists but never as a trusted proxy, so
+/// their `X-Forwarded-*` headers are ignored even if 127.0.0.1 is listed in
+/// `DEN_TRUSTED_PROXIES`.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalPeer;
+
+fn request_origin(proxies: &TrustedProxies, req: &Request<axum::body::Body>) -> ClientOrigin {
+    if req.extensions().get::<LocalPeer>().is_some() {
+        return ClientOrigin::local();
+    }
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    proxies.origin(req.headers(), peer)
+}
+
+/// Outermost HTTP layer: resolves the [`ClientOrigin`] and applies the lists
+/// to it. Requests without a peer address (only possible in-process) are
+/// rejected whenever a list is configured.
+pub async fn ip_filter_middleware(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let origin = request_origin(&state.trusted_proxies, &req);
+    req.extensions_mut().insert(origin);
+    if !state.ip_filter.is_active() {
+        return next.run(req).await;
+    }
+    match origin.ip {
+        Some(ip) if state.ip_filter.is_allowed(ip) => next.run(req).await,
+        _ => {
+            tracing::warn!("IP filter rejected {:?}: {}", origin.ip, req.uri().path());
+            StatusCode::FORBIDDEN.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_cidr() {
+        assert!("10.0.0.0/8".parse::<Cidr>().is_ok());
+        assert!("192.168.1.5".parse::<Cidr>().is_ok());
+        assert!("fd00::/8".parse::<Cidr>().is_ok());
+        assert!("0.0.0.0/0".parse::<Cidr>().is_ok());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+        assert!("vpn".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let net: Cidr = "100.64.0.0/10".parse().unwrap();
+        assert!(net.contains(ip("100.64.0.1")));
+        assert!(net.contains(ip("100.127.255.255")));
+        assert!(!net.contains(ip("100.128.0.0")));
+        // IPv4-mapped IPv6 peers (dual-stack listeners)
+        assert!(net.contains(ip("::ffff:100.64.0.1")));
+        assert!(!net.contains(ip("fd00::1")));
+
+        let host: Cidr = "192.168.1.5".parse().unwrap();
+        assert!(host.contains(ip("192.168.1.5")));
+        assert!(!host.contains(ip("192.168.1.6")));
+
+        let v6: Cidr = "fd7a:115c:a1e0::/48".parse().unwrap();
+        assert!(v6.contains(ip("fd7a:115c:a1e0::1")));
+        assert!(!v6.contains(ip("fd7a:115c:a1e1::1")));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let filter = IpFilter::new(&["10.0.0.0/8".to_string()], &["10.0.0.13".to_string()]);
+        assert!(filter.is_active());
+        assert!(filter.is_allowed(ip("10.1.2.3")));
+        assert!(!filter.is_allowed(ip("10.0.0.13")));
+        assert!(!filter.is_allowed(ip("192.168.0.1")));
+
+        let deny_only = IpFilter::new(&[], &["203.0.113.0/24".to_string()]);
+        assert!(deny_only.is_allowed(ip("192.168.0.1")));
+        assert!(!deny_only.is_allowed(ip("203.0.113.9")));
+
+        assert!(!IpFilter::default().is_active());
+        assert!(IpFilter::default().is_allowed(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn forwarded_for_only_from_trusted_proxies() {
+        let proxies = TrustedProxies::new(&["10.0.0.1".to_string(), "10.0.1.0/24".to_string()]);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "1.2.3.4, 203.0.113.7, 10.0.1.5".parse().unwrap(),
+        );
+        // Right-most untrusted hop; the spoofable left part is ignored
+        assert_eq!(
+            proxies.client_ip(&headers, Some(ip("10.0.0.1"))),
+            Some(ip("203.0.113.7"))
+        );
+        // Direct clients can't spoof the header
+        assert_eq!(
+            proxies.client_ip(&headers, Some(ip("198.51.100.2"))),
+            Some(ip("198.51.100.2"))
+        );
+        assert_eq!(
+            TrustedProxies::default().client_ip(&headers, Some(ip("10.0.0.1"))),
+            Some(ip("10.0.0.1"))
+        );
+        // Proxy without the header
+        assert_eq!(
+            proxies.client_ip(&HeaderMap::new(), Some(ip("10.0.0.1"))),
+            Some(ip("10.0.0.1"))
+        );
+        assert_eq!(proxies.client_ip(&headers, None), None);
+    }
+
+    #[test]
+    fn forwarded_proto_only_from_trusted_proxies() {
+        let proxies = TrustedProxies::new(&["127.0.0.1".to_string()]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+        assert_eq!(
+            proxies.origin(&headers, Some(ip("127.0.0.1"))),
+            ClientOrigin {
+                ip: Some(ip("203.0.113.7")),
+                forwarded_https: true,
+            }
+        );
+        assert!(
+            !proxies
+                .origin(&headers, Some(ip("192.0.2.1")))
+                .forwarded_https
+        );
+        headers.insert("x-forwarded-proto", "http".parse().unwrap());
+        assert!(
+            !proxies
+                .origin(&headers, Some(ip("127.0.0.1")))
+                .forwarded_https
+        );
+    }
+
+    #[test]
+    fn local_socket_is_never_a_trusted_proxy() {
+        let proxies = TrustedProxies::new(&["127.0.0.1".to_string()]);
+        let mut req = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7")
+            .header("x-forwarded-proto", "https")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let loopback = SocketAddr::new(ip("127.0.0.1"), 0);
+        req.extensions_mut().insert(ConnectInfo(loopback));
+        assert_eq!(request_origin(&proxies, &req).ip, Some(ip("203.0.113.7")));
+
+        req.extensions_mut().insert(LocalPeer);
+        assert_eq!(request_origin(&proxies, &req), ClientOrigin::local());
+        assert_eq!(ClientOrigin::local().ip, Some(ip("127.0.0.1")));
+    }
+}
@@ -1,43 +1,189 @@
 use tokio::net::TcpListener;
 
+pub mod acme;
+pub mod archive;
 pub mod assets;
+pub mod audit;
 pub mod auth;
+pub mod cleanup;
 pub mod clipboard_api;
 pub mod clipboard_monitor;
 pub mod config;
+pub mod dashboard;
+pub mod devices_api;
+pub mod diff;
+pub mod events;
 pub mod filer;
+pub mod ip_filter;
+pub mod live_settings;
+pub mod local_socket;
+pub mod logs;
+pub mod metrics;
 pub mod multiplexer_api;
+pub mod notify;
+pub mod oidc;
+pub mod openapi;
 pub mod pty;
 pub mod remote;
+pub mod service;
 pub mod sftp;
+pub mod shutdown;
+pub mod signed_url;
 pub mod ssh;
+pub mod status;
 pub mod store;
 pub mod store_api;
+pub mod systemd;
+pub mod telemetry;
 pub mod terminal_filter;
 pub mod tls;
+pub mod tokens_api;
 pub mod update;
+pub mod users_api;
+pub mod vault;
 pub mod ws;
 
 use axum::{
-    Router, middleware,
+    Router,
+    extract::DefaultBodyLimit,
+    http::{Extensions, HeaderMap, StatusCode, Version, header},
+    middleware,
     routing::{any, delete, get, post, put},
 };
 use config::Config;
 use pty::registry::SessionRegistry;
 use std::sync::Arc;
 use store::Store;
+use tower_http::compression::{CompressionLayer, DefaultPredicate, Predicate};
 
 pub struct AppState {
     pub config: Config,
     pub store: Store,
     pub registry: Arc<SessionRegistry>,
-    pub hmac_secret: Vec<u8>,
-    pub rate_limiter: auth::LoginRateLimiter,
+    /// オーナー設定の変更を再起動なしで反映する（shell / ssh_port / log_level など）
+    pub live_settings: live_settings::LiveSettings,
+    /// パスワード変更でローテーションされる（`hmac_secret()` で参照）
+    hmac_secret: std::sync::RwLock<Vec<u8>>,
+    /// Web ログインと SSH パスワード認証で共有
+    pub rate_limiter: Arc<auth::LoginRateLimiter>,
     pub sftp_manager: sftp::client::SftpManager,
     pub remote_manager: Arc<remote::RemoteManager>,
     pub tls_info: Option<tls::TlsInfo>,
     pub tls_certificate_der: Option<Vec<u8>>,
     pub preview_store: filer::preview::PreviewStore,
+    /// `POST /api/filer/share` で発行したダウンロードリンク（メモリのみ）
+    pub shares: filer::share::ShareStore,
+    pub events: events::EventHub,
+    /// 終了処理の開始を WebSocket ハンドラーに伝える
+    pub shutdown: shutdown::Shutdown,
+    /// 起動時の自己診断・警告と SSH リスナーの状態（GET /api/status）
+    pub diagnostics: status::Diagnostics,
+    pub transfers: sftp::transfer::TransferManager,
+    pub sync_jobs: sftp::sync::SyncManager,
+    pub watches: filer::watch::WatchManager,
+    pub du_jobs: filer::du::DuManager,
+    pub filer_journal: filer::journal::Journal,
+    pub uploads: filer::upload::UploadManager,
+    pub filer_roots: filer::roots::FilerRoots,
+    pub ip_filter: ip_filter::IpFilter,
+    pub trusted_proxies: ip_filter::TrustedProxies,
+    pub audit: audit::AuditLog,
+    /// None = OIDC シングルサインオン無効
+    pub oidc: Option<oidc::OidcManager>,
+    /// ログイン・セキュリティイベントの通知（DEN_NOTIFY_*）
+    pub notifier: notify::Notifier,
+    /// DEN_VAULT_KEYFILE から読んだ保管庫の鍵（`vault_key()` で参照）
+    vault_keyfile_key: Option<vault::VaultKey>,
+    /// オーナーパスワードで開いたデータ鍵（開けるまで None。失敗はキャッシュしない）
+    vault_password_key: std::sync::Mutex<Option<vault::VaultKey>>,
+}
+
+impl AppState {
+    /// ログイントークンの署名に使う現在の HMAC シークレット
+    pub fn hmac_secret(&self) -> Vec<u8> {
+        self.hmac_secret.read().unwrap().clone()
+    }
+
+    /// HMAC シークレットを作り直し、発行済みのログイントークンを全て無効にする。
+    /// DEN_PERSIST_SECRET 指定時は新しいシークレットを保存する（blocking）。
+    pub fn rotate_hmac_secret(&self) {
+        let secret = rand::random::<[u8; 32]>().to_vec();
+        if self.config.persist_secret
+            && let Err(e) = self.store.save_hmac_secret(&secret)
+        {
+            tracing::warn!("Failed to persist rotated HMAC secret: {e}");
+        }
+        *self.hmac_secret.write().unwrap() = secret;
+    }
+
+    /// オーナーの認証情報（パスワード変更済みなら保存したハッシュ、なければ環境変数）
+    pub fn owner_credential(&self) -> auth::OwnerCredential {
+        auth::current_owner_credential(&self.config.owner_credential(), &self.store)
+    }
+
+    /// シークレット保管庫の鍵: DEN_VAULT_KEYFILE、なければオーナーの平文パスワードで
+    /// 包んだデータ鍵。パスワードがハッシュでしか分からない場合は、オーナーが
+    /// パスワードでログインする（`unlock_vault`）まで None。
+    /// 初回は argon2 で鍵を開くため blocking。
+    pub fn vault_key(&self) -> Option<vault::VaultKey> {
+        if let Some(ref key) = self.vault_keyfile_key {
+            return Some(key.clone());
+        }
+        match self.owner_credential().plaintext() {
+            Some(password) => self.open_vault(password),
+            None => self.vault_password_key.lock().unwrap().clone(),
+        }
+    }
+
+    /// オーナーのパスワードログイン成功時に保管庫を開く（blocking）
+    pub fn unlock_vault(&self, password: &str) {
+        if self.vault_key_from_password() {
+            self.open_vault(password);
+        }
+    }
+
+    fn open_vault(&self, password: &str) -> Option<vault::VaultKey> {
+        let mut cached = self.vault_password_key.lock().unwrap();
+        if let Some(ref key) = *cached {
+            return Some(key.clone());
+        }
+        let key = vault::unlock(&self.store, password)
+            .inspect_err(|e| tracing::warn!("Cannot open secrets vault: {e}"))
+            .ok()?;
+        *cached = Some(key.clone());
+        Some(key)
+    }
+
+    /// オーナーパスワードの変更時にデータ鍵を新しいパスワードで包み直す（blocking）。
+    /// 封印済みのエントリはデータ鍵のままなので書き換えない。
+    pub fn rewrap_vault_key(&self, old_password: &str, new_password: &str) {
+        if !self.vault_key_from_password() {
+            return;
+        }
+        let mut cached = self.vault_password_key.lock().unwrap();
+        let key = match cached.clone() {
+            Some(key) => key,
+            None => match vault::unlock(&self.store, old_password) {
+                Ok(key) => key,
+                Err(e) => {
+                    tracing::warn!("Cannot re-wrap secrets vault key: {e}");
+                    return;
+                }
+            },
+        };
+        match self
+            .store
+            .save_vault_key(&vault::WrappedKey::wrap(&key, new_password))
+        {
+            Ok(()) => *cached = Some(key),
+            Err(e) => tracing::warn!("Failed to save secrets vault key: {e}"),
+        }
+    }
+
+    /// Whether the vault key follows the owner password (and must be re-keyed on change)
+    pub fn vault_key_from_password(&self) -> bool {
+        self.vault_keyfile_key.is_none()
+    }
 }
 
 /// アプリケーション Router を構築（テストからも利用可能）
@@ -47,9 +193,16 @@ pub fn create_app(
     store: Store,
     tls_runtime: Option<&tls::TlsRuntime>,
 ) -> (Router, Arc<AppState>) {
-    // 起動ごとにランダムな HMAC シークレットを生成
-    // 再起動で全トークンが無効化される（セキュリティ上望ましい）
-    let hmac_secret: Vec<u8> = rand::random::<[u8; 32]>().to_vec();
+    // 既定では起動ごとにランダムな HMAC シークレットを生成し、再起動で全トークンを無効化する。
+    // DEN_PERSIST_SECRET 指定時は data_dir に保存したシークレットを使い回す。
+    let hmac_secret: Vec<u8> = if config.persist_secret {
+        store.load_or_create_hmac_secret().unwrap_or_else(|e| {
+            tracing::warn!("Failed to load persisted HMAC secret, using a random one: {e}");
+            rand::random::<[u8; 32]>().to_vec()
+        })
+    } else {
+        rand::random::<[u8; 32]>().to_vec()
+    };
     create_app_with_secret(config, registry, hmac_secret, store, tls_runtime)
 }
 
@@ -61,63 +214,285 @@ pub fn create_app_with_secret(
     store: Store,
     tls_runtime: Option<&tls::TlsRuntime>,
 ) -> (Router, Arc<AppState>) {
-    // NOTE: 永続化状態を追加する場合は、ここでスタートアップ時の整合性チェックを実装すること。
-    // 例: 前回の異常終了で中断状態のままのリソースをリセットする（orphaned state cleanup）。
-    // 以前はセッション永続化に対して store.cleanup_stale_running_sessions() を呼んでいた。
+    // 前回の異常終了で中断状態のまま残ったリソースを片付ける（orphaned state cleanup）。
+    // 永続化状態を追加する場合は cleanup::TASKS に登録すること。
+    cleanup::run(&cleanup::CleanupContext {
+        data_dir: std::path::Path::new(&config.data_dir),
+        store: &store,
+    });
 
     let sftp_manager = sftp::client::SftpManager::new(store.clone());
 
     let remote_manager = Arc::new(remote::RemoteManager::default());
 
+    let events = events::EventHub::new();
+    let transfers = sftp::transfer::TransferManager::new(events.clone());
+    let sync_jobs = sftp::sync::SyncManager::new(store.clone());
+    let watches = filer::watch::WatchManager::new(events.clone());
+    let du_jobs = filer::du::DuManager::new(events.clone());
+    let uploads = filer::upload::UploadManager::new(store.clone());
+    let filer_roots = filer::roots::FilerRoots::new(&config.filer_roots);
+    metrics::init();
+    let ip_filter = ip_filter::IpFilter::new(&config.allow_cidrs, &config.deny_cidrs);
+    let trusted_proxies = ip_filter::TrustedProxies::new(&config.trusted_proxies);
+    let audit = audit::AuditLog::new(&config.data_dir);
+    let rate_limiter = Arc::new(auth::LoginRateLimiter::persistent(store.clone()));
+    let oidc = config.oidc.clone().map(oidc::OidcManager::new);
+    let notifier = notify::Notifier::new(config.notify.clone());
+    let vault_keyfile_key = config.vault_keyfile.as_deref().map(|path| {
+        vault::VaultKey::from_keyfile(path).expect("DEN_VAULT_KEYFILE is validated at startup")
+    });
+    if vault_keyfile_key.is_none()
+        && auth::current_owner_credential(&config.owner_credential(), &store)
+            .plaintext()
+            .is_none()
+    {
+        tracing::info!(
+            "Secrets vault stays locked until the owner signs in with the password (or set DEN_VAULT_KEYFILE)"
+        );
+    }
+
+    let live_settings = live_settings::LiveSettings::new(store.load_settings());
     let state = Arc::new(AppState {
         config,
         store,
         registry,
-        hmac_secret,
-        rate_limiter: auth::LoginRateLimiter::new(),
+        live_settings,
+        hmac_secret: std::sync::RwLock::new(hmac_secret),
+        rate_limiter,
         sftp_manager,
         remote_manager,
         tls_info: tls_runtime.map(|tls| tls.info.clone()),
         tls_certificate_der: tls_runtime.map(|tls| tls.certificate_der.clone()),
         preview_store: filer::preview::PreviewStore::new(),
+        shares: filer::share::ShareStore::new(),
+        events,
+        shutdown: shutdown::Shutdown::new(),
+        diagnostics: status::Diagnostics::default(),
+        transfers,
+        sync_jobs,
+        watches,
+        du_jobs,
+        filer_journal: filer::journal::Journal::new(),
+        uploads,
+        filer_roots,
+        ip_filter,
+        trusted_proxies,
+        audit,
+        oidc,
+        notifier,
+        vault_keyfile_key,
+        vault_password_key: std::sync::Mutex::new(None),
     });
 
+    // DEN_ADMIN_PORT 指定時、管理用エンドポイントは admin_app() の別リスナーだけで提供する
+    let router = build_router(&state, state.config.admin_listener.is_none());
+    (router, state)
+}
+
+/// Router for the admin listener (`DEN_ADMIN_PORT`): everything, including
+/// the management endpoints that the main listener then leaves out
+pub fn admin_app(state: &Arc<AppState>) -> Router {
+    build_router(state, true)
+}
+
+/// `admin` = mount the management endpoints (user management, audit log,
+/// diagnostics, self-update, `/metrics`)
+fn build_router(state: &Arc<AppState>, admin: bool) -> Router {
+    let disabled = state.config.disabled;
+
+    // 無効化されたサブシステム（DEN_DISABLE_*）のルートはマウントしない
+    let mut public_feature_routes = Router::new();
+    let mut user_feature_routes = Router::new();
+    let mut feature_routes = Router::new();
+    let mut owner_feature_routes = Router::new();
+    let mut admin_feature_routes = Router::new();
+    if !disabled.filer {
+        public_feature_routes = public_feature_routes
+            // Filer HTML preview — token in URL path is the sole authorization,
+            // so the parent den_token cookie never reaches this endpoint. The
+            // iframe uses sandbox="allow-scripts" with a null origin.
+            .route(
+                "/api/filer/preview/{token}/{*path}",
+                get(filer::preview::serve),
+            )
+            // Shared download link — the token is the sole authorization
+            .route("/api/filer/shared/{token}", get(filer::share::serve));
+        feature_routes = feature_routes
+            // Filer API
+            .route("/api/filer/list", get(filer::api::list))
+            .route("/api/filer/read", get(filer::api::read))
+            .route("/api/filer/tail", get(filer::api::tail))
+            .route("/api/filer/preview", get(filer::api::preview))
+            .route("/api/filer/write", put(filer::api::write))
+            .route("/api/filer/mkdir", post(filer::api::mkdir))
+            .route("/api/filer/create", post(filer::api::create))
+            .route("/api/filer/attributes", post(filer::api::attributes))
+            .route("/api/filer/symlink", post(filer::api::symlink))
+            .route("/api/filer/rename", post(filer::api::rename))
+            .route("/api/filer/copy", post(filer::api::copy))
+            .route("/api/filer/move", post(filer::api::move_path))
+            .route("/api/filer/bulk", post(filer::api::bulk))
+            .route(
+                "/api/filer/journal",
+                get(filer::journal::list).delete(filer::journal::clear),
+            )
+            .route("/api/filer/undo", post(filer::journal::undo))
+            .route("/api/filer/delete", delete(filer::api::delete))
+            .route(
+                "/api/filer/trash",
+                get(filer::trash::list).delete(filer::trash::empty),
+            )
+            .route("/api/filer/trash/{id}", delete(filer::trash::purge))
+            .route("/api/filer/trash/{id}/restore", post(filer::trash::restore))
+            .route("/api/filer/download", get(filer::api::download))
+            .route("/api/filer/download-dir", get(filer::api::download_dir))
+            .route(
+                "/api/filer/share",
+                get(filer::share::list).post(filer::share::create),
+            )
+            .route("/api/filer/share/{token}", delete(filer::share::revoke))
+            .route("/api/filer/sign-url", post(signed_url::sign_filer))
+            .route(
+                "/api/filer/upload",
+                post(filer::api::upload)
+                    .layer(DefaultBodyLimit::max(filer::api::UPLOAD_BODY_LIMIT)),
+            )
+            .route("/api/filer/upload/init", post(filer::upload::init))
+            .route(
+                "/api/filer/upload/{id}",
+                get(filer::upload::status)
+                    .put(filer::upload::chunk)
+                    .delete(filer::upload::abort)
+                    .layer(DefaultBodyLimit::max(filer::upload::MAX_CHUNK_SIZE)),
+            )
+            .route(
+                "/api/filer/upload/{id}/complete",
+                post(filer::upload::complete),
+            )
+            .route("/api/filer/search", get(filer::api::search))
+            .route("/api/filer/checksum", get(filer::api::checksum))
+            .route("/api/filer/watch", post(filer::watch::watch))
+            .route("/api/filer/watch/{id}", delete(filer::watch::unwatch))
+            .route(
+                "/api/filer/recent",
+                get(filer::bookmarks::recent).delete(filer::bookmarks::clear_recent),
+            )
+            .route(
+                "/api/filer/bookmarks",
+                get(filer::bookmarks::list).post(filer::bookmarks::create),
+            )
+            .route(
+                "/api/filer/bookmarks/{id}",
+                put(filer::bookmarks::update).delete(filer::bookmarks::remove),
+            )
+            .route("/api/filer/dedupe-scan", post(filer::dedupe::scan))
+            .route("/api/filer/du", post(filer::du::start))
+            .route(
+                "/api/filer/du/{id}",
+                get(filer::du::status).delete(filer::du::cancel),
+            )
+            // Filer HTML preview — session management (issuing and revoking tokens
+            // require the normal user auth; the actual asset serve is token-only).
+            .route(
+                "/api/filer/preview-session",
+                post(filer::preview::create_session),
+            )
+            .route(
+                "/api/filer/preview-session/{token}",
+                delete(filer::preview::revoke_session),
+            )
+            .route("/api/diff", post(diff::diff));
+    }
+    if !disabled.sftp {
+        owner_feature_routes = owner_feature_routes
+            // SFTP API
+            .route("/api/sftp/connect", post(sftp::api::connect))
+            .route("/api/sftp/status", get(sftp::api::status))
+            .route("/api/sftp/disconnect", post(sftp::api::disconnect))
+            .route("/api/sftp/list", get(sftp::api::list))
+            .route("/api/sftp/read", get(sftp::api::read))
+            .route("/api/sftp/tail", get(sftp::api::tail))
+            .route("/api/sftp/write", put(sftp::api::write))
+            .route("/api/sftp/append", post(sftp::api::append))
+            .route("/api/sftp/mkdir", post(sftp::api::mkdir))
+            .route("/api/sftp/create", post(sftp::api::create))
+            .route("/api/sftp/rename", post(sftp::api::rename))
+            .route("/api/sftp/delete", delete(sftp::api::delete))
+            .route("/api/sftp/batch", post(sftp::api::batch))
+            .route("/api/sftp/download", get(sftp::api::download))
+            .route("/api/sftp/download-many", get(sftp::api::download_many))
+            .route("/api/sftp/sign-url", post(signed_url::sign_sftp))
+            .route("/api/sftp/upload", post(sftp::api::upload))
+            .route("/api/sftp/search", get(sftp::api::search))
+            .route("/api/sftp/statvfs", get(sftp::api::statvfs))
+            .route("/api/sftp/checksum", get(sftp::api::checksum))
+            // Tracked transfers (progress is pushed over /api/events)
+            .route("/api/transfer", post(sftp::api::copy))
+            .route("/api/transfers", get(sftp::transfer::list))
+            .route("/api/transfers/{id}", delete(sftp::transfer::cancel))
+            .route(
+                "/api/sync-jobs",
+                get(sftp::sync::list).post(sftp::sync::create),
+            )
+            .route(
+                "/api/sync-jobs/{id}",
+                put(sftp::sync::update).delete(sftp::sync::remove),
+            )
+            .route("/api/sync-jobs/{id}/run", post(sftp::sync::run))
+            .route(
+                "/api/sftp/known-hosts",
+                get(sftp::api::list_known_hosts)
+                    .post(sftp::api::trust_host)
+                    .delete(sftp::api::remove_known_host),
+            )
+            .route(
+                "/api/sftp/saved-passwords",
+                get(sftp::api::list_saved_passwords).delete(sftp::api::forget_saved_password),
+            );
+    }
+    if !disabled.remote {
+        user_feature_routes = user_feature_routes
+            .route(
+                "/api/system/tls/trusted",
+                get(tls::list_trusted)
+                    .post(tls::trust)
+                    .patch(tls::update_trusted_display_name)
+                    .delete(tls::remove_trusted),
+            )
+            .route("/api/remote/connect", post(remote::connect))
+            .route("/api/remote/connections", get(remote::list_connections))
+            .route("/api/remote/{id}/disconnect", post(remote::disconnect))
+            .route("/api/remote/{id}/ws", get(remote::remote_ws_handler))
+            .route(
+                "/api/remote/{id}/{*rest}",
+                any(remote::remote_proxy_catch_all),
+            );
+    }
+    if !disabled.update {
+        feature_routes = feature_routes.route("/api/system/version", get(update::get_version));
+        admin_feature_routes =
+            admin_feature_routes.route("/api/system/update", post(update::do_update));
+    }
+
     // 認証不要のルート
     let public_routes = Router::new()
         .route("/api/login", post(auth::login))
         .route("/api/logout", post(auth::logout))
+        .route("/api/auth/cert", post(auth::cert_login))
+        .route("/api/auth/oidc", get(oidc::status))
+        .route("/api/auth/oidc/login", get(oidc::login))
+        .route("/api/auth/oidc/callback", get(oidc::callback))
         .route("/api/system/tls", get(tls::status))
         .route("/api/system/tls/certificate", get(tls::certificate))
-        // Filer HTML preview — token in URL path is the sole authorization,
-        // so the parent den_token cookie never reaches this endpoint. The
-        // iframe uses sandbox="allow-scripts" with a null origin.
-        .route(
-            "/api/filer/preview/{token}/{*path}",
-            get(filer::preview::serve),
-        )
         .route("/", get(assets::serve_index))
-        .route("/{*path}", get(assets::serve_static));
+        .route("/{*path}", get(assets::serve_static))
+        .merge(public_feature_routes);
 
-    let user_only_routes = Router::new()
-        .route(
-            "/api/system/tls/trusted",
-            get(tls::list_trusted)
-                .post(tls::trust)
-                .patch(tls::update_trusted_display_name)
-                .delete(tls::remove_trusted),
-        )
-        .route("/api/remote/connect", post(remote::connect))
-        .route("/api/remote/connections", get(remote::list_connections))
-        .route("/api/remote/{id}/disconnect", post(remote::disconnect))
-        .route("/api/remote/{id}/ws", get(remote::remote_ws_handler))
-        .route(
-            "/api/remote/{id}/{*rest}",
-            any(remote::remote_proxy_catch_all),
-        )
-        .layer(middleware::from_fn_with_state(
-            Arc::clone(&state),
-            auth::user_auth_middleware,
-        ));
+    let user_only_routes = user_feature_routes.layer(middleware::from_fn_with_state(
+        Arc::clone(state),
+        auth::user_auth_middleware,
+    ));
 
     // 認証必要のルート（Cookie / Authorization ヘッダーで認証）
     let protected_routes = Router::new()
@@ -127,15 +502,10 @@ pub fn create_app_with_secret(
             "/api/keep-awake",
             get(store_api::get_keep_awake).put(store_api::put_keep_awake),
         )
-        // Clipboard history API
-        .route(
-            "/api/clipboard-history",
-            get(clipboard_api::get_clipboard_history)
-                .post(clipboard_api::add_clipboard_entry)
-                .delete(clipboard_api::clear_clipboard_history),
-        )
         // WebSocket: Cookie 認証（ブラウザが自動で Cookie を送信）
         .route("/api/ws", get(ws::ws_handler))
+        // Global event stream (transfer progress etc.)
+        .route("/api/events", get(events::ws_handler))
         // Terminal session management API
         .route(
             "/api/terminal/sessions",
@@ -151,62 +521,159 @@ pub fn create_app_with_secret(
         .route("/api/multiplexer/kill", post(multiplexer_api::kill))
         .route("/api/multiplexer/delete", post(multiplexer_api::delete))
         .route("/api/multiplexer/rename", post(multiplexer_api::rename))
-        // Filer API
-        .route("/api/filer/list", get(filer::api::list))
-        .route("/api/filer/read", get(filer::api::read))
-        .route("/api/filer/write", put(filer::api::write))
-        .route("/api/filer/mkdir", post(filer::api::mkdir))
-        .route("/api/filer/rename", post(filer::api::rename))
-        .route("/api/filer/delete", delete(filer::api::delete))
-        .route("/api/filer/download", get(filer::api::download))
-        .route("/api/filer/upload", post(filer::api::upload))
-        .route("/api/filer/search", get(filer::api::search))
-        // Filer HTML preview — session management (issuing and revoking tokens
-        // require the normal user auth; the actual asset serve is token-only).
+        .route("/api/auth/me", get(auth::me))
+        .route("/api/auth/change-password", post(auth::change_password))
         .route(
-            "/api/filer/preview-session",
-            post(filer::preview::create_session),
+            "/api/tokens",
+            get(tokens_api::list_tokens).post(tokens_api::create_token),
         )
+        .route("/api/tokens/{id}", delete(tokens_api::revoke_token))
+        .route("/api/devices", get(devices_api::list_devices))
         .route(
-            "/api/filer/preview-session/{token}",
-            delete(filer::preview::revoke_session),
+            "/api/devices/{id}",
+            put(devices_api::rename_device).delete(devices_api::revoke_device),
         )
-        // SFTP API
-        .route("/api/sftp/connect", post(sftp::api::connect))
-        .route("/api/sftp/status", get(sftp::api::status))
-        .route("/api/sftp/disconnect", post(sftp::api::disconnect))
-        .route("/api/sftp/list", get(sftp::api::list))
-        .route("/api/sftp/read", get(sftp::api::read))
-        .route("/api/sftp/write", put(sftp::api::write))
-        .route("/api/sftp/mkdir", post(sftp::api::mkdir))
-        .route("/api/sftp/rename", post(sftp::api::rename))
-        .route("/api/sftp/delete", delete(sftp::api::delete))
-        .route("/api/sftp/download", get(sftp::api::download))
-        .route("/api/sftp/upload", post(sftp::api::upload))
-        .route("/api/sftp/search", get(sftp::api::search))
-        // System update API
-        .route("/api/system/version", get(update::get_version))
-        .route("/api/system/update", post(update::do_update))
+        .route("/api/system/features", get(store_api::get_features))
+        .route("/api/version", get(update::version))
+        .route("/api/dashboard", get(dashboard::handler))
+        .route("/api/openapi.json", get(openapi::spec))
+        .route("/api/docs", get(openapi::swagger_ui))
+        .merge(feature_routes)
+        // 変更系リクエストを監査ログに記録（auth_middleware の内側）
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            audit::audit_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            auth::auth_middleware,
+        ));
+
+    // オーナーのみ（SFTP 接続・クリップボード履歴はサーバーに 1 つしかない）
+    let owner_routes = owner_feature_routes
+        // Clipboard history API
         .route(
-            "/api/sftp/known-hosts",
-            get(sftp::api::list_known_hosts)
-                .post(sftp::api::trust_host)
-                .delete(sftp::api::remove_known_host),
+            "/api/clipboard-history",
+            get(clipboard_api::get_clipboard_history)
+                .post(clipboard_api::add_clipboard_entry)
+                .delete(clipboard_api::clear_clipboard_history),
         )
+        .layer(middleware::from_fn(auth::owner_middleware))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            audit::audit_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
-            Arc::clone(&state),
+            Arc::clone(state),
             auth::auth_middleware,
         ));
 
-    let router = Router::new()
+    // 管理者のみ（ユーザー管理・自己更新・監査ログ・診断）。auth_middleware の内側で role を確認する
+    let admin_routes = admin_feature_routes
+        .route(
+            "/api/users",
+            get(users_api::list_users).post(users_api::create_user),
+        )
+        .route(
+            "/api/users/{name}",
+            put(users_api::update_user).delete(users_api::delete_user),
+        )
+        .route("/api/audit", get(audit::list))
+        .route("/api/logs/tail", get(logs::tail_handler))
+        .route("/api/status", get(status::handler))
+        .route("/api/auth/bans", get(auth::login_bans))
+        .layer(middleware::from_fn(auth::admin_middleware))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            audit::audit_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            auth::auth_middleware,
+        ));
+
+    let mut router = Router::new();
+    if admin {
+        router = router
+            .merge(admin_routes)
+            // 管理者ログイン・metrics:read トークン・DEN_METRICS_TOKEN のいずれかで認証
+            .route("/metrics", get(metrics::handler));
+    }
+    let mut router = router
         .merge(user_only_routes)
+        .merge(owner_routes)
         .merge(protected_routes)
         .merge(public_routes)
         // CSP ヘッダーを全レスポンスに付与（XSS 防止）
-        .layer(middleware::from_fn(auth::csp_middleware))
-        .with_state(Arc::clone(&state));
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            auth::csp_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            auth::security_headers_middleware,
+        ))
+        .layer(middleware::from_fn(metrics::latency_middleware))
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            telemetry::trace_middleware,
+        ));
+    // JSON・テキストのレスポンスを gzip / brotli で圧縮（モバイル回線でのファイル一覧など）
+    router = router.layer(
+        CompressionLayer::new()
+            .gzip(true)
+            .br(true)
+            .compress_when(DefaultPredicate::new().and(is_compressible as CompressPredicate)),
+    );
+    // プリフライト（OPTIONS）は認証なしで応答する必要があるので認証の外側に置く
+    if let Some(ref cors) = state.config.cors {
+        router = router.layer(auth::cors_layer(cors));
+    }
+    router
+        // 認証より前にクライアント IP を制限（公開ルート・静的ファイルも含む）
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(state),
+            ip_filter::ip_filter_middleware,
+        ))
+        .with_state(Arc::clone(state))
+}
 
-    (router, state)
+type CompressPredicate = fn(StatusCode, Version, &HeaderMap, &Extensions) -> bool;
+
+/// JSON / text / scripts only: file downloads are often already compressed,
+/// and a Range response must keep the byte offsets of the original file.
+fn is_compressible(
+    status: StatusCode,
+    _version: Version,
+    headers: &HeaderMap,
+    _extensions: &Extensions,
+) -> bool {
+    if status == StatusCode::PARTIAL_CONTENT || headers.contains_key(header::CONTENT_RANGE) {
+        return false;
+    }
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+        )
 }
 
 /// Bind a TCP listener with retries (handles port still held by previous process after update).
@@ -0,0 +1,97 @@
+//! Owner settings that take effect without restarting den.
+//!
+//! `PUT /api/settings` publishes the owner's saved settings here. Each
+//! subscriber watches the part it owns:
+//! - `shell` and sleep prevention: the session registry (`spawn_registry_subscriber`)
+//! - `ssh_port`: the SSH server, restarted on the new port by `main`
+//! - `log_level`: the tracing filter, reloaded by `main`
+//!
+//! Unset values fall back to the environment (`DEN_SHELL`, `DEN_SSH_PORT`,
+//! `RUST_LOG` / `DEN_LOG_LEVEL`).
+
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing_subscriber::EnvFilter;
+
+use crate::AppState;
+use crate::config::Config;
+use crate::store::Settings;
+
+pub struct LiveSettings {
+    tx: watch::Sender<Arc<Settings>>,
+}
+
+impl LiveSettings {
+    pub fn new(initial: Settings) -> Self {
+        Self {
+            tx: watch::channel(Arc::new(initial)).0,
+        }
+    }
+
+    /// Hand newly saved owner settings to every subscriber
+    pub fn publish(&self, settings: Settings) {
+        self.tx.send_replace(Arc::new(settings));
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Settings>> {
+        self.tx.subscribe()
+    }
+}
+
+/// Shell for new sessions
+pub fn shell(settings: &Settings, config: &Config) -> String {
+    settings
+        .shell
+        .clone()
+        .unwrap_or_else(|| config.shell.clone())
+}
+
+/// SSH server port (None = SSH off)
+pub fn ssh_port(settings: &Settings, config: &Config) -> Option<u16> {
+    match settings.ssh_port {
+        Some(0) => None,
+        Some(port) => Some(port),
+        None => config.ssh_port,
+    }
+}
+
+/// Filter for `log_level` (None = the startup filter from the environment)
+pub fn log_filter(log_level: Option<&str>, config: &Config) -> EnvFilter {
+    log_level
+        .and_then(|level| EnvFilter::try_new(level).ok())
+        .or_else(|| EnvFilter::try_from_default_env().ok())
+        .unwrap_or_else(|| EnvFilter::new(&config.log_level))
+}
+
+/// Apply shell and sleep prevention changes to the registry. Running
+/// sessions keep their shell; mux layouts are rewritten for the new one.
+pub fn spawn_registry_subscriber(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    let mut rx = state.live_settings.subscribe();
+    tokio::spawn(async move {
+        // 起動時の値は registry 生成時に反映済み
+        let mut applied_shell = state.registry.shell();
+        while rx.changed().await.is_ok() {
+            let settings = rx.borrow_and_update().clone();
+            state
+                .registry
+                .update_sleep_config(
+                    settings.sleep_prevention_mode,
+                    settings.sleep_prevention_timeout,
+                )
+                .await;
+            let shell = shell(&settings, &state.config);
+            if shell == applied_shell {
+                continue;
+            }
+            let data_dir = std::path::PathBuf::from(&state.config.data_dir);
+            let mux_shell = shell.clone();
+            let _ = tokio::task::spawn_blocking(move || {
+                crate::assets::ensure_mux_layouts(&data_dir, &mux_shell)
+            })
+            .await;
+            state.registry.set_shell(shell.clone());
+            tracing::info!("Shell for new sessions: {shell}");
+            applied_shell = shell;
+        }
+    })
+}
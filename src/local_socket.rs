@@ -0,0 +1,205 @@
+//! Local-only HTTP listener on a unix socket or Windows named pipe
+//! (`DEN_LOCAL_SOCKET`), served alongside the TCP listener.
+//!
+//! Companion tools on the same machine can reach the API without a network
+//! port, e.g. `curl --unix-socket ~/.den.sock -H "Authorization: Bearer .."
+//! http://den/api/...`. Authentication is unchanged; requests appear to come
+//! from 127.0.0.1 but are marked [`LocalPeer`], so `DEN_TRUSTED_PROXIES`
+//! never applies to them. The unix socket is bound inside a private (0700)
+//! directory and made owner-only (0600) before it appears at its path. The
+//! named pipe keeps its default DACL (only the creating account, SYSTEM and
+//! administrators may write) and rejects remote clients.
+
+use axum::Router;
+use axum::extract::ConnectInfo;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::service::TowerToHyperService;
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::ip_filter::LocalPeer;
+use crate::shutdown::Shutdown;
+
+/// Peer address handlers see for local connections (rate limiting, IP lists)
+const LOCAL_PEER: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+fn spawn_connection<I>(
+    connections: &mut JoinSet<()>,
+    io: I,
+    app: &Router,
+    mut closing: watch::Receiver<bool>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = tower::ServiceExt::map_request(app.clone(), |mut req: axum::http::Request<_>| {
+        req.extensions_mut().insert(ConnectInfo(LOCAL_PEER));
+        req.extensions_mut().insert(LocalPeer);
+        req
+    });
+    connections.spawn(async move {
+        let builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
+        let conn = builder
+            .serve_connection_with_upgrades(TokioIo::new(io), TowerToHyperService::new(service));
+        tokio::pin!(conn);
+        let result = tokio::select! {
+            result = conn.as_mut() => result,
+            _ = closing.changed() => {
+                conn.as_mut().graceful_shutdown();
+                conn.await
+            }
+        };
+        if let Err(err) = result {
+            tracing::debug!("Local socket connection error: {err}");
+        }
+    });
+}
+
+/// Serve `app` on the unix socket at `path` until shutdown begins, then
+/// drain open connections and remove the socket file.
+#[cfg(unix)]
+pub async fn serve(path: &str, app: Router, shutdown: Shutdown) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use tokio::net::UnixListener;
+
+    // 前回の異常終了で残ったソケットファイルは削除（使用中なら起動しない）
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{path} is in use by another process"),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    // 0700 の一時ディレクトリ内で bind して 0600 にしてから rename で公開する
+    // （bind 直後の umask 依存のパーミッションを他ユーザーに見せない）
+    let staging = format!("{path}.{:016x}", rand::random::<u64>());
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = std::path::Path::new(&staging).join("sock");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    let listener = bound?;
+
+    let (closing_tx, closing_rx) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    loop {
+        let accepted = tokio::select! {
+            _ = shutdown.started() => break,
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            accepted = listener.accept() => accepted,
+        };
+        match accepted {
+            Ok((stream, _)) => spawn_connection(&mut connections, stream, &app, closing_rx.clone()),
+            Err(e) => {
+                tracing::warn!("Local socket accept failed: {e}");
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    drop(listener);
+    let _ = std::fs::remove_file(path);
+    let _ = closing_tx.send(true);
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// Serve `app` on the named pipe `path` (`\\.\pipe\...`) until shutdown
+/// begins, then drain open connections.
+#[cfg(windows)]
+pub async fn serve(path: &str, app: Router, shutdown: Shutdown) -> io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    // 同名パイプを別プロセスが作成済みならエラー（乗っ取り防止）
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(path)?;
+
+    let (closing_tx, closing_rx) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    loop {
+        let connected = tokio::select! {
+            _ = shutdown.started() => break,
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            connected = server.connect() => connected,
+        };
+        // 次のクライアント用のインスタンスを先に用意してから引き渡す
+        let next = ServerOptions::new()
+            .reject_remote_clients(true)
+            .create(path)?;
+        let client = std::mem::replace(&mut server, next);
+        match connected {
+            Ok(()) => spawn_connection(&mut connections, client, &app, closing_rx.clone()),
+            Err(e) => tracing::warn!("Named pipe connect failed: {e}"),
+        }
+    }
+
+    drop(server);
+    let _ = closing_tx.send(true);
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn serves_http_on_owner_only_socket_and_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("den.sock");
+        let path_str = path.to_str().unwrap().to_string();
+        let app = Router::new().route(
+            "/peer",
+            axum::routing::get(|ConnectInfo(addr): ConnectInfo<SocketAddr>| async move {
+                addr.ip().to_string()
+            }),
+        );
+        let shutdown = Shutdown::new();
+        let server = tokio::spawn({
+            let shutdown = shutdown.clone();
+            let path = path_str.clone();
+            async move { serve(&path, app, shutdown).await }
+        });
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /peer HTTP/1.1\r\nHost: den\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("127.0.0.1"), "{response}");
+
+        // A second instance must not steal a live socket
+        let err = serve(&path_str, Router::new(), Shutdown::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        // Only the socket is left in the directory (no staging leftovers)
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        shutdown.begin(false);
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+}
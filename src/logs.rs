@@ -0,0 +1,370 @@
+//! Log files under `data_dir/logs/`.
+//!
+//! The file output is plain text (default) or JSON Lines (`DEN_LOG_FORMAT`).
+//! Files are named `den.log.<period>` and start over every day or hour
+//! (`DEN_LOG_ROTATION`); with `DEN_LOG_MAX_SIZE_MB` a full file continues in
+//! `den.log.<period>.1`, `.2`, ... Only the newest `DEN_LOG_MAX_FILES` files
+//! are kept. `GET /api/logs/tail` shows the end of the current file to
+//! admins.
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::AppState;
+
+const FILE_PREFIX: &str = "den.log";
+const DEFAULT_TAIL_LINES: usize = 200;
+const MAX_TAIL_LINES: usize = 2000;
+/// Only this much of the end of the file is read for a tail
+const MAX_TAIL_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl LogRotation {
+    /// File name part for the period containing `now` (UTC)
+    fn period(self, now: chrono::DateTime<chrono::Utc>) -> String {
+        match self {
+            Self::Hourly => now.format("%Y-%m-%d-%H").to_string(),
+            Self::Daily => now.format("%Y-%m-%d").to_string(),
+            Self::Never => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub rotation: LogRotation,
+    /// None = no size limit
+    pub max_size_bytes: Option<u64>,
+    /// 0 = keep every file
+    pub max_files: usize,
+}
+
+fn file_name(period: &str, index: u32) -> String {
+    match (period.is_empty(), index) {
+        (true, 0) => FILE_PREFIX.to_string(),
+        (true, n) => format!("{FILE_PREFIX}.{n}"),
+        (false, 0) => format!("{FILE_PREFIX}.{period}"),
+        (false, n) => format!("{FILE_PREFIX}.{period}.{n}"),
+    }
+}
+
+/// Log files in `dir`, oldest first (by modification time)
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(FILE_PREFIX))
+        .filter_map(|e| {
+            let meta = e.metadata().ok().filter(|m| m.is_file())?;
+            Some((meta.modified().ok()?, e.path()))
+        })
+        .collect();
+    files.sort();
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+struct Current {
+    file: Option<File>,
+    period: String,
+    index: u32,
+    size: u64,
+}
+
+/// `MakeWriter` for the file layer: rotates by time and size and prunes
+/// old files. Each event is one `write` call, so lines never interleave.
+pub struct RollingFile {
+    dir: PathBuf,
+    config: LogConfig,
+    current: Mutex<Current>,
+}
+
+impl RollingFile {
+    pub fn new(dir: &Path, config: &LogConfig) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            config: config.clone(),
+            current: Mutex::new(Current {
+                file: None,
+                period: String::new(),
+                index: 0,
+                size: 0,
+            }),
+        }
+    }
+
+    fn full(&self, size: u64, incoming: usize) -> bool {
+        self.config
+            .max_size_bytes
+            .is_some_and(|max| size > 0 && size + incoming as u64 > max)
+    }
+
+    fn write_record(&self, buf: &[u8]) -> io::Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let period = self.config.rotation.period(chrono::Utc::now());
+        if current.file.is_none() || period != current.period || self.full(current.size, buf.len())
+        {
+            if current.file.is_none() {
+                // 再起動時は同じ期間の最新ファイルに追記する（満杯なら次の番号へ）
+                current.index = self.last_index(&period);
+                current.period = period;
+            } else if period != current.period {
+                current.period = period;
+                current.index = 0;
+            } else {
+                current.index += 1;
+            }
+            fs::create_dir_all(&self.dir)?;
+            loop {
+                let path = self.dir.join(file_name(&current.period, current.index));
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                if self.full(size, buf.len()) {
+                    current.index += 1;
+                    continue;
+                }
+                current.file = Some(
+                    fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)?,
+                );
+                current.size = size;
+                break;
+            }
+            self.prune();
+        }
+        if let Some(ref mut file) = current.file {
+            file.write_all(buf)?;
+        }
+        current.size += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Highest size-rotation index already on disk for `period`
+    fn last_index(&self, period: &str) -> u32 {
+        let base = file_name(period, 0);
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return 0;
+        };
+        entries
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                match name.strip_prefix(&base)? {
+                    "" => Some(0),
+                    rest => rest.strip_prefix('.')?.parse().ok(),
+                }
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn prune(&self) {
+        if self.config.max_files == 0 {
+            return;
+        }
+        let files = log_files(&self.dir);
+        let excess = files.len().saturating_sub(self.config.max_files);
+        for path in &files[..excess] {
+            if let Err(e) = fs::remove_file(path) {
+                eprintln!("Failed to remove old log {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+pub struct RollingWriter<'a>(&'a RollingFile);
+
+impl Write for RollingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_record(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for RollingFile {
+    type Writer = RollingWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RollingWriter(self)
+    }
+}
+
+/// Last `lines` lines of the newest log file: (file name, lines)
+pub fn tail(dir: &Path, lines: usize) -> io::Result<Option<(String, Vec<String>)>> {
+    let Some(path) = log_files(dir).pop() else {
+        return Ok(None);
+    };
+    let mut file = File::open(&path)?;
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(MAX_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = Vec::with_capacity((len - start) as usize);
+    file.read_to_end(&mut buf)?;
+    let text = String::from_utf8_lossy(&buf);
+    let mut all: Vec<&str> = text.lines().collect();
+    // 途中から読んだ場合、先頭行は欠けている
+    if start > 0 && !all.is_empty() {
+        all.remove(0);
+    }
+    let from = all.len().saturating_sub(lines);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Some((
+        name,
+        all[from..].iter().map(|l| l.to_string()).collect(),
+    )))
+}
+
+#[derive(Deserialize)]
+pub struct TailQuery {
+    pub lines: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct TailResponse {
+    /// None = nothing logged yet
+    pub file: Option<String>,
+    pub format: LogFormat,
+    /// JSON format: one object per line (raw string if a line doesn't parse)
+    pub lines: Vec<serde_json::Value>,
+}
+
+/// GET /api/logs/tail (admin only)
+pub async fn tail_handler(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<TailQuery>,
+) -> Response {
+    let lines = q
+        .lines
+        .unwrap_or(DEFAULT_TAIL_LINES)
+        .clamp(1, MAX_TAIL_LINES);
+    let dir = Path::new(&state.config.data_dir).join("logs");
+    let format = state.config.log_files.format;
+    let result = tokio::task::spawn_blocking(move || tail(&dir, lines)).await;
+    let (file, raw) = match result {
+        Ok(Ok(Some((file, raw)))) => (Some(file), raw),
+        Ok(Ok(None)) => (None, Vec::new()),
+        Ok(Err(e)) => {
+            tracing::warn!("Failed to read log file: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        Err(e) => {
+            tracing::error!("log tail task panicked: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let lines = raw
+        .into_iter()
+        .map(|line| match format {
+            LogFormat::Json => {
+                serde_json::from_str(&line).unwrap_or(serde_json::Value::String(line))
+            }
+            LogFormat::Text => serde_json::Value::String(line),
+        })
+        .collect();
+    Json(TailResponse {
+        file,
+        format,
+        lines,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            format: LogFormat::Text,
+            rotation: LogRotation::Never,
+            max_size_bytes: Some(30),
+            max_files: 2,
+        };
+        let logs = RollingFile::new(dir.path(), &config);
+        for i in 0..4 {
+            // 12 bytes: two lines per file
+            logs.write_record(format!("line {i:06}\n").as_bytes())
+                .unwrap();
+            logs.write_record(b"second line\n").unwrap();
+        }
+        // den.log, .1, .2, .3 were written; the two oldest were pruned
+        assert_eq!(names(dir.path()), ["den.log.2", "den.log.3"]);
+
+        // A restart continues in the newest file only if it has room
+        let logs = RollingFile::new(dir.path(), &config);
+        logs.write_record(b"after restart\n").unwrap();
+        assert_eq!(names(dir.path()).len(), 2);
+
+        let (file, lines) = tail(dir.path(), 2).unwrap().unwrap();
+        assert_eq!(file, "den.log.4");
+        assert_eq!(lines, ["after restart"]);
+    }
+
+    #[test]
+    fn period_names() {
+        let at = chrono::DateTime::parse_from_rfc3339("2026-10-17T13:05:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            file_name(&LogRotation::Daily.period(at), 0),
+            "den.log.2026-10-17"
+        );
+        assert_eq!(
+            file_name(&LogRotation::Hourly.period(at), 2),
+            "den.log.2026-10-17-13.2"
+        );
+        assert_eq!(file_name(&LogRotation::Never.period(at), 0), "den.log");
+    }
+
+    #[test]
+    fn tail_of_missing_dir_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(tail(&dir.path().join("logs"), 10).unwrap().is_none());
+    }
+}
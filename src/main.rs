@@ -1,16 +1,32 @@
 use den::config::Config;
 use den::pty::registry::SessionRegistry;
+use den::status::SshState;
 use den::store::Store;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::Layer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
-#[tokio::main]
-async fn main() {
-    // Load .env: CWD first, then platform-specific config directory as fallback.
-    // Later values do NOT override earlier ones, so CWD takes precedence.
+fn main() {
+    // スレッド生成前に systemd の環境変数（LISTEN_FDS / NOTIFY_SOCKET など）を読み取って消す
+    den::systemd::init();
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("hash-password") => hash_password_command(),
+        Some("service") => den::service::command(args.next().as_deref(), serve),
+        Some("self-update") => {
+            load_dotenv();
+            den::update::self_update_command(args.next().as_deref());
+        }
+        _ => serve(),
+    }
+}
+
+/// Load .env: CWD first, then platform-specific config directory as fallback.
+/// Later values do NOT override earlier ones, so CWD takes precedence.
+fn load_dotenv() {
     let _ = dotenvy::dotenv();
     if cfg!(windows) {
         // Windows: exe directory (e.g. AppData\Local\den\.env)
@@ -34,10 +50,16 @@ async fn main() {
             let _ = dotenvy::from_path(dir.join("den").join(".env"));
         }
     }
+}
+
+/// Run den until shutdown (console or Windows service)
+#[tokio::main]
+async fn serve() {
+    load_dotenv();
 
     let config = Config::from_env();
     let port = config.port;
-    let ssh_port = config.ssh_port;
+    let acme = config.acme.clone();
     let tls_runtime = den::tls::setup(&config).unwrap_or_else(|e| {
         eprintln!("ERROR: TLS setup failed: {e}");
         std::process::exit(1);
@@ -46,21 +68,44 @@ async fn main() {
     // tracing 初期化: console (stderr) + file (data_dir/logs/)
     // stdout は ConPTY (OpenConsole.exe) のカーソル制御シーケンスに干渉されるため
     // stderr に明示的に出力する。
-    let filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level));
+    // フィルターは設定の log_level で差し替えられるよう reload 可能にする
+    let (filter, filter_handle) =
+        tracing_subscriber::reload::Layer::new(den::live_settings::log_filter(None, &config));
     let console_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
 
+    // ファイル出力: テキストまたは JSON Lines。時間・サイズでローテーション（den::logs）
     let log_dir = std::path::Path::new(&config.data_dir).join("logs");
     let _ = std::fs::create_dir_all(&log_dir);
-    let file_appender = tracing_appender::rolling::daily(&log_dir, "den.log");
-    let file_layer = tracing_subscriber::fmt::layer()
-        .with_ansi(false)
-        .with_writer(file_appender);
+    let log_file = den::logs::RollingFile::new(&log_dir, &config.log_files);
+    let file_layer = match config.log_files.format {
+        den::logs::LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(log_file)
+            .boxed(),
+        den::logs::LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(log_file)
+            .boxed(),
+    };
+
+    // OTLP エクスポート（DEN_OTLP_ENDPOINT 指定時のみ）
+    let telemetry = config.otlp.as_ref().map(|otlp| {
+        den::telemetry::Telemetry::init(otlp).unwrap_or_else(|e| {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        })
+    });
+    let otel_layer = telemetry
+        .as_ref()
+        .map(|t| tracing_opentelemetry::layer().with_tracer(t.tracer()));
 
     tracing_subscriber::registry()
         .with(filter)
         .with(console_layer)
         .with(file_layer)
+        .with(otel_layer)
+        // 直近の警告・エラーを GET /api/dashboard 用に保持
+        .with(den::dashboard::ErrorLayer)
         .init();
 
     let bind_address = config.bind_address.clone();
@@ -71,23 +116,22 @@ async fn main() {
         port,
         config.env
     );
-    if let Some(sp) = ssh_port {
-        tracing::info!("SSH port: {}", sp);
+    if config.password_hash.is_some() {
+        tracing::info!("Password: (argon2 hash)");
     } else {
-        tracing::info!("SSH server: disabled (set DEN_SSH_PORT to enable)");
+        tracing::info!("Password: (custom)");
     }
-    tracing::info!("Shell: {}", config.shell);
-    tracing::info!("Password: (custom)");
 
     // Settings から初期設定を読み込み、SessionRegistry を生成
     let store = Store::from_data_dir(&config.data_dir).expect("Failed to initialize data store");
     let settings = store.load_settings();
+    let shell = den::live_settings::shell(&settings, &config);
+    tracing::info!("Shell: {}", shell);
     // multiplexer 用 layout/config を data_dir に書き出し（失敗時は空パス → 該当フラグ省略）。
-    // config.shell を mux 設定にも展開し、plain セッションとシェル挙動を揃える。
-    let mux =
-        den::assets::ensure_mux_layouts(std::path::Path::new(&config.data_dir), &config.shell);
+    // shell を mux 設定にも展開し、plain セッションとシェル挙動を揃える。
+    let mux = den::assets::ensure_mux_layouts(std::path::Path::new(&config.data_dir), &shell);
     let registry = SessionRegistry::new(
-        config.shell.clone(),
+        shell,
         settings.sleep_prevention_mode,
         settings.sleep_prevention_timeout,
         Some(store.clone()),
@@ -98,38 +142,73 @@ async fn main() {
     let clipboard_handle = den::clipboard_monitor::start(store.clone());
 
     // HTTP サーバー（メイン）+ graceful shutdown
-    let shutdown_registry = Arc::clone(&registry);
     let (app, app_state) = den::create_app(config, registry, store, tls_runtime.as_ref());
 
-    // SSH サーバー（opt-in: DEN_SSH_PORT 設定時のみ起動）
+    // 起動時の自己診断（data_dir の書き込み・PTY 作成）。問題は GET /api/status に残る
+    den::status::self_check(&app_state).await;
+
+    // ローカル専用リスナー（DEN_LOCAL_SOCKET 指定時のみ）。同じマシンからの接続なので
+    // DEN_ADMIN_PORT で分けた管理用エンドポイントも提供する
+    let local_handle = app_state.config.local_socket.clone().map(|path| {
+        let local_app = den::admin_app(&app_state);
+        let state = Arc::clone(&app_state);
+        tokio::spawn(async move {
+            tracing::info!("Listening on local socket {path}");
+            let shutdown = state.shutdown.clone();
+            if let Err(e) = den::local_socket::serve(&path, local_app, shutdown).await {
+                state.diagnostics.warn(format!("Local socket {path}: {e}"));
+            }
+        })
+    });
+
+    // SSH サーバー（opt-in: DEN_SSH_PORT または設定の ssh_port）。ポート変更で再起動する。
     // JoinHandle を保持して graceful shutdown 時に abort する
-    let ssh_handle = if let Some(ssh_port) = ssh_port {
-        let ssh_registry = Arc::clone(&app_state.registry);
-        let ssh_password = app_state.config.password.clone();
-        let ssh_data_dir = app_state.config.data_dir.clone();
-        let ssh_bind = app_state.config.bind_address.clone();
-        let ssh_store = app_state.store.clone();
-        Some(tokio::spawn(async move {
-            if let Err(e) = den::ssh::server::run(
-                ssh_registry,
-                ssh_password,
-                ssh_port,
-                ssh_data_dir,
-                ssh_bind,
-                ssh_store,
-            )
+    let ssh_handle = tokio::spawn(supervise_ssh(Arc::clone(&app_state)));
+
+    // 設定変更の反映（シェル・スリープ抑止・ログレベル）
+    let registry_settings_handle =
+        den::live_settings::spawn_registry_subscriber(Arc::clone(&app_state));
+    let log_level_handle = tokio::spawn(follow_log_level(Arc::clone(&app_state), filter_handle));
+
+    // 同期ジョブのスケジューラ（interval 指定ジョブを定期実行）
+    let sync_handle = den::sftp::sync::spawn_scheduler(Arc::clone(&app_state));
+
+    // systemd のソケットアクティベーション時は渡されたソケットで待ち受ける
+    let listener = match den::systemd::take_listener(false) {
+        Some(listener) => {
+            tracing::info!("Using the listening socket from systemd");
+            listener.expect("Failed to use the socket from systemd")
+        }
+        None => den::bind_with_retry(&bind_address, port)
             .await
-            {
-                tracing::error!("SSH server error: {e}");
-            }
-        }))
-    } else {
-        None
+            .expect("Failed to bind port"),
+    };
+
+    // 管理用リスナー（DEN_ADMIN_PORT 指定時のみ）。メインと同じ TLS 設定を使う
+    let admin_handle = app_state.config.admin_listener.clone().map(|admin| {
+        let tls = tls_runtime
+            .as_ref()
+            .map(|tls| Arc::clone(&tls.server_config));
+        tokio::spawn(serve_admin(Arc::clone(&app_state), admin, tls))
+    });
+
+    // ACME: HTTP-01 の応答と証明書の取得・更新（DEN_ACME_DOMAINS 指定時のみ）
+    let acme_handle = match (acme, tls_runtime.as_ref()) {
+        (Some(acme), Some(tls)) => tls.reloadable_cert.clone().map(|cert| {
+            den::acme::spawn(
+                acme,
+                std::path::PathBuf::from(&app_state.config.data_dir),
+                bind_address.clone(),
+                port,
+                cert,
+            )
+        }),
+        _ => None,
     };
 
-    let listener = den::bind_with_retry(&bind_address, port)
-        .await
-        .expect("Failed to bind port");
+    // systemd（Type=notify）に起動完了を伝え、WatchdogSec= があれば定期的に応答する
+    den::systemd::notify("READY=1");
+    let watchdog_handle = den::systemd::spawn_watchdog();
 
     if let Some(tls_runtime) = tls_runtime {
         tracing::info!("TLS: enabled");
@@ -139,59 +218,273 @@ async fn main() {
             tls_runtime.info.subject_alt_names.join(", ")
         );
         tracing::info!("Listening on https://{}:{}", bind_address, port);
-        den::tls::serve(
+        let server = den::tls::serve(
             listener,
             app,
             tls_runtime.server_config,
-            shutdown_signal(shutdown_registry, clipboard_handle.clone()),
-        )
-        .await
-        .unwrap();
+            shutdown_signal(Arc::clone(&app_state), clipboard_handle),
+        );
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    tracing::error!("HTTPS server error: {e}");
+                }
+            }
+            () = app_state.shutdown.drain_deadline() => drain_timed_out(),
+        }
     } else {
         tracing::info!("Listening on http://{}:{}", bind_address, port);
-        axum::serve(
+        let server = axum::serve(
             listener,
             app.into_make_service_with_connect_info::<SocketAddr>(),
         )
-        .with_graceful_shutdown(shutdown_signal(shutdown_registry, clipboard_handle))
-        .await
-        .unwrap();
+        .with_graceful_shutdown(shutdown_signal(Arc::clone(&app_state), clipboard_handle));
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    tracing::error!("HTTP server error: {e}");
+                }
+            }
+            () = app_state.shutdown.drain_deadline() => drain_timed_out(),
+        }
+    }
+    tracing::info!("HTTP server stopped.");
+    for handle in [local_handle, admin_handle].into_iter().flatten() {
+        let _ = tokio::time::timeout(den::shutdown::DRAIN_TIMEOUT, handle).await;
     }
 
-    // Abort SSH server task so its TCP listener is released before restart
-    if let Some(handle) = ssh_handle {
+    sync_handle.abort();
+    registry_settings_handle.abort();
+    log_level_handle.abort();
+    if let Some(handle) = watchdog_handle {
+        handle.abort();
+    }
+    if let Some(handle) = acme_handle {
         handle.abort();
-        let _ = handle.await;
-        tracing::info!("SSH server stopped.");
+    }
+
+    // Abort SSH server task so its TCP listener is released before restart
+    ssh_handle.abort();
+    let _ = ssh_handle.await;
+    tracing::info!("SSH server stopped.");
+
+    // バッファ済みのスパン・メトリクスを送り切る
+    if let Some(telemetry) = telemetry {
+        let _ = tokio::task::spawn_blocking(move || telemetry.shutdown()).await;
     }
 
     // After graceful shutdown, check if we need to restart (update applied)
     if den::update::is_restart_requested() {
+        if den::service::is_running() {
+            // SCM の failure action が新しいバイナリで起動し直す
+            den::service::exit_for_restart();
+            return;
+        }
+        if den::systemd::is_supervised() {
+            // Restart=on-failure で systemd が新しいバイナリを起動する（待ち受けソケットは systemd が保持）
+            tracing::info!("Exiting so systemd restarts Den");
+            std::process::exit(den::systemd::RESTART_EXIT_CODE);
+        }
         // Brief delay to allow OS to release sockets (Windows TIME_WAIT)
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
         den::update::spawn_and_exit();
     }
 }
 
-/// Wait for shutdown signal (Ctrl+C or restart request) and persist sessions.
+/// Serve the admin router on `DEN_ADMIN_PORT` until shutdown begins. A bind
+/// failure leaves den running without it (reported in `/api/status`).
+async fn serve_admin(
+    state: Arc<den::AppState>,
+    admin: den::config::AdminListener,
+    tls: Option<Arc<rustls::ServerConfig>>,
+) {
+    let addr = format!("{}:{}", admin.bind_address, admin.port);
+    let listener = match den::systemd::take_listener(true) {
+        Some(listener) => listener,
+        None => den::bind_with_retry(&admin.bind_address, admin.port).await,
+    };
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            state
+                .diagnostics
+                .warn(format!("Admin listener {addr} is unavailable: {e}"));
+            return;
+        }
+    };
+    let app = den::admin_app(&state);
+    let shutdown = state.shutdown.clone();
+    let stopped = async move {
+        shutdown.started().await;
+    };
+    let result = match tls {
+        Some(server_config) => {
+            tracing::info!("Admin endpoints on https://{addr}");
+            den::tls::serve(listener, app, server_config, stopped).await
+        }
+        None => {
+            tracing::info!("Admin endpoints on http://{addr}");
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(stopped)
+            .await
+            .map_err(|e| e.to_string())
+        }
+    };
+    if let Err(e) = result {
+        tracing::error!("Admin listener error: {e}");
+    }
+}
+
+/// Run the SSH server on the configured port, restarting it whenever the
+/// owner changes `ssh_port` (0 = off). Sessions already connected over SSH
+/// stay open; only the listener moves.
+async fn supervise_ssh(state: Arc<den::AppState>) {
+    let mut settings = state.live_settings.subscribe();
+    loop {
+        let port = den::live_settings::ssh_port(&settings.borrow_and_update(), &state.config);
+        let port_changed =
+            |s: &Arc<den::store::Settings>| den::live_settings::ssh_port(s, &state.config) != port;
+        let stopped = match port {
+            Some(port) => {
+                tracing::info!("SSH port: {}", port);
+                state.diagnostics.set_ssh(SshState::Running { port });
+                tokio::select! {
+                    result = run_ssh(&state, port) => {
+                        let error = match result {
+                            Ok(()) => "stopped".to_string(),
+                            Err(e) => e.to_string(),
+                        };
+                        tracing::error!("SSH server error: {error}");
+                        state.diagnostics.set_ssh(SshState::Failed { port, error });
+                        true
+                    }
+                    changed = settings.wait_for(port_changed) => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        false
+                    }
+                }
+            }
+            None => {
+                state.diagnostics.set_ssh(SshState::Disabled);
+                tracing::info!(
+                    "SSH server: disabled (set DEN_SSH_PORT or the ssh_port setting to enable)"
+                );
+                true
+            }
+        };
+        // 起動失敗・停止時は次のポート変更まで待つ
+        if stopped && settings.wait_for(port_changed).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn run_ssh(state: &den::AppState, port: u16) -> anyhow::Result<()> {
+    den::ssh::server::run(
+        Arc::clone(&state.registry),
+        state.config.owner_credential(),
+        port,
+        state.config.data_dir.clone(),
+        state.config.bind_address.clone(),
+        state.store.clone(),
+        state.ip_filter.clone(),
+        Arc::clone(&state.rate_limiter),
+        state.notifier.clone(),
+    )
+    .await
+}
+
+/// Reload the log filter when the owner changes `log_level`
+async fn follow_log_level(
+    state: Arc<den::AppState>,
+    filter: tracing_subscriber::reload::Handle<EnvFilter, tracing_subscriber::Registry>,
+) {
+    let mut settings = state.live_settings.subscribe();
+    // None = 起動時のフィルター（環境変数）
+    let mut applied: Option<String> = None;
+    loop {
+        let level = settings.borrow_and_update().log_level.clone();
+        if level != applied {
+            let new_filter = den::live_settings::log_filter(level.as_deref(), &state.config);
+            match filter.reload(new_filter) {
+                Ok(()) => tracing::info!(
+                    "Log level: {}",
+                    level.as_deref().unwrap_or("from environment")
+                ),
+                Err(e) => tracing::warn!("Failed to change log level: {e}"),
+            }
+            applied = level;
+        }
+        if settings.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Wait for shutdown signal (Ctrl+C, SIGTERM, service stop or restart request), tell
+/// WebSocket clients and persist sessions. The server then stops accepting
+/// and drains open connections.
 async fn shutdown_signal(
-    registry: Arc<SessionRegistry>,
+    state: Arc<den::AppState>,
     clipboard_handle: den::clipboard_monitor::ClipboardMonitorHandle,
 ) {
-    // Wait for either Ctrl+C or a restart request from the update system
-    tokio::select! {
+    let restart = tokio::select! {
         _ = tokio::signal::ctrl_c() => {
             tracing::info!("Shutdown signal received, persisting sessions...");
+            false
+        }
+        _ = terminate_signal() => {
+            tracing::info!("SIGTERM received, persisting sessions...");
+            false
+        }
+        _ = den::service::stop_requested() => {
+            tracing::info!("Service stop requested, persisting sessions...");
+            false
         }
         _ = wait_for_restart() => {
             tracing::info!("Restart requested, shutting down gracefully...");
+            true
         }
-    }
+    };
+    // WebSocket ハンドラーが server_shutting_down を送ってソケットを閉じる
+    state.shutdown.begin(restart);
+    den::systemd::notify("STOPPING=1");
     clipboard_handle.stop();
-    registry.persist_sessions().await;
+    state.registry.persist_sessions().await;
     tracing::info!("Sessions persisted. Shutting down.");
 }
 
+#[cfg(unix)]
+async fn terminate_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            sigterm.recv().await;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to listen for SIGTERM: {e}");
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate_signal() {
+    std::future::pending::<()>().await;
+}
+
+fn drain_timed_out() {
+    tracing::warn!(
+        "Connections still open after {}s; shutting down anyway",
+        den::shutdown::DRAIN_TIMEOUT.as_secs()
+    );
+}
+
 /// Poll until a restart is requested (from update system).
 async fn wait_for_restart() {
     loop {
@@ -201,3 +494,23 @@ async fn wait_for_restart() {
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 }
+
+/// `den hash-password`: 標準入力から読んだパスワードを argon2id でハッシュ化し、
+/// DEN_PASSWORD_HASH に設定する値を stdout に出力する。
+fn hash_password_command() {
+    use std::io::{BufRead, IsTerminal};
+    if std::io::stdin().is_terminal() {
+        eprint!("Password: ");
+    }
+    let mut password = String::new();
+    if let Err(e) = std::io::stdin().lock().read_line(&mut password) {
+        eprintln!("ERROR: failed to read password: {e}");
+        std::process::exit(1);
+    }
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        eprintln!("ERROR: password must not be empty");
+        std::process::exit(1);
+    }
+    println!("{}", den::auth::hash_password(password));
+}
@@ -0,0 +1,273 @@
+//! Prometheus metrics at `GET /metrics` (text exposition format).
+//!
+//! Counters live in process-wide atomics so the PTY read loop and the SSH
+//! server can bump them without access to `AppState`; session and client
+//! gauges are read from the registry at scrape time. Access needs an admin
+//! login (or an API token with the `metrics:read` scope) or the static
+//! bearer token from `DEN_METRICS_TOKEN`.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method, Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::AppState;
+
+/// Upper bounds of the request latency histogram (seconds)
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+pub static PTY_OUTPUT_BYTES: AtomicU64 = AtomicU64::new(0);
+pub static PTY_INPUT_BYTES: AtomicU64 = AtomicU64::new(0);
+pub static SSH_AUTH_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// Open WebSocket connections per endpoint
+pub static TERMINAL_SOCKETS: AtomicU64 = AtomicU64::new(0);
+pub static EVENT_SOCKETS: AtomicU64 = AtomicU64::new(0);
+pub static REMOTE_SOCKETS: AtomicU64 = AtomicU64::new(0);
+
+/// HTTP responses by status class (1xx..5xx)
+static HTTP_RESPONSES: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+/// Requests per bucket (not cumulative), plus one past the last bound
+static LATENCY_COUNTS: [AtomicU64; LATENCY_BUCKETS.len() + 1] =
+    [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len() + 1];
+static LATENCY_SUM_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts an open WebSocket until dropped
+pub struct SocketGuard(&'static AtomicU64);
+
+impl SocketGuard {
+    pub fn open(gauge: &'static AtomicU64) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        Self(gauge)
+    }
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Start the uptime clock (called from `create_app`)
+pub fn init() {
+    LazyLock::force(&STARTED);
+}
+
+pub fn uptime() -> std::time::Duration {
+    STARTED.elapsed()
+}
+
+/// Index of the smallest bucket holding `secs` (`LATENCY_BUCKETS.len()` = +Inf only)
+fn bucket_index(secs: f64) -> usize {
+    LATENCY_BUCKETS
+        .iter()
+        .position(|bound| secs <= *bound)
+        .unwrap_or(LATENCY_BUCKETS.len())
+}
+
+fn observe(status: StatusCode, elapsed: std::time::Duration) {
+    let class = (status.as_u16() / 100).clamp(1, 5) as usize - 1;
+    HTTP_RESPONSES[class].fetch_add(1, Ordering::Relaxed);
+    LATENCY_COUNTS[bucket_index(elapsed.as_secs_f64())].fetch_add(1, Ordering::Relaxed);
+    LATENCY_SUM_MICROS.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+/// Records status class and time to response headers for every request
+/// (WebSocket upgrades count until the 101, not for the socket's lifetime).
+pub async fn latency_middleware(req: Request<axum::body::Body>, next: Next) -> Response {
+    let start = Instant::now();
+    let resp = next.run(req).await;
+    observe(resp.status(), start.elapsed());
+    resp
+}
+
+/// Responses so far by status class (1xx..5xx), for the OTLP exporter
+pub(crate) fn http_responses() -> [u64; 5] {
+    std::array::from_fn(|i| HTTP_RESPONSES[i].load(Ordering::Relaxed))
+}
+
+fn authorized(state: &AppState, headers: &HeaderMap) -> bool {
+    if let Some(ref expected) = state.config.metrics_token {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if bearer.is_some_and(|t| crate::auth::constant_time_eq(t, expected)) {
+            return true;
+        }
+    }
+    crate::auth::request_token(headers)
+        .and_then(|t| crate::auth::authenticate(state, &t))
+        .is_some_and(|user| user.is_admin() && user.allows(&Method::GET, "/metrics"))
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{name}{labels} {value}");
+    }
+}
+
+fn load(counter: &AtomicU64) -> f64 {
+    counter.load(Ordering::Relaxed) as f64
+}
+
+pub async fn render(state: &AppState) -> String {
+    let stats = state.registry.stats().await;
+    let mut out = String::new();
+    let version = format!("{{version=\"{}\"}}", env!("CARGO_PKG_VERSION"));
+    metric(
+        &mut out,
+        "den_build_info",
+        "gauge",
+        "Den version",
+        &[(&version, 1.0)],
+    );
+    metric(
+        &mut out,
+        "den_uptime_seconds",
+        "gauge",
+        "Seconds since Den started",
+        &[("", uptime().as_secs_f64())],
+    );
+    metric(
+        &mut out,
+        "den_pty_sessions",
+        "gauge",
+        "Terminal sessions (including exited ones not yet closed)",
+        &[("", stats.sessions as f64)],
+    );
+    metric(
+        &mut out,
+        "den_pty_sessions_alive",
+        "gauge",
+        "Terminal sessions whose process is running",
+        &[("", stats.alive as f64)],
+    );
+    metric(
+        &mut out,
+        "den_pty_clients",
+        "gauge",
+        "Clients attached to terminal sessions",
+        &[
+            ("{kind=\"websocket\"}", stats.websocket_clients as f64),
+            ("{kind=\"ssh\"}", stats.ssh_clients as f64),
+            ("{kind=\"observer\"}", stats.observers as f64),
+        ],
+    );
+    metric(
+        &mut out,
+        "den_websocket_connections",
+        "gauge",
+        "Open WebSocket connections",
+        &[
+            ("{endpoint=\"terminal\"}", load(&TERMINAL_SOCKETS)),
+            ("{endpoint=\"events\"}", load(&EVENT_SOCKETS)),
+            ("{endpoint=\"remote\"}", load(&REMOTE_SOCKETS)),
+        ],
+    );
+    metric(
+        &mut out,
+        "den_pty_output_bytes_total",
+        "counter",
+        "Bytes read from terminal processes",
+        &[("", load(&PTY_OUTPUT_BYTES))],
+    );
+    metric(
+        &mut out,
+        "den_pty_input_bytes_total",
+        "counter",
+        "Bytes written to terminal processes",
+        &[("", load(&PTY_INPUT_BYTES))],
+    );
+    metric(
+        &mut out,
+        "den_ssh_auth_failures_total",
+        "counter",
+        "Rejected SSH password and public key logins",
+        &[("", load(&SSH_AUTH_FAILURES))],
+    );
+    let classes: Vec<(String, f64)> = HTTP_RESPONSES
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (format!("{{code=\"{}xx\"}}", i + 1), load(c)))
+        .collect();
+    let classes: Vec<(&str, f64)> = classes.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+    metric(
+        &mut out,
+        "den_http_requests_total",
+        "counter",
+        "HTTP responses by status class",
+        &classes,
+    );
+
+    let name = "den_http_request_duration_seconds";
+    let _ = writeln!(out, "# HELP {name} Time to response headers");
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    let mut count = 0.0;
+    for (i, bound) in LATENCY_BUCKETS.iter().enumerate() {
+        count += load(&LATENCY_COUNTS[i]);
+        let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}");
+    }
+    count += load(&LATENCY_COUNTS[LATENCY_BUCKETS.len()]);
+    let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+    let _ = writeln!(
+        out,
+        "{name}_sum {}",
+        load(&LATENCY_SUM_MICROS) / 1_000_000.0
+    );
+    let _ = writeln!(out, "{name}_count {count}");
+    out
+}
+
+/// GET /metrics
+pub async fn handler(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        render(&state).await,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_bucket_index() {
+        assert_eq!(bucket_index(0.001), 0);
+        assert_eq!(bucket_index(0.005), 0);
+        assert_eq!(bucket_index(0.03), 3);
+        assert_eq!(bucket_index(10.0), 10);
+        assert_eq!(bucket_index(60.0), LATENCY_BUCKETS.len());
+    }
+
+    #[test]
+    fn socket_guard_tracks_open_connections() {
+        static GAUGE: AtomicU64 = AtomicU64::new(0);
+        let a = SocketGuard::open(&GAUGE);
+        let b = SocketGuard::open(&GAUGE);
+        assert_eq!(GAUGE.load(Ordering::Relaxed), 2);
+        drop(a);
+        drop(b);
+        assert_eq!(GAUGE.load(Ordering::Relaxed), 0);
+    }
+}
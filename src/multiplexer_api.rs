@@ -1,10 +1,12 @@
+use crate::auth::AuthUser;
 use crate::pty::backend::{
     SessionBackend, delete_mux_session, is_valid_mux_name, kill_mux_session, list_mux_sessions,
-    list_zellij_detailed, probe_available,
+    list_zellij_detailed, mux_session_key, mux_session_name, probe_available,
 };
-use axum::{Json, extract::State};
+use crate::pty::registry::{scoped_name, unscoped_name};
+use axum::{Extension, Json, extract::State};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, OnceLock};
 
 #[derive(Serialize)]
@@ -63,6 +65,28 @@ fn aliases_for(all: &HashMap<String, String>, backend: &str) -> HashMap<String,
         .collect()
 }
 
+/// Mux sessions visible in `namespace`, as user-facing names. den creates
+/// them as `alice__main` for user `alice` ([`mux_session_name`]); everything
+/// else belongs to the owner (root namespace).
+fn visible_sessions(
+    namespace: Option<&str>,
+    mux_names: &[String],
+    users: &HashSet<String>,
+) -> Vec<String> {
+    mux_names
+        .iter()
+        .filter_map(|mux| {
+            let key = mux_session_key(mux, |ns| users.contains(ns));
+            unscoped_name(namespace, &key).map(str::to_string)
+        })
+        .collect()
+}
+
+/// mux session name for a user-facing `name` in the caller's namespace
+fn caller_mux_name(user: &AuthUser, name: &str) -> String {
+    mux_session_name(&scoped_name(user.namespace(), name))
+}
+
 /// Availability is treated as immutable after startup and is cached (lazy, (zellij, tmux)).
 fn availability() -> &'static (bool, bool) {
     static AVAIL: OnceLock<(bool, bool)> = OnceLock::new();
@@ -75,7 +99,10 @@ fn availability() -> &'static (bool, bool) {
 }
 
 /// GET /api/multiplexer/status
-pub async fn status(State(state): State<Arc<crate::AppState>>) -> Json<MultiplexerStatus> {
+pub async fn status(
+    State(state): State<Arc<crate::AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> Json<MultiplexerStatus> {
     let (zellij_ok, tmux_ok) = *availability();
     // ls is blocking, so wrap in spawn_blocking
     // zellij: list with exited status so the UI can offer Kill (running) vs
@@ -101,21 +128,35 @@ pub async fn status(State(state): State<Arc<crate::AppState>>) -> Json<Multiplex
         Vec::new()
     };
     let store = state.store.clone();
-    let all_aliases = tokio::task::spawn_blocking(move || store.load_mux_aliases())
-        .await
-        .unwrap_or_default();
+    let (all_aliases, users) = tokio::task::spawn_blocking(move || {
+        let users: HashSet<String> = store.load_users().into_iter().map(|u| u.username).collect();
+        (store.load_mux_aliases(), users)
+    })
+    .await
+    .unwrap_or_default();
+    // 呼び出し元の名前空間のセッションだけを、ユーザー向けの名前で返す
+    let ns = user.namespace();
+    let scoped_aliases = |backend: &str| -> HashMap<String, String> {
+        aliases_for(&all_aliases, backend)
+            .into_iter()
+            .filter_map(|(mux, alias)| {
+                let key = mux_session_key(&mux, |n| users.contains(n));
+                unscoped_name(ns, &key).map(|name| (name.to_string(), alias))
+            })
+            .collect()
+    };
     Json(MultiplexerStatus {
         zellij: BackendStatus {
             available: zellij_ok,
-            sessions: zellij_sessions,
-            exited: zellij_exited,
-            aliases: aliases_for(&all_aliases, "zellij"),
+            sessions: visible_sessions(ns, &zellij_sessions, &users),
+            exited: visible_sessions(ns, &zellij_exited, &users),
+            aliases: scoped_aliases("zellij"),
         },
         tmux: BackendStatus {
             available: tmux_ok,
-            sessions: tmux_sessions,
+            sessions: visible_sessions(ns, &tmux_sessions, &users),
             exited: Vec::new(),
-            aliases: aliases_for(&all_aliases, "tmux"),
+            aliases: scoped_aliases("tmux"),
         },
     })
 }
@@ -123,21 +164,25 @@ pub async fn status(State(state): State<Arc<crate::AppState>>) -> Json<Multiplex
 /// POST /api/multiplexer/kill
 pub async fn kill(
     State(_state): State<Arc<crate::AppState>>,
+    Extension(user): Extension<AuthUser>,
     Json(op): Json<SessionOp>,
 ) -> Json<OpResult> {
-    Json(run_session_op(&op.backend, &op.name, kill_mux_session).await)
+    Json(run_session_op(&user, &op.backend, &op.name, kill_mux_session).await)
 }
 
 /// POST /api/multiplexer/delete
 pub async fn delete(
     State(_state): State<Arc<crate::AppState>>,
+    Extension(user): Extension<AuthUser>,
     Json(op): Json<SessionOp>,
 ) -> Json<OpResult> {
-    Json(run_session_op(&op.backend, &op.name, delete_mux_session).await)
+    Json(run_session_op(&user, &op.backend, &op.name, delete_mux_session).await)
 }
 
 /// Shared validation and spawn_blocking execution for kill/delete operations.
+/// `name` is user-facing; the op targets that session in the caller's namespace.
 async fn run_session_op(
+    user: &AuthUser,
     backend: &str,
     name: &str,
     op: fn(SessionBackend, &str) -> Result<(), String>,
@@ -154,7 +199,7 @@ async fn run_session_op(
             message: Some("invalid session name".into()),
         };
     }
-    let name = name.to_string();
+    let name = caller_mux_name(user, name);
     match tokio::task::spawn_blocking(move || op(be, &name)).await {
         Ok(Ok(())) => OpResult {
             ok: true,
@@ -174,6 +219,7 @@ async fn run_session_op(
 /// POST /api/multiplexer/rename — updates Den-local alias only (does not call the mux CLI).
 pub async fn rename(
     State(state): State<Arc<crate::AppState>>,
+    Extension(user): Extension<AuthUser>,
     Json(op): Json<RenameOp>,
 ) -> Json<OpResult> {
     if parse_backend(&op.backend).is_none() {
@@ -194,7 +240,7 @@ pub async fn rename(
             message: Some("alias too long".into()),
         });
     }
-    let key = format!("{}:{}", op.backend, op.name);
+    let key = format!("{}:{}", op.backend, caller_mux_name(&user, &op.name));
     let alias = op.alias.clone();
     let store = state.store.clone();
     match tokio::task::spawn_blocking(move || store.set_mux_alias(&key, &alias)).await {
@@ -293,6 +339,23 @@ mod tests {
         assert_eq!(tmux.get("main").map(String::as_str), Some("Main"));
     }
 
+    #[test]
+    fn visible_sessions_are_scoped_to_the_namespace() {
+        let users = HashSet::from(["alice".to_string(), "bob".to_string()]);
+        let mux: Vec<String> = ["main", "alice__main", "alice__dev", "bob__main", "x__y"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(visible_sessions(None, &mux, &users), vec!["main", "x__y"]);
+        assert_eq!(
+            visible_sessions(Some("alice"), &mux, &users),
+            vec!["main", "dev"]
+        );
+        assert_eq!(
+            visible_sessions(Some("carol"), &mux, &users),
+            Vec::<String>::new()
+        );
+    }
+
     #[test]
     fn alias_length_cap_threshold() {
         // Exactly 256 bytes must pass; 257 must be rejected.
@@ -0,0 +1,429 @@
+//! Security event notifications.
+//!
+//! Logins from an address the account has not used before, lockouts after
+//! repeated failed logins, and keys that newly appear in the SSH
+//! `authorized_keys` file are pushed to the channels configured with
+//! `DEN_NOTIFY_*`: a JSON webhook, an ntfy topic, and/or email handed to the
+//! local `sendmail`. Delivery runs in the background; failures are only
+//! logged so a broken channel never blocks a login.
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::store::Store;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_SENDMAIL: &str = "sendmail";
+
+/// Which events are sent (`DEN_NOTIFY_EVENTS`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    NewIpLogin,
+    LoginLockout,
+    SshKeyAdded,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 3] = [
+        EventKind::NewIpLogin,
+        EventKind::LoginLockout,
+        EventKind::SshKeyAdded,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::NewIpLogin => "new_ip_login",
+            EventKind::LoginLockout => "login_lockout",
+            EventKind::SshKeyAdded => "ssh_key_added",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifyConfig {
+    /// Receives a JSON POST per event
+    pub webhook_url: Option<String>,
+    /// Full topic URL, e.g. `https://ntfy.sh/my-den`
+    pub ntfy_url: Option<String>,
+    /// Access token for protected ntfy topics
+    pub ntfy_token: Option<String>,
+    /// Recipient address; mail is piped to `sendmail -t`
+    pub email_to: Option<String>,
+    /// None = `sendmail` from PATH
+    pub sendmail: Option<String>,
+    pub events: Vec<EventKind>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            ntfy_url: None,
+            ntfy_token: None,
+            email_to: None,
+            sendmail: None,
+            events: EventKind::ALL.to_vec(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    pub fn has_channels(&self) -> bool {
+        self.webhook_url.is_some() || self.ntfy_url.is_some() || self.email_to.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SecurityEvent {
+    /// Successful login from an address this account has not used before
+    NewIpLogin {
+        /// None = owner
+        user: Option<String>,
+        ip: IpAddr,
+        /// `password` / `sso` / `ssh-password` / `ssh-key`
+        via: &'static str,
+    },
+    /// A client hit the failed-login limit and was locked out
+    LoginLockout { ip: Option<IpAddr>, ban_secs: u64 },
+    /// `authorized_keys` contains a key that was not there on the last start
+    SshKeyAdded { fingerprint: String },
+}
+
+impl SecurityEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            SecurityEvent::NewIpLogin { .. } => EventKind::NewIpLogin,
+            SecurityEvent::LoginLockout { .. } => EventKind::LoginLockout,
+            SecurityEvent::SshKeyAdded { .. } => EventKind::SshKeyAdded,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            SecurityEvent::NewIpLogin { ip, .. } => format!("New login from {ip}"),
+            SecurityEvent::LoginLockout { ip, .. } => format!(
+                "Login locked out: {}",
+                ip.map_or_else(|| "unknown client".to_string(), |ip| ip.to_string())
+            ),
+            SecurityEvent::SshKeyAdded { .. } => "SSH key added".to_string(),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            SecurityEvent::NewIpLogin { user, ip, via } => format!(
+                "{} signed in from {ip} ({via}), an address not seen before for this account.",
+                user.as_deref().unwrap_or("The owner")
+            ),
+            SecurityEvent::LoginLockout { ip, ban_secs } => format!(
+                "Too many failed logins from {}; locked out for {ban_secs}s.",
+                ip.map_or_else(|| "an unknown client".to_string(), |ip| ip.to_string())
+            ),
+            SecurityEvent::SshKeyAdded { fingerprint } => format!(
+                "A new key was found in the SSH authorized_keys file and can now log in: {fingerprint}"
+            ),
+        }
+    }
+
+    fn ntfy_tags(&self) -> &'static str {
+        match self {
+            SecurityEvent::NewIpLogin { .. } => "bust_in_silhouette",
+            SecurityEvent::LoginLockout { .. } => "warning",
+            SecurityEvent::SshKeyAdded { .. } => "key",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    #[serde(flatten)]
+    event: &'a SecurityEvent,
+    title: String,
+    message: String,
+    host: &'a str,
+    /// RFC 3339
+    at: String,
+}
+
+/// Sends security events to the configured channels
+#[derive(Clone)]
+pub struct Notifier {
+    config: Arc<NotifyConfig>,
+    http: reqwest::Client,
+    host: String,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Self {
+        crate::tls::install_crypto_provider();
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            config: Arc::new(config),
+            http,
+            host: gethostname::gethostname().to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Deliver in the background. Must be called inside the tokio runtime.
+    pub fn notify(&self, event: SecurityEvent) {
+        if !self.config.has_channels() || !self.config.events.contains(&event.kind()) {
+            return;
+        }
+        tracing::info!("Security notification: {}", event.title());
+        let notifier = self.clone();
+        tokio::spawn(async move { notifier.deliver(&event).await });
+    }
+
+    async fn deliver(&self, event: &SecurityEvent) {
+        if let Some(ref url) = self.config.webhook_url
+            && let Err(e) = self.send_webhook(url, event).await
+        {
+            tracing::warn!("Notification webhook failed: {e}");
+        }
+        if let Some(ref url) = self.config.ntfy_url
+            && let Err(e) = self.send_ntfy(url, event).await
+        {
+            tracing::warn!("Notification to ntfy failed: {e}");
+        }
+        if let Some(ref to) = self.config.email_to
+            && let Err(e) = self.send_email(to, event).await
+        {
+            tracing::warn!("Notification email failed: {e}");
+        }
+    }
+
+    async fn send_webhook(&self, url: &str, event: &SecurityEvent) -> Result<(), String> {
+        let payload = WebhookPayload {
+            event,
+            title: event.title(),
+            message: event.message(),
+            host: &self.host,
+            at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.http
+            .post(url)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+
+    async fn send_ntfy(&self, url: &str, event: &SecurityEvent) -> Result<(), String> {
+        let mut req = self
+            .http
+            .post(url)
+            .header("Title", format!("den@{}: {}", self.host, event.title()))
+            .header("Tags", event.ntfy_tags())
+            .header("Priority", "high")
+            .body(event.message());
+        if let Some(ref token) = self.config.ntfy_token {
+            req = req.bearer_auth(token);
+        }
+        req.send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+
+    async fn send_email(&self, to: &str, event: &SecurityEvent) -> Result<(), String> {
+        let mail = email_message(to, &self.host, event);
+        let program = self.config.sendmail.as_deref().unwrap_or(DEFAULT_SENDMAIL);
+        let mut child = tokio::process::Command::new(program)
+            .args(["-t", "-i"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("{program}: {e}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(mail.as_bytes())
+                .await
+                .map_err(|e| format!("{program}: {e}"))?;
+        }
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| format!("{program}: {e}"))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{program} exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+/// RFC 5322 message for `sendmail -t`. Header values are stripped of control
+/// characters so nothing can inject extra headers.
+fn email_message(to: &str, host: &str, event: &SecurityEvent) -> String {
+    let clean = |s: &str| s.chars().filter(|c| !c.is_control()).collect::<String>();
+    format!(
+        "To: {}\r\nSubject: [den@{}] {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        clean(to),
+        clean(host),
+        clean(&event.title()),
+        event.message()
+    )
+}
+
+/// Remember a successful login and report it when the address is new for the
+/// account. Blocking (writes `login-ips.json`).
+pub fn record_login(
+    store: &Store,
+    notifier: &Notifier,
+    user: Option<&str>,
+    ip: Option<IpAddr>,
+    via: &'static str,
+) {
+    let Some(ip) = ip else {
+        return;
+    };
+    match store.record_login_ip(user, ip) {
+        Ok(true) => notifier.notify(SecurityEvent::NewIpLogin {
+            user: user.map(str::to_string),
+            ip,
+            via,
+        }),
+        Ok(false) => {}
+        Err(e) => tracing::warn!("Failed to save login IPs: {e}"),
+    }
+}
+
+/// Compare the SSH `authorized_keys` entries ("algorithm base64") with those
+/// seen on the previous start and report the new ones. The first run only
+/// records a baseline. Keys are read once at startup, so this is exactly when
+/// an added key becomes usable.
+pub fn check_authorized_keys(store: &Store, notifier: &Notifier, keys: &HashSet<String>) {
+    let known = store.load_known_ssh_keys();
+    if let Some(ref known) = known {
+        let known: HashSet<&String> = known.iter().collect();
+        let mut added: Vec<&String> = keys.iter().filter(|k| !known.contains(k)).collect();
+        added.sort();
+        for key in added {
+            notifier.notify(SecurityEvent::SshKeyAdded {
+                fingerprint: key_fingerprint(key),
+            });
+        }
+    }
+    let mut current: Vec<String> = keys.iter().cloned().collect();
+    current.sort();
+    if known.as_ref() != Some(&current)
+        && let Err(e) = store.save_known_ssh_keys(&current)
+    {
+        tracing::warn!("Failed to save known SSH keys: {e}");
+    }
+}
+
+/// OpenSSH-style `SHA256:...` fingerprint of "algorithm base64"
+fn key_fingerprint(key: &str) -> String {
+    let mut parts = key.split_whitespace();
+    let algo = parts.next().unwrap_or_default();
+    match parts.next().and_then(|data| STANDARD.decode(data).ok()) {
+        Some(blob) => format!(
+            "{algo} SHA256:{}",
+            STANDARD_NO_PAD.encode(Sha256::digest(&blob))
+        ),
+        None => algo.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_payload_shape() {
+        let event = SecurityEvent::NewIpLogin {
+            user: Some("alice".into()),
+            ip: "203.0.113.5".parse().unwrap(),
+            via: "password",
+        };
+        let payload = WebhookPayload {
+            event: &event,
+            title: event.title(),
+            message: event.message(),
+            host: "box",
+            at: "2026-01-01T00:00:00Z".into(),
+        };
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event"], "new_ip_login");
+        assert_eq!(json["user"], "alice");
+        assert_eq!(json["ip"], "203.0.113.5");
+        assert_eq!(json["via"], "password");
+        assert_eq!(json["title"], "New login from 203.0.113.5");
+        assert_eq!(json["host"], "box");
+    }
+
+    #[test]
+    fn event_kinds_parse() {
+        for kind in EventKind::ALL {
+            assert_eq!(EventKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(EventKind::parse("everything"), None);
+    }
+
+    #[test]
+    fn email_headers_cannot_be_injected() {
+        let event = SecurityEvent::LoginLockout {
+            ip: None,
+            ban_secs: 60,
+        };
+        let mail = email_message("me@example.com\r\nBcc: evil@example.com", "box", &event);
+        let headers = mail.split("\r\n\r\n").next().unwrap();
+        assert_eq!(headers.lines().count(), 3);
+        assert!(headers.starts_with("To: me@example.comBcc: evil@example.com\r\n"));
+        assert!(headers.contains("Subject: [den@box] Login locked out: unknown client"));
+    }
+
+    #[test]
+    fn fingerprint_matches_openssh() {
+        // ssh-keygen -lf: SHA256 of the decoded key blob, unpadded base64
+        let blob = STANDARD.encode(b"not really a key");
+        let expected = STANDARD_NO_PAD.encode(Sha256::digest(b"not really a key"));
+        assert_eq!(
+            key_fingerprint(&format!("ssh-ed25519 {blob}")),
+            format!("ssh-ed25519 SHA256:{expected}")
+        );
+        assert_eq!(key_fingerprint("ssh-ed25519 !!!"), "ssh-ed25519");
+    }
+
+    #[test]
+    fn authorized_keys_baseline_then_additions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = Store::new(tmp.path().to_path_buf()).unwrap();
+        let notifier = Notifier::new(NotifyConfig::default());
+        let mut keys: HashSet<String> = ["ssh-ed25519 AAAA".to_string()].into();
+        check_authorized_keys(&store, &notifier, &keys);
+        assert_eq!(
+            store.load_known_ssh_keys(),
+            Some(vec!["ssh-ed25519 AAAA".to_string()])
+        );
+        keys.insert("ssh-rsa BBBB".to_string());
+        check_authorized_keys(&store, &notifier, &keys);
+        assert_eq!(store.load_known_ssh_keys().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,570 @@
+//! Optional OpenID Connect single sign-on (authorization code flow + PKCE).
+//!
+//! Enabled by `DEN_OIDC_ISSUER` / `DEN_OIDC_CLIENT_ID` / `DEN_OIDC_REDIRECT_URL`.
+//! A successful login maps the IdP subject (`sub`) to the owner
+//! (`DEN_OIDC_OWNER_SUBJECT`) or to the den user whose `oidc_subject` matches,
+//! and issues the same session cookies as a password login. The password
+//! login stays available as a fallback.
+//!
+//! The ID token comes straight from the token endpoint over a verified TLS
+//! connection, so (per OIDC Core §3.1.3.7) its signature is not checked; the
+//! issuer, audience, expiry and nonce are. That only holds over TLS, so the
+//! issuer and the discovered endpoints must be `https` (plain `http` is
+//! accepted for a loopback IdP only).
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+use crate::auth::{self, extract_cookie};
+use crate::ip_filter::ClientOrigin;
+
+/// How long the user has to finish signing in at the IdP
+const PENDING_TTL: Duration = Duration::from_secs(10 * 60);
+/// Unfinished logins one client (IP, IPv6 /64) may have at a time
+const MAX_PENDING_PER_CLIENT: usize = 5;
+/// Hard cap on all unfinished logins; new ones are refused, in-flight ones kept
+const MAX_PENDING: usize = 10_000;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+/// Binds the callback to the browser that started the login
+const STATE_COOKIE: &str = "den_oidc_state";
+const SCOPES: &str = "openid profile email";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcConfig {
+    /// e.g. `https://accounts.example.com`
+    pub issuer: String,
+    pub client_id: String,
+    /// None = public client (PKCE only)
+    pub client_secret: Option<String>,
+    /// Must point at `/api/auth/oidc/callback` and be registered at the IdP
+    pub redirect_url: String,
+    /// IdP subject that logs in as the owner
+    pub owner_subject: Option<String>,
+}
+
+/// The parts of the discovery document den uses
+#[derive(Debug, Clone, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+struct PendingLogin {
+    verifier: String,
+    nonce: String,
+    created: Instant,
+    /// `auth::client_key` of the browser that started the login
+    client: Option<IpAddr>,
+}
+
+pub struct OidcManager {
+    config: OidcConfig,
+    http: reqwest::Client,
+    /// Fetched on first use; a failed fetch is retried on the next login
+    discovery: tokio::sync::Mutex<Option<Discovery>>,
+    /// state → PKCE verifier + nonce
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcManager {
+    pub fn new(config: OidcConfig) -> Self {
+        crate::tls::install_crypto_provider();
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            config,
+            http,
+            discovery: tokio::sync::Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn discovery(&self) -> Result<Discovery, String> {
+        let mut cached = self.discovery.lock().await;
+        if let Some(ref discovery) = *cached {
+            return Ok(discovery.clone());
+        }
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let discovery: Discovery = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("discovery request failed: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("invalid discovery document: {e}"))?;
+        if discovery.issuer.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            return Err(format!("discovery issuer mismatch: {}", discovery.issuer));
+        }
+        for (name, url) in [
+            ("authorization_endpoint", &discovery.authorization_endpoint),
+            ("token_endpoint", &discovery.token_endpoint),
+        ] {
+            if !Url::parse(url).is_ok_and(|u| is_trusted_idp_url(&u)) {
+                return Err(format!("discovery {name} must be an https URL: {url}"));
+            }
+        }
+        *cached = Some(discovery.clone());
+        Ok(discovery)
+    }
+
+    /// Start a login for `client`. None when that client (or everyone) already
+    /// has too many unfinished logins; existing ones only ever expire by age.
+    fn begin(&self, client: Option<IpAddr>) -> Option<(String, String, String)> {
+        let client = auth::client_key(client);
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.created.elapsed() < PENDING_TTL);
+        let from_client = pending.values().filter(|p| p.client == client).count();
+        if from_client >= MAX_PENDING_PER_CLIENT || pending.len() >= MAX_PENDING {
+            return None;
+        }
+        let state = random_token();
+        let nonce = random_token();
+        let verifier = random_token();
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                verifier: verifier.clone(),
+                nonce: nonce.clone(),
+                created: Instant::now(),
+                client,
+            },
+        );
+        Some((state, nonce, verifier))
+    }
+
+    fn take_pending(&self, state: &str) -> Option<PendingLogin> {
+        let pending = self.pending.lock().unwrap().remove(state)?;
+        (pending.created.elapsed() < PENDING_TTL).then_some(pending)
+    }
+
+    /// Exchange the code and return the validated subject.
+    async fn finish(&self, code: &str, pending: PendingLogin) -> Result<String, String> {
+        let discovery = self.discovery().await?;
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", pending.verifier.as_str()),
+        ];
+        if let Some(ref secret) = self.config.client_secret {
+            params.push(("client_secret", secret));
+        }
+        let response: TokenResponse = self
+            .http
+            .post(&discovery.token_endpoint)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json")
+            .body(form_urlencode(&params))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("token request failed: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("invalid token response: {e}"))?;
+        let claims = decode_claims(&response.id_token)?;
+        claims.validate(
+            &discovery.issuer,
+            &self.config.client_id,
+            &pending.nonce,
+            now_secs(),
+        )?;
+        Ok(claims.sub)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    /// A string or an array of strings
+    aud: serde_json::Value,
+    exp: u64,
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+impl IdTokenClaims {
+    fn validate(&self, issuer: &str, client_id: &str, nonce: &str, now: u64) -> Result<(), String> {
+        if self.iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err("issuer mismatch".into());
+        }
+        let audience_ok = match self.aud {
+            serde_json::Value::String(ref aud) => aud == client_id,
+            serde_json::Value::Array(ref auds) => auds.iter().any(|a| a == client_id),
+            _ => false,
+        };
+        if !audience_ok {
+            return Err("audience mismatch".into());
+        }
+        if self.exp <= now {
+            return Err("ID token expired".into());
+        }
+        if self.nonce.as_deref() != Some(nonce) {
+            return Err("nonce mismatch".into());
+        }
+        if self.sub.is_empty() {
+            return Err("missing subject".into());
+        }
+        Ok(())
+    }
+}
+
+/// Payload of a compact JWS, unverified (see the module docs).
+fn decode_claims(id_token: &str) -> Result<IdTokenClaims, String> {
+    let payload = id_token.split('.').nth(1).ok_or("malformed ID token")?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| "malformed ID token")?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("invalid ID token claims: {e}"))
+}
+
+/// `https`, or `http` to a loopback host (a local IdP during development).
+pub(crate) fn is_trusted_idp_url(url: &Url) -> bool {
+    match url.scheme() {
+        "https" => true,
+        "http" => match url.host_str() {
+            Some(host) if host.eq_ignore_ascii_case("localhost") => true,
+            Some(host) => host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback()),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn random_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+}
+
+/// RFC 7636 S256 code challenge
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+pub(crate) fn form_urlencode(params: &[(&str, &str)]) -> String {
+    // Url's query serializer is the application/x-www-form-urlencoded encoder
+    let mut url = Url::parse("http://localhost/").expect("static URL");
+    url.query_pairs_mut().extend_pairs(params);
+    url.query().unwrap_or_default().to_string()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system clock before epoch")
+        .as_secs()
+}
+
+#[derive(Serialize)]
+pub struct OidcStatus {
+    pub enabled: bool,
+}
+
+/// GET /api/auth/oidc — lets the login screen decide whether to offer SSO
+pub async fn status(State(state): State<Arc<AppState>>) -> Json<OidcStatus> {
+    Json(OidcStatus {
+        enabled: state.oidc.is_some(),
+    })
+}
+
+/// GET /api/auth/oidc/login — redirect to the IdP
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    origin: Option<axum::Extension<ClientOrigin>>,
+) -> Response {
+    let Some(ref oidc) = state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let discovery = match oidc.discovery().await {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::warn!("OIDC: {e}");
+            return failure_page(StatusCode::BAD_GATEWAY, "identity provider unavailable");
+        }
+    };
+    let origin = origin.map(|axum::Extension(o)| o).unwrap_or_default();
+    let Some((csrf_state, nonce, verifier)) = oidc.begin(origin.ip) else {
+        tracing::warn!("OIDC: too many unfinished logins from {:?}", origin.ip);
+        return failure_page(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too many sign-ins in progress, try again later",
+        );
+    };
+    let challenge = pkce_challenge(&verifier);
+    let Ok(url) = Url::parse_with_params(
+        &discovery.authorization_endpoint,
+        [
+            ("response_type", "code"),
+            ("client_id", oidc.config.client_id.as_str()),
+            ("redirect_uri", oidc.config.redirect_url.as_str()),
+            ("scope", SCOPES),
+            ("state", csrf_state.as_str()),
+            ("nonce", nonce.as_str()),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    ) else {
+        tracing::warn!("OIDC: invalid authorization endpoint");
+        return failure_page(StatusCode::BAD_GATEWAY, "identity provider misconfigured");
+    };
+
+    let secure = auth::secure_cookies(&state, &origin);
+    let mut headers = HeaderMap::new();
+    // SameSite=Lax: the IdP redirects back with a cross-site top-level GET
+    let cookie = format!(
+        "{STATE_COOKIE}={csrf_state}; HttpOnly; SameSite=Lax; Path=/api/auth/oidc; Max-Age={}{}",
+        PENDING_TTL.as_secs(),
+        if secure { "; Secure" } else { "" }
+    );
+    headers.insert(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&cookie).expect("valid cookie value"),
+    );
+    headers.insert(
+        header::LOCATION,
+        HeaderValue::from_str(url.as_str()).expect("URL is a valid header value"),
+    );
+    (StatusCode::SEE_OTHER, headers).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// GET /api/auth/oidc/callback — finish the login and set the session cookies
+pub async fn callback(
+    State(state): State<Arc<AppState>>,
+    origin: Option<axum::Extension<ClientOrigin>>,
+    req_headers: HeaderMap,
+    Query(q): Query<CallbackQuery>,
+) -> Response {
+    let Some(ref oidc) = state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(ref error) = q.error {
+        tracing::warn!("OIDC: IdP returned error: {error}");
+        return failure_page(StatusCode::UNAUTHORIZED, "sign-in was cancelled or denied");
+    }
+    let (Some(code), Some(csrf_state)) = (q.code, q.state) else {
+        return failure_page(StatusCode::BAD_REQUEST, "missing code or state");
+    };
+    if extract_cookie(&req_headers, STATE_COOKIE).as_deref() != Some(csrf_state.as_str()) {
+        return failure_page(
+            StatusCode::BAD_REQUEST,
+            "login was started in another browser",
+        );
+    }
+    let Some(pending) = oidc.take_pending(&csrf_state) else {
+        return failure_page(StatusCode::BAD_REQUEST, "login expired, try again");
+    };
+    let subject = match oidc.finish(&code, pending).await {
+        Ok(sub) => sub,
+        Err(e) => {
+            tracing::warn!("OIDC: {e}");
+            return failure_page(StatusCode::UNAUTHORIZED, "identity provider login failed");
+        }
+    };
+
+    let account = if oidc.config.owner_subject.as_deref() == Some(subject.as_str()) {
+        tracing::info!("Login successful (OIDC)");
+        None
+    } else if let Some(user) = state
+        .store
+        .load_users()
+        .into_iter()
+        .find(|u| u.oidc_subject.as_deref() == Some(subject.as_str()))
+    {
+        tracing::info!("Login successful (OIDC): {}", user.username);
+        Some(user)
+    } else {
+        tracing::warn!("OIDC: no den account for subject {subject}");
+        return failure_page(
+            StatusCode::FORBIDDEN,
+            "no den account is linked to this identity",
+        );
+    };
+
+    let origin = origin.map(|axum::Extension(o)| o).unwrap_or_default();
+    let username = account.as_ref().map(|u| u.username.as_str());
+    crate::notify::record_login(&state.store, &state.notifier, username, origin.ip, "sso");
+
+    let secure = auth::secure_cookies(&state, &origin);
+    let mut headers = auth::start_session(&state, account.as_ref(), &req_headers, &origin, None);
+    let clear_state = format!(
+        "{STATE_COOKIE}=; HttpOnly; SameSite=Lax; Path=/api/auth/oidc; Max-Age=0{}",
+        if secure { "; Secure" } else { "" }
+    );
+    headers.append(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&clear_state).expect("valid cookie value"),
+    );
+    // The session cookies are SameSite=Strict and this response belongs to a
+    // cross-site navigation, so hop to the app with a same-site refresh
+    // instead of a 303.
+    (
+        headers,
+        axum::response::Html(
+            r#"<!DOCTYPE html><meta charset="utf-8"><meta http-equiv="refresh" content="0;url=/"><title>Den</title><a href="/">Continue to Den</a>"#,
+        ),
+    )
+        .into_response()
+}
+
+/// Minimal HTML error page (the browser is mid-redirect, not in the app).
+/// `reason` is always one of the static strings above.
+fn failure_page(status: StatusCode, reason: &str) -> Response {
+    (
+        status,
+        axum::response::Html(format!(
+            r#"<!DOCTYPE html><meta charset="utf-8"><title>Den</title><p>Single sign-on failed: {reason}.</p><p><a href="/">Back to login</a></p>"#
+        )),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(nonce: Option<&str>, aud: serde_json::Value) -> IdTokenClaims {
+        IdTokenClaims {
+            iss: "https://idp.example".into(),
+            sub: "user-1".into(),
+            aud,
+            exp: 2_000,
+            nonce: nonce.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn pkce_s256() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mJ92K5ZqmB4t2Y0kW5cw6AMo-Fn1Q4"),
+            "l6q6bVmosH4FUIqq6h6Gp1yp4uRqok6FKFaz8Wm3aMA"
+        );
+    }
+
+    #[test]
+    fn id_token_validation() {
+        let aud = serde_json::json!("den");
+        let ok = claims(Some("n1"), aud.clone());
+        assert!(
+            ok.validate("https://idp.example/", "den", "n1", 1_000)
+                .is_ok()
+        );
+        assert!(
+            ok.validate("https://other.example", "den", "n1", 1_000)
+                .is_err()
+        );
+        assert!(
+            ok.validate("https://idp.example", "other", "n1", 1_000)
+                .is_err()
+        );
+        assert!(
+            ok.validate("https://idp.example", "den", "n2", 1_000)
+                .is_err()
+        );
+        assert!(
+            ok.validate("https://idp.example", "den", "n1", 2_000)
+                .is_err()
+        );
+        assert!(
+            claims(None, aud)
+                .validate("https://idp.example", "den", "n1", 1_000)
+                .is_err()
+        );
+        let multi = claims(Some("n1"), serde_json::json!(["api", "den"]));
+        assert!(
+            multi
+                .validate("https://idp.example", "den", "n1", 1_000)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn decode_jws_payload() {
+        let payload = URL_SAFE_NO_PAD.encode(
+            br#"{"iss":"https://idp.example","sub":"abc","aud":"den","exp":5,"nonce":"n"}"#,
+        );
+        let claims = decode_claims(&format!("eyJhbGciOiJSUzI1NiJ9.{payload}.sig")).unwrap();
+        assert_eq!(claims.sub, "abc");
+        assert_eq!(claims.nonce.as_deref(), Some("n"));
+        assert!(decode_claims("not-a-jwt").is_err());
+    }
+
+    #[test]
+    fn idp_urls_need_tls_unless_loopback() {
+        let trusted = |u: &str| is_trusted_idp_url(&Url::parse(u).unwrap());
+        assert!(trusted("https://idp.example/token"));
+        assert!(trusted("http://localhost:8080"));
+        assert!(trusted("http://127.0.0.1:9000/token"));
+        assert!(trusted("http://[::1]:9000"));
+        assert!(!trusted("http://idp.example"));
+        assert!(!trusted("http://10.0.0.5/token"));
+        assert!(!trusted("ftp://idp.example"));
+    }
+
+    #[test]
+    fn pending_logins_are_limited_per_client_not_evicted() {
+        let oidc = OidcManager::new(OidcConfig {
+            issuer: "https://idp.example".into(),
+            client_id: "den".into(),
+            client_secret: None,
+            redirect_url: "https://den.example/api/auth/oidc/callback".into(),
+            owner_subject: None,
+        });
+        let attacker: Option<IpAddr> = Some("2001:db8::1".parse().unwrap());
+        let victim: Option<IpAddr> = Some("198.51.100.2".parse().unwrap());
+        let (victim_state, ..) = oidc.begin(victim).unwrap();
+        for _ in 0..MAX_PENDING_PER_CLIENT {
+            assert!(oidc.begin(attacker).is_some());
+        }
+        // Same /64, fresh address: still the same client
+        assert!(oidc.begin(Some("2001:db8::2".parse().unwrap())).is_none());
+        assert!(oidc.take_pending(&victim_state).is_some());
+    }
+
+    #[test]
+    fn form_encoding() {
+        assert_eq!(
+            form_urlencode(&[("a", "x y"), ("redirect_uri", "https://h/cb?q=1&r")]),
+            "a=x+y&redirect_uri=https%3A%2F%2Fh%2Fcb%3Fq%3D1%26r"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! OpenAPI description of the REST API (`GET /api/openapi.json`) and a
+//! Swagger UI page to browse it (`GET /api/docs`).
+//!
+//! Handlers carry `#[utoipa::path]` and their request/response types derive
+//! `ToSchema` / `IntoParams`; add a handler to `paths(...)` below when you
+//! annotate it. The spec covers the file panel, SFTP, terminal session,
+//! settings and dashboard APIs. WebSockets and the rest of the API are not
+//! described yet.
+
+use axum::{
+    http::header,
+    response::{Html, IntoResponse},
+};
+use base64::Engine;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::auth::{CSRF_HEADER, TOKEN_COOKIE};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Den",
+        description = "REST API of a Den server. Authenticate with an API token \
+            (`Authorization: Bearer ...`) or the `den_token` cookie from `POST /api/login`; \
+            cookie-authenticated writes also need the `X-Den-Request` header."
+    ),
+    paths(
+        crate::filer::api::list,
+        crate::filer::api::read,
+        crate::filer::api::tail,
+        crate::filer::api::preview,
+        crate::filer::api::checksum,
+        crate::filer::api::search,
+        crate::filer::api::download,
+        crate::filer::api::write,
+        crate::filer::api::mkdir,
+        crate::filer::api::create,
+        crate::filer::api::rename,
+        crate::filer::api::copy,
+        crate::filer::api::move_path,
+        crate::filer::api::delete,
+        crate::sftp::api::connect,
+        crate::sftp::api::status,
+        crate::sftp::api::disconnect,
+        crate::sftp::api::list,
+        crate::sftp::api::read,
+        crate::sftp::api::download,
+        crate::sftp::api::write,
+        crate::sftp::api::mkdir,
+        crate::sftp::api::rename,
+        crate::sftp::api::delete,
+        crate::ws::list_sessions,
+        crate::ws::create_session,
+        crate::ws::reorder_sessions,
+        crate::ws::rename_session,
+        crate::ws::destroy_session,
+        crate::store_api::get_settings,
+        crate::store_api::put_settings,
+        crate::store_api::get_features,
+        crate::dashboard::handler,
+    ),
+    modifiers(&Security),
+    tags(
+        (name = "filer", description = "Files on the Den host (limited to DEN_FILER_ROOTS)"),
+        (name = "sftp", description = "Files on the connected SFTP host"),
+        (name = "terminal", description = "Terminal sessions; attach with the /api/ws WebSocket"),
+        (name = "settings", description = "Per-account settings"),
+        (name = "system", description = "Server state"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Bearer token and session cookie, either of which authenticates every operation
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(TOKEN_COOKIE))),
+        );
+        openapi.security = Some(vec![
+            utoipa::openapi::security::SecurityRequirement::new("bearer", Vec::<String>::new()),
+            utoipa::openapi::security::SecurityRequirement::new("cookie", Vec::<String>::new()),
+        ]);
+    }
+}
+
+/// GET /api/openapi.json
+pub async fn spec() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        ApiDoc::openapi().to_json().unwrap_or_default(),
+    )
+}
+
+const SWAGGER_UI: &str = "https://cdn.jsdelivr.net/npm/swagger-ui-dist@5";
+
+/// GET /api/docs — Swagger UI (from the jsDelivr CDN) for `/api/openapi.json`.
+/// "Try it out" uses the page's login cookie, so writes get the CSRF header.
+pub async fn swagger_ui() -> impl IntoResponse {
+    let nonce = base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 16]>());
+    let csp = format!(
+        "default-src 'none'; script-src {SWAGGER_UI}/ 'nonce-{nonce}'; \
+         style-src {SWAGGER_UI}/ 'unsafe-inline'; img-src 'self' data:; connect-src 'self'; \
+         frame-ancestors 'none'; base-uri 'none'"
+    );
+    let page = format!(
+        r##"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Den API</title>
+<link rel="stylesheet" href="{SWAGGER_UI}/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="{SWAGGER_UI}/swagger-ui-bundle.js"></script>
+<script nonce="{nonce}">
+SwaggerUIBundle({{
+  url: "/api/openapi.json",
+  dom_id: "#swagger-ui",
+  requestInterceptor: (req) => {{ req.headers["{CSRF_HEADER}"] = "1"; return req; }},
+}});
+</script>
+</body>
+</html>
+"##
+    );
+    (
+        [
+            (header::CONTENT_SECURITY_POLICY, csp),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        Html(page),
+    )
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 
+use super::registry::{NAMESPACE_SEP, scoped_name};
+
 /// セッション起動の backend 種別
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, utoipa::ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SessionBackend {
     #[default]
@@ -179,6 +181,30 @@ pub fn list_zellij_detailed() -> Vec<(String, bool)> {
     parse_zellij_ls_detailed(&output)
 }
 
+/// Namespace separator in zellij/tmux session names. Registry keys use `.`
+/// (`alice.main`), which tmux silently rewrites to `_`; `__` survives both
+/// multiplexers and never occurs in a session name (alphanumeric + `-`).
+pub const MUX_NAMESPACE_SEP: &str = "__";
+
+/// zellij/tmux session name for a registry key (`alice.main` → `alice__main`)
+pub fn mux_session_name(key: &str) -> String {
+    match key.split_once(NAMESPACE_SEP) {
+        Some((namespace, name)) => format!("{namespace}{MUX_NAMESPACE_SEP}{name}"),
+        None => key.to_string(),
+    }
+}
+
+/// Inverse of [`mux_session_name`]. Only a prefix that `is_namespace`
+/// accepts (an existing account) counts as a namespace, so sessions made
+/// outside den keep their names. Usernames may contain `_`; the session
+/// name part cannot, so the last `__` is the separator.
+pub fn mux_session_key(mux_name: &str, is_namespace: impl Fn(&str) -> bool) -> String {
+    match mux_name.rsplit_once(MUX_NAMESPACE_SEP) {
+        Some((namespace, name)) if is_namespace(namespace) => scoped_name(Some(namespace), name),
+        _ => mux_name.to_string(),
+    }
+}
+
 /// Validates a mux session name: alphanumeric + `-`, 1–64 chars.
 /// Defense-in-depth even though argv is passed directly (no shell expansion).
 pub fn is_valid_mux_name(name: &str) -> bool {
@@ -251,6 +277,21 @@ pub fn delete_mux_session(backend: SessionBackend, name: &str) -> Result<(), Str
 mod tests {
     use super::*;
 
+    #[test]
+    fn mux_names_round_trip_through_tmux_safe_separator() {
+        let is_user = |ns: &str| ["alice", "a_b"].contains(&ns);
+        for key in ["main", "alice.main", "a_b.dev-2"] {
+            let mux = mux_session_name(key);
+            // tmux rewrites `.` and `:` in session names
+            assert!(!mux.contains(['.', ':']), "{mux}");
+            assert_eq!(mux_session_key(&mux, is_user), key);
+        }
+        assert_eq!(mux_session_name("alice.main"), "alice__main");
+        // Not an account: a plain (owner) name
+        assert_eq!(mux_session_key("bob__main", is_user), "bob__main");
+        assert_eq!(mux_session_key("a_b__x", is_user), "a_b.x");
+    }
+
     fn mux(zellij_config: &str, tmux_conf: &str) -> MuxConfig {
         MuxConfig {
             zellij_config: zellij_config.to_string(),
@@ -14,6 +14,20 @@ pub struct PtySession {
 pub struct PtyManager;
 
 impl PtyManager {
+    /// PTY（Windows は ConPTY）が作れるか確認する。プロセスは起動しない
+    pub fn probe() -> Result<(), String> {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        native_pty_system()
+            .openpty(size)
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+
     /// プロセスを PTY で起動。`program` + `args`（argv 配列）を受け取る。
     /// Shell backend は `program=shell, args=[]`、multiplexer backend は
     /// `build_launch_command` が組み立てた zellij/tmux の argv を渡す。
@@ -225,7 +225,8 @@ fn apply_sleep_state(prevent: bool, config: &mut SleepConfig) {
 /// グローバルセッション管理
 pub struct SessionRegistry {
     sessions: RwLock<HashMap<String, Arc<SharedSession>>>,
-    shell: String,
+    /// 新規セッションのシェル（設定変更で差し替わる。既存セッションには影響しない）
+    shell: std::sync::RwLock<String>,
     sleep_config: Arc<std::sync::Mutex<SleepConfig>>,
     /// ユーザー操作タイムスタンプ（Unix epoch 秒、Relaxed atomic で更新）
     last_activity: Arc<AtomicU64>,
@@ -301,6 +302,8 @@ pub struct ClientInfo {
 pub enum ClientKind {
     WebSocket,
     Ssh,
+    /// 読み取り専用の閲覧者（出力のみ受信。入力・PTY サイズには関与しない）
+    Observer,
 }
 
 /// SSH session connection config
@@ -317,7 +320,7 @@ pub struct SshSessionConfig {
 }
 
 /// UI/API 向けセッション情報
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct SessionInfo {
     pub name: String,
     pub created_at: DateTime<Utc>,
@@ -327,11 +330,48 @@ pub struct SessionInfo {
     pub ssh_host: Option<String>,
 }
 
-/// セッション名バリデーション: 英数字 + ハイフンのみ、最大 64 文字
+/// メトリクス用の集計（/metrics）
+#[derive(Debug, Default)]
+pub struct RegistryStats {
+    pub sessions: usize,
+    pub alive: usize,
+    pub websocket_clients: usize,
+    pub ssh_clients: usize,
+    pub observers: usize,
+}
+
+/// ユーザー名前空間とセッション名の区切り（どちらの名前にも使えない文字）
+pub const NAMESPACE_SEP: char = '.';
+
+/// セッション名バリデーション: 英数字 + ハイフンのみ、最大 64 文字。
+/// 名前空間付きのキー（`alice.main`）は両方の部分を検証する。
 fn is_valid_session_name(name: &str) -> bool {
-    !name.is_empty()
-        && name.len() <= 64
-        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    let plain = |n: &str| {
+        !n.is_empty() && n.len() <= 64 && n.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    match name.split_once(NAMESPACE_SEP) {
+        Some((namespace, rest)) => crate::store::is_valid_username(namespace) && plain(rest),
+        None => plain(name),
+    }
+}
+
+/// Registry key for `name` in a user's namespace. `None` is the owner's
+/// (root) namespace, whose keys are the plain names.
+pub fn scoped_name(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(ns) => format!("{ns}{NAMESPACE_SEP}{name}"),
+        None => name.to_string(),
+    }
+}
+
+/// Inverse of [`scoped_name`]: the user-facing name if `key` belongs to
+/// `namespace`.
+pub fn unscoped_name<'a>(namespace: Option<&str>, key: &'a str) -> Option<&'a str> {
+    match (namespace, key.split_once(NAMESPACE_SEP)) {
+        (Some(ns), Some((key_ns, name))) if key_ns == ns => Some(name),
+        (None, None) => Some(key),
+        _ => None,
+    }
 }
 
 /// 既存セッションと要求 backend を照合してエラーを決める。
@@ -464,7 +504,7 @@ impl SessionRegistry {
 
         let registry = Arc::new(Self {
             sessions: RwLock::new(HashMap::new()),
-            shell,
+            shell: std::sync::RwLock::new(shell),
             sleep_config,
             last_activity,
             instance_id,
@@ -509,6 +549,16 @@ impl SessionRegistry {
         &self.instance_id
     }
 
+    /// Shell that new sessions start
+    pub fn shell(&self) -> String {
+        self.shell.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Change the shell for sessions created from now on
+    pub fn set_shell(&self, shell: String) {
+        *self.shell.write().unwrap_or_else(|e| e.into_inner()) = shell;
+    }
+
     /// Collect child process PIDs from all active sessions.
     /// Used for self-connection detection via process tree inspection.
     pub async fn collect_child_pids(&self) -> std::collections::HashSet<u32> {
@@ -528,6 +578,28 @@ impl SessionRegistry {
         pids
     }
 
+    /// (registry key, child PID) of live sessions, for looking at what runs in them
+    pub async fn child_pids(&self) -> Vec<(String, u32)> {
+        let session_arcs: Vec<_> = self
+            .sessions
+            .read()
+            .await
+            .iter()
+            .map(|(k, v)| (k.clone(), Arc::clone(v)))
+            .collect();
+        let mut pids = Vec::new();
+        for (name, session) in &session_arcs {
+            if !session.is_alive() {
+                continue;
+            }
+            let inner = session.inner.lock().await;
+            if let Some(pid) = inner.child.as_ref().and_then(|c| c.process_id()) {
+                pids.push((name.clone(), pid));
+            }
+        }
+        pids
+    }
+
     /// PTY を spawn し read_task/resize_task を起動する共通ヘルパー
     ///
     /// 戻り値の `broadcast::Receiver` は read_task 開始前に作成されるため、
@@ -614,6 +686,7 @@ impl SessionRegistry {
                     Ok(0) => break,
                     Ok(n) => {
                         let data = buf[..n].to_vec();
+                        crate::metrics::PTY_OUTPUT_BYTES.fetch_add(n as u64, Ordering::Relaxed);
 
                         // replay state: byte ring + VT parser を同一ロックで更新。
                         // poison しても seq の連続性を保つため into_inner で復帰する。
@@ -714,7 +787,7 @@ impl SessionRegistry {
 
         // PTY を spawn（blocking）
         let pty = tokio::task::spawn_blocking({
-            let shell = self.shell.clone();
+            let shell = self.shell();
             let instance_id = self.instance_id.clone();
             move || PtyManager::spawn(&shell, &[], cols, rows, &instance_id)
         })
@@ -815,8 +888,12 @@ impl SessionRegistry {
 
         // layout/conf パスが空（書き出し失敗）のときは build_launch_command 側で
         // layout フラグを付けずに素の attach コマンドを返す。
-        let (program, args) =
-            crate::pty::backend::build_launch_command(backend, &self.shell, name, &self.mux);
+        let (program, args) = crate::pty::backend::build_launch_command(
+            backend,
+            &self.shell(),
+            &crate::pty::backend::mux_session_name(name),
+            &self.mux,
+        );
 
         // PTY を spawn（blocking）
         let pty = tokio::task::spawn_blocking({
@@ -933,7 +1010,7 @@ impl SessionRegistry {
         let rx = session.subscribe();
 
         // アクティブクライアントがいない場合は新クライアントをアクティブにする
-        if inner.active_client_id.is_none() {
+        if inner.active_client_id.is_none() && kind != ClientKind::Observer {
             inner.active_client_id = Some(client_id);
         }
         // クライアント追加により最適サイズが変わる可能性があるため再計算
@@ -1086,6 +1163,7 @@ impl SessionRegistry {
             inner.active_client_id = inner
                 .clients
                 .iter()
+                .filter(|c| c.kind != ClientKind::Observer)
                 .max_by_key(|c| c.last_active)
                 .map(|c| c.id);
         }
@@ -1159,6 +1237,18 @@ impl SessionRegistry {
         result
     }
 
+    /// Sessions of one user namespace, with the namespace prefix stripped.
+    pub async fn list_in(&self, namespace: Option<&str>) -> Vec<SessionInfo> {
+        self.list()
+            .await
+            .into_iter()
+            .filter_map(|mut info| {
+                info.name = unscoped_name(namespace, &info.name)?.to_string();
+                Some(info)
+            })
+            .collect()
+    }
+
     /// セッション破棄
     pub async fn destroy(&self, name: &str) {
         let (session, session_count) = {
@@ -1286,6 +1376,28 @@ impl SessionRegistry {
         }
     }
 
+    /// セッション数と接続クライアント数（種別ごと）
+    pub async fn stats(&self) -> RegistryStats {
+        let sessions: Vec<_> = self.sessions.read().await.values().cloned().collect();
+        let mut stats = RegistryStats {
+            sessions: sessions.len(),
+            ..Default::default()
+        };
+        for session in &sessions {
+            if session.is_alive() {
+                stats.alive += 1;
+            }
+            for client in &session.inner.lock().await.clients {
+                match client.kind {
+                    ClientKind::WebSocket => stats.websocket_clients += 1,
+                    ClientKind::Ssh => stats.ssh_clients += 1,
+                    ClientKind::Observer => stats.observers += 1,
+                }
+            }
+        }
+        stats
+    }
+
     /// セッションが存在するか
     pub async fn exists(&self, name: &str) -> bool {
         self.sessions.read().await.contains_key(name)
@@ -1300,18 +1412,19 @@ impl SessionRegistry {
     ///
     /// アクティブなクライアントは、最後に入力またはリサイズしたクライアント。
     /// フォールバックとして last_active が最新のクライアントを使用する。
+    /// Observer はサイズ決定に関与しない（Observer だけなら現サイズを維持）。
     fn recalculate_size(inner: &mut SessionInner) {
-        if inner.clients.is_empty() {
+        let drivers = inner
+            .clients
+            .iter()
+            .filter(|c| c.kind != ClientKind::Observer);
+        let active = inner
+            .active_client_id
+            .and_then(|id| drivers.clone().find(|c| c.id == id))
+            .or_else(|| drivers.max_by_key(|c| c.last_active));
+        let Some(active) = active else {
             return;
-        }
-
-        let active = if let Some(id) = inner.active_client_id {
-            inner.clients.iter().find(|c| c.id == id)
-        } else {
-            None
-        }
-        .or_else(|| inner.clients.iter().max_by_key(|c| c.last_active))
-        .expect("clients is non-empty; checked above");
+        };
 
         let new_size = (active.cols, active.rows);
         if new_size == inner.last_size {
@@ -1398,6 +1511,7 @@ impl SharedSession {
         let mut inner = self.inner.lock().await;
         std::io::Write::write_all(&mut inner.pty_writer, data)
             .map_err(|e| format!("Write failed: {e}"))?;
+        crate::metrics::PTY_INPUT_BYTES.fetch_add(data.len() as u64, Ordering::Relaxed);
         std::io::Write::flush(&mut inner.pty_writer).map_err(|e| format!("Flush failed: {e}"))
     }
 
@@ -1415,6 +1529,9 @@ impl SharedSession {
             .store(now_epoch_secs(), Ordering::Relaxed);
         let mut inner = self.inner.lock().await;
         if let Some(client) = inner.clients.iter_mut().find(|c| c.id == client_id) {
+            if client.kind == ClientKind::Observer {
+                return Err("Observers cannot send input".to_string());
+            }
             client.last_active = std::time::Instant::now();
             if inner.active_client_id != Some(client_id) {
                 inner.active_client_id = Some(client_id);
@@ -1425,6 +1542,7 @@ impl SharedSession {
         }
         std::io::Write::write_all(&mut inner.pty_writer, data)
             .map_err(|e| format!("Write failed: {e}"))?;
+        crate::metrics::PTY_INPUT_BYTES.fetch_add(data.len() as u64, Ordering::Relaxed);
         std::io::Write::flush(&mut inner.pty_writer).map_err(|e| format!("Flush failed: {e}"))
     }
 
@@ -1437,7 +1555,7 @@ impl SharedSession {
     pub async fn resize(&self, client_id: u64, cols: u16, rows: u16) {
         let mut inner = self.inner.lock().await;
         if let Some(client) = inner.clients.iter_mut().find(|c| c.id == client_id) {
-            if client.cols == cols && client.rows == rows {
+            if client.kind == ClientKind::Observer || (client.cols == cols && client.rows == rows) {
                 return;
             }
             client.cols = cols;
@@ -1564,6 +1682,19 @@ mod tests {
         assert!(!is_valid_session_name("tab\there"));
     }
 
+    #[test]
+    fn scoped_session_names() {
+        let key = scoped_name(Some("alice"), "main");
+        assert_eq!(key, "alice.main");
+        assert!(is_valid_session_name(&key));
+        assert!(!is_valid_session_name("Alice.main"));
+        assert_eq!(unscoped_name(Some("alice"), &key), Some("main"));
+        assert_eq!(unscoped_name(Some("bob"), &key), None);
+        assert_eq!(unscoped_name(None, &key), None);
+        assert_eq!(unscoped_name(None, "main"), Some("main"));
+        assert_eq!(unscoped_name(Some("alice"), "main"), None);
+    }
+
     #[tokio::test]
     async fn rename_session_not_found() {
         let registry = SessionRegistry::new(
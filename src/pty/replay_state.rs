@@ -162,6 +162,7 @@ mod tests {
     // exactly the signal xterm.js uses to reflow.
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn snapshot_preserves_soft_wrap_for_reflow() {
         let mut rs = ReplayState::new(4096, 24, 80);
         // 200 chars, no newline, on an 80-col screen wrap across 3 rows.
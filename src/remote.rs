@@ -409,7 +409,7 @@ pub async fn remote_ws_handler(
 ) -> Result<Response, StatusCode> {
     let remote = state.remote_manager.get(&id).ok_or(StatusCode::NOT_FOUND)?;
     Ok(ws
-        .on_upgrade(move |socket| handle_remote_ws(socket, remote, query))
+        .on_upgrade(move |socket| handle_remote_ws(socket, remote, query, state.shutdown.clone()))
         .into_response())
 }
 
@@ -647,7 +647,13 @@ fn extract_cookie_from_response(response: &reqwest::Response, name: &str) -> Opt
         })
 }
 
-async fn handle_remote_ws(browser_ws: WebSocket, remote: RemoteSession, query: Option<String>) {
+async fn handle_remote_ws(
+    browser_ws: WebSocket,
+    remote: RemoteSession,
+    query: Option<String>,
+    shutdown: crate::shutdown::Shutdown,
+) {
+    let _open = crate::metrics::SocketGuard::open(&crate::metrics::REMOTE_SOCKETS);
     let ws_base = to_ws_base(&remote.base_url);
     let remote_url = match query.as_deref() {
         Some(q) if !q.is_empty() => format!("{ws_base}/api/ws?{q}"),
@@ -681,13 +687,14 @@ async fn handle_remote_ws(browser_ws: WebSocket, remote: RemoteSession, query: O
         }
     };
 
-    proxy_ws_bidirectional(browser_ws, remote_ws).await;
+    proxy_ws_bidirectional(browser_ws, remote_ws, shutdown).await;
 }
 
 /// Bidirectional WebSocket relay between browser (axum) and remote (tungstenite).
 async fn proxy_ws_bidirectional(
     browser_ws: WebSocket,
     remote_ws: tokio_tungstenite::WebSocketStream<tokio_rustls::client::TlsStream<TcpStream>>,
+    shutdown: crate::shutdown::Shutdown,
 ) {
     let (mut browser_tx, mut browser_rx) = browser_ws.split();
     let (mut remote_tx, mut remote_rx) = remote_ws.split();
@@ -777,10 +784,19 @@ async fn proxy_ws_bidirectional(
         }
     };
 
-    tokio::select! {
-        _ = browser_to_remote => {},
-        _ = remote_to_browser => {},
-    }
+    let restart = tokio::select! {
+        _ = browser_to_remote => return,
+        _ = remote_to_browser => return,
+        restart = shutdown.started() => restart,
+    };
+    // This den is going away: tell the browser and close both sides
+    let _ = browser_tx
+        .send(AxumWsMessage::Text(
+            crate::shutdown::client_message(restart).into(),
+        ))
+        .await;
+    let _ = browser_tx.close().await;
+    let _ = remote_tx.close().await;
 }
 
 async fn connect_remote_ws_client(
@@ -865,7 +881,9 @@ async fn proxy_to_remote_session(
         .http_client
         .request(method, url)
         .timeout(REMOTE_REQUEST_TIMEOUT)
-        .header(header::COOKIE, remote.cookie_header.clone());
+        .header(header::COOKIE, remote.cookie_header.clone())
+        // Cookie-authenticated writes on the remote Den require the CSRF header
+        .header(crate::auth::CSRF_HEADER, "1");
 
     if let Some(headers) = headers {
         for (name, value) in headers {
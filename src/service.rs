@@ -0,0 +1,344 @@
+//! Windows service mode: `den service install|uninstall|run`.
+//!
+//! `install` registers den with the Service Control Manager (auto start,
+//! LocalSystem) so it comes up at boot without anyone logged in. The SCM
+//! launches `den.exe service run`, which hands control to the service
+//! dispatcher; a stop or system shutdown request resolves
+//! [`stop_requested`], and den goes through its normal graceful shutdown.
+//!
+//! A self-update can't respawn the process under the SCM, so den exits with
+//! a service-specific error instead and the restart failure action installed
+//! here brings the new binary up.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+pub const SERVICE_NAME: &str = "den";
+
+/// Set by the SCM control handler (stop / shutdown)
+static STOP: Notify = Notify::const_new();
+/// True once den is running under the service dispatcher
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// Exit with an error so the SCM failure action restarts den
+static RESTART: AtomicBool = AtomicBool::new(false);
+
+/// `den service <sub>`. `serve` runs den until it has shut down.
+pub fn command(sub: Option<&str>, serve: fn()) {
+    let result = match sub {
+        Some("install") => imp::install(),
+        Some("uninstall") => imp::uninstall(),
+        Some("run") => imp::run(serve),
+        _ => {
+            eprintln!("Usage: den service install|uninstall|run");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Resolves when the SCM asks den to stop (never outside service mode)
+pub async fn stop_requested() {
+    STOP.notified().await;
+}
+
+/// Whether den is running as a Windows service
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Ask the SCM to start den again once it has stopped (after an update)
+pub fn exit_for_restart() {
+    RESTART.store(true, Ordering::SeqCst);
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::io;
+
+    fn unsupported() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "`den service` is only available on Windows (use systemd or launchd elsewhere)",
+        ))
+    }
+
+    pub fn install() -> io::Result<()> {
+        unsupported()
+    }
+
+    pub fn uninstall() -> io::Result<()> {
+        unsupported()
+    }
+
+    pub fn run(_serve: fn()) -> io::Result<()> {
+        unsupported()
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::ffi::{OsStr, c_void};
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{null, null_mut};
+    use std::sync::OnceLock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use windows_sys::Win32::Foundation::{
+        ERROR_CALL_NOT_IMPLEMENTED, ERROR_FAILED_SERVICE_CONTROLLER_CONNECT,
+        ERROR_SERVICE_NOT_ACTIVE, ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR,
+    };
+    use windows_sys::Win32::Storage::FileSystem::DELETE;
+    use windows_sys::Win32::System::Services::{
+        ChangeServiceConfig2W, CloseServiceHandle, ControlService, CreateServiceW, DeleteService,
+        OpenSCManagerW, OpenServiceW, RegisterServiceCtrlHandlerExW, SC_ACTION, SC_ACTION_RESTART,
+        SC_HANDLE, SC_MANAGER_CONNECT, SC_MANAGER_CREATE_SERVICE, SERVICE_ACCEPT_SHUTDOWN,
+        SERVICE_ACCEPT_STOP, SERVICE_AUTO_START, SERVICE_CHANGE_CONFIG, SERVICE_CONFIG_DESCRIPTION,
+        SERVICE_CONFIG_FAILURE_ACTIONS, SERVICE_CONFIG_FAILURE_ACTIONS_FLAG,
+        SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP,
+        SERVICE_DESCRIPTIONW, SERVICE_ERROR_NORMAL, SERVICE_FAILURE_ACTIONS_FLAG,
+        SERVICE_FAILURE_ACTIONSW, SERVICE_QUERY_STATUS, SERVICE_RUNNING, SERVICE_STATUS,
+        SERVICE_STATUS_CURRENT_STATE, SERVICE_STATUS_HANDLE, SERVICE_STOP, SERVICE_STOP_PENDING,
+        SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS, SetServiceStatus,
+        StartServiceCtrlDispatcherW,
+    };
+    use windows_sys::core::PWSTR;
+
+    use super::{RESTART, RUNNING, SERVICE_NAME, STOP};
+
+    const DISPLAY_NAME: &str = "Den";
+    const DESCRIPTION: &str = "Den web workstation (terminal, files and SSH in the browser)";
+
+    static SERVE: OnceLock<fn()> = OnceLock::new();
+    /// SERVICE_STATUS_HANDLE (a pointer, which isn't Sync)
+    static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+
+    struct ScHandle(SC_HANDLE);
+
+    impl Drop for ScHandle {
+        fn drop(&mut self) {
+            unsafe {
+                CloseServiceHandle(self.0);
+            }
+        }
+    }
+
+    fn checked(handle: SC_HANDLE) -> io::Result<ScHandle> {
+        if handle.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ScHandle(handle))
+        }
+    }
+
+    fn open_manager(access: u32) -> io::Result<ScHandle> {
+        checked(unsafe { OpenSCManagerW(null(), null(), access) })
+    }
+
+    fn change_config<T>(service: &ScHandle, level: u32, info: &T) -> io::Result<()> {
+        let ok =
+            unsafe { ChangeServiceConfig2W(service.0, level, info as *const T as *const c_void) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn install() -> io::Result<()> {
+        let exe = std::env::current_exe()?;
+        let command = format!("\"{}\" service run", exe.display());
+        let manager = open_manager(SC_MANAGER_CREATE_SERVICE)?;
+        let name = wide(SERVICE_NAME);
+        let display = wide(DISPLAY_NAME);
+        let command_w = wide(&command);
+        let service = checked(unsafe {
+            CreateServiceW(
+                manager.0,
+                name.as_ptr(),
+                display.as_ptr(),
+                SERVICE_CHANGE_CONFIG,
+                SERVICE_WIN32_OWN_PROCESS,
+                SERVICE_AUTO_START,
+                SERVICE_ERROR_NORMAL,
+                command_w.as_ptr(),
+                null(),
+                null_mut(),
+                null(),
+                // LocalSystem
+                null(),
+                null(),
+            )
+        })?;
+
+        let mut description = wide(DESCRIPTION);
+        change_config(
+            &service,
+            SERVICE_CONFIG_DESCRIPTION,
+            &SERVICE_DESCRIPTIONW {
+                lpDescription: description.as_mut_ptr(),
+            },
+        )?;
+        // 更新後の再起動（service-specific error で終了）とクラッシュ時の復帰。
+        // 失敗カウントは 1 日でリセット
+        let mut actions = [
+            SC_ACTION {
+                Type: SC_ACTION_RESTART,
+                Delay: 1000,
+            },
+            SC_ACTION {
+                Type: SC_ACTION_RESTART,
+                Delay: 5000,
+            },
+            SC_ACTION {
+                Type: SC_ACTION_RESTART,
+                Delay: 30_000,
+            },
+        ];
+        change_config(
+            &service,
+            SERVICE_CONFIG_FAILURE_ACTIONS,
+            &SERVICE_FAILURE_ACTIONSW {
+                dwResetPeriod: 24 * 60 * 60,
+                lpRebootMsg: null_mut(),
+                lpCommand: null_mut(),
+                cActions: actions.len() as u32,
+                lpsaActions: actions.as_mut_ptr(),
+            },
+        )?;
+        change_config(
+            &service,
+            SERVICE_CONFIG_FAILURE_ACTIONS_FLAG,
+            &SERVICE_FAILURE_ACTIONS_FLAG {
+                fFailureActionsOnNonCrashFailures: 1,
+            },
+        )?;
+
+        println!("Installed service \"{SERVICE_NAME}\": {command}");
+        println!("It starts at boot; start it now with: sc start {SERVICE_NAME}");
+        Ok(())
+    }
+
+    pub fn uninstall() -> io::Result<()> {
+        let manager = open_manager(SC_MANAGER_CONNECT)?;
+        let name = wide(SERVICE_NAME);
+        let service = checked(unsafe {
+            OpenServiceW(
+                manager.0,
+                name.as_ptr(),
+                SERVICE_STOP | SERVICE_QUERY_STATUS | DELETE,
+            )
+        })?;
+        let mut status: SERVICE_STATUS = unsafe { std::mem::zeroed() };
+        if unsafe { ControlService(service.0, SERVICE_CONTROL_STOP, &mut status) } == 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(ERROR_SERVICE_NOT_ACTIVE as i32) {
+                return Err(e);
+            }
+        }
+        // 停止完了後に SCM が削除する
+        if unsafe { DeleteService(service.0) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        println!("Removed service \"{SERVICE_NAME}\"");
+        Ok(())
+    }
+
+    pub fn run(serve: fn()) -> io::Result<()> {
+        let _ = SERVE.set(serve);
+        let mut name = wide(SERVICE_NAME);
+        let table = [
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: name.as_mut_ptr(),
+                lpServiceProc: Some(service_main),
+            },
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: null_mut(),
+                lpServiceProc: None,
+            },
+        ];
+        // サービス停止まで戻らない
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT as i32) {
+                return Err(io::Error::other(
+                    "`den service run` is started by the Service Control Manager; \
+                     register it with `den service install`",
+                ));
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn set_status(state: SERVICE_STATUS_CURRENT_STATE, service_exit_code: u32) {
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            dwWin32ExitCode: if service_exit_code == 0 {
+                NO_ERROR
+            } else {
+                ERROR_SERVICE_SPECIFIC_ERROR
+            },
+            dwServiceSpecificExitCode: service_exit_code,
+            dwCheckPoint: 0,
+            // Draining plus session persistence
+            dwWaitHint: if state == SERVICE_STOP_PENDING {
+                (crate::shutdown::DRAIN_TIMEOUT.as_millis() + 5000) as u32
+            } else {
+                0
+            },
+        };
+        unsafe {
+            SetServiceStatus(
+                STATUS_HANDLE.load(Ordering::SeqCst) as SERVICE_STATUS_HANDLE,
+                &status,
+            );
+        }
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+        let name = wide(SERVICE_NAME);
+        let handle =
+            unsafe { RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), null()) };
+        if handle.is_null() {
+            return;
+        }
+        STATUS_HANDLE.store(handle as usize, Ordering::SeqCst);
+        RUNNING.store(true, Ordering::SeqCst);
+        set_status(SERVICE_RUNNING, 0);
+        if let Some(serve) = SERVE.get() {
+            serve();
+        }
+        let exit_code = u32::from(RESTART.load(Ordering::SeqCst));
+        set_status(SERVICE_STOPPED, exit_code);
+    }
+
+    unsafe extern "system" fn control_handler(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut c_void,
+        _context: *mut c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                set_status(SERVICE_STOP_PENDING, 0);
+                STOP.notify_one();
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+}
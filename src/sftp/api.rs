@@ -1,23 +1,33 @@
 use axum::{
     Json,
     extract::{Multipart, Query, State},
-    http::{StatusCode, header},
+    http::{HeaderName, StatusCode, header},
     response::IntoResponse,
 };
+use futures::StreamExt;
 use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::OpenFlags;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::AppState;
+use crate::archive::tar;
 use crate::filer::api::{
-    DeleteQuery, DownloadQuery, ErrorResponse, FileContent, FilerEntry, FilerListing, MkdirRequest,
-    ReadQuery, RenameRequest, SearchQuery, SearchResult, WriteRequest, err, is_binary,
-    is_hidden_name,
+    ChecksumAlgo, ChecksumQuery, ChecksumResponse, CopyMoveResponse, CreateRequest, DeleteQuery,
+    DownloadQuery, ErrorResponse, FileContent, FilerEntry, FilerListing, MkdirRequest, ReadQuery,
+    RenameRequest, SearchMode, SearchQuery, SearchResult, TailResponse, WriteRequest,
+    WriteResponse, attachment_name, check_if_match, err, io_err, is_binary, is_hidden_name,
+    template_content,
 };
+use crate::filer::roots::FilerRoots;
 use crate::store::KnownHost;
 
-use super::client::SftpError;
+use super::client::{SftpAuth, SftpError, SftpGuard, SshHop, shell_quote};
+use super::transfer::{Transfer, TransferDirection, TransferError, TransferIdQuery};
 
 /// 共通エラー型
 type ApiError = (StatusCode, Json<ErrorResponse>);
@@ -28,14 +38,56 @@ const MAX_READ_SIZE: u64 = 10 * 1024 * 1024;
 const MAX_UPLOAD_SIZE: usize = 50 * 1024 * 1024;
 /// ダウンロード上限: 100MB
 const MAX_DOWNLOAD_SIZE: u64 = 100 * 1024 * 1024;
+/// tail のデフォルト読み込みサイズ: 64KB
+const DEFAULT_TAIL_BYTES: u64 = 64 * 1024;
 /// 検索深さ上限
 const MAX_SEARCH_DEPTH: u32 = 10;
 /// 検索結果上限
 const MAX_SEARCH_RESULTS: usize = 100;
+/// stdout cap for exec-based search (100 lines of ≤ a few KB each)
+const MAX_EXEC_SEARCH_OUTPUT: usize = 512 * 1024;
+/// Remote find/grep budget before returning partial results
+const EXEC_SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Remote checksum budget (large files hash at disk speed)
+const EXEC_CHECKSUM_TIMEOUT: Duration = Duration::from_secs(600);
+/// `df` fallback budget for disk usage
+const EXEC_DF_TIMEOUT: Duration = Duration::from_secs(10);
+/// download-many: 一度に選択できるパス数
+const MAX_DOWNLOAD_MANY_PATHS: usize = 1000;
+/// download-many: files up to this size are prefetched concurrently
+const PREFETCH_MAX_SIZE: u64 = 4 * 1024 * 1024;
+const DOWNLOAD_MANY_CONCURRENCY: usize = 4;
+/// Pipe between the tar writer task and the response body
+const ARCHIVE_PIPE_SIZE: usize = 64 * 1024;
+/// バッチ操作の上限
+const MAX_BATCH_OPS: usize = 500;
+/// Jump host chain length limit
+const MAX_JUMP_HOSTS: usize = 8;
+
+/// Response header carrying the tracked transfer id
+static TRANSFER_ID_HEADER: HeaderName = HeaderName::from_static("x-transfer-id");
 
 // --- リクエスト型 ---
 
 #[derive(Deserialize)]
+pub struct DownloadManyQuery {
+    /// JSON array of remote paths
+    pub paths: String,
+    /// Archive file name without extension
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TailQuery {
+    pub path: String,
+    /// Max bytes to return (default 64KB, capped at the read limit)
+    pub bytes: Option<u64>,
+    /// Follow mode: resume from the `size` returned by the previous call
+    pub offset: Option<u64>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct ConnectRequest {
     pub host: String,
     pub port: Option<u16>,
@@ -43,18 +95,88 @@ pub struct ConnectRequest {
     pub auth_type: String, // "password", "key", or "agent"
     pub password: Option<String>,
     pub key_path: Option<String>,
+    /// Keep the password in the encrypted secrets vault; later connects to the
+    /// same `user@host:port` may omit it
+    #[serde(default)]
+    pub save_password: bool,
+    /// Bastions to traverse in order before reaching `host`
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHostRequest>,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct JumpHostRequest {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub auth_type: String, // "password", "key", or "agent"
+    pub password: Option<String>,
+    pub key_path: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct StatusResponse {
     pub connected: bool,
     pub host: Option<String>,
     pub username: Option<String>,
 }
 
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOp {
+    Mkdir {
+        path: String,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    Delete {
+        path: String,
+    },
+    /// `mode` is octal text, e.g. "755"
+    Chmod {
+        path: String,
+        mode: String,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    pub ops: Vec<BatchOp>,
+    #[serde(default)]
+    pub stop_on_error: bool,
+}
+
+#[derive(Serialize)]
+pub struct BatchResult {
+    index: usize,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BatchResponse {
+    results: Vec<BatchResult>,
+}
+
+#[derive(Serialize)]
+pub struct DiskUsage {
+    path: String,
+    /// Bytes
+    total: u64,
+    /// Bytes free (including root-reserved blocks), when known
+    free: Option<u64>,
+    /// Bytes available to the connected user
+    available: u64,
+    /// "statvfs" or "df"
+    source: &'static str,
+}
+
 // --- ヘルパー ---
 
-fn sftp_err(e: SftpError) -> ApiError {
+pub(super) fn sftp_err(e: SftpError) -> ApiError {
     match &e {
         SftpError::NotConnected => err(StatusCode::SERVICE_UNAVAILABLE, "Not connected to SFTP"),
         SftpError::AuthFailed => err(StatusCode::UNAUTHORIZED, "Authentication failed"),
@@ -75,8 +197,47 @@ fn sftp_err(e: SftpError) -> ApiError {
     }
 }
 
+/// Per-transfer rate limit wins (0 = explicitly unlimited); otherwise the
+/// Settings default applies.
+pub(super) fn effective_rate_limit(state: &AppState, requested: Option<u32>) -> Option<u32> {
+    requested.or_else(|| state.store.load_settings().transfer_rate_limit_kbps)
+}
+
+pub(super) fn transfer_err(e: &TransferError) -> ApiError {
+    match e {
+        TransferError::Cancelled => err(StatusCode::CONFLICT, "Transfer cancelled"),
+        // Local path outside the filer roots (`local_in_roots`)
+        TransferError::Io(ie) if ie.kind() == std::io::ErrorKind::PermissionDenied => {
+            err(StatusCode::FORBIDDEN, &ie.to_string())
+        }
+        TransferError::Io(ie) => err(StatusCode::BAD_GATEWAY, &format!("SFTP error: {ie}")),
+    }
+}
+
+/// Vault entry name prefix for saved SFTP passwords
+const SAVED_PASSWORD_PREFIX: &str = "sftp:";
+
+/// `user@host:port`
+fn saved_password_target(username: &str, host: &str, port: u16) -> String {
+    format!("{username}@{host}:{port}")
+}
+
+/// Build the auth method from the wire fields (shared by target and jump hosts).
+fn parse_auth(
+    auth_type: &str,
+    password: Option<String>,
+    key_path: Option<String>,
+) -> Result<SftpAuth, &'static str> {
+    match auth_type {
+        "password" => password.map(SftpAuth::Password).ok_or("Password required"),
+        "key" => key_path.map(SftpAuth::KeyFile).ok_or("Key path required"),
+        "agent" => Ok(SftpAuth::Agent),
+        _ => Err("auth_type must be 'password', 'key', or 'agent'"),
+    }
+}
+
 /// パス検証: null バイト拒否、空パス拒否
-fn validate_path(raw: &str) -> Result<String, ApiError> {
+pub(super) fn validate_path(raw: &str) -> Result<String, ApiError> {
     if raw.is_empty() {
         return Err(err(StatusCode::BAD_REQUEST, "Empty path"));
     }
@@ -87,7 +248,7 @@ fn validate_path(raw: &str) -> Result<String, ApiError> {
 }
 
 /// ~ をリモートホームに展開
-async fn expand_home(sftp: &SftpSession, raw: &str) -> Result<String, SftpError> {
+pub(super) async fn expand_home(sftp: &SftpSession, raw: &str) -> Result<String, SftpError> {
     if raw == "~" || raw.starts_with("~/") {
         let home = sftp.canonicalize(".").await?;
         if raw == "~" {
@@ -111,14 +272,14 @@ fn mtime_to_rfc3339(mtime: u32) -> String {
 
 // --- Host key types ---
 
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct ConnectErrorResponse {
     error: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     host_key: Option<HostKeyInfo>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct HostKeyInfo {
     host_port: String,
     fingerprint: String,
@@ -130,52 +291,79 @@ pub struct HostKeyInfo {
 type ConnectApiError = (StatusCode, Json<ConnectErrorResponse>);
 
 /// POST /api/sftp/connect
+#[utoipa::path(
+    post,
+    path = "/api/sftp/connect",
+    tag = "sftp",
+    summary = "Connect to an SSH host for SFTP",
+    request_body = ConnectRequest,
+    responses(
+        (status = 200, body = StatusResponse),
+        (status = 400, description = "Invalid request", body = ConnectErrorResponse),
+        (status = 409, description = "Unknown or changed host key; trust it with POST /api/sftp/known-hosts", body = ConnectErrorResponse),
+        (status = 502, description = "Connection or authentication failed", body = ConnectErrorResponse),
+    )
+)]
 pub async fn connect(
     State(state): State<Arc<AppState>>,
     Json(req): Json<ConnectRequest>,
 ) -> Result<Json<StatusResponse>, ConnectApiError> {
-    let auth = match req.auth_type.as_str() {
-        "password" => {
-            let pw = req.password.ok_or_else(|| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ConnectErrorResponse {
-                        error: "Password required".to_string(),
-                        host_key: None,
-                    }),
-                )
-            })?;
-            super::client::SftpAuth::Password(pw)
-        }
-        "key" => {
-            let path = req.key_path.ok_or_else(|| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ConnectErrorResponse {
-                        error: "Key path required".to_string(),
-                        host_key: None,
-                    }),
-                )
-            })?;
-            super::client::SftpAuth::KeyFile(path)
-        }
-        "agent" => super::client::SftpAuth::Agent,
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ConnectErrorResponse {
-                    error: "auth_type must be 'password', 'key', or 'agent'".to_string(),
-                    host_key: None,
-                }),
-            ));
-        }
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ConnectErrorResponse {
+                error,
+                host_key: None,
+            }),
+        )
     };
 
     let port = req.port.unwrap_or(22);
+    let target = saved_password_target(&req.username, &req.host, port);
+    let typed_password = req.password.filter(|p| !p.is_empty());
+    let password = match typed_password {
+        Some(ref p) => Some(p.clone()),
+        None if req.auth_type == "password" => {
+            let state = state.clone();
+            let name = format!("{SAVED_PASSWORD_PREFIX}{target}");
+            tokio::task::spawn_blocking(move || {
+                let key = state.vault_key()?;
+                state.store.get_secret(&key, &name)
+            })
+            .await
+            .unwrap_or(None)
+        }
+        None => None,
+    };
+    let auth = parse_auth(&req.auth_type, password, req.key_path)
+        .map_err(|e| bad_request(e.to_string()))?;
+
+    if req.jump_hosts.len() > MAX_JUMP_HOSTS {
+        return Err(bad_request(format!(
+            "Too many jump hosts (max {MAX_JUMP_HOSTS})"
+        )));
+    }
+    let mut jump_hosts = Vec::with_capacity(req.jump_hosts.len());
+    for (i, hop) in req.jump_hosts.into_iter().enumerate() {
+        if hop.host.is_empty() || hop.username.is_empty() {
+            return Err(bad_request(format!(
+                "Jump host {}: host and username required",
+                i + 1
+            )));
+        }
+        let auth = parse_auth(&hop.auth_type, hop.password, hop.key_path)
+            .map_err(|e| bad_request(format!("Jump host {}: {e}", i + 1)))?;
+        jump_hosts.push(SshHop {
+            host: hop.host,
+            port: hop.port.unwrap_or(22),
+            username: hop.username,
+            auth,
+        });
+    }
 
     if let Err(e) = state
         .sftp_manager
-        .connect(&req.host, port, &req.username, auth)
+        .connect_via(&req.host, port, &req.username, auth, jump_hosts)
         .await
     {
         return Err(match e {
@@ -226,6 +414,26 @@ pub async fn connect(
         });
     }
 
+    // 認証が通ったパスワードだけを保存する
+    if req.save_password
+        && let Some(password) = typed_password
+    {
+        let vault_state = state.clone();
+        let name = format!("{SAVED_PASSWORD_PREFIX}{target}");
+        match tokio::task::spawn_blocking(move || match vault_state.vault_key() {
+            Some(key) => vault_state.store.set_secret(&key, &name, &password),
+            None => Err(std::io::Error::other(
+                "secrets vault is unavailable (set DEN_VAULT_KEYFILE)",
+            )),
+        })
+        .await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("sftp: failed to save password for {target}: {e}"),
+            Err(e) => tracing::error!("sftp: save password task panicked: {e}"),
+        }
+    }
+
     let status = state.sftp_manager.status().await;
     Ok(Json(StatusResponse {
         connected: status.connected,
@@ -235,6 +443,15 @@ pub async fn connect(
 }
 
 /// GET /api/sftp/status
+#[utoipa::path(
+    get,
+    path = "/api/sftp/status",
+    tag = "sftp",
+    summary = "Current SFTP connection",
+    responses(
+        (status = 200, body = StatusResponse),
+    )
+)]
 pub async fn status(State(state): State<Arc<AppState>>) -> Json<StatusResponse> {
     let s = state.sftp_manager.status().await;
     Json(StatusResponse {
@@ -245,12 +462,35 @@ pub async fn status(State(state): State<Arc<AppState>>) -> Json<StatusResponse>
 }
 
 /// POST /api/sftp/disconnect
+#[utoipa::path(
+    post,
+    path = "/api/sftp/disconnect",
+    tag = "sftp",
+    summary = "Close the SFTP connection",
+    responses(
+        (status = 200, description = "Disconnected"),
+    )
+)]
 pub async fn disconnect(State(state): State<Arc<AppState>>) -> StatusCode {
     state.sftp_manager.disconnect().await;
     StatusCode::OK
 }
 
 /// GET /api/sftp/list
+#[tracing::instrument(name = "sftp.list", skip_all, fields(path = %q.path))]
+#[utoipa::path(
+    get,
+    path = "/api/sftp/list",
+    tag = "sftp",
+    summary = "List a remote directory",
+    params(crate::filer::api::ListQuery),
+    responses(
+        (status = 200, body = FilerListing),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn list(
     State(state): State<Arc<AppState>>,
     Query(q): Query<crate::filer::api::ListQuery>,
@@ -283,7 +523,12 @@ pub async fn list(
         let size = meta.size.unwrap_or(0);
         let modified = meta.mtime.map(mtime_to_rfc3339);
 
-        entries.push(FilerEntry::new(name, is_dir, size, modified));
+        let mut entry = FilerEntry::new(name, is_dir, size, modified).with_mode(meta.permissions);
+        if meta.is_symlink() {
+            // リンク先の解決は 1 エントリ 1 往復になるので一覧では行わない
+            entry = entry.with_link(None);
+        }
+        entries.push(entry);
     }
 
     entries.sort_by_cached_key(|e| (!e.is_dir(), e.name().to_lowercase()));
@@ -309,11 +554,45 @@ pub async fn list(
 }
 
 /// GET /api/sftp/read
+#[tracing::instrument(name = "sftp.read", skip_all, fields(path = %q.path))]
+#[utoipa::path(
+    get,
+    path = "/api/sftp/read",
+    tag = "sftp",
+    summary = "Read a remote text file",
+    params(ReadQuery),
+    responses(
+        (status = 200, body = FileContent),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn read(
     State(state): State<Arc<AppState>>,
     Query(q): Query<ReadQuery>,
 ) -> Result<Json<FileContent>, ApiError> {
-    let path = validate_path(&q.path)?;
+    let (path, data) = read_limited(&state, &q.path).await?;
+    let binary = is_binary(&data);
+
+    let content = if binary {
+        String::new()
+    } else {
+        String::from_utf8_lossy(&data).into_owned()
+    };
+    let mtime = remote_mtime(&state, &path).await;
+
+    Ok(Json(
+        FileContent::new(path, content, data.len() as u64, binary).with_version(&data, mtime),
+    ))
+}
+
+/// Read a remote file, enforcing the text read limit.
+pub(crate) async fn read_limited(
+    state: &AppState,
+    raw: &str,
+) -> Result<(String, Vec<u8>), ApiError> {
+    let path = validate_path(raw)?;
     let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
     let sftp = guard.sftp();
 
@@ -336,28 +615,39 @@ pub async fn read(
         .read(&path)
         .await
         .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
-    let binary = is_binary(&data);
-
-    let content = if binary {
-        String::new()
-    } else {
-        String::from_utf8_lossy(&data).into_owned()
-    };
-
-    Ok(Json(FileContent::new(
-        path,
-        content,
-        data.len() as u64,
-        binary,
-    )))
+    Ok((path, data))
 }
 
 /// PUT /api/sftp/write
+#[tracing::instrument(name = "sftp.write", skip_all, fields(path = %req.path))]
+#[utoipa::path(
+    put,
+    path = "/api/sftp/write",
+    tag = "sftp",
+    summary = "Write a remote text file",
+    request_body = WriteRequest,
+    responses(
+        (status = 200, body = WriteResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 409, description = "The file changed since it was read", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn write(
     State(state): State<Arc<AppState>>,
     Json(req): Json<WriteRequest>,
-) -> Result<StatusCode, ApiError> {
+) -> Result<Json<WriteResponse>, ApiError> {
     let path = validate_path(&req.path)?;
+    if req.if_match.is_some() {
+        let current = if remote_exists(&state, &path).await? {
+            Some(read_limited(&state, &path).await?.1)
+        } else {
+            None
+        };
+        check_if_match(req.if_match.as_deref(), current.as_deref())?;
+    }
+
     let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
     let sftp = guard.sftp();
 
@@ -365,10 +655,268 @@ pub async fn write(
     sftp.write(&path, req.content.as_bytes())
         .await
         .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    drop(guard);
+    let mtime = remote_mtime(&state, &path).await;
+    Ok(Json(WriteResponse::new(req.content.as_bytes(), mtime)))
+}
+
+async fn remote_exists(state: &AppState, path: &str) -> Result<bool, ApiError> {
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    match guard.sftp().metadata(path).await {
+        Ok(_) => Ok(true),
+        Err(russh_sftp::client::error::Error::Status(status))
+            if status.status_code == russh_sftp::protocol::StatusCode::NoSuchFile =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(sftp_err(SftpError::Sftp(e))),
+    }
+}
+
+/// 更新日時（取得できなければ None）
+async fn remote_mtime(state: &AppState, path: &str) -> Option<String> {
+    let guard = state.sftp_manager.get().await.ok()?;
+    let meta = guard.sftp().metadata(path).await.ok()?;
+    meta.mtime.map(mtime_to_rfc3339)
+}
+
+/// GET /api/sftp/tail
+///
+/// Without `offset`, returns the last `bytes` of the file. With `offset`,
+/// returns what was appended since (at most `bytes`, newest kept). A file
+/// shorter than `offset` is treated as rotated and read from the start.
+#[tracing::instrument(name = "sftp.tail", skip_all, fields(path = %q.path))]
+pub async fn tail(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<TailQuery>,
+) -> Result<Json<TailResponse>, ApiError> {
+    let path = validate_path(&q.path)?;
+    let limit = q
+        .bytes
+        .unwrap_or(DEFAULT_TAIL_BYTES)
+        .clamp(1, MAX_READ_SIZE);
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+
+    let meta = sftp
+        .metadata(&path)
+        .await
+        .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    if meta.is_dir() {
+        return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+    }
+    let size = meta.size.unwrap_or(0);
+    let (from, rotated) = match q.offset {
+        Some(o) if o <= size => (o, false),
+        Some(_) => (0, true),
+        None => (0, false),
+    };
+    let start = from.max(size.saturating_sub(limit));
+
+    let mut data = Vec::new();
+    if start < size {
+        let mut file = sftp
+            .open(&path)
+            .await
+            .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+        file.seek(std::io::SeekFrom::Start(start))
+            .await
+            .map_err(|e| sftp_err(SftpError::Io(e)))?;
+        (&mut file)
+            .take(size - start)
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| sftp_err(SftpError::Io(e)))?;
+    }
+    Ok(Json(TailResponse::new(path, &data, start, from, rotated)))
+}
+
+/// POST /api/sftp/append
+#[tracing::instrument(name = "sftp.append", skip_all, fields(path = %req.path))]
+pub async fn append(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<WriteRequest>,
+) -> Result<StatusCode, ApiError> {
+    let path = validate_path(&req.path)?;
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+
+    tracing::info!("sftp: append {} ({} bytes)", path, req.content.len());
+    let mut file = sftp
+        .open_with_flags(
+            &path,
+            OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::CREATE,
+        )
+        .await
+        .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    // Servers that ignore APPEND still honour an explicit offset
+    file.seek(std::io::SeekFrom::End(0))
+        .await
+        .map_err(|e| sftp_err(SftpError::Io(e)))?;
+    file.write_all(req.content.as_bytes())
+        .await
+        .map_err(|e| sftp_err(SftpError::Io(e)))?;
+    file.shutdown()
+        .await
+        .map_err(|e| sftp_err(SftpError::Io(e)))?;
     Ok(StatusCode::OK)
 }
 
+/// GET /api/sftp/statvfs
+#[tracing::instrument(name = "sftp.statvfs", skip_all, fields(path = %q.path))]
+pub async fn statvfs(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ReadQuery>,
+) -> Result<Json<DiskUsage>, ApiError> {
+    let raw_path = validate_path(&q.path)?;
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+    let path = expand_home(sftp, &raw_path).await.map_err(sftp_err)?;
+
+    match sftp.fs_info(path.as_str()).await {
+        Ok(Some(st)) => {
+            return Ok(Json(DiskUsage {
+                path,
+                total: st.blocks.saturating_mul(st.fragment_size),
+                free: Some(st.blocks_free.saturating_mul(st.fragment_size)),
+                available: st.blocks_avail.saturating_mul(st.fragment_size),
+                source: "statvfs",
+            }));
+        }
+        Ok(None) => {}
+        Err(e) => tracing::debug!("sftp: statvfs failed for {path}: {e}"),
+    }
+
+    if guard.exec_available() {
+        let out = guard
+            .exec(
+                &format!("df -Pk -- {}", shell_quote(&path)),
+                64 * 1024,
+                EXEC_DF_TIMEOUT,
+            )
+            .await;
+        match out {
+            Ok(out) if out.exit_status == Some(0) => {
+                if let Some((total, available)) = parse_df(&String::from_utf8_lossy(&out.stdout)) {
+                    return Ok(Json(DiskUsage {
+                        path,
+                        total,
+                        free: None,
+                        available,
+                        source: "df",
+                    }));
+                }
+            }
+            Ok(out) => tracing::debug!("sftp: df exited with {:?}", out.exit_status),
+            Err(e) => tracing::debug!("sftp: df unavailable: {e}"),
+        }
+    }
+    Err(err(
+        StatusCode::NOT_IMPLEMENTED,
+        "Disk usage not available on this server",
+    ))
+}
+
+/// GET /api/sftp/checksum
+///
+/// Runs `sha256sum`/`sha512sum` on the remote host when exec is allowed,
+/// otherwise streams the file through den and hashes it here.
+#[tracing::instrument(name = "sftp.checksum", skip_all, fields(path = %q.path))]
+pub async fn checksum(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ChecksumQuery>,
+) -> Result<Json<ChecksumResponse>, ApiError> {
+    let raw_path = validate_path(&q.path)?;
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+    let path = expand_home(sftp, &raw_path).await.map_err(sftp_err)?;
+
+    let meta = sftp
+        .metadata(&path)
+        .await
+        .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    if meta.is_dir() {
+        return Err(err(StatusCode::NOT_FOUND, "Not a file"));
+    }
+
+    if guard.exec_available() {
+        let command = format!("{} -- {}", q.algo.command(), shell_quote(&path));
+        match guard.exec(&command, 64 * 1024, EXEC_CHECKSUM_TIMEOUT).await {
+            Ok(out) if out.exit_status == Some(0) => {
+                if let Some(hash) = parse_checksum(&String::from_utf8_lossy(&out.stdout), q.algo) {
+                    return Ok(Json(ChecksumResponse::new(path, q.algo, hash, "exec")));
+                }
+            }
+            Ok(out) => tracing::debug!(
+                "sftp: {} exited with {:?}",
+                q.algo.command(),
+                out.exit_status
+            ),
+            Err(e) => tracing::debug!("sftp: exec checksum unavailable: {e}"),
+        }
+    }
+
+    let mut file = sftp
+        .open(&path)
+        .await
+        .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    let mut hasher = q.algo.hasher();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .await
+            .map_err(|e| sftp_err(SftpError::Io(e)))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(Json(ChecksumResponse::new(
+        path,
+        q.algo,
+        hasher.finish(),
+        "stream",
+    )))
+}
+
+/// First token of `sha*sum` output, if it looks like a digest of the right size.
+fn parse_checksum(output: &str, algo: ChecksumAlgo) -> Option<String> {
+    // sha*sum prefixes the line with '\\' when the file name needed escaping
+    let token = output.split_whitespace().next()?.trim_start_matches('\\');
+    (token.len() == algo.hex_len() && token.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then(|| token.to_ascii_lowercase())
+}
+
+/// Parse POSIX `df -Pk` output into (total, available) bytes.
+/// Anchors on the capacity column (`NN%`) so names with spaces still parse.
+fn parse_df(output: &str) -> Option<(u64, u64)> {
+    let line = output.lines().skip(1).find(|l| !l.trim().is_empty())?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let cap = fields.iter().rposition(|f| f.ends_with('%'))?;
+    if cap < 3 {
+        return None;
+    }
+    let total: u64 = fields[cap - 3].parse().ok()?;
+    let available: u64 = fields[cap - 1].parse().ok()?;
+    Some((total * 1024, available * 1024))
+}
+
 /// POST /api/sftp/mkdir
+#[tracing::instrument(name = "sftp.mkdir", skip_all, fields(path = %req.path))]
+#[utoipa::path(
+    post,
+    path = "/api/sftp/mkdir",
+    tag = "sftp",
+    summary = "Create a remote directory",
+    request_body = MkdirRequest,
+    responses(
+        (status = 201, description = "Created"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn mkdir(
     State(state): State<Arc<AppState>>,
     Json(req): Json<MkdirRequest>,
@@ -384,7 +932,56 @@ pub async fn mkdir(
     Ok(StatusCode::CREATED)
 }
 
+/// POST /api/sftp/create
+#[tracing::instrument(name = "sftp.create", skip_all, fields(path = %req.path))]
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateRequest>,
+) -> Result<(StatusCode, Json<CopyMoveResponse>), ApiError> {
+    let path = validate_path(&req.path)?;
+    let content = template_content(&state, req.template.as_deref())?;
+    // EXCLUDE の失敗は汎用 Failure で返るサーバーが多いので先に確認する
+    if remote_exists(&state, &path).await? {
+        return Err(err(StatusCode::CONFLICT, "File already exists"));
+    }
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+
+    tracing::info!("sftp: create {}", path);
+    let mut file = sftp
+        .open_with_flags(
+            &path,
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUDE,
+        )
+        .await
+        .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    file.write_all(content.as_bytes())
+        .await
+        .map_err(|e| sftp_err(SftpError::Io(e)))?;
+    file.shutdown()
+        .await
+        .map_err(|e| sftp_err(SftpError::Io(e)))?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CopyMoveResponse::new(std::path::Path::new(&path))),
+    ))
+}
+
 /// POST /api/sftp/rename
+#[tracing::instrument(name = "sftp.rename", skip_all, fields(from = %req.from, to = %req.to))]
+#[utoipa::path(
+    post,
+    path = "/api/sftp/rename",
+    tag = "sftp",
+    summary = "Rename a remote file or directory",
+    request_body = RenameRequest,
+    responses(
+        (status = 200, description = "Renamed"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn rename(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RenameRequest>,
@@ -402,27 +999,140 @@ pub async fn rename(
 }
 
 /// DELETE /api/sftp/delete
+#[tracing::instrument(name = "sftp.delete", skip_all, fields(path = %q.path))]
+#[utoipa::path(
+    delete,
+    path = "/api/sftp/delete",
+    tag = "sftp",
+    summary = "Delete a remote file or directory",
+    params(DeleteQuery),
+    responses(
+        (status = 200, description = "Deleted"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn delete(
     State(state): State<Arc<AppState>>,
     Query(q): Query<DeleteQuery>,
 ) -> Result<StatusCode, ApiError> {
+    // ゴミ箱はローカルのみ。黙って完全削除しないよう明示的に拒否する
+    if q.trash {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Trash is not supported for remote files",
+        ));
+    }
     let path = validate_path(&q.path)?;
     let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
     let sftp = guard.sftp();
 
     tracing::info!("sftp: delete {}", path);
-    let meta = sftp
-        .metadata(&path)
-        .await
-        .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
-    if meta.is_dir() {
-        remove_dir_recursive(sftp, &path).await.map_err(sftp_err)?;
+    delete_path(sftp, &path).await.map_err(sftp_err)?;
+    Ok(StatusCode::OK)
+}
+
+/// Delete a file, or a directory recursively.
+async fn delete_path(sftp: &SftpSession, path: &str) -> Result<(), SftpError> {
+    if sftp.metadata(path).await?.is_dir() {
+        remove_dir_recursive(sftp, path).await
     } else {
-        sftp.remove_file(&path)
-            .await
-            .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+        Ok(sftp.remove_file(path).await?)
+    }
+}
+
+/// POST /api/sftp/batch
+///
+/// Runs the operations in order on one connection and reports each result.
+/// Later operations still run after a failure unless `stop_on_error` is set.
+#[tracing::instrument(name = "sftp.batch", skip_all, fields(ops = req.ops.len()))]
+pub async fn batch(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BatchRequest>,
+) -> Result<Json<BatchResponse>, ApiError> {
+    if req.ops.is_empty() || req.ops.len() > MAX_BATCH_OPS {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Batch must contain 1-{MAX_BATCH_OPS} operations"),
+        ));
+    }
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+
+    let mut results = Vec::with_capacity(req.ops.len());
+    let mut failed = false;
+    for (index, op) in req.ops.iter().enumerate() {
+        if failed && req.stop_on_error {
+            results.push(BatchResult {
+                index,
+                ok: false,
+                error: Some("Skipped".to_string()),
+            });
+            continue;
+        }
+        let outcome = run_batch_op(sftp, op).await;
+        if let Err((_, Json(e))) = &outcome {
+            failed = true;
+            results.push(BatchResult {
+                index,
+                ok: false,
+                error: Some(e.message().to_string()),
+            });
+        } else {
+            results.push(BatchResult {
+                index,
+                ok: true,
+                error: None,
+            });
+        }
+    }
+    Ok(Json(BatchResponse { results }))
+}
+
+async fn run_batch_op(sftp: &SftpSession, op: &BatchOp) -> Result<(), ApiError> {
+    match op {
+        BatchOp::Mkdir { path } => {
+            let path = validate_path(path)?;
+            tracing::info!("sftp: batch mkdir {}", path);
+            sftp.create_dir(&path)
+                .await
+                .map_err(|e| sftp_err(SftpError::Sftp(e)))
+        }
+        BatchOp::Rename { from, to } => {
+            let from = validate_path(from)?;
+            let to = validate_path(to)?;
+            tracing::info!("sftp: batch rename {} -> {}", from, to);
+            sftp.rename(&from, &to)
+                .await
+                .map_err(|e| sftp_err(SftpError::Sftp(e)))
+        }
+        BatchOp::Delete { path } => {
+            let path = validate_path(path)?;
+            tracing::info!("sftp: batch delete {}", path);
+            delete_path(sftp, &path).await.map_err(sftp_err)
+        }
+        BatchOp::Chmod { path, mode } => {
+            let path = validate_path(path)?;
+            let mode = parse_mode(mode)
+                .ok_or_else(|| err(StatusCode::BAD_REQUEST, "Invalid mode (octal, e.g. 755)"))?;
+            tracing::info!("sftp: batch chmod {:o} {}", mode, path);
+            let mut attrs = russh_sftp::protocol::FileAttributes::empty();
+            attrs.permissions = Some(mode);
+            sftp.set_metadata(&path, attrs)
+                .await
+                .map_err(|e| sftp_err(SftpError::Sftp(e)))
+        }
+    }
+}
+
+/// Octal permission bits ("755", "0644"), limited to 0o7777.
+fn parse_mode(mode: &str) -> Option<u32> {
+    let mode = mode.trim();
+    if mode.is_empty() || mode.len() > 5 {
+        return None;
     }
-    Ok(StatusCode::OK)
+    u32::from_str_radix(mode, 8).ok().filter(|&m| m <= 0o7777)
 }
 
 /// SFTP に rm -rf がないため再帰削除
@@ -445,9 +1155,27 @@ async fn remove_dir_recursive(sftp: &SftpSession, path: &str) -> Result<(), Sftp
 }
 
 /// GET /api/sftp/download
+///
+/// Progress is published on `/api/events` under the transfer id (the optional
+/// `transfer_id` query parameter, or a generated one returned in `x-transfer-id`).
+#[tracing::instrument(name = "sftp.download", skip_all, fields(path = %q.path))]
+#[utoipa::path(
+    get,
+    path = "/api/sftp/download",
+    tag = "sftp",
+    summary = "Download a remote file",
+    params(DownloadQuery, ("transfer_id" = Option<String>, Query, description = "Id to report progress under on /api/events")),
+    responses(
+        (status = 200, description = "File contents", content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Not connected", body = ErrorResponse),
+        (status = 502, description = "SFTP error", body = ErrorResponse),
+    )
+)]
 pub async fn download(
     State(state): State<Arc<AppState>>,
     Query(q): Query<DownloadQuery>,
+    Query(t): Query<TransferIdQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     let path = validate_path(&q.path)?;
     let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
@@ -468,21 +1196,33 @@ pub async fn download(
         ));
     }
 
-    let data = sftp
-        .read(&path)
+    // Throttled reads must not hold the manager lock: use a channel of our own
+    let session = guard.open_transfer_session().await.map_err(sftp_err)?;
+    drop(guard);
+    let mut file = session
+        .open(&path)
         .await
         .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
+    let mut transfer = state.transfers.start(
+        t.transfer_id,
+        TransferDirection::Download,
+        &path,
+        Some(size),
+    );
+    transfer.set_rate_limit(effective_rate_limit(&state, t.rate_limit_kbps));
+    let mut data = Vec::with_capacity(size as usize);
+    let copied = transfer.copy(&mut file, &mut data).await;
+    drop(file);
+    let _ = session.close().await;
+    if let Err(e) = copied {
+        let api_err = transfer_err(&e);
+        transfer.fail(&e);
+        return Err(api_err);
+    }
+    let transfer_id = transfer.id().to_string();
+    transfer.finish();
 
-    let file_name = path.rsplit('/').next().unwrap_or("download").to_string();
-    let safe_name: String = file_name
-        .chars()
-        .filter(|c| c.is_ascii_alphanumeric() || *c == ' ' || *c == '.' || *c == '_' || *c == '-')
-        .collect();
-    let safe_name = if safe_name.is_empty() {
-        "download".to_string()
-    } else {
-        safe_name
-    };
+    let safe_name = attachment_name(path.rsplit('/').next().unwrap_or("download"));
 
     let mime = mime_guess::from_path(&path)
         .first_or_octet_stream()
@@ -495,17 +1235,251 @@ pub async fn download(
                 header::CONTENT_DISPOSITION,
                 format!("attachment; filename=\"{}\"", safe_name),
             ),
+            (TRANSFER_ID_HEADER.clone(), transfer_id),
         ],
         data,
     ))
 }
 
+/// One entry of a multi-file tar download.
+struct ArchiveItem {
+    /// Path inside the archive
+    name: String,
+    remote: String,
+    entry: CopyEntry,
+}
+
+/// GET /api/sftp/download-many?paths=["/a","/b"]
+///
+/// Streams the selected files and directory trees as one tar. Small files are
+/// prefetched a few at a time; large ones are streamed in place. A failure
+/// after the response has started aborts the body so the client sees an
+/// incomplete download rather than a silently truncated archive.
+#[tracing::instrument(name = "sftp.download_many", skip_all)]
+pub async fn download_many(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<DownloadManyQuery>,
+    Query(t): Query<TransferIdQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let paths: Vec<String> = serde_json::from_str(&q.paths).map_err(|_| {
+        err(
+            StatusCode::BAD_REQUEST,
+            "paths must be a JSON array of strings",
+        )
+    })?;
+    if paths.is_empty() || paths.len() > MAX_DOWNLOAD_MANY_PATHS {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Select 1-{MAX_DOWNLOAD_MANY_PATHS} paths"),
+        ));
+    }
+
+    let mut items = Vec::new();
+    {
+        let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+        let sftp = guard.sftp();
+        for raw in &paths {
+            let path = expand_home(sftp, &validate_path(raw)?)
+                .await
+                .map_err(sftp_err)?;
+            let base = path
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .filter(|s| !s.is_empty())
+                .unwrap_or("root")
+                .to_string();
+            for entry in collect_remote(sftp, &path).await.map_err(sftp_err)? {
+                items.push(ArchiveItem {
+                    name: if entry.rel.is_empty() {
+                        base.clone()
+                    } else {
+                        format!("{base}/{}", entry.rel)
+                    },
+                    remote: remote_join(&path, &entry.rel),
+                    entry,
+                });
+            }
+        }
+    }
+
+    let total = items
+        .iter()
+        .filter(|i| !i.entry.is_dir)
+        .map(|i| i.entry.size)
+        .sum();
+    let label = match paths.len() {
+        1 => paths[0].clone(),
+        n => format!("{} (+{} more)", paths[0], n - 1),
+    };
+    let mut transfer = state.transfers.start(
+        t.transfer_id,
+        TransferDirection::Download,
+        &label,
+        Some(total),
+    );
+    transfer.set_rate_limit(effective_rate_limit(&state, t.rate_limit_kbps));
+    let transfer_id = transfer.id().to_string();
+    tracing::info!(
+        "sftp: download-many {} ({} entries, {} bytes)",
+        label,
+        items.len(),
+        total
+    );
+
+    let (mut writer, reader) = tokio::io::duplex(ARCHIVE_PIPE_SIZE);
+    let failed = Arc::new(AtomicBool::new(false));
+    let task_failed = Arc::clone(&failed);
+    let task_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        match write_tar(&task_state, &items, &mut writer, &mut transfer).await {
+            Ok(()) => transfer.finish(),
+            Err(e) => {
+                tracing::warn!("sftp: download-many aborted: {e}");
+                task_failed.store(true, Ordering::Release);
+                transfer.fail(&e);
+            }
+        }
+    });
+
+    let body = futures::stream::unfold(Some(reader), move |reader| {
+        let failed = Arc::clone(&failed);
+        async move {
+            let mut reader = reader?;
+            let mut buf = vec![0u8; ARCHIVE_PIPE_SIZE];
+            match reader.read(&mut buf).await {
+                Ok(0) if failed.load(Ordering::Acquire) => {
+                    Some((Err(std::io::Error::other("archive aborted")), None))
+                }
+                Ok(0) => None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(bytes::Bytes::from(buf)), Some(reader)))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        }
+    });
+
+    let file_name = match q.name.as_deref() {
+        Some(name) => attachment_name(name),
+        None if paths.len() == 1 => attachment_name(label.rsplit('/').next().unwrap_or("")),
+        None => "download".to_string(),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}.tar\""),
+            ),
+            (TRANSFER_ID_HEADER.clone(), transfer_id),
+        ],
+        axum::body::Body::from_stream(body),
+    ))
+}
+
+/// Read small files up front so several requests are in flight at once.
+async fn prefetch<'a>(
+    sftp: &SftpSession,
+    item: &'a ArchiveItem,
+) -> Result<(&'a ArchiveItem, Option<Vec<u8>>), TransferError> {
+    let data = if !item.entry.is_dir && item.entry.size <= PREFETCH_MAX_SIZE {
+        Some(sftp.read(&item.remote).await.map_err(sftp_transfer_err)?)
+    } else {
+        None
+    };
+    Ok((item, data))
+}
+
+async fn write_tar(
+    state: &AppState,
+    items: &[ArchiveItem],
+    out: &mut tokio::io::DuplexStream,
+    transfer: &mut Transfer,
+) -> Result<(), TransferError> {
+    let session = async {
+        let guard = state.sftp_manager.get().await?;
+        guard.open_transfer_session().await
+    }
+    .await
+    .map_err(|e| TransferError::Io(std::io::Error::other(e.to_string())))?;
+    let result = write_tar_entries(&session, items, out, transfer).await;
+    let _ = session.close().await;
+    result
+}
+
+/// Body of [`write_tar`] on its own SFTP channel (no manager lock is held
+/// while the transfer is throttled).
+async fn write_tar_entries(
+    sftp: &SftpSession,
+    items: &[ArchiveItem],
+    out: &mut tokio::io::DuplexStream,
+    transfer: &mut Transfer,
+) -> Result<(), TransferError> {
+    // Futures are lazy; collecting them up front keeps the stream free of
+    // borrowing closures (which trip higher-ranked lifetime checks in spawn).
+    let pending: Vec<_> = items.iter().map(|item| prefetch(sftp, item)).collect();
+    let mut fetched = futures::stream::iter(pending).buffered(DOWNLOAD_MANY_CONCURRENCY);
+
+    while let Some(next) = fetched.next().await {
+        let (item, data) = next?;
+        let mtime = item.entry.mtime.unwrap_or(0);
+        if item.entry.is_dir {
+            out.write_all(&tar::header(
+                &item.name,
+                tar::EntryType::Dir,
+                0,
+                0o755,
+                mtime,
+            ))
+            .await?;
+            continue;
+        }
+        // The header promises `size` bytes, so a file that changed since the
+        // listing is cut or zero-filled to match.
+        let size = item.entry.size;
+        out.write_all(&tar::header(
+            &item.name,
+            tar::EntryType::File,
+            size,
+            0o644,
+            mtime,
+        ))
+        .await?;
+        let written = match data {
+            Some(data) => {
+                let data = &data[..data.len().min(size as usize)];
+                transfer.copy(&mut &data[..], out).await?
+            }
+            None => {
+                let file = sftp.open(&item.remote).await.map_err(sftp_transfer_err)?;
+                transfer.copy(&mut file.take(size), out).await?
+            }
+        };
+        if written < size {
+            tracing::debug!("sftp: {} shrank while archiving", item.remote);
+            out.write_all(&vec![0u8; (size - written) as usize]).await?;
+        }
+        out.write_all(&vec![0u8; tar::padding(size)]).await?;
+    }
+    out.write_all(&tar::end_of_archive()).await?;
+    out.shutdown().await?;
+    Ok(())
+}
+
 /// POST /api/sftp/upload (multipart)
+///
+/// The server → SFTP leg is tracked like downloads; an optional `transfer_id`
+/// field names the transfer.
+#[tracing::instrument(name = "sftp.upload", skip_all)]
 pub async fn upload(
     State(state): State<Arc<AppState>>,
     mut multipart: Multipart,
-) -> Result<StatusCode, ApiError> {
+) -> Result<impl IntoResponse, ApiError> {
     let mut target_path: Option<String> = None;
+    let mut transfer_id: Option<String> = None;
+    let mut rate_limit_kbps: Option<u32> = None;
     let mut file_data: Option<(String, Vec<u8>)> = None;
 
     while let Some(field) = multipart
@@ -523,6 +1497,12 @@ pub async fn upload(
                     )
                 })?);
             }
+            "transfer_id" => {
+                transfer_id = field.text().await.ok();
+            }
+            "rate_limit_kbps" => {
+                rate_limit_kbps = field.text().await.ok().and_then(|v| v.trim().parse().ok());
+            }
             "file" => {
                 let file_name = field.file_name().unwrap_or("upload").to_string();
                 let data = field.bytes().await.map_err(|e| {
@@ -568,20 +1548,58 @@ pub async fn upload(
 
     let resolved_dir = expand_home(sftp, &dir_path).await.map_err(sftp_err)?;
     let dest = format!("{}/{}", resolved_dir, file_name);
+    // Throttled writes must not hold the manager lock: use a channel of our own
+    let session = guard.open_transfer_session().await.map_err(sftp_err)?;
+    drop(guard);
+    let sftp = &session;
 
     tracing::info!("sftp: upload {} ({} bytes)", dest, data.len());
-    sftp.write(&dest, &data)
+    let mut file = sftp
+        .create(&dest)
         .await
         .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
-    Ok(StatusCode::CREATED)
+    let mut transfer = state.transfers.start(
+        transfer_id,
+        TransferDirection::Upload,
+        &dest,
+        Some(data.len() as u64),
+    );
+    transfer.set_rate_limit(effective_rate_limit(&state, rate_limit_kbps));
+    let result = match transfer.copy(&mut &data[..], &mut file).await {
+        Ok(_) => file.shutdown().await.map_err(TransferError::Io),
+        Err(e) => Err(e),
+    };
+    drop(file);
+    if let Err(e) = result {
+        let api_err = transfer_err(&e);
+        transfer.fail(&e);
+        // Don't leave a truncated file behind
+        let _ = sftp.remove_file(&dest).await;
+        let _ = session.close().await;
+        return Err(api_err);
+    }
+    let _ = session.close().await;
+    let transfer_id = transfer.id().to_string();
+    transfer.finish();
+    Ok((
+        StatusCode::CREATED,
+        [(TRANSFER_ID_HEADER.clone(), transfer_id)],
+    ))
 }
 
 /// GET /api/sftp/search
+#[tracing::instrument(name = "sftp.search", skip_all, fields(path = %q.path))]
 pub async fn search(
     State(state): State<Arc<AppState>>,
     Query(q): Query<SearchQuery>,
 ) -> Result<Json<Vec<SearchResult>>, ApiError> {
     let raw_path = validate_path(&q.path)?;
+    if q.mode != SearchMode::Substring {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "Only substring search is supported for remote files",
+        ));
+    }
     let query_lower = q.query.to_lowercase();
     let content_search = q.content;
     let show_hidden = q.show_hidden;
@@ -596,6 +1614,24 @@ pub async fn search(
         .await
         .map_err(|e| sftp_err(SftpError::Sftp(e)))?;
 
+    if guard.exec_available() {
+        match exec_search(
+            &guard,
+            &canonical,
+            &query_lower,
+            content_search,
+            show_hidden,
+        )
+        .await
+        {
+            Ok(results) => return Ok(Json(results)),
+            Err(e) => {
+                tracing::debug!("sftp: exec search unavailable, falling back to SFTP walk: {e}");
+                guard.disable_exec();
+            }
+        }
+    }
+
     let mut results = Vec::new();
     search_recursive(
         sftp,
@@ -610,6 +1646,128 @@ pub async fn search(
     Ok(Json(results))
 }
 
+/// Search by running `find` (and `grep` for content) on the remote host.
+///
+/// Mirrors `search_recursive`: same depth/result limits, hidden entries
+/// pruned unless requested, and content matches only for files whose name
+/// did not already match. Errors mean "exec not usable here" and the caller
+/// falls back to walking over SFTP.
+async fn exec_search(
+    guard: &SftpGuard<'_>,
+    dir: &str,
+    query: &str,
+    content_search: bool,
+    show_hidden: bool,
+) -> Result<Vec<SearchResult>, SftpError> {
+    let script = build_search_script(dir, query, content_search, show_hidden);
+    let out = guard
+        .exec(
+            &format!("sh -c {}", shell_quote(&script)),
+            MAX_EXEC_SEARCH_OUTPUT,
+            EXEC_SEARCH_TIMEOUT,
+        )
+        .await?;
+    match out.exit_status {
+        Some(0) | None => Ok(parse_search_output(
+            &String::from_utf8_lossy(&out.stdout),
+            query,
+        )),
+        Some(code) => Err(SftpError::Io(std::io::Error::other(format!(
+            "remote search exited with status {code}"
+        )))),
+    }
+}
+
+/// POSIX sh script printing `d\t<path>` / `f\t<path>` for name matches and
+/// `<path>:<line>:<text>` (grep format) for content matches.
+fn build_search_script(dir: &str, query: &str, content_search: bool, show_hidden: bool) -> String {
+    let dir = shell_quote(dir);
+    let max_depth = MAX_SEARCH_DEPTH + 1;
+    let prune = if show_hidden {
+        ""
+    } else {
+        "-name '.*' -prune -o "
+    };
+    let find = format!("find {dir} -mindepth 1 -maxdepth {max_depth} {prune}");
+    let pattern = shell_quote(&format!("*{}*", glob_escape(query)));
+
+    let mut script = String::from("command -v find >/dev/null 2>&1 || exit 127; ");
+    if content_search {
+        script.push_str("command -v grep >/dev/null 2>&1 || exit 127; ");
+    }
+    script.push_str("{ ");
+    script.push_str(&format!(
+        "{find}-iname {pattern} \\( -type d -exec printf 'd\\t%s\\n' {{}} + \
+         -o -exec printf 'f\\t%s\\n' {{}} + \\) 2>/dev/null; "
+    ));
+    if content_search {
+        script.push_str(&format!(
+            "{find}-type f -size -{}c -exec grep -HnIiF -e {} -- {{}} + 2>/dev/null; ",
+            MAX_READ_SIZE + 1,
+            shell_quote(query)
+        ));
+    }
+    script.push_str(&format!("}} | head -n {MAX_SEARCH_RESULTS}"));
+    script
+}
+
+/// Escape `find -name` glob metacharacters so the query matches literally.
+fn glob_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn parse_search_output(output: &str, query: &str) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    for line in output.lines() {
+        if results.len() >= MAX_SEARCH_RESULTS {
+            break;
+        }
+        if let Some(path) = line.strip_prefix("d\t") {
+            results.push(SearchResult::new(path.to_string(), true, None, None));
+        } else if let Some(path) = line.strip_prefix("f\t") {
+            results.push(SearchResult::new(path.to_string(), false, None, None));
+        } else if let Some((path, line_no, text)) = split_grep_line(line) {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            if name.to_lowercase().contains(query) {
+                continue;
+            }
+            results.push(SearchResult::new(
+                path.to_string(),
+                false,
+                Some(line_no),
+                Some(text.chars().take(200).collect()),
+            ));
+        }
+    }
+    results
+}
+
+/// Split `path:line:text`. Paths may contain ':', so the first `:<digits>:`
+/// is taken as the separator.
+fn split_grep_line(line: &str) -> Option<(&str, u32, &str)> {
+    let mut from = 0;
+    while let Some(i) = line[from..].find(':') {
+        let sep = from + i;
+        let rest = &line[sep + 1..];
+        if let Some(end) = rest.find(':')
+            && end > 0
+            && rest[..end].bytes().all(|b| b.is_ascii_digit())
+        {
+            let line_no = rest[..end].parse().ok()?;
+            return Some((&line[..sep], line_no, &rest[end + 1..]));
+        }
+        from = sep + 1;
+    }
+    None
+}
+
 async fn search_recursive(
     sftp: &SftpSession,
     dir: &str,
@@ -696,6 +1854,379 @@ async fn search_recursive(
     }
 }
 
+// --- Local ⇄ SFTP copy ---
+
+#[derive(Deserialize)]
+pub struct CopyRequest {
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    #[serde(default)]
+    pub transfer_id: Option<String>,
+    /// KB/s (0 = unlimited, absent = Settings default)
+    #[serde(default)]
+    pub rate_limit_kbps: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct CopyResponse {
+    id: String,
+    files: u64,
+    bytes: u64,
+}
+
+/// One file or directory of a copy, relative to the source root
+/// (`rel` is `/`-separated; empty = the root itself).
+pub(super) struct CopyEntry {
+    pub rel: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Modification time (unix seconds), when known
+    pub mtime: Option<u64>,
+}
+
+/// POST /api/transfer
+///
+/// Copies a file or directory tree between the local filesystem and the
+/// connected SFTP host entirely server-side. `local_path` / `remote_path` name
+/// the source and the destination itself (not its parent); existing files are
+/// overwritten. Progress is published on `/api/events`.
+#[tracing::instrument(name = "sftp.copy", skip_all, fields(direction = ?req.direction, remote_path = %req.remote_path))]
+pub async fn copy(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CopyRequest>,
+) -> Result<Json<CopyResponse>, ApiError> {
+    let remote_raw = validate_path(&req.remote_path)?;
+    let local = state.filer_roots.resolve(&req.local_path)?;
+
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+    let remote = expand_home(sftp, &remote_raw).await.map_err(sftp_err)?;
+
+    let (entries, source) = match req.direction {
+        TransferDirection::Upload => {
+            let root = local.clone();
+            let entries = tokio::task::spawn_blocking(move || collect_local(&root))
+                .await
+                .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+                .map_err(io_err)?;
+            (entries, local.to_string_lossy().into_owned())
+        }
+        TransferDirection::Download => {
+            let entries = collect_remote(sftp, &remote).await.map_err(sftp_err)?;
+            (entries, remote.clone())
+        }
+    };
+    // The copy itself runs on its own channel without the manager lock
+    let session = guard.open_transfer_session().await.map_err(sftp_err)?;
+    drop(guard);
+    let sftp = &session;
+
+    let total = entries.iter().map(|e| e.size).sum();
+    let mut transfer = state
+        .transfers
+        .start(req.transfer_id, req.direction, &source, Some(total));
+    transfer.set_rate_limit(effective_rate_limit(&state, req.rate_limit_kbps));
+    tracing::info!(
+        "sftp: copy {:?} {} <-> {} ({} entries, {} bytes)",
+        req.direction,
+        local.display(),
+        remote,
+        entries.len(),
+        total
+    );
+
+    let result = match req.direction {
+        TransferDirection::Upload => {
+            upload_entries(
+                sftp,
+                &state.filer_roots,
+                &local,
+                &remote,
+                &entries,
+                &mut transfer,
+            )
+            .await
+        }
+        TransferDirection::Download => {
+            download_entries(
+                sftp,
+                &state.filer_roots,
+                &remote,
+                &local,
+                &entries,
+                &mut transfer,
+            )
+            .await
+        }
+    };
+    let _ = session.close().await;
+    match result {
+        Ok(files) => {
+            let id = transfer.id().to_string();
+            let bytes = transfer.transferred();
+            transfer.finish();
+            Ok(Json(CopyResponse { id, files, bytes }))
+        }
+        Err(e) => {
+            let api_err = transfer_err(&e);
+            transfer.fail(&e);
+            Err(api_err)
+        }
+    }
+}
+
+/// Whether a directory entry name is one plain path component. A hostile
+/// server could list `..` or `a/../../x` to write outside a download's
+/// destination.
+fn is_safe_entry_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains(['/', '\\', '\0'])
+}
+
+/// `root` joined with the `/`-separated `rel`. Components that are not plain
+/// names are rejected, so the result always stays under `root`.
+pub(super) fn local_join(root: &std::path::Path, rel: &str) -> std::io::Result<std::path::PathBuf> {
+    rel.split('/')
+        .filter(|c| !c.is_empty())
+        .try_fold(root.to_path_buf(), |p, c| {
+            if is_safe_entry_name(c) {
+                Ok(p.join(c))
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unsafe path component in {rel:?}"),
+                ))
+            }
+        })
+}
+
+/// Local path for `rel` (a download target or an upload source), re-checked
+/// against the filer roots so a symlink inside the tree cannot lead out of them.
+pub(super) fn local_in_roots(
+    roots: &FilerRoots,
+    root: &std::path::Path,
+    rel: &str,
+) -> std::io::Result<std::path::PathBuf> {
+    let path = local_join(root, rel)?;
+    roots.resolve(&path.to_string_lossy()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is outside the allowed filer roots", path.display()),
+        )
+    })
+}
+
+pub(super) fn remote_join(root: &str, rel: &str) -> String {
+    if rel.is_empty() {
+        root.to_string()
+    } else {
+        format!("{}/{}", root.trim_end_matches('/'), rel)
+    }
+}
+
+pub(super) fn sftp_transfer_err(e: russh_sftp::client::error::Error) -> TransferError {
+    TransferError::Io(std::io::Error::other(e.to_string()))
+}
+
+fn local_mtime(meta: &std::fs::Metadata) -> Option<u64> {
+    meta.modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// Walk a local file/directory. Directory symlinks are not followed (no cycles).
+/// File symlinks are listed as files; uploads check their targets against the
+/// filer roots (`local_in_roots`) before reading them.
+pub(super) fn collect_local(root: &std::path::Path) -> std::io::Result<Vec<CopyEntry>> {
+    let meta = std::fs::metadata(root)?;
+    if !meta.is_dir() {
+        return Ok(vec![CopyEntry {
+            rel: String::new(),
+            is_dir: false,
+            size: meta.len(),
+            mtime: local_mtime(&meta),
+        }]);
+    }
+    let mut entries = vec![CopyEntry {
+        rel: String::new(),
+        is_dir: true,
+        size: 0,
+        mtime: None,
+    }];
+    collect_local_dir(root, "", &mut entries)?;
+    Ok(entries)
+}
+
+fn collect_local_dir(
+    dir: &std::path::Path,
+    prefix: &str,
+    entries: &mut Vec<CopyEntry>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rel = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}/{name}")
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            entries.push(CopyEntry {
+                rel: rel.clone(),
+                is_dir: true,
+                size: 0,
+                mtime: None,
+            });
+            collect_local_dir(&entry.path(), &rel, entries)?;
+        } else {
+            // Follows file symlinks; dangling links and symlinked dirs are skipped
+            match std::fs::metadata(entry.path()) {
+                Ok(m) if m.is_file() => entries.push(CopyEntry {
+                    rel,
+                    is_dir: false,
+                    size: m.len(),
+                    mtime: local_mtime(&m),
+                }),
+                _ => tracing::debug!("sftp: copy skips {}", entry.path().display()),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(super) async fn collect_remote(
+    sftp: &SftpSession,
+    root: &str,
+) -> Result<Vec<CopyEntry>, SftpError> {
+    let meta = sftp.metadata(root).await?;
+    if !meta.is_dir() {
+        return Ok(vec![CopyEntry {
+            rel: String::new(),
+            is_dir: false,
+            size: meta.size.unwrap_or(0),
+            mtime: meta.mtime.map(u64::from),
+        }]);
+    }
+    let mut entries = vec![CopyEntry {
+        rel: String::new(),
+        is_dir: true,
+        size: 0,
+        mtime: None,
+    }];
+    let mut pending = vec![String::new()];
+    while let Some(prefix) = pending.pop() {
+        for entry in sftp.read_dir(remote_join(root, &prefix)).await? {
+            let name = entry.file_name();
+            if !is_safe_entry_name(&name) {
+                if name != "." && name != ".." {
+                    tracing::warn!("sftp: skipping unsafe entry name {name:?} under {root}");
+                }
+                continue;
+            }
+            let rel = if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}/{name}")
+            };
+            let meta = entry.metadata();
+            if meta.is_dir() {
+                entries.push(CopyEntry {
+                    rel: rel.clone(),
+                    is_dir: true,
+                    size: 0,
+                    mtime: None,
+                });
+                pending.push(rel);
+            } else if meta.file_type().is_file() {
+                entries.push(CopyEntry {
+                    rel,
+                    is_dir: false,
+                    size: meta.size.unwrap_or(0),
+                    mtime: meta.mtime.map(u64::from),
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+async fn upload_entries(
+    sftp: &SftpSession,
+    roots: &FilerRoots,
+    local_root: &std::path::Path,
+    remote_root: &str,
+    entries: &[CopyEntry],
+    transfer: &mut Transfer,
+) -> Result<u64, TransferError> {
+    let mut files = 0;
+    for entry in entries {
+        let dest = remote_join(remote_root, &entry.rel);
+        if entry.is_dir {
+            if !sftp.try_exists(&dest).await.map_err(sftp_transfer_err)? {
+                sftp.create_dir(&dest).await.map_err(sftp_transfer_err)?;
+            }
+            continue;
+        }
+        let src = local_in_roots(roots, local_root, &entry.rel)?;
+        upload_file(sftp, &src, &dest, transfer).await?;
+        files += 1;
+    }
+    Ok(files)
+}
+
+pub(super) async fn upload_file(
+    sftp: &SftpSession,
+    src: &std::path::Path,
+    dest: &str,
+    transfer: &mut Transfer,
+) -> Result<(), TransferError> {
+    let mut reader = tokio::fs::File::open(src).await?;
+    let mut writer = sftp.create(dest).await.map_err(sftp_transfer_err)?;
+    transfer.copy(&mut reader, &mut writer).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+async fn download_entries(
+    sftp: &SftpSession,
+    roots: &FilerRoots,
+    remote_root: &str,
+    local_root: &std::path::Path,
+    entries: &[CopyEntry],
+    transfer: &mut Transfer,
+) -> Result<u64, TransferError> {
+    let mut files = 0;
+    for entry in entries {
+        let src = remote_join(remote_root, &entry.rel);
+        let dest = local_in_roots(roots, local_root, &entry.rel)?;
+        if entry.is_dir {
+            tokio::fs::create_dir_all(&dest).await?;
+            continue;
+        }
+        download_file(sftp, &src, &dest, transfer).await?;
+        files += 1;
+    }
+    Ok(files)
+}
+
+pub(super) async fn download_file(
+    sftp: &SftpSession,
+    src: &str,
+    dest: &std::path::Path,
+    transfer: &mut Transfer,
+) -> Result<(), TransferError> {
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut reader = sftp.open(src).await.map_err(sftp_transfer_err)?;
+    let mut writer = tokio::fs::File::create(dest).await?;
+    transfer.copy(&mut reader, &mut writer).await?;
+    Ok(())
+}
+
 // --- Known Hosts API ---
 
 #[derive(Deserialize)]
@@ -786,6 +2317,54 @@ pub async fn trust_host(
     Ok(StatusCode::OK)
 }
 
+/// GET /api/sftp/saved-passwords — `user@host:port` targets with a saved password
+pub async fn list_saved_passwords(State(state): State<Arc<AppState>>) -> Json<Vec<String>> {
+    let store = state.store.clone();
+    let names = tokio::task::spawn_blocking(move || store.secret_names(SAVED_PASSWORD_PREFIX))
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("sftp: list_saved_passwords spawn_blocking failed: {e}");
+            Vec::new()
+        });
+    Json(
+        names
+            .iter()
+            .filter_map(|name| name.strip_prefix(SAVED_PASSWORD_PREFIX))
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct SavedPasswordQuery {
+    /// `user@host:port`
+    pub target: String,
+}
+
+/// DELETE /api/sftp/saved-passwords?target=user@host:port
+pub async fn forget_saved_password(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<SavedPasswordQuery>,
+) -> Result<StatusCode, ApiError> {
+    let store = state.store.clone();
+    let name = format!("{SAVED_PASSWORD_PREFIX}{}", q.target);
+    let removed = tokio::task::spawn_blocking(move || store.remove_secret(&name))
+        .await
+        .map_err(|e| {
+            tracing::error!("sftp: forget_saved_password spawn_blocking failed: {e}");
+            err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+        })?
+        .map_err(|e| {
+            tracing::error!("sftp: forget_saved_password failed: {e}");
+            err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+        })?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(err(StatusCode::NOT_FOUND, "No saved password"))
+    }
+}
+
 /// DELETE /api/sftp/known-hosts
 pub async fn remove_known_host(
     State(state): State<Arc<AppState>>,
@@ -808,3 +2387,153 @@ pub async fn remove_known_host(
 
     Ok(StatusCode::OK)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_grep_line_handles_colons_in_path() {
+        assert_eq!(
+            split_grep_line("/srv/a:b/c.txt:12:foo: bar"),
+            Some(("/srv/a:b/c.txt", 12, "foo: bar"))
+        );
+        assert_eq!(split_grep_line("no separators"), None);
+    }
+
+    #[test]
+    fn parse_search_output_mixes_name_and_content_hits() {
+        let out = "d\t/srv/logs\nf\t/srv/logs.txt\n/srv/logs.txt:3:logs here\n/srv/app.rs:7:let logs = 1;\n";
+        let results: Vec<serde_json::Value> = parse_search_output(out, "logs")
+            .iter()
+            .map(|r| serde_json::to_value(r).unwrap())
+            .collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["is_dir"], true);
+        assert_eq!(results[1]["path"], "/srv/logs.txt");
+        // content hit in a file whose name already matched is dropped
+        assert_eq!(results[2]["path"], "/srv/app.rs");
+        assert_eq!(results[2]["line"], 7);
+    }
+
+    #[test]
+    fn search_script_quotes_user_input() {
+        let script = build_search_script("/srv/it's", "a*'b", true, false);
+        assert!(script.contains(r"find '/srv/it'\''s'"));
+        assert!(script.contains(r"-iname '*a\*'\''b*'"));
+        assert!(script.contains("-name '.*' -prune -o"));
+        assert!(script.ends_with("| head -n 100"));
+    }
+
+    #[test]
+    fn parse_checksum_output() {
+        let hash = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(
+            parse_checksum(&format!("{hash}  /tmp/empty\n"), ChecksumAlgo::Sha256),
+            Some(hash.to_string())
+        );
+        assert_eq!(
+            parse_checksum(&format!("\\{hash}  /tmp/a\\nb\n"), ChecksumAlgo::Sha256),
+            Some(hash.to_string())
+        );
+        assert_eq!(parse_checksum(hash, ChecksumAlgo::Sha512), None);
+        assert_eq!(parse_checksum("", ChecksumAlgo::Sha256), None);
+    }
+
+    #[test]
+    fn parse_mode_octal() {
+        assert_eq!(parse_mode("755"), Some(0o755));
+        assert_eq!(parse_mode("0644"), Some(0o644));
+        assert_eq!(parse_mode("4755"), Some(0o4755));
+        assert_eq!(parse_mode("888"), None);
+        assert_eq!(parse_mode("77777"), None);
+        assert_eq!(parse_mode(""), None);
+    }
+
+    #[test]
+    fn parse_df_posix_output() {
+        let out = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+                   /dev/sda1         41152736 20000000  19033208      52% /\n";
+        assert_eq!(parse_df(out), Some((41152736 * 1024, 19033208 * 1024)));
+        let spaced = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+                      //nas/My Share 100 40 60 40% /mnt/my share\n";
+        assert_eq!(parse_df(spaced), Some((100 * 1024, 60 * 1024)));
+        assert_eq!(parse_df("garbage"), None);
+    }
+
+    #[test]
+    fn glob_escape_metacharacters() {
+        assert_eq!(glob_escape("a*b?[c]"), r"a\*b\?\[c\]");
+    }
+
+    #[test]
+    fn remote_join_paths() {
+        assert_eq!(remote_join("/home/u", ""), "/home/u");
+        assert_eq!(remote_join("/home/u/", "a/b"), "/home/u/a/b");
+    }
+
+    #[test]
+    fn local_join_splits_on_slash() {
+        let root = std::path::Path::new("root");
+        assert_eq!(local_join(root, "").unwrap(), root.to_path_buf());
+        assert_eq!(local_join(root, "a/b").unwrap(), root.join("a").join("b"));
+    }
+
+    #[test]
+    fn local_join_rejects_hostile_names() {
+        let root = std::path::Path::new("root");
+        for rel in ["..", "a/../../etc", "a/./b", "..\\..\\x", "a\0b"] {
+            assert!(local_join(root, rel).is_err(), "{rel}");
+        }
+        assert!(!is_safe_entry_name(".."));
+        assert!(!is_safe_entry_name("a\\b"));
+        assert!(is_safe_entry_name("..hidden"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_in_roots_stays_inside_filer_roots() {
+        let allowed = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), allowed.path().join("link")).unwrap();
+        let roots = FilerRoots::new(&[allowed.path().to_string_lossy().into_owned()]);
+
+        assert!(local_in_roots(&roots, allowed.path(), "dir/new.txt").is_ok());
+        let e = local_in_roots(&roots, allowed.path(), "link/evil.txt").unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(local_in_roots(&roots, allowed.path(), "../evil.txt").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn upload_source_symlink_outside_roots_is_refused() {
+        let allowed = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("id_rsa"), "secret").unwrap();
+        std::fs::write(allowed.path().join("ok.txt"), "fine").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("id_rsa"), allowed.path().join("key"))
+            .unwrap();
+        let roots = FilerRoots::new(&[allowed.path().to_string_lossy().into_owned()]);
+
+        // The walk lists the link as a file; reading it is what gets checked
+        let entries = collect_local(allowed.path()).unwrap();
+        assert!(entries.iter().any(|e| e.rel == "key" && !e.is_dir));
+        assert!(local_in_roots(&roots, allowed.path(), "ok.txt").is_ok());
+        let e = local_in_roots(&roots, allowed.path(), "key").unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn collect_local_walks_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("a.txt"), "abc").unwrap();
+        std::fs::write(dir.path().join("sub").join("b.txt"), "de").unwrap();
+
+        let entries = collect_local(dir.path()).unwrap();
+        let mut rels: Vec<_> = entries.iter().map(|e| e.rel.as_str()).collect();
+        rels.sort();
+        assert_eq!(rels, ["", "a.txt", "sub", "sub/b.txt"]);
+        assert_eq!(entries.iter().map(|e| e.size).sum::<u64>(), 5);
+    }
+}
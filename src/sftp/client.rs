@@ -1,7 +1,10 @@
+use russh::ChannelMsg;
 use russh::keys::agent::client::AgentClient;
 use russh::keys::ssh_key;
 use russh_sftp::client::SftpSession;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tokio::sync::{Mutex, MutexGuard};
 
 use crate::store::Store;
@@ -105,6 +108,14 @@ pub enum SftpAuth {
     Agent,
 }
 
+/// One SSH hop: a jump host or the final target.
+pub struct SshHop {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth: SftpAuth,
+}
+
 // --- SSH Agent 接続 ---
 
 pub(crate) type DynAgentClient =
@@ -255,9 +266,20 @@ impl russh::client::Handler for SftpClientHandler {
 pub struct SftpConnection {
     pub sftp: SftpSession,
     handle: russh::client::Handle<SftpClientHandler>,
+    /// Jump host sessions carrying `handle`, outermost first
+    jumps: Vec<russh::client::Handle<SftpClientHandler>>,
     pub host: String,
     pub port: u16,
     pub username: String,
+    /// Set once the server refuses exec (SFTP-only accounts, no POSIX shell)
+    exec_disabled: AtomicBool,
+}
+
+/// Result of a remote command run over an exec channel.
+pub struct ExecOutput {
+    pub stdout: Vec<u8>,
+    /// None if the command did not finish (timeout / output cap reached)
+    pub exit_status: Option<u32>,
 }
 
 // --- SftpManager ---
@@ -302,133 +324,136 @@ impl SftpManager {
         port: u16,
         username: &str,
         auth: SftpAuth,
+    ) -> Result<(), SftpError> {
+        self.connect_via(host, port, username, auth, Vec::new())
+            .await
+    }
+
+    /// Connect through a chain of jump hosts (bastions). Each hop is reached
+    /// over a `direct-tcpip` channel of the previous one, and every hop's host
+    /// key is verified against the known-hosts store like a direct connection.
+    pub async fn connect_via(
+        &self,
+        host: &str,
+        port: u16,
+        username: &str,
+        auth: SftpAuth,
+        jump_hosts: Vec<SshHop>,
     ) -> Result<(), SftpError> {
         // 既存接続があれば切断
         self.disconnect().await;
 
-        let config = russh::client::Config {
+        let config = Arc::new(russh::client::Config {
             inactivity_timeout: Some(std::time::Duration::from_secs(300)),
             keepalive_interval: Some(std::time::Duration::from_secs(30)),
             keepalive_max: 5,
             ..Default::default()
-        };
+        });
 
-        let host_port = format_host_port(host, port);
-        let handler = SftpClientHandler {
-            host_port,
-            store: self.store.clone(),
+        let via: Vec<String> = jump_hosts
+            .iter()
+            .map(|h| format_host_port(&h.host, h.port))
+            .collect();
+        let target = SshHop {
+            host: host.to_string(),
+            port,
+            username: username.to_string(),
+            auth,
         };
 
-        let mut session = russh::client::connect(Arc::new(config), (host, port), handler)
-            .await
-            .map_err(|e| {
-                // Downcast to HostKeyStatus if this is a host key verification failure
-                if let Some(status) = e.downcast_ref::<HostKeyStatus>() {
-                    return match status {
-                        HostKeyStatus::Unknown {
-                            host_port,
-                            fingerprint,
-                            algorithm,
-                        } => SftpError::UnknownHostKey {
-                            host_port: host_port.clone(),
-                            fingerprint: fingerprint.clone(),
-                            algorithm: algorithm.clone(),
-                        },
-                        HostKeyStatus::Mismatch {
-                            host_port,
-                            fingerprint,
-                            algorithm,
-                            expected,
-                        } => SftpError::HostKeyMismatch {
-                            host_port: host_port.clone(),
-                            fingerprint: fingerprint.clone(),
-                            algorithm: algorithm.clone(),
-                            expected_fingerprint: expected.clone(),
-                        },
-                    };
-                }
-                SftpError::Ssh(russh::Error::IO(std::io::Error::other(e.to_string())))
-            })?;
-
-        // 認証
-        match auth {
-            SftpAuth::Password(password) => {
-                let auth_result = session.authenticate_password(username, &password).await?;
-                if !auth_result.success() {
-                    let _ = session
-                        .disconnect(russh::Disconnect::ByApplication, "", "")
-                        .await;
-                    return Err(SftpError::AuthFailed);
+        let mut jumps: Vec<russh::client::Handle<SftpClientHandler>> = Vec::new();
+        let mut session = None;
+        for hop in jump_hosts.into_iter().chain(std::iter::once(target)) {
+            let opened = match session.take() {
+                None => self.open_hop(&config, &hop, None).await,
+                Some(prev) => {
+                    let opened = self.open_hop(&config, &hop, Some(&prev)).await;
+                    jumps.push(prev);
+                    opened
                 }
-            }
-            SftpAuth::KeyFile(key_path) => {
-                let key_data = tokio::fs::read_to_string(&key_path).await?;
-                let key_pair = russh::keys::decode_secret_key(&key_data, None).map_err(|e| {
-                    SftpError::Io(std::io::Error::other(format!("Invalid key: {e}")))
-                })?;
-                let key_with_alg = russh::keys::PrivateKeyWithHashAlg::new(
-                    Arc::new(key_pair),
-                    None, // デフォルトのハッシュアルゴリズム
-                );
-                let auth_result = session
-                    .authenticate_publickey(username, key_with_alg)
-                    .await?;
-                if !auth_result.success() {
-                    let _ = session
-                        .disconnect(russh::Disconnect::ByApplication, "", "")
-                        .await;
-                    return Err(SftpError::AuthFailed);
+            };
+            match opened {
+                Ok(s) => session = Some(s),
+                Err(e) => {
+                    disconnect_all(jumps).await;
+                    return Err(e);
                 }
             }
-            SftpAuth::Agent => {
-                // Agent auth uses russh's Signer RPITIT which causes higher-ranked
-                // lifetime / Send issues with axum's Handler trait. We isolate the
-                // problematic future on a dedicated OS thread with its own single-thread
-                // runtime, avoiding both the Send requirement and blocking-thread-pool
-                // exhaustion that spawn_blocking would cause.
-                let username_owned = username.to_string();
-                let (tx, rx) = tokio::sync::oneshot::channel();
-                std::thread::spawn(move || {
-                    let rt = tokio::runtime::Builder::new_current_thread()
-                        .enable_all()
-                        .build();
-                    let result = match rt {
-                        Ok(rt) => rt.block_on(authenticate_agent(session, username_owned)),
-                        Err(e) => Err(SftpError::Io(e)),
-                    };
-                    let _ = tx.send(result);
-                });
-                session = tokio::time::timeout(std::time::Duration::from_secs(30), rx)
-                    .await
-                    .map_err(|_| {
-                        SftpError::Io(std::io::Error::other(
-                            "Agent auth timed out after 30 seconds",
-                        ))
-                    })?
-                    .map_err(|_| {
-                        SftpError::Io(std::io::Error::other("Agent auth thread panicked"))
-                    })??;
-            }
         }
+        let session = session.expect("target hop is always present");
 
         // SFTP サブシステムを開く
-        let channel = session.channel_open_session().await?;
-        channel.request_subsystem(true, "sftp").await?;
-        let sftp = SftpSession::new(channel.into_stream()).await?;
+        let sftp = match open_sftp(&session).await {
+            Ok(sftp) => sftp,
+            Err(e) => {
+                let _ = session
+                    .disconnect(russh::Disconnect::ByApplication, "", "")
+                    .await;
+                disconnect_all(jumps).await;
+                return Err(e);
+            }
+        };
 
         let connection = SftpConnection {
             sftp,
             handle: session,
+            jumps,
             host: host.to_string(),
             port,
             username: username.to_string(),
+            exec_disabled: AtomicBool::new(false),
         };
 
         *self.conn.lock().await = Some(connection);
-        tracing::info!("sftp: connected to {}@{}:{}", username, host, port);
+        if via.is_empty() {
+            tracing::info!("sftp: connected to {}@{}:{}", username, host, port);
+        } else {
+            tracing::info!(
+                "sftp: connected to {}@{}:{} via {}",
+                username,
+                host,
+                port,
+                via.join(" -> ")
+            );
+        }
         Ok(())
     }
 
+    /// Open and authenticate one hop, either directly over TCP or tunnelled
+    /// through `through` (the previous hop).
+    async fn open_hop(
+        &self,
+        config: &Arc<russh::client::Config>,
+        hop: &SshHop,
+        through: Option<&russh::client::Handle<SftpClientHandler>>,
+    ) -> Result<russh::client::Handle<SftpClientHandler>, SftpError> {
+        let handler = SftpClientHandler {
+            host_port: format_host_port(&hop.host, hop.port),
+            store: self.store.clone(),
+        };
+        let session = match through {
+            None => {
+                russh::client::connect(Arc::clone(config), (hop.host.as_str(), hop.port), handler)
+                    .await
+            }
+            Some(prev) => {
+                let channel = prev
+                    .channel_open_direct_tcpip(
+                        hop.host.clone(),
+                        u32::from(hop.port),
+                        "127.0.0.1",
+                        0,
+                    )
+                    .await?;
+                russh::client::connect_stream(Arc::clone(config), channel.into_stream(), handler)
+                    .await
+            }
+        }
+        .map_err(map_connect_error)?;
+
+        authenticate(session, &hop.username, &hop.auth).await
+    }
+
     /// 切断
     pub async fn disconnect(&self) {
         let mut guard = self.conn.lock().await;
@@ -438,6 +463,7 @@ impl SftpManager {
                 .handle
                 .disconnect(russh::Disconnect::ByApplication, "", "")
                 .await;
+            disconnect_all(conn.jumps).await;
             tracing::info!(
                 "sftp: disconnected from {}@{}:{}",
                 conn.username,
@@ -475,22 +501,220 @@ impl SftpManager {
     }
 }
 
+/// Downcast a russh connect error to host key status where applicable.
+fn map_connect_error(e: anyhow::Error) -> SftpError {
+    if let Some(status) = e.downcast_ref::<HostKeyStatus>() {
+        return match status {
+            HostKeyStatus::Unknown {
+                host_port,
+                fingerprint,
+                algorithm,
+            } => SftpError::UnknownHostKey {
+                host_port: host_port.clone(),
+                fingerprint: fingerprint.clone(),
+                algorithm: algorithm.clone(),
+            },
+            HostKeyStatus::Mismatch {
+                host_port,
+                fingerprint,
+                algorithm,
+                expected,
+            } => SftpError::HostKeyMismatch {
+                host_port: host_port.clone(),
+                fingerprint: fingerprint.clone(),
+                algorithm: algorithm.clone(),
+                expected_fingerprint: expected.clone(),
+            },
+        };
+    }
+    SftpError::Ssh(russh::Error::IO(std::io::Error::other(e.to_string())))
+}
+
+/// 認証（失敗時は session を切断してエラーを返す）
+async fn authenticate(
+    mut session: russh::client::Handle<SftpClientHandler>,
+    username: &str,
+    auth: &SftpAuth,
+) -> Result<russh::client::Handle<SftpClientHandler>, SftpError> {
+    match auth {
+        SftpAuth::Password(password) => {
+            let auth_result = session.authenticate_password(username, password).await?;
+            if !auth_result.success() {
+                let _ = session
+                    .disconnect(russh::Disconnect::ByApplication, "", "")
+                    .await;
+                return Err(SftpError::AuthFailed);
+            }
+        }
+        SftpAuth::KeyFile(key_path) => {
+            let key_data = tokio::fs::read_to_string(key_path).await?;
+            let key_pair = russh::keys::decode_secret_key(&key_data, None)
+                .map_err(|e| SftpError::Io(std::io::Error::other(format!("Invalid key: {e}"))))?;
+            let key_with_alg = russh::keys::PrivateKeyWithHashAlg::new(
+                Arc::new(key_pair),
+                None, // デフォルトのハッシュアルゴリズム
+            );
+            let auth_result = session
+                .authenticate_publickey(username, key_with_alg)
+                .await?;
+            if !auth_result.success() {
+                let _ = session
+                    .disconnect(russh::Disconnect::ByApplication, "", "")
+                    .await;
+                return Err(SftpError::AuthFailed);
+            }
+        }
+        SftpAuth::Agent => {
+            // Agent auth uses russh's Signer RPITIT which causes higher-ranked
+            // lifetime / Send issues with axum's Handler trait. We isolate the
+            // problematic future on a dedicated OS thread with its own single-thread
+            // runtime, avoiding both the Send requirement and blocking-thread-pool
+            // exhaustion that spawn_blocking would cause.
+            let username_owned = username.to_string();
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            std::thread::spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build();
+                let result = match rt {
+                    Ok(rt) => rt.block_on(authenticate_agent(session, username_owned)),
+                    Err(e) => Err(SftpError::Io(e)),
+                };
+                let _ = tx.send(result);
+            });
+            session = tokio::time::timeout(std::time::Duration::from_secs(30), rx)
+                .await
+                .map_err(|_| {
+                    SftpError::Io(std::io::Error::other(
+                        "Agent auth timed out after 30 seconds",
+                    ))
+                })?
+                .map_err(|_| {
+                    SftpError::Io(std::io::Error::other("Agent auth thread panicked"))
+                })??;
+        }
+    }
+    Ok(session)
+}
+
+async fn open_sftp(
+    session: &russh::client::Handle<SftpClientHandler>,
+) -> Result<SftpSession, SftpError> {
+    let channel = session.channel_open_session().await?;
+    channel.request_subsystem(true, "sftp").await?;
+    Ok(SftpSession::new(channel.into_stream()).await?)
+}
+
+/// Tear down jump host sessions, innermost first.
+async fn disconnect_all(jumps: Vec<russh::client::Handle<SftpClientHandler>>) {
+    for handle in jumps.into_iter().rev() {
+        let _ = handle
+            .disconnect(russh::Disconnect::ByApplication, "", "")
+            .await;
+    }
+}
+
 /// SFTP セッションへのアクセスを提供するガード型
 pub struct SftpGuard<'a> {
     guard: MutexGuard<'a, Option<SftpConnection>>,
 }
 
 impl SftpGuard<'_> {
-    pub fn sftp(&self) -> &SftpSession {
+    fn conn(&self) -> &SftpConnection {
         // get() で None チェック済み
-        &self.guard.as_ref().unwrap().sftp
+        self.guard.as_ref().unwrap()
+    }
+
+    pub fn sftp(&self) -> &SftpSession {
+        &self.conn().sftp
+    }
+
+    /// Open a separate SFTP channel on this connection. Long transfers run on
+    /// their own channel so the guard (and every other SFTP call) is free.
+    pub async fn open_transfer_session(&self) -> Result<SftpSession, SftpError> {
+        open_sftp(&self.conn().handle).await
+    }
+
+    /// Whether exec-based fast paths should be attempted on this connection.
+    pub fn exec_available(&self) -> bool {
+        !self.conn().exec_disabled.load(Ordering::Relaxed)
+    }
+
+    /// Stop trying exec on this connection (callers fall back to plain SFTP).
+    pub fn disable_exec(&self) {
+        self.conn().exec_disabled.store(true, Ordering::Relaxed);
     }
+
+    /// Run `command` on the remote host over a fresh exec channel.
+    ///
+    /// stderr is discarded. Collection stops at `max_output` bytes of stdout
+    /// or after `timeout`; in both cases the partial output is returned with
+    /// `exit_status: None`.
+    pub async fn exec(
+        &self,
+        command: &str,
+        max_output: usize,
+        timeout: Duration,
+    ) -> Result<ExecOutput, SftpError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut channel = self.conn().handle.channel_open_session().await?;
+        channel.exec(true, command).await?;
+
+        let mut stdout = Vec::new();
+        let mut exit_status = None;
+        loop {
+            let msg = match tokio::time::timeout_at(deadline, channel.wait()).await {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(_) => {
+                    exit_status = None;
+                    break;
+                }
+            };
+            match msg {
+                ChannelMsg::Failure => {
+                    let _ = channel.close().await;
+                    return Err(SftpError::Io(std::io::Error::other(
+                        "exec request refused by server",
+                    )));
+                }
+                ChannelMsg::Data { data } => {
+                    let room = max_output.saturating_sub(stdout.len());
+                    stdout.extend_from_slice(&data[..data.len().min(room)]);
+                    if stdout.len() >= max_output {
+                        exit_status = None;
+                        break;
+                    }
+                }
+                ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+                ChannelMsg::Close => break,
+                _ => {}
+            }
+        }
+        let _ = channel.close().await;
+        Ok(ExecOutput {
+            stdout,
+            exit_status,
+        })
+    }
+}
+
+/// Quote `s` as a single POSIX shell word.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$(rm -rf /)"), "'$(rm -rf /)'");
+    }
+
     #[test]
     fn format_host_port_ipv4() {
         assert_eq!(format_host_port("example.com", 22), "example.com:22");
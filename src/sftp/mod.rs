@@ -1,3 +1,5 @@
 // SFTP クライアント機能（リモートファイル操作）
 pub mod api;
 pub mod client;
+pub mod sync;
+pub mod transfer;
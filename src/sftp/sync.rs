@@ -0,0 +1,711 @@
+//! One-way directory mirror jobs between the local filesystem and the SFTP host.
+//!
+//! A job compares source and destination by size and mtime, copies new and
+//! changed files (preserving the source mtime so the next run sees them as
+//! unchanged) and optionally deletes destination files missing from the
+//! source. Jobs run on demand or on an interval while the SFTP connection
+//! they were created on is active.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::AppState;
+use crate::filer::api::{ErrorResponse, err, io_err};
+use crate::store::Store;
+
+use super::api::{
+    CopyEntry, collect_local, collect_remote, download_file, effective_rate_limit, expand_home,
+    local_in_roots, local_join, remote_join, sftp_err, transfer_err, upload_file, validate_path,
+};
+use super::client::SftpError;
+use super::transfer::{TransferDirection, TransferError};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+const MAX_SYNC_JOBS: usize = 50;
+const MAX_NAME_LEN: usize = 100;
+/// Scheduled interval bounds (minutes)
+const MIN_INTERVAL_MINUTES: u32 = 5;
+const MAX_INTERVAL_MINUTES: u32 = 7 * 24 * 60;
+/// How often the scheduler looks for due jobs
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+/// Max paths listed per category in a report (counts stay exact)
+const MAX_REPORTED_PATHS: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncJob {
+    pub id: String,
+    pub name: String,
+    /// Upload = local → SFTP, Download = SFTP → local
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    /// Remove destination entries that no longer exist in the source
+    #[serde(default)]
+    pub delete: bool,
+    /// Run automatically every N minutes
+    #[serde(default)]
+    pub interval_minutes: Option<u32>,
+    /// `user@host:port` of the SFTP connection this job targets
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub last_run: Option<SyncReport>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Unix timestamp in milliseconds
+    pub started_at: u64,
+    /// Unix timestamp in milliseconds
+    pub finished_at: u64,
+    pub dry_run: bool,
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub created_count: u64,
+    pub updated_count: u64,
+    pub deleted_count: u64,
+    pub unchanged: u64,
+    pub bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SyncReport {
+    fn record(list: &mut Vec<String>, count: &mut u64, rel: &str) {
+        *count += 1;
+        if list.len() < MAX_REPORTED_PATHS {
+            list.push(if rel.is_empty() {
+                ".".to_string()
+            } else {
+                rel.to_string()
+            });
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SyncJobRequest {
+    pub name: String,
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    #[serde(default)]
+    pub delete: bool,
+    #[serde(default)]
+    pub interval_minutes: Option<u32>,
+    /// Defaults to the current SFTP connection
+    #[serde(default)]
+    pub host: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RunQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Persisted job list plus the set of jobs currently running.
+#[derive(Clone)]
+pub struct SyncManager {
+    store: Store,
+    jobs: Arc<std::sync::Mutex<Vec<SyncJob>>>,
+    running: Arc<std::sync::Mutex<HashSet<String>>>,
+}
+
+impl SyncManager {
+    pub fn new(store: Store) -> Self {
+        let jobs = store.load_sync_jobs();
+        Self {
+            store,
+            jobs: Arc::new(std::sync::Mutex::new(jobs)),
+            running: Arc::new(std::sync::Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn list(&self) -> Vec<SyncJob> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<SyncJob> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|j| j.id == id)
+            .cloned()
+    }
+
+    /// Apply `f` to the job list and persist the result.
+    fn update<T>(&self, f: impl FnOnce(&mut Vec<SyncJob>) -> T) -> std::io::Result<T> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let result = f(&mut jobs);
+        self.store.save_sync_jobs(&jobs)?;
+        Ok(result)
+    }
+
+    /// Mark a job as running; false if it already is.
+    fn try_begin(&self, id: &str) -> bool {
+        self.running.lock().unwrap().insert(id.to_string())
+    }
+
+    fn end(&self, id: &str) {
+        self.running.lock().unwrap().remove(id);
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// `user@host:port` of the active connection, if any.
+async fn current_host(state: &AppState) -> Option<String> {
+    let status = state.sftp_manager.status().await;
+    match (status.username, status.host) {
+        (Some(user), Some(host)) if status.connected => Some(format!("{user}@{host}")),
+        _ => None,
+    }
+}
+
+fn validate_request(req: &SyncJobRequest) -> Result<(), ApiError> {
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Name must be 1-{MAX_NAME_LEN} characters"),
+        ));
+    }
+    if req.local_path.trim().is_empty() || req.remote_path.trim().is_empty() {
+        return Err(err(StatusCode::BAD_REQUEST, "Paths must not be empty"));
+    }
+    if let Some(m) = req.interval_minutes
+        && !(MIN_INTERVAL_MINUTES..=MAX_INTERVAL_MINUTES).contains(&m)
+    {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("Interval must be {MIN_INTERVAL_MINUTES}-{MAX_INTERVAL_MINUTES} minutes"),
+        ));
+    }
+    Ok(())
+}
+
+// --- Handlers ---
+
+/// GET /api/sync-jobs
+pub async fn list(State(state): State<Arc<AppState>>) -> Json<Vec<SyncJob>> {
+    Json(state.sync_jobs.list())
+}
+
+/// POST /api/sync-jobs
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SyncJobRequest>,
+) -> Result<(StatusCode, Json<SyncJob>), ApiError> {
+    validate_request(&req)?;
+    let host = match req.host {
+        Some(h) => Some(h),
+        None => current_host(&state).await,
+    };
+    let job = SyncJob {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: req.name.trim().to_string(),
+        direction: req.direction,
+        local_path: req.local_path,
+        remote_path: req.remote_path,
+        delete: req.delete,
+        interval_minutes: req.interval_minutes,
+        host,
+        last_run: None,
+    };
+    let created = job.clone();
+    let added = state
+        .sync_jobs
+        .update(move |jobs| {
+            if jobs.len() >= MAX_SYNC_JOBS {
+                return false;
+            }
+            jobs.push(job);
+            true
+        })
+        .map_err(io_err)?;
+    if !added {
+        return Err(err(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            &format!("Too many sync jobs (max {MAX_SYNC_JOBS})"),
+        ));
+    }
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// PUT /api/sync-jobs/{id}
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<SyncJobRequest>,
+) -> Result<Json<SyncJob>, ApiError> {
+    validate_request(&req)?;
+    let updated = state
+        .sync_jobs
+        .update(|jobs| {
+            let job = jobs.iter_mut().find(|j| j.id == id)?;
+            job.name = req.name.trim().to_string();
+            job.direction = req.direction;
+            job.local_path = req.local_path;
+            job.remote_path = req.remote_path;
+            job.delete = req.delete;
+            job.interval_minutes = req.interval_minutes;
+            if req.host.is_some() {
+                job.host = req.host;
+            }
+            Some(job.clone())
+        })
+        .map_err(io_err)?;
+    updated
+        .map(Json)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Sync job not found"))
+}
+
+/// DELETE /api/sync-jobs/{id}
+pub async fn remove(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let removed = state
+        .sync_jobs
+        .update(|jobs| {
+            let before = jobs.len();
+            jobs.retain(|j| j.id != id);
+            jobs.len() != before
+        })
+        .map_err(io_err)?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(err(StatusCode::NOT_FOUND, "Sync job not found"))
+    }
+}
+
+/// POST /api/sync-jobs/{id}/run
+pub async fn run(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<RunQuery>,
+) -> Result<Json<SyncReport>, ApiError> {
+    let job = state
+        .sync_jobs
+        .get(&id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Sync job not found"))?;
+    run_job(&state, &job, q.dry_run).await.map(Json)
+}
+
+/// Run a job and record its report (dry runs are not recorded).
+async fn run_job(state: &AppState, job: &SyncJob, dry_run: bool) -> Result<SyncReport, ApiError> {
+    if let Some(host) = &job.host {
+        match current_host(state).await {
+            Some(current) if &current == host => {}
+            Some(_) => {
+                return Err(err(
+                    StatusCode::CONFLICT,
+                    &format!("Sync job targets {host}; connected to a different host"),
+                ));
+            }
+            None => return Err(sftp_err(SftpError::NotConnected)),
+        }
+    }
+    if !state.sync_jobs.try_begin(&job.id) {
+        return Err(err(StatusCode::CONFLICT, "Sync job already running"));
+    }
+
+    let mut report = SyncReport {
+        started_at: now_millis(),
+        dry_run,
+        ..SyncReport::default()
+    };
+    let result = execute(state, job, dry_run, &mut report).await;
+    state.sync_jobs.end(&job.id);
+    report.finished_at = now_millis();
+    if let Err((_, Json(e))) = &result {
+        report.error = Some(e.message().to_string());
+    }
+    tracing::info!(
+        "sync: job '{}' {} created, {} updated, {} deleted, {} unchanged{}",
+        job.name,
+        report.created_count,
+        report.updated_count,
+        report.deleted_count,
+        report.unchanged,
+        if dry_run { " (dry run)" } else { "" }
+    );
+
+    if !dry_run {
+        let saved = report.clone();
+        let id = job.id.clone();
+        let persisted = state.sync_jobs.update(move |jobs| {
+            if let Some(j) = jobs.iter_mut().find(|j| j.id == id) {
+                j.last_run = Some(saved);
+            }
+        });
+        if let Err(e) = persisted {
+            tracing::warn!("sync: failed to save report: {e}");
+        }
+    }
+    result.map(|()| report)
+}
+
+/// What to do with one source entry.
+fn plan(src: &CopyEntry, dest: Option<&CopyEntry>) -> Result<Action, String> {
+    match dest {
+        None => Ok(Action::Create),
+        Some(d) if d.is_dir != src.is_dir => Err(format!(
+            "Type conflict at '{}' (file vs directory)",
+            if src.rel.is_empty() { "." } else { &src.rel }
+        )),
+        Some(_) if src.is_dir => Ok(Action::Skip),
+        Some(d) if d.size != src.size => Ok(Action::Update),
+        Some(d) if src.mtime.is_some() && d.mtime != src.mtime => Ok(Action::Update),
+        Some(_) => Ok(Action::Skip),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Create,
+    Update,
+    Skip,
+}
+
+async fn execute(
+    state: &AppState,
+    job: &SyncJob,
+    dry_run: bool,
+    report: &mut SyncReport,
+) -> Result<(), ApiError> {
+    let remote_raw = validate_path(&job.remote_path)?;
+    let local = state.filer_roots.resolve(&job.local_path)?;
+
+    let guard = state.sftp_manager.get().await.map_err(sftp_err)?;
+    let sftp = guard.sftp();
+    let remote = expand_home(sftp, &remote_raw).await.map_err(sftp_err)?;
+
+    let local_entries = {
+        let root = local.clone();
+        tokio::task::spawn_blocking(move || {
+            if root.exists() {
+                collect_local(&root)
+            } else {
+                Ok(Vec::new())
+            }
+        })
+        .await
+        .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))?
+        .map_err(io_err)?
+    };
+    let remote_entries = if sftp
+        .try_exists(&remote)
+        .await
+        .map_err(|e| sftp_err(SftpError::Sftp(e)))?
+    {
+        collect_remote(sftp, &remote).await.map_err(sftp_err)?
+    } else {
+        Vec::new()
+    };
+
+    let (source, dest) = match job.direction {
+        TransferDirection::Upload => (local_entries, remote_entries),
+        TransferDirection::Download => (remote_entries, local_entries),
+    };
+    if source.is_empty() {
+        return Err(err(StatusCode::NOT_FOUND, "Sync source does not exist"));
+    }
+    let dest_by_rel: HashMap<&str, &CopyEntry> = dest.iter().map(|e| (e.rel.as_str(), e)).collect();
+
+    let mut work = Vec::new();
+    for entry in &source {
+        let action = plan(entry, dest_by_rel.get(entry.rel.as_str()).copied())
+            .map_err(|m| err(StatusCode::CONFLICT, &m))?;
+        match action {
+            Action::Skip if !entry.is_dir => report.unchanged += 1,
+            Action::Skip => {}
+            _ => work.push((entry, action)),
+        }
+    }
+    let source_rels: HashSet<&str> = source.iter().map(|e| e.rel.as_str()).collect();
+    let mut stale: Vec<&CopyEntry> = if job.delete {
+        dest.iter()
+            .filter(|e| !source_rels.contains(e.rel.as_str()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    // Children before parents
+    stale.sort_by_key(|e| std::cmp::Reverse(e.rel.matches('/').count()));
+
+    if dry_run {
+        for (entry, action) in &work {
+            if entry.is_dir {
+                continue;
+            }
+            report.bytes += entry.size;
+            match action {
+                Action::Create => {
+                    SyncReport::record(&mut report.created, &mut report.created_count, &entry.rel)
+                }
+                _ => SyncReport::record(&mut report.updated, &mut report.updated_count, &entry.rel),
+            }
+        }
+        for entry in stale.iter().filter(|e| !e.is_dir) {
+            SyncReport::record(&mut report.deleted, &mut report.deleted_count, &entry.rel);
+        }
+        return Ok(());
+    }
+
+    let total = work
+        .iter()
+        .filter(|(e, _)| !e.is_dir)
+        .map(|(e, _)| e.size)
+        .sum();
+    let source_root = match job.direction {
+        TransferDirection::Upload => local.to_string_lossy().into_owned(),
+        TransferDirection::Download => remote.clone(),
+    };
+    // Throttled copies must not hold the manager lock: use a channel of our own
+    let session = guard.open_transfer_session().await.map_err(sftp_err)?;
+    drop(guard);
+    let sftp = &session;
+    let mut transfer = state
+        .transfers
+        .start(None, job.direction, &source_root, Some(total));
+    transfer.set_rate_limit(effective_rate_limit(state, None));
+
+    let copied: Result<(), TransferError> = async {
+        for (entry, action) in &work {
+            // Upload sources too: a file symlink may point outside the roots
+            let local_path = local_in_roots(&state.filer_roots, &local, &entry.rel)?;
+            let remote_path = remote_join(&remote, &entry.rel);
+            match (job.direction, entry.is_dir) {
+                (TransferDirection::Upload, true) => {
+                    sftp.create_dir(&remote_path)
+                        .await
+                        .map_err(super::api::sftp_transfer_err)?;
+                }
+                (TransferDirection::Download, true) => {
+                    tokio::fs::create_dir_all(&local_path).await?;
+                }
+                (TransferDirection::Upload, false) => {
+                    upload_file(sftp, &local_path, &remote_path, &mut transfer).await?;
+                    if let Some(mtime) = entry.mtime {
+                        let mut attrs = russh_sftp::protocol::FileAttributes::empty();
+                        attrs.atime = Some(mtime as u32);
+                        attrs.mtime = Some(mtime as u32);
+                        if let Err(e) = sftp.set_metadata(&remote_path, attrs).await {
+                            tracing::debug!("sync: set mtime failed for {remote_path}: {e}");
+                        }
+                    }
+                }
+                (TransferDirection::Download, false) => {
+                    download_file(sftp, &remote_path, &local_path, &mut transfer).await?;
+                    if let Some(mtime) = entry.mtime {
+                        let time = std::time::UNIX_EPOCH + Duration::from_secs(mtime);
+                        let set = std::fs::File::options()
+                            .write(true)
+                            .open(&local_path)
+                            .and_then(|f| f.set_modified(time));
+                        if let Err(e) = set {
+                            tracing::debug!(
+                                "sync: set mtime failed for {}: {e}",
+                                local_path.display()
+                            );
+                        }
+                    }
+                }
+            }
+            if entry.is_dir {
+                continue;
+            }
+            match action {
+                Action::Create => {
+                    SyncReport::record(&mut report.created, &mut report.created_count, &entry.rel)
+                }
+                _ => SyncReport::record(&mut report.updated, &mut report.updated_count, &entry.rel),
+            }
+        }
+
+        for entry in &stale {
+            match job.direction {
+                TransferDirection::Upload => {
+                    let path = remote_join(&remote, &entry.rel);
+                    if entry.is_dir {
+                        sftp.remove_dir(&path).await
+                    } else {
+                        sftp.remove_file(&path).await
+                    }
+                    .map_err(super::api::sftp_transfer_err)?;
+                }
+                TransferDirection::Download => {
+                    let path = local_join(&local, &entry.rel)?;
+                    if entry.is_dir {
+                        tokio::fs::remove_dir(&path).await?;
+                    } else {
+                        tokio::fs::remove_file(&path).await?;
+                    }
+                }
+            }
+            if !entry.is_dir {
+                SyncReport::record(&mut report.deleted, &mut report.deleted_count, &entry.rel);
+            }
+        }
+        Ok(())
+    }
+    .await;
+    let _ = session.close().await;
+
+    report.bytes = transfer.transferred();
+    match copied {
+        Ok(()) => {
+            transfer.finish();
+            Ok(())
+        }
+        Err(e) => {
+            let api_err = transfer_err(&e);
+            transfer.fail(&e);
+            Err(api_err)
+        }
+    }
+}
+
+// --- Scheduler ---
+
+/// Whether a scheduled job should run now.
+fn is_due(job: &SyncJob, now: u64) -> bool {
+    let Some(minutes) = job.interval_minutes else {
+        return false;
+    };
+    match &job.last_run {
+        Some(r) => now.saturating_sub(r.started_at) >= u64::from(minutes) * 60_000,
+        None => true,
+    }
+}
+
+/// Background loop running due jobs whose host matches the live connection.
+pub fn spawn_scheduler(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(SCHEDULER_TICK);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tick.tick().await;
+            let Some(host) = current_host(&state).await else {
+                continue;
+            };
+            let now = now_millis();
+            let due: Vec<SyncJob> = state
+                .sync_jobs
+                .list()
+                .into_iter()
+                .filter(|j| j.host.as_deref() == Some(host.as_str()) && is_due(j, now))
+                .collect();
+            for job in due {
+                if let Err((status, Json(e))) = run_job(&state, &job, false).await {
+                    tracing::warn!(
+                        "sync: scheduled job '{}' failed ({status}): {}",
+                        job.name,
+                        e.message()
+                    );
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rel: &str, is_dir: bool, size: u64, mtime: Option<u64>) -> CopyEntry {
+        CopyEntry {
+            rel: rel.to_string(),
+            is_dir,
+            size,
+            mtime,
+        }
+    }
+
+    #[test]
+    fn plan_compares_size_and_mtime() {
+        let src = entry("a", false, 3, Some(100));
+        assert_eq!(plan(&src, None), Ok(Action::Create));
+        assert_eq!(
+            plan(&src, Some(&entry("a", false, 3, Some(100)))),
+            Ok(Action::Skip)
+        );
+        assert_eq!(
+            plan(&src, Some(&entry("a", false, 4, Some(100)))),
+            Ok(Action::Update)
+        );
+        assert_eq!(
+            plan(&src, Some(&entry("a", false, 3, Some(99)))),
+            Ok(Action::Update)
+        );
+        assert!(plan(&src, Some(&entry("a", true, 0, None))).is_err());
+    }
+
+    #[test]
+    fn due_only_with_interval() {
+        let mut job = SyncJob {
+            id: "j".to_string(),
+            name: "dotfiles".to_string(),
+            direction: TransferDirection::Upload,
+            local_path: "/tmp/a".to_string(),
+            remote_path: "/tmp/b".to_string(),
+            delete: false,
+            interval_minutes: None,
+            host: None,
+            last_run: None,
+        };
+        assert!(!is_due(&job, 1_000_000));
+        job.interval_minutes = Some(5);
+        assert!(is_due(&job, 1_000_000));
+        job.last_run = Some(SyncReport {
+            started_at: 1_000_000,
+            ..SyncReport::default()
+        });
+        assert!(!is_due(&job, 1_000_000 + 4 * 60_000));
+        assert!(is_due(&job, 1_000_000 + 5 * 60_000));
+    }
+
+    #[test]
+    fn manager_persists_jobs() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let store = Store::new(tmp.path().to_path_buf()).unwrap();
+        let mgr = SyncManager::new(store.clone());
+        mgr.update(|jobs| {
+            jobs.push(SyncJob {
+                id: "j1".to_string(),
+                name: "n".to_string(),
+                direction: TransferDirection::Download,
+                local_path: "/l".to_string(),
+                remote_path: "/r".to_string(),
+                delete: true,
+                interval_minutes: Some(10),
+                host: Some("u@h:22".to_string()),
+                last_run: None,
+            })
+        })
+        .unwrap();
+        let reloaded = SyncManager::new(store);
+        assert_eq!(reloaded.list().len(), 1);
+        assert!(reloaded.get("j1").unwrap().delete);
+        assert!(reloaded.try_begin("j1"));
+        assert!(!reloaded.try_begin("j1"));
+    }
+}
@@ -0,0 +1,425 @@
+//! Tracked SFTP transfers: progress events and cancellation.
+//!
+//! Each upload/download gets an id (client-supplied or generated). The copy
+//! loop reports progress through [`Transfer`], which throttles events onto the
+//! [`EventHub`] and checks a cancel flag between chunks.
+//!
+//! The SFTP routes are owner-only (there is one shared SFTP connection), so
+//! every transfer belongs to the owner and its events go to the owner's
+//! sockets.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::AppState;
+use crate::events::{Event, EventHub};
+
+/// Copy buffer size for streamed transfers.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Minimum interval between progress events for a single transfer.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Max length of a client-supplied transfer id.
+const MAX_ID_LEN: usize = 64;
+/// Smallest chunk used when throttling (keeps syscall overhead sane at low rates).
+const MIN_THROTTLED_CHUNK: usize = 1024;
+
+/// `Upload` = local → SFTP host, `Download` = SFTP host → local.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+#[derive(Debug)]
+pub enum TransferError {
+    Cancelled,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferError::Cancelled => write!(f, "Transfer cancelled"),
+            TransferError::Io(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for TransferError {
+    fn from(e: std::io::Error) -> Self {
+        TransferError::Io(e)
+    }
+}
+
+/// Shared state of an in-flight transfer (visible to the list/cancel API).
+struct TransferShared {
+    direction: TransferDirection,
+    path: String,
+    total: Option<u64>,
+    transferred: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+#[derive(Serialize)]
+pub struct TransferInfo {
+    pub id: String,
+    pub direction: TransferDirection,
+    pub path: String,
+    pub transferred: u64,
+    pub total: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct TransferManager {
+    active: Arc<Mutex<HashMap<String, Arc<TransferShared>>>>,
+    events: EventHub,
+}
+
+impl TransferManager {
+    pub fn new(events: EventHub) -> Self {
+        Self {
+            active: Arc::new(Mutex::new(HashMap::new())),
+            events,
+        }
+    }
+
+    /// Register a new transfer. A missing, invalid or already-active id is
+    /// replaced with a generated one.
+    pub fn start(
+        &self,
+        requested_id: Option<String>,
+        direction: TransferDirection,
+        path: &str,
+        total: Option<u64>,
+    ) -> Transfer {
+        let shared = Arc::new(TransferShared {
+            direction,
+            path: path.to_string(),
+            total,
+            transferred: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+        });
+        let mut active = self.active.lock().expect("transfer map poisoned");
+        let id = requested_id
+            .filter(|id| is_valid_id(id) && !active.contains_key(id))
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        active.insert(id.clone(), Arc::clone(&shared));
+        drop(active);
+
+        let now = Instant::now();
+        Transfer {
+            id,
+            shared,
+            manager: self.clone(),
+            started: now,
+            last_emit: now,
+            rate_limit: None,
+            finished: false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<TransferInfo> {
+        let active = self.active.lock().expect("transfer map poisoned");
+        active
+            .iter()
+            .map(|(id, t)| TransferInfo {
+                id: id.clone(),
+                direction: t.direction,
+                path: t.path.clone(),
+                transferred: t.transferred.load(Ordering::Relaxed),
+                total: t.total,
+            })
+            .collect()
+    }
+
+    /// Request cancellation. The copy loop notices it before the next chunk.
+    pub fn cancel(&self, id: &str) -> bool {
+        let active = self.active.lock().expect("transfer map poisoned");
+        match active.get(id) {
+            Some(t) => {
+                t.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&self, id: &str) {
+        self.active
+            .lock()
+            .expect("transfer map poisoned")
+            .remove(id);
+    }
+}
+
+/// Transfer ids travel in URLs and events: keep them short and boring.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Handle for one in-flight transfer. Dropping it without calling
+/// [`Transfer::finish`] reports the transfer as failed.
+pub struct Transfer {
+    id: String,
+    shared: Arc<TransferShared>,
+    manager: TransferManager,
+    started: Instant,
+    last_emit: Instant,
+    /// Bytes per second (None = unlimited)
+    rate_limit: Option<u64>,
+    finished: bool,
+}
+
+impl Transfer {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn transferred(&self) -> u64 {
+        self.shared.transferred.load(Ordering::Relaxed)
+    }
+
+    /// Cap the average rate of this transfer (KB/s; `None` or 0 = unlimited).
+    pub fn set_rate_limit(&mut self, kbps: Option<u32>) {
+        self.rate_limit = kbps.filter(|&k| k > 0).map(|k| u64::from(k) * 1024);
+    }
+
+    /// Sleep until the average rate since start is back under the limit.
+    /// SFTP transfers run on their own channel (`open_transfer_session`), so
+    /// this never sleeps while the manager lock is held.
+    async fn throttle(&self) {
+        let Some(limit) = self.rate_limit else {
+            return;
+        };
+        let target = Duration::from_secs_f64(self.transferred() as f64 / limit as f64);
+        let elapsed = self.started.elapsed();
+        if target > elapsed {
+            tokio::time::sleep(target - elapsed).await;
+        }
+    }
+
+    /// Record `n` more bytes; emits a progress event at most every
+    /// `PROGRESS_INTERVAL`.
+    pub fn advance(&mut self, n: u64) {
+        let transferred = self.shared.transferred.fetch_add(n, Ordering::Relaxed) + n;
+        let now = Instant::now();
+        if now.duration_since(self.last_emit) < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last_emit = now;
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            (transferred as f64 / elapsed) as u64
+        } else {
+            0
+        };
+        self.manager.events.publish(
+            None,
+            &Event::TransferProgress {
+                id: self.id.clone(),
+                direction: self.shared.direction,
+                path: self.shared.path.clone(),
+                transferred,
+                total: self.shared.total,
+                rate,
+            },
+        );
+    }
+
+    /// Stream `reader` into `writer`, reporting progress and honouring
+    /// cancellation between chunks. Returns the number of bytes copied.
+    pub async fn copy<R, W>(&mut self, reader: &mut R, writer: &mut W) -> Result<u64, TransferError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        // Throttled transfers use ~1/4 s worth of data per chunk so the pacing is smooth
+        let chunk = match self.rate_limit {
+            Some(limit) => ((limit / 4) as usize).clamp(MIN_THROTTLED_CHUNK, CHUNK_SIZE),
+            None => CHUNK_SIZE,
+        };
+        let mut buf = vec![0u8; chunk];
+        let mut copied = 0u64;
+        loop {
+            if self.is_cancelled() {
+                return Err(TransferError::Cancelled);
+            }
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buf[..n]).await?;
+            copied += n as u64;
+            self.advance(n as u64);
+            self.throttle().await;
+        }
+        writer.flush().await?;
+        Ok(copied)
+    }
+
+    /// Mark the transfer as completed successfully.
+    pub fn finish(mut self) {
+        self.finished = true;
+        self.manager.events.publish(
+            None,
+            &Event::TransferDone {
+                id: self.id.clone(),
+                direction: self.shared.direction,
+                path: self.shared.path.clone(),
+                transferred: self.transferred(),
+            },
+        );
+    }
+
+    /// Mark the transfer as failed (or cancelled, for `TransferError::Cancelled`).
+    pub fn fail(mut self, error: &TransferError) {
+        self.finished = true;
+        self.publish_failure(Some(error));
+    }
+
+    fn publish_failure(&self, error: Option<&TransferError>) {
+        let event = match error {
+            Some(TransferError::Cancelled) => Event::TransferCancelled {
+                id: self.id.clone(),
+                direction: self.shared.direction,
+                path: self.shared.path.clone(),
+            },
+            other => Event::TransferError {
+                id: self.id.clone(),
+                direction: self.shared.direction,
+                path: self.shared.path.clone(),
+                error: other.map_or_else(|| "Transfer aborted".to_string(), |e| e.to_string()),
+            },
+        };
+        self.manager.events.publish(None, &event);
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.publish_failure(None);
+        }
+        self.manager.remove(&self.id);
+    }
+}
+
+// --- API ハンドラ ---
+
+#[derive(Deserialize)]
+pub struct TransferIdQuery {
+    pub transfer_id: Option<String>,
+    /// Per-transfer rate limit in KB/s (0 = unlimited, absent = Settings default)
+    pub rate_limit_kbps: Option<u32>,
+}
+
+/// GET /api/transfers
+pub async fn list(State(state): State<Arc<AppState>>) -> Json<Vec<TransferInfo>> {
+    Json(state.transfers.list())
+}
+
+/// DELETE /api/transfers/{id}
+pub async fn cancel(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> StatusCode {
+    if state.transfers.cancel(&id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> TransferManager {
+        TransferManager::new(EventHub::new())
+    }
+
+    #[test]
+    fn valid_ids() {
+        assert!(is_valid_id("abc-123_X"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("a/b"));
+        assert!(!is_valid_id(&"a".repeat(MAX_ID_LEN + 1)));
+    }
+
+    #[test]
+    fn start_uses_requested_id_once() {
+        let m = manager();
+        let t1 = m.start(Some("x".into()), TransferDirection::Upload, "/a", None);
+        let t2 = m.start(Some("x".into()), TransferDirection::Upload, "/b", None);
+        assert_eq!(t1.id(), "x");
+        assert_ne!(t2.id(), "x");
+        assert_eq!(m.list().len(), 2);
+        drop(t1);
+        drop(t2);
+        assert!(m.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn copy_reports_bytes_and_done_event() {
+        let m = manager();
+        let mut rx = m.events.subscribe(None);
+        let mut t = m.start(None, TransferDirection::Download, "/a", Some(3));
+        let mut out = Vec::new();
+        let n = t.copy(&mut &b"abc"[..], &mut out).await.unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(out, b"abc");
+        t.finish();
+        let msg = rx.recv().await.unwrap();
+        assert!(msg.contains("transfer_done"));
+        assert!(m.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rate_limit_paces_copy() {
+        let m = manager();
+        let mut t = m.start(None, TransferDirection::Upload, "/a", None);
+        t.set_rate_limit(Some(8));
+        let data = vec![0u8; 4096];
+        let mut out = Vec::new();
+        let started = Instant::now();
+        t.copy(&mut &data[..], &mut out).await.unwrap();
+        // 4 KiB at 8 KiB/s ≈ 0.5 s
+        assert!(started.elapsed() >= Duration::from_millis(400));
+        assert_eq!(out.len(), 4096);
+    }
+
+    #[test]
+    fn zero_rate_limit_is_unlimited() {
+        let m = manager();
+        let mut t = m.start(None, TransferDirection::Upload, "/a", None);
+        t.set_rate_limit(Some(0));
+        assert!(t.rate_limit.is_none());
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_copy() {
+        let m = manager();
+        let mut t = m.start(Some("c".into()), TransferDirection::Upload, "/a", None);
+        assert!(m.cancel("c"));
+        let mut out = Vec::new();
+        let result = t.copy(&mut &b"abc"[..], &mut out).await;
+        assert!(matches!(result, Err(TransferError::Cancelled)));
+        assert!(out.is_empty());
+        assert!(!m.cancel("missing"));
+    }
+}
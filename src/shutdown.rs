@@ -0,0 +1,102 @@
+//! Graceful shutdown.
+//!
+//! `Shutdown::begin` is called once when den is about to stop (Ctrl+C,
+//! SIGTERM or a restart after an update). Every WebSocket handler watches
+//! it, tells its client `{"type":"server_shutting_down","restart":..}` and
+//! closes the socket, so HTTP connection draining isn't held up by
+//! long-lived sockets. Draining is bounded by `DRAIN_TIMEOUT`.
+
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long open connections get to finish once shutdown has begun
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct Shutdown {
+    /// None = running, Some(restart)
+    tx: watch::Sender<Option<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            tx: watch::channel(None).0,
+        }
+    }
+
+    /// Start shutting down. `restart` = den comes straight back (self-update),
+    /// so clients should reconnect rather than give up. Later calls are no-ops.
+    pub fn begin(&self, restart: bool) {
+        self.tx.send_if_modified(|state| {
+            if state.is_some() {
+                return false;
+            }
+            *state = Some(restart);
+            true
+        });
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.tx.borrow().is_some()
+    }
+
+    /// Resolves once shutdown has begun, with its `restart` flag
+    pub async fn started(&self) -> bool {
+        let mut rx = self.tx.subscribe();
+        let started = rx
+            .wait_for(Option::is_some)
+            .await
+            .map(|state| state.unwrap_or_default());
+        match started {
+            Ok(restart) => restart,
+            // The sender lives as long as `self`
+            Err(_) => std::future::pending().await,
+        }
+    }
+
+    /// Resolves `DRAIN_TIMEOUT` after shutdown has begun
+    pub async fn drain_deadline(&self) {
+        self.started().await;
+        tokio::time::sleep(DRAIN_TIMEOUT).await;
+    }
+}
+
+/// Text frame sent to WebSocket clients before their socket is closed
+pub fn client_message(restart: bool) -> String {
+    format!(r#"{{"type":"server_shutting_down","restart":{restart}}}"#)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn started_resolves_with_the_first_reason() {
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.is_started());
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.started().await }
+        });
+        shutdown.begin(true);
+        shutdown.begin(false);
+        assert!(waiter.await.unwrap());
+        assert!(shutdown.is_started());
+        // Already started: resolves immediately
+        assert!(shutdown.started().await);
+    }
+
+    #[test]
+    fn client_message_is_json() {
+        let msg: serde_json::Value = serde_json::from_str(&client_message(false)).unwrap();
+        assert_eq!(msg["type"], "server_shutting_down");
+        assert_eq!(msg["restart"], false);
+    }
+}
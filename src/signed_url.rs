@@ -0,0 +1,182 @@
+//! Signed download URLs for scripts on other machines.
+//!
+//! `POST /api/filer/sign-url` / `POST /api/sftp/sign-url` return a
+//! `.../download?path=..&expires=..&sig=..` link that `curl` or `wget` can
+//! fetch without a cookie. The signature is an HMAC over the route, path and
+//! expiry, keyed with the signing account's token key, so the link is
+//! reusable until it expires and dies early when that account's password
+//! changes, the account is deleted or the signing secret rotates. Nothing is
+//! stored server-side.
+
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+    http::{Method, Request, StatusCode},
+};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::AppState;
+use crate::auth::{AuthUser, account_token_key, constant_time_eq};
+use crate::filer::api::{ErrorResponse, err, io_err};
+use crate::store::{Role, UserAccount};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Routes that accept a signed query, with the scope area they belong to
+const SIGNED_ROUTES: &[(&str, &str)] = &[
+    ("/api/filer/download", "filer"),
+    ("/api/sftp/download", "sftp"),
+];
+
+const DEFAULT_EXPIRES_MINS: u64 = 60;
+/// 7 days
+const MAX_EXPIRES_MINS: u64 = 7 * 24 * 60;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn signature(state: &AppState, key: &str, route: &str, path: &str, expires: u64) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(&state.hmac_secret()).expect("HMAC accepts any key length");
+    mac.update(format!("signed-url\n{key}\n{route}\n{path}\n{expires}").as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[derive(Deserialize)]
+struct SignedQuery {
+    path: String,
+    expires: u64,
+    sig: String,
+    /// Signing account (absent = owner)
+    user: Option<String>,
+}
+
+/// Resolve a GET/HEAD to a signed route carrying a valid, unexpired
+/// signature. The principal is the signing account, limited to `area:read`.
+pub(crate) fn authenticate(state: &AppState, req: &Request<axum::body::Body>) -> Option<AuthUser> {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return None;
+    }
+    let (route, area) = SIGNED_ROUTES
+        .iter()
+        .find(|(route, _)| *route == req.uri().path())?;
+    let Query(q) = Query::<SignedQuery>::try_from_uri(req.uri()).ok()?;
+    if q.expires <= unix_now() {
+        return None;
+    }
+    let account = match q.user {
+        None => None,
+        Some(ref username) => Some(state.store.get_user(username)?),
+    };
+    let key = account_token_key(state, account.as_ref());
+    if !constant_time_eq(&q.sig, &signature(state, &key, route, &q.path, q.expires)) {
+        return None;
+    }
+    Some(AuthUser {
+        role: account.as_ref().map_or(Role::Admin, |u| u.role),
+        username: q.user,
+        scopes: Some(vec![format!("{area}:read")]),
+        token_id: None,
+        device_id: None,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct SignRequest {
+    pub path: String,
+    /// Default 60, at most 7 days
+    pub expires_in_mins: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct SignResponse {
+    /// Path and query of the download link (prefix with the den origin)
+    pub url: String,
+    /// Unix seconds
+    pub expires_at: u64,
+}
+
+fn sign(
+    state: &AppState,
+    user: &AuthUser,
+    route: &str,
+    req: SignRequest,
+) -> Result<Json<SignResponse>, ApiError> {
+    let expires_in_mins = req.expires_in_mins.unwrap_or(DEFAULT_EXPIRES_MINS);
+    if !(1..=MAX_EXPIRES_MINS).contains(&expires_in_mins) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "expires_in_mins must be between 1 and 10080",
+        ));
+    }
+    let account: Option<UserAccount> = match user.username {
+        None => None,
+        Some(ref username) => Some(
+            state
+                .store
+                .get_user(username)
+                .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "Unknown user"))?,
+        ),
+    };
+    let expires_at = unix_now() + expires_in_mins * 60;
+    let key = account_token_key(state, account.as_ref());
+    let sig = signature(state, &key, route, &req.path, expires_at);
+    let expires = expires_at.to_string();
+    let mut params = vec![
+        ("path", req.path.as_str()),
+        ("expires", expires.as_str()),
+        ("sig", sig.as_str()),
+    ];
+    if let Some(ref username) = user.username {
+        params.push(("user", username));
+    }
+    tracing::info!(
+        "signed download URL: {route} {} ({expires_in_mins} min)",
+        req.path
+    );
+    Ok(Json(SignResponse {
+        url: format!("{route}?{}", crate::oidc::form_urlencode(&params)),
+        expires_at,
+    }))
+}
+
+/// POST /api/filer/sign-url
+pub async fn sign_filer(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Json(req): Json<SignRequest>,
+) -> Result<Json<SignResponse>, ApiError> {
+    let roots = state.filer_roots.clone();
+    let path = req.path.clone();
+    let metadata = tokio::task::spawn_blocking(move || {
+        let resolved = roots.resolve(&path)?;
+        std::fs::metadata(&resolved).map_err(io_err)
+    })
+    .await
+    .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))??;
+    if !metadata.is_file() {
+        return Err(err(StatusCode::BAD_REQUEST, "Not a file"));
+    }
+    sign(&state, &user, "/api/filer/download", req)
+}
+
+/// POST /api/sftp/sign-url — the path is only checked when the link is used,
+/// against whatever SFTP connection is open then.
+pub async fn sign_sftp(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Json(req): Json<SignRequest>,
+) -> Result<Json<SignResponse>, ApiError> {
+    if req.path.is_empty() || req.path.contains('\0') {
+        return Err(err(StatusCode::BAD_REQUEST, "Invalid path"));
+    }
+    sign(&state, &user, "/api/sftp/download", req)
+}
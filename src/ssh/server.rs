@@ -9,8 +9,11 @@ use russh::server::{Auth, Handler, Msg, Server as _, Session};
 use russh::{ChannelId, Pty};
 
 use tokio::sync::mpsc;
+use tracing::Instrument;
 
-use crate::auth::constant_time_eq;
+use crate::auth::{LoginRateLimiter, OwnerCredential};
+use crate::ip_filter::IpFilter;
+use crate::notify::Notifier;
 use crate::pty::registry::{ClientKind, SessionRegistry, SharedSession};
 use crate::sftp::client::{HostKeyStatus, connect_agent};
 use crate::store::Store;
@@ -162,16 +165,20 @@ fn key_identity(openssh_line: &str) -> String {
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
     registry: Arc<SessionRegistry>,
-    password: String,
+    password: OwnerCredential,
     port: u16,
     data_dir: String,
     bind_address: String,
     store: Store,
+    ip_filter: IpFilter,
+    rate_limiter: Arc<LoginRateLimiter>,
+    notifier: Notifier,
 ) -> anyhow::Result<()> {
     // ホストキー読み込み/生成
     let host_key = super::keys::load_or_generate_host_key(std::path::Path::new(&data_dir))?;
 
     let authorized_keys: Arc<HashSet<String>> = Arc::new(load_authorized_keys(&data_dir));
+    crate::notify::check_authorized_keys(&store, &notifier, &authorized_keys);
 
     // auth_rejection_time を 0 にして、パスワード認証のみハンドラ側で遅延させる。
     // これにより公開鍵認証の拒否が即座に完了し、クライアントがパスワード認証に
@@ -196,6 +203,9 @@ pub async fn run(
         loopback_count: Arc::new(AtomicUsize::new(0)),
         ssh_port: port,
         store,
+        ip_filter,
+        rate_limiter,
+        notifier,
     };
 
     let addr = format!("{bind_address}:{port}");
@@ -210,12 +220,15 @@ pub async fn run(
 #[derive(Clone)]
 struct DenSshServer {
     registry: Arc<SessionRegistry>,
-    password: String,
+    password: OwnerCredential,
     authorized_keys: Arc<HashSet<String>>,
     instance_id: String,
     loopback_count: Arc<AtomicUsize>,
     ssh_port: u16,
     store: Store,
+    ip_filter: IpFilter,
+    rate_limiter: Arc<LoginRateLimiter>,
+    notifier: Notifier,
 }
 
 impl russh::server::Server for DenSshServer {
@@ -227,6 +240,9 @@ impl russh::server::Server for DenSshServer {
         if is_local {
             self.loopback_count.fetch_add(1, Ordering::Relaxed);
         }
+        // 許可されていないアドレスは認証段階で切断する
+        let ip_allowed = addr.is_some_and(|a| self.ip_filter.is_allowed(a.ip()))
+            || (addr.is_none() && !self.ip_filter.is_active());
         DenSshHandler {
             registry: Arc::clone(&self.registry),
             password: self.password.clone(),
@@ -238,6 +254,9 @@ impl russh::server::Server for DenSshServer {
             loopback_count: Arc::clone(&self.loopback_count),
             peer_addr: addr,
             ssh_port: self.ssh_port,
+            ip_allowed,
+            rate_limiter: Arc::clone(&self.rate_limiter),
+            notifier: self.notifier.clone(),
             session_name: None,
             client_id: None,
             channel_id: None,
@@ -256,7 +275,7 @@ impl russh::server::Server for DenSshServer {
 
 struct DenSshHandler {
     registry: Arc<SessionRegistry>,
-    password: String,
+    password: OwnerCredential,
     authorized_keys: Arc<HashSet<String>>,
     store: Store,
     // Self-connection detection
@@ -266,6 +285,11 @@ struct DenSshHandler {
     loopback_count: Arc<AtomicUsize>,
     peer_addr: Option<std::net::SocketAddr>,
     ssh_port: u16,
+    /// DEN_ALLOW_CIDRS / DEN_DENY_CIDRS の判定結果
+    ip_allowed: bool,
+    /// Web ログインと共有するブルートフォース対策
+    rate_limiter: Arc<LoginRateLimiter>,
+    notifier: Notifier,
     // Per-connection state
     session_name: Option<String>,
     client_id: Option<u64>,
@@ -371,6 +395,11 @@ impl DenSshHandler {
         let (shared_session, mut output_rx, replay, client_id) = self
             .registry
             .get_or_create(session_name, ClientKind::Ssh, cols, rows, None)
+            .instrument(tracing::info_span!(
+                "pty.attach",
+                session = %session_name,
+                kind = ?ClientKind::Ssh
+            ))
             .await
             .map_err(|e| anyhow::anyhow!("{e}"))?;
         let replay = replay.data;
@@ -618,7 +647,7 @@ impl DenSshHandler {
                 store,
                 &host,
                 port,
-                &password,
+                password.plaintext(),
                 &r_session,
                 cols,
                 rows,
@@ -762,7 +791,7 @@ async fn remote_bridge_task(
     store: Store,
     host: &str,
     port: u16,
-    password: &str,
+    password: Option<&str>,
     remote_session: &str,
     cols: u16,
     rows: u16,
@@ -806,16 +835,20 @@ async fn remote_bridge_task(
         }
     };
 
-    // Try password auth first (most common: same DEN_PASSWORD on both instances)
-    let password_ok = match remote
-        .authenticate_password(REMOTE_SSH_USERNAME, password)
-        .await
-    {
-        Ok(result) => result.success(),
-        Err(e) => {
-            tracing::debug!("ssh-remote: password auth error: {e}");
-            false
-        }
+    // Try password auth first (most common: same DEN_PASSWORD on both instances).
+    // With only DEN_PASSWORD_HASH there is no plaintext to offer, so go straight to the agent.
+    let password_ok = match password {
+        Some(password) => match remote
+            .authenticate_password(REMOTE_SSH_USERNAME, password)
+            .await
+        {
+            Ok(result) => result.success(),
+            Err(e) => {
+                tracing::debug!("ssh-remote: password auth error: {e}");
+                false
+            }
+        },
+        None => false,
     };
 
     if !password_ok {
@@ -881,22 +914,20 @@ async fn remote_bridge_task(
             msg = reader.wait() => {
                 match msg {
                     Some(russh::ChannelMsg::Data { data }) => {
-                        if local_handle
+                        let sent = local_handle
                             .data(local_channel, Bytes::copy_from_slice(&data))
-                            .await
-                            .is_err()
-                        {
+                            .await;
+                        if sent.is_err() {
                             tracing::info!("ssh-remote: local channel closed");
                             break;
                         }
                     }
                     Some(russh::ChannelMsg::ExtendedData { data, .. }) => {
                         // stderr — forward as-is
-                        if local_handle
+                        let sent = local_handle
                             .data(local_channel, Bytes::copy_from_slice(&data))
-                            .await
-                            .is_err()
-                        {
+                            .await;
+                        if sent.is_err() {
                             break;
                         }
                     }
@@ -950,6 +981,18 @@ async fn remote_bridge_task(
     Ok(())
 }
 
+impl DenSshHandler {
+    /// Err を返すと russh が接続を閉じる
+    fn ensure_ip_allowed(&self) -> anyhow::Result<()> {
+        if self.ip_allowed {
+            Ok(())
+        } else {
+            tracing::warn!("SSH client rejected by IP filter: {:?}", self.peer_addr);
+            Err(anyhow::anyhow!("client address not allowed"))
+        }
+    }
+}
+
 impl Handler for DenSshHandler {
     type Error = anyhow::Error;
 
@@ -958,6 +1001,7 @@ impl Handler for DenSshHandler {
         _user: &str,
         public_key: &ssh_key::PublicKey,
     ) -> Result<Auth, Self::Error> {
+        self.ensure_ip_allowed()?;
         if self.authorized_keys.is_empty() {
             return Ok(Auth::Reject {
                 proceed_with_methods: None,
@@ -981,12 +1025,21 @@ impl Handler for DenSshHandler {
         _user: &str,
         public_key: &ssh_key::PublicKey,
     ) -> Result<Auth, Self::Error> {
+        self.ensure_ip_allowed()?;
         let offered = key_identity(&public_key.to_string());
         if self.authorized_keys.contains(&offered) {
             tracing::info!("SSH auth: public key accepted");
+            crate::notify::record_login(
+                &self.store,
+                &self.notifier,
+                None,
+                self.peer_addr.map(|a| a.ip()),
+                "ssh-key",
+            );
             Ok(Auth::Accept)
         } else {
             tracing::warn!("SSH auth: public key rejected");
+            crate::metrics::SSH_AUTH_FAILURES.fetch_add(1, Ordering::Relaxed);
             Ok(Auth::Reject {
                 proceed_with_methods: None,
                 partial_success: false,
@@ -995,11 +1048,42 @@ impl Handler for DenSshHandler {
     }
 
     async fn auth_password(&mut self, _user: &str, password: &str) -> Result<Auth, Self::Error> {
-        if constant_time_eq(password, &self.password) {
+        self.ensure_ip_allowed()?;
+        let client_ip = self.peer_addr.map(|a| a.ip());
+        if !self.rate_limiter.check(client_ip) {
+            tracing::warn!("SSH auth: rate limited {client_ip:?}");
+            tokio::time::sleep(SSH_PASSWORD_DELAY).await;
+            return Ok(Auth::Reject {
+                proceed_with_methods: None,
+                partial_success: false,
+            });
+        }
+        // argon2 ハッシュの検証は重いので blocking スレッドで行う
+        let owner = crate::auth::current_owner_credential(&self.password, &self.store);
+        let candidate = password.to_string();
+        let verified = tokio::task::spawn_blocking(move || owner.verify(&candidate))
+            .await
+            .unwrap_or(false);
+        if verified {
             tracing::info!("SSH auth: password accepted");
+            self.rate_limiter.record_success(client_ip);
+            crate::notify::record_login(
+                &self.store,
+                &self.notifier,
+                None,
+                client_ip,
+                "ssh-password",
+            );
             Ok(Auth::Accept)
         } else {
             tracing::warn!("SSH auth: password rejected");
+            crate::metrics::SSH_AUTH_FAILURES.fetch_add(1, Ordering::Relaxed);
+            crate::auth::record_login_failure(
+                &self.rate_limiter,
+                &self.store,
+                &self.notifier,
+                client_ip,
+            );
             // auth_rejection_time を 0 にしたため、ブルートフォース対策の遅延をここで入れる
             tokio::time::sleep(SSH_PASSWORD_DELAY).await;
             Ok(Auth::Reject {
@@ -0,0 +1,280 @@
+//! `GET /api/status` (admin only): what den knows about itself, for
+//! troubleshooting without reading logs or guessing at proxy setups.
+//!
+//! `self_check` runs once at startup. It probes the data directory and PTY
+//! support and keeps anything wrong as a startup warning (also logged).
+//! Other startup code adds its own with `Diagnostics::warn`.
+
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, Uri, header},
+};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::AppState;
+use crate::ip_filter::ClientOrigin;
+
+/// SSH listener as last reported by its supervisor
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum SshState {
+    #[default]
+    Disabled,
+    Running {
+        port: u16,
+    },
+    Failed {
+        port: u16,
+        error: String,
+    },
+}
+
+#[derive(Default)]
+pub struct Diagnostics {
+    warnings: Mutex<Vec<String>>,
+    /// None until `self_check` has run
+    pty: OnceLock<Result<(), String>>,
+    ssh: Mutex<SshState>,
+}
+
+impl Diagnostics {
+    /// Log a startup problem and keep it for `/api/status`
+    pub fn warn(&self, message: impl Into<String>) {
+        let message = message.into();
+        tracing::warn!("{message}");
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(message);
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn set_ssh(&self, ssh: SshState) {
+        *self.ssh.lock().unwrap_or_else(|e| e.into_inner()) = ssh;
+    }
+
+    pub fn ssh(&self) -> SshState {
+        self.ssh.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Create and remove a file in `dir` to prove it is writable
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(".den-write-check");
+    std::fs::write(&probe, b"ok")
+        .and_then(|()| std::fs::remove_file(&probe))
+        .map_err(|e| e.to_string())
+}
+
+/// Startup self-check (blocking probes run off the async runtime)
+pub async fn self_check(state: &AppState) {
+    let dir = std::path::PathBuf::from(&state.config.data_dir);
+    let (data_dir, pty) = tokio::task::spawn_blocking(move || {
+        (
+            check_writable(&dir),
+            crate::pty::manager::PtyManager::probe(),
+        )
+    })
+    .await
+    .unwrap_or_else(|e| (Err(e.to_string()), Err(e.to_string())));
+    if let Err(e) = data_dir {
+        state.diagnostics.warn(format!(
+            "Data directory {} is not writable: {e}",
+            state.config.data_dir
+        ));
+    }
+    if let Err(ref e) = pty {
+        state
+            .diagnostics
+            .warn(format!("Terminals are unavailable ({PTY_BACKEND}): {e}"));
+    }
+    let _ = state.diagnostics.pty.set(pty);
+}
+
+#[cfg(windows)]
+const PTY_BACKEND: &str = "conpty";
+#[cfg(not(windows))]
+const PTY_BACKEND: &str = "openpty";
+
+#[derive(Serialize)]
+pub struct DataDirStatus {
+    pub path: String,
+    pub writable: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PtyStatus {
+    pub backend: &'static str,
+    /// None = not checked yet
+    pub available: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SessionStatus {
+    pub total: usize,
+    pub alive: usize,
+}
+
+#[derive(Serialize)]
+pub struct FeatureStatus {
+    pub tls: bool,
+    pub client_certificates: bool,
+    pub acme: bool,
+    pub oidc: bool,
+    pub local_socket: bool,
+    pub admin_listener: bool,
+    pub ip_filter: bool,
+    pub trusted_proxies: bool,
+    pub metrics_token: bool,
+    pub otlp: bool,
+    pub filer: bool,
+    pub sftp: bool,
+    pub remote: bool,
+    pub update: bool,
+}
+
+/// The request as den sees it (for checking reverse proxy setups)
+#[derive(Serialize)]
+pub struct RequestStatus {
+    /// Directly connected peer (the proxy, if there is one)
+    pub peer: Option<String>,
+    /// Client address after unwrapping trusted proxies
+    pub client_ip: Option<String>,
+    pub host: Option<String>,
+    pub forwarded_for: Option<String>,
+    /// Scheme the browser used: TLS here, or `X-Forwarded-Proto` from a trusted proxy
+    pub https: bool,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponse {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub uptime_secs: u64,
+    pub data_dir: DataDirStatus,
+    pub pty: PtyStatus,
+    pub ssh: SshState,
+    pub sessions: SessionStatus,
+    pub features: FeatureStatus,
+    pub request: RequestStatus,
+    pub warnings: Vec<String>,
+}
+
+fn header_value(
+    headers: &HeaderMap,
+    name: impl axum::http::header::AsHeaderName,
+) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// GET /api/status (admin only)
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    origin: Option<Extension<ClientOrigin>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Json<StatusResponse> {
+    let config = &state.config;
+    // 現在の状態を見るため data_dir は毎回確認する
+    let dir = std::path::PathBuf::from(&config.data_dir);
+    let data_dir = tokio::task::spawn_blocking(move || check_writable(&dir))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    let pty = state.diagnostics.pty.get();
+    let stats = state.registry.stats().await;
+    let origin = origin.map(|Extension(o)| o).unwrap_or_default();
+
+    Json(StatusResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        uptime_secs: crate::metrics::uptime().as_secs(),
+        data_dir: DataDirStatus {
+            path: config.data_dir.clone(),
+            writable: data_dir.is_ok(),
+            error: data_dir.err(),
+        },
+        pty: PtyStatus {
+            backend: PTY_BACKEND,
+            available: pty.map(Result::is_ok),
+            error: pty.and_then(|r| r.clone().err()),
+        },
+        ssh: state.diagnostics.ssh(),
+        sessions: SessionStatus {
+            total: stats.sessions,
+            alive: stats.alive,
+        },
+        features: FeatureStatus {
+            tls: config.tls_enabled,
+            client_certificates: config.tls_client_ca.is_some(),
+            acme: config.acme.is_some(),
+            oidc: config.oidc.is_some(),
+            local_socket: config.local_socket.is_some(),
+            admin_listener: config.admin_listener.is_some(),
+            ip_filter: state.ip_filter.is_active(),
+            trusted_proxies: !config.trusted_proxies.is_empty(),
+            metrics_token: config.metrics_token.is_some(),
+            otlp: config.otlp.is_some(),
+            filer: !config.disabled.filer,
+            sftp: !config.disabled.sftp,
+            remote: !config.disabled.remote,
+            update: !config.disabled.update,
+        },
+        request: RequestStatus {
+            peer: peer.map(|Extension(ConnectInfo(addr))| addr.to_string()),
+            client_ip: origin.ip.map(|ip| ip.to_string()),
+            // HTTP/2 では Host ヘッダーの代わりに :authority が使われる
+            host: header_value(&headers, header::HOST)
+                .or_else(|| uri.authority().map(|a| a.to_string())),
+            forwarded_for: header_value(&headers, "x-forwarded-for"),
+            https: config.tls_enabled || origin.forwarded_https,
+        },
+        warnings: state.diagnostics.warnings(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_and_ssh_state_are_kept() {
+        let diagnostics = Diagnostics::default();
+        assert_eq!(diagnostics.ssh(), SshState::Disabled);
+        diagnostics.warn("Removed 2 orphaned uploads");
+        diagnostics.set_ssh(SshState::Failed {
+            port: 22,
+            error: "Permission denied".into(),
+        });
+        assert_eq!(diagnostics.warnings(), ["Removed 2 orphaned uploads"]);
+        let ssh = serde_json::to_value(diagnostics.ssh()).unwrap();
+        assert_eq!(
+            ssh,
+            serde_json::json!({"state": "failed", "port": 22, "error": "Permission denied"})
+        );
+    }
+
+    #[test]
+    fn writable_check_reports_missing_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_writable(dir.path()).is_ok());
+        assert!(check_writable(&dir.path().join("missing")).is_err());
+    }
+}
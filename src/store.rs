@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+use crate::vault::{VaultKey, WrappedKey};
+
 /// スリープ抑止モード
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, utoipa::ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum SleepPreventionMode {
     Always,
@@ -26,6 +28,23 @@ pub struct Store {
     known_hosts_cache: Arc<Mutex<Option<HashMap<String, KnownHost>>>>,
     /// Write-through cache for trusted TLS certificates
     trusted_tls_cache: Arc<Mutex<Option<HashMap<String, TrustedTlsCert>>>>,
+    /// Write-through cache for recently opened filer paths
+    filer_recent_cache: Arc<Mutex<Option<Vec<FilerRecent>>>>,
+    /// Write-through cache for filer bookmarks
+    filer_bookmarks_cache: Arc<Mutex<Option<Vec<FilerBookmark>>>>,
+    /// Write-through cache for user accounts (read on every authenticated request)
+    users_cache: Arc<Mutex<Option<Vec<UserAccount>>>>,
+    /// Write-through cache for API tokens (read on every token-authenticated request)
+    api_tokens_cache: Arc<Mutex<Option<Vec<ApiToken>>>>,
+    /// Write-through cache for sealed vault entries (`secrets.json`)
+    secrets_cache: Arc<Mutex<Option<BTreeMap<String, String>>>>,
+    /// Write-through cache for the addresses each account has logged in from
+    login_ips_cache: Arc<Mutex<Option<Vec<KnownLoginIps>>>>,
+    /// Write-through cache for devices.json
+    devices_cache: Arc<Mutex<Option<Vec<Device>>>>,
+    /// Write-through cache for the owner-password file (read on every
+    /// authenticated request); the inner None = no override
+    owner_password_cache: Arc<Mutex<Option<Option<String>>>>,
 }
 
 // --- データモデル ---
@@ -42,6 +61,146 @@ pub struct ClipboardEntry {
 const CLIPBOARD_MAX_ENTRIES: usize = 100;
 const CLIPBOARD_MAX_TEXT_BYTES: usize = 10_240; // 10KB
 
+/// ファイラで最近読み書きしたパス
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilerRecent {
+    pub path: String,
+    /// "read" or "write"
+    pub action: String,
+    /// Unix timestamp in milliseconds
+    pub timestamp: u64,
+}
+
+const FILER_RECENT_MAX_ENTRIES: usize = 50;
+
+/// ファイラのブックマーク（デバイス間で共有）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilerBookmark {
+    pub id: String,
+    pub label: String,
+    pub path: String,
+}
+
+pub const FILER_BOOKMARKS_MAX: usize = 200;
+
+/// アカウントの権限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// ユーザー管理などサーバー全体の操作が可能
+    Admin,
+    #[default]
+    User,
+    /// 閲覧のみ（セッション一覧・ファイル閲覧・ターミナル観覧）。変更系ルートと PTY 入力は 403
+    #[serde(rename = "readonly")]
+    ReadOnly,
+}
+
+/// DEN_PASSWORD のオーナーとは別に追加されたログインアカウント
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserAccount {
+    pub username: String,
+    /// argon2id PHC string
+    pub password_hash: String,
+    #[serde(default)]
+    pub role: Role,
+    pub created_at: String,
+    /// OIDC `sub` that signs in as this user (single sign-on)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oidc_subject: Option<String>,
+}
+
+pub const MAX_USERS: usize = 20;
+
+/// 自動化スクリプト用の長期 API トークン（平文は発行時に一度だけ返す）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    /// sha256 hex of the token
+    pub token_hash: String,
+    /// e.g. `filer:read`, `terminal:*`
+    pub scopes: Vec<String>,
+    /// Issuing user (None = owner); the token acts as this user
+    #[serde(default)]
+    pub username: Option<String>,
+    pub created_at: String,
+    /// RFC 3339（None = 失効するまで有効）
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+impl ApiToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.as_deref().is_some_and(|at| {
+            chrono::DateTime::parse_from_rfc3339(at).map_or(true, |at| at <= chrono::Utc::now())
+        })
+    }
+}
+
+pub const MAX_API_TOKENS: usize = 100;
+
+/// 送信元 IP ごとのログイン失敗記録（再起動をまたいで締め出しを維持する）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginAttemptRecord {
+    /// None = 送信元アドレス不明
+    pub ip: Option<std::net::IpAddr>,
+    /// ウィンドウ内の失敗時刻（unix 秒）。締め出しだけを保存するため新しい記録では空
+    #[serde(default)]
+    pub failures: Vec<u64>,
+    /// 締め出し終了時刻（unix 秒）
+    pub banned_until: Option<u64>,
+}
+
+/// アカウントごとのログイン成功元（新しい IP からのログイン通知に使う）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownLoginIps {
+    /// None = オーナー
+    pub user: Option<String>,
+    /// 古い順
+    pub ips: Vec<std::net::IpAddr>,
+}
+
+/// アカウントごとに覚えておくログイン元 IP の上限（古いものから忘れる）
+const MAX_KNOWN_LOGIN_IPS: usize = 100;
+
+/// ログインした端末。ログイントークンは端末 ID に紐づき、端末を消すとその
+/// 端末のログインだけが無効になる。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    /// 16 hex
+    pub id: String,
+    /// e.g. "iPhone", "Work laptop"
+    pub name: String,
+    /// None = オーナー
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub user_agent: String,
+    /// User-Agent の sha256 先頭 16 hex（同じブラウザかどうかの目安）
+    #[serde(default)]
+    pub fingerprint: String,
+    /// unix 秒
+    pub created_at: u64,
+    /// unix 秒
+    pub last_seen: u64,
+    #[serde(default)]
+    pub last_ip: Option<std::net::IpAddr>,
+}
+
+/// アカウントごとの端末数の上限（超えたら最後に使われたのが最も古いものから消す）
+pub const MAX_DEVICES_PER_ACCOUNT: usize = 50;
+
+/// ユーザー名: 英小文字・数字・`-`・`_`、最大 32 文字
+/// （セッション名の名前空間やディレクトリ名にそのまま使うため制限を厳しくする）
+pub fn is_valid_username(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KnownHost {
     pub fingerprint: String,
@@ -63,7 +222,7 @@ pub struct TrustedTlsCert {
     pub display_name: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct Snippet {
     pub label: String,
     pub command: String,
@@ -71,7 +230,15 @@ pub struct Snippet {
     pub auto_run: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+/// 新規ファイル作成用テンプレート（例: `docker-compose.yml`）
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct FileTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, utoipa::ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SshAuthType {
     #[default]
@@ -80,7 +247,7 @@ pub enum SshAuthType {
     Agent,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct SshBookmark {
     pub label: String,
     pub host: String,
@@ -98,7 +265,7 @@ fn default_ssh_port() -> u16 {
     22
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct DenBookmark {
     /// Deprecated: kept for migration only (read old JSON, never write).
     #[serde(default, skip_serializing)]
@@ -135,7 +302,7 @@ where
     Ok(value.and_then(|v| crate::pty::backend::SessionBackend::deserialize(v).ok()))
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct KeybarButton {
     #[serde(default)]
     pub label: String,
@@ -151,12 +318,13 @@ pub struct KeybarButton {
     #[serde(default)]
     pub display: Option<String>,
     #[serde(default)]
+    #[schema(no_recursion)]
     pub items: Option<Vec<KeybarButton>>,
     #[serde(default)]
     pub selected: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct KeybarPosition {
     #[serde(default)]
     pub left: f64,
@@ -186,7 +354,7 @@ fn default_orientation() -> String {
     "horizontal".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct Settings {
     #[serde(default = "default_font_size")]
     pub font_size: u8,
@@ -230,6 +398,36 @@ pub struct Settings {
     /// Separate from SessionRecord so externally-created sessions can be aliased too.
     #[serde(default)]
     pub mux_aliases: Option<std::collections::HashMap<String, String>>,
+    /// Default SFTP transfer rate limit in KB/s (None = unlimited).
+    /// A per-transfer `rate_limit_kbps` overrides it.
+    #[serde(default)]
+    pub transfer_rate_limit_kbps: Option<u32>,
+    /// Named templates for `POST /api/filer/create`
+    #[serde(default)]
+    pub file_templates: Option<Vec<FileTemplate>>,
+    /// Days of audit log to keep. Valid range: 1–3650 (clamped in put_settings)
+    #[serde(default = "default_audit_retention_days")]
+    pub audit_retention_days: u16,
+    /// Failed logins from one client IP within `login_window_secs` before it
+    /// is locked out. Valid range: 1–100 (clamped in put_settings)
+    #[serde(default = "default_login_max_attempts")]
+    pub login_max_attempts: u16,
+    /// Sliding window for counting failed logins. Valid range: 1–86400
+    #[serde(default = "default_login_window_secs")]
+    pub login_window_secs: u32,
+    /// Lockout duration once the limit is hit. Valid range: 1–604800
+    #[serde(default = "default_login_ban_secs")]
+    pub login_ban_secs: u32,
+    /// Shell for new sessions (owner only, None = DEN_SHELL). Applied without a restart
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// SSH server port (owner only, None = DEN_SSH_PORT, 0 = off). Applied without a restart
+    #[serde(default)]
+    pub ssh_port: Option<u16>,
+    /// Log filter such as "debug" or "info,den=trace" (owner only,
+    /// None = RUST_LOG / DEN_LOG_LEVEL). Applied without a restart
+    #[serde(default)]
+    pub log_level: Option<String>,
     #[serde(skip_deserializing, default)]
     pub version: String,
     #[serde(skip_deserializing, default)]
@@ -248,6 +446,18 @@ fn default_scrollback() -> u32 {
 fn default_sleep_prevention_timeout() -> u16 {
     30
 }
+fn default_audit_retention_days() -> u16 {
+    90
+}
+fn default_login_max_attempts() -> u16 {
+    5
+}
+fn default_login_window_secs() -> u32 {
+    60
+}
+fn default_login_ban_secs() -> u32 {
+    60
+}
 
 impl Default for Settings {
     fn default() -> Self {
@@ -271,6 +481,15 @@ impl Default for Settings {
             restty_font: None,
             default_backend: None,
             mux_aliases: None,
+            transfer_rate_limit_kbps: None,
+            file_templates: None,
+            audit_retention_days: default_audit_retention_days(),
+            login_max_attempts: default_login_max_attempts(),
+            login_window_secs: default_login_window_secs(),
+            login_ban_secs: default_login_ban_secs(),
+            shell: None,
+            ssh_port: None,
+            log_level: None,
             version: String::new(),
             hostname: String::new(),
         }
@@ -295,6 +514,14 @@ impl Store {
             clipboard_cache: Arc::new(Mutex::new(None)),
             known_hosts_cache: Arc::new(Mutex::new(None)),
             trusted_tls_cache: Arc::new(Mutex::new(None)),
+            filer_recent_cache: Arc::new(Mutex::new(None)),
+            filer_bookmarks_cache: Arc::new(Mutex::new(None)),
+            users_cache: Arc::new(Mutex::new(None)),
+            api_tokens_cache: Arc::new(Mutex::new(None)),
+            secrets_cache: Arc::new(Mutex::new(None)),
+            login_ips_cache: Arc::new(Mutex::new(None)),
+            devices_cache: Arc::new(Mutex::new(None)),
+            owner_password_cache: Arc::new(Mutex::new(None)),
         })
     }
 
@@ -494,6 +721,492 @@ impl Store {
         Ok(())
     }
 
+    // --- Filer Recent ---
+
+    pub fn load_filer_recent(&self) -> Vec<FilerRecent> {
+        let mut cache = self.filer_recent_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let entries: Vec<FilerRecent> = self.load_json_or_default("filer-recent.json");
+        *cache = Some(entries.clone());
+        entries
+    }
+
+    /// Move `path` to the front of the recent list (deduplicated by path).
+    pub fn add_filer_recent(&self, path: &str, action: &str) -> std::io::Result<()> {
+        let mut cache = self.filer_recent_cache.lock().unwrap();
+        let mut entries = cache
+            .take()
+            .unwrap_or_else(|| self.load_json_or_default("filer-recent.json"));
+
+        entries.retain(|e| e.path != path);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        entries.insert(
+            0,
+            FilerRecent {
+                path: path.to_string(),
+                action: action.to_string(),
+                timestamp: now,
+            },
+        );
+        entries.truncate(FILER_RECENT_MAX_ENTRIES);
+
+        let result = self.write_json("filer-recent.json", &entries);
+        *cache = Some(entries);
+        result
+    }
+
+    pub fn clear_filer_recent(&self) -> std::io::Result<()> {
+        let mut cache = self.filer_recent_cache.lock().unwrap();
+        self.write_json("filer-recent.json", &Vec::<FilerRecent>::new())?;
+        *cache = Some(Vec::new());
+        Ok(())
+    }
+
+    // --- Filer Bookmarks ---
+
+    pub fn load_filer_bookmarks(&self) -> Vec<FilerBookmark> {
+        let mut cache = self.filer_bookmarks_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let bookmarks: Vec<FilerBookmark> = self.load_json_or_default("filer-bookmarks.json");
+        *cache = Some(bookmarks.clone());
+        bookmarks
+    }
+
+    /// Add a bookmark. Returns None when `FILER_BOOKMARKS_MAX` is reached.
+    pub fn add_filer_bookmark(
+        &self,
+        label: String,
+        path: String,
+    ) -> std::io::Result<Option<FilerBookmark>> {
+        let mut cache = self.filer_bookmarks_cache.lock().unwrap();
+        let mut bookmarks = cache
+            .take()
+            .unwrap_or_else(|| self.load_json_or_default("filer-bookmarks.json"));
+        if bookmarks.len() >= FILER_BOOKMARKS_MAX {
+            *cache = Some(bookmarks);
+            return Ok(None);
+        }
+        let bookmark = FilerBookmark {
+            id: uuid::Uuid::new_v4().to_string(),
+            label,
+            path,
+        };
+        bookmarks.push(bookmark.clone());
+        let result = self.write_json("filer-bookmarks.json", &bookmarks);
+        if result.is_err() {
+            bookmarks.pop();
+        }
+        *cache = Some(bookmarks);
+        result.map(|()| Some(bookmark))
+    }
+
+    /// Replace label/path of a bookmark. Returns None if the id is unknown.
+    pub fn update_filer_bookmark(
+        &self,
+        id: &str,
+        label: String,
+        path: String,
+    ) -> std::io::Result<Option<FilerBookmark>> {
+        let mut cache = self.filer_bookmarks_cache.lock().unwrap();
+        let mut bookmarks = cache
+            .take()
+            .unwrap_or_else(|| self.load_json_or_default("filer-bookmarks.json"));
+        let previous = bookmarks.clone();
+        let Some(bookmark) = bookmarks.iter_mut().find(|b| b.id == id) else {
+            *cache = Some(bookmarks);
+            return Ok(None);
+        };
+        bookmark.label = label;
+        bookmark.path = path;
+        let updated = bookmark.clone();
+        if let Err(e) = self.write_json("filer-bookmarks.json", &bookmarks) {
+            *cache = Some(previous);
+            return Err(e);
+        }
+        *cache = Some(bookmarks);
+        Ok(Some(updated))
+    }
+
+    /// Returns false if the id is unknown.
+    pub fn remove_filer_bookmark(&self, id: &str) -> std::io::Result<bool> {
+        let mut cache = self.filer_bookmarks_cache.lock().unwrap();
+        let mut bookmarks = cache
+            .take()
+            .unwrap_or_else(|| self.load_json_or_default("filer-bookmarks.json"));
+        let before = bookmarks.len();
+        let previous = bookmarks.clone();
+        bookmarks.retain(|b| b.id != id);
+        if bookmarks.len() == before {
+            *cache = Some(bookmarks);
+            return Ok(false);
+        }
+        if let Err(e) = self.write_json("filer-bookmarks.json", &bookmarks) {
+            *cache = Some(previous);
+            return Err(e);
+        }
+        *cache = Some(bookmarks);
+        Ok(true)
+    }
+
+    // --- Users ---
+
+    pub fn load_users(&self) -> Vec<UserAccount> {
+        let mut cache = self.users_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let users: Vec<UserAccount> = self.load_json_or_default("users.json");
+        *cache = Some(users.clone());
+        users
+    }
+
+    pub fn get_user(&self, username: &str) -> Option<UserAccount> {
+        self.load_users()
+            .into_iter()
+            .find(|u| u.username == username)
+    }
+
+    /// Apply `f` to the user list and persist it. The cache lock is held
+    /// throughout, so concurrent edits cannot lose each other's changes.
+    pub fn update_users<R>(
+        &self,
+        f: impl FnOnce(&mut Vec<UserAccount>) -> R,
+    ) -> std::io::Result<R> {
+        let mut cache = self.users_cache.lock().unwrap();
+        let mut users = cache
+            .clone()
+            .unwrap_or_else(|| self.load_json_or_default("users.json"));
+        let result = f(&mut users);
+        self.write_json("users.json", &users)?;
+        *cache = Some(users);
+        Ok(result)
+    }
+
+    // --- API Tokens ---
+
+    pub fn load_api_tokens(&self) -> Vec<ApiToken> {
+        let mut cache = self.api_tokens_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let tokens: Vec<ApiToken> = self.load_json_or_default("api-tokens.json");
+        *cache = Some(tokens.clone());
+        tokens
+    }
+
+    /// Same contract as [`Store::update_users`].
+    pub fn update_api_tokens<R>(
+        &self,
+        f: impl FnOnce(&mut Vec<ApiToken>) -> R,
+    ) -> std::io::Result<R> {
+        let mut cache = self.api_tokens_cache.lock().unwrap();
+        let mut tokens = cache
+            .clone()
+            .unwrap_or_else(|| self.load_json_or_default("api-tokens.json"));
+        let result = f(&mut tokens);
+        self.write_json("api-tokens.json", &tokens)?;
+        *cache = Some(tokens);
+        Ok(result)
+    }
+
+    // --- Devices ---
+
+    pub fn load_devices(&self) -> Vec<Device> {
+        let mut cache = self.devices_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let devices: Vec<Device> = self.load_json_or_default("devices.json");
+        *cache = Some(devices.clone());
+        devices
+    }
+
+    pub fn get_device(&self, id: &str) -> Option<Device> {
+        self.load_devices().into_iter().find(|d| d.id == id)
+    }
+
+    /// Same contract as [`Store::update_users`].
+    pub fn update_devices<R>(&self, f: impl FnOnce(&mut Vec<Device>) -> R) -> std::io::Result<R> {
+        let mut cache = self.devices_cache.lock().unwrap();
+        let mut devices = cache
+            .clone()
+            .unwrap_or_else(|| self.load_json_or_default("devices.json"));
+        let result = f(&mut devices);
+        self.write_json("devices.json", &devices)?;
+        *cache = Some(devices);
+        Ok(result)
+    }
+
+    // --- Login Attempts ---
+
+    pub fn load_login_attempts(&self) -> Vec<LoginAttemptRecord> {
+        self.load_json_or_default("login-attempts.json")
+    }
+
+    pub fn save_login_attempts(&self, records: &[LoginAttemptRecord]) -> std::io::Result<()> {
+        self.write_json("login-attempts.json", records)
+    }
+
+    // --- Pending Uploads ---
+
+    /// 進行中のチャンクアップロードの part ファイル。異常終了後の起動時に削除する
+    pub fn load_pending_uploads(&self) -> Vec<PathBuf> {
+        self.load_json_or_default("uploads.json")
+    }
+
+    pub fn save_pending_uploads(&self, parts: &[PathBuf]) -> std::io::Result<()> {
+        self.write_json("uploads.json", parts)
+    }
+
+    // --- Login IPs ---
+
+    /// ログイン元 IP を記録する。そのアカウントで初めて見る IP なら true
+    pub fn record_login_ip(
+        &self,
+        user: Option<&str>,
+        ip: std::net::IpAddr,
+    ) -> std::io::Result<bool> {
+        let mut cache = self.login_ips_cache.lock().unwrap();
+        let mut records: Vec<KnownLoginIps> = cache
+            .clone()
+            .unwrap_or_else(|| self.load_json_or_default("login-ips.json"));
+        let index = match records.iter().position(|r| r.user.as_deref() == user) {
+            Some(i) => i,
+            None => {
+                records.push(KnownLoginIps {
+                    user: user.map(str::to_string),
+                    ips: Vec::new(),
+                });
+                records.len() - 1
+            }
+        };
+        let ips = &mut records[index].ips;
+        let is_new = match ips.iter().position(|known| *known == ip) {
+            Some(i) => {
+                // 最近使ったものを末尾へ（上限で忘れられないように）
+                ips.remove(i);
+                false
+            }
+            None => true,
+        };
+        ips.push(ip);
+        if ips.len() > MAX_KNOWN_LOGIN_IPS {
+            ips.remove(0);
+        }
+        self.write_json("login-ips.json", &records)?;
+        *cache = Some(records);
+        Ok(is_new)
+    }
+
+    // --- Known SSH Keys ---
+
+    /// 前回起動時の authorized_keys（"algorithm base64"）。初回は None
+    pub fn load_known_ssh_keys(&self) -> Option<Vec<String>> {
+        let content = fs::read_to_string(self.root.join("known-ssh-keys.json")).ok()?;
+        serde_json::from_str(&content)
+            .inspect_err(|e| tracing::warn!("Corrupt known-ssh-keys.json: {e}"))
+            .ok()
+    }
+
+    pub fn save_known_ssh_keys(&self, keys: &[String]) -> std::io::Result<()> {
+        self.write_json("known-ssh-keys.json", keys)
+    }
+
+    // --- HMAC Secret ---
+
+    /// data_dir/hmac-secret の HMAC シークレット（hex）を読み込む。
+    /// 無い・壊れている場合は新しく生成して保存する。
+    pub fn load_or_create_hmac_secret(&self) -> std::io::Result<Vec<u8>> {
+        let path = self.root.join("hmac-secret");
+        match fs::read_to_string(&path) {
+            Ok(s) => match hex::decode(s.trim()) {
+                Ok(secret) if secret.len() >= 32 => return Ok(secret),
+                _ => tracing::warn!("Invalid {}, regenerating", path.display()),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let secret = rand::random::<[u8; 32]>().to_vec();
+        self.save_hmac_secret(&secret)?;
+        Ok(secret)
+    }
+
+    pub fn save_hmac_secret(&self, secret: &[u8]) -> std::io::Result<()> {
+        fs::write(self.root.join("hmac-secret"), hex::encode(secret))
+    }
+
+    // --- Owner Password ---
+
+    /// `POST /api/auth/change-password` で設定したオーナーの argon2id ハッシュ。
+    /// 存在すれば DEN_PASSWORD / DEN_PASSWORD_HASH より優先（削除して再起動すると環境変数に戻る）
+    pub fn load_owner_password_hash(&self) -> Option<String> {
+        let mut cache = self.owner_password_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let hash = self.read_owner_password_hash();
+        *cache = Some(hash.clone());
+        hash
+    }
+
+    fn read_owner_password_hash(&self) -> Option<String> {
+        let path = self.root.join("owner-password");
+        match fs::read_to_string(&path) {
+            Ok(s) if s.trim().starts_with("$argon2") => Some(s.trim().to_string()),
+            Ok(_) => {
+                tracing::warn!("Invalid {}, ignoring", path.display());
+                None
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn save_owner_password_hash(&self, hash: &str) -> std::io::Result<()> {
+        let mut cache = self.owner_password_cache.lock().unwrap();
+        fs::write(self.root.join("owner-password"), hash)?;
+        *cache = Some(Some(hash.to_string()));
+        Ok(())
+    }
+
+    // --- Secrets Vault ---
+
+    fn update_secrets<R>(
+        &self,
+        f: impl FnOnce(&mut BTreeMap<String, String>) -> R,
+    ) -> std::io::Result<R> {
+        let mut cache = self.secrets_cache.lock().unwrap();
+        let mut secrets = cache
+            .clone()
+            .unwrap_or_else(|| self.load_json_or_default("secrets.json"));
+        let result = f(&mut secrets);
+        self.write_json("secrets.json", &secrets)?;
+        *cache = Some(secrets);
+        Ok(result)
+    }
+
+    fn load_secrets(&self) -> BTreeMap<String, String> {
+        let mut cache = self.secrets_cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            return cached.clone();
+        }
+        let secrets: BTreeMap<String, String> = self.load_json_or_default("secrets.json");
+        *cache = Some(secrets.clone());
+        secrets
+    }
+
+    /// 復号した値。未登録、または別の鍵で封印されている場合は None
+    pub fn get_secret(&self, key: &VaultKey, name: &str) -> Option<String> {
+        let sealed = self.load_secrets().remove(name)?;
+        match crate::vault::open(&sealed, key.bytes()) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::warn!("Cannot open secret {name}: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn set_secret(&self, key: &VaultKey, name: &str, value: &str) -> std::io::Result<()> {
+        let sealed = crate::vault::seal(value, key.bytes());
+        self.update_secrets(|secrets| {
+            secrets.insert(name.to_string(), sealed);
+        })
+    }
+
+    /// 削除したら true
+    pub fn remove_secret(&self, name: &str) -> std::io::Result<bool> {
+        if !self.load_secrets().contains_key(name) {
+            return Ok(false);
+        }
+        self.update_secrets(|secrets| secrets.remove(name).is_some())
+    }
+
+    /// `prefix` で始まるエントリ名（値は返さない）
+    pub fn secret_names(&self, prefix: &str) -> Vec<String> {
+        self.load_secrets()
+            .into_keys()
+            .filter(|name| name.starts_with(prefix))
+            .collect()
+    }
+
+    /// オーナーパスワードで包んだ保管庫のデータ鍵（`vault-key.json`）。未作成なら None
+    pub fn load_vault_key(&self) -> Option<WrappedKey> {
+        let content = fs::read_to_string(self.root.join("vault-key.json")).ok()?;
+        serde_json::from_str(&content)
+            .inspect_err(|e| tracing::warn!("Corrupt vault-key.json: {e}"))
+            .ok()
+    }
+
+    pub fn save_vault_key(&self, wrapped: &WrappedKey) -> std::io::Result<()> {
+        self.write_json("vault-key.json", wrapped)
+    }
+
+    fn user_dir(&self, username: &str) -> PathBuf {
+        self.root.join("users").join(username)
+    }
+
+    /// Per-user settings (`users/<name>/settings.json`). The owner keeps using
+    /// `load_settings`.
+    pub fn load_user_settings(&self, username: &str) -> Settings {
+        let path = self.user_dir(username).join("settings.json");
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Corrupt settings for user {username}, using defaults: {e}");
+                Settings::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                tracing::warn!("Failed to read settings for user {username}: {e}");
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save_user_settings(&self, username: &str, settings: &Settings) -> std::io::Result<()> {
+        let dir = self.user_dir(username);
+        fs::create_dir_all(&dir)?;
+        let json = serde_json::to_string_pretty(settings).map_err(std::io::Error::other)?;
+        fs::write(dir.join("settings.json"), json)
+    }
+
+    /// Delete everything stored for a removed user.
+    pub fn remove_user_data(&self, username: &str) -> std::io::Result<()> {
+        match fs::remove_dir_all(self.user_dir(username)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn load_json_or_default<T: serde::de::DeserializeOwned + Default>(&self, name: &str) -> T {
+        match fs::read_to_string(self.root.join(name)) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Corrupt {name}, using empty: {e}");
+                T::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => T::default(),
+            Err(e) => {
+                tracing::warn!("Failed to read {name}: {e}");
+                T::default()
+            }
+        }
+    }
+
+    fn write_json<T: Serialize + ?Sized>(&self, name: &str, value: &T) -> std::io::Result<()> {
+        let json = serde_json::to_string(value).map_err(std::io::Error::other)?;
+        fs::write(self.root.join(name), json)
+    }
+
     // --- Session Order ---
 
     pub fn load_session_order(&self) -> Vec<String> {
@@ -540,6 +1253,29 @@ impl Store {
         fs::write(path, json)
     }
 
+    // --- Sync Jobs ---
+
+    pub fn load_sync_jobs(&self) -> Vec<crate::sftp::sync::SyncJob> {
+        let path = self.root.join("sync-jobs.json");
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Corrupt sync-jobs.json, using empty: {e}");
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                tracing::warn!("Failed to read sync-jobs.json: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save_sync_jobs(&self, jobs: &[crate::sftp::sync::SyncJob]) -> std::io::Result<()> {
+        let path = self.root.join("sync-jobs.json");
+        let json = serde_json::to_string_pretty(jobs).map_err(std::io::Error::other)?;
+        fs::write(path, json)
+    }
+
     // --- SSH Known Hosts ---
 
     pub fn load_known_hosts(&self) -> HashMap<String, KnownHost> {
@@ -839,6 +1575,123 @@ mod tests {
     }
 
     #[test]
+    fn api_token_expiry() {
+        let token = |expires_at: Option<&str>| ApiToken {
+            id: "t".into(),
+            name: "t".into(),
+            token_hash: String::new(),
+            scopes: vec![],
+            username: None,
+            created_at: String::new(),
+            expires_at: expires_at.map(str::to_string),
+        };
+        assert!(!token(None).is_expired());
+        assert!(token(Some("2000-01-01T00:00:00Z")).is_expired());
+        assert!(!token(Some("2999-01-01T00:00:00Z")).is_expired());
+        assert!(token(Some("not a date")).is_expired());
+    }
+
+    #[test]
+    fn hmac_secret_persists() {
+        let (store, _tmp) = temp_store();
+        let secret = store.load_or_create_hmac_secret().unwrap();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(store.load_or_create_hmac_secret().unwrap(), secret);
+
+        std::fs::write(store.root.join("hmac-secret"), "garbage").unwrap();
+        let regenerated = store.load_or_create_hmac_secret().unwrap();
+        assert_ne!(regenerated, secret);
+        assert_eq!(regenerated.len(), 32);
+    }
+
+    #[test]
+    fn owner_password_override() {
+        let (store, _tmp) = temp_store();
+        assert_eq!(store.load_owner_password_hash(), None);
+        store
+            .save_owner_password_hash("$argon2id$v=19$m=19456,t=2,p=1$aaaa$bbbb")
+            .unwrap();
+        assert_eq!(
+            store.load_owner_password_hash().as_deref(),
+            Some("$argon2id$v=19$m=19456,t=2,p=1$aaaa$bbbb")
+        );
+        // Read once and cached; a broken file is ignored on the next start
+        std::fs::write(store.root.join("owner-password"), "plaintext").unwrap();
+        assert!(store.load_owner_password_hash().is_some());
+        let reopened = Store::new(store.root.clone()).unwrap();
+        assert_eq!(reopened.load_owner_password_hash(), None);
+    }
+
+    #[test]
+    fn login_ips_are_tracked_per_account() {
+        let (store, _tmp) = temp_store();
+        let ip: std::net::IpAddr = "203.0.113.5".parse().unwrap();
+        assert!(store.record_login_ip(None, ip).unwrap());
+        assert!(!store.record_login_ip(None, ip).unwrap());
+        // Same address, different account
+        assert!(store.record_login_ip(Some("alice"), ip).unwrap());
+        // Survives a restart
+        let reopened = Store::new(store.root.clone()).unwrap();
+        assert!(!reopened.record_login_ip(Some("alice"), ip).unwrap());
+        assert!(
+            reopened
+                .record_login_ip(None, "203.0.113.6".parse().unwrap())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn known_ssh_keys_roundtrip() {
+        let (store, _tmp) = temp_store();
+        assert_eq!(store.load_known_ssh_keys(), None);
+        store.save_known_ssh_keys(&[]).unwrap();
+        assert_eq!(store.load_known_ssh_keys(), Some(Vec::new()));
+        store
+            .save_known_ssh_keys(&["ssh-ed25519 AAAA".to_string()])
+            .unwrap();
+        assert_eq!(
+            store.load_known_ssh_keys(),
+            Some(vec!["ssh-ed25519 AAAA".to_string()])
+        );
+    }
+
+    #[test]
+    fn secrets_are_sealed_on_disk() {
+        let (store, _tmp) = temp_store();
+        let key = VaultKey::generate();
+        assert_eq!(store.get_secret(&key, "sftp:a@h:22"), None);
+        store.set_secret(&key, "sftp:a@h:22", "hunter2").unwrap();
+        store.set_secret(&key, "webhook:ci", "tok").unwrap();
+        assert_eq!(
+            store.get_secret(&key, "sftp:a@h:22").as_deref(),
+            Some("hunter2")
+        );
+        assert_eq!(store.secret_names("sftp:"), vec!["sftp:a@h:22".to_string()]);
+        let on_disk = std::fs::read_to_string(store.root.join("secrets.json")).unwrap();
+        assert!(!on_disk.contains("hunter2"));
+
+        // A fresh Store (restart) reads the same file
+        let reopened = Store::new(store.root.clone()).unwrap();
+        assert_eq!(
+            reopened.get_secret(&key, "webhook:ci").as_deref(),
+            Some("tok")
+        );
+
+        let wrong = VaultKey::generate();
+        assert_eq!(store.get_secret(&wrong, "webhook:ci"), None);
+
+        assert!(store.load_vault_key().is_none());
+        store.save_vault_key(&WrappedKey::wrap(&key, "pw")).unwrap();
+        let wrapped = reopened.load_vault_key().unwrap();
+        assert_eq!(wrapped.unwrap("pw").unwrap().bytes(), key.bytes());
+
+        assert!(store.remove_secret("webhook:ci").unwrap());
+        assert!(!store.remove_secret("webhook:ci").unwrap());
+        assert_eq!(store.secret_names(""), vec!["sftp:a@h:22".to_string()]);
+    }
+
+    #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn settings_roundtrip() {
         let (store, _tmp) = temp_store();
         let mut settings = Settings::default();
@@ -1013,6 +1866,49 @@ mod tests {
 
     // --- Clipboard History tests ---
 
+    #[test]
+    fn filer_recent_dedup_and_persist() {
+        let (store, _tmp) = temp_store();
+        store.add_filer_recent("/a", "read").unwrap();
+        store.add_filer_recent("/b", "read").unwrap();
+        store.add_filer_recent("/a", "write").unwrap();
+        *store.filer_recent_cache.lock().unwrap() = None;
+        let entries = store.load_filer_recent();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "/a");
+        assert_eq!(entries[0].action, "write");
+        assert_eq!(entries[1].path, "/b");
+    }
+
+    #[test]
+    fn filer_bookmarks_crud() {
+        let (store, _tmp) = temp_store();
+        let b = store
+            .add_filer_bookmark("src".to_string(), "/src".to_string())
+            .unwrap()
+            .unwrap();
+        let updated = store
+            .update_filer_bookmark(&b.id, "code".to_string(), "/code".to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.label, "code");
+        assert!(
+            store
+                .update_filer_bookmark("missing", String::new(), String::new())
+                .unwrap()
+                .is_none()
+        );
+
+        *store.filer_bookmarks_cache.lock().unwrap() = None;
+        let loaded = store.load_filer_bookmarks();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].path, "/code");
+
+        assert!(store.remove_filer_bookmark(&b.id).unwrap());
+        assert!(!store.remove_filer_bookmark(&b.id).unwrap());
+        assert!(store.load_filer_bookmarks().is_empty());
+    }
+
     #[test]
     fn clipboard_empty_when_missing() {
         let (store, _tmp) = temp_store();
@@ -1288,6 +2184,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_get_then_check)]
     fn mux_alias_set_and_load_roundtrip() {
         let dir = std::env::temp_dir().join("den-mux-alias-test-1");
         let _ = std::fs::remove_dir_all(&dir);
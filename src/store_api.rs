@@ -1,60 +1,25 @@
 // テスト: tests/api_test.rs の Settings API セクションで統合テスト済み
 // （GET/PUT 正常系・認証必須・不正JSON・部分JSON）
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use crate::AppState;
-use crate::store::Settings;
+use crate::auth::AuthUser;
+use crate::store::{Settings, Store};
 
 // --- Bookmark password encryption (AES-256-GCM with HMAC-derived key) ---
 
 fn derive_bookmark_key(master_password: &str) -> [u8; 32] {
-    use hmac::{Hmac, KeyInit, Mac};
-    use sha2::Sha256;
-    type HmacSha256 = Hmac<Sha256>;
-    let mut mac =
-        HmacSha256::new_from_slice(b"den-bookmark-encryption-key").expect("HMAC key length");
-    mac.update(master_password.as_bytes());
-    let result = mac.finalize().into_bytes();
-    let mut key = [0u8; 32];
-    key.copy_from_slice(&result);
-    key
+    crate::vault::derive_key(b"den-bookmark-encryption-key", master_password)
 }
 
 fn encrypt_password(plain: &str, key: &[u8; 32]) -> String {
-    use aes_gcm::aead::Aead;
-    use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
-    use base64::Engine;
-    let cipher = Aes256Gcm::new_from_slice(key).expect("AES key length");
-    let nonce_bytes: [u8; 12] = rand::random();
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    let ciphertext = cipher
-        .encrypt(nonce, plain.as_bytes())
-        .expect("AES-GCM encrypt");
-    let mut combined = nonce_bytes.to_vec();
-    combined.extend_from_slice(&ciphertext);
-    base64::engine::general_purpose::STANDARD.encode(&combined)
+    crate::vault::seal(plain, key)
 }
 
 fn decrypt_password(encrypted: &str, key: &[u8; 32]) -> Result<String, String> {
-    use aes_gcm::aead::Aead;
-    use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
-    use base64::Engine;
-    let combined = base64::engine::general_purpose::STANDARD
-        .decode(encrypted)
-        .map_err(|e| format!("base64 decode: {e}"))?;
-    if combined.len() < 29 {
-        // 12 nonce + 1 plaintext min + 16 tag
-        return Err("encrypted data too short".into());
-    }
-    let (nonce_bytes, ciphertext) = combined.split_at(12);
-    let cipher = Aes256Gcm::new_from_slice(key).expect("AES key length");
-    let nonce = Nonce::from_slice(nonce_bytes);
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|_| "decryption failed (wrong password?)")?;
-    String::from_utf8(plaintext).map_err(|e| format!("utf8: {e}"))
+    crate::vault::open(encrypted, key)
 }
 
 /// Encrypt plaintext bookmark passwords for disk storage
@@ -70,6 +35,49 @@ fn encrypt_den_bookmarks(settings: &mut Settings, key: &[u8; 32]) {
     }
 }
 
+/// Re-encrypt bookmark passwords under a new key; returns whether anything changed.
+/// Passwords that don't decrypt with `old_key` are left as they are.
+fn rekey_den_bookmarks(settings: &mut Settings, old_key: &[u8; 32], new_key: &[u8; 32]) -> bool {
+    let mut changed = false;
+    if let Some(ref mut bookmarks) = settings.den_bookmarks {
+        for b in bookmarks.iter_mut() {
+            if let Some(ref pw) = b.password
+                && !pw.is_empty()
+                && let Ok(plain) = decrypt_password(pw, old_key)
+            {
+                b.password = Some(encrypt_password(&plain, new_key));
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// The bookmark key derives from the owner credential, so a password change
+/// must carry the saved bookmark passwords (owner and every user) over to the
+/// new key. Blocking.
+pub(crate) fn reencrypt_den_bookmarks(store: &Store, old_secret: &str, new_secret: &str) {
+    let old_key = derive_bookmark_key(old_secret);
+    let new_key = derive_bookmark_key(new_secret);
+    let mut settings = store.load_settings();
+    if rekey_den_bookmarks(&mut settings, &old_key, &new_key)
+        && let Err(e) = store.save_settings(&settings)
+    {
+        tracing::warn!("Failed to re-encrypt bookmark passwords: {e}");
+    }
+    for user in store.load_users() {
+        let mut settings = store.load_user_settings(&user.username);
+        if rekey_den_bookmarks(&mut settings, &old_key, &new_key)
+            && let Err(e) = store.save_user_settings(&user.username, &settings)
+        {
+            tracing::warn!(
+                "Failed to re-encrypt bookmark passwords for {}: {e}",
+                user.username
+            );
+        }
+    }
+}
+
 /// Decrypt bookmark passwords for API response (best-effort: leave encrypted on failure)
 fn decrypt_den_bookmarks(settings: &mut Settings, key: &[u8; 32]) {
     if let Some(ref mut bookmarks) = settings.den_bookmarks {
@@ -85,14 +93,33 @@ fn decrypt_den_bookmarks(settings: &mut Settings, key: &[u8; 32]) {
 }
 
 /// GET /api/settings
-pub async fn get_settings(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+///
+/// 登録ユーザーは自分専用の設定を読み書きする（オーナーは従来の settings.json）。
+#[utoipa::path(
+    get,
+    path = "/api/settings",
+    tag = "settings",
+    summary = "Settings of the signed-in account",
+    responses(
+        (status = 200, body = Settings),
+    )
+)]
+pub async fn get_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> impl IntoResponse {
     let store = state.store.clone();
-    match tokio::task::spawn_blocking(move || store.load_settings()).await {
+    match tokio::task::spawn_blocking(move || match user.username {
+        Some(name) => store.load_user_settings(&name),
+        None => store.load_settings(),
+    })
+    .await
+    {
         Ok(mut settings) => {
             settings.version = env!("CARGO_PKG_VERSION").to_string();
             settings.hostname = gethostname::gethostname().to_string_lossy().into_owned();
             // Decrypt bookmark passwords for API response
-            let key = derive_bookmark_key(&state.config.password);
+            let key = derive_bookmark_key(state.owner_credential().secret());
             decrypt_den_bookmarks(&mut settings, &key);
             Json(settings).into_response()
         }
@@ -104,8 +131,20 @@ pub async fn get_settings(State(state): State<Arc<AppState>>) -> impl IntoRespon
 }
 
 /// PUT /api/settings
+#[utoipa::path(
+    put,
+    path = "/api/settings",
+    tag = "settings",
+    summary = "Replace the settings of the signed-in account",
+    request_body = Settings,
+    responses(
+        (status = 200, description = "Saved"),
+        (status = 422, description = "A value is out of range or too long"),
+    )
+)]
 pub async fn put_settings(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
     Json(mut settings): Json<Settings>,
 ) -> impl IntoResponse {
     // Server-side validation: clamp to match frontend constraints (100–50000)
@@ -238,22 +277,92 @@ pub async fn put_settings(
             }
         }
     }
+    // Validate file_templates: limit count/size, names must be unique
+    if let Some(ref templates) = settings.file_templates {
+        if templates.len() > 50 {
+            return (StatusCode::UNPROCESSABLE_ENTITY, "too many file templates").into_response();
+        }
+        let mut names = std::collections::HashSet::new();
+        for t in templates {
+            let name = t.name.trim();
+            if name.is_empty() || name.chars().count() > 100 {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "file template name must be 1-100 chars",
+                )
+                    .into_response();
+            }
+            if !names.insert(name) {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "duplicate file template name",
+                )
+                    .into_response();
+            }
+            if t.content.len() > 1024 * 1024 {
+                return (StatusCode::UNPROCESSABLE_ENTITY, "file template too large")
+                    .into_response();
+            }
+        }
+    }
+    // 0 KB/s means "no limit" — normalize so the transfer code only sees real limits
+    if settings.transfer_rate_limit_kbps == Some(0) {
+        settings.transfer_rate_limit_kbps = None;
+    }
+    // shell / ssh_port / log_level はホスト全体の設定。オーナー以外の値は保存しない
+    if user.username.is_some() {
+        settings.shell = None;
+        settings.ssh_port = None;
+        settings.log_level = None;
+    }
+    settings.shell = settings
+        .shell
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    if settings
+        .shell
+        .as_ref()
+        .is_some_and(|s| s.len() > 4096 || s.chars().any(char::is_control))
+    {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "invalid shell").into_response();
+    }
+    settings.log_level = settings
+        .log_level
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty());
+    if let Some(ref level) = settings.log_level
+        && tracing_subscriber::EnvFilter::try_new(level).is_err()
+    {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "invalid log_level").into_response();
+    }
     // sleep_prevention_mode: enum 化により serde が不正値を拒否（422 を返す）
     settings.sleep_prevention_timeout = settings.sleep_prevention_timeout.clamp(1, 480);
+    settings.audit_retention_days = settings.audit_retention_days.clamp(1, 3650);
+    settings.login_max_attempts = settings.login_max_attempts.clamp(1, 100);
+    settings.login_window_secs = settings.login_window_secs.clamp(1, 24 * 60 * 60);
+    settings.login_ban_secs = settings.login_ban_secs.clamp(1, 7 * 24 * 60 * 60);
 
     // Encrypt bookmark passwords before saving to disk
-    let key = derive_bookmark_key(&state.config.password);
+    let key = derive_bookmark_key(state.owner_credential().secret());
     encrypt_den_bookmarks(&mut settings, &key);
 
     let store = state.store.clone();
-    let sleep_mode = settings.sleep_prevention_mode;
-    let sleep_timeout = settings.sleep_prevention_timeout;
-    match tokio::task::spawn_blocking(move || store.save_settings(&settings)).await {
-        Ok(Ok(())) => {
-            state
-                .registry
-                .update_sleep_config(sleep_mode, sleep_timeout)
-                .await;
+    let username = user.username;
+    let is_owner = username.is_none();
+    match tokio::task::spawn_blocking(move || {
+        match username {
+            Some(name) => store.save_user_settings(&name, &settings),
+            None => store.save_settings(&settings),
+        }
+        .map(|()| settings)
+    })
+    .await
+    {
+        Ok(Ok(settings)) => {
+            // スリープ抑止・シェルなどはホスト全体の設定なのでオーナーの設定だけを反映する
+            if is_owner {
+                state.live_settings.publish(settings);
+            }
             StatusCode::OK.into_response()
         }
         Ok(Err(e)) => {
@@ -297,3 +406,36 @@ pub async fn put_keep_awake(
         enabled: req.enabled,
     })
 }
+
+/// 有効なサブシステム（DEN_DISABLE_* で外したものは false）。
+/// SFTP はオーナー専用なので、ユーザーアカウントには常に false
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FeaturesResponse {
+    pub filer: bool,
+    pub sftp: bool,
+    pub remote: bool,
+    pub update: bool,
+}
+
+/// GET /api/system/features
+#[utoipa::path(
+    get,
+    path = "/api/system/features",
+    tag = "settings",
+    summary = "Subsystems enabled on this server",
+    responses(
+        (status = 200, body = FeaturesResponse),
+    )
+)]
+pub async fn get_features(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> Json<FeaturesResponse> {
+    let disabled = state.config.disabled;
+    Json(FeaturesResponse {
+        filer: !disabled.filer,
+        sftp: !disabled.sftp && user.is_owner(),
+        remote: !disabled.remote,
+        update: !disabled.update,
+    })
+}
@@ -0,0 +1,242 @@
+//! systemd integration on Linux: socket activation and `sd_notify`.
+//!
+//! With a `den.socket` unit, systemd owns the listening socket and hands it
+//! to den (`LISTEN_FDS`), so connections made while den restarts queue up
+//! instead of being refused. A socket named `admin` (`FileDescriptorName=`)
+//! serves the admin listener.
+//!
+//! Under `Type=notify`, den reports `READY=1` once it is serving and
+//! `STOPPING=1` when shutdown begins. With `WatchdogSec=` it pings at half
+//! the interval from the async runtime, so a wedged den is killed and
+//! restarted. Elsewhere, or when not started by systemd, all of this is a
+//! no-op.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+const LISTEN_FDS_START: i32 = 3;
+/// `FileDescriptorName=` of the admin listener socket
+pub const ADMIN_SOCKET_NAME: &str = "admin";
+/// Exit status after a self-update; `Restart=on-failure` brings den back up
+pub const RESTART_EXIT_CODE: i32 = 75;
+
+#[derive(Debug, Default)]
+struct SystemdEnv {
+    /// (fd, name) not yet taken
+    listen_fds: Mutex<Vec<(i32, String)>>,
+    notify_socket: Option<String>,
+    watchdog: Option<Duration>,
+}
+
+static ENV: OnceLock<SystemdEnv> = OnceLock::new();
+
+/// Read and clear the systemd variables. Call first thing in `main`, before
+/// any threads start, so terminals spawned later don't inherit them.
+pub fn init() {
+    let var = |name| std::env::var(name).ok();
+    let pid = std::process::id();
+    let env = SystemdEnv {
+        listen_fds: Mutex::new(parse_listen_fds(
+            var("LISTEN_PID").as_deref(),
+            var("LISTEN_FDS").as_deref(),
+            var("LISTEN_FDNAMES").as_deref(),
+            pid,
+        )),
+        notify_socket: var("NOTIFY_SOCKET").filter(|s| !s.is_empty()),
+        watchdog: watchdog_interval(
+            var("WATCHDOG_USEC").as_deref(),
+            var("WATCHDOG_PID").as_deref(),
+            pid,
+        ),
+    };
+    for name in [
+        "LISTEN_PID",
+        "LISTEN_FDS",
+        "LISTEN_FDNAMES",
+        "NOTIFY_SOCKET",
+        "WATCHDOG_USEC",
+        "WATCHDOG_PID",
+    ] {
+        // SAFETY: main() からスレッド生成前に一度だけ呼ぶ
+        unsafe { std::env::remove_var(name) };
+    }
+    let _ = ENV.set(env);
+}
+
+fn env() -> Option<&'static SystemdEnv> {
+    ENV.get()
+}
+
+/// Sockets passed to this process, per `sd_listen_fds(3)`. Unnamed sockets
+/// (`unknown`) are treated like any other non-admin name.
+fn parse_listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    names: Option<&str>,
+    pid: u32,
+) -> Vec<(i32, String)> {
+    if listen_pid.and_then(|p| p.trim().parse::<u32>().ok()) != Some(pid) {
+        return Vec::new();
+    }
+    let count = listen_fds
+        .and_then(|n| n.trim().parse::<i32>().ok())
+        .unwrap_or(0);
+    let mut names = names.unwrap_or_default().split(':');
+    (0..count.max(0))
+        .map(|i| {
+            let name = names
+                .next()
+                .filter(|n| !n.is_empty())
+                .unwrap_or("unknown")
+                .to_string();
+            (LISTEN_FDS_START + i, name)
+        })
+        .collect()
+}
+
+/// Ping interval: half of `WATCHDOG_USEC`, if the watchdog is meant for us
+fn watchdog_interval(usec: Option<&str>, watchdog_pid: Option<&str>, pid: u32) -> Option<Duration> {
+    if let Some(watchdog_pid) = watchdog_pid
+        && watchdog_pid.trim().parse::<u32>().ok() != Some(pid)
+    {
+        return None;
+    }
+    let usec = usec?.trim().parse::<u64>().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec) / 2)
+}
+
+/// Whether den runs under a `Type=notify` systemd service
+pub fn is_supervised() -> bool {
+    cfg!(target_os = "linux") && env().is_some_and(|env| env.notify_socket.is_some())
+}
+
+/// Take the activated socket for the main listener (`admin = false`) or the
+/// admin listener. None = not socket-activated; bind the port as usual.
+pub fn take_listener(admin: bool) -> Option<std::io::Result<tokio::net::TcpListener>> {
+    let mut fds = env()?.listen_fds.lock().unwrap_or_else(|e| e.into_inner());
+    let index = fds
+        .iter()
+        .position(|(_, name)| (name == ADMIN_SOCKET_NAME) == admin)?;
+    let (fd, _) = fds.remove(index);
+    Some(imp::listener_from_fd(fd))
+}
+
+/// Send a state string (`READY=1`, `STATUS=...`) to systemd. Failures are
+/// logged and otherwise ignored.
+pub fn notify(state: &str) {
+    let Some(socket) = env().and_then(|env| env.notify_socket.as_deref()) else {
+        return;
+    };
+    if let Err(e) = imp::notify(socket, state) {
+        tracing::warn!("sd_notify failed: {e}");
+    }
+}
+
+/// Ping the systemd watchdog from the runtime (only with `WatchdogSec=`)
+pub fn spawn_watchdog() -> Option<tokio::task::JoinHandle<()>> {
+    let interval = env()?.watchdog?;
+    if !is_supervised() {
+        return None;
+    }
+    tracing::info!("systemd watchdog: every {}ms", interval.as_millis());
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    }))
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::io;
+    use std::os::fd::FromRawFd;
+
+    pub fn listener_from_fd(fd: i32) -> io::Result<tokio::net::TcpListener> {
+        // SAFETY: systemd が渡した fd は LISTEN_FDS の範囲内で、ここで一度だけ所有する
+        let inherited = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        // systemd は CLOEXEC なしで渡すので、複製（F_DUPFD_CLOEXEC）して元を閉じ、
+        // シェルなどの子プロセスに待ち受けソケットを継承させない
+        let listener = inherited.try_clone()?;
+        drop(inherited);
+        listener.set_nonblocking(true)?;
+        tokio::net::TcpListener::from_std(listener)
+    }
+
+    pub fn notify(socket: &str, state: &str) -> io::Result<()> {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+        let addr = match socket.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(socket)?,
+        };
+        UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+
+    pub fn listener_from_fd(_fd: i32) -> io::Result<tokio::net::TcpListener> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "socket activation is only supported on Linux",
+        ))
+    }
+
+    pub fn notify(_socket: &str, _state: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_fds_are_only_taken_when_meant_for_us() {
+        assert_eq!(
+            parse_listen_fds(Some("42"), Some("2"), Some("den:admin"), 42),
+            vec![(3, "den".to_string()), (4, "admin".to_string())]
+        );
+        assert_eq!(
+            parse_listen_fds(Some("42"), Some("1"), None, 42),
+            vec![(3, "unknown".to_string())]
+        );
+        // Inherited from a parent that was socket-activated
+        assert!(parse_listen_fds(Some("41"), Some("1"), None, 42).is_empty());
+        assert!(parse_listen_fds(None, None, None, 42).is_empty());
+    }
+
+    #[test]
+    fn watchdog_pings_at_half_the_timeout() {
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(watchdog_interval(Some("30000000"), Some("41"), 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(None, None, 42), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn notify_sends_a_datagram() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        imp::notify(path.to_str().unwrap(), "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
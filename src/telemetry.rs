@@ -0,0 +1,244 @@
+//! OpenTelemetry export over OTLP/HTTP (`DEN_OTLP_ENDPOINT`).
+//!
+//! When enabled, `tracing` spans are exported as traces: one span per HTTP
+//! request (continuing an incoming W3C `traceparent`), with child spans for
+//! PTY attach and SFTP operations. The counters behind `/metrics` are pushed
+//! as OTLP metrics every `METRICS_INTERVAL`. Disabled, nothing here runs and
+//! the request middleware is a pass-through.
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::{HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::AppState;
+use crate::metrics;
+
+pub const DEFAULT_SERVICE_NAME: &str = "den";
+const METRICS_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtlpConfig {
+    /// Collector base URL, e.g. `http://localhost:4318`
+    /// (`/v1/traces` and `/v1/metrics` are appended)
+    pub endpoint: String,
+    /// Extra request headers (`DEN_OTLP_HEADERS=key=value,...`), e.g. an API key
+    pub headers: Vec<(String, String)>,
+    pub service_name: String,
+}
+
+impl OtlpConfig {
+    fn signal_url(&self, signal: &str) -> String {
+        format!("{}/v1/{signal}", self.endpoint.trim_end_matches('/'))
+    }
+}
+
+/// Running exporters. Call `shutdown` before exiting so buffered spans and
+/// the last metric readings are sent.
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Telemetry {
+    pub fn init(config: &OtlpConfig) -> Result<Self, String> {
+        let headers: std::collections::HashMap<String, String> =
+            config.headers.iter().cloned().collect();
+        let resource = Resource::builder()
+            .with_service_name(config.service_name.clone())
+            .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+            .build();
+
+        let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(config.signal_url("traces"))
+            .with_headers(headers.clone())
+            .build()
+            .map_err(|e| format!("OTLP trace exporter: {e}"))?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(span_exporter)
+            .with_resource(resource.clone())
+            .build();
+
+        let metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .with_endpoint(config.signal_url("metrics"))
+            .with_headers(headers)
+            .build()
+            .map_err(|e| format!("OTLP metric exporter: {e}"))?;
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(
+                PeriodicReader::builder(metric_exporter)
+                    .with_interval(METRICS_INTERVAL)
+                    .build(),
+            )
+            .with_resource(resource)
+            .build();
+        register_instruments(&meter_provider);
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        Ok(Self {
+            tracer_provider,
+            meter_provider,
+        })
+    }
+
+    /// Tracer for the `tracing_opentelemetry` layer
+    pub fn tracer(&self) -> SdkTracer {
+        self.tracer_provider.tracer("den")
+    }
+
+    pub fn shutdown(&self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("OTLP trace export shutdown failed: {e}");
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            eprintln!("OTLP metric export shutdown failed: {e}");
+        }
+    }
+}
+
+fn register_instruments(provider: &SdkMeterProvider) {
+    let meter = provider.meter("den");
+    let counter =
+        |name: &'static str, unit: &'static str, help: &'static str, v: &'static AtomicU64| {
+            meter
+                .u64_observable_counter(name)
+                .with_unit(unit)
+                .with_description(help)
+                .with_callback(move |obs| obs.observe(v.load(Ordering::Relaxed), &[]))
+                .build();
+        };
+    counter(
+        "den.pty.output",
+        "By",
+        "Bytes read from terminal processes",
+        &metrics::PTY_OUTPUT_BYTES,
+    );
+    counter(
+        "den.pty.input",
+        "By",
+        "Bytes written to terminal processes",
+        &metrics::PTY_INPUT_BYTES,
+    );
+    counter(
+        "den.ssh.auth_failures",
+        "{failure}",
+        "Rejected SSH password and public key logins",
+        &metrics::SSH_AUTH_FAILURES,
+    );
+    meter
+        .u64_observable_gauge("den.websocket.connections")
+        .with_unit("{connection}")
+        .with_description("Open WebSocket connections")
+        .with_callback(|obs| {
+            for (endpoint, gauge) in [
+                ("terminal", &metrics::TERMINAL_SOCKETS),
+                ("events", &metrics::EVENT_SOCKETS),
+                ("remote", &metrics::REMOTE_SOCKETS),
+            ] {
+                obs.observe(
+                    gauge.load(Ordering::Relaxed),
+                    &[KeyValue::new("endpoint", endpoint)],
+                );
+            }
+        })
+        .build();
+    meter
+        .u64_observable_counter("den.http.responses")
+        .with_unit("{response}")
+        .with_description("HTTP responses by status class")
+        .with_callback(|obs| {
+            for (i, count) in metrics::http_responses().into_iter().enumerate() {
+                obs.observe(count, &[KeyValue::new("code", format!("{}xx", i + 1))]);
+            }
+        })
+        .build();
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// One `http.request` span per request while OTLP export is on. The span is
+/// named after the route pattern, so `/api/sftp/list?path=...` doesn't leak
+/// paths into span names.
+pub async fn trace_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    if state.config.otlp.is_none() {
+        return next.run(req).await;
+    }
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string());
+    let name = match route {
+        Some(ref route) => format!("{} {route}", req.method()),
+        None => req.method().to_string(),
+    };
+    let span = tracing::info_span!(
+        "http.request",
+        otel.name = %name,
+        otel.kind = "server",
+        http.request.method = %req.method(),
+        http.route = route.as_deref(),
+        url.path = req.uri().path(),
+        http.response.status_code = tracing::field::Empty,
+    );
+    let parent = opentelemetry::global::get_text_map_propagator(|p| {
+        p.extract(&HeaderExtractor(req.headers()))
+    });
+    let _ = span.set_parent(parent);
+    let resp = next.run(req).instrument(span.clone()).await;
+    span.record("http.response.status_code", resp.status().as_u16());
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_urls_append_the_otlp_paths() {
+        let config = OtlpConfig {
+            endpoint: "http://collector:4318/".into(),
+            headers: Vec::new(),
+            service_name: DEFAULT_SERVICE_NAME.into(),
+        };
+        assert_eq!(
+            config.signal_url("traces"),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            config.signal_url("metrics"),
+            "http://collector:4318/v1/metrics"
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::Once;
+use std::sync::{Once, RwLock};
 
 use axum::extract::connect_info::ConnectInfo;
 use axum::extract::{Query, State};
@@ -10,8 +10,11 @@ use axum::response::{IntoResponse, Response};
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::service::TowerToHyperService;
 use rcgen::generate_simple_self_signed;
-use rustls::ServerConfig;
+use rustls::pki_types::pem::PemObject;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
@@ -26,6 +29,8 @@ use crate::store::TrustedTlsCert;
 const DEFAULT_CERT_FILENAME: &str = "server-cert.der";
 const DEFAULT_KEY_FILENAME: &str = "server-key.der";
 const DEFAULT_META_FILENAME: &str = "server-cert.json";
+/// h2 first: clients that speak it multiplex requests over one connection
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
 static INSTALL_RUSTLS_PROVIDER: Once = Once::new();
 
 #[derive(Debug, Clone, Serialize)]
@@ -38,11 +43,39 @@ pub struct TlsInfo {
     pub generated: bool,
 }
 
+/// mTLS で検証済みのクライアント証明書。`serve` がリクエストごとに extensions へ入れる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertIdentity {
+    /// Subject の CN（den のアカウントに対応付ける）
+    pub common_name: Option<String>,
+    pub fingerprint: String,
+}
+
+impl ClientCertIdentity {
+    pub fn from_der(certificate_der: &[u8]) -> Self {
+        let common_name = x509_parser::parse_x509_certificate(certificate_der)
+            .ok()
+            .and_then(|(_, cert)| {
+                cert.subject()
+                    .iter_common_name()
+                    .next()
+                    .and_then(|cn| cn.as_str().ok())
+                    .map(str::to_string)
+            });
+        Self {
+            common_name,
+            fingerprint: sha256_fingerprint(certificate_der),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TlsRuntime {
     pub server_config: Arc<ServerConfig>,
     pub info: TlsInfo,
     pub certificate_der: Vec<u8>,
+    /// ACME 有効時のみ。更新した証明書をここから差し込む
+    pub reloadable_cert: Option<Arc<ReloadableCert>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -96,10 +129,17 @@ pub fn setup(config: &Config) -> Result<Option<TlsRuntime>, String> {
 
     let requested_sans = build_subject_alt_names(config);
     let data_dir = PathBuf::from(&config.data_dir);
+    // ACME で取得済みの証明書。初回の取得が終わるまでは自己署名で待つ
+    let acme_paths = config
+        .acme
+        .as_ref()
+        .map(|_| crate::acme::certificate_paths(&data_dir))
+        .filter(|(cert, key)| cert.exists() && key.exists());
     let (cert_path, key_path, meta_path, generated) =
-        match (&config.tls_cert_path, &config.tls_key_path) {
-            (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key), None, false),
-            (None, None) => {
+        match (&config.tls_cert_path, &config.tls_key_path, acme_paths) {
+            (Some(cert), Some(key), _) => (PathBuf::from(cert), PathBuf::from(key), None, false),
+            (None, None, Some((cert, key))) => (cert, key, None, false),
+            (None, None, None) => {
                 let tls_dir = data_dir.join("tls");
                 (
                     tls_dir.join(DEFAULT_CERT_FILENAME),
@@ -115,32 +155,49 @@ pub fn setup(config: &Config) -> Result<Option<TlsRuntime>, String> {
             }
         };
 
-    let (certificate_der, private_key_der) = if generated {
-        load_or_generate_self_signed(&cert_path, &key_path, meta_path.as_deref(), &requested_sans)?
+    let (certificate_chain, private_key) = if generated {
+        let (certificate_der, private_key_der) = load_or_generate_self_signed(
+            &cert_path,
+            &key_path,
+            meta_path.as_deref(),
+            &requested_sans,
+        )?;
+        (
+            vec![CertificateDer::from(certificate_der)],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(private_key_der)),
+        )
     } else {
         (
-            std::fs::read(&cert_path).map_err(|e| {
-                format!(
-                    "failed to read TLS certificate {}: {e}",
-                    cert_path.display()
-                )
-            })?,
-            std::fs::read(&key_path).map_err(|e| {
-                format!("failed to read TLS private key {}: {e}", key_path.display())
-            })?,
+            load_certificate_chain(&cert_path)?,
+            load_private_key(&key_path)?,
         )
     };
+    let certificate_der = certificate_chain[0].to_vec();
 
     let fingerprint = sha256_fingerprint(&certificate_der);
-    let server_config = Arc::new(
-        ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(
-                vec![CertificateDer::from(certificate_der.clone())],
-                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(private_key_der)),
-            )
+    let builder = match config.tls_client_ca {
+        Some(ref ca_path) => {
+            ServerConfig::builder().with_client_cert_verifier(client_cert_verifier(ca_path)?)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+    let reloadable_cert = match config.acme {
+        Some(_) => Some(Arc::new(
+            ReloadableCert::new(certificate_chain.clone(), private_key.clone_key())
+                .map_err(|e| format!("TLS certificate {}: {e}", cert_path.display()))?,
+        )),
+        None => None,
+    };
+    let mut server_config = match reloadable_cert {
+        Some(ref cert) => builder.with_cert_resolver(cert.clone()),
+        None => builder
+            .with_single_cert(certificate_chain, private_key)
             .map_err(|e| format!("failed to build TLS server config: {e}"))?,
-    );
+    };
+    // ブラウザは ALPN で h2 を選び、API リクエストを 1 接続に多重化する。
+    // WebSocket は extended CONNECT を広告しないので別の HTTP/1.1 接続で Upgrade される
+    server_config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    let server_config = Arc::new(server_config);
 
     Ok(Some(TlsRuntime {
         server_config,
@@ -153,10 +210,117 @@ pub fn setup(config: &Config) -> Result<Option<TlsRuntime>, String> {
             generated,
         },
         certificate_der,
+        reloadable_cert,
     }))
 }
 
-fn install_crypto_provider() {
+fn is_pem(bytes: &[u8]) -> bool {
+    bytes.trim_ascii_start().starts_with(b"-----BEGIN")
+}
+
+/// 明示指定の証明書。PEM ならチェーン全体（certbot の fullchain.pem など、先頭がサーバー証明書）、
+/// それ以外は DER 1 枚
+fn load_certificate_chain(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("failed to read TLS certificate {}: {e}", path.display()))?;
+    if !is_pem(&bytes) {
+        return Ok(vec![CertificateDer::from(bytes)]);
+    }
+    pem_chain(&bytes).map_err(|e| format!("TLS certificate {}: {e}", path.display()))
+}
+
+fn pem_chain(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, String> {
+    let chain = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate: {e}"))?;
+    if chain.is_empty() {
+        return Err("no certificates found".to_string());
+    }
+    Ok(chain)
+}
+
+/// 実行中に差し替えられるサーバー証明書（ACME の自動更新用）。
+/// 差し替え後のハンドシェイクから新しい証明書を使う
+#[derive(Debug)]
+pub struct ReloadableCert {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadableCert {
+    fn new(
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            current: RwLock::new(certified_key(chain, key)?),
+        })
+    }
+
+    /// PEM のチェーン（先頭がサーバー証明書）と秘密鍵に差し替える
+    pub fn replace_pem(&self, chain_pem: &[u8], key_pem: &[u8]) -> Result<(), String> {
+        let chain = pem_chain(chain_pem)?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem)
+            .map_err(|e| format!("invalid private key: {e}"))?;
+        *self.current.write().unwrap() = certified_key(chain, key)?;
+        Ok(())
+    }
+
+    /// 現在のサーバー証明書（DER）
+    pub fn certificate_der(&self) -> Vec<u8> {
+        self.current.read().unwrap().cert[0].to_vec()
+    }
+}
+
+fn certified_key(
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<Arc<CertifiedKey>, String> {
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|e| format!("unsupported private key: {e}"))?;
+    Ok(Arc::new(CertifiedKey::new(chain, signing_key)))
+}
+
+impl ResolvesServerCert for ReloadableCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// 明示指定の秘密鍵。PEM（PKCS#8 / PKCS#1 / SEC1）か PKCS#8 DER
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("failed to read TLS private key {}: {e}", path.display()))?;
+    if !is_pem(&bytes) {
+        return Ok(PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(bytes)));
+    }
+    PrivateKeyDer::from_pem_slice(&bytes)
+        .map_err(|e| format!("invalid TLS private key {}: {e}", path.display()))
+}
+
+/// DEN_TLS_CLIENT_CA の CA が署名したクライアント証明書を必須にする
+fn client_cert_verifier(
+    ca_path: &str,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_path)
+        .map_err(|e| format!("failed to read DEN_TLS_CLIENT_CA {ca_path}: {e}"))?
+    {
+        let cert = cert.map_err(|e| format!("invalid DEN_TLS_CLIENT_CA {ca_path}: {e}"))?;
+        roots
+            .add(cert)
+            .map_err(|e| format!("invalid DEN_TLS_CLIENT_CA {ca_path}: {e}"))?;
+    }
+    if roots.is_empty() {
+        return Err(format!(
+            "DEN_TLS_CLIENT_CA {ca_path}: no certificates found"
+        ));
+    }
+    WebPkiClientVerifier::builder(Arc::new(roots))
+        .build()
+        .map_err(|e| format!("failed to build client certificate verifier: {e}"))
+}
+
+pub(crate) fn install_crypto_provider() {
     INSTALL_RUSTLS_PROVIDER.call_once(|| {
         let _ = rustls::crypto::ring::default_provider().install_default();
     });
@@ -477,12 +641,16 @@ pub async fn serve(
     let acceptor = TlsAcceptor::from(server_config);
     let mut make_service = app.into_make_service();
     tokio::pin!(shutdown);
+    // 終了時に各接続へ graceful shutdown を伝え、処理中のリクエストを待つ
+    let (closing_tx, closing_rx) = tokio::sync::watch::channel(false);
+    let mut connections = tokio::task::JoinSet::new();
 
     loop {
         tokio::select! {
             _ = &mut shutdown => {
                 break;
             }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
             accepted = listener.accept() => {
                 let (tcp_stream, remote_addr) = accepted
                     .map_err(|e| format!("TLS accept failed: {e}"))?;
@@ -491,8 +659,9 @@ pub async fn serve(
                     Ok(service) => service,
                     Err(err) => match err {},
                 };
+                let mut closing = closing_rx.clone();
 
-                tokio::spawn(async move {
+                connections.spawn(async move {
                     let tls_stream = match tls_acceptor.accept(tcp_stream).await {
                         Ok(stream) => stream,
                         Err(err) => {
@@ -501,16 +670,36 @@ pub async fn serve(
                         }
                     };
 
+                    // mTLS: the verified client certificate (only when DEN_TLS_CLIENT_CA is set)
+                    let client_cert = tls_stream
+                        .get_ref()
+                        .1
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .map(|cert| ClientCertIdentity::from_der(cert));
+
                     // Inject ConnectInfo so handlers can access the remote address
                     let service = tower::ServiceExt::map_request(service, move |mut req: axum::http::Request<_>| {
                         req.extensions_mut().insert(ConnectInfo(remote_addr));
+                        if let Some(ref identity) = client_cert {
+                            req.extensions_mut().insert(identity.clone());
+                        }
                         req
                     });
 
                     let io = TokioIo::new(tls_stream);
                     let builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
                     let hyper_service = TowerToHyperService::new(service);
-                    if let Err(err) = builder.serve_connection_with_upgrades(io, hyper_service).await {
+                    let conn = builder.serve_connection_with_upgrades(io, hyper_service);
+                    tokio::pin!(conn);
+                    let result = tokio::select! {
+                        result = conn.as_mut() => result,
+                        _ = closing.changed() => {
+                            conn.as_mut().graceful_shutdown();
+                            conn.await
+                        }
+                    };
+                    if let Err(err) = result {
                         let msg = err.to_string();
                         if msg.contains("close_notify") {
                             tracing::debug!(%remote_addr, "TLS connection closed without close_notify");
@@ -523,6 +712,9 @@ pub async fn serve(
         }
     }
 
+    drop(listener);
+    let _ = closing_tx.send(true);
+    while connections.join_next().await.is_some() {}
     Ok(())
 }
 
@@ -536,16 +728,38 @@ mod tests {
         Config {
             port: 8080,
             password: "pw".to_string(),
+            password_hash: None,
             shell: "sh".to_string(),
             env: Environment::Development,
             log_level: "info".to_string(),
+            log_files: Default::default(),
             data_dir: data_dir.display().to_string(),
             bind_address: "0.0.0.0".to_string(),
             ssh_port: None,
+            local_socket: None,
             tls_enabled: true,
             tls_cert_path: None,
             tls_key_path: None,
             tls_subject_alt_names: vec!["10.0.0.2".to_string(), "den-a".to_string()],
+            tls_client_ca: None,
+            tls_client_owner_cn: None,
+            acme: None,
+            filer_roots: Vec::new(),
+            token_ttl_hours: 24,
+            persist_secret: false,
+            allow_cidrs: Vec::new(),
+            deny_cidrs: Vec::new(),
+            trusted_proxies: Vec::new(),
+            oidc: None,
+            vault_keyfile: None,
+            metrics_token: None,
+            admin_listener: None,
+            otlp: None,
+            disabled: Default::default(),
+            csp: Default::default(),
+            security_headers: Default::default(),
+            cors: None,
+            notify: Default::default(),
         }
     }
 
@@ -646,4 +860,229 @@ mod tests {
         assert!(!is_valid_fingerprint(&format!("SHA256:{}", "g".repeat(64))));
         assert!(!is_valid_fingerprint(&"a".repeat(64)));
     }
+
+    /// CA と、その CA が署名した CN 付きクライアント証明書（DER, PKCS#8 鍵）
+    fn client_ca_and_cert(common_name: &str) -> (String, Vec<u8>, Vec<u8>) {
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca =
+            rcgen::CertifiedIssuer::self_signed(ca_params, rcgen::KeyPair::generate().unwrap())
+                .unwrap();
+        let mut params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, common_name);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &ca).unwrap();
+        (ca.pem(), cert.der().to_vec(), key.serialize_der())
+    }
+
+    /// ハンドシェイクしてサーバー側の結果を返す
+    async fn handshake(
+        runtime: &TlsRuntime,
+        client_cert: Option<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<Option<ClientCertIdentity>, String> {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(runtime.certificate_der.clone()))
+            .unwrap();
+        let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+        let client_config = match client_cert {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from(cert)],
+                    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let acceptor = TlsAcceptor::from(runtime.server_config.clone());
+        let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+        let server_name = rustls::pki_types::ServerName::try_from("den-a").unwrap();
+        let (server, _client) = tokio::join!(
+            acceptor.accept(server_io),
+            connector.connect(server_name, client_io)
+        );
+        let stream = server.map_err(|e| e.to_string())?;
+        Ok(stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| ClientCertIdentity::from_der(cert)))
+    }
+
+    #[tokio::test]
+    async fn client_ca_requires_signed_client_certificate() {
+        install_crypto_provider();
+        let dir = tempdir().unwrap();
+        let (ca_pem, cert_der, key_der) = client_ca_and_cert("alice");
+        let ca_path = dir.path().join("client-ca.pem");
+        std::fs::write(&ca_path, ca_pem).unwrap();
+
+        let mut config = base_config(dir.path());
+        config.tls_client_ca = Some(ca_path.display().to_string());
+        let runtime = setup(&config).unwrap().unwrap();
+
+        let identity = handshake(&runtime, Some((cert_der.clone(), key_der.clone())))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.common_name.as_deref(), Some("alice"));
+        assert_eq!(identity.fingerprint, sha256_fingerprint(&cert_der));
+        assert!(handshake(&runtime, None).await.is_err());
+
+        // A certificate from some other CA is refused too
+        let (_, other_cert, other_key) = client_ca_and_cert("alice");
+        assert!(
+            handshake(&runtime, Some((other_cert, other_key)))
+                .await
+                .is_err()
+        );
+
+        // Without DEN_TLS_CLIENT_CA no certificate is asked for
+        config.tls_client_ca = None;
+        let runtime = setup(&config).unwrap().unwrap();
+        assert_eq!(handshake(&runtime, None).await.unwrap(), None);
+    }
+
+    async fn negotiated_protocol(runtime: &TlsRuntime, offered: &[&[u8]]) -> Option<Vec<u8>> {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(runtime.certificate_der.clone()))
+            .unwrap();
+        let mut client_config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = offered.iter().map(|p| p.to_vec()).collect();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let acceptor = TlsAcceptor::from(runtime.server_config.clone());
+        let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+        let server_name = rustls::pki_types::ServerName::try_from("den-a").unwrap();
+        let (server, _client) = tokio::join!(
+            acceptor.accept(server_io),
+            connector.connect(server_name, client_io)
+        );
+        server
+            .unwrap()
+            .get_ref()
+            .1
+            .alpn_protocol()
+            .map(<[u8]>::to_vec)
+    }
+
+    #[tokio::test]
+    async fn alpn_prefers_h2_and_keeps_http1() {
+        install_crypto_provider();
+        let dir = tempdir().unwrap();
+        let runtime = setup(&base_config(dir.path())).unwrap().unwrap();
+
+        assert_eq!(
+            negotiated_protocol(&runtime, &[b"http/1.1", b"h2"]).await,
+            Some(b"h2".to_vec())
+        );
+        // WebSocket を開くブラウザ接続など h1 のみのクライアント
+        assert_eq!(
+            negotiated_protocol(&runtime, &[b"http/1.1"]).await,
+            Some(b"http/1.1".to_vec())
+        );
+        assert_eq!(negotiated_protocol(&runtime, &[]).await, None);
+    }
+
+    #[test]
+    fn setup_loads_explicit_pem_chain_and_der_identity() {
+        let dir = tempdir().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca =
+            rcgen::CertifiedIssuer::self_signed(ca_params, rcgen::KeyPair::generate().unwrap())
+                .unwrap();
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["den.example".to_string()])
+            .unwrap()
+            .signed_by(&key, &ca)
+            .unwrap();
+        let cert_path = dir.path().join("fullchain.pem");
+        let key_path = dir.path().join("privkey.pem");
+        std::fs::write(&cert_path, format!("{}{}", cert.pem(), ca.pem())).unwrap();
+        std::fs::write(&key_path, key.serialize_pem()).unwrap();
+
+        let mut config = base_config(dir.path());
+        config.tls_cert_path = Some(cert_path.display().to_string());
+        config.tls_key_path = Some(key_path.display().to_string());
+        let runtime = setup(&config).unwrap().unwrap();
+        assert!(!runtime.info.generated);
+        assert_eq!(runtime.certificate_der, cert.der().to_vec());
+        assert_eq!(runtime.info.fingerprint, sha256_fingerprint(cert.der()));
+        assert!(!dir.path().join("tls").exists());
+
+        // DER (single certificate, PKCS#8 key) still works
+        std::fs::write(&cert_path, cert.der()).unwrap();
+        std::fs::write(&key_path, key.serialize_der()).unwrap();
+        let runtime = setup(&config).unwrap().unwrap();
+        assert_eq!(runtime.certificate_der, cert.der().to_vec());
+
+        std::fs::write(&cert_path, "-----BEGIN NOTHING-----\n").unwrap();
+        assert!(setup(&config).is_err());
+    }
+
+    #[test]
+    fn acme_serves_stored_certificate_and_swaps_renewals() {
+        let dir = tempdir().unwrap();
+        let mut config = base_config(dir.path());
+        config.acme = Some(crate::acme::AcmeConfig {
+            domains: vec!["den.example".to_string()],
+            email: None,
+            directory_url: crate::acme::LETS_ENCRYPT_DIRECTORY.to_string(),
+            http_port: 80,
+        });
+
+        // Before the first order: the self-signed identity, but swappable
+        let runtime = setup(&config).unwrap().unwrap();
+        assert!(runtime.info.generated);
+        let reloadable = runtime.reloadable_cert.unwrap();
+        assert_eq!(reloadable.certificate_der(), runtime.certificate_der);
+
+        let issue = || {
+            let key = rcgen::KeyPair::generate().unwrap();
+            let cert = rcgen::CertificateParams::new(vec!["den.example".to_string()])
+                .unwrap()
+                .self_signed(&key)
+                .unwrap();
+            (cert, key)
+        };
+        let (cert, key) = issue();
+        reloadable
+            .replace_pem(cert.pem().as_bytes(), key.serialize_pem().as_bytes())
+            .unwrap();
+        assert_eq!(reloadable.certificate_der(), cert.der().to_vec());
+        assert!(reloadable.replace_pem(b"", b"").is_err());
+
+        // A stored ACME certificate is used from startup
+        let (cert, key) = issue();
+        let (cert_path, key_path) = crate::acme::certificate_paths(dir.path());
+        std::fs::create_dir_all(cert_path.parent().unwrap()).unwrap();
+        std::fs::write(&cert_path, cert.pem()).unwrap();
+        std::fs::write(&key_path, key.serialize_pem()).unwrap();
+        let runtime = setup(&config).unwrap().unwrap();
+        assert!(!runtime.info.generated);
+        assert_eq!(runtime.certificate_der, cert.der().to_vec());
+        assert!(runtime.reloadable_cert.is_some());
+
+        config.acme = None;
+        assert!(setup(&config).unwrap().unwrap().reloadable_cert.is_none());
+    }
+
+    #[test]
+    fn client_ca_must_contain_certificates() {
+        let dir = tempdir().unwrap();
+        let ca_path = dir.path().join("client-ca.pem");
+        std::fs::write(&ca_path, "not a certificate").unwrap();
+        let mut config = base_config(dir.path());
+        config.tls_client_ca = Some(ca_path.display().to_string());
+        assert!(setup(&config).is_err());
+        config.tls_client_ca = Some(dir.path().join("missing.pem").display().to_string());
+        assert!(setup(&config).is_err());
+    }
 }
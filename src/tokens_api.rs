@@ -0,0 +1,197 @@
+// API トークン管理（対話ログインのみ: API トークン自身はこのルートのスコープを持てない）
+// テスト: tests/api_test.rs の API tokens セクション
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::auth::{API_TOKEN_PREFIX, AuthUser, hash_api_token, is_valid_scope};
+use crate::store::{ApiToken, MAX_API_TOKENS};
+
+const MAX_TOKEN_NAME_CHARS: usize = 100;
+const MAX_SCOPES: usize = 32;
+const MAX_EXPIRES_IN_DAYS: u32 = 3650;
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// 省略時は失効するまで有効
+    #[serde(default)]
+    pub expires_in_days: Option<u32>,
+}
+
+/// トークン一覧用（ハッシュは返さない）
+#[derive(Serialize)]
+pub struct TokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+}
+
+impl From<&ApiToken> for TokenInfo {
+    fn from(t: &ApiToken) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            scopes: t.scopes.clone(),
+            created_at: t.created_at.clone(),
+            expires_at: t.expires_at.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CreateTokenResponse {
+    #[serde(flatten)]
+    pub info: TokenInfo,
+    /// 平文トークン。再表示できないので発行時に控えてもらう
+    pub token: String,
+}
+
+/// GET /api/tokens — 自分が発行したトークン
+pub async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> impl IntoResponse {
+    let store = state.store.clone();
+    match tokio::task::spawn_blocking(move || store.load_api_tokens()).await {
+        Ok(tokens) => Json(
+            tokens
+                .iter()
+                .filter(|t| t.username == user.username)
+                .map(TokenInfo::from)
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => {
+            tracing::error!("load_api_tokens task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /api/tokens
+pub async fn create_token(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Json(req): Json<CreateTokenRequest>,
+) -> impl IntoResponse {
+    let name = req.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_CHARS {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "token name must be 1-100 chars",
+        )
+            .into_response();
+    }
+    if req.scopes.is_empty() || req.scopes.len() > MAX_SCOPES {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "1-32 scopes required").into_response();
+    }
+    if let Some(bad) = req.scopes.iter().find(|s| !is_valid_scope(s)) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("unknown scope: {bad}"),
+        )
+            .into_response();
+    }
+    if req
+        .expires_in_days
+        .is_some_and(|d| d == 0 || d > MAX_EXPIRES_IN_DAYS)
+    {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "expires_in_days must be 1-3650",
+        )
+            .into_response();
+    }
+
+    let now = chrono::Utc::now();
+    let token = format!(
+        "{API_TOKEN_PREFIX}{}",
+        hex::encode(rand::random::<[u8; 32]>())
+    );
+    let api_token = ApiToken {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        token_hash: hash_api_token(&token),
+        scopes: req.scopes,
+        username: user.username,
+        created_at: now.to_rfc3339(),
+        expires_at: req
+            .expires_in_days
+            .map(|d| (now + chrono::Duration::days(d.into())).to_rfc3339()),
+    };
+    let info = TokenInfo::from(&api_token);
+
+    let store = state.store.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        store.update_api_tokens(|tokens| {
+            // 期限切れのトークンはここで掃除する
+            tokens.retain(|t| !t.is_expired());
+            if tokens.len() >= MAX_API_TOKENS {
+                return false;
+            }
+            tokens.push(api_token);
+            true
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(true)) => {
+            tracing::info!("API token issued: {} {:?}", info.name, info.scopes);
+            (
+                StatusCode::CREATED,
+                Json(CreateTokenResponse { info, token }),
+            )
+                .into_response()
+        }
+        Ok(Ok(false)) => (StatusCode::UNPROCESSABLE_ENTITY, "too many tokens").into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to save API tokens: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(e) => {
+            tracing::error!("create_token task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// DELETE /api/tokens/{id} — 失効（自分が発行したもののみ）
+pub async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let store = state.store.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        store.update_api_tokens(|tokens| {
+            let before = tokens.len();
+            tokens.retain(|t| !(t.id == id && t.username == user.username));
+            tokens.len() != before
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(true)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok(false)) => (StatusCode::NOT_FOUND, "token not found").into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to save API tokens: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(e) => {
+            tracing::error!("revoke_token task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
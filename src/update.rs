@@ -1,4 +1,10 @@
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -10,12 +16,20 @@ static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
 
 const GITHUB_REPO: &str = "jss826/den";
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Ed25519 public key (base64 of the raw 32 bytes) that release archives are
+/// signed with. Baked in by the release build and deliberately not
+/// configurable at runtime: whoever can change the service environment must
+/// not be able to swap the trust anchor.
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("DEN_RELEASE_PUBLIC_KEY");
 
 #[derive(Serialize)]
 pub struct VersionInfo {
     pub current: String,
     pub latest: Option<String>,
     pub update_available: bool,
+    /// false = this build has no release signing key (source/dev builds),
+    /// so it can't install updates itself; the UI hides the update button
+    pub can_update: bool,
 }
 
 #[derive(Deserialize)]
@@ -96,8 +110,36 @@ fn fetch_latest_version() -> Result<String, String> {
     Ok(version.to_string())
 }
 
+#[derive(Deserialize)]
+pub struct VersionQuery {
+    /// Also ask GitHub for the latest release
+    #[serde(default)]
+    pub check: bool,
+}
+
+/// GET /api/version — the running version; `?check=true` adds the upstream
+/// release check (skipped when DEN_DISABLE_UPDATE is set)
+pub async fn version(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<VersionQuery>,
+) -> Json<VersionInfo> {
+    if query.check && !state.config.disabled.update {
+        return Json(check_version().await);
+    }
+    Json(VersionInfo {
+        current: CURRENT_VERSION.to_string(),
+        latest: None,
+        update_available: false,
+        can_update: can_update(),
+    })
+}
+
 /// GET /api/system/version
 pub async fn get_version(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(check_version().await)
+}
+
+async fn check_version() -> VersionInfo {
     let result = tokio::task::spawn_blocking(fetch_latest_version).await;
 
     let (latest, update_available) = match result {
@@ -108,15 +150,30 @@ pub async fn get_version(State(_state): State<Arc<AppState>>) -> impl IntoRespon
         _ => (None, false),
     };
 
-    Json(VersionInfo {
+    VersionInfo {
         current: CURRENT_VERSION.to_string(),
         latest,
         update_available,
-    })
+        can_update: can_update(),
+    }
+}
+
+/// Whether this build can verify (and so install) release archives
+fn can_update() -> bool {
+    release_public_key().is_ok()
 }
 
 /// POST /api/system/update — download, replace binary, restart
 pub async fn do_update(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
+    // 署名鍵の無いビルドでは何もダウンロードしない（UI もボタンを出さない）
+    if let Err(e) = release_public_key() {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": e })),
+        )
+            .into_response();
+    }
+
     // Prevent concurrent updates
     if UPDATE_IN_PROGRESS.swap(true, Ordering::SeqCst) {
         return (
@@ -186,6 +243,68 @@ pub async fn do_update(State(_state): State<Arc<AppState>>) -> impl IntoResponse
     }
 }
 
+/// Release signing key built into this binary
+fn release_public_key() -> Result<Vec<u8>, String> {
+    let encoded = RELEASE_PUBLIC_KEY.ok_or(
+        "This build has no release signing key, so it cannot update itself; \
+         install a release build to get updates",
+    )?;
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid release signing key: {e}"))?;
+    if key.len() != 32 {
+        return Err("Invalid release signing key: expected 32 bytes".to_string());
+    }
+    Ok(key)
+}
+
+/// Check a detached Ed25519 signature (raw 64 bytes or base64) over `archive`
+fn verify_signature(public_key: &[u8], archive: &[u8], signature: &[u8]) -> Result<(), String> {
+    let signature = if signature.len() == 64 {
+        signature.to_vec()
+    } else {
+        base64::engine::general_purpose::STANDARD
+            .decode(signature.trim_ascii())
+            .map_err(|_| "Malformed update signature".to_string())?
+    };
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(archive, &signature)
+        .map_err(|_| "Update signature does not match; refusing to install".to_string())
+}
+
+/// Download `url` to `dest` with curl
+fn download(url: &str, dest: &std::path::Path) -> Result<(), String> {
+    let status = std::process::Command::new("curl")
+        .args(["-fsL", "--max-time", "120", "-o"])
+        .arg(dest)
+        .arg(url)
+        .status()
+        .map_err(|e| format!("curl failed: {e}"))?;
+    if !status.success() {
+        return Err(format!("Download failed: {url}"));
+    }
+    Ok(())
+}
+
+/// Download the release archive and its `.sig`, and verify it before anything
+/// is extracted. A bad archive is deleted.
+fn download_verified(url: &str, dest: &std::path::Path) -> Result<(), String> {
+    let public_key = release_public_key()?;
+    download(url, dest)?;
+    let sig_path = dest.with_extension("sig");
+    let verified = download(&format!("{url}.sig"), &sig_path).and_then(|()| {
+        let archive = std::fs::read(dest).map_err(|e| format!("Cannot read download: {e}"))?;
+        let signature =
+            std::fs::read(&sig_path).map_err(|e| format!("Cannot read signature: {e}"))?;
+        verify_signature(&public_key, &archive, &signature)
+    });
+    let _ = std::fs::remove_file(&sig_path);
+    if verified.is_err() {
+        let _ = std::fs::remove_file(dest);
+    }
+    verified
+}
+
 fn perform_update() -> Result<(), String> {
     let current_exe =
         std::env::current_exe().map_err(|e| format!("Cannot determine exe path: {e}"))?;
@@ -212,17 +331,8 @@ fn update_windows(
     let tmp_zip = parent.join("den-update.zip");
     let tmp_dir = parent.join("den-update-tmp");
 
-    // Download
-    let status = std::process::Command::new("curl")
-        .args(["-sL", "--max-time", "120", "-o"])
-        .arg(&tmp_zip)
-        .arg(url)
-        .status()
-        .map_err(|e| format!("curl failed: {e}"))?;
-
-    if !status.success() {
-        return Err("Download failed".to_string());
-    }
+    // Download and verify the signature
+    download_verified(url, &tmp_zip)?;
 
     // Extract using PowerShell
     let _ = std::fs::remove_dir_all(&tmp_dir);
@@ -279,17 +389,8 @@ fn update_linux(
     let tmp_tar = parent.join("den-update.tar.gz");
     let tmp_dir = parent.join("den-update-tmp");
 
-    // Download
-    let status = std::process::Command::new("curl")
-        .args(["-sL", "--max-time", "120", "-o"])
-        .arg(&tmp_tar)
-        .arg(url)
-        .status()
-        .map_err(|e| format!("curl failed: {e}"))?;
-
-    if !status.success() {
-        return Err("Download failed".to_string());
-    }
+    // Download and verify the signature
+    download_verified(url, &tmp_tar)?;
 
     // Extract
     let _ = std::fs::create_dir_all(&tmp_dir);
@@ -351,6 +452,7 @@ fn update_linux(
 }
 
 /// Find a file by name recursively in a directory.
+#[cfg_attr(not(windows), allow(dead_code))]
 fn find_file_recursive(dir: &std::path::Path, name: &str) -> Option<std::path::PathBuf> {
     if let Ok(entries) = std::fs::read_dir(dir) {
         for entry in entries.flatten() {
@@ -404,6 +506,92 @@ pub fn spawn_and_exit() -> ! {
     }
 }
 
+/// `den self-update [--check]`: install the latest release over this binary
+/// (signature checked), then restart the den service if one is running.
+pub fn self_update_command(arg: Option<&str>) {
+    let check_only = match arg {
+        None => false,
+        Some("--check") => true,
+        _ => {
+            eprintln!("Usage: den self-update [--check]");
+            std::process::exit(2);
+        }
+    };
+    let latest = fetch_latest_version().unwrap_or_else(|e| {
+        eprintln!("ERROR: update check failed: {e}");
+        std::process::exit(1);
+    });
+    if !is_newer(CURRENT_VERSION, &latest) {
+        println!("Den v{CURRENT_VERSION} is up to date");
+        return;
+    }
+    println!("Den v{latest} is available (running v{CURRENT_VERSION})");
+    if check_only {
+        return;
+    }
+    if let Err(e) = release_public_key() {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    }
+    if let Err(e) = perform_update() {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    }
+    println!("Installed v{latest}");
+    match restart_service() {
+        Ok(true) => println!("Restarted the den service"),
+        Ok(false) => println!("Restart Den to run the new version"),
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Restart the running den service. Ok(false) = no service is running.
+#[cfg(windows)]
+fn restart_service() -> Result<bool, String> {
+    let name = crate::service::SERVICE_NAME;
+    let running = std::process::Command::new("sc.exe")
+        .args(["query", name])
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains("RUNNING"));
+    if !running {
+        return Ok(false);
+    }
+    let status = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("Restart-Service -Name '{name}'"),
+        ])
+        .status()
+        .map_err(|e| format!("Restart-Service failed: {e}"))?;
+    if !status.success() {
+        return Err("Failed to restart the den service (run from an elevated prompt)".into());
+    }
+    Ok(true)
+}
+
+#[cfg(not(windows))]
+fn restart_service() -> Result<bool, String> {
+    let running = std::process::Command::new("systemctl")
+        .args(["is-active", "--quiet", "den.service"])
+        .status()
+        .is_ok_and(|s| s.success());
+    if !running {
+        return Ok(false);
+    }
+    let status = std::process::Command::new("systemctl")
+        .args(["restart", "den.service"])
+        .status()
+        .map_err(|e| format!("systemctl failed: {e}"))?;
+    if !status.success() {
+        return Err("Failed to restart den.service (try with sudo)".into());
+    }
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -427,6 +615,24 @@ mod tests {
         assert!(is_newer("1.6.1", "2.0.0-rc.1"));
     }
 
+    #[test]
+    fn signature_must_match_the_archive() {
+        use ring::signature::KeyPair;
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = ring::signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key = ring::signature::Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = key.public_key().as_ref();
+        let archive = b"den release archive";
+        let signature = key.sign(archive);
+
+        assert!(verify_signature(public_key, archive, signature.as_ref()).is_ok());
+        // base64 text, as some tools write it
+        let encoded = base64::engine::general_purpose::STANDARD.encode(signature.as_ref());
+        assert!(verify_signature(public_key, archive, format!("{encoded}\n").as_bytes()).is_ok());
+        assert!(verify_signature(public_key, b"tampered archive", signature.as_ref()).is_err());
+        assert!(verify_signature(public_key, archive, b"not a signature").is_err());
+    }
+
     #[test]
     fn test_asset_filename() {
         let name = asset_filename();
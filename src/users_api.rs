@@ -0,0 +1,287 @@
+// ユーザー管理 API（admin のみ、lib.rs で admin_middleware を適用）
+// テスト: tests/api_test.rs の Users API セクション
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::auth::hash_password;
+use crate::store::{MAX_USERS, Role, UserAccount, is_valid_username};
+
+const MIN_PASSWORD_CHARS: usize = 8;
+const MAX_PASSWORD_BYTES: usize = 1024;
+/// OIDC Core: `sub` is at most 255 ASCII characters
+const MAX_OIDC_SUBJECT_BYTES: usize = 255;
+
+/// パスワードハッシュを含まない一覧用の表現
+#[derive(Serialize)]
+pub struct UserInfo {
+    pub username: String,
+    pub role: Role,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oidc_subject: Option<String>,
+}
+
+impl From<&UserAccount> for UserInfo {
+    fn from(u: &UserAccount) -> Self {
+        Self {
+            username: u.username.clone(),
+            role: u.role,
+            created_at: u.created_at.clone(),
+            oidc_subject: u.oidc_subject.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub role: Role,
+    /// OIDC subject linked to this account (single sign-on)
+    #[serde(default)]
+    pub oidc_subject: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub role: Option<Role>,
+    /// Empty string unlinks the OIDC subject
+    #[serde(default)]
+    pub oidc_subject: Option<String>,
+}
+
+pub(crate) fn validate_password(password: &str) -> Result<(), &'static str> {
+    if password.chars().count() < MIN_PASSWORD_CHARS {
+        return Err("password must be at least 8 characters");
+    }
+    if password.len() > MAX_PASSWORD_BYTES {
+        return Err("password too long");
+    }
+    Ok(())
+}
+
+fn validate_oidc_subject(subject: &str) -> Result<(), &'static str> {
+    if subject.len() > MAX_OIDC_SUBJECT_BYTES || subject.chars().any(char::is_control) {
+        return Err("invalid oidc_subject");
+    }
+    Ok(())
+}
+
+enum UpdateError {
+    Exists,
+    Limit,
+    NotFound,
+    SubjectTaken,
+}
+
+/// GET /api/users
+pub async fn list_users(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let store = state.store.clone();
+    match tokio::task::spawn_blocking(move || store.load_users()).await {
+        Ok(users) => Json(users.iter().map(UserInfo::from).collect::<Vec<_>>()).into_response(),
+        Err(e) => {
+            tracing::error!("load_users task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /api/users
+pub async fn create_user(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateUserRequest>,
+) -> impl IntoResponse {
+    if !is_valid_username(&req.username) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "username must be 1-32 chars of a-z, 0-9, - or _",
+        )
+            .into_response();
+    }
+    if let Err(msg) = validate_password(&req.password) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+    }
+    let oidc_subject = req.oidc_subject.filter(|s| !s.is_empty());
+    if let Some(Err(msg)) = oidc_subject.as_deref().map(validate_oidc_subject) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+    }
+
+    let store = state.store.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let account = UserAccount {
+            username: req.username,
+            password_hash: hash_password(&req.password),
+            role: req.role,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            oidc_subject,
+        };
+        store.update_users(|users| {
+            if users.iter().any(|u| u.username == account.username) {
+                return Err(UpdateError::Exists);
+            }
+            if account.oidc_subject.is_some()
+                && users.iter().any(|u| u.oidc_subject == account.oidc_subject)
+            {
+                return Err(UpdateError::SubjectTaken);
+            }
+            if users.len() >= MAX_USERS {
+                return Err(UpdateError::Limit);
+            }
+            let info = UserInfo::from(&account);
+            users.push(account);
+            Ok(info)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Ok(info))) => {
+            tracing::info!("User created: {}", info.username);
+            (StatusCode::CREATED, Json(info)).into_response()
+        }
+        Ok(Ok(Err(UpdateError::Exists))) => {
+            (StatusCode::CONFLICT, "user already exists").into_response()
+        }
+        Ok(Ok(Err(UpdateError::SubjectTaken))) => {
+            (StatusCode::CONFLICT, "oidc_subject already linked").into_response()
+        }
+        Ok(Ok(Err(_))) => (StatusCode::UNPROCESSABLE_ENTITY, "too many users").into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to save users: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(e) => {
+            tracing::error!("create_user task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// PUT /api/users/{name}
+pub async fn update_user(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Json(req): Json<UpdateUserRequest>,
+) -> impl IntoResponse {
+    if let Some(Err(msg)) = req.password.as_deref().map(validate_password) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+    }
+    if let Some(Err(msg)) = req.oidc_subject.as_deref().map(validate_oidc_subject) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+    }
+
+    let password_changed = req.password.is_some();
+    let store = state.store.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let password_hash = req.password.as_deref().map(hash_password);
+        store.update_users(|users| {
+            if let Some(ref subject) = req.oidc_subject
+                && !subject.is_empty()
+                && users
+                    .iter()
+                    .any(|u| u.username != username && u.oidc_subject.as_ref() == Some(subject))
+            {
+                return Err(UpdateError::SubjectTaken);
+            }
+            let user = users
+                .iter_mut()
+                .find(|u| u.username == username)
+                .ok_or(UpdateError::NotFound)?;
+            // ハッシュが変わるとトークン署名鍵も変わり、既存ログインは無効になる
+            if let Some(hash) = password_hash {
+                user.password_hash = hash;
+            }
+            if let Some(role) = req.role {
+                user.role = role;
+            }
+            if let Some(subject) = req.oidc_subject {
+                user.oidc_subject = Some(subject).filter(|s| !s.is_empty());
+            }
+            Ok::<_, UpdateError>(UserInfo::from(&*user))
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Ok(info))) => {
+            if password_changed {
+                crate::devices_api::remove_user_devices(&state, &info.username);
+            }
+            Json(info).into_response()
+        }
+        Ok(Ok(Err(UpdateError::SubjectTaken))) => {
+            (StatusCode::CONFLICT, "oidc_subject already linked").into_response()
+        }
+        Ok(Ok(Err(_))) => (StatusCode::NOT_FOUND, "user not found").into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to save users: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(e) => {
+            tracing::error!("update_user task panicked: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// DELETE /api/users/{name}
+///
+/// アカウントと一緒にそのユーザーのターミナルセッション・API トークン・端末・設定も削除する。
+pub async fn delete_user(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    let store = state.store.clone();
+    let name = username.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        store.update_users(|users| {
+            let before = users.len();
+            users.retain(|u| u.username != name);
+            users.len() != before
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(true)) => {}
+        Ok(Ok(false)) => return (StatusCode::NOT_FOUND, "user not found").into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to save users: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        Err(e) => {
+            tracing::error!("delete_user task panicked: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    for session in state.registry.list_in(Some(&username)).await {
+        let key = crate::pty::registry::scoped_name(Some(&username), &session.name);
+        state.registry.destroy(&key).await;
+    }
+    let store = state.store.clone();
+    let name = username.clone();
+    if let Ok(Err(e)) = tokio::task::spawn_blocking(move || {
+        store
+            .update_api_tokens(|tokens| tokens.retain(|t| t.username.as_deref() != Some(&name)))?;
+        store.update_devices(|devices| devices.retain(|d| d.user.as_deref() != Some(&name)))?;
+        store.remove_user_data(&name)
+    })
+    .await
+    {
+        tracing::warn!("Failed to remove data for user {username}: {e}");
+    }
+    tracing::info!("User deleted: {username}");
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,184 @@
+//! Encrypted secrets vault (`data_dir/secrets.json`).
+//!
+//! Values are sealed with AES-256-GCM so saved credentials (SFTP passwords,
+//! future webhook tokens, ...) never sit on disk in plaintext. The key is
+//! `DEN_VAULT_KEYFILE` when set; otherwise a random data key wrapped under
+//! argon2id(owner password, salt) in `vault-key.json`. The password has to be
+//! known in plaintext for that, never the stored hash. Each entry is
+//! authenticated on its own: an entry sealed under a different key just reads
+//! as missing. Storage lives in `Store` (`get_secret` / `set_secret` / ...);
+//! this module holds the key and cipher.
+
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Keyfiles shorter than this are rejected
+const MIN_KEYFILE_BYTES: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// 32-byte AES key. `Debug` is deliberately not derived.
+#[derive(Clone)]
+pub struct VaultKey([u8; 32]);
+
+impl VaultKey {
+    /// Fresh random data key
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+
+    /// Key-encryption key: argon2id over the plaintext password (slow; blocking)
+    fn from_password(password: &str, salt: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .expect("argon2 output length");
+        Self(key)
+    }
+
+    /// Key from a keyfile's contents (at least 32 bytes, e.g. `openssl rand 32`)
+    pub fn from_keyfile(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        if bytes.len() < MIN_KEYFILE_BYTES {
+            return Err(format!(
+                "{path}: keyfile must be at least {MIN_KEYFILE_BYTES} bytes"
+            ));
+        }
+        Ok(Self(Sha256::digest(&bytes).into()))
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Data key sealed under a password-derived key (`data_dir/vault-key.json`)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WrappedKey {
+    /// base64 argon2id salt
+    salt: String,
+    /// `seal` of the hex data key
+    key: String,
+}
+
+impl WrappedKey {
+    /// Wraps `data_key` under `password` with a fresh salt (blocking)
+    pub fn wrap(data_key: &VaultKey, password: &str) -> Self {
+        let salt: [u8; SALT_LEN] = rand::random();
+        let kek = VaultKey::from_password(password, &salt);
+        Self {
+            salt: STANDARD.encode(salt),
+            key: seal(&hex::encode(data_key.bytes()), kek.bytes()),
+        }
+    }
+
+    /// Recovers the data key; fails for a wrong password (blocking)
+    pub fn unwrap(&self, password: &str) -> Result<VaultKey, String> {
+        let salt = STANDARD
+            .decode(&self.salt)
+            .map_err(|e| format!("base64 decode: {e}"))?;
+        let kek = VaultKey::from_password(password, &salt);
+        let data = hex::decode(open(&self.key, kek.bytes())?).map_err(|e| e.to_string())?;
+        let data: [u8; 32] = data.try_into().map_err(|_| "bad data key length")?;
+        Ok(VaultKey(data))
+    }
+}
+
+/// Data key wrapped under `password` in `vault-key.json`, created on first
+/// use. Fails when the file was wrapped under another password (blocking).
+pub fn unlock(store: &crate::store::Store, password: &str) -> Result<VaultKey, String> {
+    if let Some(wrapped) = store.load_vault_key() {
+        return wrapped
+            .unwrap(password)
+            .map_err(|e| format!("vault-key.json: {e}"));
+    }
+    let key = VaultKey::generate();
+    store
+        .save_vault_key(&WrappedKey::wrap(&key, password))
+        .map_err(|e| format!("vault-key.json: {e}"))?;
+    Ok(key)
+}
+
+/// HMAC-SHA256(label, secret)
+pub(crate) fn derive_key(label: &[u8], secret: &str) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as hmac::KeyInit>::new_from_slice(label).expect("HMAC key length");
+    mac.update(secret.as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+/// base64(nonce || ciphertext || tag)
+pub(crate) fn seal(plain: &str, key: &[u8; 32]) -> String {
+    let cipher = Aes256Gcm::new_from_slice(key).expect("AES key length");
+    let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), plain.as_bytes())
+        .expect("AES-GCM encrypt");
+    let mut combined = nonce_bytes.to_vec();
+    combined.extend_from_slice(&ciphertext);
+    STANDARD.encode(&combined)
+}
+
+pub(crate) fn open(sealed: &str, key: &[u8; 32]) -> Result<String, String> {
+    let combined = STANDARD
+        .decode(sealed)
+        .map_err(|e| format!("base64 decode: {e}"))?;
+    if combined.len() <= NONCE_LEN + TAG_LEN {
+        return Err("encrypted data too short".into());
+    }
+    let (nonce_bytes, ciphertext) = combined.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new_from_slice(key).expect("AES key length");
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        .map_err(|_| "decryption failed (wrong key?)")?;
+    String::from_utf8(plaintext).map_err(|e| format!("utf8: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_roundtrip_and_wrong_key() {
+        let key = VaultKey::generate();
+        let sealed = seal("s3cret", key.bytes());
+        assert!(!sealed.contains("s3cret"));
+        assert_eq!(open(&sealed, key.bytes()).unwrap(), "s3cret");
+        // Fresh nonce every time
+        assert_ne!(seal("s3cret", key.bytes()), sealed);
+
+        let other = VaultKey::generate();
+        assert!(open(&sealed, other.bytes()).is_err());
+        assert!(open("dG9vIHNob3J0", key.bytes()).is_err());
+    }
+
+    #[test]
+    fn wrapped_key_needs_the_password() {
+        let data = VaultKey::generate();
+        let wrapped = WrappedKey::wrap(&data, "hunter2");
+        assert_eq!(wrapped.unwrap("hunter2").unwrap().bytes(), data.bytes());
+        assert!(wrapped.unwrap("hunter3").is_err());
+        // Salted: the same password wraps differently every time
+        let again = WrappedKey::wrap(&data, "hunter2");
+        assert_ne!(again.salt, wrapped.salt);
+        assert_eq!(again.unwrap("hunter2").unwrap().bytes(), data.bytes());
+    }
+
+    #[test]
+    fn keyfile_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("vault.key");
+        std::fs::write(&path, [7u8; 16]).unwrap();
+        assert!(VaultKey::from_keyfile(&path.to_string_lossy()).is_err());
+        std::fs::write(&path, [7u8; 32]).unwrap();
+        let a = VaultKey::from_keyfile(&path.to_string_lossy()).unwrap();
+        let b = VaultKey::from_keyfile(&path.to_string_lossy()).unwrap();
+        assert_eq!(a.bytes(), b.bytes());
+        assert!(VaultKey::from_keyfile("/nonexistent/vault.key").is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    Json,
+    Extension, Json,
     extract::{
         Path, Query, State, WebSocketUpgrade,
         ws::{Message, WebSocket},
@@ -11,9 +11,14 @@ use futures::{SinkExt, StreamExt};
 use serde::Deserialize;
 use std::borrow::Cow;
 use std::sync::Arc;
+use tracing::Instrument;
 
 use crate::AppState;
-use crate::pty::registry::{ClientKind, RegistryError, SessionInfo, SshSessionConfig};
+use crate::auth::AuthUser;
+use crate::pty::registry::{
+    ClientKind, NAMESPACE_SEP, RegistryError, SessionInfo, SshSessionConfig, scoped_name,
+};
+use crate::shutdown::{Shutdown, client_message as shutdown_message};
 use crate::store::SshAuthType;
 use crate::terminal_filter::{filter_conpty_private_modes, filter_terminal_responses};
 
@@ -86,6 +91,7 @@ pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Query(query): Query<WsQuery>,
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
 ) -> axum::response::Response {
     let Some(session_name) = query.session.filter(|s| !s.is_empty()) else {
         tracing::warn!("WebSocket rejected: missing or empty session parameter");
@@ -95,23 +101,51 @@ pub async fn ws_handler(
         )
             .into_response();
     };
+    let Some(session_name) = session_key(&user, &session_name) else {
+        return (StatusCode::BAD_REQUEST, "Invalid session name").into_response();
+    };
+    // 読み取り専用ユーザーは既存セッションの観覧のみ（作成・入力は不可）
+    let kind = if user.is_read_only() {
+        if !state.registry.exists(&session_name).await {
+            return (StatusCode::NOT_FOUND, "Session not found").into_response();
+        }
+        ClientKind::Observer
+    } else {
+        ClientKind::WebSocket
+    };
     let cols = query.cols.unwrap_or(80);
     let rows = query.rows.unwrap_or(24);
     let since = query.since;
     let registry = Arc::clone(&state.registry);
-
-    ws.on_upgrade(move |socket| handle_socket(socket, registry, session_name, cols, rows, since))
-        .into_response()
+    let shutdown = state.shutdown.clone();
+
+    ws.on_upgrade(move |socket| {
+        handle_socket(
+            socket,
+            registry,
+            session_name,
+            kind,
+            cols,
+            rows,
+            since,
+            shutdown,
+        )
+    })
+    .into_response()
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_socket(
     socket: WebSocket,
     registry: Arc<crate::pty::registry::SessionRegistry>,
     session_name: String,
+    kind: ClientKind,
     cols: u16,
     rows: u16,
     since: Option<u64>,
+    shutdown: Shutdown,
 ) {
+    let _open = crate::metrics::SocketGuard::open(&crate::metrics::TERMINAL_SOCKETS);
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // The sink (`ws_tx`) is owned by the output task; the input task (which sees
@@ -119,11 +153,22 @@ async fn handle_socket(
     // so the output task is the single writer.
     let (pong_tx, mut pong_rx) = tokio::sync::mpsc::channel::<()>(4);
 
-    // SessionRegistry に attach（なければ create）。`since` で差分リプレイを要求。
-    let (session, mut output_rx, replay, client_id) = match registry
-        .get_or_create(&session_name, ClientKind::WebSocket, cols, rows, since)
-        .await
-    {
+    // SessionRegistry に attach（なければ create。Observer は attach のみ）。
+    // `since` で差分リプレイを要求。
+    let observer = kind == ClientKind::Observer;
+    let span = tracing::info_span!("pty.attach", session = %session_name, kind = ?kind);
+    let attached = if observer {
+        registry
+            .attach(&session_name, kind, cols, rows, since)
+            .instrument(span)
+            .await
+    } else {
+        registry
+            .get_or_create(&session_name, kind, cols, rows, since)
+            .instrument(span)
+            .await
+    };
+    let (session, mut output_rx, replay, client_id) = match attached {
         Ok(result) => result,
         Err(e) => {
             tracing::error!("Session attach failed: {e}");
@@ -193,6 +238,15 @@ async fn handle_socket(
                     // No new PTY output to replay; loop back and wait again.
                     continue;
                 }
+                // den is stopping: the session itself is kept (persisted) for
+                // the client to reattach to after a restart
+                restart = shutdown.started() => {
+                    let _ = ws_tx
+                        .send(Message::Text(shutdown_message(restart).into()))
+                        .await;
+                    let _ = ws_tx.close().await;
+                    break;
+                }
                 recv = tokio::time::timeout(OUTPUT_RECV_TIMEOUT, output_rx.recv()) => {
                     match recv {
                         Ok(Ok(_)) => false, // woke: 内容は無視（リングバッファが真実）
@@ -278,6 +332,8 @@ async fn handle_socket(
     let ws_to_pty = async move {
         while let Some(Ok(msg)) = ws_rx.next().await {
             match msg {
+                // Observer の入力は捨てる（出力の受信と ping のみ）
+                Message::Binary(_) if observer => {}
                 Message::Binary(data) => {
                     let filtered = filter_mouse_sequences(&data);
                     let filtered = filter_terminal_responses(&filtered);
@@ -290,6 +346,9 @@ async fn handle_socket(
                 }
                 Message::Text(text) => {
                     if let Ok(cmd) = serde_json::from_str::<WsCommand>(&text) {
+                        if observer && !matches!(cmd, WsCommand::Ping) {
+                            continue;
+                        }
                         match cmd {
                             WsCommand::Resize { cols, rows } => {
                                 session.resize(client_id, cols, rows).await;
@@ -341,14 +400,33 @@ async fn handle_socket(
 
 // --- REST API for terminal session management ---
 
+/// Registry key for a user-facing session name: sessions live in the
+/// caller's namespace, so users never see or touch each other's terminals.
+/// None if the name tries to address another namespace.
+fn session_key(user: &AuthUser, name: &str) -> Option<String> {
+    (!name.contains(NAMESPACE_SEP)).then(|| scoped_name(user.namespace(), name))
+}
+
 /// GET /api/terminal/sessions
-pub async fn list_sessions(State(state): State<Arc<AppState>>) -> Json<Vec<SessionInfo>> {
-    let sessions = state.registry.list().await;
+#[utoipa::path(
+    get,
+    path = "/api/terminal/sessions",
+    tag = "terminal",
+    summary = "List terminal sessions",
+    responses(
+        (status = 200, body = Vec<SessionInfo>),
+    )
+)]
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
+) -> Json<Vec<SessionInfo>> {
+    let sessions = state.registry.list_in(user.namespace()).await;
     Json(sessions)
 }
 
 /// POST /api/terminal/sessions { "name": "...", "ssh": { ... }, "backend": "zellij" }
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct CreateSessionRequest {
     pub name: String,
     pub ssh: Option<CreateSessionSsh>,
@@ -356,7 +434,7 @@ pub struct CreateSessionRequest {
     pub backend: Option<crate::pty::backend::SessionBackend>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct CreateSessionSsh {
     pub host: String,
     pub port: Option<u16>,
@@ -431,10 +509,30 @@ pub fn build_ssh_command(ssh: &SshSessionConfig) -> String {
     cmd
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/terminal/sessions",
+    tag = "terminal",
+    summary = "Create a terminal session (local shell, tmux/zellij or SSH)",
+    request_body = CreateSessionRequest,
+    responses(
+        (status = 201, description = "Created"),
+        (status = 200, description = "A session with this name already exists"),
+        (status = 400, description = "Invalid name or SSH parameters"),
+        (status = 409, description = "The existing session uses another backend"),
+        (status = 429, description = "Session limit reached"),
+    )
+)]
 pub async fn create_session(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<CreateSessionRequest>,
+    Extension(user): Extension<AuthUser>,
+    Json(mut req): Json<CreateSessionRequest>,
 ) -> axum::response::Response {
+    match session_key(&user, &req.name) {
+        Some(key) => req.name = key,
+        None => return (StatusCode::BAD_REQUEST, "Invalid session name").into_response(),
+    }
+
     // SSH 指定時は従来の ssh 経路（無改変）
     if req.ssh.is_some() {
         return create_session_ssh(state, req).await;
@@ -525,28 +623,66 @@ async fn create_session_ssh(
 }
 
 /// PUT /api/terminal/sessions/{name}
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct RenameSessionRequest {
     pub name: String,
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/terminal/sessions/{name}",
+    tag = "terminal",
+    summary = "Rename a terminal session",
+    params(("name" = String, Path, description = "Session name")),
+    request_body = RenameSessionRequest,
+    responses(
+        (status = 204, description = "Renamed"),
+        (status = 400, description = "Invalid name or rename failed"),
+    )
+)]
 pub async fn rename_session(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
     Path(old_name): Path<String>,
     Json(req): Json<RenameSessionRequest>,
 ) -> impl IntoResponse {
-    match state.registry.rename(&old_name, &req.name).await {
+    let (Some(old_key), Some(new_key)) =
+        (session_key(&user, &old_name), session_key(&user, &req.name))
+    else {
+        return (StatusCode::BAD_REQUEST, "Invalid session name").into_response();
+    };
+    match state.registry.rename(&old_key, &new_key).await {
         Ok(()) => StatusCode::NO_CONTENT.into_response(),
         Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
     }
 }
 
 /// PUT /api/terminal/sessions/order
+#[utoipa::path(
+    put,
+    path = "/api/terminal/sessions/order",
+    tag = "terminal",
+    summary = "Save the tab order of the sessions",
+    request_body = Vec<String>,
+    responses(
+        (status = 204, description = "Saved"),
+    )
+)]
 pub async fn reorder_sessions(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
     Json(order): Json<Vec<String>>,
 ) -> impl IntoResponse {
-    if let Err(e) = state.store.save_session_order(&order) {
+    // 並び順は全ユーザーで 1 ファイル: 他の名前空間の並びは保持する
+    let namespace = user.namespace();
+    let mut merged: Vec<String> = state
+        .store
+        .load_session_order()
+        .into_iter()
+        .filter(|key| crate::pty::registry::unscoped_name(namespace, key).is_none())
+        .collect();
+    merged.extend(order.iter().filter_map(|name| session_key(&user, name)));
+    if let Err(e) = state.store.save_session_order(&merged) {
         tracing::warn!("Failed to save session order: {e}");
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
@@ -554,11 +690,26 @@ pub async fn reorder_sessions(
 }
 
 /// DELETE /api/terminal/sessions/{name}
+#[utoipa::path(
+    delete,
+    path = "/api/terminal/sessions/{name}",
+    tag = "terminal",
+    summary = "Close a terminal session",
+    params(("name" = String, Path, description = "Session name")),
+    responses(
+        (status = 204, description = "Closed"),
+        (status = 400, description = "Invalid name"),
+    )
+)]
 pub async fn destroy_session(
     State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthUser>,
     Path(name): Path<String>,
 ) -> StatusCode {
-    state.registry.destroy(&name).await;
+    let Some(key) = session_key(&user, &name) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    state.registry.destroy(&key).await;
     StatusCode::NO_CONTENT
 }
 
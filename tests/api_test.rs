@@ -1,6 +1,6 @@
 use axum::body::Body;
 use axum::http::{Request, StatusCode, header};
-use den::auth::generate_token;
+use den::auth::{generate_token, generate_token_at};
 use den::config::{Config, Environment};
 use den::pty::registry::SessionRegistry;
 use den::store::{SleepPreventionMode, TrustedTlsCert};
@@ -19,16 +19,38 @@ fn test_config() -> Config {
     Config {
         port: 0,
         password: "testpass".to_string(),
+        password_hash: None,
         shell: "powershell.exe".to_string(),
         env: Environment::Development,
         log_level: "debug".to_string(),
+        log_files: Default::default(),
         data_dir: tmp.to_string_lossy().to_string(),
         bind_address: "127.0.0.1".to_string(),
         ssh_port: None,
+        local_socket: None,
         tls_enabled: false,
         tls_cert_path: None,
         tls_key_path: None,
         tls_subject_alt_names: Vec::new(),
+        tls_client_ca: None,
+        tls_client_owner_cn: None,
+        acme: None,
+        filer_roots: Vec::new(),
+        token_ttl_hours: 24,
+        persist_secret: false,
+        allow_cidrs: Vec::new(),
+        deny_cidrs: Vec::new(),
+        trusted_proxies: Vec::new(),
+        oidc: None,
+        vault_keyfile: None,
+        metrics_token: None,
+        admin_listener: None,
+        otlp: None,
+        disabled: Default::default(),
+        csp: Default::default(),
+        security_headers: Default::default(),
+        cors: None,
+        notify: Default::default(),
     }
 }
 
@@ -121,6 +143,38 @@ async fn login_sets_secure_cookie_when_tls_enabled() {
     );
 }
 
+#[tokio::test]
+async fn login_with_password_hash() {
+    let config = Config {
+        password: String::new(),
+        password_hash: Some(den::auth::hash_password("hashedpass")),
+        ..test_config()
+    };
+    let (app, state) = test_app_from_config(config);
+    // The vault is never keyed by the hash; it needs DEN_VAULT_KEYFILE here
+    assert!(state.vault_key().is_none());
+    let login = |password: &'static str| {
+        let app = app.clone();
+        async move {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "password": password }).to_string(),
+                ))
+                .unwrap();
+            app.oneshot(req).await.unwrap().status()
+        }
+    };
+
+    assert_eq!(login("hashedpass").await, StatusCode::OK);
+    assert_eq!(login("testpass").await, StatusCode::UNAUTHORIZED);
+    // Tokens are keyed by the hash, not a (missing) plaintext password
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &auth_header(), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
 #[tokio::test]
 async fn tls_status_omits_internal_paths() {
     let mut config = test_config();
@@ -461,6 +515,120 @@ async fn login_rate_limit() {
     assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
 }
 
+#[tokio::test]
+async fn login_rate_limit_is_per_client_ip() {
+    let config = Config {
+        trusted_proxies: vec!["127.0.0.1".to_string()],
+        ..test_config()
+    };
+    let (app, _) = test_app_from_config(config);
+    let login_from = |peer: &str, forwarded: Option<&str>, password: &str| {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/api/login")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(forwarded) = forwarded {
+            req = req.header("x-forwarded-for", forwarded);
+        }
+        let mut req = req
+            .body(Body::from(
+                serde_json::json!({ "password": password }).to_string(),
+            ))
+            .unwrap();
+        let addr: std::net::SocketAddr = peer.parse().unwrap();
+        req.extensions_mut()
+            .insert(axum::extract::ConnectInfo(addr));
+        app.clone().oneshot(req)
+    };
+
+    // Attacker behind the trusted proxy gets locked out...
+    for _ in 0..5 {
+        let resp = login_from("127.0.0.1:40000", Some("203.0.113.7"), "wrong")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+    let resp = login_from("127.0.0.1:40000", Some("203.0.113.7"), "testpass")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    // ...without locking out other clients, through the proxy or direct
+    let resp = login_from("127.0.0.1:40000", Some("198.51.100.2"), "testpass")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    // A direct client can't spoof its way out with the header
+    for _ in 0..5 {
+        login_from("192.0.2.1:40000", Some("198.51.100.2"), "wrong")
+            .await
+            .unwrap();
+    }
+    let resp = login_from("192.0.2.1:40000", Some("198.51.100.9"), "testpass")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, json) = json_request(&app, "GET", "/api/auth/bans", &auth_header(), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let banned: Vec<&str> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|c| !c["banned_for_secs"].is_null())
+        .map(|c| c["ip"].as_str().unwrap())
+        .collect();
+    assert_eq!(banned.len(), 2);
+    assert!(banned.contains(&"203.0.113.7"));
+    assert!(banned.contains(&"192.0.2.1"));
+}
+
+#[tokio::test]
+async fn trusted_proxy_forwards_client_address_and_scheme() {
+    let config = Config {
+        trusted_proxies: vec!["127.0.0.1".to_string()],
+        deny_cidrs: vec!["203.0.113.0/24".to_string()],
+        ..test_config()
+    };
+    let (app, _) = test_app_from_config(config);
+    let login_from = |peer: &str, forwarded_for: &str, proto: &str| {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/api/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-forwarded-for", forwarded_for)
+            .header("x-forwarded-proto", proto)
+            .body(Body::from(r#"{"password":"testpass"}"#))
+            .unwrap();
+        let addr: std::net::SocketAddr = peer.parse().unwrap();
+        req.extensions_mut()
+            .insert(axum::extract::ConnectInfo(addr));
+        app.clone().oneshot(req)
+    };
+    let secure_cookie = |resp: &axum::response::Response| {
+        resp.headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .all(|v| v.to_str().unwrap().ends_with("; Secure"))
+    };
+
+    let resp = login_from("127.0.0.1:40000", "198.51.100.2", "https")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(secure_cookie(&resp));
+    // Untrusted peers can't claim HTTPS
+    let resp = login_from("192.0.2.1:40000", "198.51.100.2", "https")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!secure_cookie(&resp));
+    // The deny list applies to the forwarded client, not the proxy
+    let resp = login_from("127.0.0.1:40000", "203.0.113.7", "https")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
 // --- Auth middleware ---
 
 #[tokio::test]
@@ -574,6 +742,337 @@ async fn static_css() {
     assert!(content_type.contains("css"));
 }
 
+#[tokio::test]
+async fn csp_nonce_and_api_policy() {
+    let app = test_app();
+    let index = |app: axum::Router| async move {
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let csp = resp.headers()[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+        assert!(resp.headers().get(header::ETAG).is_none());
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        (csp, String::from_utf8(body.to_vec()).unwrap())
+    };
+
+    let (csp, html) = index(app.clone()).await;
+    let nonce = csp
+        .split("'nonce-")
+        .nth(1)
+        .and_then(|rest| rest.split('\'').next())
+        .unwrap()
+        .to_string();
+    assert!(html.contains(&format!("<script nonce=\"{nonce}\" src=\"/js/app.js")));
+    // A fresh nonce for every response
+    let (next_csp, _) = index(app.clone()).await;
+    assert_ne!(next_csp, csp);
+
+    // API responses get the strict policy
+    let req = Request::builder()
+        .uri("/api/settings")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(
+        resp.headers()[header::CONTENT_SECURITY_POLICY],
+        "default-src 'none'; frame-ancestors 'none'; base-uri 'none'"
+    );
+
+    // Operator-supplied policy without a nonce keeps the cacheable shell
+    let mut config = test_config();
+    config.csp.page = "default-src 'self'".to_string();
+    let (app, _) = test_app_from_config(config);
+    let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(
+        resp.headers()[header::CONTENT_SECURITY_POLICY],
+        "default-src 'self'; frame-ancestors 'self'"
+    );
+    assert!(resp.headers().get(header::ETAG).is_some());
+}
+
+#[tokio::test]
+async fn security_headers_defaults_and_overrides() {
+    let get = |app: axum::Router, uri: &'static str| async move {
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap().headers().clone()
+    };
+
+    let app = test_app();
+    let headers = get(app.clone(), "/").await;
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::REFERRER_POLICY], "same-origin");
+    assert!(
+        headers[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .ends_with("; frame-ancestors 'self'")
+    );
+    // HSTS only over HTTPS
+    assert!(headers.get(header::STRICT_TRANSPORT_SECURITY).is_none());
+    let headers = get(app.clone(), "/api/settings").await;
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    // The API policy already forbids framing
+    assert_eq!(
+        headers[header::CONTENT_SECURITY_POLICY],
+        "default-src 'none'; frame-ancestors 'none'; base-uri 'none'"
+    );
+
+    let mut config = test_config();
+    config.tls_enabled = true;
+    config.security_headers.hsts = Some("max-age=63072000; includeSubDomains".to_string());
+    config.security_headers.referrer_policy = None;
+    config.security_headers.frame_ancestors = None;
+    let (app, _) = test_app_from_config(config);
+    let headers = get(app, "/").await;
+    assert_eq!(
+        headers[header::STRICT_TRANSPORT_SECURITY],
+        "max-age=63072000; includeSubDomains"
+    );
+    assert!(headers.get(header::REFERRER_POLICY).is_none());
+    assert!(
+        !headers[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .contains("frame-ancestors")
+    );
+}
+
+#[tokio::test]
+async fn cors_allows_configured_origins_only() {
+    let request = |app: axum::Router, method: &str, origin: &str| {
+        let req = Request::builder()
+            .method(method)
+            .uri("/api/settings")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-den-request")
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap();
+        async move { app.oneshot(req).await.unwrap() }
+    };
+
+    // Off by default: no CORS headers at all
+    let resp = request(test_app(), "GET", "https://app.example").await;
+    assert!(
+        resp.headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none()
+    );
+
+    let mut config = test_config();
+    config.cors = Some(den::config::CorsConfig {
+        origins: vec!["https://app.example".to_string()],
+        credentials: true,
+    });
+    let (app, _) = test_app_from_config(config);
+    // The preflight is answered before authentication
+    let resp = request(app.clone(), "OPTIONS", "https://app.example").await;
+    assert!(resp.status().is_success());
+    let headers = resp.headers();
+    assert_eq!(
+        headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://app.example"
+    );
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+    assert!(
+        headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap()
+            .contains("x-den-request")
+    );
+
+    let resp = request(app.clone(), "GET", "https://app.example").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://app.example"
+    );
+    let resp = request(app, "GET", "https://evil.example").await;
+    assert!(
+        resp.headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn json_responses_are_compressed_when_accepted() {
+    let get = |app: axum::Router, encoding: Option<&str>| {
+        let mut req = Request::builder()
+            .uri("/api/settings")
+            .header(header::AUTHORIZATION, auth_header());
+        if let Some(encoding) = encoding {
+            req = req.header(header::ACCEPT_ENCODING, encoding);
+        }
+        let req = req.body(Body::empty()).unwrap();
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let app = test_app();
+
+    let plain = get(app.clone(), None).await;
+    assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+    let plain = plain.into_body().collect().await.unwrap().to_bytes();
+
+    let resp = get(app.clone(), Some("gzip")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()[header::CONTENT_ENCODING], "gzip");
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let mut decoded = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&body[..]), &mut decoded).unwrap();
+    assert_eq!(decoded, plain);
+
+    let resp = get(app, Some("br;q=1.0, gzip;q=0.5")).await;
+    assert_eq!(resp.headers()[header::CONTENT_ENCODING], "br");
+}
+
+#[tokio::test]
+async fn openapi_spec_matches_routes() {
+    let app = test_app();
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/openapi.json")
+                .header(header::AUTHORIZATION, auth_header())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let paths = spec["paths"].as_object().unwrap();
+    assert!(paths.contains_key("/api/filer/list"));
+    assert!(paths.contains_key("/api/terminal/sessions/{name}"));
+
+    // 記載された全 operation がルーティングされていること（ルーターの 404/405 にならない）
+    for (path, item) in paths {
+        let uri = path.replace("{name}", "spec-check");
+        for method in item.as_object().unwrap().keys() {
+            let req = Request::builder()
+                .method(method.to_uppercase().as_str())
+                .uri(&uri)
+                .header(header::AUTHORIZATION, auth_header())
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            let status = resp.status();
+            assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{method} {path}");
+            if status == StatusCode::NOT_FOUND {
+                // ハンドラが返す 404 は JSON ボディ付き、ルーターの 404 は空
+                let body = resp.into_body().collect().await.unwrap().to_bytes();
+                assert!(!body.is_empty(), "{method} {path} is not routed");
+            }
+        }
+    }
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/docs")
+                .header(header::AUTHORIZATION, auth_header())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let csp = resp.headers()[header::CONTENT_SECURITY_POLICY]
+        .to_str()
+        .unwrap();
+    assert!(csp.contains("'nonce-"));
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&body).contains("/api/openapi.json"));
+}
+
+#[tokio::test]
+async fn metrics_require_admin_or_metrics_token() {
+    let get = |app: axum::Router, auth: Option<String>| async move {
+        let mut req = Request::builder().uri("/metrics");
+        if let Some(auth) = auth {
+            req = req.header(header::AUTHORIZATION, auth);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+    };
+
+    let mut config = test_config();
+    config.metrics_token = Some("scrape-me".to_string());
+    let (app, _) = test_app_from_config(config);
+    assert_eq!(
+        get(app.clone(), None).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        get(app.clone(), Some("Bearer wrong".to_string()))
+            .await
+            .status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        get(app.clone(), Some("Bearer scrape-me".to_string()))
+            .await
+            .status(),
+        StatusCode::OK
+    );
+
+    let resp = get(app, Some(auth_header())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        resp.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain; version=0.0.4")
+    );
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("\nden_pty_sessions 0\n"), "{text}");
+    assert!(text.contains("den_pty_clients{kind=\"ssh\"} 0"));
+    assert!(text.contains("den_http_request_duration_seconds_bucket{le=\"+Inf\"}"));
+}
+
+#[tokio::test]
+async fn dashboard_summarizes_server_state() {
+    let app = test_app();
+    let owner = auth_header();
+
+    let (status, json) = json_request(&app, "GET", "/api/dashboard", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(json["sessions"], serde_json::json!([]));
+    assert_eq!(json["attached_clients"], 0);
+    assert_eq!(json["claude_sessions"], serde_json::json!([]));
+    assert_eq!(json["sftp"]["connected"], false);
+    assert!(json["disk"]["total"].as_u64().unwrap() > 0);
+    assert!(json["recent_errors"].is_array());
+
+    // エラー一覧は管理者のみ
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"alice-secret"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let alice = user_login(&app, "alice", "alice-secret").await.unwrap();
+    let (status, json) = json_request(&app, "GET", "/api/dashboard", &alice, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["recent_errors"].is_null());
+    assert_eq!(json["sessions"], serde_json::json!([]));
+}
+
 #[tokio::test]
 async fn static_404() {
     let app = test_app();
@@ -651,6 +1150,89 @@ async fn settings_put_and_get() {
     assert_eq!(json["terminal_scrollback"], 2000);
 }
 
+#[tokio::test]
+async fn settings_transfer_rate_limit_zero_means_unlimited() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("PUT")
+        .uri("/api/settings")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(r#"{"transfer_rate_limit_kbps":0}"#))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = Request::builder()
+        .uri("/api/settings")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["transfer_rate_limit_kbps"].is_null());
+}
+
+#[tokio::test]
+async fn settings_host_wide_changes_apply_without_restart() {
+    let (app, state) = test_app_with_state();
+    let subscriber = den::live_settings::spawn_registry_subscriber(state.clone());
+    let owner = auth_header();
+
+    let (status, _) = json_request(
+        &app,
+        "PUT",
+        "/api/settings",
+        &owner,
+        Some(r#"{"log_level":"info,den=verbose"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = json_request(
+        &app,
+        "PUT",
+        "/api/settings",
+        &owner,
+        Some(r#"{"shell":" zsh ","ssh_port":0,"log_level":"debug"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while state.registry.shell() != "zsh" {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("registry picks up the new shell");
+
+    // Users can't change host-wide settings
+    json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"alice-secret"}"#),
+    )
+    .await;
+    let alice = user_login(&app, "alice", "alice-secret").await.unwrap();
+    let (status, _) = json_request(
+        &app,
+        "PUT",
+        "/api/settings",
+        &alice,
+        Some(r#"{"shell":"sh","ssh_port":22}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, json) = json_request(&app, "GET", "/api/settings", &alice, None).await;
+    assert!(json["shell"].is_null());
+    assert!(json["ssh_port"].is_null());
+    assert_eq!(state.registry.shell(), "zsh");
+    subscriber.abort();
+}
+
 #[tokio::test]
 async fn settings_requires_auth() {
     let app = test_app();
@@ -1101,14 +1683,13 @@ async fn sftp_write_not_connected() {
 }
 
 #[tokio::test]
-async fn sftp_mkdir_not_connected() {
+async fn sftp_download_many_not_connected() {
     let app = test_app();
+    let paths = urlencoding::encode(r#"["/tmp/a","/tmp/b"]"#);
     let req = Request::builder()
-        .method("POST")
-        .uri("/api/sftp/mkdir")
-        .header(header::CONTENT_TYPE, "application/json")
+        .uri(format!("/api/sftp/download-many?paths={paths}"))
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(r#"{"path":"/tmp/newdir"}"#))
+        .body(Body::empty())
         .unwrap();
 
     let resp = app.oneshot(req).await.unwrap();
@@ -1116,28 +1697,29 @@ async fn sftp_mkdir_not_connected() {
 }
 
 #[tokio::test]
-async fn sftp_rename_not_connected() {
+async fn sftp_download_many_invalid_paths() {
     let app = test_app();
     let req = Request::builder()
-        .method("POST")
-        .uri("/api/sftp/rename")
-        .header(header::CONTENT_TYPE, "application/json")
+        .uri("/api/sftp/download-many?paths=not-json")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(r#"{"from":"/tmp/a","to":"/tmp/b"}"#))
+        .body(Body::empty())
         .unwrap();
 
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
-async fn sftp_delete_not_connected() {
+async fn sftp_batch_not_connected() {
     let app = test_app();
     let req = Request::builder()
-        .method("DELETE")
-        .uri("/api/sftp/delete?path=/tmp/test.txt")
+        .method("POST")
+        .uri("/api/sftp/batch")
+        .header(header::CONTENT_TYPE, "application/json")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
+        .body(Body::from(
+            r#"{"ops":[{"op":"mkdir","path":"/tmp/a"},{"op":"chmod","path":"/tmp/a","mode":"700"}]}"#,
+        ))
         .unwrap();
 
     let resp = app.oneshot(req).await.unwrap();
@@ -1145,23 +1727,25 @@ async fn sftp_delete_not_connected() {
 }
 
 #[tokio::test]
-async fn sftp_download_not_connected() {
+async fn sftp_batch_empty_rejected() {
     let app = test_app();
     let req = Request::builder()
-        .uri("/api/sftp/download?path=/tmp/test.txt")
+        .method("POST")
+        .uri("/api/sftp/batch")
+        .header(header::CONTENT_TYPE, "application/json")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
+        .body(Body::from(r#"{"ops":[]}"#))
         .unwrap();
 
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
-async fn sftp_search_not_connected() {
+async fn sftp_checksum_not_connected() {
     let app = test_app();
     let req = Request::builder()
-        .uri("/api/sftp/search?path=/&query=test&content=false")
+        .uri("/api/sftp/checksum?path=/tmp/a.bin&algo=sha512")
         .header(header::AUTHORIZATION, auth_header())
         .body(Body::empty())
         .unwrap();
@@ -1171,20 +1755,144 @@ async fn sftp_search_not_connected() {
 }
 
 #[tokio::test]
-async fn sftp_connect_missing_fields() {
+async fn sftp_checksum_unknown_algo() {
     let app = test_app();
-    // host and username are required.
     let req = Request::builder()
-        .method("POST")
-        .uri("/api/sftp/connect")
-        .header(header::CONTENT_TYPE, "application/json")
+        .uri("/api/sftp/checksum?path=/tmp/a.bin&algo=crc7")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(r#"{"auth_type":"password"}"#))
+        .body(Body::empty())
         .unwrap();
 
     let resp = app.oneshot(req).await.unwrap();
-    // axum deserialization error -> 422
-    assert!(
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn sftp_statvfs_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/sftp/statvfs?path=/home")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_tail_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/sftp/tail?path=/var/log/syslog&bytes=1024&offset=0")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_append_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/append")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(r#"{"path":"/tmp/test.log","content":"line\n"}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_mkdir_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/mkdir")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(r#"{"path":"/tmp/newdir"}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_rename_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/rename")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(r#"{"from":"/tmp/a","to":"/tmp/b"}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_delete_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("DELETE")
+        .uri("/api/sftp/delete?path=/tmp/test.txt")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_download_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/sftp/download?path=/tmp/test.txt")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_search_not_connected() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/sftp/search?path=/&query=test&content=false")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_connect_missing_fields() {
+    let app = test_app();
+    // host and username are required.
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/connect")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(r#"{"auth_type":"password"}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    // axum deserialization error -> 422
+    assert!(
         resp.status() == StatusCode::BAD_REQUEST
             || resp.status() == StatusCode::UNPROCESSABLE_ENTITY
     );
@@ -1224,6 +1932,43 @@ async fn sftp_connect_password_missing() {
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
+#[tokio::test]
+async fn sftp_saved_password_is_used_and_can_be_forgotten() {
+    let (app, state) = test_app_with_state();
+    let owner = auth_header();
+    state
+        .store
+        .set_secret(
+            &state.vault_key().unwrap(),
+            "sftp:user@127.0.0.1:1",
+            "hunter2",
+        )
+        .unwrap();
+    let on_disk =
+        std::fs::read_to_string(std::path::Path::new(&state.config.data_dir).join("secrets.json"))
+            .unwrap();
+    assert!(!on_disk.contains("hunter2"));
+
+    // The saved password satisfies the check; the connect itself fails (nothing listens)
+    let connect = r#"{"host":"127.0.0.1","port":1,"username":"user","auth_type":"password"}"#;
+    let (status, _) = json_request(&app, "POST", "/api/sftp/connect", &owner, Some(connect)).await;
+    assert_ne!(status, StatusCode::BAD_REQUEST);
+
+    let (status, targets) =
+        json_request(&app, "GET", "/api/sftp/saved-passwords", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(targets, serde_json::json!(["user@127.0.0.1:1"]));
+
+    let uri = "/api/sftp/saved-passwords?target=user%40127.0.0.1%3A1";
+    let (status, _) = json_request(&app, "DELETE", uri, &owner, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = json_request(&app, "DELETE", uri, &owner, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = json_request(&app, "POST", "/api/sftp/connect", &owner, Some(connect)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn sftp_connect_key_path_missing() {
     let app = test_app();
@@ -1241,6 +1986,49 @@ async fn sftp_connect_key_path_missing() {
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
+#[tokio::test]
+async fn sftp_connect_jump_host_password_missing() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/connect")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(
+            r#"{"host":"internal","username":"user","auth_type":"agent",
+                "jump_hosts":[{"host":"bastion","username":"jump","auth_type":"password"}]}"#,
+        ))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "Jump host 1: Password required");
+}
+
+#[tokio::test]
+async fn sftp_connect_too_many_jump_hosts() {
+    let app = test_app();
+    let hop = serde_json::json!({"host":"b","username":"u","auth_type":"agent"});
+    let body = serde_json::json!({
+        "host": "internal",
+        "username": "user",
+        "auth_type": "agent",
+        "jump_hosts": vec![hop; 9],
+    });
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/connect")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn sftp_requires_auth() {
     let app = test_app();
@@ -1324,96 +2112,244 @@ async fn sftp_requires_auth() {
 }
 
 #[tokio::test]
-async fn sftp_upload_not_connected() {
+async fn events_and_transfers_require_auth() {
+    let app = test_app();
+    for uri in ["/api/events", "/api/transfers"] {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(
+            resp.status(),
+            StatusCode::UNAUTHORIZED,
+            "GET {} should require auth",
+            uri
+        );
+    }
+}
+
+#[tokio::test]
+async fn transfers_list_empty_and_cancel_unknown() {
     let app = test_app();
-    let boundary = "----TestBoundary";
-    let body = format!(
-        "------TestBoundary\r\nContent-Disposition: form-data; name=\"path\"\r\n\r\n/tmp\r\n------TestBoundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n------TestBoundary--\r\n"
-    );
     let req = Request::builder()
-        .method("POST")
-        .uri("/api/sftp/upload")
-        .header(
-            header::CONTENT_TYPE,
-            format!("multipart/form-data; boundary={}", boundary),
-        )
+        .uri("/api/transfers")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(body))
+        .body(Body::empty())
         .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json, serde_json::json!([]));
 
+    let req = Request::builder()
+        .method("DELETE")
+        .uri("/api/transfers/nope")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
 
 #[tokio::test]
-async fn sftp_write_empty_path() {
+async fn sftp_copy_not_connected() {
     let app = test_app();
+    let tmp = std::env::temp_dir();
+    let body = serde_json::json!({
+        "direction": "upload",
+        "local_path": tmp.to_string_lossy(),
+        "remote_path": "/tmp/x",
+    });
     let req = Request::builder()
-        .method("PUT")
-        .uri("/api/sftp/write")
-        .header(header::CONTENT_TYPE, "application/json")
+        .method("POST")
+        .uri("/api/transfer")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(r#"{"path":"","content":"hello"}"#))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
         .unwrap();
-
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
 }
 
 #[tokio::test]
-async fn sftp_write_null_byte_path() {
+async fn sftp_copy_invalid_direction() {
     let app = test_app();
     let req = Request::builder()
-        .method("PUT")
-        .uri("/api/sftp/write")
-        .header(header::CONTENT_TYPE, "application/json")
+        .method("POST")
+        .uri("/api/transfer")
         .header(header::AUTHORIZATION, auth_header())
+        .header(header::CONTENT_TYPE, "application/json")
         .body(Body::from(
-            r#"{"path":"/tmp/\u0000evil.txt","content":"hello"}"#,
+            r#"{"direction":"sideways","local_path":"/tmp","remote_path":"/tmp"}"#,
         ))
         .unwrap();
-
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
 }
 
-// --- Clipboard History API ---
-
 #[tokio::test]
-async fn clipboard_history_get_empty() {
+async fn sync_jobs_crud_and_run_not_connected() {
     let app = test_app();
     let req = Request::builder()
-        .uri("/api/clipboard-history")
+        .method("POST")
+        .uri("/api/sync-jobs")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            r#"{"name":"dotfiles","direction":"upload","local_path":"/tmp","remote_path":"~/dotfiles","delete":true}"#,
+        ))
         .unwrap();
-
-    let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::OK);
-
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
     let body = resp.into_body().collect().await.unwrap().to_bytes();
-    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
-    assert!(json.as_array().unwrap().is_empty());
-}
-
-#[tokio::test]
-async fn clipboard_history_post_and_get() {
-    let config = test_config();
-    let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
-    let registry = SessionRegistry::new(
-        "powershell.exe".to_string(),
-        SleepPreventionMode::Off,
-        30,
-        None,
-        den::pty::backend::MuxConfig::default(),
-    );
-    let (app, _state) =
-        den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None);
+    let job: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = job["id"].as_str().unwrap().to_string();
+    assert_eq!(job["delete"], true);
 
-    // POST
     let req = Request::builder()
-        .method("POST")
-        .uri("/api/clipboard-history")
+        .uri("/api/sync-jobs")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let jobs: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(jobs.as_array().unwrap().len(), 1);
+
+    let req = Request::builder()
+        .method("POST")
+        .uri(format!("/api/sync-jobs/{id}/run"))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let req = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/sync-jobs/{id}"))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = Request::builder()
+        .method("POST")
+        .uri(format!("/api/sync-jobs/{id}/run"))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn sync_job_interval_out_of_range() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sync-jobs")
+        .header(header::AUTHORIZATION, auth_header())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            r#"{"name":"x","direction":"download","local_path":"/tmp","remote_path":"/tmp","interval_minutes":1}"#,
+        ))
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn sftp_upload_not_connected() {
+    let app = test_app();
+    let boundary = "----TestBoundary";
+    let body = String::from(
+        "------TestBoundary\r\nContent-Disposition: form-data; name=\"path\"\r\n\r\n/tmp\r\n------TestBoundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n------TestBoundary--\r\n",
+    );
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/sftp/upload")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn sftp_write_empty_path() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("PUT")
+        .uri("/api/sftp/write")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(r#"{"path":"","content":"hello"}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn sftp_write_null_byte_path() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("PUT")
+        .uri("/api/sftp/write")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(
+            r#"{"path":"/tmp/\u0000evil.txt","content":"hello"}"#,
+        ))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+// --- Clipboard History API ---
+
+#[tokio::test]
+async fn clipboard_history_get_empty() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/clipboard-history")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn clipboard_history_post_and_get() {
+    let config = test_config();
+    let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
+    let registry = SessionRegistry::new(
+        "powershell.exe".to_string(),
+        SleepPreventionMode::Off,
+        30,
+        None,
+        den::pty::backend::MuxConfig::default(),
+    );
+    let (app, _state) =
+        den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None);
+
+    // POST
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/clipboard-history")
         .header(header::CONTENT_TYPE, "application/json")
         .header(header::AUTHORIZATION, auth_header())
         .body(Body::from(r#"{"text":"hello world","source":"copy"}"#))
@@ -1909,3 +2845,1424 @@ async fn remote_connections_list_and_disconnect() {
     let resp = app.clone().oneshot(req).await.unwrap();
     assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
+
+// --- Users API / multi-user ---
+
+async fn json_request(
+    app: &axum::Router,
+    method: &str,
+    uri: &str,
+    auth: &str,
+    body: Option<&str>,
+) -> (StatusCode, serde_json::Value) {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, auth);
+    if body.is_some() {
+        req = req.header(header::CONTENT_TYPE, "application/json");
+    }
+    let req = req
+        .body(body.map(|b| Body::from(b.to_string())).unwrap_or_default())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+    (status, json)
+}
+
+/// Log in as a registered user and return the Bearer header built from the cookie.
+async fn user_login(app: &axum::Router, username: &str, password: &str) -> Option<String> {
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({ "username": username, "password": password }).to_string(),
+        ))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    if resp.status() != StatusCode::OK {
+        return None;
+    }
+    resp.headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(|c| c.strip_prefix("den_token="))
+        .and_then(|c| c.split(';').next())
+        .map(|token| format!("Bearer {token}"))
+}
+
+#[tokio::test]
+async fn users_crud_and_login() {
+    let app = test_app();
+    let owner = auth_header();
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"short"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, json) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"alice-secret"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["role"], "user");
+    assert!(json.get("password_hash").is_none());
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"another-secret"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    assert!(user_login(&app, "alice", "wrong-password").await.is_none());
+    let alice = user_login(&app, "alice", "alice-secret").await.unwrap();
+
+    let (status, json) = json_request(&app, "GET", "/api/auth/me", &alice, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["username"], "alice");
+    assert_eq!(json["role"], "user");
+
+    let (_, json) = json_request(&app, "GET", "/api/auth/me", &owner, None).await;
+    assert!(json["username"].is_null());
+    assert_eq!(json["role"], "admin");
+
+    // Non-admins cannot manage users or update den
+    let (status, _) = json_request(&app, "GET", "/api/users", &alice, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = json_request(&app, "POST", "/api/system/update", &alice, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Changing the password invalidates existing tokens
+    let (status, _) = json_request(
+        &app,
+        "PUT",
+        "/api/users/alice",
+        &owner,
+        Some(r#"{"password":"rotated-secret"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &alice, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let alice = user_login(&app, "alice", "rotated-secret").await.unwrap();
+
+    let (status, json) = json_request(&app, "GET", "/api/users", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json.as_array().unwrap().len(), 1);
+
+    let (status, _) = json_request(&app, "DELETE", "/api/users/alice", &owner, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &alice, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = json_request(&app, "DELETE", "/api/users/alice", &owner, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn shared_resources_and_jobs_are_scoped_to_the_account() {
+    let app = test_app();
+    let owner = auth_header();
+    json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"alice-secret"}"#),
+    )
+    .await;
+    let alice = user_login(&app, "alice", "alice-secret").await.unwrap();
+
+    // The SFTP connection and the clipboard are the owner's
+    for uri in [
+        "/api/sftp/status",
+        "/api/transfers",
+        "/api/clipboard-history",
+    ] {
+        let (status, _) = json_request(&app, "GET", uri, &alice, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{uri}");
+        let (status, _) = json_request(&app, "GET", uri, &owner, None).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+    }
+    let (_, json) = json_request(&app, "GET", "/api/system/features", &alice, None).await;
+    assert_eq!(json["sftp"], false);
+    let (_, json) = json_request(&app, "GET", "/api/system/features", &owner, None).await;
+    assert_eq!(json["sftp"], true);
+
+    // Background jobs are invisible to other accounts
+    let dir = tempfile::tempdir().unwrap();
+    let body = serde_json::json!({ "path": dir.path() }).to_string();
+    let (status, json) = json_request(&app, "POST", "/api/filer/du", &owner, Some(&body)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let job = format!("/api/filer/du/{}", json["id"].as_str().unwrap());
+    let (status, _) = json_request(&app, "GET", &job, &alice, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = json_request(&app, "DELETE", &job, &alice, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = json_request(&app, "GET", &job, &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn users_have_separate_settings() {
+    let app = test_app();
+    let owner = auth_header();
+    json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"bob","password":"bob-secret"}"#),
+    )
+    .await;
+    let bob = user_login(&app, "bob", "bob-secret").await.unwrap();
+
+    let (status, _) = json_request(
+        &app,
+        "PUT",
+        "/api/settings",
+        &bob,
+        Some(r#"{"font_size":22,"theme":"light","terminal_scrollback":1000}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, json) = json_request(&app, "GET", "/api/settings", &bob, None).await;
+    assert_eq!(json["font_size"], 22);
+    let (_, json) = json_request(&app, "GET", "/api/settings", &owner, None).await;
+    assert_eq!(json["font_size"], 14);
+}
+
+#[tokio::test]
+async fn users_see_only_their_terminal_sessions() {
+    let config = test_config();
+    let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
+    let record = |name: &str| den::store::SessionRecord {
+        name: name.to_string(),
+        ssh: None,
+        backend: None,
+    };
+    store
+        .save_sessions(&[record("main"), record("carol.work")])
+        .unwrap();
+    let registry = SessionRegistry::new(
+        "powershell.exe".to_string(),
+        SleepPreventionMode::Off,
+        30,
+        Some(store.clone()),
+        den::pty::backend::MuxConfig::default(),
+    );
+    let (app, _state) =
+        den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None);
+    let owner = auth_header();
+    json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"carol","password":"carol-secret"}"#),
+    )
+    .await;
+    let carol = user_login(&app, "carol", "carol-secret").await.unwrap();
+
+    let names = |json: serde_json::Value| -> Vec<String> {
+        json.as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let (_, json) = json_request(&app, "GET", "/api/terminal/sessions", &owner, None).await;
+    assert_eq!(names(json), vec!["main"]);
+    let (_, json) = json_request(&app, "GET", "/api/terminal/sessions", &carol, None).await;
+    assert_eq!(names(json), vec!["work"]);
+
+    // Namespaced names cannot be addressed directly
+    let (status, _) = json_request(
+        &app,
+        "DELETE",
+        "/api/terminal/sessions/carol.work",
+        &owner,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn readonly_user_can_view_but_not_mutate() {
+    let config = test_config();
+    let data_dir = config.data_dir.clone();
+    let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
+    store
+        .save_sessions(&[den::store::SessionRecord {
+            name: "main".to_string(),
+            ssh: None,
+            backend: None,
+        }])
+        .unwrap();
+    let registry = SessionRegistry::new(
+        "powershell.exe".to_string(),
+        SleepPreventionMode::Off,
+        30,
+        Some(store.clone()),
+        den::pty::backend::MuxConfig::default(),
+    );
+    let (app, _state) =
+        den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None);
+    let owner = auth_header();
+    let (status, json) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"monitor","password":"monitor-secret","role":"readonly"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(json["role"], "readonly");
+    let monitor = user_login(&app, "monitor", "monitor-secret").await.unwrap();
+
+    // Sees the owner's sessions and files
+    let (status, json) = json_request(&app, "GET", "/api/terminal/sessions", &monitor, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json[0]["name"], "main");
+    let list_uri = format!("/api/filer/list?path={}", data_dir.replace('\\', "/"));
+    let (status, _) = json_request(&app, "GET", &list_uri, &monitor, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Every mutating route is rejected
+    let mkdir = serde_json::json!({ "path": format!("{data_dir}/nope") }).to_string();
+    let (status, _) = json_request(&app, "POST", "/api/filer/mkdir", &monitor, Some(&mkdir)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(!std::path::Path::new(&data_dir).join("nope").exists());
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/terminal/sessions",
+        &monitor,
+        Some(r#"{"name":"x"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = json_request(&app, "PUT", "/api/settings", &monitor, Some("{}")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = json_request(&app, "GET", "/api/remote/connections", &monitor, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+/// Bearer header from the `den_token` Set-Cookie of a response.
+fn session_bearer(resp: &axum::response::Response) -> Option<String> {
+    resp.headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(|c| c.strip_prefix("den_token="))
+        .and_then(|c| c.split(';').next())
+        .map(|token| format!("Bearer {token}"))
+}
+
+async fn change_password(
+    app: &axum::Router,
+    auth: &str,
+    current: &str,
+    new: &str,
+) -> axum::response::Response {
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/auth/change-password")
+        .header(header::AUTHORIZATION, auth)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({ "current_password": current, "new_password": new }).to_string(),
+        ))
+        .unwrap();
+    app.clone().oneshot(req).await.unwrap()
+}
+
+#[tokio::test]
+async fn vault_opens_at_owner_login_after_password_change_and_restart() {
+    let (app, state) = test_app_with_state();
+    state
+        .store
+        .set_secret(&state.vault_key().unwrap(), "sftp:user@host:22", "hunter2")
+        .unwrap();
+    let resp = change_password(&app, &auth_header(), "testpass", "new-owner-pass").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Restart: only the stored hash is known, so the vault is locked
+    let (app, state) = test_app_from_config(state.config.clone());
+    assert!(state.vault_key().is_none());
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"password":"new-owner-pass"}"#))
+        .unwrap();
+    assert_eq!(app.oneshot(req).await.unwrap().status(), StatusCode::OK);
+    let key = state.vault_key().expect("unlocked by the owner login");
+    assert_eq!(
+        state.store.get_secret(&key, "sftp:user@host:22").as_deref(),
+        Some("hunter2")
+    );
+}
+
+#[tokio::test]
+async fn change_password_rotates_secret_and_invalidates_logins() {
+    let (app, state) = test_app_with_state();
+    let owner = auth_header();
+    state
+        .store
+        .set_secret(&state.vault_key().unwrap(), "webhook:ci", "tok")
+        .unwrap();
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"alice-pass"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let alice = user_login(&app, "alice", "alice-pass").await.unwrap();
+
+    let resp = change_password(&app, &owner, "wrong", "new-owner-pass").await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = change_password(&app, &owner, "testpass", "short").await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let resp = change_password(&app, &owner, "testpass", "new-owner-pass").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let new_owner = session_bearer(&resp).expect("fresh session cookie");
+
+    // Every login issued before the change is gone, the caller's new one works
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &owner, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &alice, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &new_owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let login = |password: &'static str| {
+        let app = app.clone();
+        async move {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "password": password }).to_string(),
+                ))
+                .unwrap();
+            app.oneshot(req).await.unwrap().status()
+        }
+    };
+    assert_eq!(login("testpass").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(login("new-owner-pass").await, StatusCode::OK);
+    // The vault data key stays the same and is now wrapped under the new password
+    assert_eq!(
+        state
+            .store
+            .get_secret(&state.vault_key().unwrap(), "webhook:ci")
+            .as_deref(),
+        Some("tok")
+    );
+    let wrapped = state.store.load_vault_key().unwrap();
+    assert!(wrapped.unwrap("new-owner-pass").is_ok());
+    assert!(wrapped.unwrap("testpass").is_err());
+
+    // Registered users change their own password
+    let alice = user_login(&app, "alice", "alice-pass").await.unwrap();
+    let resp = change_password(&app, &alice, "alice-pass", "alice-pass-2").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(user_login(&app, "alice", "alice-pass").await.is_none());
+    assert!(user_login(&app, "alice", "alice-pass-2").await.is_some());
+    // Only alice's old logins die (her tokens carry the password hash); the owner stays
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &alice, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &new_owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// --- API tokens ---
+
+#[tokio::test]
+async fn api_tokens_scoped_and_revocable() {
+    let config = test_config();
+    let data_dir = config.data_dir.clone();
+    let (app, _state) = test_app_from_config(config);
+    let owner = auth_header();
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/tokens",
+        &owner,
+        Some(r#"{"name":"backup","scopes":["filer:delete"]}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/tokens",
+        &owner,
+        Some(r#"{"name":"backup","scopes":["filer:read"],"expires_in_days":0}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, json) = json_request(
+        &app,
+        "POST",
+        "/api/tokens",
+        &owner,
+        Some(r#"{"name":"backup","scopes":["filer:read"]}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = json["id"].as_str().unwrap().to_string();
+    let token = format!("Bearer {}", json["token"].as_str().unwrap());
+    assert!(json["token"].as_str().unwrap().starts_with("den_"));
+
+    let list_uri = format!("/api/filer/list?path={}", data_dir.replace('\\', "/"));
+    let (status, _) = json_request(&app, "GET", &list_uri, &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/filer/mkdir",
+        &token,
+        Some(&serde_json::json!({ "path": format!("{data_dir}/x") }).to_string()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    // Tokens cannot mint more tokens
+    let (status, _) = json_request(&app, "GET", "/api/tokens", &token, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, json) = json_request(&app, "GET", "/api/tokens", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json[0]["name"], "backup");
+    assert!(json[0].get("token_hash").is_none());
+
+    let (status, _) =
+        json_request(&app, "DELETE", &format!("/api/tokens/{id}"), &owner, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = json_request(&app, "GET", &list_uri, &token, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// --- Devices ---
+
+/// Log in as the owner from a named device; returns the Bearer header and the
+/// `den_device` cookie value.
+async fn device_login(
+    app: &axum::Router,
+    device_name: &str,
+    user_agent: &str,
+    device_cookie: Option<&str>,
+) -> (String, String) {
+    let mut req = Request::builder()
+        .method("POST")
+        .uri("/api/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::USER_AGENT, user_agent);
+    if let Some(id) = device_cookie {
+        req = req.header(header::COOKIE, format!("den_device={id}"));
+    }
+    let req = req
+        .body(Body::from(
+            serde_json::json!({ "password": "testpass", "device_name": device_name }).to_string(),
+        ))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cookie = |name: &str| {
+        resp.headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(|c| c.strip_prefix(&format!("{name}=")))
+            .and_then(|c| c.split(';').next())
+            .map(str::to_string)
+            .unwrap()
+    };
+    (
+        format!("Bearer {}", cookie("den_token")),
+        cookie("den_device"),
+    )
+}
+
+#[tokio::test]
+async fn devices_are_listed_renamed_and_revoked_one_by_one() {
+    let app = test_app();
+    let iphone_ua = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) Version/17.4 Mobile/15E148 Safari/604.1";
+    let (_, phone_id) = device_login(&app, "iPhone", iphone_ua, None).await;
+    let (laptop, laptop_id) = device_login(&app, "", "curl/8.5.0", None).await;
+    assert_ne!(phone_id, laptop_id);
+
+    let (status, json) = json_request(&app, "GET", "/api/devices", &laptop, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let devices = json.as_array().unwrap();
+    assert_eq!(devices.len(), 2);
+    let laptop_entry = devices.iter().find(|d| d["id"] == laptop_id).unwrap();
+    assert_eq!(laptop_entry["name"], "curl");
+    assert_eq!(laptop_entry["current"], true);
+    let phone_entry = devices.iter().find(|d| d["id"] == phone_id).unwrap();
+    assert_eq!(phone_entry["name"], "iPhone");
+    assert_eq!(phone_entry["current"], false);
+    assert_eq!(phone_entry["fingerprint"].as_str().unwrap().len(), 16);
+
+    let uri = format!("/api/devices/{laptop_id}");
+    let (status, _) = json_request(&app, "PUT", &uri, &laptop, Some(r#"{"name":"  "}"#)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, json) = json_request(
+        &app,
+        "PUT",
+        &uri,
+        &laptop,
+        Some(r#"{"name":"Work laptop"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["name"], "Work laptop");
+
+    // Logging in again from the same browser reuses its device
+    let (phone, again) = device_login(&app, "", iphone_ua, Some(&phone_id)).await;
+    assert_eq!(again, phone_id);
+    let (_, json) = json_request(&app, "GET", "/api/devices", &phone, None).await;
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    assert_eq!(
+        json.as_array()
+            .unwrap()
+            .iter()
+            .find(|d| d["id"] == phone_id)
+            .unwrap()["name"],
+        "iPhone"
+    );
+
+    // A token cannot be moved onto another device id
+    let forged = phone.replacen(&phone_id, &laptop_id, 1);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &forged, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Other accounts cannot see or revoke the owner's devices
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &auth_header(),
+        Some(r#"{"username":"alice","password":"alice-secret"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let alice = user_login(&app, "alice", "alice-secret").await.unwrap();
+    let (_, json) = json_request(&app, "GET", "/api/devices", &alice, None).await;
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    let (status, _) = json_request(&app, "DELETE", &uri, &alice, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Revoking the phone logs out only the phone
+    let (status, _) = json_request(
+        &app,
+        "DELETE",
+        &format!("/api/devices/{phone_id}"),
+        &laptop,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &phone, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &laptop, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Logging out forgets the device as well
+    let (status, _) = json_request(&app, "POST", "/api/logout", &laptop, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = json_request(&app, "GET", "/api/auth/me", &laptop, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (_, json) = json_request(&app, "GET", "/api/devices", &auth_header(), None).await;
+    assert!(json.as_array().unwrap().is_empty());
+}
+
+// --- Token expiry / refresh ---
+
+async fn cookie_request(app: &axum::Router, token: &str) -> (StatusCode, Vec<String>) {
+    let req = Request::builder()
+        .uri("/api/auth/me")
+        .header(header::COOKIE, format!("den_token={token}"))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let cookies = resp
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok().map(str::to_string))
+        .collect();
+    (resp.status(), cookies)
+}
+
+fn hours_ago(hours: u64) -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - hours * 60 * 60
+}
+
+#[tokio::test]
+async fn session_cookie_slides_past_half_ttl() {
+    let app = test_app();
+
+    let fresh = generate_token("testpass", TEST_HMAC_SECRET);
+    let (status, cookies) = cookie_request(&app, &fresh).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(cookies.is_empty());
+
+    let aging = generate_token_at("testpass", TEST_HMAC_SECRET, hours_ago(13));
+    let (status, cookies) = cookie_request(&app, &aging).await;
+    assert_eq!(status, StatusCode::OK);
+    let renewed = cookies
+        .iter()
+        .find_map(|c| c.strip_prefix("den_token="))
+        .and_then(|c| c.split(';').next())
+        .expect("token cookie reissued");
+    assert_ne!(renewed, aging);
+    assert!(cookies.iter().any(|c| c.starts_with("den_logged_in=1")));
+    let (status, cookies) = cookie_request(&app, renewed).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(cookies.is_empty());
+
+    // Bearer clients manage their own tokens
+    let req = Request::builder()
+        .uri("/api/auth/me")
+        .header(header::AUTHORIZATION, format!("Bearer {aging}"))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::SET_COOKIE).is_none());
+}
+
+#[tokio::test]
+async fn token_ttl_is_configurable() {
+    let config = Config {
+        token_ttl_hours: 1,
+        ..test_config()
+    };
+    let (app, _state) = test_app_from_config(config);
+    let token = generate_token_at("testpass", TEST_HMAC_SECRET, hours_ago(2));
+    let (status, _) = cookie_request(&app, &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn persisted_secret_survives_restart() {
+    let config = Config {
+        persist_secret: true,
+        ..test_config()
+    };
+    let start = |config: Config| {
+        let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
+        let registry = SessionRegistry::new(
+            "powershell.exe".to_string(),
+            SleepPreventionMode::Off,
+            30,
+            None,
+            den::pty::backend::MuxConfig::default(),
+        );
+        den::create_app(config, registry, store, None)
+    };
+
+    let (_app, before) = start(config.clone());
+    let token = generate_token("testpass", &before.hmac_secret());
+    let (after_app, after) = start(config.clone());
+    assert_eq!(before.hmac_secret(), after.hmac_secret());
+    let (status, _) = cookie_request(&after_app, &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Without the opt-in every start gets a fresh secret
+    let (_app, random) = start(Config {
+        persist_secret: false,
+        ..config
+    });
+    assert_ne!(random.hmac_secret(), after.hmac_secret());
+}
+
+// --- Feature toggles ---
+
+#[tokio::test]
+async fn disabled_features_are_not_mounted() {
+    let owner = auth_header();
+    let (app, _) = test_app_with_state();
+    let (status, features) = json_request(&app, "GET", "/api/system/features", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        features,
+        serde_json::json!({ "filer": true, "sftp": true, "remote": true, "update": true })
+    );
+
+    let mut config = test_config();
+    config.disabled.filer = true;
+    config.disabled.sftp = true;
+    let (app, _) = test_app_from_config(config);
+    let (_, features) = json_request(&app, "GET", "/api/system/features", &owner, None).await;
+    assert_eq!(features["filer"], false);
+    assert_eq!(features["sftp"], false);
+    assert_eq!(features["remote"], true);
+
+    let (status, _) = json_request(&app, "GET", "/api/filer/list?path=.", &owner, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = json_request(&app, "GET", "/api/sftp/status", &owner, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/filer/mkdir",
+        &owner,
+        Some(r#"{"path":"x"}"#),
+    )
+    .await;
+    assert!(!status.is_success());
+    // The rest of the app is unaffected
+    let (status, _) = json_request(&app, "GET", "/api/settings", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// --- Security notifications ---
+
+/// Collects webhook payloads posted to it
+async fn spawn_mock_webhook() -> (
+    String,
+    tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>,
+) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let app = axum::Router::new().route(
+        "/hook",
+        axum::routing::post(move |axum::Json(payload): axum::Json<serde_json::Value>| {
+            let tx = tx.clone();
+            async move {
+                tx.send(payload).unwrap();
+            }
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn security_events_are_sent_to_webhook() {
+    let (url, mut events) = spawn_mock_webhook().await;
+    let mut config = test_config();
+    config.notify.webhook_url = Some(url);
+    let (app, _) = test_app_from_config(config);
+    let login_from = |peer: &str, password: &str| {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/api/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({ "password": password }).to_string(),
+            ))
+            .unwrap();
+        let addr: std::net::SocketAddr = peer.parse().unwrap();
+        req.extensions_mut()
+            .insert(axum::extract::ConnectInfo(addr));
+        app.clone().oneshot(req)
+    };
+    let next_event =
+        async |events: &mut tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>| {
+            tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+                .await
+                .expect("notification not delivered")
+                .unwrap()
+        };
+
+    let resp = login_from("203.0.113.5:40000", "testpass").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let event = next_event(&mut events).await;
+    assert_eq!(event["event"], "new_ip_login");
+    assert_eq!(event["ip"], "203.0.113.5");
+    assert_eq!(event["via"], "password");
+    assert!(event["user"].is_null());
+
+    // A known address is quiet; the lockout is reported
+    let resp = login_from("203.0.113.5:40001", "testpass").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    for _ in 0..5 {
+        login_from("198.51.100.9:40000", "wrong").await.unwrap();
+    }
+    let event = next_event(&mut events).await;
+    assert_eq!(event["event"], "login_lockout");
+    assert_eq!(event["ip"], "198.51.100.9");
+    assert!(events.try_recv().is_err());
+}
+
+// --- IP filter ---
+
+#[tokio::test]
+async fn ip_filter_applies_before_auth() {
+    let config = Config {
+        allow_cidrs: vec!["10.0.0.0/8".to_string()],
+        deny_cidrs: vec!["10.0.0.13".to_string()],
+        ..test_config()
+    };
+    let (app, _) = test_app_from_config(config);
+    let request_from = |peer: Option<&str>, uri: &str| {
+        let mut req = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap();
+        if let Some(peer) = peer {
+            let addr: std::net::SocketAddr = peer.parse().unwrap();
+            req.extensions_mut()
+                .insert(axum::extract::ConnectInfo(addr));
+        }
+        app.clone().oneshot(req)
+    };
+
+    let resp = request_from(Some("10.1.2.3:50000"), "/api/auth/me")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = request_from(Some("10.0.0.13:50000"), "/api/auth/me")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    // Public routes are covered too
+    let resp = request_from(Some("192.168.0.5:50000"), "/").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = request_from(None, "/api/auth/me").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+// --- Audit log ---
+
+#[tokio::test]
+async fn audit_log_records_mutating_calls() {
+    let config = test_config();
+    let data_dir = config.data_dir.clone();
+    let (app, _state) = test_app_from_config(config);
+    let owner = auth_header();
+
+    let dir = format!("{}/audited", data_dir.replace('\\', "/"));
+    let body = serde_json::json!({ "path": dir }).to_string();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/mkdir")
+        .header(header::AUTHORIZATION, &owner)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(Body::from(body))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert!(resp.status().is_success());
+    let (status, _) = json_request(
+        &app,
+        "DELETE",
+        &format!("/api/filer/delete?path={}", urlencoding::encode(&dir)),
+        &owner,
+        None,
+    )
+    .await;
+    assert!(status.is_success());
+    // Reads are not audited
+    let (status, _) = json_request(&app, "GET", "/api/settings", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Entries are written off the request path
+    let mut entries = serde_json::Value::Null;
+    for _ in 0..50 {
+        let (status, json) = json_request(&app, "GET", "/api/audit", &owner, None).await;
+        assert_eq!(status, StatusCode::OK);
+        if json.as_array().is_some_and(|a| a.len() >= 2) {
+            entries = json;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let entries = entries.as_array().expect("audit entries");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["method"], "DELETE");
+    assert_eq!(entries[0]["args"]["path"], dir.as_str());
+    assert_eq!(entries[1]["path"], "/api/filer/mkdir");
+    assert_eq!(entries[1]["args"]["path"], dir.as_str());
+    assert!(entries[1]["user"].is_null());
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"bob","password":"long-enough-pw"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let bob = user_login(&app, "bob", "long-enough-pw").await.unwrap();
+    let (status, _) = json_request(&app, "GET", "/api/audit", &bob, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn logs_tail_returns_parsed_json_lines_to_admins() {
+    let mut config = test_config();
+    config.log_files.format = den::logs::LogFormat::Json;
+    let log_dir = std::path::Path::new(&config.data_dir).join("logs");
+    let (app, _state) = test_app_from_config(config);
+    let owner = auth_header();
+
+    let (status, json) = json_request(&app, "GET", "/api/logs/tail", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["file"].is_null());
+    assert_eq!(json["lines"], serde_json::json!([]));
+
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::write(
+        log_dir.join("den.log.2026-10-17"),
+        "{\"level\":\"INFO\",\"fields\":{\"message\":\"one\"}}\nnot json\n{\"level\":\"WARN\"}\n",
+    )
+    .unwrap();
+    let (status, json) = json_request(&app, "GET", "/api/logs/tail?lines=2", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["file"], "den.log.2026-10-17");
+    assert_eq!(json["format"], "json");
+    assert_eq!(
+        json["lines"],
+        serde_json::json!(["not json", {"level": "WARN"}])
+    );
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"bob","password":"long-enough-pw"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let bob = user_login(&app, "bob", "long-enough-pw").await.unwrap();
+    let (status, _) = json_request(&app, "GET", "/api/logs/tail", &bob, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn status_reports_self_check_to_admins() {
+    let (app, state) = test_app_with_state();
+    let owner = auth_header();
+
+    let (status, json) = json_request(&app, "GET", "/api/status", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(json["data_dir"]["writable"], true);
+    // Not checked until self_check has run
+    assert!(json["pty"]["available"].is_null());
+    assert_eq!(json["ssh"]["state"], "disabled");
+    assert_eq!(json["features"]["filer"], true);
+    assert_eq!(json["features"]["tls"], false);
+    assert_eq!(json["warnings"], serde_json::json!([]));
+
+    den::status::self_check(&state).await;
+    state.diagnostics.warn("Removed 1 orphaned upload");
+    state
+        .diagnostics
+        .set_ssh(den::status::SshState::Running { port: 2222 });
+    let (_, json) = json_request(&app, "GET", "/api/status", &owner, None).await;
+    assert!(json["pty"]["available"].is_boolean());
+    assert_eq!(
+        json["ssh"],
+        serde_json::json!({"state": "running", "port": 2222})
+    );
+    assert!(
+        json["warnings"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("Removed 1 orphaned upload"))
+    );
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"bob","password":"long-enough-pw"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let bob = user_login(&app, "bob", "long-enough-pw").await.unwrap();
+    let (status, _) = json_request(&app, "GET", "/api/status", &bob, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn version_reports_the_running_build() {
+    let app = test_app();
+    let (status, json) = json_request(&app, "GET", "/api/version", &auth_header(), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["current"], env!("CARGO_PKG_VERSION"));
+    // No upstream check unless asked for
+    assert!(json["latest"].is_null());
+    assert_eq!(json["update_available"], false);
+    // Test builds carry no release signing key
+    assert_eq!(json["can_update"], false);
+
+    let (status, _) = json_request(&app, "GET", "/api/version", "", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Refused up front, before anything is downloaded
+    let (status, json) =
+        json_request(&app, "POST", "/api/system/update", &auth_header(), None).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert!(json["error"].as_str().unwrap().contains("signing key"));
+}
+
+#[tokio::test]
+async fn admin_listener_takes_the_management_endpoints() {
+    let mut config = test_config();
+    config.admin_listener = Some(den::config::AdminListener {
+        bind_address: "127.0.0.1".into(),
+        port: 3940,
+    });
+    let (app, state) = test_app_from_config(config);
+    let admin = den::admin_app(&state);
+    let owner = auth_header();
+
+    for uri in ["/api/users", "/api/audit", "/api/status", "/metrics"] {
+        let (status, _) = json_request(&app, "GET", uri, &owner, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{uri} on the main listener");
+        let (status, _) = json_request(&admin, "GET", uri, &owner, None).await;
+        assert_eq!(status, StatusCode::OK, "{uri} on the admin listener");
+    }
+    // The UI and the rest of the API stay on the main listener
+    let (status, _) = json_request(&app, "GET", "/api/settings", &owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, json) = json_request(&admin, "GET", "/api/status", &owner, None).await;
+    assert_eq!(json["features"]["admin_listener"], true);
+}
+
+// --- CSRF ---
+
+#[tokio::test]
+async fn cookie_writes_require_csrf_header() {
+    let app = test_app();
+    let token = generate_token("testpass", TEST_HMAC_SECRET);
+    let put_keep_awake = |cookie: bool, csrf: bool| {
+        let mut req = Request::builder()
+            .method("PUT")
+            .uri("/api/keep-awake")
+            .header(header::CONTENT_TYPE, "application/json");
+        req = if cookie {
+            req.header(header::COOKIE, format!("den_token={token}"))
+        } else {
+            req.header(header::AUTHORIZATION, format!("Bearer {token}"))
+        };
+        if csrf {
+            req = req.header("X-Den-Request", "1");
+        }
+        let req = req.body(Body::from(r#"{"enabled":false}"#)).unwrap();
+        app.clone().oneshot(req)
+    };
+
+    let resp = put_keep_awake(true, false).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = put_keep_awake(true, true).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    // Bearer tokens cannot be attached by a cross-site page, so no header needed
+    let resp = put_keep_awake(false, false).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Reads via cookie are unaffected
+    let (status, _) = cookie_request(&app, &token).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// --- Client certificate login ---
+
+async fn cert_login(app: &axum::Router, common_name: Option<&str>) -> (StatusCode, Option<String>) {
+    let mut req = Request::builder().method("POST").uri("/api/auth/cert");
+    if let Some(cn) = common_name {
+        req = req.extension(den::tls::ClientCertIdentity {
+            common_name: Some(cn.to_string()),
+            fingerprint: format!("SHA256:{}", "ab".repeat(32)),
+        });
+    }
+    let resp = app
+        .clone()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let bearer = resp
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(|c| c.strip_prefix("den_token="))
+        .and_then(|c| c.split(';').next())
+        .map(|token| format!("Bearer {token}"));
+    (resp.status(), bearer)
+}
+
+#[tokio::test]
+async fn client_certificate_logs_in_as_mapped_account() {
+    // Not configured: the route does nothing even with a certificate
+    let app = test_app();
+    assert_eq!(
+        cert_login(&app, Some("owner")).await.0,
+        StatusCode::NOT_FOUND
+    );
+
+    let mut config = test_config();
+    config.tls_client_ca = Some("client-ca.pem".to_string());
+    config.tls_client_owner_cn = Some("den-owner".to_string());
+    let (app, _) = test_app_from_config(config);
+    assert_eq!(cert_login(&app, None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        cert_login(&app, Some("mallory")).await.0,
+        StatusCode::FORBIDDEN
+    );
+
+    let (status, owner) = cert_login(&app, Some("den-owner")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, json) = json_request(&app, "GET", "/api/auth/me", &owner.unwrap(), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["username"].is_null());
+    assert_eq!(json["role"], "admin");
+
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &auth_header(),
+        Some(r#"{"username":"alice","password":"alice-secret"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, alice) = cert_login(&app, Some("alice")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, json) = json_request(&app, "GET", "/api/auth/me", &alice.unwrap(), None).await;
+    assert_eq!(json["username"], "alice");
+}
+
+// --- OIDC single sign-on ---
+
+/// Minimal IdP: discovery + a token endpoint that checks the PKCE verifier and
+/// returns an unsigned ID token for `sub`, echoing the nonce it is handed.
+async fn spawn_mock_idp(
+    sub: &'static str,
+    expected: std::sync::Arc<std::sync::Mutex<(String, String)>>,
+) -> String {
+    use axum::routing::{get, post};
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use sha2::Digest;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+    let discovery = serde_json::json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+    });
+    let token_issuer = issuer.clone();
+    let app = axum::Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(move || async move { axum::Json(discovery) }),
+        )
+        .route(
+            "/token",
+            post(move |body: String| async move {
+                let form: std::collections::HashMap<String, String> =
+                    reqwest::Url::parse(&format!("http://x/?{body}"))
+                        .unwrap()
+                        .query_pairs()
+                        .into_owned()
+                        .collect();
+                let (challenge, nonce) = expected.lock().unwrap().clone();
+                let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+                if form.get("code").map(String::as_str) != Some("good-code")
+                    || URL_SAFE_NO_PAD.encode(sha2::Sha256::digest(verifier.as_bytes()))
+                        != challenge
+                {
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+                let claims = serde_json::json!({
+                    "iss": token_issuer,
+                    "sub": sub,
+                    "aud": "den",
+                    "exp": 4_000_000_000u64,
+                    "nonce": nonce,
+                });
+                let id_token = format!(
+                    "eyJhbGciOiJub25lIn0.{}.",
+                    URL_SAFE_NO_PAD.encode(claims.to_string())
+                );
+                Ok(axum::Json(serde_json::json!({
+                    "access_token": "at",
+                    "token_type": "Bearer",
+                    "id_token": id_token,
+                })))
+            }),
+        );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    issuer
+}
+
+/// Start the login, returning (state cookie value, state, nonce, code_challenge).
+async fn oidc_begin(app: &axum::Router) -> (String, String, String, String) {
+    let req = Request::builder()
+        .uri("/api/auth/oidc/login")
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let cookie = resp
+        .headers()
+        .get(header::SET_COOKIE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let location = reqwest::Url::parse(
+        resp.headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(location.path().ends_with("/authorize"));
+    let param = |name: &str| {
+        location
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+            .unwrap()
+    };
+    assert_eq!(param("code_challenge_method"), "S256");
+    assert_eq!(param("client_id"), "den");
+    (
+        cookie,
+        param("state"),
+        param("nonce"),
+        param("code_challenge"),
+    )
+}
+
+async fn oidc_callback(app: &axum::Router, cookie: &str, state: &str) -> axum::response::Response {
+    let req = Request::builder()
+        .uri(format!(
+            "/api/auth/oidc/callback?code=good-code&state={state}"
+        ))
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(req).await.unwrap()
+}
+
+fn oidc_config(issuer: &str) -> Config {
+    let mut config = test_config();
+    config.oidc = Some(den::oidc::OidcConfig {
+        issuer: issuer.to_string(),
+        client_id: "den".to_string(),
+        client_secret: Some("s3cret".to_string()),
+        redirect_url: "http://localhost/api/auth/oidc/callback".to_string(),
+        owner_subject: Some("owner-sub".to_string()),
+    });
+    config
+}
+
+#[tokio::test]
+async fn oidc_disabled_by_default() {
+    let app = test_app();
+    let (status, body) = json_request(&app, "GET", "/api/auth/oidc", "", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["enabled"], false);
+    let req = Request::builder()
+        .uri("/api/auth/oidc/login")
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn oidc_login_maps_subjects_to_accounts() {
+    let expected = std::sync::Arc::new(std::sync::Mutex::new((String::new(), String::new())));
+    let issuer = spawn_mock_idp("alice-sub", std::sync::Arc::clone(&expected)).await;
+    let (app, _) = test_app_from_config(oidc_config(&issuer));
+    let (_, body) = json_request(&app, "GET", "/api/auth/oidc", "", None).await;
+    assert_eq!(body["enabled"], true);
+
+    // Unlinked subject is refused
+    let (cookie, state, nonce, challenge) = oidc_begin(&app).await;
+    *expected.lock().unwrap() = (challenge, nonce);
+    let resp = oidc_callback(&app, &cookie, &state).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let owner = auth_header();
+    let (status, user) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"alice","password":"alice-pass","oidc_subject":"alice-sub"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(user["oidc_subject"], "alice-sub");
+    let (status, _) = json_request(
+        &app,
+        "POST",
+        "/api/users",
+        &owner,
+        Some(r#"{"username":"bob","password":"bob-pass1","oidc_subject":"alice-sub"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // State must match the browser's cookie and can only be used once
+    let (cookie, state, nonce, challenge) = oidc_begin(&app).await;
+    *expected.lock().unwrap() = (challenge, nonce);
+    let resp = oidc_callback(&app, "den_oidc_state=other", &state).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = oidc_callback(&app, &cookie, &state).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let token = session_bearer(&resp).expect("session cookie");
+    let (status, me) = json_request(&app, "GET", "/api/auth/me", &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(me["username"], "alice");
+
+    let resp = oidc_callback(&app, &cookie, &state).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Password login still works alongside SSO
+    assert!(user_login(&app, "alice", "alice-pass").await.is_some());
+}
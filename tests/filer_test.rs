@@ -19,16 +19,38 @@ fn test_config() -> Config {
     Config {
         port: 0,
         password: "testpass".to_string(),
+        password_hash: None,
         shell: "powershell.exe".to_string(),
         env: Environment::Development,
         log_level: "debug".to_string(),
+        log_files: Default::default(),
         data_dir: tmp.to_string_lossy().to_string(),
         bind_address: "127.0.0.1".to_string(),
         ssh_port: None,
+        local_socket: None,
         tls_enabled: false,
         tls_cert_path: None,
         tls_key_path: None,
         tls_subject_alt_names: vec![],
+        tls_client_ca: None,
+        tls_client_owner_cn: None,
+        acme: None,
+        filer_roots: vec![],
+        token_ttl_hours: 24,
+        persist_secret: false,
+        allow_cidrs: Vec::new(),
+        deny_cidrs: Vec::new(),
+        trusted_proxies: Vec::new(),
+        oidc: None,
+        vault_keyfile: None,
+        metrics_token: None,
+        admin_listener: None,
+        otlp: None,
+        disabled: Default::default(),
+        csp: Default::default(),
+        security_headers: Default::default(),
+        cors: None,
+        notify: Default::default(),
     }
 }
 
@@ -45,6 +67,24 @@ fn test_app() -> axum::Router {
     den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None).0
 }
 
+/// Helper: app whose filer is jailed to a fresh tempdir
+fn test_app_with_roots() -> (axum::Router, tempfile::TempDir) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut config = test_config();
+    config.filer_roots = vec![dir.path().to_string_lossy().into_owned()];
+    let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
+    let registry = SessionRegistry::new(
+        "powershell.exe".to_string(),
+        SleepPreventionMode::Off,
+        30,
+        None,
+        den::pty::backend::MuxConfig::default(),
+    );
+    let (app, _state) =
+        den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None);
+    (app, dir)
+}
+
 fn auth_header() -> String {
     format!("Bearer {}", generate_token("testpass", TEST_HMAC_SECRET))
 }
@@ -348,10 +388,283 @@ async fn read_requires_auth() {
     assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[tokio::test]
+async fn read_byte_range() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("range.txt");
+    std::fs::write(&file, "0123456789").unwrap();
+
+    let req = Request::builder()
+        .uri(format!(
+            "/api/filer/read?path={}&offset=3&length=4",
+            encode_path(&file)
+        ))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["content"], "3456");
+    assert_eq!(json["offset"], 3);
+    assert_eq!(json["size"], 4);
+    assert_eq!(json["total_size"], 10);
+}
+
+#[tokio::test]
+async fn read_hex_dump_of_binary() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("blob.bin");
+    let mut data = vec![0u8; 40];
+    data[..4].copy_from_slice(b"\x89PNG");
+    std::fs::write(&file, &data).unwrap();
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!(
+                "/api/filer/read?path={}&format=hex&offset=16",
+                encode_path(&file)
+            ),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = json_body(resp).await;
+    assert_eq!(json["is_binary"], true);
+    assert_eq!(json["size"], 24);
+    let content = json["content"].as_str().unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("00000010  00 00"));
+    assert!(lines[1].starts_with("00000020  00"));
+}
+
+// ============================================================
+// GET /api/filer/checksum
+// ============================================================
+
+#[tokio::test]
+async fn checksum_algorithms() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("hash.txt");
+    std::fs::write(&file, "abc").unwrap();
+
+    for (algo, expected) in [
+        ("md5", "900150983cd24fb0d6963f7d28e17f72"),
+        ("sha1", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+        (
+            "sha256",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+    ] {
+        let req = Request::builder()
+            .uri(format!(
+                "/api/filer/checksum?path={}&algo={algo}",
+                encode_path(&file)
+            ))
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["hash"], expected, "{algo}");
+        assert_eq!(json["algo"], algo);
+    }
+}
+
+#[tokio::test]
+async fn checksum_rejects_directory() {
+    let (app, dir) = test_app_with_dir();
+    let req = Request::builder()
+        .uri(format!(
+            "/api/filer/checksum?path={}",
+            encode_path(dir.path())
+        ))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// ============================================================
+// GET /api/filer/preview
+// ============================================================
+
+#[tokio::test]
+async fn preview_returns_leading_lines() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("big.log");
+    let content: String = (1..=1000).map(|i| format!("line {i}\n")).collect();
+    std::fs::write(&file, &content).unwrap();
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/preview?path={}&lines=3", encode_path(&file)),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = json_body(resp).await;
+    assert_eq!(json["content"], "line 1\nline 2\nline 3\n");
+    assert_eq!(json["lines"], 3);
+    assert_eq!(json["total_lines"], 1000);
+    assert_eq!(json["size"], content.len());
+    assert_eq!(json["truncated"], true);
+    assert_eq!(json["encoding"], "utf-8");
+}
+
+#[tokio::test]
+async fn preview_respects_byte_cap_and_small_files() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("one-line.txt");
+    std::fs::write(&file, "x".repeat(1000)).unwrap();
+    let small = dir.path().join("small.txt");
+    std::fs::write(&small, "\u{feff}hello").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/preview?path={}&bytes=10", encode_path(&file)),
+        ))
+        .await
+        .unwrap();
+    let json = json_body(resp).await;
+    assert_eq!(json["content"], "x".repeat(10));
+    assert_eq!(json["truncated"], true);
+    assert_eq!(json["total_lines"], 1);
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/preview?path={}", encode_path(&small)),
+        ))
+        .await
+        .unwrap();
+    let json = json_body(resp).await;
+    assert_eq!(json["content"], "hello");
+    assert_eq!(json["encoding"], "utf-8-bom");
+    assert_eq!(json["truncated"], false);
+}
+
+// ============================================================
+// GET /api/filer/tail
+// ============================================================
+
+#[tokio::test]
+async fn tail_lines_and_follow() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("app.log");
+    let log: String = (1..=50).map(|i| format!("line {i}\n")).collect();
+    std::fs::write(&file, &log).unwrap();
+
+    let tail = |query: String| {
+        Request::builder()
+            .uri(format!(
+                "/api/filer/tail?path={}{query}",
+                encode_path(&file)
+            ))
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let resp = app.clone().oneshot(tail("&lines=2".into())).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["content"], "line 49\nline 50\n");
+    assert_eq!(json["truncated"], true);
+    let size = json["size"].as_u64().unwrap();
+    assert_eq!(size, log.len() as u64);
+
+    // Follow: only appended data comes back
+    let mut f = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&file)
+        .unwrap();
+    std::io::Write::write_all(&mut f, b"line 51\n").unwrap();
+    let resp = app.oneshot(tail(format!("&offset={size}"))).await.unwrap();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["content"], "line 51\n");
+    assert_eq!(json["offset"], size);
+    assert_eq!(json["rotated"], false);
+}
+
 // ============================================================
 // PUT /api/filer/write
 // ============================================================
 
+fn write_request(body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method("PUT")
+        .uri("/api/filer/write")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn write_with_if_match_detects_external_change() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("edit.txt");
+    std::fs::write(&file, "v1").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/read?path={}", encode_path(&file)),
+        ))
+        .await
+        .unwrap();
+    let read = json_body(resp).await;
+    let hash = read["hash"].as_str().unwrap().to_string();
+    assert!(read["mtime"].is_string());
+
+    // Matching hash: write succeeds and returns the new version
+    let resp = app
+        .clone()
+        .oneshot(write_request(
+            serde_json::json!({ "path": file, "content": "v2", "if_match": hash }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let new_hash = json_body(resp).await["hash"].as_str().unwrap().to_string();
+    assert_ne!(new_hash, hash);
+
+    // Changed in the terminal meanwhile: stale hash is rejected
+    std::fs::write(&file, "edited elsewhere").unwrap();
+    let resp = app
+        .clone()
+        .oneshot(write_request(
+            serde_json::json!({ "path": file, "content": "v3", "if_match": new_hash }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "edited elsewhere");
+
+    // Deleted meanwhile
+    std::fs::remove_file(&file).unwrap();
+    let resp = app
+        .oneshot(write_request(
+            serde_json::json!({ "path": file, "content": "v3", "if_match": new_hash }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(!file.exists());
+}
+
 #[tokio::test]
 async fn write_new_file() {
     let (app, dir) = test_app_with_dir();
@@ -501,131 +814,1147 @@ async fn mkdir_requires_auth() {
 }
 
 // ============================================================
-// POST /api/filer/rename
+// POST /api/filer/create
 // ============================================================
 
-#[tokio::test]
-async fn rename_file() {
-    let (app, dir) = test_app_with_dir();
-    let from = dir.path().join("old.txt");
-    let to = dir.path().join("new.txt");
-    std::fs::write(&from, "content").unwrap();
-
-    let req = Request::builder()
+fn create_request(body: serde_json::Value) -> Request<Body> {
+    Request::builder()
         .method("POST")
-        .uri("/api/filer/rename")
+        .uri("/api/filer/create")
         .header(header::CONTENT_TYPE, "application/json")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(
-            serde_json::json!({
-                "from": from.to_string_lossy(),
-                "to": to.to_string_lossy()
-            })
-            .to_string(),
-        ))
-        .unwrap();
-
-    let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::OK);
-    assert!(!from.exists());
-    assert!(to.exists());
-    assert_eq!(std::fs::read_to_string(&to).unwrap(), "content");
+        .body(Body::from(body.to_string()))
+        .unwrap()
 }
 
 #[tokio::test]
-async fn rename_nonexistent() {
+async fn create_empty_file_conflicts_on_existing() {
     let (app, dir) = test_app_with_dir();
-    let from = dir.path().join("missing.txt");
-    let to = dir.path().join("new.txt");
+    let file = dir.path().join("sub").join("new.txt");
 
-    let req = Request::builder()
-        .method("POST")
-        .uri("/api/filer/rename")
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::AUTHORIZATION, auth_header())
-        .body(Body::from(
-            serde_json::json!({
-                "from": from.to_string_lossy(),
-                "to": to.to_string_lossy()
-            })
-            .to_string(),
+    let resp = app
+        .clone()
+        .oneshot(create_request(
+            serde_json::json!({"path": file.to_string_lossy()}),
         ))
+        .await
         .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
 
-    let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    std::fs::write(&file, "keep").unwrap();
+    let resp = app
+        .oneshot(create_request(
+            serde_json::json!({"path": file.to_string_lossy()}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep");
 }
 
 #[tokio::test]
-async fn rename_requires_auth() {
-    let app = test_app();
-    let req = Request::builder()
-        .method("POST")
-        .uri("/api/filer/rename")
-        .header(header::CONTENT_TYPE, "application/json")
-        .body(Body::from(r#"{"from":"~/a","to":"~/b"}"#))
+async fn create_from_settings_template() {
+    let (app, dir) = test_app_with_dir();
+    let settings = serde_json::json!({
+        "file_templates": [{"name": "compose", "content": "services:\n"}]
+    });
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/api/settings")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, auth_header())
+                .body(Body::from(settings.to_string()))
+                .unwrap(),
+        )
+        .await
         .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
 
-    let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let file = dir.path().join("docker-compose.yml");
+    let resp = app
+        .clone()
+        .oneshot(create_request(
+            serde_json::json!({"path": file.to_string_lossy(), "template": "compose"}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "services:\n");
+
+    let other = dir.path().join("other.yml");
+    let resp = app
+        .oneshot(create_request(
+            serde_json::json!({"path": other.to_string_lossy(), "template": "missing"}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(!other.exists());
 }
 
 // ============================================================
-// DELETE /api/filer/delete
+// /api/filer/recent, /api/filer/bookmarks
 // ============================================================
 
 #[tokio::test]
-async fn delete_file() {
+async fn recent_tracks_read_and_write() {
     let (app, dir) = test_app_with_dir();
-    let file = dir.path().join("to-delete.txt");
-    std::fs::write(&file, "bye").unwrap();
-
-    let path = encode_path(&file);
-    let req = Request::builder()
-        .method("DELETE")
-        .uri(format!("/api/filer/delete?path={}", path))
-        .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    std::fs::write(&a, "a").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/read?path={}", encode_path(&a)),
+        ))
+        .await
         .unwrap();
-
-    let resp = app.oneshot(req).await.unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
-    assert!(!file.exists());
+    let resp = app
+        .clone()
+        .oneshot(write_request(
+            serde_json::json!({"path": b.to_string_lossy(), "content": "b"}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(authed("GET", "/api/filer/recent"))
+        .await
+        .unwrap();
+    let recent = json_body(resp).await;
+    assert_eq!(recent[0]["action"], "write");
+    assert!(recent[0]["path"].as_str().unwrap().ends_with("b.txt"));
+    assert_eq!(recent[1]["action"], "read");
+
+    let resp = app
+        .clone()
+        .oneshot(authed("DELETE", "/api/filer/recent"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = app
+        .oneshot(authed("GET", "/api/filer/recent"))
+        .await
+        .unwrap();
+    assert_eq!(json_body(resp).await, serde_json::json!([]));
 }
 
 #[tokio::test]
-async fn delete_directory() {
+async fn bookmarks_crud() {
     let (app, dir) = test_app_with_dir();
-    let sub = dir.path().join("subdir");
+    let sub = dir.path().join("projects");
     std::fs::create_dir(&sub).unwrap();
-    std::fs::write(sub.join("file.txt"), "content").unwrap();
 
-    let path = encode_path(&sub);
-    let req = Request::builder()
-        .method("DELETE")
-        .uri(format!("/api/filer/delete?path={}", path))
-        .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
-        .unwrap();
+    let bookmark_request = |method: &str, uri: &str, body: serde_json::Value| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
 
-    let resp = app.oneshot(req).await.unwrap();
+    let resp = app
+        .clone()
+        .oneshot(bookmark_request(
+            "POST",
+            "/api/filer/bookmarks",
+            serde_json::json!({"path": sub.to_string_lossy()}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let created = json_body(resp).await;
+    assert_eq!(created["label"], "projects");
+    let id = created["id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(bookmark_request(
+            "PUT",
+            &format!("/api/filer/bookmarks/{id}"),
+            serde_json::json!({"path": sub.to_string_lossy(), "label": "Work"}),
+        ))
+        .await
+        .unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
-    assert!(!sub.exists());
-}
 
-#[tokio::test]
-async fn delete_nonexistent() {
-    let (app, dir) = test_app_with_dir();
-    let file = dir.path().join("nonexistent.txt");
-    let path = encode_path(&file);
-    let req = Request::builder()
-        .method("DELETE")
-        .uri(format!("/api/filer/delete?path={}", path))
-        .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
+    let resp = app
+        .clone()
+        .oneshot(bookmark_request(
+            "POST",
+            "/api/filer/bookmarks",
+            serde_json::json!({"path": dir.path().join("missing").to_string_lossy()}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+        .clone()
+        .oneshot(authed("GET", "/api/filer/bookmarks"))
+        .await
         .unwrap();
+    let list = json_body(resp).await;
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["label"], "Work");
 
-    let resp = app.oneshot(req).await.unwrap();
+    let resp = app
+        .clone()
+        .oneshot(authed("DELETE", &format!("/api/filer/bookmarks/{id}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = app
+        .oneshot(authed("DELETE", &format!("/api/filer/bookmarks/{id}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// ============================================================
+// POST /api/filer/dedupe-scan
+// ============================================================
+
+#[tokio::test]
+async fn dedupe_scan_finds_duplicates() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::create_dir(dir.path().join("backup")).unwrap();
+    std::fs::write(dir.path().join("photo.jpg"), vec![7u8; 2048]).unwrap();
+    std::fs::write(dir.path().join("backup").join("photo.jpg"), vec![7u8; 2048]).unwrap();
+    std::fs::write(dir.path().join("other.jpg"), vec![8u8; 2048]).unwrap();
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/dedupe-scan",
+            serde_json::json!({"path": dir.path().to_string_lossy()}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = json_body(resp).await;
+    assert_eq!(json["scanned_files"], 3);
+    assert_eq!(json["groups"].as_array().unwrap().len(), 1);
+    assert_eq!(json["groups"][0]["size"], 2048);
+    assert_eq!(json["groups"][0]["paths"].as_array().unwrap().len(), 2);
+    assert_eq!(json["reclaimable"], 2048);
+}
+
+// ============================================================
+// POST /api/filer/du
+// ============================================================
+
+#[tokio::test]
+async fn du_job_reports_total_size() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub").join("a.bin"), vec![0u8; 1000]).unwrap();
+    std::fs::write(dir.path().join("b.txt"), "hello").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/filer/du")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, auth_header())
+                .body(Body::from(
+                    serde_json::json!({"path": dir.path().to_string_lossy()}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["id"].as_str().unwrap().to_string();
+
+    let mut status = serde_json::Value::Null;
+    for _ in 0..100 {
+        let resp = app
+            .clone()
+            .oneshot(authed("GET", &format!("/api/filer/du/{id}")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        status = serde_json::from_slice(&body).unwrap();
+        if status["state"] != "running" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(status["state"], "done");
+    assert_eq!(status["size"], 1005);
+    assert_eq!(status["files"], 2);
+    assert_eq!(status["children"][0]["name"], "sub");
+
+    let resp = app
+        .oneshot(authed("GET", "/api/filer/du/unknown"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn list_with_git_status() {
+    let (app, dir) = test_app_with_dir();
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .is_ok_and(|o| o.status.success())
+    };
+    if !git(&["init", "-q"]) {
+        return; // git not installed
+    }
+    std::fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src").join("main.rs"), "fn main() {}").unwrap();
+    std::fs::write(dir.path().join("clean.txt"), "clean").unwrap();
+    assert!(git(&["add", "."]));
+    assert!(git(&[
+        "-c",
+        "user.name=t",
+        "-c",
+        "user.email=t@example.com",
+        "commit",
+        "-qm",
+        "init"
+    ]));
+    std::fs::write(dir.path().join("src").join("main.rs"), "fn main() { }").unwrap();
+    std::fs::write(dir.path().join("new.txt"), "new").unwrap();
+    std::fs::write(dir.path().join("debug.log"), "log").unwrap();
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!(
+                "/api/filer/list?path={}&git=true&show_hidden=true",
+                encode_path(dir.path())
+            ),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = json_body(resp).await;
+    let status = |name: &str| {
+        json["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["name"] == name)
+            .unwrap()["git"]
+            .clone()
+    };
+    assert_eq!(status("src"), "modified");
+    assert_eq!(status("new.txt"), "untracked");
+    assert_eq!(status("debug.log"), "ignored");
+    assert!(status("clean.txt").is_null());
+}
+
+// ============================================================
+// POST /api/filer/attributes
+// ============================================================
+
+#[tokio::test]
+async fn list_includes_attributes() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("file.txt"), "x").unwrap();
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/list?path={}", encode_path(dir.path())),
+        ))
+        .await
+        .unwrap();
+    let json = json_body(resp).await;
+    let entry = &json["entries"][0];
+    assert_eq!(entry["readonly"], false);
+    assert_eq!(entry["hidden"], false);
+    #[cfg(unix)]
+    assert!(entry["mode"].is_number());
+    #[cfg(windows)]
+    assert!(entry.get("mode").is_none());
+}
+
+#[tokio::test]
+async fn attributes_toggle_readonly() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("locked.txt");
+    std::fs::write(&file, "x").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/attributes",
+            serde_json::json!({ "path": file, "readonly": true }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(json_body(resp).await["readonly"], true);
+    assert!(std::fs::metadata(&file).unwrap().permissions().readonly());
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/attributes",
+            serde_json::json!({ "path": file, "readonly": false }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(json_body(resp).await["readonly"], false);
+    assert!(!std::fs::metadata(&file).unwrap().permissions().readonly());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn attributes_set_unix_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("script.sh");
+    std::fs::write(&file, "#!/bin/sh").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/attributes",
+            serde_json::json!({ "path": file, "mode": "0750" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(json_body(resp).await["mode"], 0o750);
+    let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o750);
+
+    for body in [
+        serde_json::json!({ "path": file, "mode": "999" }),
+        serde_json::json!({ "path": file, "hidden": true }),
+    ] {
+        let resp = app
+            .clone()
+            .oneshot(copy_move_request("/api/filer/attributes", body))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[cfg(windows)]
+#[tokio::test]
+async fn attributes_toggle_hidden() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("file.txt");
+    std::fs::write(&file, "x").unwrap();
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/attributes",
+            serde_json::json!({ "path": file, "hidden": true }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(json_body(resp).await["hidden"], true);
+}
+
+// ============================================================
+// Symlinks
+// ============================================================
+
+#[cfg(unix)]
+#[tokio::test]
+async fn list_reports_symlinks() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::create_dir(dir.path().join("real")).unwrap();
+    std::os::unix::fs::symlink("real", dir.path().join("link")).unwrap();
+    std::os::unix::fs::symlink("missing", dir.path().join("broken")).unwrap();
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/list?path={}", encode_path(dir.path())),
+        ))
+        .await
+        .unwrap();
+    let json = json_body(resp).await;
+    let entry = |name: &str| {
+        json["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["name"] == name)
+            .unwrap()
+            .clone()
+    };
+    let link = entry("link");
+    assert_eq!(link["is_symlink"], true);
+    assert_eq!(link["is_dir"], true);
+    assert_eq!(link["link_target"], "real");
+    assert_eq!(entry("broken")["is_symlink"], true);
+    assert_eq!(entry("real")["is_symlink"], false);
+    assert!(entry("real").get("link_target").is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn delete_symlink_keeps_target() {
+    let (app, dir) = test_app_with_dir();
+    let real = dir.path().join("real");
+    std::fs::create_dir(&real).unwrap();
+    std::fs::write(real.join("keep.txt"), "x").unwrap();
+    let link = dir.path().join("link");
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+
+    let resp = app
+        .oneshot(authed(
+            "DELETE",
+            &format!("/api/filer/delete?path={}", encode_path(&link)),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(std::fs::symlink_metadata(&link).is_err());
+    assert!(real.join("keep.txt").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn copy_and_rename_act_on_links() {
+    let (app, dir) = test_app_with_dir();
+    let real = dir.path().join("real");
+    std::fs::create_dir(&real).unwrap();
+    std::fs::write(real.join("a.txt"), "x").unwrap();
+    let tree = dir.path().join("tree");
+    std::fs::create_dir(&tree).unwrap();
+    std::os::unix::fs::symlink(&real, tree.join("link")).unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({ "from": tree, "to": dir.path().join("copy") }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let copied = dir.path().join("copy/link");
+    assert!(
+        std::fs::symlink_metadata(&copied)
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(std::fs::read_link(&copied).unwrap(), real);
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/rename",
+            serde_json::json!({ "from": copied, "to": dir.path().join("copy/renamed") }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(real.join("a.txt").exists());
+    assert!(
+        std::fs::symlink_metadata(dir.path().join("copy/renamed"))
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn create_symlink() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("target.txt"), "hello").unwrap();
+    let link = dir.path().join("link.txt");
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/symlink",
+            serde_json::json!({ "path": link, "target": "target.txt" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(
+        std::fs::read_link(&link).unwrap(),
+        std::path::Path::new("target.txt")
+    );
+    assert_eq!(std::fs::read_to_string(&link).unwrap(), "hello");
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/symlink",
+            serde_json::json!({ "path": link, "target": "target.txt" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+// ============================================================
+// POST /api/filer/rename
+// ============================================================
+
+#[tokio::test]
+async fn rename_file() {
+    let (app, dir) = test_app_with_dir();
+    let from = dir.path().join("old.txt");
+    let to = dir.path().join("new.txt");
+    std::fs::write(&from, "content").unwrap();
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/rename")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(
+            serde_json::json!({
+                "from": from.to_string_lossy(),
+                "to": to.to_string_lossy()
+            })
+            .to_string(),
+        ))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!from.exists());
+    assert!(to.exists());
+    assert_eq!(std::fs::read_to_string(&to).unwrap(), "content");
+}
+
+#[tokio::test]
+async fn rename_nonexistent() {
+    let (app, dir) = test_app_with_dir();
+    let from = dir.path().join("missing.txt");
+    let to = dir.path().join("new.txt");
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/rename")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(
+            serde_json::json!({
+                "from": from.to_string_lossy(),
+                "to": to.to_string_lossy()
+            })
+            .to_string(),
+        ))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn rename_requires_auth() {
+    let app = test_app();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/rename")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"from":"~/a","to":"~/b"}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ============================================================
+// POST /api/filer/copy, /api/filer/move
+// ============================================================
+
+fn copy_move_request(uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn copy_directory_recursive() {
+    let (app, dir) = test_app_with_dir();
+    let from = dir.path().join("src");
+    std::fs::create_dir_all(from.join("nested")).unwrap();
+    std::fs::write(from.join("nested/a.txt"), "a").unwrap();
+    let to = dir.path().join("dst");
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({ "from": from, "to": to }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        std::fs::read_to_string(to.join("nested/a.txt")).unwrap(),
+        "a"
+    );
+    assert!(from.join("nested/a.txt").exists());
+}
+
+#[tokio::test]
+async fn copy_conflict_policies() {
+    let (app, dir) = test_app_with_dir();
+    let from = dir.path().join("a.txt");
+    let to = dir.path().join("b.txt");
+    std::fs::write(&from, "new").unwrap();
+    std::fs::write(&to, "old").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({ "from": from, "to": to }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({ "from": from, "to": to, "on_conflict": "rename" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["path"].as_str().unwrap().ends_with("b (1).txt"));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("b (1).txt")).unwrap(),
+        "new"
+    );
+    assert_eq!(std::fs::read_to_string(&to).unwrap(), "old");
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({ "from": from, "to": to, "on_conflict": "overwrite" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(std::fs::read_to_string(&to).unwrap(), "new");
+}
+
+#[tokio::test]
+async fn copy_into_itself_rejected() {
+    let (app, dir) = test_app_with_dir();
+    let from = dir.path().join("src");
+    std::fs::create_dir(&from).unwrap();
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({ "from": from, "to": from.join("inner") }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(!from.join("inner").exists());
+}
+
+#[tokio::test]
+async fn move_file() {
+    let (app, dir) = test_app_with_dir();
+    let from = dir.path().join("a.txt");
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    let to = dir.path().join("sub/a.txt");
+    std::fs::write(&from, "content").unwrap();
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/move",
+            serde_json::json!({ "from": from, "to": to }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!from.exists());
+    assert_eq!(std::fs::read_to_string(&to).unwrap(), "content");
+}
+
+#[tokio::test]
+async fn move_overwrite_parent_rejected() {
+    let (app, dir) = test_app_with_dir();
+    let parent = dir.path().join("p");
+    let from = parent.join("child");
+    std::fs::create_dir_all(&from).unwrap();
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/move",
+            serde_json::json!({ "from": from, "to": parent, "on_conflict": "overwrite" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(from.exists());
+}
+
+#[tokio::test]
+async fn bulk_reports_per_item_results() {
+    let (app, dir) = test_app_with_dir();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    std::fs::write(&a, "a").unwrap();
+    std::fs::write(&b, "b").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/bulk",
+            serde_json::json!({ "ops": [
+                { "op": "copy", "from": a, "to": dir.path().join("sub/a.txt") },
+                { "op": "move", "from": b, "to": dir.path().join("sub/b.txt") },
+                { "op": "delete", "path": dir.path().join("missing.txt") },
+                { "op": "delete", "path": a, "trash": true },
+            ]}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0]["ok"], true);
+    assert!(results[0]["path"].as_str().unwrap().ends_with("a.txt"));
+    assert_eq!(results[1]["ok"], true);
+    assert_eq!(results[2]["ok"], false);
+    assert_eq!(results[2]["error"], "Not found");
+    assert_eq!(results[3]["ok"], true);
+
+    assert!(!a.exists());
+    assert!(!b.exists());
+    assert!(dir.path().join("sub/a.txt").exists());
+    assert!(dir.path().join("sub/b.txt").exists());
+}
+
+#[tokio::test]
+async fn bulk_stop_on_error() {
+    let (app, dir) = test_app_with_dir();
+    let a = dir.path().join("a.txt");
+    std::fs::write(&a, "a").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/bulk",
+            serde_json::json!({ "stop_on_error": true, "ops": [
+                { "op": "delete", "path": dir.path().join("missing.txt") },
+                { "op": "delete", "path": a },
+            ]}),
+        ))
+        .await
+        .unwrap();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["results"][1]["error"], "Skipped");
+    assert!(a.exists());
+
+    let resp = app
+        .oneshot(copy_move_request(
+            "/api/filer/bulk",
+            serde_json::json!({ "ops": [] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+// ============================================================
+// DELETE /api/filer/delete
+// ============================================================
+
+#[tokio::test]
+async fn delete_file() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("to-delete.txt");
+    std::fs::write(&file, "bye").unwrap();
+
+    let path = encode_path(&file);
+    let req = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/filer/delete?path={}", path))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!file.exists());
+}
+
+#[tokio::test]
+async fn delete_directory() {
+    let (app, dir) = test_app_with_dir();
+    let sub = dir.path().join("subdir");
+    std::fs::create_dir(&sub).unwrap();
+    std::fs::write(sub.join("file.txt"), "content").unwrap();
+
+    let path = encode_path(&sub);
+    let req = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/filer/delete?path={}", path))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!sub.exists());
+}
+
+#[tokio::test]
+async fn delete_nonexistent() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("nonexistent.txt");
+    let path = encode_path(&file);
+    let req = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/filer/delete?path={}", path))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+fn authed(method: &str, uri: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap()
+}
+
+async fn trash_list(app: &axum::Router) -> Vec<serde_json::Value> {
+    let resp = app
+        .clone()
+        .oneshot(authed("GET", "/api/filer/trash"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn trash_and_restore() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("keep.txt");
+    std::fs::write(&file, "precious").unwrap();
+
+    let uri = format!("/api/filer/delete?path={}&trash=true", encode_path(&file));
+    let resp = app.clone().oneshot(authed("DELETE", &uri)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!file.exists());
+
+    let items = trash_list(&app).await;
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["name"], "keep.txt");
+    assert_eq!(items[0]["original_path"], file.to_string_lossy().as_ref());
+    let id = items[0]["id"].as_str().unwrap().to_string();
+
+    // A new file took the old name: fail by default, rename on request
+    std::fs::write(&file, "newer").unwrap();
+    let resp = app
+        .clone()
+        .oneshot(authed("POST", &format!("/api/filer/trash/{id}/restore")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "POST",
+            &format!("/api/filer/trash/{id}/restore?on_conflict=rename"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("keep (1).txt")).unwrap(),
+        "precious"
+    );
+    assert!(trash_list(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn trash_restore_stays_inside_filer_roots() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let allowed = dir.path().join("allowed");
+    std::fs::create_dir(&allowed).unwrap();
+    let file = dir.path().join("outside.txt");
+    std::fs::write(&file, "x").unwrap();
+
+    // Trashed while unrestricted, then DEN_FILER_ROOTS is narrowed (same data dir)
+    let config = test_config();
+    let app_for = |config: Config| {
+        let store = den::store::Store::from_data_dir(&config.data_dir).unwrap();
+        let registry = SessionRegistry::new(
+            "powershell.exe".to_string(),
+            SleepPreventionMode::Off,
+            30,
+            None,
+            den::pty::backend::MuxConfig::default(),
+        );
+        den::create_app_with_secret(config, registry, TEST_HMAC_SECRET.to_vec(), store, None).0
+    };
+    let mut narrowed = config.clone();
+    narrowed.filer_roots = vec![allowed.to_string_lossy().into_owned()];
+    let app = app_for(config);
+    let uri = format!("/api/filer/delete?path={}&trash=true", encode_path(&file));
+    let resp = app.clone().oneshot(authed("DELETE", &uri)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let app = app_for(narrowed);
+    let items = trash_list(&app).await;
+    let id = items[0]["id"].as_str().unwrap().to_string();
+    let resp = app
+        .clone()
+        .oneshot(authed("POST", &format!("/api/filer/trash/{id}/restore")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(!file.exists());
+    assert_eq!(trash_list(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn undo_reverts_rename_and_trash() {
+    let (app, dir) = test_app_with_dir();
+    let original = dir.path().join("notes.txt");
+    let renamed = dir.path().join("notes-old.txt");
+    std::fs::write(&original, "data").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/rename",
+            serde_json::json!({"from": original.to_string_lossy(), "to": renamed.to_string_lossy()}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let uri = format!(
+        "/api/filer/delete?path={}&trash=true",
+        encode_path(&renamed)
+    );
+    let resp = app.clone().oneshot(authed("DELETE", &uri)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!renamed.exists());
+
+    let resp = app
+        .clone()
+        .oneshot(authed("GET", "/api/filer/journal"))
+        .await
+        .unwrap();
+    let journal = json_body(resp).await;
+    assert_eq!(journal[0]["op"], "trash");
+    assert_eq!(journal[1]["op"], "rename");
+
+    // Undo the trash, then the rename
+    let resp = app
+        .clone()
+        .oneshot(authed("POST", "/api/filer/undo"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(std::fs::read_to_string(&renamed).unwrap(), "data");
+    let resp = app
+        .clone()
+        .oneshot(authed("POST", "/api/filer/undo"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!renamed.exists());
+    assert_eq!(std::fs::read_to_string(&original).unwrap(), "data");
+    assert!(trash_list(&app).await.is_empty());
+
+    let resp = app
+        .oneshot(authed("POST", "/api/filer/undo"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn trash_purge_and_empty() {
+    let (app, dir) = test_app_with_dir();
+    for name in ["a", "b"] {
+        let sub = dir.path().join(name);
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(sub.join("f.txt"), "x").unwrap();
+        let uri = format!("/api/filer/delete?path={}&trash=true", encode_path(&sub));
+        let resp = app.clone().oneshot(authed("DELETE", &uri)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let items = trash_list(&app).await;
+    assert_eq!(items.len(), 2);
+    assert!(items.iter().all(|i| i["is_dir"] == true));
+
+    let id = items[0]["id"].as_str().unwrap();
+    let resp = app
+        .clone()
+        .oneshot(authed("DELETE", &format!("/api/filer/trash/{id}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(trash_list(&app).await.len(), 1);
+
+    let resp = app
+        .clone()
+        .oneshot(authed("DELETE", "/api/filer/trash"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(trash_list(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn trash_rejects_bad_id() {
+    let app = test_app();
+    let resp = app
+        .oneshot(authed("DELETE", "/api/filer/trash/..%2F..%2Fetc"))
+        .await
+        .unwrap();
     assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
 
@@ -642,6 +1971,100 @@ async fn delete_requires_auth() {
     assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
 }
 
+// ============================================================
+// Filer roots (DEN_FILER_ROOTS)
+// ============================================================
+
+#[tokio::test]
+async fn filer_roots_deny_outside_paths() {
+    let (app, dir) = test_app_with_roots();
+    let outside = tempfile::TempDir::new().unwrap();
+    std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+    std::fs::write(dir.path().join("ok.txt"), "ok").unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!(
+                "/api/filer/read?path={}",
+                encode_path(&outside.path().join("secret.txt"))
+            ),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Writes may not escape either (target does not exist yet)
+    let resp = app
+        .clone()
+        .oneshot(copy_move_request(
+            "/api/filer/copy",
+            serde_json::json!({
+                "from": dir.path().join("ok.txt"),
+                "to": outside.path().join("ok.txt"),
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(!outside.path().join("ok.txt").exists());
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!(
+                "/api/filer/read?path={}",
+                encode_path(&dir.path().join("ok.txt"))
+            ),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn filer_roots_list_stops_at_root() {
+    let (app, dir) = test_app_with_roots();
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(root.join("sub")).unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/list?path={}", encode_path(&root.join("sub"))),
+        ))
+        .await
+        .unwrap();
+    let json = json_body(resp).await;
+    assert_eq!(json["parent"], root.to_string_lossy().as_ref());
+
+    // At the root: no parent, allowed roots offered instead of drives
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/list?path={}", encode_path(&root)),
+        ))
+        .await
+        .unwrap();
+    let json = json_body(resp).await;
+    assert!(json["parent"].is_null());
+    assert_eq!(json["drives"][0], root.to_string_lossy().as_ref());
+
+    // "~" outside the jail lands on the first root
+    let resp = app
+        .oneshot(authed("GET", "/api/filer/list?path=~"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        json_body(resp).await["path"],
+        root.to_string_lossy().as_ref()
+    );
+}
+
 // ============================================================
 // GET /api/filer/search
 // ============================================================
@@ -833,115 +2256,693 @@ async fn search_hidden_attribute_files_respect_show_hidden() {
         .header(header::AUTHORIZATION, auth_header())
         .body(Body::empty())
         .unwrap();
-    let hidden_on_resp = app.oneshot(hidden_on_req).await.unwrap();
-    assert_eq!(hidden_on_resp.status(), StatusCode::OK);
-    let hidden_on_body = hidden_on_resp
-        .into_body()
-        .collect()
-        .await
-        .unwrap()
-        .to_bytes();
-    let hidden_on_json: serde_json::Value = serde_json::from_slice(&hidden_on_body).unwrap();
-    let hidden_on_results = hidden_on_json.as_array().unwrap();
-    assert_eq!(hidden_on_results.len(), 1);
-    assert!(
-        hidden_on_results[0]["path"]
-            .as_str()
-            .unwrap()
-            .contains("hidden-attr-target.txt")
-    );
+    let hidden_on_resp = app.oneshot(hidden_on_req).await.unwrap();
+    assert_eq!(hidden_on_resp.status(), StatusCode::OK);
+    let hidden_on_body = hidden_on_resp
+        .into_body()
+        .collect()
+        .await
+        .unwrap()
+        .to_bytes();
+    let hidden_on_json: serde_json::Value = serde_json::from_slice(&hidden_on_body).unwrap();
+    let hidden_on_results = hidden_on_json.as_array().unwrap();
+    assert_eq!(hidden_on_results.len(), 1);
+    assert!(
+        hidden_on_results[0]["path"]
+            .as_str()
+            .unwrap()
+            .contains("hidden-attr-target.txt")
+    );
+}
+
+#[tokio::test]
+async fn search_no_results() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("file.txt"), "hello").unwrap();
+
+    let path = encode_path(dir.path());
+    let req = Request::builder()
+        .uri(format!(
+            "/api/filer/search?path={}&query=zzzznotfound",
+            path
+        ))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.as_array().unwrap().is_empty());
+}
+
+fn search_paths(json: &serde_json::Value) -> Vec<String> {
+    let mut names: Vec<String> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            std::path::Path::new(r["path"].as_str().unwrap())
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn search_glob_mode() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src/main.rs"), "").unwrap();
+    std::fs::write(dir.path().join("lib.RS"), "").unwrap();
+    std::fs::write(dir.path().join("notes.rs.txt"), "").unwrap();
+
+    let path = encode_path(dir.path());
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=*.rs&mode=glob"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(search_paths(&json_body(resp).await), ["lib.RS", "main.rs"]);
+
+    // Glob only applies to names
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=*.rs&mode=glob&content=true"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_regex_mode() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("a.txt"), "fn main() {}\nlet x = 1;").unwrap();
+    std::fs::write(dir.path().join("b.txt"), "function foo").unwrap();
+
+    let path = encode_path(dir.path());
+    let query = urlencoding::encode(r"^fn \w+\(");
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query={query}&mode=regex&content=true"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = json_body(resp).await;
+    let results = json.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["line"], 1);
+    assert!(results[0]["path"].as_str().unwrap().ends_with("a.txt"));
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=%28unclosed&mode=regex"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_respects_gitignore() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join(".gitignore"), "node_modules/\n*.log\n").unwrap();
+    std::fs::create_dir(dir.path().join("node_modules")).unwrap();
+    std::fs::write(dir.path().join("node_modules/target.js"), "").unwrap();
+    std::fs::write(dir.path().join("target.log"), "").unwrap();
+    std::fs::write(dir.path().join("target.rs"), "").unwrap();
+
+    let path = encode_path(dir.path());
+    let resp = app
+        .clone()
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=target"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(search_paths(&json_body(resp).await), ["target.rs"]);
+
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=target&gitignore=false"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        search_paths(&json_body(resp).await),
+        ["target.js", "target.log", "target.rs"]
+    );
+}
+
+#[tokio::test]
+async fn search_exclude_patterns() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::create_dir(dir.path().join("build")).unwrap();
+    std::fs::write(dir.path().join("build/target.o"), "").unwrap();
+    std::fs::write(dir.path().join("target.min.js"), "").unwrap();
+    std::fs::write(dir.path().join("target.js"), "").unwrap();
+
+    let path = encode_path(dir.path());
+    let exclude = urlencoding::encode("build, *.min.js");
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=target&exclude={exclude}"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(search_paths(&json_body(resp).await), ["target.js"]);
+}
+
+#[tokio::test]
+async fn search_stops_at_result_limit_in_sorted_order() {
+    let (app, dir) = test_app_with_dir();
+    for d in 0..5 {
+        let sub = dir.path().join(format!("dir{d}"));
+        std::fs::create_dir(&sub).unwrap();
+        for f in 0..40 {
+            std::fs::write(sub.join(format!("f{f}.txt")), "needle\nneedle\n").unwrap();
+        }
+    }
+
+    let path = encode_path(dir.path());
+    let resp = app
+        .oneshot(authed(
+            "GET",
+            &format!("/api/filer/search?path={path}&query=needle&content=true"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = json_body(resp).await;
+    let results = json.as_array().unwrap();
+    assert_eq!(results.len(), 100);
+    let keys: Vec<(String, u64)> = results
+        .iter()
+        .map(|r| {
+            (
+                r["path"].as_str().unwrap().to_string(),
+                r["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    let mut sorted = keys.clone();
+    sorted.sort();
+    assert_eq!(keys, sorted);
+}
+
+#[tokio::test]
+async fn search_requires_auth() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/filer/search?path=~&query=test")
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ============================================================
+// GET /api/filer/download
+// ============================================================
+
+#[tokio::test]
+async fn download_file() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("download.txt"), "file content here").unwrap();
+
+    let file_path = encode_path(&dir.path().join("download.txt"));
+    let req = Request::builder()
+        .uri(format!("/api/filer/download?path={}", file_path))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let disposition = resp
+        .headers()
+        .get(header::CONTENT_DISPOSITION)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(disposition.contains("download.txt"));
+
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&body[..], b"file content here");
+}
+
+#[tokio::test]
+async fn download_streams_large_file() {
+    let (app, dir) = test_app_with_dir();
+    // Sparse file above the old 100MB in-memory cap
+    let path = dir.path().join("big.bin");
+    let len = 101 * 1024 * 1024;
+    std::fs::File::create(&path).unwrap().set_len(len).unwrap();
+
+    let req = Request::builder()
+        .uri(format!("/api/filer/download?path={}", encode_path(&path)))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_LENGTH).unwrap(),
+        &len.to_string()
+    );
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body.len() as u64, len);
+}
+
+#[tokio::test]
+async fn download_nonexistent() {
+    let (app, dir) = test_app_with_dir();
+    let file_path = encode_path(&dir.path().join("missing.txt"));
+    let req = Request::builder()
+        .uri(format!("/api/filer/download?path={}", file_path))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn download_requires_auth() {
+    let app = test_app();
+    let req = Request::builder()
+        .uri("/api/filer/download?path=~")
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ============================================================
+// POST /api/filer/share + GET /api/filer/shared/{token}
+// ============================================================
+
+#[tokio::test]
+async fn share_link_is_single_use_and_public() {
+    let (app, dir) = test_app_with_dir();
+    let path = dir.path().join("app.log");
+    std::fs::write(&path, "log line").unwrap();
+
+    let body = serde_json::json!({ "path": path.to_string_lossy(), "max_downloads": 1 });
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/share")
+        .header(header::AUTHORIZATION, auth_header())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    let share: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let url = share["url"].as_str().unwrap().to_string();
+    assert_eq!(share["downloads_left"], 1);
+
+    // HEAD (link previews) doesn't use up the download; no auth needed
+    let head = Request::builder()
+        .method("HEAD")
+        .uri(&url)
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(head).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let get = || Request::builder().uri(&url).body(Body::empty()).unwrap();
+    let resp = app.clone().oneshot(get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        resp.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .contains("app.log")
+    );
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&bytes[..], b"log line");
+
+    let resp = app.clone().oneshot(get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn share_link_survives_a_missing_file() {
+    let (app, dir) = test_app_with_dir();
+    let path = dir.path().join("report.csv");
+    std::fs::write(&path, "a,b").unwrap();
+
+    let body = serde_json::json!({ "path": path.to_string_lossy() });
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/share")
+        .header(header::AUTHORIZATION, auth_header())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    let share: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let url = share["url"].as_str().unwrap().to_string();
+    let get = || Request::builder().uri(&url).body(Body::empty()).unwrap();
+
+    // Moved away: the failed download doesn't use up the link
+    std::fs::rename(&path, dir.path().join("moved.csv")).unwrap();
+    let resp = app.clone().oneshot(get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    std::fs::rename(dir.path().join("moved.csv"), &path).unwrap();
+    let resp = app.clone().oneshot(get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = app.oneshot(get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn share_rejects_directories_and_bad_limits() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+    let post = |body: serde_json::Value| {
+        Request::builder()
+            .method("POST")
+            .uri("/api/filer/share")
+            .header(header::AUTHORIZATION, auth_header())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+    let dir_path = dir.path().to_string_lossy().into_owned();
+    let file_path = dir.path().join("a.txt").to_string_lossy().into_owned();
+
+    let resp = app
+        .clone()
+        .oneshot(post(serde_json::json!({ "path": dir_path })))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = app
+        .clone()
+        .oneshot(post(
+            serde_json::json!({ "path": file_path, "expires_in_mins": 0 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Listed until revoked
+    let resp = app
+        .clone()
+        .oneshot(post(
+            serde_json::json!({ "path": file_path, "max_downloads": 3 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let list = || {
+        Request::builder()
+            .uri("/api/filer/share")
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let bytes = app.clone().oneshot(list()).await.unwrap();
+    let bytes = bytes.into_body().collect().await.unwrap().to_bytes();
+    let shares: Vec<serde_json::Value> = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(shares.len(), 1);
+    let token = shares[0]["token"].as_str().unwrap();
+    let req = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/filer/share/{token}"))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let req = Request::builder()
+        .uri(format!("/api/filer/shared/{token}"))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
 
 #[tokio::test]
-async fn search_no_results() {
+async fn signed_url_downloads_without_a_cookie() {
     let (app, dir) = test_app_with_dir();
-    std::fs::write(dir.path().join("file.txt"), "hello").unwrap();
+    let path = dir.path().join("build.tar");
+    std::fs::write(&path, "artifact").unwrap();
 
-    let path = encode_path(dir.path());
+    let body = serde_json::json!({ "path": path.to_string_lossy(), "expires_in_mins": 5 });
     let req = Request::builder()
-        .uri(format!(
-            "/api/filer/search?path={}&query=zzzznotfound",
-            path
-        ))
+        .method("POST")
+        .uri("/api/filer/sign-url")
         .header(header::AUTHORIZATION, auth_header())
-        .body(Body::empty())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
         .unwrap();
-
-    let resp = app.oneshot(req).await.unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    let signed: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let url = signed["url"].as_str().unwrap().to_string();
+    assert!(url.starts_with("/api/filer/download?path="));
+    assert!(url.contains("&sig="));
+
+    let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+    // Reusable until it expires
+    for _ in 0..2 {
+        let resp = app.clone().oneshot(get(url.clone())).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&bytes[..], b"artifact");
+    }
 
-    let body = resp.into_body().collect().await.unwrap().to_bytes();
-    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
-    assert!(json.as_array().unwrap().is_empty());
+    // The signature covers the path, the expiry and the route
+    let other = dir.path().join("other.txt");
+    std::fs::write(&other, "secret").unwrap();
+    let (_, query) = url.split_once('?').unwrap();
+    let tampered = query.replace("build.tar", "other.txt");
+    let resp = app
+        .clone()
+        .oneshot(get(format!("/api/filer/download?{tampered}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let extended = query.replace("expires=", "expires=9");
+    let resp = app
+        .clone()
+        .oneshot(get(format!("/api/filer/download?{extended}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = app
+        .clone()
+        .oneshot(get(format!("/api/filer/read?{query}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let delete = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/filer/delete?{query}"))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(delete).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
 }
 
 #[tokio::test]
-async fn search_requires_auth() {
-    let app = test_app();
-    let req = Request::builder()
-        .uri("/api/filer/search?path=~&query=test")
-        .body(Body::empty())
-        .unwrap();
+async fn signed_url_rejects_directories_and_bad_expiry() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+    let post = |body: serde_json::Value| {
+        Request::builder()
+            .method("POST")
+            .uri("/api/filer/sign-url")
+            .header(header::AUTHORIZATION, auth_header())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+    let dir_path = dir.path().to_string_lossy().into_owned();
+    let file_path = dir.path().join("a.txt").to_string_lossy().into_owned();
 
-    let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = app
+        .clone()
+        .oneshot(post(serde_json::json!({ "path": dir_path })))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = app
+        .clone()
+        .oneshot(post(
+            serde_json::json!({ "path": file_path, "expires_in_mins": 10081 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
 // ============================================================
-// GET /api/filer/download
+// GET /api/filer/download-dir
 // ============================================================
 
 #[tokio::test]
-async fn download_file() {
+async fn download_dir_zip() {
     let (app, dir) = test_app_with_dir();
-    std::fs::write(dir.path().join("download.txt"), "file content here").unwrap();
+    let root = dir.path().join("project");
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    std::fs::write(root.join(".env"), "SECRET=1").unwrap();
 
-    let file_path = encode_path(&dir.path().join("download.txt"));
     let req = Request::builder()
-        .uri(format!("/api/filer/download?path={}", file_path))
+        .uri(format!(
+            "/api/filer/download-dir?path={}",
+            encode_path(&root)
+        ))
         .header(header::AUTHORIZATION, auth_header())
         .body(Body::empty())
         .unwrap();
-
     let resp = app.oneshot(req).await.unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
-
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/zip"
+    );
     let disposition = resp
         .headers()
         .get(header::CONTENT_DISPOSITION)
         .unwrap()
         .to_str()
         .unwrap();
-    assert!(disposition.contains("download.txt"));
+    assert!(disposition.contains("project.zip"));
 
     let body = resp.into_body().collect().await.unwrap().to_bytes();
-    assert_eq!(&body[..], b"file content here");
+    assert_eq!(&body[..4], b"PK\x03\x04");
+    // End of central directory record: 2 entries (src/, src/main.rs), .env hidden
+    let eocd = &body[body.len() - 22..];
+    assert_eq!(&eocd[..4], b"PK\x05\x06");
+    assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 2);
+    let names = String::from_utf8_lossy(&body);
+    assert!(names.contains("src/main.rs"));
+    assert!(!names.contains(".env"));
 }
 
 #[tokio::test]
-async fn download_nonexistent() {
+async fn download_dir_show_hidden() {
     let (app, dir) = test_app_with_dir();
-    let file_path = encode_path(&dir.path().join("missing.txt"));
+    std::fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+
     let req = Request::builder()
-        .uri(format!("/api/filer/download?path={}", file_path))
+        .uri(format!(
+            "/api/filer/download-dir?path={}&show_hidden=true",
+            encode_path(dir.path())
+        ))
         .header(header::AUTHORIZATION, auth_header())
         .body(Body::empty())
         .unwrap();
-
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let eocd = &body[body.len() - 22..];
+    assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 1);
 }
 
 #[tokio::test]
-async fn download_requires_auth() {
-    let app = test_app();
+async fn download_dir_rejects_file() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("a.txt");
+    std::fs::write(&file, "x").unwrap();
+
     let req = Request::builder()
-        .uri("/api/filer/download?path=~")
+        .uri(format!(
+            "/api/filer/download-dir?path={}",
+            encode_path(&file)
+        ))
+        .header(header::AUTHORIZATION, auth_header())
         .body(Body::empty())
         .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+// ============================================================
+// POST /api/filer/watch, DELETE /api/filer/watch/{id}
+// ============================================================
+
+#[tokio::test]
+async fn watch_and_unwatch() {
+    let (app, dir) = test_app_with_dir();
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/watch")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(
+            serde_json::json!({ "path": dir.path() }).to_string(),
+        ))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["id"].as_str().unwrap().to_string();
+
+    let unwatch = |id: &str| {
+        Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/filer/watch/{id}"))
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let resp = app.clone().oneshot(unwatch(&id)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = app.oneshot(unwatch(&id)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
 
+#[tokio::test]
+async fn watch_rejects_file() {
+    let (app, dir) = test_app_with_dir();
+    let file = dir.path().join("a.txt");
+    std::fs::write(&file, "x").unwrap();
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/watch")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(serde_json::json!({ "path": file }).to_string()))
+        .unwrap();
     let resp = app.oneshot(req).await.unwrap();
-    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
 // ============================================================
@@ -988,6 +2989,162 @@ async fn upload_file() {
     );
 }
 
+#[tokio::test]
+async fn upload_above_default_body_limit() {
+    let (app, dir) = test_app_with_dir();
+    // axum's default body limit is 2MB; the upload route raises it
+    let content = "x".repeat(3 * 1024 * 1024);
+    let boundary = "----TestBoundary";
+    let body = format!(
+        "--{boundary}\r\n\
+         Content-Disposition: form-data; name=\"path\"\r\n\r\n\
+         {}\r\n\
+         --{boundary}\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"big.txt\"\r\n\r\n\
+         {content}\r\n\
+         --{boundary}--\r\n",
+        dir.path().to_string_lossy(),
+    );
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/upload")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body))
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(
+        std::fs::metadata(dir.path().join("big.txt")).unwrap().len(),
+        content.len() as u64
+    );
+}
+
+async fn json_body(resp: axum::response::Response) -> serde_json::Value {
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn chunk_request(id: &str, offset: u64, data: &'static [u8]) -> Request<Body> {
+    Request::builder()
+        .method("PUT")
+        .uri(format!("/api/filer/upload/{id}?offset={offset}"))
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(data))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn chunked_upload_resume_and_complete() {
+    let (app, dir) = test_app_with_dir();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/filer/upload/init")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(
+            serde_json::json!({ "path": dir.path(), "name": "disk.iso", "size": 10 }).to_string(),
+        ))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let id = json_body(resp).await["id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(chunk_request(&id, 0, b"hello"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(json_body(resp).await["received"], 5);
+
+    // A gap is rejected; the client resumes from the reported offset
+    let resp = app
+        .clone()
+        .oneshot(chunk_request(&id, 7, b"xyz"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = app
+        .clone()
+        .oneshot(authed("GET", &format!("/api/filer/upload/{id}")))
+        .await
+        .unwrap();
+    assert_eq!(json_body(resp).await["received"], 5);
+
+    // Completing early fails
+    let complete = format!("/api/filer/upload/{id}/complete");
+    let resp = app
+        .clone()
+        .oneshot(authed("POST", &complete))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = app
+        .clone()
+        .oneshot(chunk_request(&id, 5, b"world"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = app
+        .clone()
+        .oneshot(authed("POST", &complete))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("disk.iso")).unwrap(),
+        "helloworld"
+    );
+    // No leftover part files
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    let resp = app
+        .oneshot(authed("GET", &format!("/api/filer/upload/{id}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn chunked_upload_abort_and_conflict() {
+    let (app, dir) = test_app_with_dir();
+    std::fs::write(dir.path().join("exists.bin"), "x").unwrap();
+    let init = |name: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/api/filer/upload/init")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, auth_header())
+            .body(Body::from(
+                serde_json::json!({ "path": dir.path(), "name": name, "size": 4 }).to_string(),
+            ))
+            .unwrap()
+    };
+    let resp = app.clone().oneshot(init("exists.bin")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = app.clone().oneshot(init("new.bin")).await.unwrap();
+    let id = json_body(resp).await["id"].as_str().unwrap().to_string();
+    let resp = app
+        .clone()
+        .oneshot(chunk_request(&id, 0, b"toolong"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .oneshot(authed("DELETE", &format!("/api/filer/upload/{id}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
 #[tokio::test]
 async fn upload_requires_auth() {
     let app = test_app();
@@ -1099,3 +3256,63 @@ async fn list_sorts_dirs_first() {
     assert!(!entries[3]["is_dir"].as_bool().unwrap());
     assert_eq!(entries[3]["name"], "bbb-file.txt");
 }
+
+// ============================================================
+// POST /api/diff
+// ============================================================
+
+#[tokio::test]
+async fn diff_two_local_files() {
+    let (app, dir) = test_app_with_dir();
+    let old = dir.path().join("old.conf");
+    let new = dir.path().join("new.conf");
+    std::fs::write(&old, "a\nb\nc\n").unwrap();
+    std::fs::write(&new, "a\nB\nc\n").unwrap();
+
+    let body = serde_json::json!({
+        "left": {"source": "local", "path": old.to_string_lossy()},
+        "right": {"source": "local", "path": new.to_string_lossy()},
+    });
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/diff")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["identical"], false);
+    let hunks = json["hunks"].as_array().unwrap();
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0]["old_start"], 1);
+    assert_eq!(hunks[0]["lines"][1]["kind"], "remove");
+    assert_eq!(hunks[0]["lines"][2]["kind"], "add");
+    assert_eq!(hunks[0]["lines"][2]["text"], "B");
+}
+
+#[tokio::test]
+async fn diff_sftp_side_not_connected() {
+    let (app, dir) = test_app_with_dir();
+    let local = dir.path().join("a.txt");
+    std::fs::write(&local, "x\n").unwrap();
+
+    let body = serde_json::json!({
+        "left": {"source": "local", "path": local.to_string_lossy()},
+        "right": {"source": "sftp", "path": "/etc/hosts"},
+    });
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/diff")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, auth_header())
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
@@ -318,6 +318,7 @@ fn pty_non_interactive() {
 
 #[test]
 #[serial]
+#[allow(clippy::while_let_loop)]
 fn pty_interactive() {
     let rt = build_test_runtime();
     rt.block_on(async {
@@ -46,6 +46,8 @@ class DenSession:
 
     def __init__(self):
         self.session = requests.Session()
+        # Cookie auth requires the CSRF header on state-changing requests
+        self.session.headers["X-Den-Request"] = "1"
         self._login()
 
     def _login(self):